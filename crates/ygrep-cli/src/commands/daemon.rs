@@ -0,0 +1,43 @@
+use anyhow::Result;
+use ygrep_core::Config;
+
+use crate::daemon;
+
+/// Run the daemon in the foreground
+pub fn start() -> Result<()> {
+    daemon::serve(&Config::load())
+}
+
+/// Stop the running daemon
+pub fn stop() -> Result<()> {
+    if daemon::stop(&Config::load()) {
+        println!("ygrep daemon stopped.");
+    } else {
+        println!("No ygrep daemon running.");
+    }
+    Ok(())
+}
+
+/// Show whether a daemon is running
+pub fn status() -> Result<()> {
+    let config = Config::load();
+    let Some(status) = daemon::status(&config) else {
+        println!("No ygrep daemon running.");
+        return Ok(());
+    };
+
+    println!(
+        "ygrep daemon running (pid {}, up {}s)",
+        status.pid, status.uptime_secs
+    );
+    println!("  Socket: {}", daemon::socket_path(&config).display());
+    if status.workspaces.is_empty() {
+        println!("  No workspaces open");
+    } else {
+        println!("  Open workspaces:");
+        for root in &status.workspaces {
+            println!("    {}", root.display());
+        }
+    }
+    Ok(())
+}
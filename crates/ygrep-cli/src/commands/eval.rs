@@ -0,0 +1,350 @@
+//! Search-quality evaluation (`ygrep eval <cases.yaml>`): queries with known relevant
+//! files are run in each search mode, and precision, recall and MRR are reported so
+//! weights, models and chunking can be tuned by measurement
+//!
+//! ```yaml
+//! cases:
+//!   - query: connection pool
+//!     expected: [src/db/pool.rs]
+//!   - query: where are auth tokens refreshed
+//!     expected: [src/auth/refresh.rs, src/auth/session.rs]
+//! ```
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use ygrep_core::search::SearchHit;
+use ygrep_core::{Config, Workspace};
+
+use crate::OutputFormat;
+
+/// Search modes `ygrep eval` can compare
+pub const EVAL_MODES: [&str; 3] = ["text", "semantic", "hybrid"];
+
+/// Contents of a cases file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EvalCases {
+    cases: Vec<EvalCase>,
+}
+
+/// A query and the files it should find
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct EvalCase {
+    pub query: String,
+    /// Relevant files, relative to the workspace root
+    pub expected: Vec<String>,
+}
+
+/// How one mode did on one case
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct CaseScore {
+    query: String,
+    /// Share of the returned files that are expected
+    precision: f64,
+    /// Share of the expected files that were returned
+    recall: f64,
+    /// 1 / rank of the first expected file (0 if none was returned)
+    reciprocal_rank: f64,
+    /// Expected files that weren't returned
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    missed: Vec<String>,
+}
+
+/// Averages over all cases for one mode
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ModeReport {
+    pub mode: &'static str,
+    /// Why the mode wasn't evaluated (e.g. no semantic index)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<String>,
+    pub precision: f64,
+    pub recall: f64,
+    pub mrr: f64,
+    cases: Vec<CaseScore>,
+}
+
+impl ModeReport {
+    fn skipped(mode: &'static str, reason: &str) -> Self {
+        Self {
+            mode,
+            skipped: Some(reason.to_string()),
+            precision: 0.0,
+            recall: 0.0,
+            mrr: 0.0,
+            cases: Vec::new(),
+        }
+    }
+
+    fn from_cases(mode: &'static str, cases: Vec<CaseScore>) -> Self {
+        let mean = |value: fn(&CaseScore) -> f64| {
+            if cases.is_empty() {
+                0.0
+            } else {
+                cases.iter().map(value).sum::<f64>() / cases.len() as f64
+            }
+        };
+        Self {
+            mode,
+            skipped: None,
+            precision: mean(|case| case.precision),
+            recall: mean(|case| case.recall),
+            mrr: mean(|case| case.reciprocal_rank),
+            cases,
+        }
+    }
+}
+
+pub fn run(
+    workspace_path: &Path,
+    cases_path: &Path,
+    limit: usize,
+    modes: &[String],
+    format: OutputFormat,
+) -> Result<()> {
+    let cases = load_cases(cases_path)?;
+    let modes: Vec<&'static str> = if modes.is_empty() {
+        EVAL_MODES.to_vec()
+    } else {
+        EVAL_MODES
+            .into_iter()
+            .filter(|mode| modes.iter().any(|m| m == mode))
+            .collect()
+    };
+    let reports = evaluate(workspace_path, Config::load(), &cases, limit, &modes)?;
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        OutputFormat::Ai | OutputFormat::Pretty => {
+            print!("{}", format_reports(&reports, cases.len(), limit))
+        }
+    }
+    Ok(())
+}
+
+/// Read a cases file
+pub(crate) fn load_cases(cases_path: &Path) -> Result<Vec<EvalCase>> {
+    let content = std::fs::read_to_string(cases_path)
+        .with_context(|| format!("Failed to read {}", cases_path.display()))?;
+    let cases: EvalCases = serde_yaml::from_str(&content)
+        .with_context(|| format!("Invalid cases file {}", cases_path.display()))?;
+    if cases.cases.is_empty() {
+        anyhow::bail!("{} has no cases", cases_path.display());
+    }
+    Ok(cases.cases)
+}
+
+/// Run every case in each mode, scoring the top `limit` files
+pub(crate) fn evaluate(
+    workspace_path: &Path,
+    config: Config,
+    cases: &[EvalCase],
+    limit: usize,
+    modes: &[&'static str],
+) -> Result<Vec<ModeReport>> {
+    // Errors already say whether to index or rebuild
+    let workspace = Workspace::open_with_config(workspace_path, config.clone())?;
+
+    let mut reports = Vec::with_capacity(modes.len());
+    for &mode in modes {
+        let report = match mode {
+            "text" => {
+                let scores = cases
+                    .iter()
+                    .map(|case| {
+                        let hits = workspace.search(&case.query, Some(limit))?.hits;
+                        Ok(score_case(case, &ranked_paths(&hits, limit)))
+                    })
+                    .collect::<Result<_>>()?;
+                ModeReport::from_cases("text", scores)
+            }
+            "semantic" | "hybrid" if !workspace.has_semantic_index() => {
+                ModeReport::skipped(mode, "no semantic index (run `ygrep index --semantic`)")
+            }
+            #[cfg(feature = "embeddings")]
+            "semantic" => {
+                // Without BM25's share of the fused score, hits rank by vector similarity
+                // alone; BM25-only hits are left out
+                let mut config = config.clone();
+                config.search.bm25_weight = 0.0;
+                let semantic = Workspace::open_with_config(workspace_path, config)?;
+                let scores = cases
+                    .iter()
+                    .map(|case| {
+                        let result =
+                            semantic.search_hybrid(&case.query, Some(limit), Default::default())?;
+                        let hits: Vec<SearchHit> = result
+                            .hits
+                            .into_iter()
+                            .filter(|hit| hit.match_type != ygrep_core::search::MatchType::Text)
+                            .collect();
+                        Ok(score_case(case, &ranked_paths(&hits, limit)))
+                    })
+                    .collect::<Result<_>>()?;
+                ModeReport::from_cases("semantic", scores)
+            }
+            #[cfg(feature = "embeddings")]
+            "hybrid" => {
+                let scores = cases
+                    .iter()
+                    .map(|case| {
+                        let result = workspace.search_hybrid(
+                            &case.query,
+                            Some(limit),
+                            Default::default(),
+                        )?;
+                        Ok(score_case(case, &ranked_paths(&result.hits, limit)))
+                    })
+                    .collect::<Result<_>>()?;
+                ModeReport::from_cases("hybrid", scores)
+            }
+            _ => continue,
+        };
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+/// Distinct files in hit order, at most `limit` (a file's chunks count once)
+fn ranked_paths(hits: &[SearchHit], limit: usize) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for hit in hits {
+        if paths.len() >= limit {
+            break;
+        }
+        if !paths.contains(&hit.path) {
+            paths.push(hit.path.clone());
+        }
+    }
+    paths
+}
+
+fn score_case(case: &EvalCase, ranked: &[String]) -> CaseScore {
+    let is_expected = |path: &String| case.expected.contains(path);
+    let found = ranked.iter().filter(|path| is_expected(path)).count();
+    let first = ranked.iter().position(is_expected);
+
+    CaseScore {
+        query: case.query.clone(),
+        precision: if ranked.is_empty() {
+            0.0
+        } else {
+            found as f64 / ranked.len() as f64
+        },
+        recall: if case.expected.is_empty() {
+            1.0
+        } else {
+            found as f64 / case.expected.len() as f64
+        },
+        reciprocal_rank: first.map_or(0.0, |rank| 1.0 / (rank + 1) as f64),
+        missed: case
+            .expected
+            .iter()
+            .filter(|path| !ranked.contains(path))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Table of the averages per mode, then the cases each mode missed files for
+fn format_reports(reports: &[ModeReport], case_count: usize, limit: usize) -> String {
+    let mut output = format!("# {} cases, top {} files\n\n", case_count, limit);
+    output.push_str(&format!(
+        "{:<10} {:>9} {:>7} {:>6}\n",
+        "mode", "precision", "recall", "mrr"
+    ));
+    for report in reports {
+        match report.skipped {
+            Some(ref reason) => {
+                output.push_str(&format!("{:<10} skipped: {}\n", report.mode, reason))
+            }
+            None => output.push_str(&format!(
+                "{:<10} {:>9.3} {:>7.3} {:>6.3}\n",
+                report.mode, report.precision, report.recall, report.mrr
+            )),
+        }
+    }
+
+    for report in reports {
+        let missed: Vec<&CaseScore> = report
+            .cases
+            .iter()
+            .filter(|case| !case.missed.is_empty())
+            .collect();
+        if missed.is_empty() {
+            continue;
+        }
+        output.push_str(&format!("\n# Missed ({})\n", report.mode));
+        for case in missed {
+            output.push_str(&format!(
+                "  \"{}\": {}\n",
+                case.query,
+                case.missed.join(", ")
+            ));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn case(query: &str, expected: &[&str]) -> EvalCase {
+        EvalCase {
+            query: query.to_string(),
+            expected: expected.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_score_case() {
+        let ranked: Vec<String> = ["a.rs", "b.rs", "c.rs", "d.rs"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        let score = score_case(&case("q", &["b.rs", "d.rs", "e.rs"]), &ranked);
+        assert_eq!(score.precision, 0.5);
+        assert!((score.recall - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(score.reciprocal_rank, 0.5);
+        assert_eq!(score.missed, vec!["e.rs".to_string()]);
+
+        let score = score_case(&case("q", &["e.rs"]), &[]);
+        assert_eq!(
+            (score.precision, score.recall, score.reciprocal_rank),
+            (0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_evaluate_text_mode() -> Result<()> {
+        let temp = tempdir()?;
+        let root = temp.path().join("workspace");
+        std::fs::create_dir_all(&root)?;
+        std::fs::write(root.join("pool.rs"), "fn connection_pool() {}")?;
+        std::fs::write(root.join("main.rs"), "fn main() { connection_pool() }")?;
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp.path().join("data");
+        Workspace::create_with_config(&root, config.clone())?.index_all()?;
+
+        let cases: EvalCases = serde_yaml::from_str(
+            "cases:\n  - query: connection_pool\n    expected: [pool.rs]\n  - query: missing_fn\n    expected: [pool.rs]\n",
+        )?;
+        let reports = evaluate(&root, config, &cases.cases, 10, &["text", "semantic"])?;
+
+        let text = &reports[0];
+        assert_eq!(text.cases[0].recall, 1.0);
+        assert_eq!(text.cases[0].precision, 0.5);
+        assert_eq!(text.cases[1].missed, vec!["pool.rs".to_string()]);
+        assert_eq!(text.recall, 0.5);
+        // A text-only index can't be evaluated semantically
+        assert!(reports[1].skipped.is_some());
+
+        let output = format_reports(&reports, 2, 10);
+        assert!(output.contains("# Missed (text)\n  \"missing_fn\": pool.rs\n"));
+        Ok(())
+    }
+}
@@ -0,0 +1,18 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use ygrep_core::Workspace;
+
+/// Mark a search hit as relevant (or not) for a query
+pub fn run(workspace_path: &Path, query: &str, doc_id: &str, relevant: bool) -> Result<()> {
+    let workspace = Workspace::open(workspace_path).with_context(|| {
+        format!(
+            "No index for {} (run `ygrep index` first)",
+            workspace_path.display()
+        )
+    })?;
+    let path = workspace.record_feedback(query, doc_id, relevant)?;
+
+    let verdict = if relevant { "relevant" } else { "not relevant" };
+    println!("Recorded {} as {} for \"{}\"", path, verdict, query);
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use ygrep_core::Workspace;
+
+use crate::OutputFormat;
+
+/// Find indexed files by fuzzy or glob pattern
+pub fn run(
+    workspace_path: &Path,
+    pattern: &str,
+    limit: usize,
+    show_scores: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let workspace = Workspace::open(workspace_path).with_context(|| {
+        format!(
+            "No index for {} (run `ygrep index` first)",
+            workspace_path.display()
+        )
+    })?;
+    let found = workspace.find_files(pattern, limit)?;
+
+    let output = match format {
+        OutputFormat::Ai => found.format_ai(),
+        OutputFormat::Json => found.format_json(),
+        OutputFormat::Pretty => found.format_pretty(show_scores),
+    };
+    print!("{}", output);
+    Ok(())
+}
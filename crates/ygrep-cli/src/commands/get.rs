@@ -0,0 +1,43 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use ygrep_core::Workspace;
+
+use crate::OutputFormat;
+
+/// Print a file as it is indexed, or only a hit's lines with `expand` more around them
+pub fn run(
+    workspace_path: &Path,
+    id: &str,
+    expand: Option<usize>,
+    format: OutputFormat,
+) -> Result<()> {
+    let workspace = Workspace::open(workspace_path).with_context(|| {
+        format!(
+            "No index for {} (run `ygrep index` first)",
+            workspace_path.display()
+        )
+    })?;
+    if let Some(lines) = expand {
+        let hit = workspace.expand_hit(id, lines, lines)?;
+        match format {
+            OutputFormat::Json => println!("{}", hit.format_json()),
+            OutputFormat::Ai | OutputFormat::Pretty => {
+                println!("# {}:{}-{}", hit.path, hit.line_start, hit.line_end);
+                println!("{}", hit.content);
+            }
+        }
+        return Ok(());
+    }
+    let file = workspace.get_file(id)?;
+
+    match format {
+        OutputFormat::Json => println!("{}", file.format_json()),
+        OutputFormat::Ai | OutputFormat::Pretty => {
+            print!("{}", file.content);
+            if !file.content.ends_with('\n') {
+                println!();
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,40 +1,125 @@
 use anyhow::{Context, Result};
+use std::io::IsTerminal;
 use std::path::Path;
 use std::time::Instant;
-use ygrep_core::Workspace;
+use ygrep_core::index::{IndexProgress, ProgressBarOutput, ProgressEvent};
+use ygrep_core::{index, Config, MovedIndex, Workspace, YgrepError};
 
+use crate::commands;
+
+/// Progress as one JSON object per line on stdout (`--json-progress`)
+struct JsonProgress;
+
+impl IndexProgress for JsonProgress {
+    fn report(&self, event: ProgressEvent) {
+        if let Ok(line) = serde_json::to_string(&event) {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Ask whether to reuse a moved workspace's index; without a terminal to ask on, only
+/// point out `ygrep indexes relink`
+fn confirm_relink(moved: &MovedIndex) -> bool {
+    let old_root = moved.old_root.display();
+    if !std::io::stdin().is_terminal() {
+        eprintln!(
+            "(found the index of {}, which no longer exists; `ygrep indexes relink` reuses it)",
+            old_root
+        );
+        return false;
+    }
+    eprint!(
+        "Found the index of {}, which no longer exists. Was this workspace moved from there? \
+         Reuse the index [Y/n] ",
+        old_root
+    );
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "" | "y" | "yes")
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     workspace_path: &Path,
     rebuild: bool,
+    full: bool,
     semantic_flag: bool,
     text_flag: bool,
+    hidden_flag: Option<bool>,
+    no_ignore: bool,
+    throttle: bool,
+    json_progress: bool,
+    output: Option<&Path>,
 ) -> Result<()> {
     let start = Instant::now();
+    // `--output` builds a self-contained index outside the data directory
+    let open = |config: Config| match output {
+        Some(dir) => Workspace::create_in(workspace_path, dir, config),
+        None => Workspace::create_with_config(workspace_path, config),
+    };
+
+    // A workspace that was moved or renamed can keep its index
+    if !rebuild && output.is_none() {
+        let config = Config::load();
+        if let Some(moved) = Workspace::find_moved_index(workspace_path, &config)? {
+            if confirm_relink(&moved) {
+                return commands::indexes::relink_moved(workspace_path, &moved, config);
+            }
+        }
+    }
 
     eprintln!("Indexing {}...", workspace_path.display());
 
-    // Open workspace first to read stored flag (before potential rebuild)
+    // Open workspace first to read stored flags (before potential rebuild)
     // Use create() here since we may need to create the index
-    let stored_semantic = if !rebuild {
-        Workspace::create(workspace_path)
-            .ok()
-            .and_then(|ws| ws.stored_semantic_flag())
+    let (stored_semantic, stored_hidden, model_changed, outdated) = if !rebuild {
+        match open(Config::load()) {
+            Ok(ws) => (
+                ws.stored_semantic_flag(),
+                ws.stored_hidden_flag(),
+                ws.embedding_model_changed(),
+                false,
+            ),
+            Err(YgrepError::IndexOutdated(_)) => (None, None, false, true),
+            Err(_) => (None, None, false, false),
+        }
     } else {
-        None
+        (None, None, false, false)
+    };
+
+    // Hidden files that are already indexed would otherwise stay searchable
+    let rebuild = if stored_hidden == Some(true) && hidden_flag == Some(false) {
+        eprintln!("(excluding hidden files - rebuilding)");
+        true
+    } else if model_changed && !text_flag {
+        eprintln!("(embedding model changed - rebuilding)");
+        true
+    } else if outdated {
+        eprintln!("(index format changed - rebuilding)");
+        true
+    } else {
+        rebuild
     };
 
-    if rebuild {
+    let index_path = match output {
+        Some(dir) => dir.to_path_buf(),
+        None => Workspace::index_dir(workspace_path, &Config::load())?,
+    };
+    // The old index stays searchable until the new one is swapped in
+    let staging = if rebuild {
         eprintln!("Rebuilding index from scratch...");
-        // Delete existing index directory
-        if let Ok(workspace) = Workspace::create(workspace_path) {
-            let index_path = workspace.index_path().to_path_buf();
-            drop(workspace); // Release the workspace before deleting
-            if index_path.exists() {
-                std::fs::remove_dir_all(&index_path).context("Failed to remove existing index")?;
-                eprintln!("  Cleared old index at {}", index_path.display());
-            }
+        let staging =
+            index::rebuild::prepare(&index_path).context("Failed to start the rebuild")?;
+        if index_path.exists() {
+            eprintln!("  The old index stays searchable until the rebuild is done");
         }
-    }
+        Some(staging)
+    } else {
+        None
+    };
 
     // Determine whether to use embeddings:
     // 1. Explicit --semantic flag always enables
@@ -60,16 +145,56 @@ pub fn run(
         eprintln!("(converting to text-only index)");
     }
 
-    // Create or open workspace for indexing
-    let workspace = Workspace::create(workspace_path).context("Failed to create workspace")?;
+    // Hidden files: explicit flag, then the stored setting, then the config
+    let mut config = Config::load();
+    if let Some(include_hidden) = hidden_flag.or(stored_hidden) {
+        config.indexer.include_hidden = include_hidden;
+    }
+    if config.indexer.include_hidden {
+        eprintln!("(including hidden files)");
+    }
+    if no_ignore {
+        config.indexer.respect_gitignore = false;
+        eprintln!("(not honoring .gitignore and .ignore files)");
+    }
+    config.indexer.incremental &= !full;
+    config.indexer.background_priority |= throttle;
+    if config.indexer.background_priority {
+        eprintln!("(throttled - indexing at background priority)");
+    }
 
     // Index all files
-    let stats = workspace
-        .index_all_with_options(with_embeddings)
-        .context("Failed to index workspace")?;
+    let progress: Box<dyn IndexProgress> = if json_progress {
+        Box::new(JsonProgress)
+    } else {
+        Box::new(ProgressBarOutput::default())
+    };
+    let indexed = match &staging {
+        Some(dir) => Workspace::create_in(workspace_path, dir, config),
+        None => open(config),
+    }
+    .context("Failed to create workspace")
+    .and_then(|workspace| {
+        workspace
+            .index_all_with_progress(with_embeddings, progress.as_ref())
+            .context("Failed to index workspace")
+    });
+    let stats = match (indexed, &staging) {
+        (Ok(stats), Some(dir)) => {
+            index::rebuild::swap(dir, &index_path).context("Failed to replace the old index")?;
+            stats
+        }
+        (Ok(stats), None) => stats,
+        (Err(e), staging) => {
+            if let Some(dir) = staging {
+                index::rebuild::discard(dir);
+            }
+            return Err(e);
+        }
+    };
 
     let elapsed = start.elapsed();
-    let index_size = dir_size(workspace.index_path());
+    let index_size = dir_size(&index_path);
 
     let index_type = if with_embeddings { "semantic" } else { "text" };
 
@@ -77,14 +202,35 @@ pub fn run(
     eprintln!("Indexing complete in {:.2}s", elapsed.as_secs_f64());
     eprintln!("  Index type: {}", index_type);
     eprintln!("  Files indexed: {}", stats.indexed);
+    if stats.unchanged > 0 {
+        eprintln!("  Unchanged since the last index: {}", stats.unchanged);
+    }
+    if stats.removed > 0 {
+        eprintln!("  Removed: {}", stats.removed);
+    }
     if stats.embedded > 0 {
         eprintln!("  Semantic indexed: {}", stats.embedded);
     }
+    if !stats.budget_excluded.is_empty() {
+        eprintln!(
+            "  Excluded from semantic index (max_index_size): {}",
+            stats.budget_excluded.len()
+        );
+        for path in stats.budget_excluded.iter().take(10) {
+            eprintln!("    {}", path);
+        }
+        if stats.budget_excluded.len() > 10 {
+            eprintln!("    ... and {} more", stats.budget_excluded.len() - 10);
+        }
+    }
+    if stats.transcoded > 0 {
+        eprintln!("  Transcoded to UTF-8: {}", stats.transcoded);
+    }
     eprintln!("  Files skipped: {}", stats.skipped);
     eprintln!("  Errors: {}", stats.errors);
     eprintln!("  Index size: {}", format_size(index_size));
     eprintln!();
-    eprintln!("Index stored at: {}", workspace.index_path().display());
+    eprintln!("Index stored at: {}", index_path.display());
 
     Ok(())
 }
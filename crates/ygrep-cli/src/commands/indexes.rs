@@ -1,6 +1,13 @@
 use anyhow::{Context, Result};
 use std::fs;
-use std::path::PathBuf;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use ygrep_core::layout;
+use ygrep_core::registry::WorkspaceRegistry;
+use ygrep_core::storage::{snapshot, IndexDelta};
+use ygrep_core::{Config, MovedIndex, Workspace};
+
+use crate::error::CliError;
 
 /// Get the indexes directory
 fn get_indexes_dir() -> Result<PathBuf> {
@@ -14,7 +21,6 @@ fn get_indexes_dir() -> Result<PathBuf> {
 #[derive(Debug)]
 struct IndexInfo {
     hash: String,
-    #[allow(dead_code)]
     path: PathBuf,
     workspace: Option<String>,
     size_bytes: u64,
@@ -74,7 +80,7 @@ fn dir_size(path: &PathBuf) -> Result<u64> {
 }
 
 /// Format bytes as human readable
-fn format_size(bytes: u64) -> String {
+pub(crate) fn format_size(bytes: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = KB * 1024;
     const GB: u64 = MB * 1024;
@@ -150,7 +156,7 @@ pub fn list() -> Result<()> {
 }
 
 /// Remove orphaned indexes (workspaces that no longer exist)
-pub fn clean() -> Result<()> {
+pub fn clean(dry_run: bool, force: bool) -> Result<()> {
     let indexes_dir = get_indexes_dir()?;
 
     if !indexes_dir.exists() {
@@ -158,9 +164,7 @@ pub fn clean() -> Result<()> {
         return Ok(());
     }
 
-    let mut removed = 0;
-    let mut freed = 0u64;
-
+    let mut orphans = Vec::new();
     for entry in fs::read_dir(&indexes_dir)? {
         let entry = entry?;
         let path = entry.path();
@@ -174,85 +178,596 @@ pub fn clean() -> Result<()> {
                     };
 
                     if should_remove {
-                        let size = info.size_bytes;
-                        fs::remove_dir_all(&path)?;
-                        println!(
-                            "Removed: {} ({})",
-                            info.workspace.as_deref().unwrap_or(&info.hash),
-                            format_size(size)
-                        );
-                        removed += 1;
-                        freed += size;
+                        orphans.push(info);
                     }
                 }
             }
         }
     }
 
-    if removed == 0 {
+    if orphans.is_empty() {
         println!("No orphaned indexes found.");
-    } else {
+        return Ok(());
+    }
+    delete_indexes(&indexes_dir, &orphans, dry_run, force)
+}
+
+/// Removals larger than this ask for confirmation (unless `--force`)
+const CONFIRM_BYTES: u64 = 500 * 1024 * 1024;
+
+/// Removals of more indexes than this ask for confirmation (unless `--force`)
+const CONFIRM_COUNT: usize = 10;
+
+/// Days removed indexes stay in the trash before they are deleted for good
+const TRASH_DAYS: u64 = 7;
+
+/// Where removed indexes are kept for `TRASH_DAYS`, next to the indexes directory
+fn trash_dir(indexes_dir: &Path) -> PathBuf {
+    indexes_dir.with_file_name("trash")
+}
+
+/// Move `indexes` to the trash, or list them for `--dry-run`; asks first when they are
+/// many or large
+fn delete_indexes(
+    indexes_dir: &Path,
+    indexes: &[IndexInfo],
+    dry_run: bool,
+    force: bool,
+) -> Result<()> {
+    let total: u64 = indexes.iter().map(|info| info.size_bytes).sum();
+    let plural = if indexes.len() == 1 { "" } else { "es" };
+
+    if dry_run {
+        for info in indexes {
+            println!(
+                "Would remove: {} ({})",
+                info.workspace.as_deref().unwrap_or(&info.hash),
+                format_size(info.size_bytes)
+            );
+        }
         println!(
-            "\nRemoved {} indexes, freed {}",
-            removed,
-            format_size(freed)
+            "
+Would remove {} index{}, freeing {}",
+            indexes.len(),
+            plural,
+            format_size(total)
         );
+        return Ok(());
+    }
+
+    let large = total > CONFIRM_BYTES || indexes.len() > CONFIRM_COUNT;
+    if large && !force && !confirm_removal(indexes.len(), total)? {
+        println!("Nothing removed.");
+        return Ok(());
     }
 
+    let trash = trash_dir(indexes_dir);
+    purge_trash(&trash, TRASH_DAYS * 24 * 60 * 60);
+    for info in indexes {
+        move_to_trash(&info.path, &trash, &info.hash)?;
+        println!(
+            "Removed: {} ({})",
+            info.workspace.as_deref().unwrap_or(&info.hash),
+            format_size(info.size_bytes)
+        );
+    }
+    println!(
+        "
+Removed {} index{}, freed {} (kept in {} for {} days)",
+        indexes.len(),
+        plural,
+        format_size(total),
+        trash.display(),
+        TRASH_DAYS
+    );
     Ok(())
 }
 
-/// Remove a specific index by hash or workspace path
-pub fn remove(identifier: &str) -> Result<()> {
-    let indexes_dir = get_indexes_dir()?;
+/// Ask before removing `count` indexes of `total` bytes; without a terminal to ask on,
+/// fail and point out `--force`
+fn confirm_removal(count: usize, total: u64) -> Result<bool> {
+    let what = format!(
+        "{} index{} ({})",
+        count,
+        if count == 1 { "" } else { "es" },
+        format_size(total)
+    );
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "Refusing to remove {} without confirmation; pass --force",
+            what
+        );
+    }
+    eprint!("Remove {}? [y/N] ", what);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
 
-    if !indexes_dir.exists() {
-        println!("No indexes found.");
-        return Ok(());
+/// Move an index directory into the trash as `<hash>.<unix time>`
+fn move_to_trash(path: &Path, trash: &Path, hash: &str) -> Result<()> {
+    fs::create_dir_all(trash)?;
+    let removed_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let target = trash.join(format!("{}.{}", hash, removed_at));
+    if fs::rename(path, &target).is_err() {
+        // (already in the trash from a removal in the same second)
+        fs::remove_dir_all(path)?;
+    }
+    Ok(())
+}
+
+/// Delete the indexes that have been in the trash for longer than `grace_secs`
+fn purge_trash(trash: &Path, grace_secs: u64) {
+    let Ok(entries) = fs::read_dir(trash) else {
+        return;
+    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let removed_at = name
+            .to_str()
+            .and_then(|name| name.rsplit_once('.'))
+            .and_then(|(_, secs)| secs.parse::<u64>().ok());
+        if removed_at.is_some_and(|at| now.saturating_sub(at) > grace_secs) {
+            if let Err(e) = fs::remove_dir_all(entry.path()) {
+                tracing::debug!("Failed to empty {}: {}", entry.path().display(), e);
+            }
+        }
     }
+}
 
-    // First try as hash
+/// Find an index directory by hash or workspace path
+fn find_index(indexes_dir: &Path, identifier: &str) -> Result<Option<(PathBuf, IndexInfo)>> {
+    // First try as hash (not a path, which `join` would resolve outside the indexes)
     let index_path = indexes_dir.join(identifier);
-    if index_path.exists() && index_path.is_dir() {
+    if index_path.parent() == Some(indexes_dir) && index_path.is_dir() {
         let info = read_index_info(identifier, &index_path)?;
-        fs::remove_dir_all(&index_path)?;
-        println!(
-            "Removed index: {} ({})",
-            identifier,
-            format_size(info.size_bytes)
-        );
-        return Ok(());
+        return Ok(Some((index_path, info)));
     }
 
     // Try to find by workspace path
     let target_path = std::fs::canonicalize(identifier).ok();
 
-    for entry in fs::read_dir(&indexes_dir)? {
+    for entry in fs::read_dir(indexes_dir)? {
         let entry = entry?;
         let path = entry.path();
         if path.is_dir() {
             if let Some(hash) = path.file_name().and_then(|n| n.to_str()) {
                 if let Ok(info) = read_index_info(hash, &path) {
                     let matches = match (&info.workspace, &target_path) {
-                        (Some(ws), Some(target)) => PathBuf::from(ws) == *target,
+                        (Some(ws), Some(target)) => Path::new(ws) == target,
                         (Some(ws), None) => ws.contains(identifier),
                         _ => false,
                     };
 
                     if matches {
-                        fs::remove_dir_all(&path)?;
-                        println!(
-                            "Removed index for: {} ({})",
-                            info.workspace.as_deref().unwrap_or(&info.hash),
-                            format_size(info.size_bytes)
-                        );
-                        return Ok(());
+                        return Ok(Some((path, info)));
                     }
                 }
             }
         }
     }
 
-    println!("Index not found: {}", identifier);
+    Ok(None)
+}
+
+/// Remove a specific index by hash or workspace path
+pub fn remove(identifier: &str, dry_run: bool, force: bool) -> Result<()> {
+    let indexes_dir = get_indexes_dir()?;
+
+    if !indexes_dir.exists() {
+        println!("No indexes found.");
+        return Ok(());
+    }
+
+    match find_index(&indexes_dir, identifier)? {
+        Some((_, info)) => delete_indexes(&indexes_dir, &[info], dry_run, force)?,
+        None => println!("Index not found: {}", identifier),
+    }
+    Ok(())
+}
+
+/// Check an index (by hash or workspace path, default the current workspace's) for
+/// unreadable segments, inconsistent vectors and broken metadata; false if it failed
+pub fn verify(identifier: Option<&str>, workspace_path: &Path) -> Result<bool> {
+    let index_path = match identifier {
+        Some(identifier) => {
+            let indexes_dir = get_indexes_dir()?;
+            let found = if indexes_dir.exists() {
+                find_index(&indexes_dir, identifier)?
+            } else {
+                None
+            };
+            match found {
+                Some((path, _)) => path,
+                None => {
+                    println!("Index not found: {}", identifier);
+                    return Ok(false);
+                }
+            }
+        }
+        None => Workspace::index_dir(workspace_path, &Config::load())?,
+    };
+    if !index_path.is_dir() {
+        println!("Index not found: {}", index_path.display());
+        return Ok(false);
+    }
+
+    println!("Verifying {}", index_path.display());
+    let report = ygrep_core::index::verify_index(&index_path);
+    for check in &report.checks {
+        let status = if check.passed() { "ok" } else { "FAILED" };
+        match &check.summary {
+            Some(summary) => println!("  {:<16} {:<7} {}", check.name, status, summary),
+            None => println!("  {:<16} {}", check.name, status),
+        }
+        for problem in &check.problems {
+            println!("    - {}", problem);
+        }
+    }
+
+    println!();
+    if report.passed() {
+        println!("Index OK.");
+    } else {
+        println!(
+            "Index has {} problem{}; `ygrep indexes repair` salvages what it can, \
+             `ygrep index --rebuild` recreates it.",
+            report.problem_count(),
+            if report.problem_count() == 1 { "" } else { "s" }
+        );
+    }
+    Ok(report.passed())
+}
+
+/// Workspace of an index given by hash or workspace path (default: the current
+/// workspace); `None` (after saying so) if there is no such index
+fn resolve_workspace(identifier: Option<&str>, workspace_path: &Path) -> Result<Option<PathBuf>> {
+    let Some(identifier) = identifier else {
+        return Ok(Some(workspace_path.to_path_buf()));
+    };
+    let indexes_dir = get_indexes_dir()?;
+    let found = if indexes_dir.exists() {
+        find_index(&indexes_dir, identifier)?
+    } else {
+        None
+    };
+    match found {
+        Some((_, info)) if info.workspace.is_some() => Ok(info.workspace.map(PathBuf::from)),
+        // An index without readable metadata is only found by its workspace path
+        _ if Path::new(identifier).is_dir() => Ok(Some(PathBuf::from(identifier))),
+        Some((path, _)) => anyhow::bail!(
+            "Can't tell which workspace {} belongs to (its workspace.json is unreadable); \
+             pass the workspace path instead",
+            path.display()
+        ),
+        None => {
+            println!("Index not found: {}", identifier);
+            Ok(None)
+        }
+    }
+}
+
+/// Repair an index (by hash or workspace path, default the current workspace's) in
+/// place, keeping what is intact and indexing only the files that were lost
+pub fn repair(identifier: Option<&str>, workspace_path: &Path) -> Result<()> {
+    let Some(root) = resolve_workspace(identifier, workspace_path)? else {
+        return Ok(());
+    };
+
+    println!("Repairing index of {}", root.display());
+    let report = Workspace::repair(&root, Config::load())?;
+    if report.is_clean() {
+        println!("Nothing to repair.");
+        return Ok(());
+    }
+    for repaired in &report.repaired {
+        println!("  - {}", repaired);
+    }
+    if report.reindexed > 0 {
+        println!("  - Indexed {} files again", report.reindexed);
+    }
+    println!();
+    println!("Index repaired; `ygrep indexes verify` checks it again.");
+    Ok(())
+}
+
+/// Drop vectors whose documents are no longer in the text index
+pub fn gc(identifier: Option<&str>, workspace_path: &Path) -> Result<()> {
+    let Some(root) = resolve_workspace(identifier, workspace_path)? else {
+        return Ok(());
+    };
+    let workspace = Workspace::open(&root)?;
+    if !workspace.has_semantic_index() {
+        println!("No semantic index, nothing to collect.");
+        return Ok(());
+    }
+    match workspace.remove_orphan_vectors()? {
+        0 => println!("No orphan vectors found."),
+        dropped => println!("Removed {} orphan vectors.", dropped),
+    }
+    Ok(())
+}
+
+/// Merge the text index segments, drop orphan vectors and remove unused files
+pub fn optimize(identifier: Option<&str>, workspace_path: &Path) -> Result<()> {
+    let Some(root) = resolve_workspace(identifier, workspace_path)? else {
+        return Ok(());
+    };
+    let workspace = Workspace::open(&root)?;
+    let index_path = workspace.index_path().to_path_buf();
+    let size_before = dir_size(&index_path).unwrap_or(0);
+
+    println!("Optimizing index of {}", workspace.root().display());
+    let stats = workspace.optimize()?;
+    println!(
+        "  Text index: {} segments -> {}",
+        stats.segments_before, stats.segments_after
+    );
+    if workspace.has_semantic_index() || stats.orphan_vectors > 0 {
+        println!("  Orphan vectors removed: {}", stats.orphan_vectors);
+    }
+    println!(
+        "  Size: {} -> {}",
+        format_size(size_before),
+        format_size(dir_size(&index_path).unwrap_or(0))
+    );
+    Ok(())
+}
+
+/// Publish the workspace's index to storage (`s3://`, `gs://` or a directory), for
+/// consumers that search it with `indexer.remote_index` instead of indexing
+pub fn publish(url: &str, workspace_path: &Path) -> Result<()> {
+    let workspace = Workspace::open(workspace_path)
+        .map_err(|_| CliError::not_indexed(workspace_path, "publish this workspace"))?;
+
+    println!(
+        "Publishing index of {} to {}",
+        workspace.root().display(),
+        url
+    );
+    let stats = workspace.publish(url).context("Failed to publish index")?;
+    println!(
+        "  Uploaded {} files ({}), {} unchanged",
+        stats.copied,
+        format_size(stats.bytes),
+        stats.unchanged
+    );
+    if stats.removed > 0 {
+        println!("  Removed {} files no longer in the index", stats.removed);
+    }
+    Ok(())
+}
+
+/// Upload the workspace's index as a bundle for its repository and commit
+pub fn push(url: &str, repo: Option<&str>, workspace_path: &Path) -> Result<()> {
+    let workspace = Workspace::open(workspace_path)
+        .map_err(|_| CliError::not_indexed(workspace_path, "push its index"))?;
+
+    let bundle = workspace
+        .push_bundle(url, repo)
+        .context("Failed to push index")?;
+    println!(
+        "Pushed index of {} at {} ({})",
+        bundle.repo,
+        short_commit(&bundle.commit),
+        format_size(bundle.size)
+    );
+    Ok(())
+}
+
+/// Replace the workspace's index with the newest bundle pushed for its history
+pub fn pull(url: &str, repo: Option<&str>, workspace_path: &Path) -> Result<()> {
+    let (workspace, pulled) = Workspace::pull_bundle(workspace_path, Config::load(), url, repo)
+        .context("Failed to pull index")?;
+
+    let bundle = &pulled.bundle;
+    println!(
+        "Pulled index of {} at {} ({})",
+        bundle.repo,
+        short_commit(&bundle.commit),
+        format_size(bundle.size)
+    );
+    if pulled.behind > 0 {
+        println!(
+            "  {} commits behind HEAD, re-indexed {} changed files",
+            pulled.behind, pulled.refreshed
+        );
+    } else if pulled.refreshed > 0 {
+        println!("  Re-indexed {} uncommitted files", pulled.refreshed);
+    }
+    println!("  {}", workspace.index_path().display());
+    Ok(())
+}
+
+/// Write the changes between two index snapshots to a delta file
+pub fn diff(old: &Path, new: &Path, output: &Path) -> Result<()> {
+    let delta = IndexDelta::between(old, new).context("Failed to diff indexes")?;
+    let size = delta.write(output).context("Failed to write delta")?;
+    let stats = delta.stats();
+    println!(
+        "Wrote delta to {} ({})",
+        output.display(),
+        format_size(size)
+    );
+    if let (Some(from), Some(to)) = (&delta.base_commit, &delta.target_commit) {
+        println!("  {} -> {}", short_commit(from), short_commit(to));
+    }
+    println!(
+        "  {} files changed, {} removed ({} documents, {} vectors)",
+        stats.changed, stats.removed, stats.documents, stats.vectors
+    );
+    Ok(())
+}
+
+/// Copy the workspace's index as a snapshot for `--at`
+pub fn snapshot(name: Option<&str>, workspace_path: &Path) -> Result<()> {
+    let workspace = Workspace::open(workspace_path)
+        .map_err(|_| CliError::not_indexed(workspace_path, "snapshot its index"))?;
+    let snapshot = workspace
+        .take_snapshot(name)
+        .context("Failed to snapshot index")?;
+    println!(
+        "Saved snapshot {} ({})",
+        snapshot.name,
+        format_size(dir_size(&snapshot.path).unwrap_or(0))
+    );
+    println!(
+        "  Search it with: ygrep search <query> --at {}",
+        snapshot.name
+    );
+    Ok(())
+}
+
+/// List the workspace's snapshots, oldest first
+pub fn snapshots(workspace_path: &Path) -> Result<()> {
+    let dir = Workspace::snapshots_dir(workspace_path, &Config::load())?;
+    let snapshots = snapshot::list(&dir);
+    if snapshots.is_empty() {
+        println!("No snapshots (take one with `ygrep indexes snapshot`)");
+        return Ok(());
+    }
+    for snapshot in &snapshots {
+        let indexed = snapshot
+            .indexed_at
+            .map(|time| {
+                time.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_else(|| "-".to_string());
+        let commit = snapshot.commit.as_deref().map(short_commit).unwrap_or("-");
+        println!(
+            "{:<24} {:<16} {:<8} {}",
+            snapshot.name,
+            indexed,
+            commit,
+            format_size(dir_size(&snapshot.path).unwrap_or(0))
+        );
+    }
+    Ok(())
+}
+
+/// Reuse the index the workspace had before it was moved or renamed
+pub fn relink(workspace_path: &Path) -> Result<()> {
+    let config = Config::load();
+    let Some(moved) = Workspace::find_moved_index(workspace_path, &config)? else {
+        anyhow::bail!(
+            "No index of a moved workspace found for {} (index it with `ygrep index`)",
+            workspace_path.display()
+        );
+    };
+    relink_moved(workspace_path, &moved, config)
+}
+
+/// Re-link `moved` to the workspace, re-pointing the names registered for its old path
+pub(crate) fn relink_moved(
+    workspace_path: &Path,
+    moved: &MovedIndex,
+    config: Config,
+) -> Result<()> {
+    let data_dir = config.indexer.data_dir.clone();
+    let (workspace, refreshed) =
+        Workspace::relink(workspace_path, moved, config).context("Failed to re-link the index")?;
+    let mut registry = WorkspaceRegistry::load(&data_dir)?;
+    if registry.relocate(&moved.old_root, workspace.root()) {
+        registry.save(&data_dir)?;
+    }
+    println!(
+        "Re-linked the index of {} to {} ({} changed files re-indexed)",
+        moved.old_root.display(),
+        workspace.root().display(),
+        refreshed
+    );
+    Ok(())
+}
+
+/// Move caches into the cache directory, including the model caches older versions
+/// downloaded into the workspace and working directory
+pub fn migrate(workspace_path: &Path) -> Result<()> {
+    let config = Config::load();
+    let mut model_caches = vec![workspace_path.join(layout::LEGACY_MODEL_CACHE)];
+    if let Ok(current_dir) = std::env::current_dir() {
+        let cache = current_dir.join(layout::LEGACY_MODEL_CACHE);
+        if !model_caches.contains(&cache) {
+            model_caches.push(cache);
+        }
+    }
+    let migration = layout::migrate(&config.indexer, &model_caches)?;
+    if migration.moved.is_empty() {
+        println!(
+            "Nothing to move; caches are in {}",
+            config.indexer.cache_dir.display()
+        );
+    }
+    for (from, to) in &migration.moved {
+        println!("Moved {} to {}", from.display(), to.display());
+    }
+    Ok(())
+}
+
+/// Apply a delta to the workspace's index, or to the index directory `index`
+pub fn apply_delta(delta_path: &Path, index: Option<&Path>, workspace_path: &Path) -> Result<()> {
+    let workspace = match index {
+        Some(dir) if !dir.join("workspace.json").exists() => {
+            anyhow::bail!("No index at {}", dir.display())
+        }
+        Some(dir) => Workspace::create_in(workspace_path, dir, Config::load())?,
+        None => Workspace::open(workspace_path)
+            .map_err(|_| CliError::not_indexed(workspace_path, "apply a delta to its index"))?,
+    };
+
+    let delta = IndexDelta::read(delta_path)?;
+    let stats = workspace
+        .apply_delta(&delta)
+        .context("Failed to apply delta")?;
+    if stats.changed + stats.removed == 0 {
+        println!("{} is up to date", workspace.index_path().display());
+        return Ok(());
+    }
+    println!(
+        "Updated {}: {} files changed, {} removed",
+        workspace.index_path().display(),
+        stats.changed,
+        stats.removed
+    );
     Ok(())
 }
+
+fn short_commit(commit: &str) -> &str {
+    &commit[..commit.len().min(7)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trash() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let indexes_dir = temp.path().join("indexes");
+        let index = indexes_dir.join("0123456789abcdef");
+        fs::create_dir_all(&index)?;
+        fs::write(index.join("workspace.json"), "{}")?;
+        let trash = trash_dir(&indexes_dir);
+
+        move_to_trash(&index, &trash, "0123456789abcdef")?;
+        assert!(!index.exists());
+        let trashed: Vec<_> = fs::read_dir(&trash)?.flatten().collect();
+        assert_eq!(trashed.len(), 1);
+        assert!(trashed[0].path().join("workspace.json").exists());
+
+        // Kept within the grace period, deleted after it
+        purge_trash(&trash, 60);
+        assert!(trashed[0].path().exists());
+        fs::create_dir_all(trash.join("fedcba9876543210.1000"))?;
+        purge_trash(&trash, 60);
+        assert!(trashed[0].path().exists());
+        assert!(!trash.join("fedcba9876543210.1000").exists());
+        Ok(())
+    }
+}
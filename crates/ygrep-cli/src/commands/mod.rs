@@ -1,6 +1,16 @@
+pub mod daemon;
+pub mod eval;
+pub mod feedback;
+pub mod files;
+pub mod get;
 pub mod index;
 pub mod indexes;
 pub mod install;
+pub mod model;
+pub mod refs;
 pub mod search;
+pub mod serve;
 pub mod status;
+pub mod todos;
 pub mod watch;
+pub mod workspaces;
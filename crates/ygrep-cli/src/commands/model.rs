@@ -0,0 +1,256 @@
+//! Embedding model comparison (`ygrep model bench`): a sample of the workspace is indexed
+//! with each model, and indexing throughput, index size and (with eval cases) retrieval
+//! quality are reported, so a model can be picked before building a full semantic index
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use std::time::Instant;
+use ygrep_core::fs::FileWalker;
+use ygrep_core::{index, Config, Workspace};
+
+use super::eval::{self, EvalCase, ModeReport};
+use super::indexes::format_size;
+use crate::OutputFormat;
+
+/// How one model did on the sample
+#[derive(Debug, Clone, Serialize)]
+struct ModelBench {
+    /// Name for the `indexer.embedding_model` setting
+    model: &'static str,
+    files: usize,
+    /// Files and chunks embedded
+    embedded: usize,
+    /// Indexing time, model loading included
+    seconds: f64,
+    /// Documents embedded per second
+    docs_per_second: f64,
+    index_size: u64,
+    /// Semantic and hybrid search quality (with `--cases`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    quality: Vec<ModeReport>,
+}
+
+/// Models that can be compared, by `indexer.embedding_model` name
+fn models() -> Vec<&'static str> {
+    #[cfg(feature = "embeddings")]
+    let models = ygrep_core::embeddings::ModelType::ALL
+        .iter()
+        .map(|model| model.id())
+        .collect();
+    #[cfg(not(feature = "embeddings"))]
+    let models = Vec::new();
+    models
+}
+
+pub fn bench(
+    workspace_path: &Path,
+    sample: usize,
+    cases_path: Option<&Path>,
+    limit: usize,
+    format: OutputFormat,
+) -> Result<()> {
+    let models = models();
+    if models.is_empty() {
+        anyhow::bail!(
+            "Semantic search is not available in this build (rebuild with the `embeddings` feature)"
+        );
+    }
+    let cases = cases_path.map(eval::load_cases).transpose()?;
+
+    let config = Config::load();
+    let bench_dir = ygrep_core::layout::bench_dir(&config.indexer);
+    if bench_dir.exists() {
+        std::fs::remove_dir_all(&bench_dir).context("Failed to clear the last benchmark")?;
+    }
+    let result = run_bench(
+        workspace_path,
+        &bench_dir,
+        config,
+        &models,
+        sample,
+        cases.as_deref().unwrap_or_default(),
+        limit,
+    );
+    if let Err(e) = std::fs::remove_dir_all(&bench_dir) {
+        tracing::warn!("Failed to remove {}: {}", bench_dir.display(), e);
+    }
+    let (files, benches) = result?;
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&benches)?),
+        OutputFormat::Ai | OutputFormat::Pretty => {
+            print!("{}", format_benches(&benches, files, cases.is_some()))
+        }
+    }
+    Ok(())
+}
+
+/// Copy a sample of the workspace into `bench_dir` and index it with each model.
+/// Returns the number of sampled files with a result per model.
+fn run_bench(
+    workspace_path: &Path,
+    bench_dir: &Path,
+    config: Config,
+    models: &[&'static str],
+    sample: usize,
+    cases: &[EvalCase],
+    limit: usize,
+) -> Result<(usize, Vec<ModelBench>)> {
+    let root = std::fs::canonicalize(workspace_path)?;
+    let mut files = Vec::new();
+    for entry in FileWalker::new(root.clone(), config.indexer.clone())?.walk() {
+        if entry.path.starts_with(&root) {
+            files.push(ygrep_core::fs::relative_path(&entry.path, &root));
+        }
+    }
+    let expected: Vec<String> = cases
+        .iter()
+        .flat_map(|case| case.expected.iter().cloned())
+        .collect();
+    let files = pick_sample(files, sample, &expected);
+
+    let sample_root = bench_dir.join("sample");
+    for file in &files {
+        let target = sample_root.join(file);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(root.join(file), &target)
+            .with_context(|| format!("Failed to copy {} into the sample", file))?;
+    }
+    // Cases can only find files that made it into the sample
+    let cases: Vec<EvalCase> = cases
+        .iter()
+        .filter(|case| case.expected.iter().any(|path| files.contains(path)))
+        .cloned()
+        .collect();
+
+    let mut benches = Vec::with_capacity(models.len());
+    for &model in models {
+        eprintln!("Indexing {} files with {}...", files.len(), model);
+        let mut config = config.clone();
+        config.indexer.data_dir = bench_dir.join(model);
+        config.indexer.embedding_model = model.to_string();
+
+        let start = Instant::now();
+        let workspace = Workspace::create_with_config(&sample_root, config.clone())?;
+        let stats = workspace.index_all_with_options(true)?;
+        let seconds = start.elapsed().as_secs_f64();
+        let index_size = index::history::index_size(workspace.index_path());
+        drop(workspace);
+
+        let quality = if cases.is_empty() {
+            Vec::new()
+        } else {
+            eval::evaluate(&sample_root, config, &cases, limit, &["semantic", "hybrid"])?
+        };
+        benches.push(ModelBench {
+            model,
+            files: stats.indexed,
+            embedded: stats.embedded,
+            seconds,
+            docs_per_second: stats.embedded as f64 / seconds.max(f64::EPSILON),
+            index_size,
+            quality,
+        });
+    }
+    Ok((files.len(), benches))
+}
+
+/// The files the cases expect (if they exist), then evenly spaced files from the rest
+/// up to `sample` in all
+fn pick_sample(mut files: Vec<String>, sample: usize, expected: &[String]) -> Vec<String> {
+    files.sort();
+    files.dedup();
+    let (mut picked, rest): (Vec<String>, Vec<String>) =
+        files.into_iter().partition(|file| expected.contains(file));
+
+    let wanted = sample.saturating_sub(picked.len()).min(rest.len());
+    if wanted > 0 {
+        let step = rest.len() as f64 / wanted as f64;
+        picked.extend((0..wanted).map(|i| rest[(i as f64 * step) as usize].clone()));
+    }
+    picked
+}
+
+/// Table with a row per model
+fn format_benches(benches: &[ModelBench], files: usize, with_cases: bool) -> String {
+    let mut output = format!("# {} sample files\n\n", files);
+    output.push_str(&format!(
+        "{:<20} {:>8} {:>8} {:>8} {:>11}",
+        "model", "docs", "seconds", "docs/s", "index size"
+    ));
+    if with_cases {
+        output.push_str(&format!(
+            " {:>12} {:>10} {:>12} {:>10}",
+            "semantic mrr", "recall", "hybrid mrr", "recall"
+        ));
+    }
+    output.push('\n');
+
+    for bench in benches {
+        output.push_str(&format!(
+            "{:<20} {:>8} {:>8.1} {:>8.1} {:>11}",
+            bench.model,
+            bench.embedded,
+            bench.seconds,
+            bench.docs_per_second,
+            format_size(bench.index_size)
+        ));
+        for report in &bench.quality {
+            match report.skipped {
+                Some(_) => output.push_str(&format!(" {:>12} {:>10}", "-", "-")),
+                None => output.push_str(&format!(" {:>12.3} {:>10.3}", report.mrr, report.recall)),
+            }
+        }
+        output.push('\n');
+    }
+    if with_cases && benches.iter().all(|bench| bench.quality.is_empty()) {
+        output.push_str("\nNo case expects a sampled file; quality wasn't measured.\n");
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pick_sample() {
+        let files = paths(&["a.rs", "b.rs", "c.rs", "d.rs", "e.rs", "f.rs"]);
+        assert_eq!(
+            pick_sample(files.clone(), 3, &[]),
+            paths(&["a.rs", "c.rs", "e.rs"])
+        );
+
+        // Expected files come first and count towards the sample
+        let sample = pick_sample(files.clone(), 3, &paths(&["f.rs", "missing.rs"]));
+        assert_eq!(sample, paths(&["f.rs", "a.rs", "c.rs"]));
+
+        assert_eq!(pick_sample(files.clone(), 100, &[]), files);
+        assert!(pick_sample(files, 0, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_format_benches() {
+        let bench = ModelBench {
+            model: "all-minilm-l6-v2",
+            files: 10,
+            embedded: 40,
+            seconds: 2.0,
+            docs_per_second: 20.0,
+            index_size: 2048,
+            quality: Vec::new(),
+        };
+        let output = format_benches(&[bench], 10, false);
+        assert!(output.starts_with("# 10 sample files\n"));
+        assert!(output.contains("all-minilm-l6-v2"));
+        assert!(output.contains("2.0 KB"));
+        assert!(!output.contains("mrr"));
+    }
+}
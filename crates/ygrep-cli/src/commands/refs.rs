@@ -0,0 +1,24 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use ygrep_core::Workspace;
+
+use crate::OutputFormat;
+
+/// Find where a symbol is defined and used
+pub fn run(workspace_path: &Path, symbol: &str, limit: usize, format: OutputFormat) -> Result<()> {
+    let workspace = Workspace::open(workspace_path).with_context(|| {
+        format!(
+            "No index for {} (run `ygrep index` first)",
+            workspace_path.display()
+        )
+    })?;
+    let refs = workspace.find_references(symbol, limit)?;
+
+    let output = match format {
+        OutputFormat::Ai => refs.format_ai(),
+        OutputFormat::Json => refs.format_json(),
+        OutputFormat::Pretty => refs.format_pretty(),
+    };
+    print!("{}", output);
+    Ok(())
+}
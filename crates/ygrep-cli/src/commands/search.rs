@@ -1,89 +1,439 @@
 use anyhow::{Context, Result};
-use std::path::Path;
-use ygrep_core::search::{MatchType, SearchHit, SearchResult};
-use ygrep_core::Workspace;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use ygrep_core::fs::{find_packages, find_submodules, relative_path, FileKind};
+use ygrep_core::search::{
+    DisplayOptions, HeatmapDimension, MatchType, SearchFilters, SearchHit, SearchResult,
+    LAST_RESULT_SET,
+};
+use ygrep_core::storage::snapshot;
+use ygrep_core::{Config, Workspace, YgrepError};
 
+use crate::daemon;
+use crate::error::CliError;
 use crate::output::format_tree_heatmap;
-use crate::OutputFormat;
+use crate::{OutputFormat, SearchArgs};
 
 pub fn run(
     workspace_path: &Path,
     query: &str,
-    limit: usize,
-    extensions: Vec<String>,
-    paths: Vec<String>,
-    use_regex: bool,
-    show_scores: bool,
-    text_only: bool,
-    tree: bool,
-    depth: Option<usize>,
-    tree_min_score: Option<f32>,
-    tree_top: Option<usize>,
+    args: &SearchArgs,
     format: OutputFormat,
 ) -> Result<()> {
-    // Open existing workspace (fails if not indexed)
-    let workspace = match Workspace::open(workspace_path) {
+    let config = search_config(args)?;
+    let display = DisplayOptions::from_config(&config.search);
+    let args = &scoped(args, workspace_path, &config);
+    let result = search_workspace(workspace_path, query, args, config)?;
+    print_result(result, args, format, &display)
+}
+
+/// Search several workspaces and merge the hits by score
+///
+/// Each entry is a label (the `-C` argument, e.g. `@backend`) and the workspace root;
+/// hit paths are prefixed with the label so results from different workspaces stay apart.
+pub fn run_many(
+    workspaces: &[(String, PathBuf)],
+    query: &str,
+    args: &SearchArgs,
+    format: OutputFormat,
+) -> Result<()> {
+    let config = search_config(args)?;
+    let display = DisplayOptions::from_config(&config.search);
+    let mut results = Vec::with_capacity(workspaces.len());
+    for (label, path) in workspaces {
+        let args = &scoped(args, path, &config);
+        let result = search_workspace(path, query, args, config.clone())?;
+        results.push((label.clone(), result));
+    }
+    let result = merge_results(results, args.limit);
+    print_result(result, args, format, &display)
+}
+
+/// Load the config and apply the command-line overrides
+fn search_config(args: &SearchArgs) -> Result<Config> {
+    let mut config = Config::load();
+    config.search.facets |= args.facets;
+    config.search.explain |= args.explain;
+    config.search.chunk_context |= args.chunk_context;
+    config.search.expand_synonyms |= args.synonyms;
+    config.search.smart_case |= args.smart_case;
+    config.search.identifier_matching |= args.ident;
+    config.search.negation |= args.negate;
+    config.search.auto_index |= args.auto_index;
+    config.search.auto_refresh |= args.auto_refresh;
+    config.search.fallback_scan |= args.fallback_scan;
+    config.search.scope_cwd |= args.scope_cwd;
+    if let Some(engine) = &args.engine {
+        config.search.regex_engine = engine.parse()?;
+    }
+    if let Some(lines) = args.snippet_lines {
+        config.search.snippet_lines = lines;
+    }
+    if let Some(lines) = args.context_before {
+        config.search.context_before = lines;
+    }
+    if args.context_after.is_some() {
+        config.search.context_after = args.context_after;
+    }
+    if let Some(lines) = args.expand {
+        config.search.expand_lines = lines;
+    }
+    if let Some(lines) = args.preview_lines {
+        config.search.preview_lines = lines;
+    }
+    if args.max_line_length.is_some() {
+        config.search.max_line_length = args.max_line_length;
+    }
+    if args.max_per_file.is_some() {
+        config.search.max_per_file = args.max_per_file;
+    }
+    if args.timeout.is_some() {
+        config.search.regex_timeout_ms = args.timeout;
+    }
+    if args.max_scanned.is_some() {
+        config.search.regex_max_scanned = args.max_scanned;
+    }
+    if args.max_tokens.is_some() {
+        config.search.max_tokens = args.max_tokens;
+    }
+    Ok(config)
+}
+
+/// `args` with `scope_dir` set to the current directory's path in the workspace, for
+/// `--scope-cwd`; unscoped when the current directory is the root or outside it
+fn scoped(args: &SearchArgs, workspace_path: &Path, config: &Config) -> SearchArgs {
+    let mut args = args.clone();
+    if !config.search.scope_cwd {
+        return args;
+    }
+    let current_dir = std::env::current_dir().and_then(std::fs::canonicalize);
+    let root = std::fs::canonicalize(workspace_path);
+    if let (Ok(current_dir), Ok(root)) = (current_dir, root) {
+        if current_dir.starts_with(&root) && current_dir != root {
+            args.scope_dir = Some(relative_path(&current_dir, &root));
+        }
+    }
+    args
+}
+
+/// Search one workspace and remember the result paths for `--within-last`/`--save-as`
+fn search_workspace(
+    workspace_path: &Path,
+    query: &str,
+    args: &SearchArgs,
+    config: Config,
+) -> Result<SearchResult> {
+    if args.index.is_some() || args.at.is_some() {
+        return search_in(&open_workspace(workspace_path, args, config)?, query, args);
+    }
+
+    // A running `ygrep daemon` already has the workspace (and semantic model) loaded;
+    // indexing and refreshing stay in this process
+    if !config.search.auto_index && !config.search.auto_refresh {
+        if let Some(result) = daemon::search(workspace_path, query, args, &config) {
+            return Ok(result);
+        }
+    }
+
+    // Open existing workspace (fails if not indexed, unless auto-indexing)
+    let auto_index = config.search.auto_index;
+    let refresh_interval = config
+        .search
+        .auto_refresh
+        .then(|| Duration::from_secs(config.search.refresh_interval_secs));
+    let workspace = match Workspace::open_with_config(workspace_path, config.clone()) {
         Ok(ws) => ws,
+        Err(e @ (YgrepError::IndexOutdated(_) | YgrepError::Storage(_))) => return Err(e.into()),
+        Err(_) if auto_index => {
+            eprintln!(
+                "Workspace not indexed, indexing {} (text-only)...",
+                workspace_path.display()
+            );
+            let workspace = Workspace::create_with_config(workspace_path, config)
+                .context("Failed to create workspace")?;
+            workspace.index_all().context("Failed to index workspace")?;
+            workspace
+        }
+        Err(_) if config.search.fallback_scan => {
+            let workspace =
+                Workspace::scan(workspace_path, config).context("Failed to scan workspace")?;
+            let files = workspace.scan_stats().map_or(0, |scan| scan.files);
+            eprintln!(
+                "Workspace not indexed, searched {} files directly (run `ygrep index` for faster searches)",
+                files
+            );
+            return search_in(&workspace, query, args);
+        }
         Err(_) => {
-            eprintln!("Workspace not indexed: {}", workspace_path.display());
-            eprintln!();
-            eprintln!("To index this workspace, run:");
-            eprintln!("  ygrep index              # Text-only (fast)");
-            eprintln!("  ygrep index --semantic   # With semantic search (slower, better results)");
-            std::process::exit(1);
+            return Err(CliError::not_indexed(workspace_path, "search this workspace").into())
         }
     };
 
-    // Search: use hybrid search by default if semantic index is available
-    #[cfg(feature = "embeddings")]
-    let use_hybrid = !text_only && workspace.has_semantic_index();
-    #[cfg(not(feature = "embeddings"))]
-    let use_hybrid = false;
-    let _ = text_only; // Suppress unused warning when embeddings disabled
+    if let Some(interval) = refresh_interval {
+        match workspace.refresh_if_stale(interval) {
+            Ok(Some(updated)) if updated > 0 => {
+                eprintln!("Refreshed index: {} changed files", updated)
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: failed to refresh index: {}", e),
+        }
+    }
 
-    // Hold copies so we can consistently apply filters after search (hybrid ignores them)
-    let extension_filters = extensions.clone();
-    let path_filters = paths.clone();
+    search_in(&workspace, query, args)
+}
+
+/// Open the workspace's index, or the `--index` directory or `--at` snapshot read-only
+fn open_workspace(workspace_path: &Path, args: &SearchArgs, config: Config) -> Result<Workspace> {
+    if let Some(at) = &args.at {
+        let dir = Workspace::snapshots_dir(workspace_path, &config)?;
+        let snapshot = snapshot::resolve(&dir, at)?;
+        return Workspace::open_read_only(&snapshot.path, workspace_path, config)
+            .with_context(|| format!("Failed to open snapshot {}", snapshot.name));
+    }
+    match &args.index {
+        Some(index_dir) => Workspace::open_read_only(index_dir, workspace_path, config)
+            .with_context(|| format!("Failed to open index {}", index_dir.display())),
+        None => Ok(Workspace::open_with_config(workspace_path, config)?),
+    }
+}
+
+/// Search an open workspace (here or in the daemon)
+pub(crate) fn search_in(
+    workspace: &Workspace,
+    query: &str,
+    args: &SearchArgs,
+) -> Result<SearchResult> {
+    let limit = args.limit;
+    let use_regex = args.regex;
+    let use_hybrid = hybrid_mode(workspace, args).is_ok();
+    let filters = search_filters(workspace, args)?;
 
-    let mut result = if use_hybrid && !use_regex {
-        // Hybrid search (BM25 + vector with RRF) - not supported with regex
+    let mut result = if use_hybrid {
+        // Hybrid search (BM25, or the regex, + vector with RRF)
         #[cfg(feature = "embeddings")]
         {
-            workspace
-                .search_hybrid(query, Some(limit))
-                .context("Hybrid search failed")?
+            if use_regex {
+                workspace.search_hybrid_regex(query, Some(limit), filters)
+            } else {
+                workspace.search_hybrid(query, Some(limit), filters)
+            }
+            .context("Hybrid search failed")?
         }
         #[cfg(not(feature = "embeddings"))]
         unreachable!()
     } else {
-        // Build filters for text-only search
-        let ext_filter = if extensions.is_empty() {
-            None
-        } else {
-            Some(extensions)
-        };
-        let path_filter = if paths.is_empty() { None } else { Some(paths) };
-
         workspace
-            .search_filtered(query, Some(limit), ext_filter, path_filter, use_regex)
+            .search_with_filters(query, Some(limit), filters, use_regex)
             .context("Search failed")?
     };
 
-    // Apply filters to hybrid results (text search is a no-op)
-    apply_filters(&mut result, &extension_filters, &path_filters);
+    if args.stats {
+        result.stats = Some(workspace.stats());
+    }
+
+    // Remember the result paths so the next search can refine them
+    if let Err(e) = workspace.save_result_set(LAST_RESULT_SET, query, &result) {
+        tracing::debug!("Failed to save last results: {}", e);
+    }
+    if let Some(name) = &args.save_as {
+        workspace
+            .save_result_set(name, query, &result)
+            .with_context(|| format!("Failed to save results as '{}'", name))?;
+    }
 
-    if tree {
-        let tree_hits = apply_tree_filters(&result.hits, tree_min_score, tree_top);
-        let output = format_tree_heatmap(&tree_hits, depth);
+    Ok(result)
+}
+
+/// Show how a query would be interpreted and run (`ygrep explain-query`)
+pub fn explain(
+    workspace_path: &Path,
+    query: &str,
+    args: &SearchArgs,
+    format: OutputFormat,
+) -> Result<()> {
+    let config = search_config(args)?;
+    let args = &scoped(args, workspace_path, &config);
+    let workspace = open_workspace(workspace_path, args, config)?;
+    let hybrid = hybrid_mode(&workspace, args);
+    let mut plan = workspace.explain_query(
+        query,
+        search_filters(&workspace, args)?,
+        args.regex,
+        hybrid.is_ok(),
+    )?;
+    plan.hybrid_reason = Some(hybrid.unwrap_or_else(|reason| reason).to_string());
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&plan)?),
+        OutputFormat::Ai | OutputFormat::Pretty => print!("{}", plan.format_text()),
+    }
+    Ok(())
+}
+
+/// Whether a search runs hybrid (BM25 + vector with RRF), with the reason either way
+fn hybrid_mode(workspace: &Workspace, args: &SearchArgs) -> Result<&'static str, &'static str> {
+    if !cfg!(feature = "embeddings") {
+        Err("semantic search is not available in this build")
+    } else if args.text_only {
+        Err("--text-only")
+    } else if !workspace.has_semantic_index() {
+        Err("no semantic index")
+    } else {
+        Ok("semantic index available")
+    }
+}
+
+/// `-e`/`-p`/`-P`/`--kind`/`--within` as search filters; applied inside the index by both text
+/// and hybrid search (including extensionless files typed by their shebang), so they
+/// don't starve the limit
+fn search_filters(workspace: &Workspace, args: &SearchArgs) -> Result<SearchFilters> {
+    // Restrict to the files returned by an earlier search
+    let within_name = if args.within_last {
+        Some(LAST_RESULT_SET)
+    } else {
+        args.within.as_deref()
+    };
+    let within = match within_name {
+        Some(name) => Some(
+            workspace
+                .load_result_set(name)
+                .with_context(|| format!("Cannot refine search within '{}'", name))?
+                .paths,
+        ),
+        None => None,
+    };
+
+    Ok(SearchFilters {
+        extensions: (!args.extensions.is_empty()).then(|| args.extensions.clone()),
+        paths: (!args.paths.is_empty()).then(|| args.paths.clone()),
+        dirs: scope_dirs(
+            package_dirs(workspace.root(), &args.packages)?,
+            args.scope_dir.as_deref(),
+        ),
+        submodules: submodule_names(workspace.root(), &args.submodules)?,
+        within,
+        ..kind_filters(args)?
+    })
+}
+
+/// Directories of the `--package` packages (`None` without any)
+fn package_dirs(root: &Path, names: &[String]) -> Result<Option<Vec<String>>> {
+    if names.is_empty() {
+        return Ok(None);
+    }
+    let packages = find_packages(root);
+    let mut dirs = Vec::new();
+    for name in names {
+        let found: Vec<String> = packages
+            .iter()
+            .filter(|package| package.is_named(name))
+            .map(|package| package.dir.clone())
+            .collect();
+        if found.is_empty() {
+            let known: Vec<&str> = packages.iter().map(|p| p.name.as_str()).collect();
+            anyhow::bail!(
+                "No package named '{}' in {} (packages: {})",
+                name,
+                root.display(),
+                if known.is_empty() {
+                    "none".to_string()
+                } else {
+                    known.join(", ")
+                }
+            );
+        }
+        dirs.extend(found);
+    }
+    Ok(Some(dirs))
+}
+
+/// `dirs` narrowed to the `--scope-cwd` directory: the parts of each that lie under it
+/// (nothing for packages elsewhere)
+fn scope_dirs(dirs: Option<Vec<String>>, scope: Option<&str>) -> Option<Vec<String>> {
+    let Some(scope) = scope else {
+        return dirs;
+    };
+    let Some(dirs) = dirs else {
+        return Some(vec![scope.to_string()]);
+    };
+    let is_under = |path: &str, dir: &str| {
+        dir.is_empty()
+            || path
+                .strip_prefix(dir)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    };
+    let scoped = dirs
+        .into_iter()
+        .filter_map(|dir| {
+            if is_under(&dir, scope) {
+                Some(dir)
+            } else if is_under(scope, &dir) {
+                Some(scope.to_string())
+            } else {
+                None
+            }
+        })
+        .collect();
+    Some(scoped)
+}
+
+/// Names of the `--submodule` submodules (`None` without any)
+fn submodule_names(root: &Path, names: &[String]) -> Result<Option<Vec<String>>> {
+    if names.is_empty() {
+        return Ok(None);
+    }
+    let submodules = find_submodules(root);
+    let mut found = Vec::new();
+    for name in names {
+        match submodules.iter().find(|submodule| submodule.is_named(name)) {
+            Some(submodule) => found.push(submodule.name.clone()),
+            None => {
+                let known: Vec<&str> = submodules.iter().map(|s| s.name.as_str()).collect();
+                anyhow::bail!(
+                    "No submodule named '{}' in {} (submodules: {})",
+                    name,
+                    root.display(),
+                    if known.is_empty() {
+                        "none".to_string()
+                    } else {
+                        known.join(", ")
+                    }
+                );
+            }
+        }
+    }
+    Ok(Some(found))
+}
+
+fn print_result(
+    mut result: SearchResult,
+    args: &SearchArgs,
+    format: OutputFormat,
+    display: &DisplayOptions,
+) -> Result<()> {
+    if args.facets {
+        result.facets = Some(result.facets());
+    }
+
+    if args.tree || args.heatmap.is_some() {
+        let dimension =
+            HeatmapDimension::parse(args.heatmap.as_deref().unwrap_or("dir"), args.depth)?;
+        let tree_hits = apply_tree_filters(&result.hits, args.tree_min_score, args.tree_top);
+        let output = format_tree_heatmap(&tree_hits, dimension);
         print!("{}", output);
         return Ok(());
     }
 
     // Output results
+    let format = if args.fields.is_empty() {
+        format
+    } else {
+        OutputFormat::Json
+    };
     let output = match format {
-        OutputFormat::Ai => result.format_ai(),
-        OutputFormat::Json => result.format_json(),
-        OutputFormat::Pretty => result.format_pretty(show_scores),
+        OutputFormat::Ai => result.format_ai_with(display),
+        OutputFormat::Json => result.format_json_fields(&args.fields)?,
+        OutputFormat::Pretty => result.format_pretty_with(args.scores, display),
     };
 
     print!("{}", output);
@@ -91,33 +441,77 @@ pub fn run(
     Ok(())
 }
 
-fn apply_filters(result: &mut SearchResult, extensions: &[String], paths: &[String]) {
-    if extensions.is_empty() && paths.is_empty() {
-        return;
-    }
+/// `--kind` / `--not-kind` as search filters
+fn kind_filters(args: &SearchArgs) -> Result<SearchFilters> {
+    let parse = |kinds: &[String]| -> Result<Option<Vec<FileKind>>> {
+        if kinds.is_empty() {
+            return Ok(None);
+        }
+        let kinds = kinds.iter().map(|k| k.parse()).collect::<Result<_, _>>()?;
+        Ok(Some(kinds))
+    };
+    Ok(SearchFilters {
+        kinds: parse(&args.kinds)?,
+        exclude_kinds: parse(&args.not_kinds)?,
+        ..Default::default()
+    })
+}
 
-    if !extensions.is_empty() {
-        result.hits.retain(|hit| {
-            Path::new(&hit.path)
-                .extension()
-                .map(|ext| {
-                    extensions
-                        .iter()
-                        .any(|allowed| allowed.eq_ignore_ascii_case(&ext.to_string_lossy()))
-                })
-                .unwrap_or(false)
-        });
+/// Merge per-workspace results, prefixing paths with the workspace label (absolute paths
+/// are kept; the merged result has no single workspace root)
+fn merge_results(results: Vec<(String, SearchResult)>, limit: usize) -> SearchResult {
+    let mut merged = SearchResult::default();
+    for (label, result) in results {
+        let prefix = |path: &str| format!("{}/{}", label.trim_end_matches('/'), path);
+        merged
+            .hits
+            .extend(result.hits.into_iter().map(|hit| SearchHit {
+                path: prefix(&hit.path),
+                ..hit
+            }));
+        merged
+            .matched_paths
+            .extend(result.matched_paths.iter().map(|path| prefix(path)));
+        merged.total += result.total;
+        merged.query_time_ms += result.query_time_ms;
+        // Report the stalest index
+        if let Some(index) = result.index {
+            if merged
+                .index
+                .as_ref()
+                .is_none_or(|m| m.age_secs < index.age_secs)
+            {
+                merged.index = Some(index);
+            }
+        }
+        if let Some(incomplete) = result.incomplete {
+            merged
+                .incomplete
+                .get_or_insert(format!("{}: {}", label, incomplete));
+        }
+        for suggestion in result.suggestions {
+            if !merged.suggestions.contains(&suggestion) {
+                merged.suggestions.push(suggestion);
+            }
+        }
     }
-
-    if !paths.is_empty() {
-        result.hits.retain(|hit| {
-            paths
-                .iter()
-                .any(|pattern| hit.path.starts_with(pattern) || hit.path.contains(pattern))
-        });
+    // Suggestions only help when no workspace had a hit
+    if !merged.hits.is_empty() {
+        merged.suggestions.clear();
     }
 
-    result.total = result.hits.len();
+    merged.hits.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    merged.hits.truncate(limit);
+    count_hits(&mut merged);
+    merged
+}
+
+/// Recompute the text/semantic breakdown after hits were filtered or merged
+fn count_hits(result: &mut SearchResult) {
     result.text_hits = result
         .hits
         .iter()
@@ -172,6 +566,7 @@ mod tests {
             is_chunk: false,
             doc_id: path.to_string(),
             match_type,
+            ..Default::default()
         }
     }
 
@@ -182,39 +577,30 @@ mod tests {
             query_time_ms: 0,
             text_hits: 0,
             semantic_hits: 0,
+            ..Default::default()
         }
     }
 
     #[test]
-    fn filters_by_extension() {
-        let mut result = make_result(vec![
-            make_hit("src/main.rs", MatchType::Text, 0.5),
-            make_hit("src/lib.ts", MatchType::Semantic, 0.5),
-        ]);
-
-        let extensions = vec!["rs".to_string()];
-        apply_filters(&mut result, &extensions, &[]);
-
-        assert_eq!(result.hits.len(), 1);
-        assert_eq!(result.hits[0].path, "src/main.rs");
-        assert_eq!(result.text_hits, 1);
-        assert_eq!(result.semantic_hits, 0);
-    }
-
-    #[test]
-    fn filters_by_path_pattern() {
-        let mut result = make_result(vec![
-            make_hit("src/main.rs", MatchType::Hybrid, 0.5),
-            make_hit("tests/test.rs", MatchType::Semantic, 0.5),
+    fn merges_workspaces_by_score() {
+        let backend = make_result(vec![make_hit("src/db.rs", MatchType::Text, 0.4)]);
+        let frontend = make_result(vec![
+            make_hit("src/app.ts", MatchType::Text, 0.9),
+            make_hit("src/db.ts", MatchType::Text, 0.1),
         ]);
 
-        let paths = vec!["tests".to_string()];
-        apply_filters(&mut result, &[], &paths);
+        let merged = merge_results(
+            vec![
+                ("@backend".to_string(), backend),
+                ("@frontend".to_string(), frontend),
+            ],
+            2,
+        );
 
-        assert_eq!(result.hits.len(), 1);
-        assert_eq!(result.hits[0].path, "tests/test.rs");
-        assert_eq!(result.semantic_hits, 1);
-        assert_eq!(result.text_hits, 0);
+        let paths: Vec<&str> = merged.hits.iter().map(|h| h.path.as_str()).collect();
+        assert_eq!(paths, vec!["@frontend/src/app.ts", "@backend/src/db.rs"]);
+        assert_eq!(merged.total, 3);
+        assert_eq!(merged.text_hits, 2);
     }
 
     #[test]
@@ -222,7 +608,9 @@ mod tests {
         let hits = vec![
             make_hit("src/high.rs", MatchType::Text, 0.02),
             make_hit("src/low.rs", MatchType::Text, 0.006),
-            make_hit("src/mid.rs", MatchType::Text, 0.015),
+            // (shown as 54%; the threshold is on the displayed score, not relative to
+            // the best hit)
+            make_hit("src/mid.rs", MatchType::Text, 0.018),
         ];
 
         let filtered = apply_tree_filters(&hits, Some(0.5), Some(2));
@@ -231,4 +619,18 @@ mod tests {
         assert_eq!(filtered[0].path, "src/high.rs");
         assert_eq!(filtered[1].path, "src/mid.rs");
     }
+
+    #[test]
+    fn scope_narrows_package_dirs() {
+        let dirs = |dirs: &[&str]| Some(dirs.iter().map(|d| d.to_string()).collect::<Vec<_>>());
+        assert_eq!(scope_dirs(None, None), None);
+        assert_eq!(scope_dirs(None, Some("src/api")), dirs(&["src/api"]));
+        assert_eq!(
+            scope_dirs(
+                dirs(&["", "src", "src/api/v2", "src/apiary", "web"]),
+                Some("src/api")
+            ),
+            dirs(&["src/api", "src/api", "src/api/v2"])
+        );
+    }
 }
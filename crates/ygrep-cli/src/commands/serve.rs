@@ -0,0 +1,24 @@
+use anyhow::Result;
+use std::path::Path;
+use ygrep_core::{Config, Workspace, YgrepError};
+
+use crate::error::CliError;
+use crate::serve;
+
+/// Serve searches of the workspace, and of `server.workspaces` (every indexed workspace
+/// with `all`), until the process is stopped
+pub fn run(workspace_path: &Path, addr: &str, watch: bool, all: bool) -> Result<()> {
+    let config = Config::load();
+    let mut roots = config.server.workspaces.clone();
+    if all {
+        roots.extend(serve::indexed_workspaces(&config));
+    }
+    let workspace = match Workspace::open_with_config(workspace_path, config.clone()) {
+        Ok(ws) => Some(ws),
+        Err(e @ YgrepError::IndexOutdated(_)) => return Err(e.into()),
+        // Serving other workspaces doesn't need this one indexed
+        Err(_) if !roots.is_empty() && !watch => None,
+        Err(_) => return Err(CliError::not_indexed(workspace_path, "serve this workspace").into()),
+    };
+    serve::serve(workspace, roots, addr, watch, &config)
+}
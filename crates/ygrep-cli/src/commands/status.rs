@@ -1,8 +1,11 @@
 use anyhow::Result;
 use std::path::Path;
+use ygrep_core::index::HistoryEntry;
 use ygrep_core::Workspace;
 
-pub fn run(workspace_path: &Path, detailed: bool) -> Result<()> {
+use super::indexes::format_size;
+
+pub fn run(workspace_path: &Path, detailed: bool, history: bool) -> Result<()> {
     println!("ygrep status");
     println!("============");
     println!();
@@ -34,6 +37,11 @@ pub fn run(workspace_path: &Path, detailed: bool) -> Result<()> {
                 // TODO: Add more detailed stats from index
                 println!("  (detailed stats coming in future version)");
             }
+
+            if history {
+                println!();
+                print_history(&workspace.index_history());
+            }
         }
         Err(_) => {
             println!("Indexed: no");
@@ -46,3 +54,67 @@ pub fn run(workspace_path: &Path, detailed: bool) -> Result<()> {
 
     Ok(())
 }
+
+/// Index runs with their size and duration, and the change since the previous run
+fn print_history(entries: &[HistoryEntry]) {
+    if entries.is_empty() {
+        println!("No index history yet (it's recorded by each `ygrep index` run).");
+        return;
+    }
+
+    println!("Index history:");
+    println!(
+        "  {:<20} {:>8} {:>8} {:>10} {:>10} {:>10}",
+        "Indexed at", "Files", "Embedded", "Size", "Growth", "Duration"
+    );
+    let mut previous: Option<&HistoryEntry> = None;
+    for entry in entries {
+        let timestamp = chrono::DateTime::parse_from_rfc3339(&entry.timestamp)
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| entry.timestamp.clone());
+        let growth = match previous {
+            Some(previous) => format_growth(previous.size, entry.size),
+            None => "-".to_string(),
+        };
+        println!(
+            "  {:<20} {:>8} {:>8} {:>10} {:>10} {:>10}",
+            timestamp,
+            entry.files,
+            entry.embedded,
+            format_size(entry.size),
+            growth,
+            format_duration(entry.duration_ms)
+        );
+        previous = Some(entry);
+    }
+
+    if let (Some(first), Some(last)) = (entries.first(), entries.last()) {
+        if entries.len() > 1 {
+            println!();
+            println!(
+                "  Since first run: {:+} files, {} -> {}, duration {} -> {}",
+                last.files as i64 - first.files as i64,
+                format_size(first.size),
+                format_size(last.size),
+                format_duration(first.duration_ms),
+                format_duration(last.duration_ms)
+            );
+        }
+    }
+}
+
+fn format_growth(before: u64, after: u64) -> String {
+    if after >= before {
+        format!("+{}", format_size(after - before))
+    } else {
+        format!("-{}", format_size(before - after))
+    }
+}
+
+fn format_duration(ms: u64) -> String {
+    if ms >= 60_000 {
+        format!("{}m{:02}s", ms / 60_000, ms / 1000 % 60)
+    } else {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    }
+}
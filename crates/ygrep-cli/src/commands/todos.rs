@@ -0,0 +1,39 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use ygrep_core::search::TodoGrouping;
+use ygrep_core::Workspace;
+
+use crate::OutputFormat;
+
+/// Ways `ygrep todos --by` groups markers
+pub const TODO_GROUPINGS: [&str; 2] = ["dir", "author"];
+
+/// List the TODO/FIXME markers of the workspace
+pub fn run(
+    workspace_path: &Path,
+    markers: &[String],
+    by: &str,
+    depth: usize,
+    limit: usize,
+    format: OutputFormat,
+) -> Result<()> {
+    let workspace = Workspace::open(workspace_path).with_context(|| {
+        format!(
+            "No index for {} (run `ygrep index` first)",
+            workspace_path.display()
+        )
+    })?;
+    let grouping = match by {
+        "author" => TodoGrouping::Author,
+        _ => TodoGrouping::Directory(depth),
+    };
+    let report = workspace.find_todos(markers, grouping, limit)?;
+
+    let output = match format {
+        OutputFormat::Ai => report.format_ai(),
+        OutputFormat::Json => report.format_json(),
+        OutputFormat::Pretty => report.format_pretty(),
+    };
+    print!("{}", output);
+    Ok(())
+}
@@ -1,36 +1,57 @@
 use anyhow::{Context, Result};
 use std::path::Path;
-use ygrep_core::{WatchEvent, Workspace};
+use ygrep_core::{Config, WatchEvent, Workspace, YgrepError};
 
-pub fn run(workspace_path: &Path) -> Result<()> {
+use crate::error::CliError;
+
+/// Files whose embeddings may wait at once in semantic mode before saves wait for them
+const EMBEDDING_QUEUE_CAPACITY: usize = 1024;
+
+pub fn run(workspace_path: &Path, throttle: bool) -> Result<()> {
     eprintln!("Opening workspace {}...", workspace_path.display());
 
+    let mut config = Config::load();
+    config.indexer.background_priority |= throttle;
+
     // Open existing workspace (fails if not indexed)
-    let workspace = match Workspace::open(workspace_path) {
+    let workspace = match Workspace::open_with_config(workspace_path, config) {
         Ok(ws) => ws,
-        Err(_) => {
-            eprintln!("Workspace not indexed: {}", workspace_path.display());
-            eprintln!();
-            eprintln!("To watch this workspace, first index it:");
-            eprintln!("  ygrep index              # Text-only (fast)");
-            eprintln!("  ygrep index --semantic   # With semantic search (slower, better results)");
-            std::process::exit(1);
-        }
+        Err(e @ YgrepError::IndexOutdated(_)) => return Err(e.into()),
+        Err(_) => return Err(CliError::not_indexed(workspace_path, "watch this workspace").into()),
     };
+    watch(&workspace)
+}
 
+/// Re-index files of an open workspace as they change, until the watcher stops
+pub(crate) fn watch(workspace: &Workspace) -> Result<()> {
     // Read the stored semantic flag
     let use_semantic = workspace.stored_semantic_flag().unwrap_or(false);
 
     let mode = if use_semantic { "semantic" } else { "text" };
-    eprintln!("Starting file watcher (mode: {})...", mode);
+    let priority = if workspace.indexer_config().background_priority {
+        ", throttled"
+    } else {
+        ""
+    };
+    eprintln!("Starting file watcher (mode: {}{})...", mode, priority);
     eprintln!("Press Ctrl+C to stop.\n");
 
+    // Same rules as `ygrep index` (extensions, index_filenames, ignore patterns)
+    let mut walker = workspace
+        .file_walker()
+        .context("Failed to load indexing rules")?;
+    walker.load_ignore_files();
+
     let mut watcher = workspace
         .create_watcher()
         .context("Failed to create file watcher")?;
 
     watcher.start().context("Failed to start file watcher")?;
 
+    // In semantic mode the text index is updated right away and embeddings follow on a
+    // worker thread
+    let queue = use_semantic.then(|| workspace.embedding_queue(EMBEDDING_QUEUE_CAPACITY));
+
     // Create tokio runtime for async event handling
     let rt = tokio::runtime::Runtime::new().context("Failed to create async runtime")?;
 
@@ -43,8 +64,12 @@ pub fn run(workspace_path: &Path) -> Result<()> {
             match watcher.next_event().await {
                 Some(WatchEvent::Changed(path)) => {
                     // Check if it's a text file we should index
-                    if is_indexable(&path) {
-                        match workspace.index_file_with_options(&path, use_semantic) {
+                    if walker.accepts(&path) {
+                        let result = match &queue {
+                            Some(queue) => workspace.index_file_queued(&path, queue),
+                            None => workspace.index_file(&path),
+                        };
+                        match result {
                             Ok(()) => {
                                 changed_count += 1;
                                 eprintln!("  [+] {}", path.display());
@@ -57,6 +82,9 @@ pub fn run(workspace_path: &Path) -> Result<()> {
                     }
                 }
                 Some(WatchEvent::Deleted(path)) => {
+                    if let Some(queue) = &queue {
+                        queue.forget(&path);
+                    }
                     match workspace.delete_file(&path) {
                         Ok(()) => {
                             deleted_count += 1;
@@ -74,6 +102,36 @@ pub fn run(workspace_path: &Path) -> Result<()> {
                 Some(WatchEvent::DirDeleted(path)) => {
                     eprintln!("  [d] {} (directory removed)", path.display());
                 }
+                Some(WatchEvent::IgnoreChanged(path)) => {
+                    eprintln!("  [i] {} (ignore rules changed)", path.display());
+                    let previous = match workspace.file_walker() {
+                        Ok(current) => {
+                            current.load_ignore_files();
+                            std::mem::replace(&mut walker, current)
+                        }
+                        Err(e) => {
+                            error_count += 1;
+                            eprintln!("  [!] {} - {}", path.display(), e);
+                            continue;
+                        }
+                    };
+                    match workspace.reconcile_ignored(&previous) {
+                        Ok(changes) => {
+                            for relative in &changes.removed {
+                                eprintln!("  [-] {} (now ignored)", relative);
+                            }
+                            for relative in &changes.added {
+                                eprintln!("  [+] {} (no longer ignored)", relative);
+                            }
+                            deleted_count += changes.removed.len() as u64;
+                            changed_count += changes.added.len() as u64;
+                        }
+                        Err(e) => {
+                            error_count += 1;
+                            eprintln!("  [!] Failed to apply {}: {}", path.display(), e);
+                        }
+                    }
+                }
                 Some(WatchEvent::Error(e)) => {
                     error_count += 1;
                     eprintln!("  [!] Watch error: {}", e);
@@ -85,10 +143,16 @@ pub fn run(workspace_path: &Path) -> Result<()> {
             }
 
             // Print periodic stats
-            if (changed_count + deleted_count) % 100 == 0 && (changed_count + deleted_count) > 0 {
+            if (changed_count + deleted_count).is_multiple_of(100)
+                && (changed_count + deleted_count) > 0
+            {
+                let pending = queue
+                    .as_ref()
+                    .map(|queue| format!(", {} awaiting embeddings", queue.pending()))
+                    .unwrap_or_default();
                 eprintln!(
-                    "\n--- Stats: {} indexed, {} deleted, {} errors ---\n",
-                    changed_count, deleted_count, error_count
+                    "\n--- Stats: {} indexed, {} deleted, {} errors{} ---\n",
+                    changed_count, deleted_count, error_count, pending
                 );
             }
         }
@@ -99,117 +163,11 @@ pub fn run(workspace_path: &Path) -> Result<()> {
         );
     });
 
-    Ok(())
-}
-
-/// Check if a file should be indexed (simple extension check)
-fn is_indexable(path: &Path) -> bool {
-    const TEXT_EXTENSIONS: &[&str] = &[
-        "rs",
-        "py",
-        "js",
-        "ts",
-        "jsx",
-        "tsx",
-        "mjs",
-        "mts",
-        "cjs",
-        "cts",
-        "go",
-        "rb",
-        "php",
-        "java",
-        "c",
-        "cpp",
-        "cc",
-        "h",
-        "hpp",
-        "hh",
-        "cs",
-        "swift",
-        "kt",
-        "scala",
-        "clj",
-        "ex",
-        "exs",
-        "erl",
-        "hs",
-        "ml",
-        "fs",
-        "r",
-        "jl",
-        "lua",
-        "pl",
-        "pm",
-        "sh",
-        "bash",
-        "zsh",
-        "fish",
-        "ps1",
-        "bat",
-        "cmd",
-        "html",
-        "htm",
-        "css",
-        "scss",
-        "sass",
-        "less",
-        "xml",
-        "json",
-        "yaml",
-        "yml",
-        "toml",
-        "twig",
-        "blade",
-        "ejs",
-        "hbs",
-        "handlebars",
-        "mustache",
-        "pug",
-        "jade",
-        "erb",
-        "haml",
-        "njk",
-        "nunjucks",
-        "jinja",
-        "jinja2",
-        "liquid",
-        "eta",
-        "md",
-        "markdown",
-        "rst",
-        "txt",
-        "csv",
-        "sql",
-        "graphql",
-        "gql",
-        "dockerfile",
-        "makefile",
-        "cmake",
-        "gradle",
-        "pom",
-        "ini",
-        "conf",
-        "cfg",
-        "vue",
-        "svelte",
-        "astro",
-        "tf",
-        "hcl",
-        "nix",
-        "proto",
-        "thrift",
-        "avsc",
-        "gitignore",
-        "gitattributes",
-        "editorconfig",
-        "env",
-    ];
-
-    if let Some(ext) = path.extension() {
-        let ext_lower = ext.to_string_lossy().to_lowercase();
-        TEXT_EXTENSIONS.contains(&ext_lower.as_str())
-    } else {
-        false
+    if let Some(queue) = queue {
+        if queue.pending() > 0 {
+            eprintln!("Embedding {} remaining files...", queue.pending());
+        }
+        queue.finish();
     }
+    Ok(())
 }
@@ -0,0 +1,72 @@
+use anyhow::Result;
+use std::path::Path;
+use ygrep_core::registry::WorkspaceRegistry;
+use ygrep_core::Config;
+
+use crate::workspace::{get_index_path_for_hash, hash_workspace_path, index_exists};
+
+/// Register a workspace under a name
+pub fn add(name: &str, path: &Path) -> Result<()> {
+    let data_dir = Config::load().indexer.data_dir;
+    let mut registry = WorkspaceRegistry::load(&data_dir)?;
+    let root = registry.add(name, path)?;
+    registry.save(&data_dir)?;
+
+    println!(
+        "Added @{} -> {}",
+        name.trim_start_matches('@'),
+        root.display()
+    );
+    if !is_indexed(&data_dir, &root) {
+        println!("  (not indexed yet; run `ygrep index {}`)", root.display());
+    }
+    Ok(())
+}
+
+/// List registered workspaces
+pub fn list() -> Result<()> {
+    let data_dir = Config::load().indexer.data_dir;
+    let registry = WorkspaceRegistry::load(&data_dir)?;
+
+    let workspaces: Vec<_> = registry.iter().collect();
+    if workspaces.is_empty() {
+        println!("No workspaces registered. Add one with `ygrep workspaces add <name> <path>`.");
+        return Ok(());
+    }
+
+    println!("# {} workspaces\n", workspaces.len());
+    for (name, root) in workspaces {
+        let status = if !root.exists() {
+            "  [missing]"
+        } else if !is_indexed(&data_dir, root) {
+            "  [not indexed]"
+        } else {
+            ""
+        };
+        println!("@{}  {}{}", name, root.display(), status);
+    }
+    Ok(())
+}
+
+/// Forget a registered workspace (the index itself is kept)
+pub fn remove(name: &str) -> Result<()> {
+    let data_dir = Config::load().indexer.data_dir;
+    let mut registry = WorkspaceRegistry::load(&data_dir)?;
+    let name = name.trim_start_matches('@');
+
+    match registry.remove(name) {
+        Some(root) => {
+            registry.save(&data_dir)?;
+            println!("Removed @{} ({})", name, root.display());
+        }
+        None => println!("Workspace not found: @{}", name),
+    }
+    Ok(())
+}
+
+fn is_indexed(data_dir: &Path, root: &Path) -> bool {
+    index_exists(&get_index_path_for_hash(
+        data_dir,
+        &hash_workspace_path(root),
+    ))
+}
@@ -0,0 +1,402 @@
+//! Per-user search daemon (`ygrep daemon`)
+//!
+//! The daemon keeps workspaces open between searches, with their index readers, vector
+//! indexes and warm semantic model. While it runs, searches are sent to it over a Unix
+//! socket in the data directory (one JSON line each way); without it (or with
+//! `YGREP_NO_DAEMON` set) the CLI searches in-process as usual.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use ygrep_core::search::SearchResult;
+use ygrep_core::Config;
+
+use crate::SearchArgs;
+
+/// Directory of the socket inside the data directory, only accessible to its owner
+const SOCKET_DIR: &str = "daemon";
+
+/// Socket file inside `SOCKET_DIR`
+const SOCKET_FILE: &str = "daemon.sock";
+
+/// Set to always search in-process
+const NO_DAEMON_ENV: &str = "YGREP_NO_DAEMON";
+
+/// Most workspaces kept open at once; the least recently used is closed first
+#[cfg(unix)]
+const MAX_WORKSPACES: usize = 16;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    Search {
+        workspace: PathBuf,
+        query: String,
+        args: Box<SearchArgs>,
+        /// The client's config (with its `.ygrep.toml` and command-line overrides)
+        config: Box<Config>,
+    },
+    Status,
+    Stop,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Response {
+    Result { result: Box<SearchResult> },
+    Error { message: String },
+    Status(DaemonStatus),
+    Stopped,
+}
+
+/// What a running daemon reports about itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub uptime_secs: u64,
+    /// Roots of the workspaces it has open
+    pub workspaces: Vec<PathBuf>,
+}
+
+/// The daemon's socket for a config's data directory
+pub fn socket_path(config: &Config) -> PathBuf {
+    config.indexer.data_dir.join(SOCKET_DIR).join(SOCKET_FILE)
+}
+
+/// Search through a running daemon; `None` if there is none, or if it couldn't search
+/// (the caller then searches itself and reports errors as usual)
+pub fn search(
+    workspace: &Path,
+    query: &str,
+    args: &SearchArgs,
+    config: &Config,
+) -> Option<SearchResult> {
+    if std::env::var_os(NO_DAEMON_ENV).is_some() {
+        return None;
+    }
+    let request = Request::Search {
+        workspace: workspace.to_path_buf(),
+        query: query.to_string(),
+        args: Box::new(args.clone()),
+        config: Box::new(config.clone()),
+    };
+    match send(&socket_path(config), &request) {
+        Ok(Response::Result { result }) => Some(*result),
+        Ok(Response::Error { message }) => {
+            tracing::debug!("Daemon search failed: {}", message);
+            None
+        }
+        Ok(response) => {
+            tracing::debug!("Unexpected daemon response: {:?}", response);
+            None
+        }
+        Err(e) => {
+            tracing::debug!("No daemon: {}", e);
+            None
+        }
+    }
+}
+
+/// Status of the running daemon, if any
+pub fn status(config: &Config) -> Option<DaemonStatus> {
+    match send(&socket_path(config), &Request::Status) {
+        Ok(Response::Status(status)) => Some(status),
+        _ => None,
+    }
+}
+
+/// Ask the running daemon to exit; false if none was running
+pub fn stop(config: &Config) -> bool {
+    matches!(
+        send(&socket_path(config), &Request::Stop),
+        Ok(Response::Stopped)
+    )
+}
+
+#[cfg(unix)]
+fn send(socket: &Path, request: &Request) -> Result<Response> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(socket)?;
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    Ok(serde_json::from_str(&response)?)
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &Path, _request: &Request) -> Result<Response> {
+    anyhow::bail!("The ygrep daemon needs Unix domain sockets")
+}
+
+#[cfg(not(unix))]
+pub fn serve(_config: &Config) -> Result<()> {
+    anyhow::bail!("The ygrep daemon isn't supported on this platform")
+}
+
+/// Run the daemon until `ygrep daemon stop`
+#[cfg(unix)]
+pub fn serve(config: &Config) -> Result<()> {
+    use anyhow::Context;
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+    use std::sync::Arc;
+
+    let socket = socket_path(config);
+    if socket.exists() {
+        if status(config).is_some() {
+            anyhow::bail!("A ygrep daemon is already running ({})", socket.display());
+        }
+        // Left behind by a daemon that didn't exit cleanly
+        std::fs::remove_file(&socket)?;
+    }
+    // Searches can return any indexed content, so only this user may connect: the socket
+    // is created in a directory no one else can enter, before it accepts anyone
+    let socket_dir = socket.parent().unwrap_or(&config.indexer.data_dir);
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(socket_dir)?;
+    std::fs::set_permissions(socket_dir, std::fs::Permissions::from_mode(0o700))?;
+    let listener = UnixListener::bind(&socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))?;
+    eprintln!("ygrep daemon listening on {}", socket.display());
+
+    let daemon = Arc::new(server::Daemon::new(socket.clone()));
+    for stream in listener.incoming() {
+        if daemon.is_stopping() {
+            break;
+        }
+        match stream {
+            Ok(stream) => {
+                let daemon = Arc::clone(&daemon);
+                std::thread::spawn(move || daemon.handle(stream));
+            }
+            Err(e) => tracing::warn!("Daemon connection failed: {}", e),
+        }
+    }
+
+    std::fs::remove_file(&socket)?;
+    eprintln!("ygrep daemon stopped.");
+    Ok(())
+}
+
+#[cfg(unix)]
+mod server {
+    use parking_lot::Mutex;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Instant, SystemTime};
+    use ygrep_core::{Config, Workspace};
+
+    use super::{DaemonStatus, Request, Response, MAX_WORKSPACES};
+    use crate::commands::search::search_in;
+
+    /// An open workspace, for one config
+    struct OpenWorkspace {
+        root: PathBuf,
+        config: String,
+        /// When the index was last written (a newer one is reopened)
+        indexed_at: Option<SystemTime>,
+        last_used: Instant,
+        workspace: Arc<Workspace>,
+    }
+
+    pub(super) struct Daemon {
+        socket: PathBuf,
+        started: Instant,
+        stopping: AtomicBool,
+        workspaces: Mutex<Vec<OpenWorkspace>>,
+    }
+
+    impl Daemon {
+        pub fn new(socket: PathBuf) -> Self {
+            Self {
+                socket,
+                started: Instant::now(),
+                stopping: AtomicBool::new(false),
+                workspaces: Mutex::new(Vec::new()),
+            }
+        }
+
+        pub fn is_stopping(&self) -> bool {
+            self.stopping.load(Ordering::SeqCst)
+        }
+
+        /// Answer one request on a connection
+        pub fn handle(&self, stream: UnixStream) {
+            let mut line = String::new();
+            let mut reader = BufReader::new(&stream);
+            if let Err(e) = reader.read_line(&mut line) {
+                tracing::debug!("Failed to read daemon request: {}", e);
+                return;
+            }
+
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => self.respond(request),
+                Err(e) => Response::Error {
+                    message: format!("Invalid request: {}", e),
+                },
+            };
+            let stopped = matches!(response, Response::Stopped);
+
+            let mut stream = &stream;
+            let written = serde_json::to_string(&response)
+                .map_err(std::io::Error::other)
+                .and_then(|json| writeln!(stream, "{}", json));
+            if let Err(e) = written {
+                tracing::debug!("Failed to answer daemon request: {}", e);
+            }
+
+            if stopped {
+                // Wake the accept loop so it sees the flag
+                let _ = UnixStream::connect(&self.socket);
+            }
+        }
+
+        fn respond(&self, request: Request) -> Response {
+            match request {
+                Request::Search {
+                    workspace,
+                    query,
+                    args,
+                    config,
+                } => {
+                    let result = self
+                        .workspace(&workspace, *config)
+                        .and_then(|ws| search_in(&ws, &query, &args));
+                    match result {
+                        Ok(result) => Response::Result {
+                            result: Box::new(result),
+                        },
+                        Err(e) => Response::Error {
+                            message: format!("{:#}", e),
+                        },
+                    }
+                }
+                Request::Status => Response::Status(DaemonStatus {
+                    pid: std::process::id(),
+                    uptime_secs: self.started.elapsed().as_secs(),
+                    workspaces: self
+                        .workspaces
+                        .lock()
+                        .iter()
+                        .map(|open| open.root.clone())
+                        .collect(),
+                }),
+                Request::Stop => {
+                    self.stopping.store(true, Ordering::SeqCst);
+                    Response::Stopped
+                }
+            }
+        }
+
+        /// The open workspace for a root and config, opening (or reopening, after the
+        /// index was rebuilt) it as needed
+        fn workspace(&self, root: &Path, config: Config) -> anyhow::Result<Arc<Workspace>> {
+            let key = serde_json::to_string(&config)?;
+            let indexed_at = Workspace::index_dir(root, &config)
+                .ok()
+                .and_then(|dir| std::fs::metadata(dir.join("workspace.json")).ok())
+                .and_then(|metadata| metadata.modified().ok());
+
+            {
+                let mut workspaces = self.workspaces.lock();
+                if let Some(open) = workspaces
+                    .iter_mut()
+                    .find(|open| open.root == root && open.config == key)
+                {
+                    if open.indexed_at == indexed_at {
+                        open.last_used = Instant::now();
+                        return Ok(Arc::clone(&open.workspace));
+                    }
+                }
+            }
+
+            // Opening can take a while (vector index), so other searches go on meanwhile
+            let workspace = Arc::new(Workspace::open_with_config(root, config)?);
+            let mut workspaces = self.workspaces.lock();
+            workspaces.retain(|open| !(open.root == root && open.config == key));
+            if workspaces.len() >= MAX_WORKSPACES {
+                if let Some(oldest) = workspaces
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, open)| open.last_used)
+                    .map(|(i, _)| i)
+                {
+                    workspaces.remove(oldest);
+                }
+            }
+            workspaces.push(OpenWorkspace {
+                root: root.to_path_buf(),
+                config: key,
+                indexed_at,
+                last_used: Instant::now(),
+                workspace: Arc::clone(&workspace),
+            });
+            Ok(workspace)
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::Cli;
+    use clap::Parser;
+    use std::time::{Duration, Instant};
+    use tempfile::tempdir;
+    use ygrep_core::Workspace;
+
+    #[test]
+    fn test_daemon_search() -> Result<()> {
+        let temp = tempdir()?;
+        let root = temp.path().join("workspace");
+        std::fs::create_dir_all(&root)?;
+        std::fs::write(root.join("hello.rs"), "fn hello_daemon() {}")?;
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp.path().join("data");
+        Workspace::create_with_config(&root, config.clone())?.index_all()?;
+        let args = Cli::parse_from(["ygrep", "hello_daemon"]).search;
+
+        // No daemon yet: the caller searches itself
+        assert!(search(&root, "hello_daemon", &args, &config).is_none());
+
+        let server_config = config.clone();
+        let server = std::thread::spawn(move || serve(&server_config));
+        let started = Instant::now();
+        while status(&config).is_none() {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // Only this user can reach the socket
+        let mode = |path: &Path| -> Result<u32> {
+            use std::os::unix::fs::PermissionsExt;
+            Ok(std::fs::metadata(path)?.permissions().mode() & 0o777)
+        };
+        let socket = socket_path(&config);
+        assert_eq!(mode(socket.parent().unwrap())?, 0o700);
+        assert_eq!(mode(&socket)?, 0o600);
+
+        let root = std::fs::canonicalize(&root)?;
+        let result = search(&root, "hello_daemon", &args, &config).unwrap();
+        assert_eq!(result.hits[0].path, "hello.rs");
+        assert_eq!(status(&config).unwrap().workspaces, vec![root.clone()]);
+
+        assert!(stop(&config));
+        server.join().unwrap()?;
+        assert!(!socket_path(&config).exists());
+        assert!(search(&root, "hello_daemon", &args, &config).is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+//! Errors reported to the user: prose on stderr, or with `--json` an object on stdout
+//! (`{"error": {"code": "NOT_INDEXED", ...}}`), with an exit code per kind of failure
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use ygrep_core::{Config, Workspace, YgrepError};
+
+use crate::workspace::ResolveError;
+
+/// Help offering to re-link the index the workspace had before it was moved, if one is
+/// found (`Workspace::find_moved_index`)
+pub fn moved_index_help(workspace: &Path) -> Vec<String> {
+    match Workspace::find_moved_index(workspace, &Config::load()) {
+        Ok(Some(moved)) => vec![
+            String::new(),
+            format!(
+                "Found the index of {}, which no longer exists. If the workspace was moved \
+                 or renamed, reuse it instead:",
+                moved.old_root.display()
+            ),
+            "  ygrep indexes relink".to_string(),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// Kind of failure, stable for scripts and agent wrappers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// Unexpected failure (I/O, index corruption, ...)
+    Internal,
+    /// Invalid arguments or configuration
+    InvalidArgument,
+    /// The query could not be parsed (e.g. a bad regex)
+    InvalidQuery,
+    /// The workspace has no index
+    NotIndexed,
+    /// The index was built by an older ygrep and needs `ygrep index --rebuild`
+    IndexOutdated,
+    /// The workspace path or name doesn't exist
+    WorkspaceNotFound,
+}
+
+impl ErrorCode {
+    /// Process exit code (2 matches clap's usage errors)
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorCode::Internal => 1,
+            ErrorCode::InvalidArgument | ErrorCode::InvalidQuery => 2,
+            ErrorCode::NotIndexed => 3,
+            ErrorCode::IndexOutdated => 4,
+            ErrorCode::WorkspaceNotFound => 5,
+        }
+    }
+
+    fn of(error: &YgrepError) -> Self {
+        match error {
+            YgrepError::Config(_) => ErrorCode::InvalidArgument,
+            YgrepError::QueryParse(_) | YgrepError::Search(_) => ErrorCode::InvalidQuery,
+            YgrepError::WorkspaceNotIndexed(_) => ErrorCode::NotIndexed,
+            YgrepError::IndexOutdated(_) => ErrorCode::IndexOutdated,
+            YgrepError::WorkspaceNotFound(_) | YgrepError::InvalidPath(_) => {
+                ErrorCode::WorkspaceNotFound
+            }
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
+/// An error with its code and, for text output, lines of advice
+#[derive(Debug, Clone, Serialize)]
+pub struct CliError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Workspace the error is about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<PathBuf>,
+    /// Printed after the message in text output
+    #[serde(skip)]
+    pub help: Vec<String>,
+}
+
+impl CliError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            workspace: None,
+            help: Vec::new(),
+        }
+    }
+
+    /// The workspace has no index; `action` says what needs one (e.g. "watch this workspace")
+    pub fn not_indexed(workspace: &Path, action: &str) -> Self {
+        let mut help = vec![
+            String::new(),
+            format!("To {}, first index it:", action),
+            "  ygrep index              # Text-only (fast)".to_string(),
+            "  ygrep index --semantic   # With semantic search (slower, better results)"
+                .to_string(),
+        ];
+        help.extend(moved_index_help(workspace));
+        Self {
+            workspace: Some(workspace.to_path_buf()),
+            help,
+            ..Self::new(
+                ErrorCode::NotIndexed,
+                format!("Workspace not indexed: {}", workspace.display()),
+            )
+        }
+    }
+
+    pub fn with_workspace(mut self, workspace: &Path) -> Self {
+        self.workspace = Some(workspace.to_path_buf());
+        self
+    }
+
+    /// Classify any error by the first known error in its chain
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if let Some(e) = cause.downcast_ref::<CliError>() {
+                return e.clone();
+            }
+            if let Some(e) = cause.downcast_ref::<YgrepError>() {
+                let mut cli_error = Self::new(ErrorCode::of(e), format!("{:#}", error));
+                if let YgrepError::WorkspaceNotIndexed(path)
+                | YgrepError::IndexOutdated(path)
+                | YgrepError::WorkspaceNotFound(path) = e
+                {
+                    cli_error.workspace = Some(path.clone());
+                }
+                return cli_error;
+            }
+            if let Some(e) = cause.downcast_ref::<ResolveError>() {
+                return match e {
+                    ResolveError::InvalidPath { path, .. } => {
+                        Self::new(ErrorCode::WorkspaceNotFound, format!("{:#}", error))
+                            .with_workspace(path)
+                    }
+                    ResolveError::NotIndexed { path } => {
+                        Self::new(ErrorCode::NotIndexed, format!("{:#}", error))
+                            .with_workspace(path)
+                    }
+                };
+            }
+        }
+        Self::new(ErrorCode::Internal, format!("{:#}", error))
+    }
+
+    /// Print the error (as JSON on stdout when `json`, else on stderr)
+    pub fn report(&self, json: bool) {
+        if json {
+            #[derive(Serialize)]
+            struct Output<'a> {
+                error: &'a CliError,
+            }
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&Output { error: self }).unwrap_or_default()
+            );
+        } else {
+            eprintln!("Error: {}", self.message);
+            for line in &self.help {
+                eprintln!("{}", line);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_error_chains() {
+        let error = anyhow::Error::from(YgrepError::IndexOutdated(PathBuf::from("/repo")));
+        let error = CliError::from_error(&error.context("Failed to open workspace"));
+        assert_eq!(error.code, ErrorCode::IndexOutdated);
+        assert_eq!(error.code.exit_code(), 4);
+        assert_eq!(error.workspace, Some(PathBuf::from("/repo")));
+        assert!(error
+            .message
+            .starts_with("Failed to open workspace: Index was built"));
+
+        let error = CliError::from_error(&CliError::not_indexed(Path::new("/repo"), "x").into());
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["code"], "NOT_INDEXED");
+        assert_eq!(json["workspace"], "/repo");
+        assert!(json.get("help").is_none());
+
+        let error = CliError::from_error(&anyhow::anyhow!("disk full"));
+        assert_eq!(error.code, ErrorCode::Internal);
+        assert_eq!(error.message, "disk full");
+    }
+
+    #[test]
+    fn unindexed_workspace_exits_3() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("unindexed");
+        std::fs::create_dir_all(&root).unwrap();
+
+        let error = crate::commands::files::run(&root, "lib", 10, false, crate::OutputFormat::Json)
+            .unwrap_err();
+        let error = CliError::from_error(&error);
+        assert_eq!(error.code, ErrorCode::NotIndexed);
+        assert_eq!(error.code.exit_code(), 3);
+        assert_eq!(error.workspace, Some(std::fs::canonicalize(&root).unwrap()));
+    }
+}
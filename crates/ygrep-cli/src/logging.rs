@@ -0,0 +1,95 @@
+//! Logging setup: stderr by default, or a (rotated) log file for long-running `watch`,
+//! `serve` and `daemon` processes, as text or JSON lines, with levels by module
+
+use anyhow::{Context, Result};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::EnvFilter;
+use ygrep_core::config::{LogConfig, LogFormat, LogRotation};
+
+use crate::Cli;
+
+/// Install the global subscriber from the `[log]` config and `--log-file`/`--log-format`
+pub fn init(cli: &Cli) -> Result<()> {
+    let mut config = ygrep_core::Config::load().log;
+    if let Some(file) = &cli.log_file {
+        config.file = Some(file.clone());
+    }
+    if let Some(format) = cli.log_format {
+        config.format = format;
+    }
+
+    let filter = EnvFilter::try_new(filter_directives(&config))
+        .context("Invalid log level in the [log] config")?;
+    match &config.file {
+        Some(file) => {
+            let dir = match file.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => std::path::Path::new("."),
+            };
+            let name = file
+                .file_name()
+                .with_context(|| format!("Invalid log file: {}", file.display()))?;
+            let mut appender = RollingFileAppender::builder()
+                .rotation(match config.rotation {
+                    LogRotation::Never => Rotation::NEVER,
+                    LogRotation::Hourly => Rotation::HOURLY,
+                    LogRotation::Daily => Rotation::DAILY,
+                })
+                .filename_prefix(name.to_string_lossy());
+            if let Some(max_files) = config.max_files {
+                appender = appender.max_log_files(max_files);
+            }
+            let appender = appender
+                .build(dir)
+                .with_context(|| format!("Failed to open log file {}", file.display()))?;
+            install(filter, config.format, appender, false);
+        }
+        None => install(filter, config.format, std::io::stderr, true),
+    }
+    Ok(())
+}
+
+/// `YGREP_DEBUG` raises the base level to debug, keeping the per-module levels
+fn filter_directives(config: &LogConfig) -> String {
+    let debug = std::env::var("YGREP_DEBUG").is_ok();
+    config.directives(debug.then_some("debug"))
+}
+
+fn install<W>(filter: EnvFilter, format: LogFormat, writer: W, ansi: bool)
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directives() {
+        let mut config = LogConfig::default();
+        assert_eq!(config.directives(None), "warn");
+        config
+            .modules
+            .insert("ygrep_core::index".to_string(), "debug".to_string());
+        config
+            .modules
+            .insert("tantivy".to_string(), "error".to_string());
+        assert_eq!(
+            config.directives(Some("info")),
+            "info,tantivy=error,ygrep_core::index=debug"
+        );
+        assert!(EnvFilter::try_new(config.directives(None)).is_ok());
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+}
@@ -1,12 +1,23 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
 mod commands;
+mod daemon;
+mod error;
+mod logging;
 mod output;
+mod serve;
 mod workspace;
 
-use crate::workspace::{discover_parent_indexes, resolve_workspace};
+use ygrep_core::config::LogFormat;
+use ygrep_core::registry::WorkspaceRegistry;
+use ygrep_core::search::{HeatmapDimension, SearchResult};
+use ygrep_core::Config;
+
+use crate::error::{CliError, ErrorCode};
+use crate::workspace::{discover_parent_indexes, resolve_workspace, ResolveError};
 
 #[derive(Parser)]
 #[command(name = "ygrep")]
@@ -18,7 +29,7 @@ Uses literal text matching by default. Special characters work:\n\
 Use -r/--regex for regex patterns: ygrep \"fn\\\\s+main\" -r\n\n\
 Output formats:\n\
   (default)  AI-optimized: path:line (score%) with match indicators\n\
-  --json     Full JSON with metadata\n\
+  --json     Full JSON with metadata (schema: --json-schema)\n\
   --pretty   Human-readable with line numbers and context\n\n\
 Match indicators in default output:\n\
   +  hybrid match (text AND semantic)\n\
@@ -35,6 +46,8 @@ Match indicators in default output:\n\
     ygrep \"auth\" -p src/api -p tests Filter by path substrings\n\
     ygrep \"fn\\\\s+main\" -r            Regex search\n\
     ygrep search \"api\" --json       JSON output\n\
+    ygrep files searcher            Fuzzy-find indexed files (or a glob: \"*.rs\")\n\
+    ygrep workspaces add api ../api Name a workspace, then: ygrep -C @api \"query\"\n\
     ygrep install claude-code       Install for Claude Code\n\n\
 For more info: https://github.com/yetidevworks/ygrep")]
 pub struct Cli {
@@ -44,13 +57,13 @@ pub struct Cli {
     /// Search query (shorthand for `ygrep search <QUERY>`)
     pub query: Option<String>,
 
-    /// Maximum results
-    #[arg(short = 'n', long, default_value = "100")]
-    pub limit: usize,
+    #[command(flatten)]
+    pub search: SearchArgs,
 
-    /// Workspace root (default: current directory)
+    /// Workspace root or registered name (`@backend`); repeat to search several workspaces
+    /// (default: current directory)
     #[arg(short = 'C', long, global = true)]
-    pub workspace: Option<PathBuf>,
+    pub workspace: Vec<PathBuf>,
 
     /// Output as JSON
     #[arg(long, global = true, conflicts_with = "pretty")]
@@ -60,29 +73,32 @@ pub struct Cli {
     #[arg(long, global = true, conflicts_with = "json")]
     pub pretty: bool,
 
-    /// Output aggregated results as a tree heatmap
-    #[arg(long, alias = "heatmap", conflicts_with_all = ["json", "pretty"])]
-    pub tree: bool,
-
-    /// Depth cutoff for tree output (path segments from workspace root)
-    #[arg(long, value_name = "N", requires = "tree")]
-    pub depth: Option<usize>,
-
-    /// Minimum score threshold for tree output (0.0-1.0 == 0-100%)
-    #[arg(long = "tree-min-score", value_name = "SCORE", requires = "tree")]
-    pub tree_min_score: Option<f32>,
-
-    /// Limit tree output to top N hits by score
-    #[arg(long = "tree-top", value_name = "N", requires = "tree")]
-    pub tree_top: Option<usize>,
+    /// Print the JSON Schema of `--json` search results and exit
+    #[arg(long)]
+    pub json_schema: bool,
 
     /// Verbose output
     #[arg(short, long, global = true)]
     pub verbose: bool,
 
-    /// Treat query as regex pattern
-    #[arg(short = 'r', long)]
-    pub regex: bool,
+    /// Write logs to this file instead of stderr (`log.file`)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Log format: text or json (`log.format`)
+    #[arg(long, global = true, value_name = "FORMAT")]
+    pub log_format: Option<LogFormat>,
+}
+
+/// Values accepted by --kind and --not-kind
+const FILE_KINDS: [&str; 5] = ["source", "test", "config", "docs", "generated"];
+
+/// Search options shared by `ygrep <QUERY>` and `ygrep search <QUERY>`
+#[derive(Args, Clone, Debug, Serialize, Deserialize)]
+pub struct SearchArgs {
+    /// Maximum results
+    #[arg(short = 'n', long, default_value = "100")]
+    pub limit: usize,
 
     /// Filter by file extension (e.g., -e rs -e ts)
     #[arg(short = 'e', long = "ext")]
@@ -92,9 +108,197 @@ pub struct Cli {
     #[arg(short = 'p', long = "path")]
     pub paths: Vec<String>,
 
+    /// Only search this Cargo/npm package or Go module, found by name in the workspace's
+    /// manifests (e.g., -P auth-service; repeatable)
+    #[arg(short = 'P', long = "package", value_name = "NAME")]
+    pub packages: Vec<String>,
+
+    /// Only search this git submodule, by its name or path in .gitmodules (repeatable)
+    #[arg(long = "submodule", value_name = "NAME")]
+    pub submodules: Vec<String>,
+
+    /// Only search files under the current directory, when it is a subdirectory of the
+    /// workspace found in a parent directory (or given with -C)
+    #[arg(long)]
+    pub scope_cwd: bool,
+
+    /// The current directory relative to the workspace root, set for --scope-cwd
+    #[arg(skip)]
+    pub scope_dir: Option<String>,
+
+    /// Only files of this kind: source, test, config, docs or generated (repeatable)
+    #[arg(long = "kind", value_name = "KIND", value_parser = FILE_KINDS)]
+    pub kinds: Vec<String>,
+
+    /// Skip files of this kind (e.g., --not-kind generated)
+    #[arg(long = "not-kind", value_name = "KIND", value_parser = FILE_KINDS)]
+    pub not_kinds: Vec<String>,
+
+    /// Treat query as regex pattern instead of literal text
+    #[arg(short = 'r', long)]
+    pub regex: bool,
+
+    /// Show relevance scores
+    #[arg(long)]
+    pub scores: bool,
+
     /// Text-only search (disable semantic search)
     #[arg(long)]
     pub text_only: bool,
+
+    /// Only search files returned by the previous search
+    #[arg(long, conflicts_with = "within")]
+    pub within_last: bool,
+
+    /// Only search files from a saved result set (see --save-as)
+    #[arg(long, value_name = "NAME")]
+    pub within: Option<String>,
+
+    /// Save the files returned by this search under NAME (for --within)
+    #[arg(long = "save-as", value_name = "NAME")]
+    pub save_as: Option<String>,
+
+    /// Show match counts by extension, directory and language (covers all matches)
+    #[arg(long)]
+    pub facets: bool,
+
+    /// Case-sensitive if the query contains uppercase, otherwise case-insensitive
+    #[arg(short = 'S', long)]
+    pub smart_case: bool,
+
+    /// Match identifiers in any case convention (getUserId = get_user_id = GET_USER_ID)
+    #[arg(long)]
+    pub ident: bool,
+
+    /// Read -term and !term in the query as exclusions (`session -test !mock`)
+    #[arg(long)]
+    pub negate: bool,
+
+    /// Regex engine: "default" or "fancy" (fancy-regex: lookarounds/backreferences; needs the
+    /// fancy feature)
+    #[arg(long, value_name = "ENGINE", requires = "regex", value_parser = ["default", "fancy"])]
+    pub engine: Option<String>,
+
+    /// Stop a regex scan after MS milliseconds (regex searches read every document by default)
+    #[arg(long, value_name = "MS", requires = "regex")]
+    pub timeout: Option<u64>,
+
+    /// Stop a regex scan after reading N documents
+    #[arg(long, value_name = "N", requires = "regex")]
+    pub max_scanned: Option<usize>,
+
+    /// Maximum lines per result snippet (default: 10)
+    #[arg(long, value_name = "N")]
+    pub snippet_lines: Option<usize>,
+
+    /// Lines of context before the first matching line (default: 2)
+    #[arg(short = 'B', long, value_name = "N")]
+    pub context_before: Option<usize>,
+
+    /// Lines of context after the first matching line (default: rest of the snippet)
+    #[arg(short = 'A', long, value_name = "N")]
+    pub context_after: Option<usize>,
+
+    /// Snippet lines shown per result with --pretty (default: 3)
+    #[arg(long, value_name = "N")]
+    pub preview_lines: Option<usize>,
+
+    /// Truncate previewed lines after N bytes; 0 disables truncation
+    /// (default: 100, or 80 with --pretty)
+    #[arg(long, value_name = "N")]
+    pub max_line_length: Option<usize>,
+
+    /// Maximum hits from any single file
+    #[arg(long, value_name = "N")]
+    pub max_per_file: Option<usize>,
+
+    /// Approximate token budget for AI output: best hits get full snippets, then
+    /// path-only lines until the budget is spent
+    #[arg(long, value_name = "N")]
+    pub max_tokens: Option<usize>,
+
+    /// Index the workspace (text-only) first if it hasn't been indexed yet
+    #[arg(long)]
+    pub auto_index: bool,
+
+    /// Re-index files changed since the last index (git HEAD and uncommitted files) first
+    #[arg(long)]
+    pub auto_refresh: bool,
+
+    /// Search a workspace that hasn't been indexed by reading its files directly (up to
+    /// `search.scan_max_files`), instead of failing
+    #[arg(long, conflicts_with = "auto_index")]
+    pub fallback_scan: bool,
+
+    /// Search a self-contained index directory (from `ygrep index --output`) read-only,
+    /// instead of the workspace's index in the data directory
+    #[arg(long, value_name = "DIR")]
+    pub index: Option<PathBuf>,
+
+    /// Search a snapshot of the index read-only (`ygrep indexes snapshot`): its name, or
+    /// the newest one indexed by a time (2026-10-09T14:30:00Z, 2026-10-09, or an age
+    /// like 7d)
+    #[arg(long, value_name = "SNAPSHOT|TIME", conflicts_with = "index")]
+    pub at: Option<String>,
+
+    /// Show how each hit was scored (BM25/vector ranks, RRF contributions, boosts)
+    #[arg(long)]
+    pub explain: bool,
+
+    /// Only these hit fields in JSON output, e.g. `path,line_start,score` (leave out
+    /// `snippet` for compact results); implies --json
+    #[arg(long, value_delimiter = ',', value_name = "FIELDS")]
+    pub fields: Vec<String>,
+
+    /// Tell chunk hits' place in their file: `[chunk 3 of 7, lines 120-180]`
+    #[arg(long)]
+    pub chunk_context: bool,
+
+    /// Show each hit with N more lines of its file before and after the snippet
+    #[arg(long, value_name = "N")]
+    pub expand: Option<usize>,
+
+    /// Show workspace statistics after the results (embedding cache hits, misses and
+    /// evictions; the daemon's when it ran the search)
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Expand abbreviations and synonyms (cfg/config, db/database) in hybrid search
+    #[arg(long)]
+    pub synonyms: bool,
+
+    /// Output aggregated results as a tree heatmap (same as `--heatmap dir`)
+    #[arg(long, group = "heatmap_output", conflicts_with_all = ["json", "pretty"])]
+    pub tree: bool,
+
+    /// Output hit counts by directory (the default), extension, file kind or author
+    /// (git blame of each hit's line) as a heatmap
+    #[arg(
+        long,
+        value_name = "BY",
+        num_args = 0..=1,
+        default_missing_value = "dir",
+        value_parser = HeatmapDimension::NAMES,
+        group = "heatmap_output",
+        conflicts_with_all = ["json", "pretty", "tree"]
+    )]
+    pub heatmap: Option<String>,
+
+    /// Depth cutoff for tree output (path segments from workspace root)
+    #[arg(long, value_name = "N", requires = "heatmap_output")]
+    pub depth: Option<usize>,
+
+    /// Minimum score threshold for tree output (0.0-1.0 == 0-100%)
+    #[arg(
+        long = "tree-min-score",
+        value_name = "SCORE",
+        requires = "heatmap_output"
+    )]
+    pub tree_min_score: Option<f32>,
+
+    /// Limit tree output to top N hits by score
+    #[arg(long = "tree-top", value_name = "N", requires = "heatmap_output")]
+    pub tree_top: Option<usize>,
 }
 
 #[derive(Subcommand)]
@@ -104,45 +308,104 @@ pub enum Commands {
         /// Search query (literal text or regex with --regex)
         query: String,
 
-        /// Maximum results
-        #[arg(short = 'n', long, default_value = "100")]
-        limit: usize,
+        #[command(flatten)]
+        args: Box<SearchArgs>,
+    },
 
-        /// Filter by file extension (e.g., -e rs -e ts)
-        #[arg(short = 'e', long = "ext")]
-        extensions: Vec<String>,
+    /// Show how a query is interpreted without running it: index terms, the literal or
+    /// regex check, filters, boosts and whether hybrid search runs
+    ExplainQuery {
+        /// Search query, as for `ygrep search`
+        query: String,
 
-        /// Filter by path prefix/substring (literal match, relative to workspace)
-        #[arg(short = 'p', long = "path")]
-        paths: Vec<String>,
+        #[command(flatten)]
+        args: Box<SearchArgs>,
+    },
 
-        /// Treat query as regex pattern instead of literal text
-        #[arg(short = 'r', long)]
-        regex: bool,
+    /// Find indexed files by name (fuzzy, or a glob when the pattern has `*`, `?` or `[`)
+    Files {
+        /// Fuzzy pattern (e.g. `srchrs`) or glob (e.g. `src/**/*.rs`)
+        pattern: String,
 
-        /// Show relevance scores
+        /// Maximum number of files
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: usize,
+
+        /// Show match scores in --pretty output
         #[arg(long)]
         scores: bool,
+    },
 
-        /// Text-only search (disable semantic search)
-        #[arg(long)]
-        text_only: bool,
+    /// Find where a symbol is defined and used, files ranked by how many files import
+    /// them (see `indexer.reference_counts`)
+    Refs {
+        /// Identifier to look up (matched case-sensitively as a whole word)
+        symbol: String,
+
+        /// Maximum number of files
+        #[arg(short = 'n', long, default_value = "50")]
+        limit: usize,
+    },
 
-        /// Output aggregated results as a tree heatmap
-        #[arg(long, alias = "heatmap", conflicts_with_all = ["json", "pretty"])]
-        tree: bool,
+    /// List TODO/FIXME/HACK/XXX comments (`search.todo_markers`), grouped by directory or
+    /// by author (git blame)
+    Todos {
+        /// Marker to look for instead of the configured ones (repeatable)
+        #[arg(long = "marker", value_name = "WORD")]
+        markers: Vec<String>,
 
-        /// Depth cutoff for tree output (path segments from workspace root)
-        #[arg(long, value_name = "N", requires = "tree")]
-        depth: Option<usize>,
+        /// Group by directory or by the author of the line
+        #[arg(long, default_value = "dir", value_parser = commands::todos::TODO_GROUPINGS)]
+        by: String,
 
-        /// Minimum score threshold for tree output (0.0-1.0 == 0-100%)
-        #[arg(long = "tree-min-score", value_name = "SCORE", requires = "tree")]
-        tree_min_score: Option<f32>,
+        /// Directory levels grouped by with `--by dir`
+        #[arg(long, default_value = "1")]
+        depth: usize,
 
-        /// Limit tree output to top N hits by score
-        #[arg(long = "tree-top", value_name = "N", requires = "tree")]
-        tree_top: Option<usize>,
+        /// Maximum lines listed per group
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// Print a file as it is indexed, by path or doc_id (a chunk's doc_id gives its whole
+    /// file, put back together from its chunks if need be)
+    Get {
+        /// File path relative to the workspace, or a doc_id from search results
+        id: String,
+
+        /// Only the hit's lines with N more before and after (for a doc_id; add
+        /// `:L<start>-<end>` for a snippet's lines rather than its whole document's)
+        #[arg(long, value_name = "N")]
+        expand: Option<usize>,
+    },
+
+    /// Mark a search hit as relevant (or not) for a query; hybrid search ranks files
+    /// with positive feedback slightly higher for similar queries
+    Feedback {
+        /// The query the hit was returned for
+        query: String,
+
+        /// The hit's doc_id (from --json output) or its file path
+        doc_id: String,
+
+        /// Mark the hit as not relevant
+        #[arg(long)]
+        irrelevant: bool,
+    },
+
+    /// Measure search quality: run queries with known relevant files (from a YAML cases
+    /// file) in each search mode and report precision, recall and MRR
+    Eval {
+        /// Cases file: `cases:` with a `query` and its `expected` paths each
+        cases: PathBuf,
+
+        /// Files per query that are scored
+        #[arg(short = 'n', long, default_value = "10")]
+        limit: usize,
+
+        /// Only evaluate these modes: text, semantic or hybrid (repeatable; default: all)
+        #[arg(long = "mode", value_name = "MODE", value_parser = commands::eval::EVAL_MODES)]
+        modes: Vec<String>,
     },
 
     /// Build search index for a workspace (run before searching)
@@ -154,6 +417,10 @@ pub enum Commands {
         #[arg(long)]
         rebuild: bool,
 
+        /// Index every file again instead of only the changed ones, keeping the index
+        #[arg(long, conflicts_with = "rebuild")]
+        full: bool,
+
         /// Build semantic index for natural language queries (slower, ~25MB model)
         #[arg(long, conflicts_with = "text")]
         semantic: bool,
@@ -161,6 +428,31 @@ pub enum Commands {
         /// Build text-only index (fast, default). Converts semantic to text-only.
         #[arg(long, conflicts_with = "semantic")]
         text: bool,
+
+        /// Index dotfiles and dot-directories such as .github (remembered for later updates)
+        #[arg(long, conflicts_with = "no_hidden")]
+        hidden: bool,
+
+        /// Skip dotfiles and dot-directories (default)
+        #[arg(long)]
+        no_hidden: bool,
+
+        /// Index files that .gitignore and .ignore files exclude (.ygrepignore still applies)
+        #[arg(long)]
+        no_ignore: bool,
+
+        /// Index at background priority: lower CPU/IO priority, pauses between files
+        #[arg(long)]
+        throttle: bool,
+
+        /// Stream progress as JSON lines on stdout instead of the progress bar
+        #[arg(long)]
+        json_progress: bool,
+
+        /// Write a self-contained, relocatable index to DIR instead of the data directory
+        /// (for CI artifacts; search it with `--index DIR`)
+        #[arg(long, value_name = "DIR")]
+        output: Option<PathBuf>,
     },
 
     /// Show index status for current workspace
@@ -168,12 +460,20 @@ pub enum Commands {
         /// Show detailed statistics
         #[arg(long)]
         detailed: bool,
+
+        /// Show how the index grew over past index runs
+        #[arg(long)]
+        history: bool,
     },
 
     /// Watch for file changes and update index automatically
     Watch {
         /// Workspace path (default: current directory)
         path: Option<PathBuf>,
+
+        /// Re-index at background priority: lower CPU/IO priority, pauses between files
+        #[arg(long)]
+        throttle: bool,
     },
 
     /// Install ygrep integration for AI coding tools
@@ -187,18 +487,195 @@ pub enum Commands {
     /// Manage stored indexes (list, clean, remove)
     #[command(subcommand)]
     Indexes(IndexesCommand),
+
+    /// Compare embedding models on a sample of the workspace
+    #[command(subcommand)]
+    Model(ModelCommand),
+
+    /// Manage named workspaces for `-C @name` (add, list, remove)
+    #[command(subcommand)]
+    Workspaces(WorkspacesCommand),
+
+    /// Keep indexes and the semantic model loaded in a background daemon; searches use
+    /// it automatically while it runs (set YGREP_NO_DAEMON to bypass it)
+    Daemon {
+        #[command(subcommand)]
+        action: Option<DaemonCommand>,
+    },
+
+    /// Serve searches of the workspace over HTTP (`GET /search?q=...`) and MCP (`POST /mcp`)
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        addr: String,
+
+        /// Also watch for file changes and update the index, in the same process
+        #[arg(long)]
+        watch: bool,
+
+        /// Also serve every other indexed workspace (besides `server.workspaces`)
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum DaemonCommand {
+    /// Run the daemon in the foreground (default)
+    Start,
+    /// Stop the running daemon
+    Stop,
+    /// Show whether a daemon is running and which workspaces it has open
+    Status,
 }
 
 #[derive(Subcommand, Clone)]
 pub enum IndexesCommand {
     /// List all indexes with size and type (text/semantic)
     List,
-    /// Remove orphaned indexes for workspaces that no longer exist
-    Clean,
-    /// Remove a specific index by hash or workspace path
+    /// Remove orphaned indexes for workspaces that no longer exist (kept in the trash for
+    /// 7 days)
+    Clean {
+        /// Only list the indexes that would be removed, with their sizes
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't ask before removing many or large indexes
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Remove a specific index by hash or workspace path (kept in the trash for 7 days)
     Remove {
         /// Index hash (from `ygrep indexes list`) or workspace path
         identifier: String,
+        /// Only show the index that would be removed, with its size
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't ask before removing a large index
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Check an index for unreadable segments, inconsistent vectors and bad metadata
+    Verify {
+        /// Index hash or workspace path (default: the current workspace)
+        identifier: Option<String>,
+    },
+    /// Repair a damaged index in place, indexing only the files that were lost
+    Repair {
+        /// Index hash or workspace path (default: the current workspace)
+        identifier: Option<String>,
+    },
+    /// Drop vectors of documents that are no longer indexed
+    Gc {
+        /// Index hash or workspace path (default: the current workspace)
+        identifier: Option<String>,
+    },
+    /// Merge index segments, drop orphan vectors and remove unused files
+    Optimize {
+        /// Index hash or workspace path (default: the current workspace)
+        identifier: Option<String>,
+    },
+    /// Publish the current workspace's index for read-only consumers, which search it by
+    /// setting `indexer.remote_index` to the same URL
+    Publish {
+        /// `s3://bucket/prefix`, `gs://bucket/prefix` (with the object-storage feature) or
+        /// a directory
+        url: String,
+    },
+    /// Upload the current workspace's index as a bundle for its repository and the
+    /// commit it was built at
+    Push {
+        /// Where bundles are kept: `s3://`, `gs://` (with the object-storage feature) or a
+        /// directory
+        url: String,
+
+        /// Repository name to store the bundle under (default: from the `origin` remote)
+        #[arg(long)]
+        repo: Option<String>,
+    },
+    /// Fetch the bundle pushed for HEAD (or the newest of the 100 commits before it) in
+    /// place of indexing, then index the files changed since
+    Pull {
+        /// Where bundles are kept
+        url: String,
+
+        /// Repository name the bundle was stored under (default: from the `origin` remote)
+        #[arg(long)]
+        repo: Option<String>,
+    },
+    /// Copy the current workspace's index as a snapshot, to search later with `--at`
+    Snapshot {
+        /// Snapshot name (default: when the index was last updated, e.g. 2026-10-09-143000)
+        name: Option<String>,
+    },
+    /// List the current workspace's snapshots
+    Snapshots,
+    /// Reuse the index a workspace had before it was moved or renamed, instead of
+    /// indexing it from scratch
+    Relink {
+        /// Workspace path (default: current directory)
+        path: Option<PathBuf>,
+    },
+    /// Move caches (downloaded models, benchmark indexes) from the data directory and the
+    /// workspace's `.fastembed_cache` into the cache directory; done automatically once
+    Migrate,
+    /// Write the changes between two snapshots of an index (index directories, e.g.
+    /// built with `index --output`) to a delta file, to ship instead of the whole index
+    Diff {
+        /// The snapshot consumers have
+        old: PathBuf,
+
+        /// The snapshot to bring them to
+        new: PathBuf,
+
+        /// Delta file to write
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Bring an index up to date with a delta written by `indexes diff`
+    ApplyDelta {
+        /// Delta file
+        delta: PathBuf,
+
+        /// Index directory to update (default: the current workspace's index)
+        #[arg(long)]
+        index: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum ModelCommand {
+    /// Index a sample of the workspace with each embedding model and report indexing
+    /// throughput, index size and (with --cases) semantic and hybrid search quality
+    Bench {
+        /// Files to sample (files the cases expect are always included)
+        #[arg(long, default_value = "200")]
+        sample: usize,
+
+        /// Eval cases file (as for `ygrep eval`) to measure retrieval quality with
+        #[arg(long)]
+        cases: Option<PathBuf>,
+
+        /// Files per query that are scored
+        #[arg(short = 'n', long, default_value = "10")]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum WorkspacesCommand {
+    /// Register a workspace under a name
+    Add {
+        /// Name to use as `-C @name`
+        name: String,
+        /// Workspace root
+        path: PathBuf,
+    },
+    /// List registered workspaces
+    List,
+    /// Forget a registered workspace (keeps its index)
+    Remove {
+        /// Registered name
+        name: String,
     },
 }
 
@@ -238,76 +715,176 @@ impl OutputFormat {
     }
 }
 
-fn main() -> Result<()> {
-    // Initialize logging
-    let filter = if std::env::var("YGREP_DEBUG").is_ok() {
-        "debug"
-    } else {
-        "warn"
-    };
-
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_writer(std::io::stderr)
-        .init();
-
+fn main() {
     let cli = Cli::parse();
+    let json = cli.json;
+    if let Err(e) = logging::init(&cli).and_then(|()| run(cli)) {
+        let error = CliError::from_error(&e);
+        error.report(json);
+        std::process::exit(error.code.exit_code());
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    if cli.json_schema {
+        println!("{}", SearchResult::json_schema());
+        return Ok(());
+    }
 
     // Get current directory for relative path resolution
     let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
 
-    // Determine workspace using resolver (respects explicit -C, searches parents)
-    let explicit_workspace: Option<&Path> = cli.workspace.as_deref();
-    let resolved_workspace = match resolve_workspace(explicit_workspace, &current_dir, None) {
-        Ok(ws) => ws,
+    // Expand registered names (`-C @backend`) to their paths
+    let config = Config::load();
+    // Caches older versions kept in the data directory move to the cache directory
+    if let Err(e) = ygrep_core::layout::migrate_once(&config.indexer) {
+        tracing::warn!(
+            "Failed to move caches to {}: {}",
+            config.indexer.cache_dir.display(),
+            e
+        );
+    }
+    // (the registry is only read for `@name` arguments)
+    let data_dir = config.indexer.data_dir.clone();
+    let resolve_workspace_arg = |path: &Path| WorkspaceRegistry::resolve_in(&data_dir, path);
+    let explicit_workspaces = match cli
+        .workspace
+        .iter()
+        .map(|ws| resolve_workspace_arg(ws))
+        .collect::<ygrep_core::Result<Vec<_>>>()
+    {
+        Ok(workspaces) => workspaces,
         Err(e) => {
-            eprintln!("Error resolving workspace: {}", e);
-            std::process::exit(1);
+            return Err(CliError::new(
+                ErrorCode::WorkspaceNotFound,
+                format!("Failed to resolve workspace: {}", e),
+            )
+            .into());
         }
     };
 
+    let is_search = cli.query.is_some()
+        || cli
+            .command
+            .as_ref()
+            .map(|c| matches!(c, Commands::Search { .. }))
+            .unwrap_or(false);
+    let needs_index = is_search
+        || matches!(
+            cli.command,
+            Some(
+                Commands::Files { .. }
+                    | Commands::Refs { .. }
+                    | Commands::Todos { .. }
+                    | Commands::Get { .. }
+                    | Commands::ExplainQuery { .. }
+                    | Commands::Feedback { .. }
+                    | Commands::Eval { .. }
+            )
+        );
+
+    // With auto-index, searching an unindexed workspace indexes it first
+    let search_args = match &cli.command {
+        Some(Commands::Search { args, .. } | Commands::ExplainQuery { args, .. }) => args.as_ref(),
+        _ => &cli.search,
+    };
+    let auto_index = is_search && (search_args.auto_index || config.search.auto_index);
+    let fallback_scan = is_search && (search_args.fallback_scan || config.search.fallback_scan);
+    // With a published index, opening the workspace syncs it; `--index` brings its own
+    let open_unindexed = auto_index
+        || fallback_scan
+        || (needs_index && config.indexer.remote_index.is_some())
+        || search_args.index.is_some()
+        || search_args.at.is_some();
+    let resolve = |explicit: Option<&Path>| match resolve_workspace(explicit, &current_dir, None) {
+        Err(ResolveError::NotIndexed { path }) if open_unindexed => Ok(Some(path)),
+        Ok(None) if open_unindexed => Ok(Some(current_dir.clone())),
+        other => other,
+    };
+
+    // Several -C workspaces: search each and merge the results
+    if explicit_workspaces.len() > 1 {
+        if !is_search {
+            return Err(CliError::new(
+                ErrorCode::InvalidArgument,
+                "Multiple workspaces (-C) are only supported for search.",
+            )
+            .into());
+        }
+        let mut workspaces = Vec::with_capacity(explicit_workspaces.len());
+        for (label, path) in cli.workspace.iter().zip(&explicit_workspaces) {
+            match resolve(Some(path)) {
+                Ok(Some(root)) => workspaces.push((label.display().to_string(), root)),
+                Ok(None) => unreachable!("explicit workspaces always resolve"),
+                Err(e) => {
+                    return Err(anyhow::Error::from(e)
+                        .context(format!("Failed to resolve workspace {}", label.display())));
+                }
+            }
+        }
+        let format = OutputFormat::from_flags(cli.json, cli.pretty);
+        match cli.command {
+            Some(Commands::Search { query, args }) => {
+                commands::search::run_many(&workspaces, &query, &args, format)?
+            }
+            _ => {
+                let query = cli.query.as_deref().unwrap_or_default();
+                commands::search::run_many(&workspaces, query, &cli.search, format)?
+            }
+        }
+        return Ok(());
+    }
+
+    // Determine workspace using resolver (respects explicit -C, searches parents)
+    let explicit_workspace: Option<&Path> = explicit_workspaces.first().map(|p| p.as_path());
+    let resolved_workspace = resolve(explicit_workspace).context("Failed to resolve workspace")?;
+
     // Handle case where no workspace is found
     let workspace = match resolved_workspace {
         Some(ws) => ws,
         None => {
             // No explicit workspace and no parent index found
             // For commands that need a workspace, show helpful error
-            if cli.query.is_some()
-                || cli
-                    .command
-                    .as_ref()
-                    .map(|c| matches!(c, Commands::Search { .. }))
-                    .unwrap_or(false)
-            {
-                eprintln!("No ygrep index found in current directory or parent directories.");
-                eprintln!();
-                eprintln!("To index this workspace, run:");
-                eprintln!("  ygrep index              # Text-only (fast)");
-                eprintln!(
+            if needs_index {
+                let mut error = CliError::new(
+                    ErrorCode::NotIndexed,
+                    "No ygrep index found in current directory or parent directories.",
+                )
+                .with_workspace(&current_dir);
+                error.help = vec![
+                    String::new(),
+                    "To index this workspace, run:".to_string(),
+                    "  ygrep index              # Text-only (fast)".to_string(),
                     "  ygrep index --semantic   # With semantic search (slower, better results)"
-                );
-                eprintln!();
-                eprintln!("Or use -C to specify a different workspace:");
-                eprintln!("  ygrep -C /path/to/workspace search \"query\"");
+                        .to_string(),
+                    String::new(),
+                    "Or use -C to specify a different workspace:".to_string(),
+                    "  ygrep -C /path/to/workspace search \"query\"".to_string(),
+                ];
+                error.help.extend(error::moved_index_help(&current_dir));
 
                 // Show discovered parent directories (if any were checked)
                 let parents = discover_parent_indexes(&current_dir, None);
                 if !parents.is_empty() {
-                    eprintln!();
-                    eprintln!("Checked directories:");
+                    error.help.push(String::new());
+                    error.help.push("Checked directories:".to_string());
                     for (path, indexed) in parents.iter().take(5) {
                         let rel_path = path.strip_prefix("/").unwrap_or(path);
                         if *indexed {
-                            eprintln!("  {} [indexed]", rel_path.display());
+                            error
+                                .help
+                                .push(format!("  {} [indexed]", rel_path.display()));
                         } else {
-                            eprintln!("  {}", rel_path.display());
+                            error.help.push(format!("  {}", rel_path.display()));
                         }
                     }
                     if parents.len() > 5 {
-                        eprintln!("  ... ({} more)", parents.len() - 5);
+                        error
+                            .help
+                            .push(format!("  ... ({} more)", parents.len() - 5));
                     }
                 }
-                std::process::exit(1);
+                return Err(error.into());
             } else {
                 // For non-search commands, use current directory
                 current_dir
@@ -320,50 +897,87 @@ fn main() -> Result<()> {
 
     // Handle command
     match cli.command {
-        Some(Commands::Search {
-            query,
+        Some(Commands::Search { query, args }) => {
+            commands::search::run(&workspace, &query, &args, format)?;
+        }
+        Some(Commands::ExplainQuery { query, args }) => {
+            commands::search::explain(&workspace, &query, &args, format)?;
+        }
+        Some(Commands::Files {
+            pattern,
             limit,
-            extensions,
-            paths,
-            regex,
             scores,
-            text_only,
-            tree,
+        }) => {
+            commands::files::run(&workspace, &pattern, limit, scores, format)?;
+        }
+        Some(Commands::Refs { symbol, limit }) => {
+            commands::refs::run(&workspace, &symbol, limit, format)?;
+        }
+        Some(Commands::Todos {
+            markers,
+            by,
             depth,
-            tree_min_score,
-            tree_top,
+            limit,
         }) => {
-            commands::search::run(
-                &workspace,
-                &query,
-                limit,
-                extensions,
-                paths,
-                regex,
-                scores,
-                text_only,
-                tree,
-                depth,
-                tree_min_score,
-                tree_top,
-                format,
-            )?;
+            commands::todos::run(&workspace, &markers, &by, depth, limit, format)?;
+        }
+        Some(Commands::Get { id, expand }) => {
+            commands::get::run(&workspace, &id, expand, format)?;
+        }
+        Some(Commands::Feedback {
+            query,
+            doc_id,
+            irrelevant,
+        }) => {
+            commands::feedback::run(&workspace, &query, &doc_id, !irrelevant)?;
+        }
+        Some(Commands::Eval {
+            cases,
+            limit,
+            modes,
+        }) => {
+            commands::eval::run(&workspace, &cases, limit, &modes, format)?;
         }
         Some(Commands::Index {
             path,
             rebuild,
+            full,
             semantic,
             text,
+            hidden,
+            no_hidden,
+            no_ignore,
+            throttle,
+            json_progress,
+            output,
         }) => {
-            let target = path.unwrap_or(workspace);
-            commands::index::run(&target, rebuild, semantic, text)?;
+            let target = match path {
+                Some(path) => resolve_workspace_arg(&path)?,
+                None => workspace,
+            };
+            let hidden = (hidden || no_hidden).then_some(hidden);
+            commands::index::run(
+                &target,
+                rebuild,
+                full,
+                semantic,
+                text,
+                hidden,
+                no_ignore,
+                throttle,
+                json_progress,
+                output.as_deref(),
+            )?;
         }
-        Some(Commands::Status { detailed }) => {
-            commands::status::run(&workspace, detailed)?;
+        Some(Commands::Status { detailed, history }) => {
+            commands::status::run(&workspace, detailed, history)?;
         }
-        Some(Commands::Watch { path }) => {
-            let target = path.unwrap_or(workspace);
-            commands::watch::run(&target)?;
+        Some(Commands::Watch { path, throttle }) => {
+            let target = match path {
+                Some(path) => resolve_workspace_arg(&path)?,
+                None => workspace,
+            };
+            commands::watch::run(&target, throttle)?;
         }
         Some(Commands::Install(target)) => match target {
             InstallTarget::ClaudeCode => commands::install::install_claude_code()?,
@@ -379,27 +993,76 @@ fn main() -> Result<()> {
         },
         Some(Commands::Indexes(cmd)) => match cmd {
             IndexesCommand::List => commands::indexes::list()?,
-            IndexesCommand::Clean => commands::indexes::clean()?,
-            IndexesCommand::Remove { identifier } => commands::indexes::remove(&identifier)?,
+            IndexesCommand::Clean { dry_run, force } => commands::indexes::clean(dry_run, force)?,
+            IndexesCommand::Remove {
+                identifier,
+                dry_run,
+                force,
+            } => commands::indexes::remove(&identifier, dry_run, force)?,
+            IndexesCommand::Verify { identifier } => {
+                if !commands::indexes::verify(identifier.as_deref(), &workspace)? {
+                    std::process::exit(1);
+                }
+            }
+            IndexesCommand::Repair { identifier } => {
+                commands::indexes::repair(identifier.as_deref(), &workspace)?
+            }
+            IndexesCommand::Gc { identifier } => {
+                commands::indexes::gc(identifier.as_deref(), &workspace)?
+            }
+            IndexesCommand::Optimize { identifier } => {
+                commands::indexes::optimize(identifier.as_deref(), &workspace)?
+            }
+            IndexesCommand::Publish { url } => commands::indexes::publish(&url, &workspace)?,
+            IndexesCommand::Push { url, repo } => {
+                commands::indexes::push(&url, repo.as_deref(), &workspace)?
+            }
+            IndexesCommand::Pull { url, repo } => {
+                commands::indexes::pull(&url, repo.as_deref(), &workspace)?
+            }
+            IndexesCommand::Snapshot { name } => {
+                commands::indexes::snapshot(name.as_deref(), &workspace)?
+            }
+            IndexesCommand::Snapshots => commands::indexes::snapshots(&workspace)?,
+            IndexesCommand::Migrate => commands::indexes::migrate(&workspace)?,
+            IndexesCommand::Relink { path } => {
+                let target = match path {
+                    Some(path) => resolve_workspace_arg(&path)?,
+                    None => workspace,
+                };
+                commands::indexes::relink(&target)?
+            }
+            IndexesCommand::Diff { old, new, output } => {
+                commands::indexes::diff(&old, &new, &output)?
+            }
+            IndexesCommand::ApplyDelta { delta, index } => {
+                commands::indexes::apply_delta(&delta, index.as_deref(), &workspace)?
+            }
+        },
+        Some(Commands::Model(cmd)) => match cmd {
+            ModelCommand::Bench {
+                sample,
+                cases,
+                limit,
+            } => commands::model::bench(&workspace, sample, cases.as_deref(), limit, format)?,
+        },
+        Some(Commands::Workspaces(cmd)) => match cmd {
+            WorkspacesCommand::Add { name, path } => commands::workspaces::add(&name, &path)?,
+            WorkspacesCommand::List => commands::workspaces::list()?,
+            WorkspacesCommand::Remove { name } => commands::workspaces::remove(&name)?,
+        },
+        Some(Commands::Serve { addr, watch, all }) => {
+            commands::serve::run(&workspace, &addr, watch, all)?;
+        }
+        Some(Commands::Daemon { action }) => match action.unwrap_or(DaemonCommand::Start) {
+            DaemonCommand::Start => commands::daemon::start()?,
+            DaemonCommand::Stop => commands::daemon::stop()?,
+            DaemonCommand::Status => commands::daemon::status()?,
         },
         None => {
             // Default: treat as search if query provided
             if let Some(query) = cli.query {
-                commands::search::run(
-                    &workspace,
-                    &query,
-                    cli.limit,
-                    cli.extensions,
-                    cli.paths,
-                    cli.regex,
-                    false,
-                    cli.text_only,
-                    cli.tree,
-                    cli.depth,
-                    cli.tree_min_score,
-                    cli.tree_top,
-                    format,
-                )?;
+                commands::search::run(&workspace, &query, &cli.search, format)?;
             } else {
                 // No query, show help
                 use clap::CommandFactory;
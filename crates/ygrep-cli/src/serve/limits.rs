@@ -0,0 +1,191 @@
+//! Limits on the searches the server runs: per-client rate limits and a bounded number
+//! of searches (and connections) at once (`[server]` in the config)
+
+use parking_lot::{Condvar, Mutex};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Clients tracked before those with full buckets are forgotten
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+/// Token bucket per client: up to `burst` searches back to back, refilled at
+/// `per_minute`
+pub(super) struct RateLimiter {
+    per_minute: u32,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// `per_minute` 0 means unlimited
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        Self {
+            per_minute,
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a search for `client`, or how long until it may search again
+    pub fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let per_second = self.per_minute as f64 / 60.0;
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock();
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            let burst = self.burst;
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * per_second
+                    < burst
+            });
+        }
+
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+        }
+    }
+}
+
+/// At most `max` searches at once
+pub(super) struct QueryPool {
+    max: usize,
+    running: Mutex<usize>,
+    freed: Condvar,
+}
+
+/// A running search's place in the pool, given back when dropped
+pub(super) struct QuerySlot<'a>(&'a QueryPool);
+
+impl QueryPool {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max: max.max(1),
+            running: Mutex::new(0),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Wait up to `timeout` for a free slot
+    pub fn acquire(&self, timeout: Duration) -> Option<QuerySlot<'_>> {
+        let deadline = Instant::now() + timeout;
+        let mut running = self.running.lock();
+        while *running >= self.max {
+            if self.freed.wait_until(&mut running, deadline).timed_out() && *running >= self.max {
+                return None;
+            }
+        }
+        *running += 1;
+        Some(QuerySlot(self))
+    }
+}
+
+impl Drop for QuerySlot<'_> {
+    fn drop(&mut self) {
+        *self.0.running.lock() -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+/// At most `max` connections open at once
+pub(super) struct ConnectionLimit {
+    max: usize,
+    open: Arc<AtomicUsize>,
+}
+
+/// An open connection's place, given back when dropped
+pub(super) struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionLimit {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max: max.max(1),
+            open: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// A place for a new connection, unless `max` are open
+    pub fn try_open(&self) -> Option<ConnectionSlot> {
+        self.open
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |open| {
+                (open < self.max).then_some(open + 1)
+            })
+            .ok()?;
+        Some(ConnectionSlot(Arc::clone(&self.open)))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(60, 2);
+        let client: IpAddr = "127.0.0.1".parse().unwrap();
+        assert!(limiter.check(client).is_ok());
+        assert!(limiter.check(client).is_ok());
+        let wait = limiter.check(client).unwrap_err();
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+        // Other clients have their own budget
+        assert!(limiter.check("10.0.0.2".parse().unwrap()).is_ok());
+
+        let unlimited = RateLimiter::new(0, 1);
+        for _ in 0..100 {
+            assert!(unlimited.check(client).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_query_pool() {
+        let pool = QueryPool::new(1);
+        let slot = pool.acquire(Duration::ZERO).unwrap();
+        assert!(pool.acquire(Duration::from_millis(10)).is_none());
+        drop(slot);
+        assert!(pool.acquire(Duration::ZERO).is_some());
+
+        // A waiting search gets the slot as soon as it is freed
+        let slot = pool.acquire(Duration::ZERO).unwrap();
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| pool.acquire(Duration::from_secs(10)).is_some());
+            std::thread::sleep(Duration::from_millis(20));
+            drop(slot);
+            assert!(waiter.join().unwrap());
+        });
+    }
+
+    #[test]
+    fn test_connection_limit() {
+        let limit = ConnectionLimit::new(2);
+        let first = limit.try_open().unwrap();
+        let _second = limit.try_open().unwrap();
+        assert!(limit.try_open().is_none());
+        drop(first);
+        assert!(limit.try_open().is_some());
+    }
+}
@@ -0,0 +1,1176 @@
+//! HTTP and MCP server (`ygrep serve`)
+//!
+//! Serves searches of workspaces over HTTP: `GET /search?q=...` (or `POST /search`
+//! with a JSON body) returns the JSON search result, and `POST /mcp` speaks the Model
+//! Context Protocol (JSON-RPC, answered as plain JSON per the streamable HTTP transport)
+//! with a `search` tool, so agents can connect to it directly. `POST /buffer` makes an
+//! editor's unsaved buffer searchable in place of the file on disk. Each connection carries
+//! one request and is handled on its own thread, as in the daemon.
+//!
+//! Besides the current workspace it can host others (see `workspaces`), picked with a
+//! `workspace` parameter or a `/w/<name>/` prefix, so one server covers all of a
+//! developer's repositories.
+//!
+//! With `--watch` the file watcher runs in the same process on the default `Workspace`:
+//! every change is committed as it is indexed and each search opens a fresh reader, so
+//! hits reflect edits within the watcher's debounce delay.
+//!
+//! Searches are rate limited per client and only `server.max_concurrent_queries` run at
+//! once (see `limits`), so a runaway agent can't exhaust the CPU or starve the watcher.
+//!
+//! With `server.tokens` (or `YGREP_SERVER_TOKENS`) set, every endpoint but `/health`
+//! needs one of them as a bearer token, and with `server.tls_cert` it serves HTTPS (the
+//! `tls` feature), so a shared index server isn't open to anyone on the network.
+
+mod limits;
+#[cfg(feature = "tls")]
+mod tls;
+mod workspaces;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use ygrep_core::search::DisplayOptions;
+use ygrep_core::{Config, Workspace, YgrepError};
+
+use limits::{ConnectionLimit, QueryPool, QuerySlot, RateLimiter};
+pub(crate) use workspaces::indexed as indexed_workspaces;
+use workspaces::Workspaces;
+
+use crate::commands::search::search_in;
+use crate::{Cli, SearchArgs};
+
+/// Largest request head (request line and headers) accepted
+const MAX_HEAD: usize = 64 * 1024;
+
+/// Largest request body accepted
+const MAX_BODY: usize = 1024 * 1024;
+
+/// MCP protocol revisions this server speaks, newest first
+const MCP_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Comma-separated API keys accepted in addition to `server.tokens`
+const TOKENS_ENV: &str = "YGREP_SERVER_TOKENS";
+
+/// Serve `default` (searched when a request names no workspace) and the workspaces at
+/// `roots` on `addr` until the process is stopped
+pub fn serve(
+    default: Option<Workspace>,
+    roots: Vec<PathBuf>,
+    addr: &str,
+    watch: bool,
+    config: &Config,
+) -> Result<()> {
+    if watch && default.is_none() {
+        anyhow::bail!("--watch needs the current directory to be an indexed workspace");
+    }
+    let server = Server::new(Workspaces::new(default, roots, config), watch, config)?;
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("Failed to listen on {}", addr))?;
+    let local = listener.local_addr()?;
+    let served = match (server.workspaces.default(), server.workspaces.len()) {
+        (Some(default), 1) => default.root().display().to_string(),
+        (_, count) => format!(
+            "{} workspaces ({})",
+            count,
+            server.workspaces.names().join(", ")
+        ),
+    };
+    eprintln!(
+        "ygrep serving {} on {}://{} (MCP endpoint: /mcp)",
+        served,
+        if server.uses_tls() { "https" } else { "http" },
+        local
+    );
+    if server.tokens.is_empty() && !local.ip().is_loopback() {
+        eprintln!(
+            "Warning: no server.tokens or {} set, so anyone who can reach {} can search \
+             these workspaces",
+            TOKENS_ENV, local
+        );
+    }
+    run(listener, Arc::new(server))
+}
+
+fn run(listener: TcpListener, server: Arc<Server>) -> Result<()> {
+    if let Some(workspace) = server.workspaces.default().filter(|_| server.watching) {
+        let workspace = Arc::clone(workspace);
+        std::thread::spawn(move || {
+            if let Err(e) = crate::commands::watch::watch(&workspace) {
+                eprintln!("File watcher stopped: {:#}", e);
+            }
+        });
+    }
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                // A client that stalls mid-request or stops reading can't keep its thread
+                let timeout = Some(server.request_timeout);
+                if let Err(e) = stream
+                    .set_read_timeout(timeout)
+                    .and_then(|_| stream.set_write_timeout(timeout))
+                {
+                    tracing::debug!("Failed to set connection timeouts: {}", e);
+                    continue;
+                }
+                // (closed unanswered: reading the request to answer it would take a thread)
+                let Some(slot) = server.connections.try_open() else {
+                    tracing::debug!(
+                        "Too many connections; closing one from {:?}",
+                        stream.peer_addr()
+                    );
+                    continue;
+                };
+                let server = Arc::clone(&server);
+                std::thread::spawn(move || {
+                    server.handle(stream);
+                    drop(slot);
+                });
+            }
+            Err(e) => tracing::warn!("Connection failed: {}", e),
+        }
+    }
+    Ok(())
+}
+
+struct Server {
+    workspaces: Workspaces,
+    /// How the MCP `search` tool formats results
+    display: DisplayOptions,
+    watching: bool,
+    started: Instant,
+    limiter: RateLimiter,
+    pool: QueryPool,
+    /// How long a search waits for a slot in `pool`
+    queue_timeout: Duration,
+    connections: ConnectionLimit,
+    /// Read and write timeout of each connection
+    request_timeout: Duration,
+    /// API keys a request must carry one of; empty = no authentication
+    tokens: Vec<String>,
+    #[cfg(feature = "tls")]
+    tls: Option<tls::Tls>,
+}
+
+/// Why a search wasn't run
+enum Refusal {
+    /// The client is over its rate limit until this much later
+    RateLimited(Duration),
+    /// No search slot freed up in time
+    Busy,
+}
+
+impl Refusal {
+    fn message(&self) -> String {
+        match self {
+            Refusal::RateLimited(wait) => {
+                format!("Rate limit exceeded; retry in {}s", retry_after_secs(*wait))
+            }
+            Refusal::Busy => "Too many searches running; try again later".to_string(),
+        }
+    }
+
+    fn response(&self) -> Response {
+        match self {
+            Refusal::RateLimited(wait) => {
+                let mut response = Response::error(429, self.message());
+                response
+                    .headers
+                    .push(("Retry-After", retry_after_secs(*wait).to_string()));
+                response
+            }
+            Refusal::Busy => Response::error(503, self.message()),
+        }
+    }
+}
+
+fn retry_after_secs(wait: Duration) -> u64 {
+    wait.as_secs_f64().ceil().max(1.0) as u64
+}
+
+/// A parsed HTTP request
+struct Request {
+    method: String,
+    /// Path without the query string
+    path: String,
+    query: Vec<(String, String)>,
+    /// From `Authorization: Bearer <token>` or `X-API-Key`
+    token: Option<String>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: &Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            headers: Vec::new(),
+            body: body.to_string(),
+        }
+    }
+
+    fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        Self::json(status, &json!({ "error": message.to_string() }))
+    }
+
+    fn unauthorized() -> Self {
+        let mut response = Self::error(401, "Missing or invalid API key");
+        response
+            .headers
+            .push(("WWW-Authenticate", "Bearer realm=\"ygrep\"".to_string()));
+        response
+    }
+
+    /// A notification was received; there is nothing to answer
+    fn accepted() -> Self {
+        Self {
+            status: 202,
+            content_type: "application/json",
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+}
+
+/// Search parameters, from the query string of `GET /search`, the JSON body of
+/// `POST /search` or the arguments of the MCP `search` tool
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SearchParams {
+    query: String,
+    limit: Option<usize>,
+    regex: bool,
+    text_only: bool,
+    extensions: Vec<String>,
+    paths: Vec<String>,
+    /// Hit fields to return (all when empty)
+    fields: Vec<String>,
+    /// Name or root of the workspace to search (default: the one served from)
+    workspace: Option<String>,
+}
+
+impl SearchParams {
+    fn from_query_string(pairs: &[(String, String)]) -> Self {
+        let mut params = Self::default();
+        for (key, value) in pairs {
+            let list = || value.split(',').map(str::to_string);
+            match key.as_str() {
+                "q" | "query" => params.query = value.clone(),
+                "workspace" => params.workspace = Some(value.clone()),
+                "n" | "limit" => params.limit = value.parse().ok(),
+                "regex" => params.regex = is_true(value),
+                "text_only" => params.text_only = is_true(value),
+                "ext" | "extensions" => params.extensions.extend(list()),
+                "path" | "paths" => params.paths.extend(list()),
+                "fields" => params.fields.extend(list()),
+                _ => {}
+            }
+        }
+        params
+    }
+
+    /// The equivalent command-line search arguments
+    fn args(&self) -> SearchArgs {
+        let mut args = Cli::parse_from(["ygrep"]).search;
+        if let Some(limit) = self.limit {
+            args.limit = limit;
+        }
+        args.regex = self.regex;
+        args.text_only = self.text_only;
+        args.extensions = self.extensions.clone();
+        args.paths = self.paths.clone();
+        args
+    }
+}
+
+/// An editor buffer for `POST /buffer`: its content is indexed in place of the file at
+/// `path`, or, without content, the file on disk is indexed again
+#[derive(Debug, Deserialize)]
+struct BufferParams {
+    /// Absolute, or relative to the workspace root
+    path: PathBuf,
+    content: Option<String>,
+    workspace: Option<String>,
+}
+
+fn is_true(value: &str) -> bool {
+    matches!(value, "1" | "true" | "yes")
+}
+
+impl Server {
+    fn new(workspaces: Workspaces, watch: bool, config: &Config) -> Result<Self> {
+        let settings = &config.server;
+        let mut tokens = settings.tokens.clone();
+        if let Ok(value) = std::env::var(TOKENS_ENV) {
+            tokens.extend(value.split(',').map(str::to_string));
+        }
+        tokens.retain(|token| !token.trim().is_empty());
+
+        #[cfg(feature = "tls")]
+        let tls = match (&settings.tls_cert, &settings.tls_key) {
+            (Some(cert), Some(key)) => Some(tls::Tls::load(cert, key)?),
+            (None, None) => None,
+            _ => anyhow::bail!("server.tls_cert and server.tls_key must be set together"),
+        };
+        #[cfg(not(feature = "tls"))]
+        if settings.tls_cert.is_some() || settings.tls_key.is_some() {
+            anyhow::bail!("HTTPS needs ygrep built with the `tls` feature");
+        }
+
+        Ok(Self {
+            workspaces,
+            display: DisplayOptions::from_config(&config.search),
+            watching: watch,
+            started: Instant::now(),
+            limiter: RateLimiter::new(settings.requests_per_minute, settings.burst),
+            pool: QueryPool::new(settings.max_concurrent_queries),
+            queue_timeout: Duration::from_millis(settings.queue_timeout_ms),
+            connections: ConnectionLimit::new(settings.max_connections),
+            request_timeout: Duration::from_millis(settings.request_timeout_ms),
+            tokens,
+            #[cfg(feature = "tls")]
+            tls,
+        })
+    }
+
+    #[cfg(feature = "tls")]
+    fn uses_tls(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    #[cfg(not(feature = "tls"))]
+    fn uses_tls(&self) -> bool {
+        false
+    }
+
+    fn handle(&self, stream: TcpStream) {
+        let client = match stream.peer_addr() {
+            Ok(addr) => addr.ip(),
+            Err(e) => {
+                tracing::debug!("Connection without a peer address: {}", e);
+                return;
+            }
+        };
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            match tls.accept(stream) {
+                Ok(stream) => self.exchange(stream, client),
+                Err(e) => tracing::debug!("TLS connection failed: {}", e),
+            }
+            return;
+        }
+        self.exchange(stream, client);
+    }
+
+    /// Read a request from the connection and answer it
+    fn exchange(&self, mut stream: impl Read + Write, client: IpAddr) {
+        let response = match read_request(&mut stream) {
+            Ok(request) => self.respond(request, client),
+            Err(e) => Response::error(400, e),
+        };
+        if let Err(e) = write_response(&mut stream, &response) {
+            tracing::debug!("Failed to answer request: {}", e);
+        }
+    }
+
+    /// Whether the request carries one of the API keys (always, without any)
+    fn authorized(&self, request: &Request) -> bool {
+        if self.tokens.is_empty() {
+            return true;
+        }
+        let Some(given) = &request.token else {
+            return false;
+        };
+        // Compared in full, so the time taken doesn't tell how much of a key matched
+        self.tokens.iter().fold(false, |found, token| {
+            let same = given.len() == token.len()
+                && given
+                    .bytes()
+                    .zip(token.bytes())
+                    .fold(0, |diff, (a, b)| diff | (a ^ b))
+                    == 0;
+            found | same
+        })
+    }
+
+    fn respond(&self, request: Request, client: IpAddr) -> Response {
+        // `/w/<name>/search` searches workspace `name`
+        let (workspace, path) = match request
+            .path
+            .strip_prefix("/w/")
+            .and_then(|rest| rest.rsplit_once('/'))
+        {
+            Some((name, endpoint)) => (Some(name.to_string()), format!("/{}", endpoint)),
+            None => (None, request.path.clone()),
+        };
+        let authorized = self.authorized(&request);
+        if !authorized && path != "/health" {
+            return Response::unauthorized();
+        }
+        let with_workspace = |mut params: SearchParams| {
+            params.workspace = workspace.clone().or(params.workspace);
+            params
+        };
+        match (request.method.as_str(), path.as_str()) {
+            ("GET", "/health") => {
+                let mut health = json!({
+                    "status": "ok",
+                    "watching": self.watching,
+                    "uptime_secs": self.started.elapsed().as_secs(),
+                });
+                // Where the workspaces live is only told to clients with a key
+                if authorized {
+                    health["workspace"] = json!(self.workspaces.default().map(|ws| ws.root()));
+                    health["workspaces"] = json!(self.workspaces.names());
+                }
+                Response::json(200, &health)
+            }
+            ("GET", "/workspaces") => {
+                Response::json(200, &json!({ "workspaces": self.workspaces.describe() }))
+            }
+            ("GET", "/search") => self.search(
+                with_workspace(SearchParams::from_query_string(&request.query)),
+                client,
+            ),
+            ("POST", "/search") => match serde_json::from_slice(&request.body) {
+                Ok(params) => self.search(with_workspace(params), client),
+                Err(e) => Response::error(400, format!("Invalid search request: {}", e)),
+            },
+            ("POST", "/mcp") => self.mcp(&request.body, workspace.as_deref(), client),
+            ("POST", "/buffer") => match serde_json::from_slice::<BufferParams>(&request.body) {
+                Ok(mut params) => {
+                    params.workspace = workspace.clone().or(params.workspace);
+                    self.buffer(params, client)
+                }
+                Err(e) => Response::error(400, format!("Invalid buffer request: {}", e)),
+            },
+            (_, "/health" | "/workspaces" | "/search" | "/mcp" | "/buffer") => {
+                Response::error(405, "Method not allowed")
+            }
+            _ => Response::error(404, "Not found"),
+        }
+    }
+
+    /// A search slot for `client`, unless it is over its rate limit or none frees up in
+    /// time
+    fn admit(&self, client: IpAddr) -> Result<QuerySlot<'_>, Refusal> {
+        self.limiter.check(client).map_err(Refusal::RateLimited)?;
+        self.pool.acquire(self.queue_timeout).ok_or(Refusal::Busy)
+    }
+
+    fn search(&self, params: SearchParams, client: IpAddr) -> Response {
+        if params.query.is_empty() {
+            return Response::error(400, "Missing query (`q`)");
+        }
+        let _slot = match self.admit(client) {
+            Ok(slot) => slot,
+            Err(refusal) => return refusal.response(),
+        };
+        let workspace = match self.workspaces.get(params.workspace.as_deref()) {
+            Ok(workspace) => workspace,
+            Err(e) => return Response::error(e.status(), e.message(&self.workspaces)),
+        };
+        match search_in(&workspace, &params.query, &params.args()) {
+            Ok(result) => match result.format_json_fields(&params.fields) {
+                Ok(body) => Response {
+                    status: 200,
+                    content_type: "application/json",
+                    headers: Vec::new(),
+                    body,
+                },
+                Err(e) => Response::error(400, e.to_string()),
+            },
+            Err(e) => Response::error(500, format!("{:#}", e)),
+        }
+    }
+
+    /// Index an unsaved buffer (or drop it, without content)
+    fn buffer(&self, params: BufferParams, client: IpAddr) -> Response {
+        let _slot = match self.admit(client) {
+            Ok(slot) => slot,
+            Err(refusal) => return refusal.response(),
+        };
+        let workspace = match self.workspaces.get(params.workspace.as_deref()) {
+            Ok(workspace) => workspace,
+            Err(e) => return Response::error(e.status(), e.message(&self.workspaces)),
+        };
+        let path = params.path.display().to_string();
+        let result = match &params.content {
+            Some(content) => workspace
+                .index_content(&params.path, content)
+                .map(|doc_id| json!({ "path": path, "doc_id": doc_id })),
+            None => workspace
+                .discard_content(&params.path)
+                .map(|()| json!({ "path": path, "discarded": true })),
+        };
+        match result {
+            Ok(body) => Response::json(200, &body),
+            Err(e @ (YgrepError::FileExcluded { .. } | YgrepError::FileTooLarge { .. })) => {
+                Response::error(422, e)
+            }
+            Err(e) => Response::error(500, e),
+        }
+    }
+
+    /// Answer an MCP JSON-RPC message; `workspace` is the one the URL names, if any
+    fn mcp(&self, body: &[u8], workspace: Option<&str>, client: IpAddr) -> Response {
+        let message: Value = match serde_json::from_slice(body) {
+            Ok(message) => message,
+            Err(e) => return rpc_error(Value::Null, -32700, format!("Parse error: {}", e)),
+        };
+        let method = message.get("method").and_then(Value::as_str).unwrap_or("");
+        // Notifications (no id) and responses get no answer
+        let Some(id) = message.get("id").cloned() else {
+            return Response::accepted();
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => {
+                let requested = params.get("protocolVersion").and_then(Value::as_str);
+                let version = MCP_VERSIONS
+                    .iter()
+                    .find(|version| Some(**version) == requested)
+                    .unwrap_or(&MCP_VERSIONS[0]);
+                json!({
+                    "protocolVersion": version,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "ygrep", "version": env!("CARGO_PKG_VERSION") },
+                })
+            }
+            "ping" => json!({}),
+            "tools/list" => {
+                // Agents choose among the workspaces unless the URL picked one
+                let choices = match workspace {
+                    None if self.workspaces.len() > 1 || self.workspaces.default().is_none() => {
+                        self.workspaces.names()
+                    }
+                    _ => Vec::new(),
+                };
+                let required = self.workspaces.default().is_none();
+                json!({ "tools": [
+                    search_tool(&choices, required),
+                    feedback_tool(&choices, required),
+                ] })
+            }
+            "tools/call" => match params.get("name").and_then(Value::as_str) {
+                Some("search") => self.call_search(params.get("arguments"), workspace, client),
+                Some("feedback") => self.call_feedback(params.get("arguments"), workspace, client),
+                Some(name) => return rpc_error(id, -32602, format!("Unknown tool: {}", name)),
+                None => return rpc_error(id, -32602, "Missing tool name"),
+            },
+            _ => return rpc_error(id, -32601, format!("Method not found: {}", method)),
+        };
+        Response::json(
+            200,
+            &json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        )
+    }
+
+    /// Run the `search` tool; failures are reported in the tool result
+    fn call_search(
+        &self,
+        arguments: Option<&Value>,
+        workspace: Option<&str>,
+        client: IpAddr,
+    ) -> Value {
+        let mut params: SearchParams = match arguments.cloned().map(serde_json::from_value) {
+            Some(Ok(params)) => params,
+            Some(Err(e)) => return tool_result(format!("Invalid arguments: {}", e), true),
+            None => return tool_result("Missing arguments", true),
+        };
+        if params.query.is_empty() {
+            return tool_result("Missing query", true);
+        }
+        let _slot = match self.admit(client) {
+            Ok(slot) => slot,
+            Err(refusal) => return tool_result(refusal.message(), true),
+        };
+        if let Some(workspace) = workspace {
+            params.workspace = Some(workspace.to_string());
+        }
+        let workspace = match self.workspaces.get(params.workspace.as_deref()) {
+            Ok(workspace) => workspace,
+            Err(e) => return tool_result(e.message(&self.workspaces), true),
+        };
+        match search_in(&workspace, &params.query, &params.args()) {
+            Ok(result) => tool_result(result.format_ai_with(&self.display), false),
+            Err(e) => tool_result(format!("{:#}", e), true),
+        }
+    }
+
+    /// Run the `feedback` tool; failures are reported in the tool result
+    fn call_feedback(
+        &self,
+        arguments: Option<&Value>,
+        workspace: Option<&str>,
+        client: IpAddr,
+    ) -> Value {
+        let mut params: FeedbackParams = match arguments.cloned().map(serde_json::from_value) {
+            Some(Ok(params)) => params,
+            Some(Err(e)) => return tool_result(format!("Invalid arguments: {}", e), true),
+            None => return tool_result("Missing arguments", true),
+        };
+        if params.query.is_empty() || params.hit.is_empty() {
+            return tool_result("Missing query or hit", true);
+        }
+        let _slot = match self.admit(client) {
+            Ok(slot) => slot,
+            Err(refusal) => return tool_result(refusal.message(), true),
+        };
+        if let Some(workspace) = workspace {
+            params.workspace = Some(workspace.to_string());
+        }
+        let workspace = match self.workspaces.get(params.workspace.as_deref()) {
+            Ok(workspace) => workspace,
+            Err(e) => return tool_result(e.message(&self.workspaces), true),
+        };
+        match workspace.record_feedback(&params.query, &params.hit, params.relevant) {
+            Ok(path) => tool_result(
+                format!(
+                    "Recorded {} as {} for \"{}\"",
+                    path,
+                    if params.relevant {
+                        "relevant"
+                    } else {
+                        "irrelevant"
+                    },
+                    params.query
+                ),
+                false,
+            ),
+            Err(e) => tool_result(format!("{:#}", e), true),
+        }
+    }
+}
+
+/// Arguments of the MCP `feedback` tool
+#[derive(Deserialize)]
+struct FeedbackParams {
+    query: String,
+    /// A hit's doc_id or path
+    hit: String,
+    #[serde(default = "default_relevant")]
+    relevant: bool,
+    workspace: Option<String>,
+}
+
+fn default_relevant() -> bool {
+    true
+}
+
+/// The MCP `search` tool's description and input schema, with a `workspace` argument
+/// taking one of `workspaces` unless that is empty
+fn search_tool(workspaces: &[String], workspace_required: bool) -> Value {
+    let tool = json!({
+        "name": "search",
+        "description": "Search the workspace's code index. Literal words by default \
+            (BM25-ranked), or a regex with `regex`; hybrid semantic search when the index \
+            has embeddings. Returns matching files with line numbers and snippets.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "Words, a phrase or a regex" },
+                "limit": { "type": "integer", "description": "Maximum results" },
+                "regex": { "type": "boolean", "description": "Treat the query as a regex" },
+                "text_only": { "type": "boolean", "description": "Skip semantic search" },
+                "extensions": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Only files with these extensions (e.g. rs)"
+                },
+                "paths": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Only files whose path contains one of these"
+                }
+            },
+            "required": ["query"]
+        }
+    });
+    with_workspace(tool, workspaces, workspace_required)
+}
+
+/// The MCP `feedback` tool's description and input schema (see `search_tool`)
+fn feedback_tool(workspaces: &[String], workspace_required: bool) -> Value {
+    let tool = json!({
+        "name": "feedback",
+        "description": "Record whether a search hit was relevant to a query. Hybrid searches \
+            rank files with positive feedback slightly higher (and irrelevant ones lower) \
+            for similar queries.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "The query that was searched" },
+                "hit": {
+                    "type": "string",
+                    "description": "The hit's doc_id or file path"
+                },
+                "relevant": {
+                    "type": "boolean",
+                    "description": "Whether the hit was relevant (default true)"
+                }
+            },
+            "required": ["query", "hit"]
+        }
+    });
+    with_workspace(tool, workspaces, workspace_required)
+}
+
+/// `tool` with a `workspace` argument taking one of `workspaces` unless that is empty
+fn with_workspace(mut tool: Value, workspaces: &[String], required: bool) -> Value {
+    if !workspaces.is_empty() {
+        tool["inputSchema"]["properties"]["workspace"] = json!({
+            "type": "string",
+            "enum": workspaces,
+            "description": "Workspace (repository) to use",
+        });
+        if required {
+            tool["inputSchema"]["required"]
+                .as_array_mut()
+                .unwrap()
+                .push(json!("workspace"));
+        }
+    }
+    tool
+}
+
+fn tool_result(text: impl Into<String>, is_error: bool) -> Value {
+    json!({
+        "content": [{ "type": "text", "text": text.into() }],
+        "isError": is_error,
+    })
+}
+
+fn rpc_error(id: Value, code: i64, message: impl Into<String>) -> Response {
+    Response::json(
+        200,
+        &json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message.into() },
+        }),
+    )
+}
+
+/// Read one request: the head up to the blank line, then `Content-Length` bytes of body
+fn read_request(stream: impl Read) -> Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let read = reader.read_until(b'\n', &mut head)?;
+        if read == 0 {
+            anyhow::bail!("Connection closed mid-request");
+        }
+        if head.len() > MAX_HEAD {
+            anyhow::bail!("Request head too large");
+        }
+    }
+
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut parsed = httparse::Request::new(&mut headers);
+    parsed.parse(&head).context("Malformed request")?;
+    let method = parsed.method.unwrap_or_default().to_string();
+    let target = parsed.path.unwrap_or("/");
+    let header = |name: &str| {
+        parsed
+            .headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case(name))
+            .and_then(|header| std::str::from_utf8(header.value).ok())
+            .map(str::trim)
+    };
+    let content_length = header("content-length")
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    let token = header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| header("x-api-key"))
+        .map(|token| token.trim().to_string());
+    if content_length > MAX_BODY {
+        anyhow::bail!("Request body too large");
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query: form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect(),
+        token,
+        body,
+    })
+}
+
+fn write_response(stream: &mut impl Write, response: &Response) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    };
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len(),
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(response.body.as_bytes())?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::time::Duration;
+    use tempfile::{tempdir, TempDir};
+    use ygrep_core::Config;
+
+    /// Send a request and return the status and body
+    fn send(addr: &str, method: &str, target: &str, body: &str) -> (u16, String) {
+        send_with(addr, method, target, "", body)
+    }
+
+    /// Send a request with extra header lines (each ending in `\r\n`)
+    fn send_with(
+        addr: &str,
+        method: &str,
+        target: &str,
+        headers: &str,
+        body: &str,
+    ) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: test\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            target,
+            headers,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, body.to_string())
+    }
+
+    /// Index a workspace at `root` holding `hello.rs` with `content`
+    fn index_workspace(root: &Path, content: &str, config: &Config) -> Result<Workspace> {
+        std::fs::create_dir_all(root)?;
+        std::fs::write(root.join("hello.rs"), content)?;
+        let workspace = Workspace::create_with_config(root, config.clone())?;
+        workspace.index_all()?;
+        Ok(workspace)
+    }
+
+    /// Serve `workspaces` on a free port; returns its address
+    fn start(workspaces: Workspaces, watch: bool, config: &Config) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?.to_string();
+        let server = Server::new(workspaces, watch, config)?;
+        std::thread::spawn(move || run(listener, Arc::new(server)));
+        Ok(addr)
+    }
+
+    /// Index a workspace with one file and serve it; returns the temp dir, the workspace
+    /// root and the server's address
+    fn start_server(
+        watch: bool,
+        configure: impl FnOnce(&mut Config),
+    ) -> Result<(TempDir, PathBuf, String)> {
+        let temp = tempdir()?;
+        let root = temp.path().join("workspace");
+        let mut config = Config::default();
+        config.indexer.data_dir = temp.path().join("data");
+        configure(&mut config);
+        let workspace = index_workspace(&root, "fn hello_serve() {}", &config)?;
+        let addr = start(
+            Workspaces::new(Some(workspace), Vec::new(), &config),
+            watch,
+            &config,
+        )?;
+        Ok((temp, root, addr))
+    }
+
+    #[test]
+    fn test_serve_search_and_mcp() -> Result<()> {
+        // Polling for the watcher's update below would run into the default rate limit
+        let (_temp, root, addr) = start_server(true, |config| {
+            config.server.requests_per_minute = 0;
+        })?;
+
+        let (status, body) = send(&addr, "GET", "/search?q=hello_serve&n=5", "");
+        assert_eq!(status, 200);
+        let result: Value = serde_json::from_str(&body)?;
+        assert_eq!(result["hits"][0]["path"], "hello.rs");
+
+        let (status, body) = send(&addr, "POST", "/search", r#"{"query": "hello_serve"}"#);
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(send(&addr, "GET", "/search", "").0, 400);
+        assert_eq!(send(&addr, "GET", "/nope", "").0, 404);
+
+        let rpc = |method: &str, params: Value| {
+            let message = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+            let (status, body) = send(&addr, "POST", "/mcp", &message.to_string());
+            assert_eq!(status, 200);
+            serde_json::from_str::<Value>(&body).unwrap()
+        };
+        let init = rpc("initialize", json!({ "protocolVersion": "2025-03-26" }));
+        assert_eq!(init["result"]["protocolVersion"], "2025-03-26");
+        let tools = rpc("tools/list", json!({}));
+        assert_eq!(tools["result"]["tools"][0]["name"], "search");
+        let call = rpc(
+            "tools/call",
+            json!({ "name": "search", "arguments": { "query": "hello_serve" } }),
+        );
+        assert_eq!(call["result"]["isError"], false);
+        let text = call["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("hello.rs"), "{}", text);
+        assert_eq!(tools["result"]["tools"][1]["name"], "feedback");
+        let feedback = rpc(
+            "tools/call",
+            json!({
+                "name": "feedback",
+                "arguments": { "query": "hello_serve", "hit": "hello.rs", "relevant": false }
+            }),
+        );
+        assert_eq!(feedback["result"]["isError"], false, "{}", feedback);
+        let text = feedback["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("hello.rs as irrelevant"), "{}", text);
+        let feedback = rpc(
+            "tools/call",
+            json!({ "name": "feedback", "arguments": { "query": "q", "hit": "missing.rs" } }),
+        );
+        assert_eq!(feedback["result"]["isError"], true);
+        assert_eq!(rpc("nope", json!({}))["error"]["code"], -32601);
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert_eq!(
+            send(&addr, "POST", "/mcp", &notification.to_string()).0,
+            202
+        );
+
+        // The watcher indexes new files into the served workspace
+        std::fs::write(root.join("later.rs"), "fn added_while_serving() {}")?;
+        let started = Instant::now();
+        loop {
+            let (_, body) = send(&addr, "GET", "/search?q=added_while_serving", "");
+            let result: Value = serde_json::from_str(&body)?;
+            if result["hits"][0]["path"] == "later.rs" {
+                break;
+            }
+            assert!(started.elapsed() < Duration::from_secs(20), "{}", body);
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_serve_buffer() -> Result<()> {
+        let (temp, _root, addr) = start_server(false, |_| {})?;
+        let hits = |query: &str| -> Result<usize> {
+            let (_, body) = send(&addr, "GET", &format!("/search?q={}", query), "");
+            let result: Value = serde_json::from_str(&body)?;
+            Ok(result["hits"].as_array().map_or(0, Vec::len))
+        };
+
+        let buffer = json!({ "path": "hello.rs", "content": "fn edited_buffer() {}" });
+        let (status, body) = send(&addr, "POST", "/buffer", &buffer.to_string());
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(hits("edited_buffer")?, 1);
+        assert_eq!(hits("hello_serve")?, 0);
+
+        let (status, _) = send(&addr, "POST", "/buffer", r#"{"path": "hello.rs"}"#);
+        assert_eq!(status, 200);
+        assert_eq!(hits("edited_buffer")?, 0);
+        assert_eq!(hits("hello_serve")?, 1);
+
+        let ignored = json!({ "path": "node_modules/x.js", "content": "x" });
+        assert_eq!(send(&addr, "POST", "/buffer", &ignored.to_string()).0, 422);
+        // Paths outside the workspace are neither read nor indexed
+        let outside = temp.path().join("outside.txt");
+        std::fs::write(&outside, "fn outside_workspace() {}")?;
+        let absolute = json!({ "path": outside });
+        assert_eq!(send(&addr, "POST", "/buffer", &absolute.to_string()).0, 422);
+        let parent = json!({ "path": "../outside2.txt", "content": "fn outside_buffer() {}" });
+        assert_eq!(send(&addr, "POST", "/buffer", &parent.to_string()).0, 422);
+        assert_eq!(hits("outside_workspace")?, 0);
+        assert_eq!(hits("outside_buffer")?, 0);
+        assert_eq!(send(&addr, "GET", "/buffer", "").0, 405);
+        Ok(())
+    }
+
+    #[test]
+    fn test_serve_rate_limit() -> Result<()> {
+        let (_temp, _root, addr) = start_server(false, |config| {
+            config.server.requests_per_minute = 1;
+            config.server.burst = 1;
+        })?;
+
+        assert_eq!(send(&addr, "GET", "/search?q=hello_serve", "").0, 200);
+        let (status, body) = send(&addr, "GET", "/search?q=hello_serve", "");
+        assert_eq!(status, 429);
+        assert!(body.contains("Rate limit exceeded"), "{}", body);
+        // Health checks aren't limited
+        assert_eq!(send(&addr, "GET", "/health", "").0, 200);
+
+        // MCP clients get the refusal as a tool error
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": "search", "arguments": { "query": "hello_serve" } },
+        });
+        let (_, body) = send(&addr, "POST", "/mcp", &message.to_string());
+        let call: Value = serde_json::from_str(&body)?;
+        assert_eq!(call["result"]["isError"], true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_serve_connection_limits() -> Result<()> {
+        let (_temp, _root, addr) = start_server(false, |config| {
+            config.server.max_connections = 1;
+            config.server.request_timeout_ms = 300;
+        })?;
+
+        // A client that connects and sends nothing holds the only connection...
+        let mut idle = TcpStream::connect(&addr)?;
+        idle.set_read_timeout(Some(Duration::from_secs(10)))?;
+        std::thread::sleep(Duration::from_millis(50));
+        let mut refused = TcpStream::connect(&addr)?;
+        refused.set_read_timeout(Some(Duration::from_secs(10)))?;
+        let mut response = String::new();
+        let _ = refused.read_to_string(&mut response);
+        assert!(response.is_empty(), "{}", response);
+
+        // ...until it times out
+        let mut response = String::new();
+        idle.read_to_string(&mut response)?;
+        assert!(response.starts_with("HTTP/1.1 400 "), "{}", response);
+        let started = Instant::now();
+        while send(&addr, "GET", "/health", "").0 != 200 {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_serve_auth() -> Result<()> {
+        let (_temp, _root, addr) = start_server(false, |config| {
+            config.server.tokens = vec!["s3cret".to_string()];
+        })?;
+
+        let search = |headers: &str| send_with(&addr, "GET", "/search?q=hello_serve", headers, "");
+        let (status, body) = search("");
+        assert_eq!(status, 401, "{}", body);
+        assert_eq!(search("Authorization: Bearer wrong\r\n").0, 401);
+        assert_eq!(search("Authorization: Bearer s3cre\r\n").0, 401);
+        assert_eq!(search("Authorization: Bearer s3cret\r\n").0, 200);
+        assert_eq!(search("X-API-Key: s3cret\r\n").0, 200);
+        assert_eq!(send(&addr, "POST", "/mcp", "{}").0, 401);
+
+        // Health checks work without a key, but don't say where the workspace is
+        let (status, body) = send(&addr, "GET", "/health", "");
+        assert_eq!(status, 200);
+        assert!(serde_json::from_str::<Value>(&body)?["workspace"].is_null());
+        let (_, body) = send_with(&addr, "GET", "/health", "X-API-Key: s3cret\r\n", "");
+        assert!(serde_json::from_str::<Value>(&body)?["workspace"].is_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_serve_workspaces() -> Result<()> {
+        let temp = tempdir()?;
+        let mut config = Config::default();
+        config.indexer.data_dir = temp.path().join("data");
+        config.server.max_open_workspaces = 2;
+        let app = index_workspace(&temp.path().join("app"), "fn in_app() {}", &config)?;
+        for name in ["lib", "tools"] {
+            index_workspace(&temp.path().join(name), "fn in_other() {}", &config)?;
+        }
+        let roots = workspaces::indexed(&config);
+        assert_eq!(roots.len(), 3);
+        let addr = start(Workspaces::new(Some(app), roots, &config), false, &config)?;
+
+        let hit = |target: &str| {
+            let (status, body) = send(&addr, "GET", target, "");
+            assert_eq!(status, 200, "{}", body);
+            let result: Value = serde_json::from_str(&body).unwrap();
+            result["hits"][0]["abs_path"]
+                .as_str()
+                .unwrap_or("")
+                .to_string()
+        };
+        assert!(hit("/search?q=in_app").ends_with("app/hello.rs"));
+        assert!(hit("/search?q=in_other&workspace=lib").ends_with("lib/hello.rs"));
+        assert!(hit("/w/tools/search?q=in_other").ends_with("tools/hello.rs"));
+        assert!(hit("/search?q=in_other&workspace=lib").ends_with("lib/hello.rs"));
+        assert_eq!(send(&addr, "GET", "/search?q=x&workspace=nope", "").0, 404);
+
+        // Only `max_open_workspaces` stay open: `tools` was closed for `lib`
+        let (_, body) = send(&addr, "GET", "/workspaces", "");
+        let listed: Value = serde_json::from_str(&body)?;
+        let open: Vec<&str> = listed["workspaces"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|ws| ws["open"] == true)
+            .map(|ws| ws["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(open, vec!["app", "lib"]);
+
+        let rpc = |target: &str, method: &str, params: Value| {
+            let message = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+            let (_, body) = send(&addr, "POST", target, &message.to_string());
+            serde_json::from_str::<Value>(&body).unwrap()
+        };
+        let tools = rpc("/mcp", "tools/list", json!({}));
+        let choices = &tools["result"]["tools"][0]["inputSchema"]["properties"]["workspace"];
+        assert_eq!(choices["enum"], json!(["app", "lib", "tools"]));
+        let tools = rpc("/w/lib/mcp", "tools/list", json!({}));
+        assert!(tools["result"]["tools"][0]["inputSchema"]["properties"]["workspace"].is_null());
+        let call = rpc(
+            "/mcp",
+            "tools/call",
+            json!({ "name": "search", "arguments": { "query": "in_other", "workspace": "tools" } }),
+        );
+        let text = call["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("hello.rs"), "{}", text);
+        Ok(())
+    }
+}
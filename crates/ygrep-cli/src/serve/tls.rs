@@ -0,0 +1,47 @@
+//! HTTPS for the server (`server.tls_cert` and `server.tls_key`)
+
+use anyhow::{Context, Result};
+use rustls::pki_types::CertificateDer;
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use std::fs::File;
+use std::io::BufReader;
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::Arc;
+
+/// TLS settings connections are accepted with
+pub(super) struct Tls(Arc<ServerConfig>);
+
+impl Tls {
+    /// Load the PEM certificate chain and private key
+    pub fn load(cert: &Path, key: &Path) -> Result<Self> {
+        let open = |path: &Path| {
+            File::open(path)
+                .map(BufReader::new)
+                .with_context(|| format!("Failed to open {}", path.display()))
+        };
+        let certs = rustls_pemfile::certs(&mut open(cert)?)
+            .collect::<std::result::Result<Vec<CertificateDer>, _>>()
+            .with_context(|| format!("Invalid certificate in {}", cert.display()))?;
+        if certs.is_empty() {
+            anyhow::bail!("No certificate found in {}", cert.display());
+        }
+        let key = rustls_pemfile::private_key(&mut open(key)?)
+            .with_context(|| format!("Invalid private key in {}", key.display()))?
+            .with_context(|| format!("No private key found in {}", key.display()))?;
+
+        let config =
+            ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()?
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .context("Invalid certificate or private key")?;
+        Ok(Self(Arc::new(config)))
+    }
+
+    /// Wrap an accepted connection; the handshake happens on first read
+    pub fn accept(&self, stream: TcpStream) -> Result<StreamOwned<ServerConnection, TcpStream>> {
+        let connection = ServerConnection::new(Arc::clone(&self.0))?;
+        Ok(StreamOwned::new(connection, stream))
+    }
+}
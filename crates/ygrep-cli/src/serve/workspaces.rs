@@ -0,0 +1,250 @@
+//! The workspaces a server hosts (`server.workspaces`, `ygrep serve --all`), routed by
+//! name and kept open up to `server.max_open_workspaces`, the least recently used closed
+//! first
+
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use ygrep_core::{Config, Workspace};
+
+/// A workspace the server can search
+struct Hosted {
+    /// The shortest trailing part of the root that tells it apart from the others
+    name: String,
+    root: PathBuf,
+}
+
+struct OpenWorkspace {
+    name: String,
+    last_used: Instant,
+    workspace: Arc<Workspace>,
+}
+
+pub(super) struct Workspaces {
+    config: Config,
+    /// The default workspace first, if any
+    hosted: Vec<Hosted>,
+    /// Searched when a request names no workspace; always open
+    default: Option<Arc<Workspace>>,
+    max_open: usize,
+    open: Mutex<Vec<OpenWorkspace>>,
+}
+
+/// Why a request's workspace can't be searched
+pub(super) enum WorkspaceError {
+    /// No hosted workspace has this name or root
+    Unknown(String),
+    /// The request names no workspace and there is no default
+    Unspecified,
+    /// The workspace failed to open
+    Open(String, String),
+}
+
+impl WorkspaceError {
+    pub fn status(&self) -> u16 {
+        match self {
+            WorkspaceError::Unknown(_) => 404,
+            WorkspaceError::Unspecified => 400,
+            WorkspaceError::Open(..) => 500,
+        }
+    }
+
+    pub fn message(&self, workspaces: &Workspaces) -> String {
+        match self {
+            WorkspaceError::Unknown(name) => format!(
+                "Unknown workspace '{}' (workspaces: {})",
+                name,
+                workspaces.names().join(", ")
+            ),
+            WorkspaceError::Unspecified => format!(
+                "Specify a workspace (`workspace`): {}",
+                workspaces.names().join(", ")
+            ),
+            WorkspaceError::Open(name, e) => format!("Failed to open workspace '{}': {}", name, e),
+        }
+    }
+}
+
+impl Workspaces {
+    /// Host `default` and the workspaces at `roots` (which are opened when first searched)
+    pub fn new(default: Option<Workspace>, roots: Vec<PathBuf>, config: &Config) -> Self {
+        let mut all: Vec<PathBuf> = default
+            .iter()
+            .map(|workspace| workspace.root().to_path_buf())
+            .collect();
+        for root in roots {
+            let root = std::fs::canonicalize(&root).unwrap_or(root);
+            if !all.contains(&root) {
+                all.push(root);
+            }
+        }
+        let hosted = names(&all)
+            .into_iter()
+            .zip(all)
+            .map(|(name, root)| Hosted { name, root })
+            .collect();
+        Self {
+            config: config.clone(),
+            hosted,
+            default: default.map(Arc::new),
+            max_open: config.server.max_open_workspaces.max(1),
+            open: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn default(&self) -> Option<&Arc<Workspace>> {
+        self.default.as_ref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.hosted.len()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.hosted
+            .iter()
+            .map(|hosted| hosted.name.clone())
+            .collect()
+    }
+
+    /// The workspace called `name` (or with that root), or the default one
+    pub fn get(&self, name: Option<&str>) -> Result<Arc<Workspace>, WorkspaceError> {
+        let Some(name) = name else {
+            return self.default.clone().ok_or(WorkspaceError::Unspecified);
+        };
+        let hosted = self
+            .hosted
+            .iter()
+            .find(|hosted| hosted.name == name || hosted.root == Path::new(name))
+            .ok_or_else(|| WorkspaceError::Unknown(name.to_string()))?;
+        if let Some(default) = &self.default {
+            if default.root() == hosted.root {
+                return Ok(Arc::clone(default));
+            }
+        }
+
+        {
+            let mut open = self.open.lock();
+            if let Some(open) = open.iter_mut().find(|open| open.name == hosted.name) {
+                open.last_used = Instant::now();
+                return Ok(Arc::clone(&open.workspace));
+            }
+        }
+
+        // Opening can take a while (vector index), so other searches go on meanwhile
+        let workspace = Workspace::open_with_config(&hosted.root, self.config.clone())
+            .map_err(|e| WorkspaceError::Open(hosted.name.clone(), e.to_string()))?;
+        let workspace = Arc::new(workspace);
+        let mut open = self.open.lock();
+        open.retain(|open| open.name != hosted.name);
+        // The default workspace counts towards the limit but is never closed
+        let max = match self.default {
+            Some(_) => self.max_open.saturating_sub(1).max(1),
+            None => self.max_open,
+        };
+        while open.len() >= max {
+            let Some(oldest) = open
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, open)| open.last_used)
+                .map(|(i, _)| i)
+            else {
+                break;
+            };
+            open.remove(oldest);
+        }
+        open.push(OpenWorkspace {
+            name: hosted.name.clone(),
+            last_used: Instant::now(),
+            workspace: Arc::clone(&workspace),
+        });
+        Ok(workspace)
+    }
+
+    /// Each workspace's name, root and whether it is open
+    pub fn describe(&self) -> Vec<Value> {
+        let open = self.open.lock();
+        self.hosted
+            .iter()
+            .map(|hosted| {
+                let is_default = self
+                    .default
+                    .as_ref()
+                    .is_some_and(|default| default.root() == hosted.root);
+                json!({
+                    "name": hosted.name,
+                    "root": hosted.root,
+                    "default": is_default,
+                    "open": is_default || open.iter().any(|open| open.name == hosted.name),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Roots of the workspaces indexed in the data directory that still exist
+pub(crate) fn indexed(config: &Config) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(config.indexer.data_dir.join("indexes")) else {
+        return Vec::new();
+    };
+    let mut roots: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("workspace.json")).ok())
+        .filter_map(|json| serde_json::from_str::<Value>(&json).ok())
+        .filter_map(|metadata| metadata.get("workspace")?.as_str().map(PathBuf::from))
+        .filter(|root| root.is_dir())
+        .collect();
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// A name for each root: its last path components, as few as tell it apart from the
+/// others (`api`, or `work/api` and `oss/api`)
+fn names(roots: &[PathBuf]) -> Vec<String> {
+    let suffix = |root: &Path, depth: usize| {
+        let mut parts: Vec<String> = root
+            .components()
+            .rev()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .take(depth)
+            .collect();
+        parts.reverse();
+        parts.join("/")
+    };
+    roots
+        .iter()
+        .map(|root| {
+            let depth = root.components().count();
+            (1..=depth)
+                .map(|depth| (depth, suffix(root, depth)))
+                .find(|(depth, name)| {
+                    !name.is_empty()
+                        && roots
+                            .iter()
+                            .all(|other| other == root || suffix(other, *depth) != *name)
+                })
+                .map(|(_, name)| name)
+                .unwrap_or_else(|| root.display().to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_names() {
+        let roots: Vec<PathBuf> = ["/src/work/api", "/src/oss/api", "/src/web", "/"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(names(&roots), vec!["work/api", "oss/api", "web", "/"]);
+    }
+}
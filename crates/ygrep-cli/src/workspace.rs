@@ -6,13 +6,14 @@
 
 use std::path::{Path, PathBuf};
 use xxhash_rust::xxh3::xxh3_64;
+use ygrep_core::fs::workspace_key;
 
 /// Maximum depth for parent directory search
 const MAX_PARENT_DEPTH: usize = 10;
 
 /// Calculate workspace hash using xxh3_64 (same algorithm as core)
 pub fn hash_workspace_path(path: &Path) -> String {
-    let hash = xxh3_64(path.to_string_lossy().as_bytes());
+    let hash = xxh3_64(workspace_key(path).as_bytes());
     format!("{:016x}", hash)
 }
 
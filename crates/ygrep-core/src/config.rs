@@ -1,8 +1,14 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::fs::FileKind;
+use crate::hooks::HookKind;
+use crate::index::SecretHandling;
+use crate::search::RegexEngine;
 
 /// Global ygrep configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
     /// Daemon configuration
@@ -16,6 +22,15 @@ pub struct Config {
 
     /// Output formatting
     pub output: OutputConfig,
+
+    /// HTTP/MCP server (`ygrep serve`)
+    pub server: ServerConfig,
+
+    /// Shell commands run on workspace events (`hooks`)
+    pub hooks: HooksConfig,
+
+    /// Logging (`--log-file`, `--log-format`)
+    pub log: LogConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,32 +52,148 @@ pub struct IndexerConfig {
     /// Base directory for all index data
     pub data_dir: PathBuf,
 
+    /// Directory for what can be downloaded or built again: embedding models and
+    /// `ygrep model bench` scratch indexes (see `layout`)
+    pub cache_dir: PathBuf,
+
     /// Maximum file size to index (bytes)
     pub max_file_size: u64,
 
+    /// Index the first this many KB of files over `max_file_size` instead of skipping
+    /// them, so large generated files still turn up in searches; their documents and hits
+    /// are marked `truncated`
+    pub oversized_head_kb: Option<u64>,
+
+    /// Maximum size of an index on disk (bytes). When the embeddings wouldn't fit next to
+    /// the text index, the least valuable files (generated, rarely changed, largest) are
+    /// left out of the semantic index.
+    pub max_index_size: Option<u64>,
+
+    /// Memory cap for indexing (MB). Near the cap the text index is flushed early, file
+    /// contents kept for embedding are trimmed to what gets embedded, and embedding
+    /// batches shrink.
+    pub max_memory_mb: Option<u64>,
+
+    /// Index in the background: lower CPU and IO priority, a pause after each file and
+    /// fewer embedding threads (also `--throttle`)
+    pub background_priority: bool,
+
+    /// Indexing a workspace again only reads the files whose size or modification time
+    /// changed (and whose content hash then differs), and drops the documents of files that
+    /// were removed; off, every file is indexed again (also `ygrep index --full`). Import
+    /// counts are kept from the last full index.
+    pub incremental: bool,
+
     /// File extensions to include (empty = all text files)
     pub include_extensions: Vec<String>,
 
+    /// File name patterns that are always indexed, even without a known extension or
+    /// outside `include_extensions` (e.g. `Dockerfile*`, `*.config`; patterns with a `/`
+    /// match the path relative to the workspace root)
+    pub index_filenames: Vec<String>,
+
     /// Additional ignore patterns (glob syntax)
     pub ignore_patterns: Vec<String>,
 
     /// Follow symlinks
     pub follow_symlinks: bool,
 
-    /// Respect .gitignore files (default: false for code search)
+    /// Leave generated and minified files (`@generated`/`DO NOT EDIT` headers, source map
+    /// references, bundles) out of the index instead of down-ranking them
+    pub skip_generated: bool,
+
+    /// Index the files of git submodules (listed in `.gitmodules`) along with the
+    /// workspace; their documents are tagged with the submodule for `--submodule`
+    pub index_submodules: bool,
+
+    /// Submodules (by name or path) left out of the index even with `index_submodules`
+    pub skip_submodules: Vec<String>,
+
+    /// Scan for credentials (known key formats, high-entropy values): "off", "skip" (leave
+    /// files with secrets out) or "redact" (replace them with `[REDACTED]`)
+    pub secrets: SecretHandling,
+
+    /// Index dotfiles and dot-directories (`.github`, `.config`, `.vscode`); version
+    /// control directories such as `.git` are always skipped
+    pub include_hidden: bool,
+
+    /// Honor `.gitignore` and `.ignore` files at every level of the tree, as ripgrep does
+    /// (`.ygrepignore` files are honored either way)
     pub respect_gitignore: bool,
 
     /// Enable content deduplication
     pub deduplicate: bool,
 
-    /// Chunk size for semantic indexing (lines)
+    /// Files longer than this many lines are also indexed as chunks of this many lines,
+    /// each a hit of its own with its line range and embedded on its own
     pub chunk_size: usize,
 
-    /// Chunk overlap (lines)
+    /// Lines each chunk repeats from the end of the previous one, so code around a chunk
+    /// boundary is whole in one of them (less than `chunk_size`)
     pub chunk_overlap: usize,
 
+    /// Chunk Rust, Python, JavaScript, TypeScript and Go files along their functions,
+    /// classes and impl blocks instead of line windows, and embed each of those chunks
+    /// rather than the whole file (builds with the `treesitter` feature). Takes effect on
+    /// the next full index.
+    pub syntax_chunks: bool,
+
+    /// Embedding model for semantic indexing: "all-minilm-l6-v2" or "bge-small-en-v1.5"
+    /// (`ygrep model bench` compares them). An existing semantic index keeps the model it
+    /// was built with until `ygrep index --rebuild`.
+    pub embedding_model: String,
+
+    /// Memory for embeddings kept between searches and while indexing (identical chunks
+    /// and repeated queries aren't embedded again), in MB; 0 disables the cache
+    pub embedding_cache_mb: usize,
+
+    /// Also index every line as its own document, so literal and regex searches return
+    /// each matching line with its exact line and column, like grep. The index gets
+    /// several times larger. Takes effect on the next full index.
+    pub line_index: bool,
+
+    /// Count how often each file is imported or included by other files (Rust, Python,
+    /// JavaScript/TypeScript, C/C++, Go, Java/Kotlin, Ruby), so ranking can favor widely
+    /// used modules (`search.reference_weight`). Reads every file once more; takes
+    /// effect on the next full index.
+    pub reference_counts: bool,
+
+    /// Also index prose files (`stem_extensions`) with stemmed words, so `searching` finds
+    /// `search` in documentation; code keeps exact tokens. Takes effect on the next full
+    /// index.
+    pub stemming: bool,
+
+    /// Extensions of the files stemmed with `stemming`
+    pub stem_extensions: Vec<String>,
+
+    /// Stemmer language: "english", "german", "french", "spanish", "russian", ...
+    pub stem_language: String,
+
+    /// Number of shards the vector index is split into (by document hash). Shards are
+    /// built, saved and loaded separately and searched in parallel; more than one helps
+    /// workspaces with millions of chunks. Takes effect on the next full index.
+    pub vector_shards: usize,
+
+    /// Distance the vector index ranks embeddings by: "dot" (vectors are normalized once
+    /// when inserted, so each comparison is a plain dot product) or "cosine". An
+    /// existing semantic index keeps its distance until the next full index.
+    pub vector_distance: VectorDistance,
+
+    /// Memory-map the saved vectors instead of reading them into memory, so a large
+    /// semantic index opens almost at once and processes searching it share the pages
+    pub mmap_vectors: bool,
+
     /// Number of indexing threads
     pub threads: usize,
+
+    /// Search an index published with `ygrep indexes publish` instead of indexing here:
+    /// `s3://bucket/prefix`, `gs://bucket/prefix` (with the `object-storage` feature) or a
+    /// directory. It is synced into the local index directory before searching; an index
+    /// built locally takes precedence.
+    pub remote_index: Option<String>,
+
+    /// Check the published index for updates at most this often (seconds)
+    pub remote_refresh_secs: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,6 +205,24 @@ pub struct SearchConfig {
     /// Vector weight in hybrid search (0.0-1.0)
     pub vector_weight: f32,
 
+    /// BM25 term-frequency saturation (Tantivy's default is 1.2)
+    pub bm25_k1: f32,
+
+    /// BM25 document-length normalization, 0.0-1.0 (Tantivy's default is 0.75)
+    pub bm25_b: f32,
+
+    /// Words ignored when ranking text matches (e.g. "the", "and" for prose-heavy repos);
+    /// they still have to match literally
+    pub stopwords: Vec<String>,
+
+    /// Score multipliers by file kind (source, test, config, docs, generated); kinds not
+    /// listed keep their score
+    pub kind_weights: BTreeMap<FileKind, f32>,
+
+    /// Largest score boost for files other files import, reached at 100 importers
+    /// (`indexer.reference_counts`); 0.1 boosts them by up to 10%, 0 disables it
+    pub reference_weight: f32,
+
     /// Default result limit
     pub default_limit: usize,
 
@@ -88,6 +237,96 @@ pub struct SearchConfig {
 
     /// Fuzzy distance (1-2)
     pub fuzzy_distance: u8,
+
+    /// Scan the full match set so facets cover every match, not just the returned page
+    pub facets: bool,
+
+    /// Attach a score breakdown to every hit
+    pub explain: bool,
+
+    /// Tell chunk hits' place in their file: which chunk of how many and the lines it
+    /// covers
+    pub chunk_context: bool,
+
+    /// Lines of the indexed file added before and after each hit's snippet, replacing it
+    /// (`--expand`; 0 leaves snippets as they are)
+    pub expand_lines: usize,
+
+    /// Expand abbreviations and synonyms (cfg/config, db/database) in the BM25 side of hybrid search
+    pub expand_synonyms: bool,
+
+    /// Extra synonym groups, one comma-separated group per line
+    /// (defaults to ~/.config/ygrep/synonyms.txt)
+    pub synonyms_file: Option<PathBuf>,
+
+    /// Regex engine for regex searches ("default" or "fancy")
+    pub regex_engine: RegexEngine,
+
+    /// Stop a regex scan after this many milliseconds (unlimited by default, so regex
+    /// searches cover every document)
+    pub regex_timeout_ms: Option<u64>,
+
+    /// Stop a regex scan after reading this many documents (unlimited by default)
+    pub regex_max_scanned: Option<usize>,
+
+    /// Match case-insensitively unless the query contains an uppercase letter
+    pub smart_case: bool,
+
+    /// Ignore diacritics in literal matching (`cafe` matches `café`)
+    pub fold_diacritics: bool,
+
+    /// Match identifiers across case conventions (`getUserId` matches `get_user_id`)
+    pub identifier_matching: bool,
+
+    /// Read `-term` and `!term` in queries as exclusions; off, they are searched for
+    /// literally (`rm -rf`, `if !ready`)
+    pub negation: bool,
+
+    /// Maximum lines in a result snippet
+    pub snippet_lines: usize,
+
+    /// Lines of context shown before the first matching line
+    pub context_before: usize,
+
+    /// Lines of context shown after the first matching line (defaults to the rest of the snippet)
+    pub context_after: Option<usize>,
+
+    /// Snippet lines shown per hit in pretty output
+    pub preview_lines: usize,
+
+    /// Truncate preview lines after this many bytes (defaults to 100 for AI output and
+    /// 80 for pretty output; 0 disables truncation)
+    pub max_line_length: Option<usize>,
+
+    /// Maximum hits a single file may contribute (unlimited by default)
+    pub max_per_file: Option<usize>,
+
+    /// Approximate token budget for AI output: hits get full snippets until it runs
+    /// low, then path-only lines (unlimited by default)
+    pub max_tokens: Option<usize>,
+
+    /// Build a text-only index before searching a workspace that hasn't been indexed
+    pub auto_index: bool,
+
+    /// Before searching, re-index files changed since the last (re)index (git workspaces only)
+    pub auto_refresh: bool,
+
+    /// Skip the auto-refresh check if the index was updated less than this many seconds ago
+    pub refresh_interval_secs: u64,
+
+    /// Search a workspace that hasn't been indexed by reading its files directly, instead
+    /// of failing
+    pub fallback_scan: bool,
+
+    /// Files read at most by a fallback scan
+    pub scan_max_files: usize,
+
+    /// From a subdirectory of a workspace, only search files under it (the workspace's
+    /// index is still used)
+    pub scope_cwd: bool,
+
+    /// Words `ygrep todos` looks for (whole words, case-sensitive)
+    pub todo_markers: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -109,13 +348,63 @@ pub struct OutputConfig {
     pub show_scores: bool,
 }
 
-impl Default for Config {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Searches each client (by IP address) may run per minute; 0 = unlimited
+    pub requests_per_minute: u32,
+
+    /// Searches a client may run back to back before `requests_per_minute` applies
+    pub burst: u32,
+
+    /// Searches run at once; others wait for a slot (default: half the CPUs), so a busy
+    /// client can't take the CPU from other clients or the file watcher
+    pub max_concurrent_queries: usize,
+
+    /// How long a search waits for a slot before it is refused (milliseconds)
+    pub queue_timeout_ms: u64,
+
+    /// Connections open at once; more are closed without an answer
+    pub max_connections: usize,
+
+    /// How long a client may take to send its request or read the response
+    /// (milliseconds), so stalled connections don't hold on to their slot
+    pub request_timeout_ms: u64,
+
+    /// API keys accepted as `Authorization: Bearer <token>` (or `X-API-Key`); empty with
+    /// `YGREP_SERVER_TOKENS` unset = no authentication
+    pub tokens: Vec<String>,
+
+    /// PEM certificate chain to serve HTTPS with (needs `tls_key`)
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key for `tls_cert`
+    pub tls_key: Option<PathBuf>,
+
+    /// Other workspaces to serve besides the current one (also `ygrep serve --all`),
+    /// chosen per request by name
+    pub workspaces: Vec<PathBuf>,
+
+    /// Workspaces kept open at once; the least recently searched is closed first
+    pub max_open_workspaces: usize,
+}
+
+impl Default for ServerConfig {
     fn default() -> Self {
         Self {
-            daemon: DaemonConfig::default(),
-            indexer: IndexerConfig::default(),
-            search: SearchConfig::default(),
-            output: OutputConfig::default(),
+            requests_per_minute: 120,
+            burst: 20,
+            max_concurrent_queries: std::thread::available_parallelism()
+                .map(|cpus| (cpus.get() / 2).max(1))
+                .unwrap_or(2),
+            queue_timeout_ms: 10_000,
+            max_connections: 64,
+            request_timeout_ms: 30_000,
+            tokens: Vec::new(),
+            tls_cert: None,
+            tls_key: None,
+            workspaces: Vec::new(),
+            max_open_workspaces: 8,
         }
     }
 }
@@ -134,8 +423,24 @@ impl Default for IndexerConfig {
     fn default() -> Self {
         Self {
             data_dir: default_data_dir(),
+            cache_dir: default_cache_dir(),
             max_file_size: 10 * 1024 * 1024, // 10MB
+            oversized_head_kb: None,
+            max_index_size: None,
+            max_memory_mb: None,
+            background_priority: false,
+            incremental: true,
             include_extensions: vec![],
+            index_filenames: vec![
+                "Dockerfile*".into(),
+                "Containerfile*".into(),
+                "Makefile".into(),
+                "GNUmakefile".into(),
+                "Justfile".into(),
+                "Jenkinsfile".into(),
+                "Vagrantfile".into(),
+                "Procfile".into(),
+            ],
             ignore_patterns: vec![
                 // Package managers & dependencies
                 "**/node_modules/**".into(),
@@ -253,13 +558,33 @@ impl Default for IndexerConfig {
                 "**/*.map".into(),
             ],
             follow_symlinks: true,
-            respect_gitignore: false,
+            include_hidden: false,
+            skip_generated: false,
+            index_submodules: true,
+            skip_submodules: Vec::new(),
+            secrets: SecretHandling::Off,
+            respect_gitignore: true,
             deduplicate: true,
             chunk_size: 50,
             chunk_overlap: 10,
+            syntax_chunks: true,
+            embedding_model: "all-minilm-l6-v2".to_string(),
+            embedding_cache_mb: 100,
+            line_index: false,
+            reference_counts: false,
+            stemming: false,
+            stem_extensions: ["md", "markdown", "mdx", "rst", "txt", "adoc", "org"]
+                .map(String::from)
+                .to_vec(),
+            stem_language: "english".to_string(),
+            vector_shards: 1,
+            vector_distance: VectorDistance::default(),
+            mmap_vectors: true,
             threads: std::thread::available_parallelism()
                 .map(|n| n.get().min(4))
                 .unwrap_or(2),
+            remote_index: None,
+            remote_refresh_secs: 300,
         }
     }
 }
@@ -269,11 +594,43 @@ impl Default for SearchConfig {
         Self {
             bm25_weight: 0.5,
             vector_weight: 0.5,
+            bm25_k1: 1.2,
+            bm25_b: 0.75,
+            stopwords: Vec::new(),
+            kind_weights: BTreeMap::from([(FileKind::Test, 0.8), (FileKind::Generated, 0.25)]),
+            reference_weight: 0.1,
             default_limit: 10,
             max_limit: 100,
             min_score: 0.1,
             fuzzy_enabled: true,
             fuzzy_distance: 1,
+            facets: false,
+            explain: false,
+            chunk_context: false,
+            expand_lines: 0,
+            expand_synonyms: false,
+            synonyms_file: None,
+            regex_engine: RegexEngine::default(),
+            regex_timeout_ms: None,
+            regex_max_scanned: None,
+            smart_case: false,
+            fold_diacritics: false,
+            identifier_matching: false,
+            negation: false,
+            snippet_lines: 10,
+            context_before: 2,
+            context_after: None,
+            preview_lines: 3,
+            max_line_length: None,
+            max_per_file: None,
+            max_tokens: None,
+            auto_index: false,
+            auto_refresh: false,
+            refresh_interval_secs: 30,
+            fallback_scan: false,
+            scope_cwd: false,
+            scan_max_files: 10_000,
+            todo_markers: ["TODO", "FIXME", "HACK", "XXX"].map(String::from).to_vec(),
         }
     }
 }
@@ -290,38 +647,163 @@ impl Default for OutputConfig {
     }
 }
 
+/// Where and how ygrep logs; `YGREP_DEBUG` still turns on debug logging
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Log to this file instead of stderr (appended to)
+    pub file: Option<PathBuf>,
+
+    /// `text` or `json` (one object per line)
+    pub format: LogFormat,
+
+    /// Start a new log file every hour or day (`file.YYYY-MM-DD-HH`, `file.YYYY-MM-DD`),
+    /// or never, leaving rotation to e.g. logrotate
+    pub rotation: LogRotation,
+
+    /// Rotated log files kept; older ones are deleted (all kept by default)
+    pub max_files: Option<usize>,
+
+    /// Level for everything not listed in `modules`: error, warn, info, debug or trace
+    pub level: String,
+
+    /// Levels by module, e.g. `"ygrep_core::index" = "debug"`
+    pub modules: BTreeMap<String, String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            format: LogFormat::Text,
+            rotation: LogRotation::Never,
+            max_files: None,
+            level: "warn".to_string(),
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+impl LogConfig {
+    /// Filter directives for the levels (`warn,ygrep_core::index=debug`), with `level`
+    /// in place of the configured one when given
+    pub fn directives(&self, level: Option<&str>) -> String {
+        let mut directives = vec![level.unwrap_or(&self.level).to_string()];
+        for (module, level) in &self.modules {
+            directives.push(format!("{}={}", module, level));
+        }
+        directives.join(",")
+    }
+}
+
+/// How log lines are written (`log.format`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// A JSON object per line, for log collectors
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = crate::error::YgrepError;
+
+    fn from_str(s: &str) -> crate::error::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(crate::error::YgrepError::Config(format!(
+                "Unknown log format '{}' (expected 'text' or 'json')",
+                other
+            ))),
+        }
+    }
+}
+
+/// When a new log file is started (`log.rotation`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    #[default]
+    Never,
+    Hourly,
+    Daily,
+}
+
+/// Shell commands run on workspace events; see `hooks` for what they are passed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// After a full index (`ygrep index`)
+    pub on_index_complete: Option<String>,
+
+    /// After a file is indexed again or removed (watcher, daemon, auto-refresh)
+    pub on_file_indexed: Option<String>,
+
+    /// After each search
+    pub on_search: Option<String>,
+}
+
+impl HooksConfig {
+    /// The command for events of this kind
+    pub fn command(&self, kind: HookKind) -> Option<&str> {
+        match kind {
+            HookKind::IndexComplete => self.on_index_complete.as_deref(),
+            HookKind::FileIndexed => self.on_file_indexed.as_deref(),
+            HookKind::Search => self.on_search.as_deref(),
+        }
+        .filter(|command| !command.trim().is_empty())
+    }
+}
+
 fn default_data_dir() -> PathBuf {
     dirs::data_dir()
         .unwrap_or_else(|| PathBuf::from("~/.local/share"))
         .join("ygrep")
 }
 
+fn default_cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("~/.cache"))
+        .join("ygrep")
+}
+
 impl Config {
     /// Load config from default locations (in order of precedence):
     /// 1. $PWD/.ygrep.toml
     /// 2. $XDG_CONFIG_HOME/ygrep/config.toml
     /// 3. ~/.config/ygrep/config.toml
     /// 4. Built-in defaults
+    ///
+    /// `[hooks]` only come from the user-level config: a repository's `.ygrep.toml` can't
+    /// make ygrep run commands.
     pub fn load() -> Self {
-        // Try project-level config
-        if let Ok(content) = std::fs::read_to_string(".ygrep.toml") {
-            if let Ok(config) = toml::from_str(&content) {
-                return config;
-            }
-        }
+        let user_config = dirs::config_dir().map(|dir| dir.join("ygrep").join("config.toml"));
+        Self::load_layers(Path::new(".ygrep.toml"), user_config.as_deref())
+    }
 
-        // Try user-level config
-        if let Some(config_dir) = dirs::config_dir() {
-            let config_path = config_dir.join("ygrep").join("config.toml");
-            if let Ok(content) = std::fs::read_to_string(&config_path) {
-                if let Ok(config) = toml::from_str(&content) {
-                    return config;
-                }
-            }
+    /// The `project` config if there is one (with the hooks of the `user` config), else the
+    /// `user` config, else defaults
+    fn load_layers(project: &Path, user: Option<&Path>) -> Self {
+        let read = |path: &Path| -> Option<Self> {
+            toml::from_str(&std::fs::read_to_string(path).ok()?).ok()
+        };
+        let user = user.and_then(read);
+        let Some(mut config) = read(project) else {
+            return user.unwrap_or_default();
+        };
+        if config.hooks.on_index_complete.is_some()
+            || config.hooks.on_file_indexed.is_some()
+            || config.hooks.on_search.is_some()
+        {
+            tracing::warn!(
+                "Ignoring [hooks] in {}: hooks are only read from the user config",
+                project.display()
+            );
         }
-
-        // Fall back to defaults
-        Self::default()
+        config.hooks = user.map(|user| user.hooks).unwrap_or_default();
+        config
     }
 
     /// Load config from a specific file
@@ -350,6 +832,17 @@ fn default_socket_path() -> PathBuf {
     }
 }
 
+/// How the vector index compares embeddings (`indexer.vector_distance`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorDistance {
+    /// Dot product of L2-normalized vectors (same ranking as cosine, less work)
+    #[default]
+    Dot,
+    /// Cosine similarity, normalizing both vectors on every comparison
+    Cosine,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
     #[error("Failed to read config file: {0}")]
@@ -358,3 +851,37 @@ pub enum ConfigError {
     #[error("Failed to parse config: {0}")]
     Parse(#[from] toml::de::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_config_hooks_ignored() {
+        let temp = tempfile::tempdir().unwrap();
+        let project = temp.path().join(".ygrep.toml");
+        let user = temp.path().join("config.toml");
+        std::fs::write(
+            &project,
+            "[search]\ndefault_limit = 7\n\n[hooks]\non_search = \"touch pwned\"\n",
+        )
+        .unwrap();
+
+        // The rest of the project config applies, its hooks don't
+        let config = Config::load_layers(&project, None);
+        assert_eq!(config.search.default_limit, 7);
+        assert!(config.hooks.on_search.is_none());
+
+        // The user's hooks stay
+        std::fs::write(&user, "[hooks]\non_index_complete = \"notify-send done\"\n").unwrap();
+        let config = Config::load_layers(&project, Some(&user));
+        assert_eq!(config.search.default_limit, 7);
+        assert!(config.hooks.on_search.is_none());
+        assert_eq!(
+            config.hooks.on_index_complete.as_deref(),
+            Some("notify-send done")
+        );
+        let config = Config::load_layers(&temp.path().join("missing.toml"), Some(&user));
+        assert!(config.hooks.on_index_complete.is_some());
+    }
+}
@@ -5,39 +5,48 @@ use parking_lot::Mutex;
 use std::num::NonZeroUsize;
 use xxhash_rust::xxh3::xxh3_64;
 
+use crate::search::EmbeddingCacheStats;
+
 /// LRU cache for computed embeddings
 pub struct EmbeddingCache {
-    cache: Mutex<LruCache<u64, Vec<f32>>>,
+    /// `None` when disabled (`indexer.embedding_cache_mb = 0`)
+    cache: Option<Mutex<LruCache<u64, Vec<f32>>>>,
+    capacity_mb: usize,
     hits: std::sync::atomic::AtomicU64,
     misses: std::sync::atomic::AtomicU64,
+    evictions: std::sync::atomic::AtomicU64,
 }
 
 impl EmbeddingCache {
     /// Create a new embedding cache
     ///
     /// # Arguments
-    /// * `capacity_mb` - Maximum cache size in megabytes
+    /// * `capacity_mb` - Maximum cache size in megabytes (0 disables the cache: every
+    ///   lookup misses and nothing is kept)
     /// * `dimension` - Embedding dimension (to calculate entry size)
     pub fn new(capacity_mb: usize, dimension: usize) -> Self {
         // Calculate number of embeddings that fit in cache
         // Each embedding is dimension * 4 bytes (f32)
-        let embedding_size = dimension * std::mem::size_of::<f32>();
+        let embedding_size = dimension.max(1) * std::mem::size_of::<f32>();
         let capacity = (capacity_mb * 1024 * 1024) / embedding_size;
-        let capacity = NonZeroUsize::new(capacity.max(100)).unwrap();
+        let cache = (capacity_mb > 0)
+            .then(|| Mutex::new(LruCache::new(NonZeroUsize::new(capacity.max(100)).unwrap())));
 
         Self {
-            cache: Mutex::new(LruCache::new(capacity)),
+            cache,
+            capacity_mb,
             hits: std::sync::atomic::AtomicU64::new(0),
             misses: std::sync::atomic::AtomicU64::new(0),
+            evictions: std::sync::atomic::AtomicU64::new(0),
         }
     }
 
     /// Get an embedding from cache
     pub fn get(&self, text: &str) -> Option<Vec<f32>> {
         let key = xxh3_64(text.as_bytes());
-        let mut cache = self.cache.lock();
+        let mut cache = self.cache.as_ref().map(|cache| cache.lock());
 
-        if let Some(embedding) = cache.get(&key) {
+        if let Some(embedding) = cache.as_mut().and_then(|cache| cache.get(&key)) {
             self.hits.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
             Some(embedding.clone())
         } else {
@@ -49,9 +58,17 @@ impl EmbeddingCache {
 
     /// Insert an embedding into cache
     pub fn insert(&self, text: &str, embedding: Vec<f32>) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
         let key = xxh3_64(text.as_bytes());
-        let mut cache = self.cache.lock();
-        cache.put(key, embedding);
+        // `push` hands back the least recently used entry when the cache was full
+        if let Some((evicted, _)) = cache.lock().push(key, embedding) {
+            if evicted != key {
+                self.evictions
+                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
     }
 
     /// Get or compute an embedding
@@ -71,38 +88,42 @@ impl EmbeddingCache {
     }
 
     /// Get cache statistics
-    pub fn stats(&self) -> CacheStats {
+    pub fn stats(&self) -> EmbeddingCacheStats {
         let hits = self.hits.load(std::sync::atomic::Ordering::Relaxed);
         let misses = self.misses.load(std::sync::atomic::Ordering::Relaxed);
         let total = hits + misses;
-
-        CacheStats {
+        let (entries, capacity) = self
+            .cache
+            .as_ref()
+            .map(|cache| {
+                let cache = cache.lock();
+                (cache.len(), cache.cap().get())
+            })
+            .unwrap_or_default();
+
+        EmbeddingCacheStats {
             hits,
             misses,
+            evictions: self.evictions.load(std::sync::atomic::Ordering::Relaxed),
             hit_rate: if total > 0 {
                 hits as f64 / total as f64
             } else {
                 0.0
             },
-            size: self.cache.lock().len(),
+            entries,
+            capacity,
+            capacity_mb: self.capacity_mb,
         }
     }
 
     /// Clear the cache
     pub fn clear(&self) {
-        self.cache.lock().clear();
+        if let Some(cache) = &self.cache {
+            cache.lock().clear();
+        }
     }
 }
 
-/// Cache statistics
-#[derive(Debug, Clone)]
-pub struct CacheStats {
-    pub hits: u64,
-    pub misses: u64,
-    pub hit_rate: f64,
-    pub size: usize,
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,7 +149,34 @@ mod tests {
         let stats = cache.stats();
         assert_eq!(stats.hits, 1);
         assert_eq!(stats.misses, 1);
-        assert_eq!(stats.size, 1);
+        assert_eq!(stats.entries, 1);
+    }
+
+    #[test]
+    fn test_evictions_and_disabled_cache() {
+        // 1MB of 384-dimension embeddings is 682 entries
+        let cache = EmbeddingCache::new(1, 384);
+        for i in 0..700 {
+            cache.insert(&i.to_string(), vec![0.0f32; 384]);
+        }
+        let stats = cache.stats();
+        assert_eq!(stats.capacity, 682);
+        assert_eq!(stats.entries, 682);
+        assert_eq!(stats.evictions, 18);
+        assert!(cache.get("0").is_none());
+        assert!(cache.get("699").is_some());
+
+        let disabled = EmbeddingCache::new(0, 384);
+        let mut computed = 0;
+        for _ in 0..2 {
+            disabled.get_or_insert("query", || {
+                computed += 1;
+                vec![0.5f32; 384]
+            });
+        }
+        assert_eq!(computed, 2);
+        let stats = disabled.stats();
+        assert_eq!((stats.misses, stats.entries, stats.capacity), (2, 0, 0));
     }
 
     #[test]
@@ -4,6 +4,7 @@
 
 use fastembed::{EmbeddingModel as FastEmbedModel, InitOptions, TextEmbedding};
 use parking_lot::RwLock;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use crate::error::{Result, YgrepError};
@@ -18,6 +19,17 @@ pub enum ModelType {
 }
 
 impl ModelType {
+    /// Every supported model
+    pub const ALL: [ModelType; 2] = [ModelType::AllMiniLmL6, ModelType::BgeSmall];
+
+    /// Short name used in configuration and reports
+    pub fn id(&self) -> &'static str {
+        match self {
+            ModelType::BgeSmall => "bge-small-en-v1.5",
+            ModelType::AllMiniLmL6 => "all-minilm-l6-v2",
+        }
+    }
+
     pub fn dimension(&self) -> usize {
         match self {
             ModelType::BgeSmall => 384,
@@ -40,6 +52,24 @@ impl ModelType {
     }
 }
 
+impl std::str::FromStr for ModelType {
+    type Err = YgrepError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.to_lowercase();
+        ModelType::ALL
+            .into_iter()
+            .find(|model| model.id() == s || model.name().to_lowercase() == s)
+            .ok_or_else(|| {
+                YgrepError::Config(format!(
+                    "Unknown embedding model '{}' (expected one of: {})",
+                    s,
+                    ModelType::ALL.map(|model| model.id()).join(", ")
+                ))
+            })
+    }
+}
+
 impl Default for ModelType {
     fn default() -> Self {
         ModelType::AllMiniLmL6
@@ -49,6 +79,9 @@ impl Default for ModelType {
 /// Lazy-loaded embedding model
 pub struct EmbeddingModel {
     model_type: ModelType,
+    /// Where the model is downloaded to (fastembed's `.fastembed_cache` in the working
+    /// directory when unset)
+    cache_dir: Option<PathBuf>,
     model: RwLock<Option<Arc<TextEmbedding>>>,
 }
 
@@ -57,10 +90,17 @@ impl EmbeddingModel {
     pub fn new(model_type: ModelType) -> Self {
         Self {
             model_type,
+            cache_dir: None,
             model: RwLock::new(None),
         }
     }
 
+    /// Download the model into `cache_dir` (`layout::models_dir`)
+    pub fn with_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = Some(cache_dir);
+        self
+    }
+
     /// Get the embedding dimension
     pub fn dimension(&self) -> usize {
         self.model_type.dimension()
@@ -71,6 +111,11 @@ impl EmbeddingModel {
         self.model_type.name()
     }
 
+    /// Get the model's short name (as in the `embedding_model` setting)
+    pub fn id(&self) -> &'static str {
+        self.model_type.id()
+    }
+
     /// Load the model if not already loaded
     fn ensure_loaded(&self) -> Result<Arc<TextEmbedding>> {
         // Fast path: model already loaded
@@ -91,10 +136,13 @@ impl EmbeddingModel {
 
         eprint!("  Loading semantic model...");
 
-        let model = TextEmbedding::try_new(
-            InitOptions::new(self.model_type.to_fastembed()).with_show_download_progress(true),
-        )
-        .map_err(|e| YgrepError::Config(format!("Failed to load semantic model: {}", e)))?;
+        let mut options =
+            InitOptions::new(self.model_type.to_fastembed()).with_show_download_progress(true);
+        if let Some(cache_dir) = &self.cache_dir {
+            options = options.with_cache_dir(cache_dir.clone());
+        }
+        let model = TextEmbedding::try_new(options)
+            .map_err(|e| YgrepError::Config(format!("Failed to load semantic model: {}", e)))?;
 
         let model = Arc::new(model);
         *guard = Some(Arc::clone(&model));
@@ -156,6 +204,15 @@ mod tests {
         assert_eq!(ModelType::AllMiniLmL6.dimension(), 384);
     }
 
+    #[test]
+    fn test_model_ids() {
+        for model in ModelType::ALL {
+            assert_eq!(model.id().parse::<ModelType>().unwrap(), model);
+            assert_eq!(model.name().parse::<ModelType>().unwrap(), model);
+        }
+        assert!("gpt-embed".parse::<ModelType>().is_err());
+    }
+
     // End-to-end test for embedding generation.
     // Requires both "e2e" and "embeddings" features, and downloads the embedding model (~25MB).
     // Run with: cargo test -p ygrep-core --features "e2e,embeddings" test_embedding_generation
@@ -21,6 +21,9 @@ pub enum YgrepError {
     #[error("Workspace not indexed: {0}")]
     WorkspaceNotIndexed(PathBuf),
 
+    #[error("Index was built by an older version of ygrep, run `ygrep index --rebuild`: {0}")]
+    IndexOutdated(PathBuf),
+
     #[error("Invalid path: {0}")]
     InvalidPath(PathBuf),
 
@@ -42,6 +45,9 @@ pub enum YgrepError {
     #[error("File too large: {path} ({size} bytes, max {max} bytes)")]
     FileTooLarge { path: PathBuf, size: u64, max: u64 },
 
+    #[error("Skipped {path}: {reason}")]
+    FileExcluded { path: PathBuf, reason: &'static str },
+
     #[error("Unsupported file type: {0}")]
     UnsupportedFileType(String),
 
@@ -53,6 +59,9 @@ pub enum YgrepError {
 
     #[error("Search error: {0}")]
     Search(String),
+
+    #[error("Storage error: {0}")]
+    Storage(String),
 }
 
 pub type Result<T> = std::result::Result<T, YgrepError>;
@@ -0,0 +1,232 @@
+//! Text encoding detection: files that aren't UTF-8 (UTF-16 with or without a byte order
+//! mark, Latin-1/Windows-1252) are transcoded so they can be indexed
+
+use std::path::Path;
+
+use crate::error::{Result, YgrepError};
+
+/// Bytes looked at to tell UTF-16 from binary content
+const SAMPLE_LEN: usize = 8192;
+
+/// The encoding a file was read in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    /// UTF-8 with a byte order mark (removed from the content)
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// Windows-1252, a superset of the printable Latin-1 (ISO-8859-1) characters
+    Windows1252,
+}
+
+impl TextEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "utf-8",
+            TextEncoding::Utf8Bom => "utf-8-bom",
+            TextEncoding::Utf16Le => "utf-16le",
+            TextEncoding::Utf16Be => "utf-16be",
+            TextEncoding::Windows1252 => "windows-1252",
+        }
+    }
+
+    /// Whether the content was converted to be indexed
+    pub fn is_transcoded(&self) -> bool {
+        !matches!(self, TextEncoding::Utf8 | TextEncoding::Utf8Bom)
+    }
+}
+
+impl std::fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Read a text file as UTF-8, transcoding it from the encoding it was detected in
+pub fn read_text(path: &Path) -> Result<(String, TextEncoding)> {
+    let bytes = std::fs::read(path)?;
+    decode(bytes).ok_or_else(|| YgrepError::FileExcluded {
+        path: path.to_path_buf(),
+        reason: "binary content",
+    })
+}
+
+/// Read the first `max_bytes` of a text file as UTF-8, up to the last whole line
+pub fn read_head(path: &Path, max_bytes: u64) -> Result<(String, TextEncoding)> {
+    use std::io::Read;
+
+    let mut bytes = Vec::new();
+    std::fs::File::open(path)?
+        .take(max_bytes)
+        .read_to_end(&mut bytes)?;
+    // A UTF-8 character cut off at the end would make the head look like another encoding
+    if let Err(e) = std::str::from_utf8(&bytes) {
+        if e.error_len().is_none() {
+            bytes.truncate(e.valid_up_to());
+        }
+    }
+    let (mut text, encoding) = decode(bytes).ok_or_else(|| YgrepError::FileExcluded {
+        path: path.to_path_buf(),
+        reason: "binary content",
+    })?;
+    if let Some(end) = text.rfind('\n') {
+        text.truncate(end + 1);
+    }
+    Ok((text, encoding))
+}
+
+/// Decode text by its byte order mark, else as UTF-8, UTF-16 (by the position of its
+/// zero bytes) or Windows-1252. None for binary content.
+pub fn decode(bytes: Vec<u8>) -> Option<(String, TextEncoding)> {
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        if let Ok(text) = std::str::from_utf8(rest) {
+            return Some((text.to_string(), TextEncoding::Utf8Bom));
+        }
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        return Some((decode_utf16(rest, false), TextEncoding::Utf16Le));
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        return Some((decode_utf16(rest, true), TextEncoding::Utf16Be));
+    }
+    let bytes = match String::from_utf8(bytes) {
+        Ok(text) => return Some((text, TextEncoding::Utf8)),
+        Err(e) => e.into_bytes(),
+    };
+    match utf16_without_bom(&bytes) {
+        Some(TextEncoding::Utf16Le) => Some((decode_utf16(&bytes, false), TextEncoding::Utf16Le)),
+        Some(_) => Some((decode_utf16(&bytes, true), TextEncoding::Utf16Be)),
+        None if bytes[..bytes.len().min(SAMPLE_LEN)].contains(&0) => None,
+        None => Some((decode_windows_1252(&bytes), TextEncoding::Windows1252)),
+    }
+}
+
+/// Whether a file's first bytes look binary: zero bytes that aren't UTF-16 text
+pub fn looks_binary(sample: &[u8]) -> bool {
+    let sample = &sample[..sample.len().min(SAMPLE_LEN)];
+    sample.contains(&0)
+        && !sample.starts_with(&[0xFF, 0xFE])
+        && !sample.starts_with(&[0xFE, 0xFF])
+        && utf16_without_bom(sample).is_none()
+}
+
+/// UTF-16 text without a byte order mark, told apart by its zero bytes: mostly ASCII
+/// text has one in nearly every high byte and almost none in the low bytes
+fn utf16_without_bom(bytes: &[u8]) -> Option<TextEncoding> {
+    let sample = &bytes[..bytes.len().min(SAMPLE_LEN) & !1];
+    let units = sample.len() / 2;
+    if units < 2 {
+        return None;
+    }
+    let even = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|&&b| b == 0)
+        .count();
+    // Non-Latin scripts have few zero bytes; they need a byte order mark
+    if odd * 10 >= units * 6 && even * 20 <= units {
+        Some(TextEncoding::Utf16Le)
+    } else if even * 10 >= units * 6 && odd * 20 <= units {
+        Some(TextEncoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+fn decode_utf16(bytes: &[u8], big_endian: bool) -> String {
+    let units = bytes.chunks_exact(2).map(|pair| {
+        if big_endian {
+            u16::from_be_bytes([pair[0], pair[1]])
+        } else {
+            u16::from_le_bytes([pair[0], pair[1]])
+        }
+    });
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Windows-1252 characters for bytes 0x80-0x9F (the C1 controls in Latin-1); the five
+/// unassigned bytes keep their Latin-1 meaning
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+fn decode_windows_1252(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| match b {
+            0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+            _ => b as char,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decode() {
+        let text = "fn café() {}\n";
+        let decoded = |bytes: Vec<u8>| decode(bytes).unwrap();
+
+        assert_eq!(decoded(text.into()), (text.to_string(), TextEncoding::Utf8));
+        let bom = [&[0xEF, 0xBB, 0xBF], text.as_bytes()].concat();
+        assert_eq!(decoded(bom), (text.to_string(), TextEncoding::Utf8Bom));
+
+        // UTF-16 by byte order mark, or by its zero bytes
+        let le = [&[0xFF, 0xFE][..], &utf16(text, false)].concat();
+        assert_eq!(decoded(le), (text.to_string(), TextEncoding::Utf16Le));
+        let be = [&[0xFE, 0xFF][..], &utf16(text, true)].concat();
+        assert_eq!(decoded(be), (text.to_string(), TextEncoding::Utf16Be));
+        assert_eq!(
+            decoded(utf16(text, false)),
+            (text.to_string(), TextEncoding::Utf16Le)
+        );
+        assert_eq!(
+            decoded(utf16(text, true)),
+            (text.to_string(), TextEncoding::Utf16Be)
+        );
+
+        // Bytes that aren't UTF-8: Latin-1 letters, Windows-1252 quotes
+        let latin1 = b"caf\xE9 \x93quoted\x94 \x80".to_vec();
+        assert_eq!(
+            decoded(latin1),
+            ("café “quoted” €".to_string(), TextEncoding::Windows1252)
+        );
+
+        assert_eq!(
+            decode(vec![0x7F, 0x45, 0x4C, 0x46, 0x02, 0x01, 0x00, 0x00, 0xFF]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(looks_binary(&[
+            0x7F, 0x45, 0x4C, 0x46, 0x00, 0x00, 0x12, 0x00, 0xFF
+        ]));
+        assert!(!looks_binary(b"plain text"));
+        assert!(!looks_binary(&utf16("plain text", false)));
+        assert!(!looks_binary(
+            &[&[0xFE, 0xFF][..], &utf16("テキスト", true)].concat()
+        ));
+    }
+}
@@ -0,0 +1,75 @@
+//! Filename patterns that mark files as indexable regardless of extension
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+use crate::error::{Result, YgrepError};
+
+/// Compiled `index_filenames` patterns (`Dockerfile*`, `*.config`, `deploy/*.env`)
+///
+/// Patterns without a `/` match the file name, others the path relative to the
+/// workspace root. Matching is case-insensitive.
+#[derive(Debug, Clone)]
+pub struct FilenameRules {
+    names: GlobSet,
+    paths: GlobSet,
+}
+
+impl FilenameRules {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = GlobBuilder::new(pattern)
+                .case_insensitive(true)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| {
+                    YgrepError::Config(format!("Invalid index_filenames pattern: {}", e))
+                })?;
+            if pattern.contains('/') {
+                paths.add(glob);
+            } else {
+                names.add(glob);
+            }
+        }
+
+        let build = |set: GlobSetBuilder| {
+            set.build()
+                .map_err(|e| YgrepError::Config(format!("Invalid index_filenames pattern: {}", e)))
+        };
+        Ok(Self {
+            names: build(names)?,
+            paths: build(paths)?,
+        })
+    }
+
+    /// Whether a file (path relative to the workspace root) matches any rule
+    pub fn matches(&self, relative: &Path) -> bool {
+        let name_matches = relative
+            .file_name()
+            .is_some_and(|name| self.names.is_match(name));
+        name_matches || self.paths.is_match(relative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filename_rules() -> Result<()> {
+        let patterns = ["Dockerfile*", "*.config", "deploy/*.env"].map(String::from);
+        let rules = FilenameRules::new(&patterns)?;
+
+        assert!(rules.matches(Path::new("docker/Dockerfile.prod")));
+        assert!(rules.matches(Path::new("dockerfile")));
+        assert!(rules.matches(Path::new("web.config")));
+        assert!(rules.matches(Path::new("deploy/staging.env")));
+        assert!(!rules.matches(Path::new("deploy/nested/staging.env")));
+        assert!(!rules.matches(Path::new("src/main.rs")));
+
+        assert!(FilenameRules::new(&["[".to_string()]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+//! Cheap workspace fingerprint (git HEAD plus uncommitted files) for detecting a stale index
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::process::Command;
+
+/// State of a git workspace at index time
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceFingerprint {
+    /// Commit checked out at HEAD (`None` before the first commit)
+    pub head: Option<String>,
+    /// Uncommitted (modified, staged or untracked) files relative to the workspace root,
+    /// with their modification times in milliseconds
+    pub dirty: BTreeMap<String, u64>,
+}
+
+impl WorkspaceFingerprint {
+    /// Fingerprint a workspace; `None` when it isn't inside a git checkout (or git is missing)
+    pub fn compute(root: &Path) -> Option<Self> {
+        let untracked = git(root, &["ls-files", "-z", "--others", "--exclude-standard"])?;
+        let head = git(root, &["rev-parse", "HEAD"]).map(|out| out.trim().to_string());
+
+        // Tracked files that differ from HEAD (staged or not); everything before the first commit
+        let tracked = match head {
+            Some(_) => git(root, &["diff", "HEAD", "--name-only", "-z", "--relative"])?,
+            None => git(root, &["ls-files", "-z"])?,
+        };
+
+        let dirty = split_paths(&tracked)
+            .chain(split_paths(&untracked))
+            .map(|path| {
+                let mtime = std::fs::metadata(root.join(&path))
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0);
+                (path, mtime)
+            })
+            .collect();
+
+        Some(Self { head, dirty })
+    }
+
+    /// Files (relative to the root) that may differ between `previous` and this fingerprint.
+    ///
+    /// Returns `None` when the change set can't be determined (e.g. the old commit is gone),
+    /// in which case the whole workspace should be re-indexed.
+    pub fn changed_since(&self, root: &Path, previous: &Self) -> Option<Vec<String>> {
+        let mut changed = BTreeSet::new();
+
+        if self.head != previous.head {
+            let (Some(old), Some(new)) = (&previous.head, &self.head) else {
+                return None;
+            };
+            let diff = git(root, &["diff", "--name-only", "-z", "--relative", old, new])?;
+            changed.extend(split_paths(&diff));
+        }
+
+        // A file whose dirty state or mtime changed (including one that became clean again)
+        for (path, mtime) in &self.dirty {
+            if previous.dirty.get(path) != Some(mtime) {
+                changed.insert(path.clone());
+            }
+        }
+        for path in previous.dirty.keys() {
+            if !self.dirty.contains_key(path) {
+                changed.insert(path.clone());
+            }
+        }
+
+        Some(changed.into_iter().collect())
+    }
+}
+
+/// How many of the last `max_commits` commits touched each file (paths relative to `root`);
+/// `None` outside a git checkout
+pub fn file_churn(root: &Path, max_commits: usize) -> Option<HashMap<String, usize>> {
+    let log = git(
+        root,
+        &[
+            "log",
+            "--format=",
+            "--name-only",
+            "-z",
+            "--relative",
+            "-n",
+            &max_commits.to_string(),
+        ],
+    )?;
+    let mut churn = HashMap::new();
+    for path in split_paths(&log) {
+        // Each commit's file list starts on a new line
+        *churn.entry(path.trim().to_string()).or_insert(0) += 1;
+    }
+    Some(churn)
+}
+
+/// The last `max_commits` commits reachable from HEAD, newest first; empty outside a git
+/// checkout
+pub fn recent_commits(root: &Path, max_commits: usize) -> Vec<String> {
+    git(
+        root,
+        &["rev-list", "--max-count", &max_commits.to_string(), "HEAD"],
+    )
+    .map(|out| out.lines().map(str::to_string).collect())
+    .unwrap_or_default()
+}
+
+/// The author of each line of `path` (relative to `root`) as of its last change, from
+/// `git blame`; `None` outside a git checkout or for a file git doesn't track. Lines not
+/// committed yet are git's "Not Committed Yet".
+pub fn line_authors(root: &Path, path: &str) -> Option<Vec<String>> {
+    let blame = git(root, &["blame", "--line-porcelain", "--", path])?;
+    Some(
+        blame
+            .lines()
+            .filter_map(|line| line.strip_prefix("author "))
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// A name for the repository a workspace belongs to that is the same in every clone:
+/// the `origin` remote without scheme, user and `.git` (`github.com/owner/repo`),
+/// followed by the workspace's directory within the checkout. `None` without a remote.
+pub fn repository_key(root: &Path) -> Option<String> {
+    let remote = git(root, &["config", "--get", "remote.origin.url"])?;
+    let remote = remote.trim().trim_end_matches('/').trim_end_matches(".git");
+    let remote = remote.split_once("://").map_or(remote, |(_, rest)| rest);
+    let remote = remote.split_once('@').map_or(remote, |(_, host)| host);
+    // scp-like `host:owner/repo`
+    let mut key = remote.replacen(':', "/", 1);
+    if key.is_empty() {
+        return None;
+    }
+
+    let prefix = git(root, &["rev-parse", "--show-prefix"]).unwrap_or_default();
+    let prefix = prefix.trim().trim_end_matches('/');
+    if !prefix.is_empty() {
+        key.push('/');
+        key.push_str(prefix);
+    }
+    Some(key)
+}
+
+/// Run a git command in `dir`, returning stdout on success
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Split NUL-separated git output
+fn split_paths(output: &str) -> impl Iterator<Item = String> + '_ {
+    output
+        .split('\0')
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_changed_since() {
+        let dir = tempdir().unwrap();
+        // Not a git checkout (or no git installed)
+        if git(dir.path(), &["init", "-q"]).is_none() {
+            return;
+        }
+
+        let previous = WorkspaceFingerprint {
+            head: None,
+            dirty: BTreeMap::from([("a.rs".to_string(), 1), ("b.rs".to_string(), 2)]),
+        };
+        let current = WorkspaceFingerprint {
+            head: None,
+            dirty: BTreeMap::from([("a.rs".to_string(), 1), ("c.rs".to_string(), 3)]),
+        };
+        assert_eq!(
+            current.changed_since(dir.path(), &previous),
+            Some(vec!["b.rs".to_string(), "c.rs".to_string()])
+        );
+
+        let committed = WorkspaceFingerprint {
+            head: Some("abc".to_string()),
+            ..current.clone()
+        };
+        assert_eq!(committed.changed_since(dir.path(), &current), None);
+
+        std::fs::write(dir.path().join("new.rs"), "fn new() {}").unwrap();
+        let fingerprint = WorkspaceFingerprint::compute(dir.path()).unwrap();
+        assert!(fingerprint.dirty.contains_key("new.rs"));
+    }
+
+    #[test]
+    fn test_repository_key() {
+        let dir = tempdir().unwrap();
+        if git(dir.path(), &["init", "-q"]).is_none() {
+            return;
+        }
+        assert_eq!(repository_key(dir.path()), None);
+
+        let sub = dir.path().join("services").join("api");
+        std::fs::create_dir_all(&sub).unwrap();
+        for (remote, expected) in [
+            ("git@github.com:owner/repo.git", "github.com/owner/repo"),
+            (
+                "https://user@gitlab.com/group/repo.git",
+                "gitlab.com/group/repo",
+            ),
+            ("ssh://git@example.com/repo/", "example.com/repo"),
+        ] {
+            git(dir.path(), &["config", "remote.origin.url", remote]).unwrap();
+            assert_eq!(repository_key(dir.path()).as_deref(), Some(expected));
+        }
+        assert_eq!(
+            repository_key(&sub).as_deref(),
+            Some("example.com/repo/services/api")
+        );
+    }
+}
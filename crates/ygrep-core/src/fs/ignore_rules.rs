@@ -0,0 +1,160 @@
+//! Ignore files (`.gitignore`, `.ignore`, `.ygrepignore`) at every level of a workspace,
+//! read as the directories they are in are reached
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::IndexerConfig;
+
+/// Names of the ignore files read with `config`, in ascending precedence: `.gitignore`
+/// and `.ignore` with `respect_gitignore`, and `.ygrepignore` (for files that are in git
+/// but shouldn't be searched) always
+fn ignore_file_names(config: &IndexerConfig) -> &'static [&'static str] {
+    if config.respect_gitignore {
+        &[".gitignore", ".ignore", ".ygrepignore"]
+    } else {
+        &[".ygrepignore"]
+    }
+}
+
+/// Whether `path` is an ignore file the walker reads with `config`, so changing it
+/// changes which files are indexed
+pub fn is_ignore_file(path: &Path, config: &IndexerConfig) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| ignore_file_names(config).contains(&name))
+}
+
+/// The ignore files of a workspace, loaded per directory on first use
+///
+/// As with git, the rules of a directory apply to everything below it, and those of
+/// deeper directories take precedence (a nested `!keep.rs` re-includes a file).
+#[derive(Debug)]
+pub struct IgnoreRules {
+    root: PathBuf,
+    names: &'static [&'static str],
+    /// Rules by directory (`None`: no ignore files there)
+    dirs: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl IgnoreRules {
+    pub fn new(root: &Path, config: &IndexerConfig) -> Self {
+        let rules = Self {
+            root: root.to_path_buf(),
+            names: ignore_file_names(config),
+            dirs: Mutex::default(),
+        };
+        rules.load_dir(root);
+        rules
+    }
+
+    /// Read the ignore files of `dir` now, if they haven't been
+    pub fn load_dir(&self, dir: &Path) {
+        self.rules(dir);
+    }
+
+    /// Whether `path` (a directory with `is_dir`) is ignored
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        // Outside the workspace (symlink targets) only the root's rules apply
+        if !path.starts_with(&self.root) {
+            return self
+                .rules(&self.root)
+                .is_some_and(|rules| rules.matched(path, is_dir).is_ignore());
+        }
+        // Directory patterns (`generated/`) cover the files inside
+        for dir in path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.root))
+        {
+            let Some(rules) = self.rules(dir) else {
+                continue;
+            };
+            let matched = rules.matched_path_or_any_parents(path, is_dir);
+            if matched.is_ignore() {
+                return true;
+            }
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+        false
+    }
+
+    /// The rules of the ignore files in `dir`
+    fn rules(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        if let Some(rules) = self.dirs.lock().get(dir) {
+            return rules.clone();
+        }
+        let rules = self.load(dir).map(Arc::new);
+        self.dirs.lock().insert(dir.to_path_buf(), rules.clone());
+        rules
+    }
+
+    fn load(&self, dir: &Path) -> Option<Gitignore> {
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        for name in self.names {
+            let path = dir.join(name);
+            if path.is_file() {
+                if let Some(e) = builder.add(&path) {
+                    tracing::warn!("Failed to read {}: {}", path.display(), e);
+                    continue;
+                }
+                found = true;
+            }
+        }
+        if !found {
+            return None;
+        }
+        builder.build().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_nested_ignore_files() {
+        let temp = tempdir().unwrap();
+        let root = temp.path();
+        std::fs::create_dir_all(root.join("web/generated")).unwrap();
+        std::fs::create_dir_all(root.join("api")).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        std::fs::write(root.join("web/.gitignore"), "generated/\n*.min.js\n").unwrap();
+        std::fs::write(root.join("web/.ignore"), "!keep.min.js\n").unwrap();
+        std::fs::write(root.join("api/.ygrepignore"), "fixtures.json\n").unwrap();
+
+        let config = IndexerConfig {
+            respect_gitignore: true,
+            ..Default::default()
+        };
+        let rules = IgnoreRules::new(root, &config);
+        assert!(rules.is_ignored(&root.join("debug.log"), false));
+        assert!(rules.is_ignored(&root.join("web/server.log"), false));
+        assert!(rules.is_ignored(&root.join("web/generated"), true));
+        assert!(rules.is_ignored(&root.join("web/generated/api.ts"), false));
+        assert!(rules.is_ignored(&root.join("web/app.min.js"), false));
+        assert!(!rules.is_ignored(&root.join("web/keep.min.js"), false));
+        assert!(!rules.is_ignored(&root.join("web/app.js"), false));
+        assert!(rules.is_ignored(&root.join("api/fixtures.json"), false));
+        // Rules of one directory don't apply to its siblings
+        assert!(!rules.is_ignored(&root.join("generated/api.ts"), false));
+
+        // Without respect_gitignore only .ygrepignore counts
+        let config = IndexerConfig {
+            respect_gitignore: false,
+            ..Default::default()
+        };
+        let rules = IgnoreRules::new(root, &config);
+        assert!(!rules.is_ignored(&root.join("web/generated/api.ts"), false));
+        assert!(rules.is_ignored(&root.join("api/fixtures.json"), false));
+        assert!(is_ignore_file(&root.join("api/.ygrepignore"), &config));
+        assert!(!is_ignore_file(&root.join("web/.gitignore"), &config));
+    }
+}
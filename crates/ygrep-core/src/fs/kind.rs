@@ -0,0 +1,284 @@
+//! File-kind classification (source, test, config, docs, generated) from path and
+//! content heuristics, stored per document at index time
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::error::{Result, YgrepError};
+
+/// Path fragments of files that are usually generated
+const GENERATED_PATH_MARKERS: &[&str] = &[
+    ".min.js",
+    ".min.css",
+    ".pb.go",
+    "_pb2.py",
+    ".g.dart",
+    ".designer.cs",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "Cargo.lock",
+    "composer.lock",
+    "generated/",
+];
+
+/// Header comments that mark a file as generated
+const GENERATED_HEADER_MARKERS: &[&str] = &[
+    "@generated",
+    "do not edit",
+    "auto-generated",
+    "autogenerated",
+    "code generated",
+];
+
+/// Comment that links a (usually built or minified) file to its source map
+const SOURCEMAP_MARKER: &str = "# sourceMappingURL=";
+
+/// Files smaller than this are never considered minified
+const MINIFIED_MIN_SIZE: usize = 1024;
+
+/// Minified files have very long lines (on average, or at least one)...
+const MINIFIED_AVG_LINE: usize = 500;
+const MINIFIED_LONG_LINE: usize = 1000;
+
+/// ...and almost no whitespace (prose with long lines has far more)
+const MINIFIED_MAX_WHITESPACE: f32 = 0.08;
+
+/// Directories whose files are tests
+const TEST_DIRS: &[&str] = &["test", "tests", "__tests__", "spec", "specs", "testdata"];
+
+/// Directories whose files are documentation
+const DOC_DIRS: &[&str] = &["doc", "docs", "documentation"];
+
+const DOC_EXTENSIONS: &[&str] = &[
+    "md", "mdx", "markdown", "rst", "adoc", "asciidoc", "org", "txt",
+];
+
+const CONFIG_EXTENSIONS: &[&str] = &[
+    "toml",
+    "yaml",
+    "yml",
+    "json",
+    "jsonc",
+    "json5",
+    "ini",
+    "cfg",
+    "conf",
+    "config",
+    "env",
+    "properties",
+    "plist",
+];
+
+/// What a file is for
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    #[default]
+    Source,
+    Test,
+    Config,
+    Docs,
+    Generated,
+}
+
+impl FileKind {
+    pub const ALL: [FileKind; 5] = [
+        FileKind::Source,
+        FileKind::Test,
+        FileKind::Config,
+        FileKind::Docs,
+        FileKind::Generated,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileKind::Source => "source",
+            FileKind::Test => "test",
+            FileKind::Config => "config",
+            FileKind::Docs => "docs",
+            FileKind::Generated => "generated",
+        }
+    }
+
+    /// Classify a file (path relative to the workspace root) from its path and content.
+    ///
+    /// Generated files win over everything else, then tests, docs and config; anything
+    /// else is source.
+    pub fn classify(path: &Path, content: &str) -> Self {
+        if looks_generated(path, content) {
+            FileKind::Generated
+        } else if is_test(path) {
+            FileKind::Test
+        } else if is_docs(path) {
+            FileKind::Docs
+        } else if is_config(path) {
+            FileKind::Config
+        } else {
+            FileKind::Source
+        }
+    }
+}
+
+impl std::str::FromStr for FileKind {
+    type Err = YgrepError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "source" | "src" => Ok(FileKind::Source),
+            "test" | "tests" => Ok(FileKind::Test),
+            "config" | "conf" => Ok(FileKind::Config),
+            "docs" | "doc" => Ok(FileKind::Docs),
+            "generated" | "gen" => Ok(FileKind::Generated),
+            other => Err(YgrepError::Config(format!(
+                "Unknown file kind '{}' (expected source, test, config, docs or generated)",
+                other
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for FileKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Guess whether a file is generated: its path, a header comment (`@generated`,
+/// `DO NOT EDIT`), a trailing source map reference or minified content
+pub fn looks_generated(path: &Path, content: &str) -> bool {
+    let path = path.to_string_lossy();
+    if GENERATED_PATH_MARKERS.iter().any(|m| path.contains(m)) {
+        return true;
+    }
+    let header = content.lines().take(5).any(|line| {
+        let line = line.to_lowercase();
+        GENERATED_HEADER_MARKERS.iter().any(|m| line.contains(m))
+    });
+    let sourcemap = content
+        .lines()
+        .rev()
+        .take(3)
+        .any(|line| line.contains(SOURCEMAP_MARKER));
+    header || sourcemap || looks_minified(content)
+}
+
+/// Minified code: very long lines with almost no whitespace
+pub fn looks_minified(content: &str) -> bool {
+    if content.len() < MINIFIED_MIN_SIZE {
+        return false;
+    }
+    let whitespace = content.bytes().filter(u8::is_ascii_whitespace).count();
+    if whitespace as f32 / content.len() as f32 >= MINIFIED_MAX_WHITESPACE {
+        return false;
+    }
+    let lines = content.lines().count().max(1);
+    content.len() / lines > MINIFIED_AVG_LINE
+        || content.lines().any(|line| line.len() >= MINIFIED_LONG_LINE)
+}
+
+fn parent_dirs(path: &Path) -> impl Iterator<Item = String> + '_ {
+    path.parent()
+        .into_iter()
+        .flat_map(|parent| parent.components())
+        .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+}
+
+fn is_test(path: &Path) -> bool {
+    if parent_dirs(path).any(|dir| TEST_DIRS.contains(&dir.as_str())) {
+        return true;
+    }
+    let Some(name) = path.file_name().map(|n| n.to_string_lossy()) else {
+        return false;
+    };
+    let stem = name.split('.').next().unwrap_or(&name);
+    let lower = name.to_lowercase();
+    // test_foo.py, foo_test.go, foo.test.ts, foo.spec.js, FooTest.java, FooTests.cs
+    lower.starts_with("test_")
+        || stem.to_lowercase().ends_with("_test")
+        || lower.contains(".test.")
+        || lower.contains(".spec.")
+        || (stem.len() > 4 && (stem.ends_with("Test") || stem.ends_with("Tests")))
+        || lower == "conftest.py"
+}
+
+fn is_docs(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if DOC_EXTENSIONS.contains(&extension.as_str()) {
+        return true;
+    }
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_uppercase())
+        .unwrap_or_default();
+    if ["README", "CHANGELOG", "LICENSE", "CONTRIBUTING"].contains(&name.as_str()) {
+        return true;
+    }
+    // Only prose under docs/; code samples there are still code
+    parent_dirs(path).any(|dir| DOC_DIRS.contains(&dir.as_str()))
+        && ["html", "tex", ""].contains(&extension.as_str())
+}
+
+fn is_config(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    CONFIG_EXTENSIONS.contains(&extension.as_str())
+        || name.starts_with(".env")
+        || name.starts_with("dockerfile")
+        || name.starts_with("containerfile")
+        || name == ".editorconfig"
+        || (name.starts_with('.') && name.ends_with("rc"))
+        || parent_dirs(path).any(|dir| dir == ".github" || dir == ".circleci")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let kind = |path: &str, content: &str| FileKind::classify(Path::new(path), content);
+
+        assert_eq!(kind("src/auth.rs", "fn login() {}"), FileKind::Source);
+        assert_eq!(kind("tests/auth.rs", ""), FileKind::Test);
+        assert_eq!(kind("pkg/auth_test.go", ""), FileKind::Test);
+        assert_eq!(kind("web/login.spec.ts", ""), FileKind::Test);
+        assert_eq!(kind("src/AuthServiceTest.java", ""), FileKind::Test);
+        assert_eq!(kind("tests/test_auth.py", ""), FileKind::Test);
+        assert_eq!(kind("README.md", ""), FileKind::Docs);
+        assert_eq!(kind("docs/examples/auth.rs", ""), FileKind::Source);
+        assert_eq!(kind("Cargo.toml", ""), FileKind::Config);
+        assert_eq!(kind(".github/workflows/ci.yml", ""), FileKind::Config);
+        assert_eq!(kind("docker/Dockerfile.prod", ""), FileKind::Config);
+        assert_eq!(kind("web/app.min.js", ""), FileKind::Generated);
+        assert_eq!(
+            kind(
+                "api/client.ts",
+                "// Code generated by openapi. DO NOT EDIT."
+            ),
+            FileKind::Generated
+        );
+
+        // Minified bundles and files with source maps, whatever their name
+        let bundle = "var a=1,b=2;function c(d){return d+a+b}".repeat(60);
+        assert_eq!(kind("web/app.js", &bundle), FileKind::Generated);
+        let built = "export const a = 1;\n//# sourceMappingURL=app.js.map\n";
+        assert_eq!(kind("dist-web/app.js", built), FileKind::Generated);
+        let prose = "word ".repeat(400);
+        assert_eq!(kind("notes/long.md", &prose), FileKind::Docs);
+
+        assert_eq!("Tests".parse::<FileKind>().unwrap(), FileKind::Test);
+        assert!("binary".parse::<FileKind>().is_err());
+    }
+}
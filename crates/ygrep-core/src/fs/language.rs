@@ -0,0 +1,230 @@
+//! Language detection from file paths
+
+use std::path::Path;
+
+/// Known extensions and the language they map to
+const EXTENSION_LANGUAGES: &[(&str, &str)] = &[
+    ("rs", "rust"),
+    ("py", "python"),
+    ("pyi", "python"),
+    ("js", "javascript"),
+    ("jsx", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("ts", "typescript"),
+    ("tsx", "typescript"),
+    ("mts", "typescript"),
+    ("cts", "typescript"),
+    ("go", "go"),
+    ("rb", "ruby"),
+    ("php", "php"),
+    ("java", "java"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cpp", "cpp"),
+    ("cc", "cpp"),
+    ("hpp", "cpp"),
+    ("hh", "cpp"),
+    ("cs", "csharp"),
+    ("swift", "swift"),
+    ("kt", "kotlin"),
+    ("scala", "scala"),
+    ("clj", "clojure"),
+    ("ex", "elixir"),
+    ("exs", "elixir"),
+    ("erl", "erlang"),
+    ("hs", "haskell"),
+    ("ml", "ocaml"),
+    ("fs", "fsharp"),
+    ("r", "r"),
+    ("jl", "julia"),
+    ("lua", "lua"),
+    ("pl", "perl"),
+    ("pm", "perl"),
+    ("sh", "shell"),
+    ("bash", "shell"),
+    ("zsh", "shell"),
+    ("fish", "shell"),
+    ("ps1", "powershell"),
+    ("bat", "batch"),
+    ("cmd", "batch"),
+    ("html", "html"),
+    ("htm", "html"),
+    ("css", "css"),
+    ("scss", "scss"),
+    ("sass", "sass"),
+    ("less", "less"),
+    ("xml", "xml"),
+    ("json", "json"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+    ("toml", "toml"),
+    ("twig", "twig"),
+    ("blade", "blade"),
+    ("erb", "erb"),
+    ("ipynb", "jupyter"),
+    ("md", "markdown"),
+    ("markdown", "markdown"),
+    ("rst", "rst"),
+    ("txt", "text"),
+    ("csv", "csv"),
+    ("sql", "sql"),
+    ("graphql", "graphql"),
+    ("gql", "graphql"),
+    ("vue", "vue"),
+    ("svelte", "svelte"),
+    ("astro", "astro"),
+    ("tf", "terraform"),
+    ("hcl", "hcl"),
+    ("nix", "nix"),
+    ("proto", "protobuf"),
+    ("thrift", "thrift"),
+    ("dockerfile", "dockerfile"),
+    ("cmake", "cmake"),
+    ("mk", "makefile"),
+    ("just", "just"),
+    ("gradle", "gradle"),
+    ("ini", "ini"),
+];
+
+/// Well-known extensionless filenames and their language
+const FILENAME_LANGUAGES: &[(&str, &str)] = &[
+    ("dockerfile", "dockerfile"),
+    ("makefile", "makefile"),
+    ("gnumakefile", "makefile"),
+    ("rakefile", "ruby"),
+    ("gemfile", "ruby"),
+    ("justfile", "just"),
+    ("cmakelists.txt", "cmake"),
+];
+
+/// Shebang interpreters (version suffix removed) and their language
+const INTERPRETER_LANGUAGES: &[(&str, &str)] = &[
+    ("python", "python"),
+    ("pypy", "python"),
+    ("node", "javascript"),
+    ("nodejs", "javascript"),
+    ("deno", "javascript"),
+    ("bun", "javascript"),
+    ("ts-node", "typescript"),
+    ("tsx", "typescript"),
+    ("sh", "shell"),
+    ("bash", "shell"),
+    ("zsh", "shell"),
+    ("dash", "shell"),
+    ("ksh", "shell"),
+    ("fish", "shell"),
+    ("ruby", "ruby"),
+    ("perl", "perl"),
+    ("php", "php"),
+    ("lua", "lua"),
+    ("luajit", "lua"),
+    ("rscript", "r"),
+    ("julia", "julia"),
+    ("elixir", "elixir"),
+    ("escript", "erlang"),
+    ("runghc", "haskell"),
+    ("runhaskell", "haskell"),
+    ("swift", "swift"),
+    ("scala", "scala"),
+    ("ocaml", "ocaml"),
+    ("pwsh", "powershell"),
+    ("make", "makefile"),
+    ("just", "just"),
+];
+
+/// Detect the language of a file from its name or extension
+pub fn language_for_path(path: &Path) -> Option<&'static str> {
+    if let Some(name) = path.file_name() {
+        let name_lower = name.to_string_lossy().to_lowercase();
+        if let Some((_, lang)) = FILENAME_LANGUAGES.iter().find(|(n, _)| *n == name_lower) {
+            return Some(lang);
+        }
+    }
+
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    language_for_extension(&ext)
+}
+
+/// Map a file extension (without the dot) to a language
+pub fn language_for_extension(ext: &str) -> Option<&'static str> {
+    let ext = ext.to_lowercase();
+    EXTENSION_LANGUAGES
+        .iter()
+        .find(|(e, _)| *e == ext)
+        .map(|(_, lang)| *lang)
+}
+
+/// Detect the language of a file from its name or extension, falling back to a
+/// `#!` line for extensionless scripts such as `bin/deploy`
+pub fn detect_language(path: &Path, content: &str) -> Option<&'static str> {
+    language_for_path(path).or_else(|| {
+        let first_line = content.lines().next()?;
+        language_for_shebang(first_line)
+    })
+}
+
+/// Map a shebang line (`#!/usr/bin/env python3`, `#!/bin/bash -e`) to a language
+pub fn language_for_shebang(line: &str) -> Option<&'static str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    // `env` runs the real interpreter: skip its options (`env -S node --flag`)
+    if program == "env" {
+        program = words.find(|w| !w.starts_with('-') && !w.contains('='))?;
+    }
+
+    // python3.12 -> python
+    let name = program
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .to_lowercase();
+    INTERPRETER_LANGUAGES
+        .iter()
+        .find(|(i, _)| *i == name)
+        .map(|(_, lang)| *lang)
+}
+
+/// The usual extension for a language (without the dot), e.g. `python` -> `py`
+pub fn extension_for_language(language: &str) -> Option<&'static str> {
+    EXTENSION_LANGUAGES
+        .iter()
+        .find(|(_, lang)| *lang == language)
+        .map(|(ext, _)| *ext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_for_path() {
+        assert_eq!(language_for_path(Path::new("src/main.rs")), Some("rust"));
+        assert_eq!(language_for_path(Path::new("App.TSX")), Some("typescript"));
+        assert_eq!(
+            language_for_path(Path::new("docker/Dockerfile")),
+            Some("dockerfile")
+        );
+        assert_eq!(language_for_path(Path::new("data.bin")), None);
+    }
+
+    #[test]
+    fn test_detect_language() {
+        let script = "#!/usr/bin/env python3\nprint('deploy')\n";
+        assert_eq!(
+            detect_language(Path::new("bin/deploy"), script),
+            Some("python")
+        );
+        assert_eq!(language_for_shebang("#!/bin/bash -e"), Some("shell"));
+        assert_eq!(
+            language_for_shebang("#!/usr/bin/env -S node --no-warnings"),
+            Some("javascript")
+        );
+        assert_eq!(
+            language_for_shebang("#!/usr/bin/python3.12"),
+            Some("python")
+        );
+        assert_eq!(language_for_shebang("# not a shebang"), None);
+        assert_eq!(detect_language(Path::new("Justfile"), ""), Some("just"));
+        assert_eq!(extension_for_language("python"), Some("py"));
+        assert_eq!(extension_for_language("makefile"), Some("mk"));
+    }
+}
@@ -1,5 +1,36 @@
+pub mod encoding;
+#[cfg(feature = "index-write")]
+mod filename_rules;
+mod fingerprint;
+#[cfg(feature = "index-write")]
+mod ignore_rules;
+mod kind;
+pub mod language;
+mod package;
+mod path;
+mod submodule;
+#[cfg(feature = "index-write")]
 mod symlink;
+#[cfg(feature = "index-write")]
 mod walker;
 
+pub use encoding::{read_head, read_text, TextEncoding};
+#[cfg(feature = "index-write")]
+pub use filename_rules::FilenameRules;
+pub use fingerprint::{
+    file_churn, line_authors, recent_commits, repository_key, WorkspaceFingerprint,
+};
+#[cfg(feature = "index-write")]
+pub use ignore_rules::{is_ignore_file, IgnoreRules};
+pub use kind::{looks_generated, looks_minified, FileKind};
+pub use language::{
+    detect_language, extension_for_language, language_for_extension, language_for_path,
+    language_for_shebang,
+};
+pub use package::{find_packages, Package};
+pub use path::{relative_path, to_slash, within_root, workspace_key};
+pub use submodule::{find_submodules, submodule_of, Submodule};
+#[cfg(feature = "index-write")]
 pub use symlink::{ResolvedPath, SkipReason, SymlinkResolver};
+#[cfg(feature = "index-write")]
 pub use walker::{FileWalker, WalkEntry, WalkStats};
@@ -0,0 +1,124 @@
+//! Packages declared by manifests in a workspace (`Cargo.toml`, `package.json`, `go.mod`),
+//! so a search can be restricted to a package by name (`--package`)
+
+use std::path::Path;
+
+/// Directories never searched for manifests: dependencies and build output
+const SKIPPED_DIRS: [&str; 4] = ["node_modules", "target", "vendor", "dist"];
+
+/// A package and where it lives
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    /// Cargo or npm package name, or Go module path
+    pub name: String,
+    /// Directory of its manifest relative to the workspace root (`""` for the root),
+    /// `/`-separated
+    pub dir: String,
+}
+
+impl Package {
+    /// Whether `name` names this package: its full name, or the last element of a scoped
+    /// npm name (`@acme/auth`) or Go module path (`github.com/acme/auth`)
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.name.rsplit('/').next() == Some(name)
+    }
+}
+
+/// Every package declared in the workspace, by directory
+pub fn find_packages(root: &Path) -> Vec<Package> {
+    let walker = walkdir::WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.depth() == 0
+                || !entry.file_type().is_dir()
+                || !(name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref()))
+        });
+
+    let mut packages = Vec::new();
+    for entry in walker.filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Some(name) = std::fs::read_to_string(entry.path())
+            .ok()
+            .and_then(|content| package_name(&entry.file_name().to_string_lossy(), &content))
+        else {
+            continue;
+        };
+        let dir = entry
+            .path()
+            .parent()
+            .and_then(|dir| dir.strip_prefix(root).ok())
+            .map(|dir| dir.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_default();
+        packages.push(Package { name, dir });
+    }
+    packages
+}
+
+/// Name declared by a manifest; `None` for other files and manifests without a package
+/// (e.g. a Cargo workspace's root)
+fn package_name(file_name: &str, content: &str) -> Option<String> {
+    match file_name {
+        "Cargo.toml" => {
+            let manifest: toml::Table = content.parse().ok()?;
+            Some(manifest.get("package")?.get("name")?.as_str()?.to_string())
+        }
+        "package.json" => {
+            let manifest: serde_json::Value = serde_json::from_str(content).ok()?;
+            Some(manifest.get("name")?.as_str()?.to_string())
+        }
+        "go.mod" => content.lines().find_map(|line| {
+            let module = line.trim().strip_prefix("module ")?;
+            Some(module.trim().trim_matches('"').to_string())
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_packages() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let write = |path: &str, content: &str| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n");
+        write(
+            "crates/auth-service/Cargo.toml",
+            "[package]\nname = \"auth-service\"\nversion = \"0.1.0\"\n",
+        );
+        write("web/package.json", r#"{"name": "@acme/web"}"#);
+        write(
+            "web/node_modules/left-pad/package.json",
+            r#"{"name": "left-pad"}"#,
+        );
+        write("tools/go.mod", "module github.com/acme/tools\n\ngo 1.22\n");
+
+        let packages = find_packages(root);
+        let found: Vec<(&str, &str)> = packages
+            .iter()
+            .map(|p| (p.name.as_str(), p.dir.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("auth-service", "crates/auth-service"),
+                ("github.com/acme/tools", "tools"),
+                ("@acme/web", "web"),
+            ]
+        );
+        assert!(packages[1].is_named("tools"));
+        assert!(packages[2].is_named("web"));
+        assert!(packages[2].is_named("@acme/web"));
+        assert!(!packages[0].is_named("auth"));
+    }
+}
@@ -0,0 +1,128 @@
+//! Paths as ygrep stores and compares them. Paths in the index are relative to the
+//! workspace root and `/`-separated on every platform, and a workspace is identified by its
+//! root with Windows' `\\?\` prefix, drive-letter case and separators normalized, so
+//! `C:\repo`, `c:/repo` and `\\?\C:\repo` share one index.
+
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+
+/// `path` relative to `root`, `/`-separated (the whole path when it isn't under `root`)
+pub fn relative_path(path: &Path, root: &Path) -> String {
+    if let Ok(relative) = path.strip_prefix(root) {
+        return relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+    }
+    // e.g. a watcher path without the `\\?\` prefix of the canonicalized root
+    let (path_key, root_key) = (workspace_key(path), workspace_key(root));
+    match path_key.strip_prefix(root_key.trim_end_matches('/')) {
+        Some(rest) if rest.starts_with('/') => rest[1..].to_string(),
+        _ => path.to_string_lossy().into_owned(),
+    }
+}
+
+/// `path` (absolute, or relative to `root`) with `.` and `..` resolved, if it stays under
+/// `root` (a canonicalized directory) once the symlinks of its existing part are followed
+pub fn within_root(path: &Path, root: &Path) -> Option<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in root.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            component => resolved.push(component),
+        }
+    }
+    // A symlink inside the workspace may still lead out of it
+    let existing = resolved.ancestors().find(|ancestor| ancestor.exists())?;
+    let canonical = existing.canonicalize().ok()?;
+    (resolved.starts_with(root) && canonical.starts_with(root)).then_some(resolved)
+}
+
+/// A path typed by the user (a `-p` filter, `--dir`) with `\` separators turned into `/`
+pub fn to_slash(path: &str) -> Cow<'_, str> {
+    if path.contains('\\') {
+        Cow::Owned(path.replace('\\', "/"))
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+/// The form of a workspace root its index is found by: no `\\?\` prefix (`\\?\UNC\server`
+/// becomes `//server`), a lowercase drive letter and `/` separators
+pub fn workspace_key(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let path = if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        Cow::Owned(format!(r"\\{}", unc))
+    } else {
+        Cow::Borrowed(path.strip_prefix(r"\\?\").unwrap_or(&path))
+    };
+    let mut key = to_slash(&path).into_owned();
+    if key.len() >= 2 && key.as_bytes()[1] == b':' && key.as_bytes()[0].is_ascii_alphabetic() {
+        key[..1].make_ascii_lowercase();
+    }
+    if key.len() > 1 && key.ends_with('/') && !key.ends_with(":/") {
+        key.pop();
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_within_root() -> std::io::Result<()> {
+        let temp = tempfile::tempdir()?;
+        let root = temp.path().canonicalize()?.join("repo");
+        std::fs::create_dir_all(root.join("src"))?;
+
+        assert_eq!(
+            within_root(Path::new("src/./new.rs"), &root),
+            Some(root.join("src/new.rs"))
+        );
+        assert_eq!(
+            within_root(&root.join("src/../lib.rs"), &root),
+            Some(root.join("lib.rs"))
+        );
+        assert_eq!(within_root(Path::new("../outside.rs"), &root), None);
+        assert_eq!(within_root(Path::new("src/../../outside.rs"), &root), None);
+        assert_eq!(within_root(&temp.path().join("outside.rs"), &root), None);
+        assert_eq!(within_root(Path::new("/tmp/outside.rs"), &root), None);
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(temp.path(), root.join("escape"))?;
+            assert_eq!(within_root(Path::new("escape/outside.rs"), &root), None);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_workspace_key() {
+        let key = |path: &str| workspace_key(Path::new(path));
+        assert_eq!(key(r"\\?\C:\Users\dev\repo"), "c:/Users/dev/repo");
+        assert_eq!(key(r"c:/Users/dev/repo/"), "c:/Users/dev/repo");
+        assert_eq!(key(r"\\?\UNC\server\share\repo"), "//server/share/repo");
+        assert_eq!(key(r"\\server\share\repo"), "//server/share/repo");
+        assert_eq!(key("/home/dev/repo"), "/home/dev/repo");
+        assert_eq!(key("/"), "/");
+
+        assert_eq!(
+            relative_path(
+                Path::new("/home/dev/repo/src/lib.rs"),
+                Path::new("/home/dev/repo")
+            ),
+            "src/lib.rs"
+        );
+        assert_eq!(
+            relative_path(Path::new(r"C:\repo\src\lib.rs"), Path::new(r"\\?\c:\repo")),
+            "src/lib.rs"
+        );
+        assert_eq!(to_slash(r"src\api"), "src/api");
+    }
+}
@@ -0,0 +1,114 @@
+//! Git submodules declared in a workspace's `.gitmodules`, so their files can be left out
+//! of the index (`indexer.index_submodules`, `indexer.skip_submodules`) or searched on
+//! their own (`--submodule`)
+
+use std::path::Path;
+
+/// A submodule and where it is checked out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Submodule {
+    /// Name from its `[submodule "<name>"]` section
+    pub name: String,
+    /// Directory relative to the workspace root, `/`-separated
+    pub path: String,
+}
+
+impl Submodule {
+    /// Whether `name` names this submodule: its name or its path
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.path == name.trim_end_matches('/')
+    }
+
+    /// Whether its files are checked out (`git submodule update --init`)
+    pub fn is_checked_out(&self, root: &Path) -> bool {
+        root.join(&self.path).join(".git").exists()
+    }
+
+    /// Whether `path` (relative to the workspace root) lies inside it
+    pub fn contains(&self, path: &str) -> bool {
+        path.strip_prefix(&self.path)
+            .is_some_and(|rest| rest.starts_with('/'))
+    }
+}
+
+/// The submodules declared in the workspace's `.gitmodules`, in order
+pub fn find_submodules(root: &Path) -> Vec<Submodule> {
+    std::fs::read_to_string(root.join(".gitmodules"))
+        .map(|content| parse_gitmodules(&content))
+        .unwrap_or_default()
+}
+
+/// The submodule `path` (relative to the workspace root) belongs to, if any
+pub fn submodule_of<'a>(submodules: &'a [Submodule], path: &str) -> Option<&'a Submodule> {
+    // (the innermost, should one path be listed inside another)
+    submodules
+        .iter()
+        .filter(|submodule| submodule.contains(path))
+        .max_by_key(|submodule| submodule.path.len())
+}
+
+/// Submodules with a path in git config syntax
+fn parse_gitmodules(content: &str) -> Vec<Submodule> {
+    let mut submodules = Vec::new();
+    let mut name: Option<String> = None;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            name = line
+                .strip_prefix("[submodule")
+                .and_then(|rest| rest.trim().strip_suffix(']'))
+                .map(|quoted| quoted.trim().trim_matches('"').to_string());
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if let (Some(name), true) = (&name, key.trim().eq_ignore_ascii_case("path")) {
+            let path = value
+                .trim()
+                .trim_matches('"')
+                .trim_end_matches('/')
+                .replace('\\', "/");
+            if !path.is_empty() {
+                submodules.push(Submodule {
+                    name: name.clone(),
+                    path,
+                });
+            }
+        }
+    }
+    submodules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gitmodules() {
+        let submodules = parse_gitmodules(
+            "[submodule \"proto\"]\n\tpath = third_party/proto\n\turl = https://example.com/proto.git\n\
+             # a comment\n[core]\n\tpath = not-a-submodule\n\
+             [submodule \"ui-kit\"]\n\turl = ../ui-kit.git\n\tpath = \"web/ui-kit/\"\n",
+        );
+        assert_eq!(
+            submodules,
+            vec![
+                Submodule {
+                    name: "proto".to_string(),
+                    path: "third_party/proto".to_string(),
+                },
+                Submodule {
+                    name: "ui-kit".to_string(),
+                    path: "web/ui-kit".to_string(),
+                },
+            ]
+        );
+        assert!(submodules[1].is_named("web/ui-kit/"));
+        assert_eq!(
+            submodule_of(&submodules, "web/ui-kit/src/button.ts").map(|s| s.name.as_str()),
+            Some("ui-kit")
+        );
+        assert_eq!(submodule_of(&submodules, "web/ui-kit-docs/index.md"), None);
+        assert_eq!(submodule_of(&submodules, "third_party/proto"), None);
+    }
+}
@@ -1,27 +1,44 @@
-use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use walkdir::WalkDir;
 
+use super::filename_rules::FilenameRules;
+use super::ignore_rules::IgnoreRules;
+use super::submodule::{find_submodules, Submodule};
 use super::symlink::{ResolvedPath, SymlinkResolver};
 use crate::config::IndexerConfig;
 use crate::error::Result;
 
-/// Walks a directory tree, respecting gitignore and handling symlinks
+/// Walks a directory tree, respecting ignore files and handling symlinks
 pub struct FileWalker {
     root: PathBuf,
     config: IndexerConfig,
-    gitignore: Option<Gitignore>,
+    ignore_rules: Arc<IgnoreRules>,
+    filename_rules: FilenameRules,
     symlink_resolver: SymlinkResolver,
+    /// Submodules whose files are indexed
+    submodules: Vec<Submodule>,
+    /// Directories of the submodules left out (`index_submodules`, `skip_submodules`)
+    skipped_submodules: Vec<PathBuf>,
 }
 
 impl FileWalker {
     pub fn new(root: PathBuf, config: IndexerConfig) -> Result<Self> {
-        let gitignore = if config.respect_gitignore {
-            load_gitignore(&root)
-        } else {
-            None
-        };
+        let ignore_rules = Arc::new(IgnoreRules::new(&root, &config));
+        let filename_rules = FilenameRules::new(&config.index_filenames)?;
         let symlink_resolver = SymlinkResolver::new(config.follow_symlinks, 20);
+        let (skipped, submodules): (Vec<Submodule>, Vec<Submodule>) =
+            find_submodules(&root).into_iter().partition(|submodule| {
+                !config.index_submodules
+                    || config
+                        .skip_submodules
+                        .iter()
+                        .any(|name| submodule.is_named(name))
+            });
+        let skipped_submodules = skipped
+            .iter()
+            .map(|submodule| root.join(&submodule.path))
+            .collect();
 
         tracing::debug!(
             "FileWalker initialized with {} ignore patterns",
@@ -34,21 +51,37 @@ impl FileWalker {
         Ok(Self {
             root,
             config,
-            gitignore,
+            ignore_rules,
+            filename_rules,
             symlink_resolver,
+            submodules,
+            skipped_submodules,
         })
     }
 
     /// Iterate over all indexable files in the directory tree
     pub fn walk(&mut self) -> impl Iterator<Item = WalkEntry> + '_ {
         let follow_links = self.config.follow_symlinks;
+        let include_hidden = self.config.include_hidden;
+        for submodule in &self.submodules {
+            if !submodule.is_checked_out(&self.root) {
+                tracing::warn!(
+                    "Submodule '{}' ({}) isn't checked out, so its files aren't indexed \
+                     (run `git submodule update --init`)",
+                    submodule.name,
+                    submodule.path
+                );
+            }
+        }
+        let skipped_submodules = self.skipped_submodules.clone();
+        let ignore_rules = Arc::clone(&self.ignore_rules);
 
         WalkDir::new(&self.root)
             .follow_links(follow_links)
             .into_iter()
             .filter_entry(move |e| {
                 // Skip hidden files/directories
-                if is_hidden(e) {
+                if !include_hidden && is_hidden(e) {
                     return false;
                 }
 
@@ -58,30 +91,17 @@ impl FileWalker {
                         return false;
                     }
 
-                    let dir_name = e.file_name().to_string_lossy();
-
                     // Quick check for common ignored directories
-                    let dominated = matches!(
-                        dir_name.as_ref(),
-                        "cache"
-                            | "node_modules"
-                            | "vendor"
-                            | "target"
-                            | "dist"
-                            | "build"
-                            | "logs"
-                            | "log"
-                            | "tmp"
-                            | "temp"
-                            | "var"
-                            | "__pycache__"
-                            | ".git"
-                            | ".svn"
-                            | "coverage"
-                            | "htmlcov"
-                    );
-
-                    if dominated {
+                    if is_ignored_dir_name(&e.file_name().to_string_lossy()) {
+                        return false;
+                    }
+
+                    if skipped_submodules.iter().any(|dir| e.path() == dir) {
+                        return false;
+                    }
+
+                    // Ignored directories aren't descended into
+                    if e.depth() > 0 && ignore_rules.is_ignored(e.path(), true) {
                         return false;
                     }
                 }
@@ -97,7 +117,7 @@ impl FileWalker {
                     return None;
                 }
 
-                // Check gitignore
+                // Check ignore files
                 if self.is_ignored(path) {
                     return None;
                 }
@@ -135,19 +155,59 @@ impl FileWalker {
             })
     }
 
-    /// Check if a path should be ignored by gitignore
-    fn is_ignored(&self, path: &Path) -> bool {
-        if let Some(ref gitignore) = self.gitignore {
-            let is_dir = path.is_dir();
-            gitignore.matched(path, is_dir).is_ignore()
-        } else {
-            false
+    /// Whether `walk` would yield this file (for updating single files incrementally)
+    pub fn accepts(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let mut components = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy());
+        let file_name = components.next_back();
+        let hidden_ok = |name: &str| self.config.include_hidden || !name.starts_with('.');
+        let dirs_ok = components.all(|dir| hidden_ok(&dir) && !is_ignored_dir_name(&dir));
+        let name_ok = file_name.is_some_and(|name| hidden_ok(&name));
+
+        dirs_ok
+            && name_ok
+            && !self
+                .skipped_submodules
+                .iter()
+                .any(|dir| path.starts_with(dir))
+            && !self.is_ignored(path)
+            && !self.matches_ignore_pattern(path)
+            && self.is_indexable(path)
+    }
+
+    /// Read the ignore files of every directory now rather than as the walk reaches them,
+    /// so the walker keeps applying the rules of this moment after they change (what
+    /// `Workspace::reconcile_ignored` compares against)
+    pub fn load_ignore_files(&self) {
+        let include_hidden = self.config.include_hidden;
+        let rules = &self.ignore_rules;
+        let dirs = WalkDir::new(&self.root)
+            .follow_links(self.config.follow_symlinks)
+            .into_iter()
+            .filter_entry(|e| {
+                e.file_type().is_dir()
+                    && (e.depth() == 0
+                        || ((include_hidden || !is_hidden(e))
+                            && !is_ignored_dir_name(&e.file_name().to_string_lossy())
+                            && !rules.is_ignored(e.path(), true)))
+            });
+        for dir in dirs.flatten() {
+            rules.load_dir(dir.path());
         }
     }
 
+    /// Check if a path should be ignored by the ignore files
+    fn is_ignored(&self, path: &Path) -> bool {
+        self.ignore_rules.is_ignored(path, path.is_dir())
+    }
+
     /// Check if path matches custom ignore patterns
+    /// (matched relative to the workspace root so parent directories don't count)
     fn matches_ignore_pattern(&self, path: &Path) -> bool {
-        let path_str = path.to_string_lossy();
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let path_str = relative.to_string_lossy();
 
         for pattern in &self.config.ignore_patterns {
             if glob_match(pattern, &path_str) {
@@ -160,6 +220,14 @@ impl FileWalker {
 
     /// Check if a file should be indexed
     fn is_indexable(&self, path: &Path) -> bool {
+        // Files matching an `index_filenames` rule are always indexed
+        if self
+            .filename_rules
+            .matches(path.strip_prefix(&self.root).unwrap_or(path))
+        {
+            return true;
+        }
+
         // Check extension filter if set
         if !self.config.include_extensions.is_empty() {
             if let Some(ext) = path.extension() {
@@ -211,18 +279,28 @@ pub struct WalkStats {
     pub visited_paths: usize,
 }
 
-/// Load .gitignore from a directory
-fn load_gitignore(root: &Path) -> Option<Gitignore> {
-    let gitignore_path = root.join(".gitignore");
-    if gitignore_path.exists() {
-        let mut builder = GitignoreBuilder::new(root);
-        if builder.add(&gitignore_path).is_none() {
-            if let Ok(gi) = builder.build() {
-                return Some(gi);
-            }
-        }
-    }
-    None
+/// Directories that are never indexed (dependencies, build output, caches)
+fn is_ignored_dir_name(name: &str) -> bool {
+    matches!(
+        name,
+        "cache"
+            | "node_modules"
+            | "vendor"
+            | "target"
+            | "dist"
+            | "build"
+            | "logs"
+            | "log"
+            | "tmp"
+            | "temp"
+            | "var"
+            | "__pycache__"
+            | ".git"
+            | ".svn"
+            | ".hg"
+            | "coverage"
+            | "htmlcov"
+    )
 }
 
 /// Check if a directory entry is hidden (starts with .)
@@ -237,8 +315,10 @@ fn is_hidden(entry: &walkdir::DirEntry) -> bool {
 /// Simple glob matching for ignore patterns (for files)
 fn glob_match(pattern: &str, path: &str) -> bool {
     // Handle **/dir/** patterns (match dir anywhere in path)
-    if pattern.starts_with("**/") && pattern.ends_with("/**") {
-        let dir_name = &pattern[3..pattern.len() - 3];
+    if let Some(dir_name) = pattern
+        .strip_prefix("**/")
+        .and_then(|p| p.strip_suffix("/**"))
+    {
         // Check if this directory name appears as a complete path component
         return path.contains(&format!("/{}/", dir_name))
             || path.starts_with(&format!("{}/", dir_name))
@@ -246,26 +326,22 @@ fn glob_match(pattern: &str, path: &str) -> bool {
     }
 
     // Handle **/*.ext patterns (match extension anywhere)
-    if pattern.starts_with("**/*.") {
-        let ext = &pattern[5..]; // Get everything after "**/*." (index 5 skips the dot)
+    if let Some(ext) = pattern.strip_prefix("**/*.") {
         return path.ends_with(&format!(".{}", ext));
     }
 
     // Handle **/something patterns (match at end)
-    if pattern.starts_with("**/") {
-        let suffix = &pattern[3..];
+    if let Some(suffix) = pattern.strip_prefix("**/") {
         return path.ends_with(suffix) || path.ends_with(&format!("/{}", suffix));
     }
 
     // Handle something/** patterns (match at start)
-    if pattern.ends_with("/**") {
-        let prefix = &pattern[..pattern.len() - 3];
+    if let Some(prefix) = pattern.strip_suffix("/**") {
         return path.starts_with(prefix) || path.contains(&format!("/{}", prefix));
     }
 
     // Handle simple * patterns (*.ext)
-    if pattern.starts_with("*.") {
-        let ext = &pattern[2..];
+    if let Some(ext) = pattern.strip_prefix("*.") {
         return path.ends_with(&format!(".{}", ext));
     }
 
@@ -335,6 +411,8 @@ fn is_text_file(path: &Path) -> bool {
         "yaml",
         "yml",
         "toml",
+        // Notebooks
+        "ipynb",
         // Templates
         "twig",
         "blade",
@@ -403,6 +481,8 @@ fn is_text_file(path: &Path) -> bool {
         const TEXT_FILENAMES: &[&str] = &[
             "dockerfile",
             "makefile",
+            "gnumakefile",
+            "justfile",
             "rakefile",
             "gemfile",
             "procfile",
@@ -420,10 +500,9 @@ fn is_text_file(path: &Path) -> bool {
     }
 
     // Fall back to checking first bytes for binary content
+    // (zero bytes, unless they belong to UTF-16 text)
     if let Ok(bytes) = std::fs::read(path) {
-        // Check first 8KB for null bytes
-        let check_len = bytes.len().min(8192);
-        !bytes[..check_len].contains(&0)
+        !super::encoding::looks_binary(&bytes)
     } else {
         false
     }
@@ -467,6 +546,24 @@ mod tests {
         assert!(!glob_match("*.log", "debug.txt"));
     }
 
+    #[test]
+    fn test_ignore_patterns_relative_to_root() {
+        // A workspace inside a directory an ignore pattern names (`**/build/**`) is
+        // walked; only its own `build` directory is skipped
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("build/test_workspace");
+        std::fs::create_dir_all(test_dir.join("build")).unwrap();
+        std::fs::write(test_dir.join("lib.rs"), "pub fn kept() {}").unwrap();
+        std::fs::write(test_dir.join("build/out.rs"), "pub fn built() {}").unwrap();
+
+        let mut walker = FileWalker::new(test_dir.clone(), IndexerConfig::default()).unwrap();
+        let paths: Vec<String> = walker
+            .walk()
+            .map(|entry| crate::fs::relative_path(&entry.path, &test_dir))
+            .collect();
+        assert_eq!(paths, vec!["lib.rs"]);
+    }
+
     #[test]
     fn test_walk_skips_fastembed_cache() {
         let temp_base = tempdir().unwrap();
@@ -495,4 +592,147 @@ mod tests {
         assert!(paths.iter().all(|path| !path.contains(".fastembed_cache")));
         assert!(paths.iter().all(|path| !path.contains("subdir/ignored.rs")));
     }
+
+    #[test]
+    fn test_index_filenames_bypass_extension_filter() {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(test_dir.join("Dockerfile.prod"), "FROM rust").unwrap();
+        std::fs::write(test_dir.join("web.config"), "<configuration/>").unwrap();
+        std::fs::write(test_dir.join("notes.txt"), "notes").unwrap();
+
+        let mut config = IndexerConfig::default();
+        config.ignore_patterns.clear();
+        config.include_extensions = vec!["rs".to_string()];
+        config.index_filenames.push("*.config".to_string());
+        let walker = FileWalker::new(test_dir.clone(), config).unwrap();
+
+        assert!(walker.accepts(&test_dir.join("Dockerfile.prod")));
+        assert!(walker.accepts(&test_dir.join("web.config")));
+        assert!(!walker.accepts(&test_dir.join("notes.txt")));
+    }
+
+    #[test]
+    fn test_skip_submodules() {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        for dir in ["src", "third_party/proto", "ui"] {
+            std::fs::create_dir_all(test_dir.join(dir)).unwrap();
+            std::fs::write(test_dir.join(dir).join("lib.rs"), "fn f() {}").unwrap();
+        }
+        std::fs::write(
+            test_dir.join(".gitmodules"),
+            "[submodule \"proto\"]\n\tpath = third_party/proto\n[submodule \"ui\"]\n\tpath = ui\n",
+        )
+        .unwrap();
+
+        let walked = |configure: &dyn Fn(&mut IndexerConfig)| {
+            let mut config = IndexerConfig::default();
+            configure(&mut config);
+            let mut walker = FileWalker::new(test_dir.clone(), config).unwrap();
+            let mut paths: Vec<String> = walker
+                .walk()
+                .map(|entry| {
+                    let relative = entry.path.strip_prefix(&test_dir).unwrap();
+                    relative.to_string_lossy().replace('\\', "/")
+                })
+                .collect();
+            paths.sort();
+            paths
+        };
+        assert_eq!(
+            walked(&|_| {}),
+            vec!["src/lib.rs", "third_party/proto/lib.rs", "ui/lib.rs"]
+        );
+        assert_eq!(
+            walked(&|config| config.skip_submodules = vec!["proto".to_string()]),
+            vec!["src/lib.rs", "ui/lib.rs"]
+        );
+        assert_eq!(
+            walked(&|config| config.index_submodules = false),
+            vec!["src/lib.rs"]
+        );
+
+        let config = IndexerConfig {
+            skip_submodules: vec!["ui".to_string()],
+            ..Default::default()
+        };
+        let walker = FileWalker::new(test_dir.clone(), config).unwrap();
+        assert!(!walker.accepts(&test_dir.join("ui/lib.rs")));
+        assert!(walker.accepts(&test_dir.join("third_party/proto/lib.rs")));
+    }
+
+    #[test]
+    fn test_walk_honors_nested_ignore_files() {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(test_dir.join("web/generated")).unwrap();
+        std::fs::write(test_dir.join("web/.gitignore"), "generated/\n").unwrap();
+        std::fs::write(test_dir.join("web/generated/api.ts"), "export {}").unwrap();
+        std::fs::write(test_dir.join("web/app.ts"), "export {}").unwrap();
+
+        let walked = |respect_gitignore: bool| {
+            let mut config = IndexerConfig::default();
+            config.ignore_patterns.clear();
+            config.respect_gitignore = respect_gitignore;
+            let mut walker = FileWalker::new(test_dir.clone(), config).unwrap();
+            let mut paths: Vec<String> = walker
+                .walk()
+                .map(|e| {
+                    let relative = e.path.strip_prefix(&test_dir).unwrap();
+                    relative.to_string_lossy().replace('\\', "/")
+                })
+                .collect();
+            paths.sort();
+            (paths, walker)
+        };
+
+        let (paths, walker) = walked(true);
+        assert_eq!(paths, vec!["web/app.ts"]);
+        assert!(!walker.accepts(&test_dir.join("web/generated/api.ts")));
+
+        let (paths, _) = walked(false);
+        assert_eq!(paths, vec!["web/app.ts", "web/generated/api.ts"]);
+    }
+
+    #[test]
+    fn test_include_hidden() {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(test_dir.join(".github/workflows")).unwrap();
+        std::fs::create_dir_all(test_dir.join(".git")).unwrap();
+        std::fs::write(test_dir.join(".github/workflows/ci.yml"), "on: push").unwrap();
+        std::fs::write(test_dir.join(".git/config"), "[core]").unwrap();
+        std::fs::write(test_dir.join(".env.example"), "PORT=8080").unwrap();
+        std::fs::write(test_dir.join("main.rs"), "fn main() {}").unwrap();
+
+        let walked = |include_hidden: bool| {
+            let mut config = IndexerConfig::default();
+            config.ignore_patterns.clear();
+            config.include_hidden = include_hidden;
+            let mut walker = FileWalker::new(test_dir.clone(), config).unwrap();
+            let mut paths: Vec<String> = walker
+                .walk()
+                .map(|e| {
+                    let relative = e.path.strip_prefix(&test_dir).unwrap();
+                    relative.to_string_lossy().replace('\\', "/")
+                })
+                .collect();
+            paths.sort();
+            (paths, walker)
+        };
+
+        let (paths, walker) = walked(false);
+        assert_eq!(paths, vec!["main.rs"]);
+        assert!(!walker.accepts(&test_dir.join(".github/workflows/ci.yml")));
+
+        let (paths, walker) = walked(true);
+        assert_eq!(
+            paths,
+            vec![".env.example", ".github/workflows/ci.yml", "main.rs"]
+        );
+        assert!(walker.accepts(&test_dir.join(".github/workflows/ci.yml")));
+        assert!(!walker.accepts(&test_dir.join(".git/config")));
+    }
 }
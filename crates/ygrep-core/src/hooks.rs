@@ -0,0 +1,228 @@
+//! Workspace events for notifications, cache invalidation or downstream tooling: Rust
+//! callbacks registered on a `Workspace` (`on_index_complete`, `on_file_indexed`,
+//! `on_search`) and the shell commands in the `[hooks]` config section
+//!
+//! Commands run through `sh -c` (`cmd /C` on Windows) in the workspace root, without
+//! waiting for them. They get the event as JSON in `YGREP_EVENT`, the workspace root in
+//! `YGREP_WORKSPACE`, and `YGREP_PATH` or `YGREP_QUERY` for file and search events. Their
+//! output goes to stderr, so it can't mix with search results.
+
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+use crate::config::HooksConfig;
+
+/// Something that happened in a workspace
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WorkspaceEvent {
+    /// A full index finished
+    IndexComplete {
+        indexed: usize,
+        embedded: usize,
+        skipped: usize,
+        errors: usize,
+        duration_ms: u64,
+    },
+    /// A file was indexed again, or removed from the index (incremental updates)
+    FileIndexed { path: String, removed: bool },
+    /// A search ran
+    Search {
+        query: String,
+        hits: usize,
+        query_time_ms: u64,
+    },
+}
+
+impl WorkspaceEvent {
+    pub fn kind(&self) -> HookKind {
+        match self {
+            WorkspaceEvent::IndexComplete { .. } => HookKind::IndexComplete,
+            WorkspaceEvent::FileIndexed { .. } => HookKind::FileIndexed,
+            WorkspaceEvent::Search { .. } => HookKind::Search,
+        }
+    }
+}
+
+/// Which events a hook is called for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    IndexComplete,
+    FileIndexed,
+    Search,
+}
+
+impl HookKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookKind::IndexComplete => "on_index_complete",
+            HookKind::FileIndexed => "on_file_indexed",
+            HookKind::Search => "on_search",
+        }
+    }
+}
+
+type Callback = Arc<dyn Fn(&WorkspaceEvent) + Send + Sync>;
+
+/// The callbacks and commands a workspace calls for its events
+#[derive(Default)]
+pub struct Hooks {
+    callbacks: RwLock<Vec<(HookKind, Callback)>>,
+    commands: HooksConfig,
+}
+
+impl Hooks {
+    pub fn new(commands: HooksConfig) -> Self {
+        Self {
+            callbacks: RwLock::new(Vec::new()),
+            commands,
+        }
+    }
+
+    /// Call `callback` for every event of this kind
+    pub fn register(
+        &self,
+        kind: HookKind,
+        callback: impl Fn(&WorkspaceEvent) + Send + Sync + 'static,
+    ) {
+        self.callbacks.write().push((kind, Arc::new(callback)));
+    }
+
+    /// Pass `event` to the callbacks registered for it, then start its command
+    pub fn fire(&self, root: &Path, event: WorkspaceEvent) {
+        let kind = event.kind();
+        // (taken out of the lock, so callbacks may register others)
+        let callbacks: Vec<Callback> = self
+            .callbacks
+            .read()
+            .iter()
+            .filter(|(registered, _)| *registered == kind)
+            .map(|(_, callback)| Arc::clone(callback))
+            .collect();
+        for callback in callbacks {
+            callback(&event);
+        }
+        if let Some(command) = self.commands.command(kind) {
+            run_command(command, root, &event);
+        }
+    }
+}
+
+/// Start a hook command and reap it in the background; failures are logged
+fn run_command(command: &str, root: &Path, event: &WorkspaceEvent) {
+    let kind = event.kind().as_str();
+    let mut shell = shell(command);
+    shell
+        .current_dir(root)
+        .env(
+            "YGREP_EVENT",
+            serde_json::to_string(event).unwrap_or_default(),
+        )
+        .env("YGREP_WORKSPACE", root)
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(std::io::stderr()));
+    match event {
+        WorkspaceEvent::FileIndexed { path, .. } => {
+            shell.env("YGREP_PATH", path);
+        }
+        WorkspaceEvent::Search { query, .. } => {
+            shell.env("YGREP_QUERY", query);
+        }
+        WorkspaceEvent::IndexComplete { .. } => {}
+    }
+
+    let mut child = match shell.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            tracing::warn!("Failed to run {} hook `{}`: {}", kind, command, e);
+            return;
+        }
+    };
+    let command = command.to_string();
+    std::thread::spawn(move || match child.wait() {
+        Ok(status) if !status.success() => {
+            tracing::warn!("{} hook `{}` failed ({})", kind, command, status);
+        }
+        Err(e) => tracing::warn!("{} hook `{}` failed: {}", kind, command, e),
+        Ok(_) => {}
+    });
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_callbacks_by_kind() {
+        let hooks = Hooks::default();
+        let searches = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&searches);
+        hooks.register(HookKind::Search, move |event| {
+            assert!(matches!(event, WorkspaceEvent::Search { hits: 2, .. }));
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let search = WorkspaceEvent::Search {
+            query: "retry".to_string(),
+            hits: 2,
+            query_time_ms: 5,
+        };
+        hooks.fire(Path::new("."), search.clone());
+        hooks.fire(
+            Path::new("."),
+            WorkspaceEvent::FileIndexed {
+                path: "src/lib.rs".to_string(),
+                removed: false,
+            },
+        );
+        assert_eq!(searches.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            serde_json::to_string(&search).unwrap(),
+            r#"{"event":"search","query":"retry","hits":2,"query_time_ms":5}"#
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let hooks = Hooks::new(HooksConfig {
+            on_file_indexed: Some(r#"printf '%s' "$YGREP_PATH" > indexed.txt"#.to_string()),
+            ..Default::default()
+        });
+        hooks.fire(
+            dir.path(),
+            WorkspaceEvent::FileIndexed {
+                path: "src/lib.rs".to_string(),
+                removed: true,
+            },
+        );
+        // Commands aren't waited for
+        let written = dir.path().join("indexed.txt");
+        for _ in 0..100 {
+            if std::fs::read_to_string(&written).is_ok_and(|s| !s.is_empty()) {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert_eq!(std::fs::read_to_string(&written).unwrap(), "src/lib.rs");
+    }
+}
@@ -0,0 +1,92 @@
+//! Index size budget (`indexer.max_index_size`): choosing which files to leave out of
+//! the semantic index when the embeddings wouldn't fit
+
+/// Estimated bytes on disk per embedded document besides its vector: HNSW links and
+/// the doc_id
+const OVERHEAD_PER_EMBEDDING: u64 = 512;
+
+/// Estimated bytes on disk per embedded document of a model with `dimension`-dim vectors
+pub fn bytes_per_embedding(dimension: usize) -> u64 {
+    dimension as u64 * std::mem::size_of::<f32>() as u64 + OVERHEAD_PER_EMBEDDING
+}
+
+/// A file that would get an embedding, with what makes it worth keeping
+#[derive(Debug, Clone)]
+pub struct EmbeddingCandidate {
+    /// Path relative to the workspace root
+    pub path: String,
+    /// Content size in bytes
+    pub size: u64,
+    /// Whether the file looks generated
+    pub generated: bool,
+    /// Commits that touched the file recently
+    pub churn: usize,
+}
+
+/// Indexes of the candidates to skip so the rest fit in `available` bytes, with vectors of
+/// `dimension` dimensions.
+///
+/// The least valuable files go first: generated ones, then those that changed least
+/// often, then the largest.
+pub fn excluded_by_budget(
+    candidates: &[EmbeddingCandidate],
+    available: u64,
+    dimension: usize,
+) -> Vec<usize> {
+    let fits = (available / bytes_per_embedding(dimension)) as usize;
+    if candidates.len() <= fits {
+        return Vec::new();
+    }
+
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&candidates[a], &candidates[b]);
+        b.generated
+            .cmp(&a.generated)
+            .then(a.churn.cmp(&b.churn))
+            .then(b.size.cmp(&a.size))
+            .then(a.path.cmp(&b.path))
+    });
+
+    let mut excluded = order[..candidates.len() - fits].to_vec();
+    excluded.sort_unstable();
+    excluded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excluded_by_budget() {
+        let candidate = |path: &str, size: u64, generated: bool, churn: usize| EmbeddingCandidate {
+            path: path.to_string(),
+            size,
+            generated,
+            churn,
+        };
+        let candidates = vec![
+            candidate("src/auth.rs", 4_000, false, 12),
+            candidate("web/app.min.js", 1_000, true, 30),
+            candidate("src/legacy.rs", 2_000, false, 0),
+            candidate("src/big_table.rs", 40_000, false, 0),
+            candidate("src/db.rs", 3_000, false, 5),
+        ];
+
+        let per_embedding = bytes_per_embedding(384);
+        assert!(excluded_by_budget(&candidates, 5 * per_embedding, 384).is_empty());
+        // Generated first, then the larger of the never-changed files
+        assert_eq!(
+            excluded_by_budget(&candidates, 3 * per_embedding, 384),
+            vec![1, 3]
+        );
+        assert_eq!(excluded_by_budget(&candidates, 0, 384).len(), 5);
+
+        // Larger vectors take more of the budget
+        assert!(bytes_per_embedding(768) > per_embedding);
+        assert_eq!(
+            excluded_by_budget(&candidates, 5 * per_embedding, 768),
+            vec![1, 2, 3]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Checkpoints of a full index run (`checkpoint.json` in the index directory): the files
+//! already committed to the text index and the documents whose embeddings are saved, so
+//! an interrupted run resumes where it stopped instead of starting over
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::error::{Result, YgrepError};
+
+/// Checkpoint file inside the index directory
+const CHECKPOINT_FILE: &str = "checkpoint.json";
+
+/// How often a running index commits its progress
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Progress of an unfinished index run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexCheckpoint {
+    /// Files committed to the text index, by relative path
+    pub committed: BTreeMap<String, CommittedFile>,
+    /// doc_ids whose embeddings are saved in the vector index
+    pub embedded: BTreeSet<String>,
+}
+
+/// A committed file, with what it looked like when it was indexed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommittedFile {
+    pub doc_id: String,
+    pub size: u64,
+    /// Modification time (milliseconds since the epoch)
+    pub modified: u64,
+}
+
+impl IndexCheckpoint {
+    /// The checkpoint left by an interrupted run, if any
+    pub fn load(index_path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(index_path.join(CHECKPOINT_FILE)).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(checkpoint) => Some(checkpoint),
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable index checkpoint: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Write the checkpoint (replacing the previous one atomically)
+    pub fn save(&self, index_path: &Path) -> Result<()> {
+        let json = serde_json::to_string(self)
+            .map_err(|e| YgrepError::Config(format!("Failed to save index checkpoint: {}", e)))?;
+        let temp = index_path.join(format!("{}.tmp", CHECKPOINT_FILE));
+        std::fs::write(&temp, json)?;
+        std::fs::rename(temp, index_path.join(CHECKPOINT_FILE))?;
+        Ok(())
+    }
+
+    /// Remove the checkpoint once a run has finished
+    pub fn remove(index_path: &Path) -> Result<()> {
+        let path = index_path.join(CHECKPOINT_FILE);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.committed.is_empty() && self.embedded.is_empty()
+    }
+
+    /// Record a file as committed with its current size and modification time
+    pub fn record_committed(&mut self, path: &str, doc_id: &str, metadata: &std::fs::Metadata) {
+        self.committed.insert(
+            path.to_string(),
+            CommittedFile {
+                doc_id: doc_id.to_string(),
+                size: metadata.len(),
+                modified: modified_millis(metadata),
+            },
+        );
+    }
+
+    /// The doc_id of a file committed by the interrupted run, unless it changed since
+    pub fn committed_doc_id(&self, path: &str, metadata: &std::fs::Metadata) -> Option<&str> {
+        self.committed
+            .get(path)
+            .filter(|file| {
+                file.size == metadata.len() && file.modified == modified_millis(metadata)
+            })
+            .map(|file| file.doc_id.as_str())
+    }
+}
+
+fn modified_millis(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |age| age.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_checkpoint_roundtrip() -> Result<()> {
+        let dir = tempdir().unwrap();
+        assert!(IndexCheckpoint::load(dir.path()).is_none());
+
+        let file = dir.path().join("auth.rs");
+        std::fs::write(&file, "fn login() {}")?;
+        let mut checkpoint = IndexCheckpoint::default();
+        checkpoint.record_committed("auth.rs", "00ab", &std::fs::metadata(&file)?);
+        checkpoint.embedded.insert("00ab".to_string());
+        checkpoint.save(dir.path())?;
+
+        let loaded = IndexCheckpoint::load(dir.path()).unwrap();
+        assert_eq!(loaded.committed, checkpoint.committed);
+        assert!(loaded.embedded.contains("00ab"));
+        assert_eq!(
+            loaded.committed_doc_id("auth.rs", &std::fs::metadata(&file)?),
+            Some("00ab")
+        );
+
+        // A file that changed since is indexed again
+        std::fs::write(&file, "fn login(user: &str) {}")?;
+        assert_eq!(
+            loaded.committed_doc_id("auth.rs", &std::fs::metadata(&file)?),
+            None
+        );
+
+        IndexCheckpoint::remove(dir.path())?;
+        assert!(IndexCheckpoint::load(dir.path()).is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+//! Index growth history (`history.json` in the index directory): one entry per full
+//! index run, shown by `ygrep status --history`
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::error::{Result, YgrepError};
+
+/// History file inside the index directory
+const HISTORY_FILE: &str = "history.json";
+
+/// Most entries kept; older runs are dropped first
+pub const MAX_ENTRIES: usize = 100;
+
+/// One full index run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// When the run finished (RFC 3339)
+    pub timestamp: String,
+    /// Files in the text index
+    pub files: usize,
+    /// Documents embedded in this run
+    pub embedded: usize,
+    /// Size of the index directory in bytes
+    pub size: u64,
+    /// How long the run took, in milliseconds
+    pub duration_ms: u64,
+    pub semantic: bool,
+}
+
+/// Entries recorded so far, oldest first (empty if there is no readable history)
+pub fn load(index_path: &Path) -> Vec<HistoryEntry> {
+    let Ok(content) = std::fs::read_to_string(index_path.join(HISTORY_FILE)) else {
+        return Vec::new();
+    };
+    match serde_json::from_str(&content) {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::warn!("Ignoring unreadable index history: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Append an entry, dropping the oldest beyond `MAX_ENTRIES`
+pub fn record(index_path: &Path, entry: HistoryEntry) -> Result<()> {
+    let mut entries = load(index_path);
+    entries.push(entry);
+    let excess = entries.len().saturating_sub(MAX_ENTRIES);
+    entries.drain(..excess);
+    save(index_path, &entries)
+}
+
+/// Replace the recorded entries (e.g. to carry them over a rebuild)
+pub fn save(index_path: &Path, entries: &[HistoryEntry]) -> Result<()> {
+    let json = serde_json::to_string_pretty(entries)
+        .map_err(|e| YgrepError::Config(format!("Failed to save index history: {}", e)))?;
+    std::fs::write(index_path.join(HISTORY_FILE), json)?;
+    Ok(())
+}
+
+/// Total size of the files in an index directory
+pub fn index_size(index_path: &Path) -> u64 {
+    walkdir::WalkDir::new(index_path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entry(files: usize) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: "2026-01-01T00:00:00+00:00".to_string(),
+            files,
+            embedded: 0,
+            size: 1024,
+            duration_ms: 10,
+            semantic: false,
+        }
+    }
+
+    #[test]
+    fn test_record_keeps_latest_entries() -> Result<()> {
+        let temp = tempdir()?;
+        assert!(load(temp.path()).is_empty());
+
+        for files in 0..MAX_ENTRIES + 5 {
+            record(temp.path(), entry(files))?;
+        }
+        let entries = load(temp.path());
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries[0].files, 5);
+        assert_eq!(entries.last().unwrap().files, MAX_ENTRIES + 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+//! Incremental indexing: the files of the last index with what they looked like when they
+//! were indexed (size, modification time and the content hash that is their doc_id), so
+//! indexing again only reads the files that changed
+
+use std::collections::HashMap;
+use std::time::UNIX_EPOCH;
+use tantivy::schema::Value;
+use tantivy::{Index, TantivyDocument};
+
+use super::schema::{units, SchemaFields};
+use crate::error::Result;
+
+/// A file as the last index saw it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedFile {
+    /// Hash of the indexed content
+    pub doc_id: String,
+    pub size: u64,
+    /// Modification time (seconds since the epoch)
+    pub mtime: u64,
+}
+
+/// The files of an index, by relative path
+#[derive(Debug, Default)]
+pub struct IndexedFiles {
+    files: HashMap<String, IndexedFile>,
+    /// When the last full index ran (seconds since the epoch)
+    indexed_at: u64,
+}
+
+impl IndexedFiles {
+    /// Read the file documents of `index`, last fully indexed at `indexed_at`
+    pub fn load(index: &Index, indexed_at: u64) -> Result<Self> {
+        let fields = SchemaFields::new(&index.schema());
+        let reader = index.reader()?;
+        let mut files = HashMap::new();
+        for segment in reader.searcher().segment_readers() {
+            let store = segment.get_store_reader(1)?;
+            for doc in store.iter::<TantivyDocument>(segment.alive_bitset()) {
+                let doc = doc?;
+                let text = |field| doc.get_first(field).and_then(|v| v.as_str());
+                let number = |field| doc.get_first(field).and_then(|v| v.as_u64());
+                if text(fields.unit) != Some(units::FILE) {
+                    continue;
+                }
+                let (Some(path), Some(doc_id)) = (text(fields.path), text(fields.doc_id)) else {
+                    continue;
+                };
+                files.insert(
+                    path.to_string(),
+                    IndexedFile {
+                        doc_id: doc_id.to_string(),
+                        size: number(fields.size).unwrap_or(0),
+                        mtime: number(fields.mtime).unwrap_or(0),
+                    },
+                );
+            }
+        }
+        Ok(Self { files, indexed_at })
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Take a file out of the set as the walk reaches it
+    pub fn take(&mut self, path: &str) -> Option<IndexedFile> {
+        self.files.remove(path)
+    }
+
+    /// Whether `file` still has the size and modification time it was indexed with.
+    /// Modification times have a one-second resolution, so a file modified in the second
+    /// the last index ran may have changed again unnoticed and isn't trusted.
+    pub fn looks_unchanged(&self, file: &IndexedFile, metadata: &std::fs::Metadata) -> bool {
+        file.size == metadata.len()
+            && file.mtime == modified_secs(metadata)
+            && file.mtime < self.indexed_at
+    }
+}
+
+/// A file's modification time as stored with its document
+pub fn modified_secs(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::IndexerConfig;
+    use crate::index::{build_document_schema, register_tokenizers, Indexer};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_indexed_files() -> Result<()> {
+        let temp = tempdir().unwrap();
+        let file = temp.path().join("auth.rs");
+        std::fs::write(&file, "fn login() {}")?;
+        let index = Index::create_in_ram(build_document_schema());
+        register_tokenizers(index.tokenizers());
+        let indexer = Indexer::new(IndexerConfig::default(), index.clone(), temp.path())?;
+        let doc_id = indexer.index_file(&file)?;
+        indexer.commit()?;
+        assert_eq!(indexer.doc_id_of(&file)?, doc_id);
+
+        let metadata = std::fs::metadata(&file)?;
+        let mut files = IndexedFiles::load(&index, modified_secs(&metadata) + 1)?;
+        assert_eq!(files.len(), 1);
+        let indexed = files.take("auth.rs").unwrap();
+        assert_eq!(indexed.doc_id, doc_id);
+        assert!(files.looks_unchanged(&indexed, &metadata));
+        assert!(files.is_empty());
+
+        // Indexed in the second it was last modified: not trusted
+        let racy = IndexedFiles::load(&index, modified_secs(&metadata))?;
+        assert!(!racy.looks_unchanged(&indexed, &metadata));
+
+        std::fs::write(&file, "fn login(user: &str) {}")?;
+        assert!(!files.looks_unchanged(&indexed, &std::fs::metadata(&file)?));
+        assert_ne!(indexer.doc_id_of(&file)?, doc_id);
+        Ok(())
+    }
+}
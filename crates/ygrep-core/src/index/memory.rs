@@ -0,0 +1,103 @@
+//! Memory cap for indexing (`indexer.max_memory_mb`): a smaller Tantivy writer heap,
+//! early commits and smaller embedding batches while the process is near the cap
+
+use crate::config::IndexerConfig;
+
+/// Tantivy writer heap without a cap
+pub const DEFAULT_WRITER_HEAP: usize = 50_000_000;
+
+/// Smallest heap Tantivy accepts for a writer thread
+const MIN_WRITER_HEAP: usize = 15_000_000;
+
+/// Share of the cap at which indexing starts freeing memory
+const HIGH_WATER: f64 = 0.85;
+
+/// Share of the cap below which embedding batches grow again
+const LOW_WATER: f64 = 0.5;
+
+/// Memory limit for an index run
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryBudget {
+    max_bytes: u64,
+}
+
+impl MemoryBudget {
+    pub fn new(max_mb: u64) -> Self {
+        Self {
+            max_bytes: max_mb.saturating_mul(1024 * 1024),
+        }
+    }
+
+    pub fn from_config(config: &IndexerConfig) -> Option<Self> {
+        config.max_memory_mb.map(Self::new)
+    }
+
+    /// Tantivy writer heap: a quarter of the cap, within what Tantivy accepts and the
+    /// uncapped default
+    pub fn writer_heap(&self) -> usize {
+        ((self.max_bytes / 4) as usize).clamp(MIN_WRITER_HEAP, DEFAULT_WRITER_HEAP)
+    }
+
+    /// Whether the process is close to the cap (never, where memory use can't be read)
+    pub fn is_exceeded(&self) -> bool {
+        resident_memory().is_some_and(|rss| rss as f64 > self.max_bytes as f64 * HIGH_WATER)
+    }
+
+    /// Whether the process is well below the cap
+    pub fn has_headroom(&self) -> bool {
+        resident_memory().is_some_and(|rss| (rss as f64) < self.max_bytes as f64 * LOW_WATER)
+    }
+
+    /// Next embedding batch size: halved near the cap, grown back towards `max` below it
+    pub fn adjust_batch_size(&self, current: usize, max: usize) -> usize {
+        if self.is_exceeded() {
+            (current / 2).max(1)
+        } else if self.has_headroom() {
+            (current * 2).min(max)
+        } else {
+            current
+        }
+    }
+}
+
+/// Writer heap for an indexer with this config
+pub fn writer_heap(config: &IndexerConfig) -> usize {
+    MemoryBudget::from_config(config).map_or(DEFAULT_WRITER_HEAP, |budget| budget.writer_heap())
+}
+
+/// Resident memory of this process in bytes (Linux only)
+pub fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    parse_vm_rss(&status)
+}
+
+/// The `VmRSS` line of /proc/self/status, in bytes
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_budget() {
+        assert_eq!(MemoryBudget::new(32).writer_heap(), MIN_WRITER_HEAP);
+        assert_eq!(MemoryBudget::new(120).writer_heap(), 30 * 1024 * 1024);
+        assert_eq!(MemoryBudget::new(4096).writer_heap(), DEFAULT_WRITER_HEAP);
+        assert_eq!(writer_heap(&IndexerConfig::default()), DEFAULT_WRITER_HEAP);
+
+        let status = "Name:\tygrep\nVmPeak:\t  204800 kB\nVmRSS:\t   51200 kB\n";
+        assert_eq!(parse_vm_rss(status), Some(50 * 1024 * 1024));
+        assert_eq!(parse_vm_rss("Name:\tygrep\n"), None);
+
+        // A cap far below (or above) what the test process uses
+        if resident_memory().is_some() {
+            assert_eq!(MemoryBudget::new(1).adjust_batch_size(64, 64), 32);
+            assert_eq!(MemoryBudget::new(1).adjust_batch_size(1, 64), 1);
+            assert_eq!(MemoryBudget::new(1 << 20).adjust_batch_size(8, 64), 16);
+        }
+    }
+}
@@ -1,11 +1,55 @@
+#[cfg(feature = "index-write")]
+pub mod budget;
+#[cfg(feature = "index-write")]
+pub mod checkpoint;
+pub mod history;
+#[cfg(feature = "index-write")]
+pub mod incremental;
+#[cfg(feature = "index-write")]
+pub mod memory;
+pub mod normalize;
+#[cfg(feature = "index-write")]
+pub mod notebook;
+#[cfg(feature = "index-write")]
+pub mod progress;
+#[cfg(feature = "index-write")]
+pub mod rebuild;
+#[cfg(feature = "index-write")]
+pub mod references;
+pub mod regions;
+#[cfg(feature = "index-write")]
+pub mod repair;
 pub mod schema;
+pub mod secrets;
+pub mod stemming;
+#[cfg(feature = "treesitter")]
+pub mod syntax;
+#[cfg(feature = "index-write")]
+pub mod throttle;
 #[cfg(feature = "embeddings")]
 pub mod vector;
+pub mod verify;
+#[cfg(feature = "index-write")]
 pub mod writer;
 
+#[cfg(feature = "index-write")]
+pub use checkpoint::IndexCheckpoint;
+pub use history::HistoryEntry;
+#[cfg(feature = "index-write")]
+pub use incremental::{IndexedFile, IndexedFiles};
+#[cfg(feature = "index-write")]
+pub use memory::MemoryBudget;
+#[cfg(feature = "index-write")]
+pub use progress::{IndexPhase, IndexProgress, ProgressBarOutput, ProgressEvent};
+#[cfg(feature = "index-write")]
+pub use repair::RepairReport;
 pub use schema::{
     build_document_schema, fields, register_tokenizers, SchemaFields, CODE_TOKENIZER,
 };
+pub use secrets::{SecretHandling, SecretScanner};
+pub use stemming::Stemming;
 #[cfg(feature = "embeddings")]
-pub use vector::VectorIndex;
+pub use vector::{VectorIndex, VectorSalvage};
+pub use verify::{verify_index, VerifyCheck, VerifyReport};
+#[cfg(feature = "index-write")]
 pub use writer::Indexer;
@@ -0,0 +1,151 @@
+//! Text normalization shared by the tokenizer and the literal post-filter:
+//! Unicode forms, diacritics and identifier case conventions
+
+use std::borrow::Cow;
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Normalize text for comparison: NFKC (so full-width and composed/decomposed forms
+/// agree), optionally with diacritics removed (`café` -> `cafe`). ASCII is returned as-is.
+pub fn normalize(text: &str, fold_diacritics: bool) -> Cow<'_, str> {
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+    if fold_diacritics {
+        // Decompose, drop the marks, then recompose what's left
+        let folded: String = text.nfkd().filter(|c| !is_combining_mark(*c)).collect();
+        Cow::Owned(folded.nfc().collect())
+    } else {
+        Cow::Owned(text.nfkc().collect())
+    }
+}
+
+/// How text is normalized before literal comparison
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchNormalizer {
+    /// Remove diacritics (`café` -> `cafe`)
+    pub fold_diacritics: bool,
+    /// Rewrite identifiers to lowercase snake_case (`getUserId` -> `get_user_id`)
+    pub identifiers: bool,
+}
+
+impl MatchNormalizer {
+    pub fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let text = normalize(text, self.fold_diacritics);
+        if self.identifiers {
+            Cow::Owned(canonical_identifiers(&text))
+        } else {
+            text
+        }
+    }
+}
+
+/// Rewrite every identifier in `text` to lowercase snake_case, so `getUserId`,
+/// `get_user_id` and `GET_USER_ID` all become `get_user_id`. Other text is kept.
+pub fn canonical_identifiers(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 8);
+    let chars: Vec<char> = text.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '_' {
+            // Collapse separator runs
+            if !out.ends_with('_') {
+                out.push('_');
+            }
+            continue;
+        }
+        if c.is_uppercase() && i > 0 && is_word_boundary(&chars, i) && !out.ends_with('_') {
+            out.push('_');
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+/// Split an identifier into its words (`getHTTPResponse` -> `get`, `HTTP`, `Response`)
+pub fn identifier_parts(token: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = token.char_indices().collect();
+    let plain: Vec<char> = chars.iter().map(|(_, c)| *c).collect();
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (i, &(pos, c)) in chars.iter().enumerate() {
+        if c == '_' || c == '-' {
+            if let Some(s) = start.take() {
+                parts.push(&token[s..pos]);
+            }
+            continue;
+        }
+        if c.is_uppercase() && i > 0 && is_word_boundary(&plain, i) {
+            if let Some(s) = start.take() {
+                parts.push(&token[s..pos]);
+            }
+        }
+        start.get_or_insert(pos);
+    }
+    if let Some(s) = start {
+        parts.push(&token[s..]);
+    }
+    parts
+}
+
+/// Whether an uppercase letter at `i` starts a new camelCase word
+fn is_word_boundary(chars: &[char], i: usize) -> bool {
+    let prev = chars[i - 1];
+    let next_is_lower = chars.get(i + 1).is_some_and(|c| c.is_lowercase());
+    prev.is_lowercase() || prev.is_numeric() || (prev.is_uppercase() && next_is_lower)
+}
+
+/// Whether a character continues a token (combining marks stay with their base letter)
+pub fn is_token_mark(c: char) -> bool {
+    is_combining_mark(c)
+}
+
+/// Whether a character is Chinese, Japanese or Korean script, written without spaces
+/// between words (so it is indexed as overlapping character pairs)
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{11FF}'     // Hangul Jamo
+        | '\u{3040}'..='\u{30FF}'   // Hiragana, Katakana
+        | '\u{3130}'..='\u{318F}'   // Hangul compatibility Jamo
+        | '\u{31F0}'..='\u{31FF}'   // Katakana phonetic extensions
+        | '\u{3400}'..='\u{4DBF}'   // CJK extension A
+        | '\u{4E00}'..='\u{9FFF}'   // CJK unified ideographs
+        | '\u{AC00}'..='\u{D7AF}'   // Hangul syllables
+        | '\u{F900}'..='\u{FAFF}'   // CJK compatibility ideographs
+        | '\u{FF66}'..='\u{FF9F}'   // Half-width Katakana
+        | '\u{20000}'..='\u{2FA1F}' // CJK extensions B-F, compatibility supplement
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        assert_eq!(normalize(composed, false), normalize(decomposed, false));
+        assert_eq!(normalize("\u{ff43}\u{ff41}\u{ff46}\u{ff45}", false), "cafe");
+        assert_eq!(normalize(decomposed, true), "cafe");
+        assert!(matches!(normalize("plain", true), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_identifier_conventions() {
+        for form in ["getUserId", "get_user_id", "GET_USER_ID", "GetUserId"] {
+            assert_eq!(canonical_identifiers(form), "get_user_id");
+        }
+        assert_eq!(
+            canonical_identifiers("x = parseHTTPResponse(a);"),
+            "x = parse_http_response(a);"
+        );
+        assert_eq!(
+            identifier_parts("parseHTTPResponse"),
+            vec!["parse", "HTTP", "Response"]
+        );
+        assert_eq!(identifier_parts("get_user_id"), vec!["get", "user", "id"]);
+        assert_eq!(identifier_parts("plain"), vec!["plain"]);
+    }
+}
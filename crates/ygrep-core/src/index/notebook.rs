@@ -0,0 +1,225 @@
+//! Jupyter notebooks (`.ipynb`): the JSON of a notebook is mostly outputs, metadata and
+//! quoting, so it is indexed as the text of its code and markdown cells instead, one
+//! chunk per cell.
+//!
+//! Notebooks as Jupyter saves them have every source line of a cell on a line of its own
+//! in the file. The cells' text is then laid out on those same lines (every other line
+//! left empty), so hits point at the lines of the file. Other notebooks (minified, or
+//! with cells stored as one string) are indexed as their cells one after the other.
+
+use serde::Deserialize;
+use std::ops::Range;
+
+/// A notebook's cells as they are indexed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notebook {
+    /// The indexed text
+    pub text: String,
+    /// Line ranges (0-based, end exclusive) of the code and markdown cells in `text`
+    pub cells: Vec<Range<usize>>,
+}
+
+#[derive(Deserialize)]
+struct RawNotebook {
+    cells: Vec<RawCell>,
+}
+
+#[derive(Deserialize)]
+struct RawCell {
+    cell_type: String,
+    #[serde(default)]
+    source: Source,
+}
+
+/// `source` is a list of lines (each but the last ending in `\n`), or one string
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Source {
+    Lines(Vec<String>),
+    Text(String),
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Self::Lines(Vec::new())
+    }
+}
+
+impl Source {
+    fn lines(&self) -> Vec<&str> {
+        match self {
+            Self::Lines(lines) => lines
+                .iter()
+                .map(|l| l.strip_suffix('\n').unwrap_or(l))
+                .collect(),
+            Self::Text(text) => text.lines().collect(),
+        }
+    }
+}
+
+impl Notebook {
+    /// The cells of `content` for a file with `extension`; `None` when it isn't a
+    /// notebook (in nbformat 4) and is indexed as it is
+    pub fn extract(extension: &str, content: &str) -> Option<Self> {
+        if !extension.eq_ignore_ascii_case("ipynb") {
+            return None;
+        }
+        let notebook: RawNotebook = serde_json::from_str(content).ok()?;
+        let cells: Vec<&RawCell> = notebook
+            .cells
+            .iter()
+            .filter(|cell| matches!(cell.cell_type.as_str(), "code" | "markdown"))
+            .collect();
+        Some(in_place(content, &cells).unwrap_or_else(|| one_after_another(&cells)))
+    }
+}
+
+/// The cells on the lines of `content` they are on, if every source line has one of its own
+fn in_place(content: &str, cells: &[&RawCell]) -> Option<Notebook> {
+    let file_lines: Vec<&str> = content.lines().collect();
+    let mut text: Vec<&str> = vec![""; file_lines.len()];
+    let mut ranges = Vec::new();
+    let mut line = 0;
+    for cell in cells {
+        let Source::Lines(source) = &cell.source else {
+            return None;
+        };
+        // (`"source"` lines inside strings start with an escaped quote instead)
+        line += file_lines[line..]
+            .iter()
+            .position(|l| l.trim_start().starts_with("\"source\":"))?;
+        let key = file_lines[line].trim();
+        if source.is_empty() {
+            line += 1;
+            continue;
+        }
+        if key != "\"source\": [" {
+            return None;
+        }
+        let start = line + 1;
+        for (i, expected) in source.iter().enumerate() {
+            let stored = file_lines.get(start + i)?.trim();
+            let stored = stored.strip_suffix(',').unwrap_or(stored);
+            let value: String = serde_json::from_str(stored).ok()?;
+            let value = value.strip_suffix('\n').unwrap_or(&value);
+            if value != expected.strip_suffix('\n').unwrap_or(expected) || value.contains('\n') {
+                return None;
+            }
+        }
+        let end = start + source.len();
+        for (slot, source_line) in text[start..end].iter_mut().zip(cell.source.lines()) {
+            *slot = source_line;
+        }
+        ranges.push(start..end);
+        line = end;
+    }
+    Some(Notebook {
+        text: text.join("\n"),
+        cells: ranges,
+    })
+}
+
+/// The cells one after the other, an empty line between two
+fn one_after_another(cells: &[&RawCell]) -> Notebook {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut ranges = Vec::new();
+    for cell in cells {
+        let source = cell.source.lines();
+        if source.is_empty() {
+            continue;
+        }
+        if !lines.is_empty() {
+            lines.push("");
+        }
+        let start = lines.len();
+        lines.extend(source);
+        ranges.push(start..lines.len());
+    }
+    Notebook {
+        text: lines.join("\n"),
+        cells: ranges,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": [
+    "# Loading the \"sales\" data\n",
+    "Read it with pandas"
+   ]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "metadata": {},
+   "outputs": [
+    {
+     "name": "stdout",
+     "output_type": "stream",
+     "text": [
+      "\"source\": [\n",
+      "df = load_sales()\n"
+     ]
+    }
+   ],
+   "source": [
+    "df = load_sales()\n",
+    "df.describe()"
+   ]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": null,
+   "metadata": {},
+   "outputs": [],
+   "source": []
+  },
+  {
+   "cell_type": "raw",
+   "metadata": {},
+   "source": [
+    "not indexed"
+   ]
+  }
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+"##;
+
+    #[test]
+    fn test_extract_notebook() {
+        let notebook = Notebook::extract("ipynb", NOTEBOOK).unwrap();
+        let lines: Vec<&str> = notebook.text.lines().collect();
+        // Cells stay on the lines of the file
+        assert_eq!(notebook.cells, vec![6..8, 25..27]);
+        assert_eq!(lines[6], "# Loading the \"sales\" data");
+        assert_eq!(lines[7], "Read it with pandas");
+        assert_eq!(lines[25..27], ["df = load_sales()", "df.describe()"]);
+        assert_eq!(notebook.text.split('\n').count(), NOTEBOOK.lines().count());
+        // Outputs and raw cells are left out
+        assert_eq!(notebook.text.matches("load_sales").count(), 1);
+        assert!(!notebook.text.contains("not indexed"));
+
+        // Minified: the cells one after the other
+        let value: serde_json::Value = serde_json::from_str(NOTEBOOK).unwrap();
+        let minified = serde_json::to_string(&value).unwrap();
+        let notebook = Notebook::extract("ipynb", &minified).unwrap();
+        assert_eq!(
+            notebook.text,
+            "# Loading the \"sales\" data\nRead it with pandas\n\ndf = load_sales()\ndf.describe()"
+        );
+        assert_eq!(notebook.cells, vec![0..2, 3..5]);
+
+        assert!(Notebook::extract("json", NOTEBOOK).is_none());
+        assert!(Notebook::extract("ipynb", "{\"worksheets\": []}").is_none());
+    }
+}
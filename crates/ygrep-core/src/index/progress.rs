@@ -0,0 +1,151 @@
+//! Progress of a full index: a progress bar on stderr by default, or events for other
+//! front ends (`ygrep index --json-progress`)
+
+#[cfg(feature = "progress")]
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(feature = "progress")]
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// Part of a full index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexPhase {
+    /// Files are read into the text index
+    Text,
+    /// Embeddings are generated for semantic search
+    Embed,
+}
+
+/// A step of a full index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// An interrupted run is resumed: `indexed` files and `embedded` documents it saved
+    /// are kept
+    Resume { indexed: usize, embedded: usize },
+    /// A phase started; `total` files (text) or documents (embed) are to be processed
+    Phase { phase: IndexPhase, total: u64 },
+    /// Files or documents processed so far, with the file just read (text phase)
+    Progress {
+        phase: IndexPhase,
+        done: u64,
+        total: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
+    },
+    /// A phase finished; `done` files were indexed or documents embedded
+    PhaseDone { phase: IndexPhase, done: u64 },
+    /// Indexing finished
+    Done {
+        indexed: usize,
+        embedded: usize,
+        skipped: usize,
+        errors: usize,
+    },
+}
+
+/// Receives the progress of `Workspace::index_all_with_progress`
+pub trait IndexProgress {
+    fn report(&self, event: ProgressEvent);
+}
+
+/// Progress on stderr: a running file count, then a bar while embedding (with the
+/// `progress` feature)
+#[derive(Default)]
+pub struct ProgressBarOutput {
+    #[cfg(feature = "progress")]
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl IndexProgress for ProgressBarOutput {
+    fn report(&self, event: ProgressEvent) {
+        match event {
+            ProgressEvent::Resume { indexed, embedded } => {
+                eprintln!(
+                    "  Resuming interrupted index ({} files indexed, {} embedded)...",
+                    indexed, embedded
+                );
+            }
+            ProgressEvent::Phase {
+                phase: IndexPhase::Embed,
+                total,
+            } => {
+                eprintln!("Building semantic index for {} documents...", total);
+                #[cfg(feature = "progress")]
+                {
+                    let bar = ProgressBar::new(total);
+                    bar.set_style(
+                        ProgressStyle::default_bar()
+                            .template("  [{bar:40.cyan/blue}] {pos}/{len} ({percent}%)")
+                            .unwrap()
+                            .progress_chars("━╸─"),
+                    );
+                    bar.enable_steady_tick(std::time::Duration::from_millis(100));
+                    *self.bar.lock() = Some(bar);
+                }
+            }
+            ProgressEvent::Progress {
+                phase: IndexPhase::Text,
+                done,
+                ..
+            } if done % 500 == 0 => {
+                eprint!("\r  Indexed {} files...          ", done);
+            }
+            #[cfg(feature = "progress")]
+            ProgressEvent::Progress {
+                phase: IndexPhase::Embed,
+                done,
+                ..
+            } => {
+                if let Some(bar) = self.bar.lock().as_ref() {
+                    bar.set_position(done);
+                }
+            }
+            ProgressEvent::PhaseDone {
+                phase: IndexPhase::Text,
+                done,
+            } => {
+                eprintln!("\r  Indexed {} files.              ", done);
+            }
+            ProgressEvent::PhaseDone {
+                phase: IndexPhase::Embed,
+                done,
+            } => {
+                #[cfg(feature = "progress")]
+                if let Some(bar) = self.bar.lock().take() {
+                    bar.finish_and_clear();
+                }
+                eprintln!("  Indexed {} documents.", done);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_json() {
+        let event = ProgressEvent::Progress {
+            phase: IndexPhase::Text,
+            done: 3,
+            total: 10,
+            path: Some("src/lib.rs".to_string()),
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"progress","phase":"text","done":3,"total":10,"path":"src/lib.rs"}"#
+        );
+        let event = ProgressEvent::PhaseDone {
+            phase: IndexPhase::Embed,
+            done: 7,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"phase_done","phase":"embed","done":7}"#
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Rebuilding an index from scratch (`ygrep index --rebuild`) without a window where it
+//! can't be searched: the new index is built in a directory next to the old one, named
+//! after the workspace hash, and swapped in only once it is complete. Until then searches
+//! keep using the old index, and a failed rebuild leaves it as it was.
+
+use std::path::{Path, PathBuf};
+
+use super::history;
+use crate::error::Result;
+
+/// Directory the rebuild of the index in `index_path` is built in
+pub fn staging_dir(index_path: &Path) -> PathBuf {
+    sibling(index_path, "rebuild")
+}
+
+/// Start a rebuild: an empty staging directory, with the growth history of the current
+/// index carried over
+pub fn prepare(index_path: &Path) -> Result<PathBuf> {
+    let staging = staging_dir(index_path);
+    discard(&staging);
+    std::fs::create_dir_all(&staging)?;
+    let history = history::load(index_path);
+    if !history.is_empty() {
+        history::save(&staging, &history)?;
+    }
+    Ok(staging)
+}
+
+/// Replace the index in `index_path` with the one built in `staging`
+pub fn swap(staging: &Path, index_path: &Path) -> Result<()> {
+    if !index_path.exists() {
+        std::fs::rename(staging, index_path)?;
+        return Ok(());
+    }
+    if exchange(staging, index_path).is_ok() {
+        // The staging directory now holds the old index
+        discard(staging);
+        return Ok(());
+    }
+    // Without an atomic exchange the index is missing between the two renames
+    let old = sibling(index_path, "old");
+    std::fs::rename(index_path, &old)?;
+    if let Err(e) = std::fs::rename(staging, index_path) {
+        let _ = std::fs::rename(&old, index_path);
+        return Err(e.into());
+    }
+    discard(&old);
+    Ok(())
+}
+
+/// Remove a rebuild that won't be swapped in
+pub fn discard(staging: &Path) {
+    if staging.exists() {
+        if let Err(e) = std::fs::remove_dir_all(staging) {
+            tracing::warn!("Failed to remove {}: {}", staging.display(), e);
+        }
+    }
+}
+
+/// `<hash>.<suffix>-<pid>` next to `index_path`
+fn sibling(index_path: &Path, suffix: &str) -> PathBuf {
+    let name = index_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    index_path.with_file_name(format!("{}.{}-{}", name, suffix, std::process::id()))
+}
+
+/// Swap two directories in one step (`renameat2` with `RENAME_EXCHANGE`)
+#[cfg(target_os = "linux")]
+fn exchange(a: &Path, b: &Path) -> std::io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let a = CString::new(a.as_os_str().as_bytes())?;
+    let b = CString::new(b.as_os_str().as_bytes())?;
+    // SAFETY: both paths are NUL-terminated and outlive the call
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            a.as_ptr(),
+            libc::AT_FDCWD,
+            b.as_ptr(),
+            libc::RENAME_EXCHANGE,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn exchange(_a: &Path, _b: &Path) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::HistoryEntry;
+
+    #[test]
+    fn test_rebuild_swap() {
+        let temp = tempfile::tempdir().unwrap();
+        let index_path = temp.path().join("abc123");
+        std::fs::create_dir_all(&index_path).unwrap();
+        std::fs::write(index_path.join("old.idx"), "old").unwrap();
+        let entry = HistoryEntry {
+            timestamp: "2026-01-01T00:00:00Z".into(),
+            files: 1,
+            embedded: 0,
+            size: 3,
+            duration_ms: 5,
+            semantic: false,
+        };
+        history::save(&index_path, std::slice::from_ref(&entry)).unwrap();
+
+        let staging = prepare(&index_path).unwrap();
+        assert!(staging.starts_with(temp.path()));
+        std::fs::write(staging.join("new.idx"), "new").unwrap();
+        // The old index is untouched until the swap
+        assert!(index_path.join("old.idx").exists());
+
+        swap(&staging, &index_path).unwrap();
+        assert!(index_path.join("new.idx").exists());
+        assert!(!index_path.join("old.idx").exists());
+        assert_eq!(history::load(&index_path), vec![entry]);
+        // Nothing is left next to the index
+        assert_eq!(std::fs::read_dir(temp.path()).unwrap().count(), 1);
+
+        // A first build has nothing to swap with
+        let fresh = temp.path().join("def456");
+        let staging = prepare(&fresh).unwrap();
+        swap(&staging, &fresh).unwrap();
+        assert!(fresh.is_dir() && !staging.exists());
+    }
+}
@@ -0,0 +1,440 @@
+//! Import/include graph (`indexer.reference_counts`): how many other files import each
+//! file, stored with its documents (the `references` fast field) as a mild popularity
+//! prior, so core modules outrank one-off scripts
+//!
+//! Imports are found with per-language patterns (Rust, Python, JavaScript/TypeScript,
+//! C/C++, Go, Java/Kotlin and Ruby) and resolved against the indexed files by path:
+//! relative imports exactly, module paths by the longest suffix that names a file,
+//! preferring files closest to the importer.
+
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::error::{Result, YgrepError};
+
+/// Reference counts of the last full index, used for files indexed again later
+const REFERENCES_FILE: &str = "references.json";
+
+/// An import as written in a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Import {
+    /// A path relative to the importing file's directory (`./session`, `"util.h"`)
+    Relative(String),
+    /// Module path segments (`crate::index::writer`, `com.acme.Auth`)
+    Module(Vec<String>),
+    /// A Go package: every file of the directory
+    Package(Vec<String>),
+}
+
+/// Finds imports in source files
+pub struct ReferenceScanner {
+    rust_mod: Regex,
+    rust_use: Regex,
+    python_from: Regex,
+    python_import: Regex,
+    js: Regex,
+    c_include: Regex,
+    go_import: Regex,
+    go_block: Regex,
+    quoted: Regex,
+    java_import: Regex,
+    ruby_require: Regex,
+}
+
+impl Default for ReferenceScanner {
+    fn default() -> Self {
+        let compile = |pattern: &str| Regex::new(pattern).expect("valid import pattern");
+        Self {
+            rust_mod: compile(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+(\w+)\s*;"),
+            rust_use: compile(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?use\s+((?:\w+::)*\w+)"),
+            python_from: compile(r"(?m)^\s*from\s+(\.*)([\w.]*)\s+import\b"),
+            python_import: compile(r"(?m)^\s*import\s+([\w.]+)"),
+            js: compile(r#"(?:\bfrom\s*|\bimport\s*\(?\s*|\brequire\(\s*)['"]([^'"\n]+)['"]"#),
+            c_include: compile(r#"(?m)^\s*#\s*include\s*"([^"]+)""#),
+            go_import: compile(r#"(?m)^\s*import\s+(?:[\w.]+\s+)?"([^"]+)""#),
+            go_block: compile(r"(?ms)^\s*import\s*\((.*?)\)"),
+            quoted: compile(r#""([^"\n]+)""#),
+            java_import: compile(r"(?m)^\s*import\s+(?:static\s+)?([\w.]+)"),
+            ruby_require: compile(r#"(?m)^\s*require(_relative)?\s*\(?\s*['"]([^'"]+)['"]"#),
+        }
+    }
+}
+
+impl ReferenceScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Imports in the content of a file with this extension (none for other languages)
+    fn imports(&self, extension: &str, content: &str) -> Vec<Import> {
+        let segments = |path: &str, separator: &str| -> Vec<String> {
+            path.split(separator)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let captures = |regex: &Regex, group: usize| -> Vec<String> {
+            regex
+                .captures_iter(content)
+                .filter_map(|c| Some(c.get(group)?.as_str().to_string()))
+                .collect()
+        };
+
+        match extension {
+            "rs" => {
+                let mut imports: Vec<Import> = captures(&self.rust_mod, 1)
+                    .into_iter()
+                    .map(Import::Relative)
+                    .collect();
+                imports.extend(captures(&self.rust_use, 1).into_iter().map(|path| {
+                    let segments = segments(&path, "::");
+                    let skip = matches!(
+                        segments.first().map(String::as_str),
+                        Some("crate" | "self" | "super")
+                    );
+                    Import::Module(segments.into_iter().skip(skip as usize).collect())
+                }));
+                imports
+            }
+            "py" => {
+                let mut imports = Vec::new();
+                for c in self.python_from.captures_iter(content) {
+                    let dots = c[1].len();
+                    let module = segments(&c[2], ".");
+                    if dots == 0 {
+                        imports.push(Import::Module(module));
+                    } else {
+                        let mut path = vec![".."; dots - 1].join("/");
+                        for segment in module {
+                            path = format!("{}/{}", path, segment);
+                        }
+                        imports.push(Import::Relative(path));
+                    }
+                }
+                imports.extend(
+                    captures(&self.python_import, 1)
+                        .iter()
+                        .map(|module| Import::Module(segments(module, "."))),
+                );
+                imports
+            }
+            "js" | "jsx" | "ts" | "tsx" | "mjs" | "cjs" | "mts" | "cts" | "vue" | "svelte" => {
+                captures(&self.js, 1)
+                    .into_iter()
+                    .map(|spec| {
+                        if spec.starts_with('.') {
+                            Import::Relative(spec)
+                        } else {
+                            Import::Module(segments(&spec, "/"))
+                        }
+                    })
+                    .collect()
+            }
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" | "m" | "mm" => {
+                captures(&self.c_include, 1)
+                    .into_iter()
+                    .map(Import::Relative)
+                    .collect()
+            }
+            "go" => {
+                let mut packages = captures(&self.go_import, 1);
+                for block in captures(&self.go_block, 1) {
+                    packages.extend(self.quoted.captures_iter(&block).map(|c| c[1].to_string()));
+                }
+                packages
+                    .iter()
+                    .map(|package| Import::Package(segments(package, "/")))
+                    .collect()
+            }
+            "java" | "kt" | "kts" | "scala" | "groovy" => captures(&self.java_import, 1)
+                .iter()
+                .map(|class| Import::Module(segments(class, ".")))
+                .collect(),
+            "rb" => self
+                .ruby_require
+                .captures_iter(content)
+                .map(|c| {
+                    if c.get(1).is_some() {
+                        Import::Relative(c[2].to_string())
+                    } else {
+                        Import::Module(segments(&c[2], "/"))
+                    }
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// How many other files import each of `files` (paths relative to `root`,
+    /// `/`-separated); files nothing imports are left out
+    pub fn count(&self, root: &Path, files: &[String]) -> HashMap<String, u64> {
+        let table = FileTable::new(files);
+        let mut counts = vec![0u64; files.len()];
+        for (importer, path) in files.iter().enumerate() {
+            let extension = Path::new(path)
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let imports = match std::fs::read_to_string(root.join(path)) {
+                Ok(content) => self.imports(&extension, &content),
+                Err(_) => continue,
+            };
+
+            // Each importer counts once per file it imports
+            let mut targets = HashSet::new();
+            for import in &imports {
+                targets.extend(table.resolve(importer, import));
+            }
+            targets.remove(&importer);
+            for target in targets {
+                counts[target] += 1;
+            }
+        }
+
+        files
+            .iter()
+            .zip(counts)
+            .filter(|(_, count)| *count > 0)
+            .map(|(path, count)| (path.clone(), count))
+            .collect()
+    }
+}
+
+/// Indexed files by the names imports may use for them
+struct FileTable<'a> {
+    paths: &'a [String],
+    /// Path without extension, and for module files also their directory
+    /// (`src/index/mod.rs` is `src/index/mod` and `src/index`)
+    by_key: HashMap<String, Vec<usize>>,
+    /// Every trailing run of segments of the key
+    by_suffix: HashMap<String, Vec<usize>>,
+    /// Every trailing run of segments of the file's directory
+    by_dir_suffix: HashMap<String, Vec<usize>>,
+}
+
+impl<'a> FileTable<'a> {
+    fn new(paths: &'a [String]) -> Self {
+        let mut table = Self {
+            paths,
+            by_key: HashMap::new(),
+            by_suffix: HashMap::new(),
+            by_dir_suffix: HashMap::new(),
+        };
+        for (i, path) in paths.iter().enumerate() {
+            let key = module_key(path);
+            for suffix in suffixes(&key) {
+                table.by_suffix.entry(suffix).or_default().push(i);
+            }
+            if key != without_extension(path) {
+                table
+                    .by_key
+                    .entry(without_extension(path).to_string())
+                    .or_default()
+                    .push(i);
+            }
+            table.by_key.entry(key).or_default().push(i);
+            if let Some((dir, _)) = path.rsplit_once('/') {
+                for suffix in suffixes(dir) {
+                    table.by_dir_suffix.entry(suffix).or_default().push(i);
+                }
+            }
+        }
+        table
+    }
+
+    /// Files an import of file `importer` refers to
+    fn resolve(&self, importer: usize, import: &Import) -> Vec<usize> {
+        let importer_path = &self.paths[importer];
+        let dir = importer_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        match import {
+            Import::Relative(spec) => {
+                // A Rust `mod` declared in `foo.rs` lives in `foo/`
+                let base = match Path::new(importer_path).file_stem() {
+                    Some(stem)
+                        if importer_path.ends_with(".rs")
+                            && !matches!(stem.to_str(), Some("mod" | "lib" | "main")) =>
+                    {
+                        without_extension(importer_path).to_string()
+                    }
+                    _ => dir.to_string(),
+                };
+                let Some(path) = join(&base, spec) else {
+                    return Vec::new();
+                };
+                // `./config.service` names `config.service.ts`, `util.h` names `util.h`
+                self.by_key
+                    .get(&path)
+                    .or_else(|| self.by_key.get(without_extension(&path)))
+                    .cloned()
+                    .unwrap_or_default()
+            }
+            Import::Module(segments) => {
+                // Leading segments may be a crate, package or alias (`@/components/x`)
+                for skip in 0..segments.len().min(2) {
+                    let segments = &segments[skip..];
+                    for len in (1..=segments.len()).rev() {
+                        if let Some(files) = self.by_suffix.get(&segments[..len].join("/")) {
+                            return closest(self.paths, files, dir);
+                        }
+                    }
+                }
+                Vec::new()
+            }
+            Import::Package(segments) => {
+                for start in 0..segments.len() {
+                    if let Some(files) = self.by_dir_suffix.get(&segments[start..].join("/")) {
+                        return files.clone();
+                    }
+                }
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// A path without its extension
+fn without_extension(path: &str) -> &str {
+    match path.rsplit_once('.') {
+        Some((stem, extension)) if !extension.contains('/') && !stem.ends_with('/') => stem,
+        _ => path,
+    }
+}
+
+/// The name a module path uses for a file: its path without extension, or for a file
+/// named like its directory's module (`mod.rs`, `__init__.py`, `index.ts`) the directory
+fn module_key(path: &str) -> String {
+    let stem = without_extension(path);
+    let extension = &path[stem.len()..];
+    let module_file = match stem.rsplit_once('/') {
+        Some((dir, name)) => matches!(
+            (name, extension),
+            ("mod", ".rs")
+                | ("__init__", ".py")
+                | (
+                    "index",
+                    ".js" | ".jsx" | ".ts" | ".tsx" | ".mjs" | ".cjs" | ".mts" | ".cts"
+                )
+        )
+        .then_some(dir),
+        None => None,
+    };
+    module_file.unwrap_or(stem).to_string()
+}
+
+/// `a/b/c`, `b/c` and `c`
+fn suffixes(path: &str) -> impl Iterator<Item = String> + '_ {
+    let segments: Vec<&str> = path.split('/').collect();
+    (0..segments.len()).map(move |start| segments[start..].join("/"))
+}
+
+/// `spec` relative to `base`, with `.` and `..` resolved; `None` above the root
+fn join(base: &str, spec: &str) -> Option<String> {
+    let mut segments: Vec<&str> = base.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in spec.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+/// The files sharing the longest directory prefix with `dir`
+fn closest(paths: &[String], files: &[usize], dir: &str) -> Vec<usize> {
+    let shared = |i: &usize| {
+        dir.split('/')
+            .zip(paths[*i].split('/'))
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+    let best = files.iter().map(shared).max().unwrap_or(0);
+    files
+        .iter()
+        .filter(|i| shared(i) == best)
+        .copied()
+        .collect()
+}
+
+/// Reference counts saved by the last full index (empty without any)
+pub fn load(index_path: &Path) -> HashMap<String, u64> {
+    std::fs::read_to_string(index_path.join(REFERENCES_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Save reference counts for later incremental updates; `None` removes them
+pub fn save(index_path: &Path, counts: Option<&HashMap<String, u64>>) -> Result<()> {
+    let path = index_path.join(REFERENCES_FILE);
+    let Some(counts) = counts else {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        return Ok(());
+    };
+    let json = serde_json::to_string(counts)
+        .map_err(|e| YgrepError::Config(format!("Failed to save reference counts: {}", e)))?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_references() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = [
+            (
+                "src/lib.rs",
+                "mod config;\npub mod index;\nuse crate::config::Config;",
+            ),
+            ("src/config.rs", "pub struct Config;"),
+            ("src/index/mod.rs", "mod writer;\nuse super::config;"),
+            ("src/index/writer.rs", "use crate::config::Config;"),
+            (
+                "web/app.ts",
+                "import { api } from './api/client';\nimport x from 'react';",
+            ),
+            ("web/api/client.ts", "export const api = 1;"),
+            ("web/api/index.ts", "export * from \"./client\";"),
+            (
+                "tools/run.py",
+                "from .helpers import go\nimport tools.helpers",
+            ),
+            ("tools/helpers.py", "def go(): pass"),
+            ("native/main.c", "#include \"util.h\"\n#include <stdio.h>"),
+            ("native/util.h", "int util(void);"),
+            (
+                "cmd/main.go",
+                "import (\n\t\"fmt\"\n\t\"example.com/app/internal/auth\"\n)",
+            ),
+            ("internal/auth/auth.go", "package auth"),
+            ("script.sh", "source ./src/config.rs"),
+        ];
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let paths: Vec<String> = files.iter().map(|(p, _)| p.to_string()).collect();
+
+        let counts = ReferenceScanner::new().count(dir.path(), &paths);
+        let count = |path: &str| counts.get(path).copied().unwrap_or(0);
+        // lib.rs and writer.rs import it by module path, index/mod.rs through `super`
+        assert_eq!(count("src/config.rs"), 3);
+        assert_eq!(count("src/index/mod.rs"), 1);
+        assert_eq!(count("src/index/writer.rs"), 1);
+        assert_eq!(count("web/api/client.ts"), 2);
+        assert_eq!(count("tools/helpers.py"), 1);
+        assert_eq!(count("native/util.h"), 1);
+        assert_eq!(count("internal/auth/auth.go"), 1);
+        assert_eq!(count("src/lib.rs"), 0);
+        assert_eq!(count("script.sh"), 0);
+    }
+}
@@ -0,0 +1,547 @@
+//! Comments and string literals of source files, tagged at index time so searches can be
+//! limited to them or to the code around them (`in:comments`, `in:strings`, `in:code`).
+//!
+//! This is a lexer, not a parser: it knows the comment markers and string quotes of each
+//! language family, which is enough to tell a TODO in a comment from the same word in
+//! code. Python docstrings are strings, as they are to Python. Documents store their
+//! regions as byte ranges of their own content (`fields::REGIONS`); documents of languages
+//! the lexer doesn't know have none stored.
+
+use std::ops::Range;
+
+use crate::fs::language_for_extension;
+
+/// What a tagged stretch of a source file is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    Comment,
+    String,
+}
+
+impl RegionKind {
+    fn tag(self) -> char {
+        match self {
+            Self::Comment => 'c',
+            Self::String => 's',
+        }
+    }
+
+    fn from_tag(tag: char) -> Option<Self> {
+        match tag {
+            'c' => Some(Self::Comment),
+            's' => Some(Self::String),
+            _ => None,
+        }
+    }
+}
+
+/// The comments and string literals of a document, as byte ranges of its content
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Regions(Vec<(RegionKind, Range<usize>)>);
+
+/// A kind of string literal
+#[derive(Clone, Copy)]
+struct Quote {
+    open: &'static str,
+    close: &'static str,
+    /// A backslash escapes the next character
+    escapes: bool,
+    /// It may span lines (others end with their line when left open)
+    multiline: bool,
+}
+
+const fn quote(open: &'static str, close: &'static str, escapes: bool, multiline: bool) -> Quote {
+    Quote {
+        open,
+        close,
+        escapes,
+        multiline,
+    }
+}
+
+const DOUBLE: Quote = quote("\"", "\"", true, false);
+const SINGLE: Quote = quote("'", "'", true, false);
+/// Shell and SQL: no escapes inside `'...'`
+const VERBATIM_SINGLE: Quote = quote("'", "'", false, false);
+const TRIPLE_DOUBLE: Quote = quote("\"\"\"", "\"\"\"", true, true);
+const TRIPLE_SINGLE: Quote = quote("'''", "'''", true, true);
+/// JavaScript template literals
+const TEMPLATE: Quote = quote("`", "`", true, true);
+/// Go raw strings
+const RAW_BACKTICK: Quote = quote("`", "`", false, true);
+const MULTILINE_DOUBLE: Quote = quote("\"", "\"", true, true);
+/// Lua long strings
+const LONG_BRACKETS: Quote = quote("[[", "]]", false, true);
+
+/// How a language family writes comments and strings
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    /// Block comments nest (`/* /* */ */` in Rust)
+    nested: bool,
+    /// Longest opening quote first
+    quotes: &'static [Quote],
+    /// Comments start only at the start of a word (`$#` in shell is no comment)
+    word_comments: bool,
+    /// Rust: `r#"..."#` raw strings, and `'` opens a char literal only before a single
+    /// character or escape (otherwise it is a lifetime)
+    rust: bool,
+}
+
+const fn syntax(
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    quotes: &'static [Quote],
+) -> Syntax {
+    Syntax {
+        line_comments,
+        block_comments,
+        nested: false,
+        quotes,
+        word_comments: false,
+        rust: false,
+    }
+}
+
+const C_BLOCK: &[(&str, &str)] = &[("/*", "*/")];
+
+const RUST: Syntax = Syntax {
+    nested: true,
+    rust: true,
+    ..syntax(&["//"], C_BLOCK, &[MULTILINE_DOUBLE])
+};
+const C_LIKE: Syntax = syntax(&["//"], C_BLOCK, &[DOUBLE, SINGLE]);
+const JAVASCRIPT: Syntax = syntax(&["//"], C_BLOCK, &[DOUBLE, SINGLE, TEMPLATE]);
+const GO: Syntax = syntax(&["//"], C_BLOCK, &[DOUBLE, SINGLE, RAW_BACKTICK]);
+/// Kotlin, Scala, Swift, Groovy: also `"""` strings
+const TRIPLE_C_LIKE: Syntax = syntax(&["//"], C_BLOCK, &[TRIPLE_DOUBLE, DOUBLE, SINGLE]);
+const PHP: Syntax = syntax(&["//", "#"], C_BLOCK, &[DOUBLE, SINGLE]);
+const CSS: Syntax = syntax(&[], C_BLOCK, &[DOUBLE, SINGLE]);
+const PYTHON: Syntax = syntax(&["#"], &[], &[TRIPLE_DOUBLE, TRIPLE_SINGLE, DOUBLE, SINGLE]);
+const ELIXIR: Syntax = syntax(&["#"], &[], &[TRIPLE_DOUBLE, DOUBLE, SINGLE]);
+const HASH: Syntax = syntax(&["#"], &[], &[DOUBLE, SINGLE]);
+/// Shell, Perl, YAML and friends, where `#` inside a word is no comment
+const HASH_WORD: Syntax = Syntax {
+    word_comments: true,
+    ..syntax(&["#"], &[], &[DOUBLE, VERBATIM_SINGLE])
+};
+const NIX: Syntax = syntax(&["#"], C_BLOCK, &[DOUBLE]);
+const POWERSHELL: Syntax = syntax(&["#"], &[("<#", "#>")], &[DOUBLE, VERBATIM_SINGLE]);
+const JULIA: Syntax = Syntax {
+    nested: true,
+    ..syntax(&["#"], &[("#=", "=#")], &[TRIPLE_DOUBLE, DOUBLE])
+};
+const GRAPHQL: Syntax = syntax(&["#"], &[], &[TRIPLE_DOUBLE, DOUBLE]);
+const INI: Syntax = syntax(&[";", "#"], &[], &[]);
+const SQL: Syntax = syntax(&["--"], C_BLOCK, &[VERBATIM_SINGLE, DOUBLE]);
+const LUA: Syntax = syntax(&["--"], &[("--[[", "]]")], &[DOUBLE, SINGLE, LONG_BRACKETS]);
+const HASKELL: Syntax = Syntax {
+    nested: true,
+    ..syntax(&["--"], &[("{-", "-}")], &[DOUBLE])
+};
+const OCAML: Syntax = Syntax {
+    nested: true,
+    ..syntax(&[], &[("(*", "*)")], &[DOUBLE])
+};
+const FSHARP: Syntax = syntax(&["//"], &[("(*", "*)")], &[TRIPLE_DOUBLE, DOUBLE]);
+const ERLANG: Syntax = syntax(&["%"], &[], &[DOUBLE]);
+const LISP: Syntax = syntax(&[";"], &[], &[DOUBLE]);
+const MARKUP: Syntax = syntax(&[], &[("<!--", "-->")], &[]);
+const JSON: Syntax = syntax(&[], &[], &[DOUBLE]);
+
+/// The syntax of a language (as `fs::language_for_extension` names it), if the lexer
+/// knows it; prose, data and template languages mixing several others have none
+fn syntax_of(language: &str) -> Option<&'static Syntax> {
+    Some(match language {
+        "rust" => &RUST,
+        "c" | "cpp" | "csharp" | "java" | "protobuf" | "scss" | "less" | "sass" => &C_LIKE,
+        "javascript" | "typescript" => &JAVASCRIPT,
+        "go" => &GO,
+        "kotlin" | "scala" | "swift" | "gradle" => &TRIPLE_C_LIKE,
+        "php" | "thrift" | "hcl" | "terraform" => &PHP,
+        "css" => &CSS,
+        "python" => &PYTHON,
+        "elixir" => &ELIXIR,
+        "ruby" | "r" | "cmake" | "toml" => &HASH,
+        "shell" | "perl" | "yaml" | "makefile" | "dockerfile" | "just" => &HASH_WORD,
+        "nix" => &NIX,
+        "powershell" => &POWERSHELL,
+        "julia" => &JULIA,
+        "graphql" => &GRAPHQL,
+        "ini" => &INI,
+        "sql" => &SQL,
+        "lua" => &LUA,
+        "haskell" => &HASKELL,
+        "ocaml" => &OCAML,
+        "fsharp" => &FSHARP,
+        "erlang" => &ERLANG,
+        "clojure" => &LISP,
+        "html" | "xml" => &MARKUP,
+        "json" => &JSON,
+        _ => return None,
+    })
+}
+
+impl Regions {
+    /// The comments and strings of a file with `extension`; `None` when the lexer doesn't
+    /// know its language
+    pub fn lex(extension: &str, content: &str) -> Option<Self> {
+        let syntax = syntax_of(language_for_extension(extension)?)?;
+        Some(Self(Lexer::new(syntax, content).run()))
+    }
+
+    /// The regions of `lines` (byte ranges of lines of the lexed content, as `line_spans`
+    /// gives them) within those lines joined with `\n`, as chunk and line documents
+    /// store them
+    pub fn for_lines(&self, lines: &[Range<usize>]) -> Self {
+        let mut regions: Vec<(RegionKind, Range<usize>)> = Vec::new();
+        let mut offset = 0;
+        for line in lines {
+            let first = self.0.partition_point(|(_, range)| range.end <= line.start);
+            for (kind, range) in &self.0[first..] {
+                if range.start >= line.end {
+                    break;
+                }
+                let start = offset + range.start.max(line.start) - line.start;
+                let end = offset + range.end.min(line.end) - line.start;
+                match regions.last_mut() {
+                    // One comment or string going on over the line break
+                    Some((last, previous)) if *last == *kind && previous.end + 1 == start => {
+                        previous.end = end
+                    }
+                    _ => regions.push((*kind, start..end)),
+                }
+            }
+            offset += line.len() + 1;
+        }
+        Self(regions)
+    }
+
+    /// `content` with everything outside the regions `keep` accepts (`None` for code)
+    /// blanked out. Byte offsets and line breaks stay as they were, so lines and columns
+    /// of matches in it hold for `content`.
+    pub fn mask(&self, content: &str, keep: impl Fn(Option<RegionKind>) -> bool) -> String {
+        let mut masked = String::with_capacity(content.len());
+        let mut add = |text: &str, kept: bool| {
+            if kept {
+                masked.push_str(text);
+                return;
+            }
+            for c in text.chars() {
+                if c == '\n' {
+                    masked.push('\n');
+                } else {
+                    masked.extend(std::iter::repeat_n(' ', c.len_utf8()));
+                }
+            }
+        };
+        let mut position = 0;
+        for (kind, range) in &self.0 {
+            let (start, end) = (range.start.max(position), range.end.min(content.len()));
+            if start >= end || !content.is_char_boundary(start) || !content.is_char_boundary(end) {
+                continue;
+            }
+            add(&content[position..start], keep(None));
+            add(&content[start..end], keep(Some(*kind)));
+            position = end;
+        }
+        add(&content[position..], keep(None));
+        masked
+    }
+
+    /// Compact form for the index: `c0-12 s20-31`
+    pub fn encode(&self) -> String {
+        let regions: Vec<String> = self
+            .0
+            .iter()
+            .map(|(kind, range)| format!("{}{}-{}", kind.tag(), range.start, range.end))
+            .collect();
+        regions.join(" ")
+    }
+
+    /// Read regions stored with `encode`, skipping what doesn't parse
+    pub fn decode(encoded: &str) -> Self {
+        let regions = encoded
+            .split_whitespace()
+            .filter_map(|region| {
+                let mut chars = region.chars();
+                let kind = RegionKind::from_tag(chars.next()?)?;
+                let (start, end) = chars.as_str().split_once('-')?;
+                Some((kind, start.parse().ok()?..end.parse().ok()?))
+            })
+            .collect();
+        Self(regions)
+    }
+}
+
+/// Byte ranges of the lines of `content` as `str::lines` yields them
+pub fn line_spans(content: &str) -> Vec<Range<usize>> {
+    let base = content.as_ptr() as usize;
+    content
+        .lines()
+        .map(|line| {
+            let start = line.as_ptr() as usize - base;
+            start..start + line.len()
+        })
+        .collect()
+}
+
+struct Lexer<'a> {
+    syntax: &'a Syntax,
+    bytes: &'a [u8],
+    content: &'a str,
+    regions: Vec<(RegionKind, Range<usize>)>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(syntax: &'a Syntax, content: &'a str) -> Self {
+        Self {
+            syntax,
+            bytes: content.as_bytes(),
+            content,
+            regions: Vec::new(),
+        }
+    }
+
+    fn run(mut self) -> Vec<(RegionKind, Range<usize>)> {
+        let mut i = 0;
+        while i < self.bytes.len() {
+            let start = i;
+            i = if let Some(&(open, close)) = self.starting(i, self.syntax.block_comments) {
+                let end = self.block_comment_end(i + open.len(), open, close);
+                self.regions.push((RegionKind::Comment, start..end));
+                end
+            } else if self.starting(i, self.syntax.line_comments).is_some()
+                && (!self.syntax.word_comments || self.at_word_start(i))
+            {
+                let end = self.line_end(i);
+                self.regions.push((RegionKind::Comment, start..end));
+                end
+            } else if let Some(end) = self.string_end(i) {
+                self.regions.push((RegionKind::String, start..end));
+                end
+            } else {
+                i + 1
+            };
+        }
+        self.regions
+    }
+
+    /// The delimiter (or delimiter pair) of `delimiters` starting at `i`
+    fn starting<T: Delimiter>(&self, i: usize, delimiters: &'a [T]) -> Option<&'a T> {
+        delimiters
+            .iter()
+            .find(|d| self.bytes[i..].starts_with(d.open().as_bytes()))
+    }
+
+    fn at_word_start(&self, i: usize) -> bool {
+        i == 0 || self.bytes[i - 1].is_ascii_whitespace()
+    }
+
+    /// End of the line `i` is on (before its line break)
+    fn line_end(&self, i: usize) -> usize {
+        self.bytes[i..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(self.bytes.len(), |n| i + n)
+    }
+
+    /// End of a block comment whose text starts at `i`
+    fn block_comment_end(&self, mut i: usize, open: &str, close: &str) -> usize {
+        let mut depth = 1;
+        while i < self.bytes.len() {
+            if self.bytes[i..].starts_with(close.as_bytes()) {
+                depth -= 1;
+                i += close.len();
+                if depth == 0 {
+                    return i;
+                }
+            } else if self.syntax.nested && self.bytes[i..].starts_with(open.as_bytes()) {
+                depth += 1;
+                i += open.len();
+            } else {
+                i += 1;
+            }
+        }
+        self.bytes.len()
+    }
+
+    /// End of the string literal starting at `i`, if one does
+    fn string_end(&self, i: usize) -> Option<usize> {
+        if self.syntax.rust {
+            if let Some(end) = self.rust_raw_string_end(i) {
+                return Some(end);
+            }
+            if self.bytes[i] == b'\'' {
+                return self.char_literal_end(i);
+            }
+        }
+        let quote = self.starting(i, self.syntax.quotes)?;
+        let mut j = i + quote.open.len();
+        while j < self.bytes.len() {
+            if quote.escapes && self.bytes[j] == b'\\' {
+                j += 2;
+            } else if self.bytes[j..].starts_with(quote.close.as_bytes()) {
+                return Some(j + quote.close.len());
+            } else if self.bytes[j] == b'\n' && !quote.multiline {
+                return Some(j);
+            } else {
+                j += 1;
+            }
+        }
+        Some(self.bytes.len())
+    }
+
+    /// `r"..."`, `r#"..."#` and `br"..."`
+    fn rust_raw_string_end(&self, i: usize) -> Option<usize> {
+        let after_prefix = match self.bytes[i..] {
+            [b'r', ..] => i + 1,
+            [b'b', b'r', ..] => i + 2,
+            _ => return None,
+        };
+        let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+        if i > 0 && is_ident(self.bytes[i - 1]) {
+            return None;
+        }
+        let hashes = self.bytes[after_prefix..]
+            .iter()
+            .take_while(|&&b| b == b'#')
+            .count();
+        let open = after_prefix + hashes;
+        if self.bytes.get(open) != Some(&b'"') {
+            return None;
+        }
+        let close = format!("\"{}", "#".repeat(hashes));
+        let end = self.bytes[open + 1..]
+            .windows(close.len())
+            .position(|window| window == close.as_bytes())
+            .map_or(self.bytes.len(), |n| open + 1 + n + close.len());
+        Some(end)
+    }
+
+    /// `'a'`, `'\n'`, `'\u{1F600}'`, but not the lifetime in `&'a str`
+    fn char_literal_end(&self, i: usize) -> Option<usize> {
+        let rest = &self.content[i + 1..];
+        let length = if rest.starts_with('\\') {
+            // (the escaped character may be the quote itself)
+            rest.get(2..)?
+                .find(['\'', '\n'])
+                .filter(|&n| n <= 8)
+                .map(|n| n + 2)?
+        } else {
+            rest.chars().next()?.len_utf8()
+        };
+        rest[length..]
+            .starts_with('\'')
+            .then_some(i + 1 + length + 1)
+    }
+}
+
+/// Something opened by a marker: a line comment marker, block comment delimiters, a quote
+trait Delimiter {
+    fn open(&self) -> &str;
+}
+
+impl Delimiter for &str {
+    fn open(&self) -> &str {
+        self
+    }
+}
+
+impl Delimiter for (&str, &str) {
+    fn open(&self) -> &str {
+        self.0
+    }
+}
+
+impl Delimiter for Quote {
+    fn open(&self) -> &str {
+        self.open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text of each region, tagged
+    fn lexed(extension: &str, content: &str) -> Vec<(RegionKind, String)> {
+        let regions = Regions::lex(extension, content).unwrap();
+        regions
+            .0
+            .iter()
+            .map(|(kind, range)| (*kind, content[range.clone()].to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_lex() {
+        use RegionKind::{Comment, String as Str};
+        let s = |text: &str| text.to_string();
+
+        let rust = "/// Docs\nfn f<'a>(x: &'a str) -> char { /* a /* nested */ b */\n    \
+                    g(\"// not a comment\", r#\"raw \"q\"\"#, '\"', '\\'') // TODO\n}\n";
+        assert_eq!(
+            lexed("rs", rust),
+            vec![
+                (Comment, s("/// Docs")),
+                (Comment, s("/* a /* nested */ b */")),
+                (Str, s("\"// not a comment\"")),
+                (Str, s("r#\"raw \"q\"\"#")),
+                (Str, s("'\"'")),
+                (Str, s("'\\''")),
+                (Comment, s("// TODO")),
+            ]
+        );
+
+        let python = "def f():\n    \"\"\"Docstring\n    # still the docstring\n    \"\"\"\n    \
+                      return 'a\\'b'  # note\n";
+        assert_eq!(
+            lexed("py", python),
+            vec![
+                (
+                    Str,
+                    s("\"\"\"Docstring\n    # still the docstring\n    \"\"\"")
+                ),
+                (Str, s("'a\\'b'")),
+                (Comment, s("# note")),
+            ]
+        );
+
+        // `#` inside a word is no shell comment, and an unclosed string ends with its line
+        let shell = "echo $# \"unclosed\necho ok # done\n";
+        assert_eq!(
+            lexed("sh", shell),
+            vec![(Str, s("\"unclosed")), (Comment, s("# done"))]
+        );
+        assert_eq!(
+            lexed("lua", "--[[ block ]] x = [[long]] -- line"),
+            vec![
+                (Comment, s("--[[ block ]]")),
+                (Str, s("[[long]]")),
+                (Comment, s("-- line")),
+            ]
+        );
+        assert!(Regions::lex("md", "# Title").is_none());
+    }
+
+    #[test]
+    fn test_regions_of_lines() {
+        let content = "let a = 1; /* one\r\ntwo */ let b = \"x\";\r\n// three\r\n";
+        let regions = Regions::lex("js", content).unwrap();
+        let spans = line_spans(content);
+        // The chunk of the last two lines, as `lines().join("\n")` gives it
+        let chunk = "two */ let b = \"x\";\n// three";
+        let chunk_regions = regions.for_lines(&spans[1..3]);
+        assert_eq!(chunk_regions.encode(), "c0-6 s15-18 c20-28");
+        assert_eq!(Regions::decode(&chunk_regions.encode()), chunk_regions);
+
+        let comments = chunk_regions.mask(chunk, |kind| kind == Some(RegionKind::Comment));
+        assert_eq!(comments, format!("two */{}\n// three", " ".repeat(13)));
+        let code = chunk_regions.mask(chunk, |kind| kind.is_none());
+        assert_eq!(code, "       let b =    ;\n        ");
+
+        // A comment going on over a line break stays one region
+        assert_eq!(regions.for_lines(&spans[..2]).encode(), "c11-24 s33-36");
+    }
+}
@@ -0,0 +1,188 @@
+//! Index repair (`ygrep indexes repair`): salvaging what can be kept of a damaged index,
+//! so only the affected files need indexing again instead of the whole workspace
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tantivy::schema::TantivyDocument;
+use tantivy::Index;
+
+use super::SchemaFields;
+use crate::error::{Result, YgrepError};
+
+/// What a repair did
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    /// Problems fixed, one line each
+    pub repaired: Vec<String>,
+    /// Files indexed again because their documents or vectors were lost
+    pub reindexed: usize,
+}
+
+impl RepairReport {
+    /// Whether nothing needed repairing
+    pub fn is_clean(&self) -> bool {
+        self.repaired.is_empty() && self.reindexed == 0
+    }
+}
+
+/// Whether a file in the index directory belongs to Tantivy (the rest is ours)
+fn is_tantivy_file(name: &str) -> bool {
+    // Segment files are named `<segment uuid>.<component>`
+    let segment = name
+        .split('.')
+        .next()
+        .is_some_and(|stem| stem.len() == 32 && stem.bytes().all(|b| b.is_ascii_hexdigit()));
+    segment
+        || matches!(
+            name,
+            "meta.json" | ".managed.json" | ".tantivy-meta.lock" | ".tantivy-writer.lock"
+        )
+}
+
+/// Remove the text index, leaving our own files (metadata, vectors, history) alone
+fn remove_text_index(index_path: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(index_path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() && entry.file_name().to_str().is_some_and(is_tantivy_file) {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Segments (by uuid without dashes) whose files fail their checksums or whose stored
+/// documents can't be read
+fn damaged_segments(index: &Index) -> Result<HashSet<String>> {
+    let mut damaged: HashSet<String> = index
+        .validate_checksum()?
+        .iter()
+        .filter_map(|file| file.to_str()?.split('.').next().map(String::from))
+        .collect();
+
+    let reader = index.reader()?;
+    for segment in reader.searcher().segment_readers() {
+        let id = segment.segment_id().uuid_string();
+        if damaged.contains(&id) {
+            continue;
+        }
+        let readable = segment.get_store_reader(1).is_ok_and(|store| {
+            store
+                .iter::<TantivyDocument>(segment.alive_bitset())
+                .all(|doc| doc.is_ok())
+        });
+        if !readable {
+            damaged.insert(id);
+        }
+    }
+    Ok(damaged)
+}
+
+/// Make the text index usable again: drop damaged segments from meta.json, or recreate
+/// the index when it can't be opened at all. Returns whether documents were lost (so
+/// files have to be indexed again).
+pub(crate) fn salvage_text_index(index_path: &Path, report: &mut RepairReport) -> Result<bool> {
+    if !index_path.join("meta.json").exists() {
+        remove_text_index(index_path)?;
+        report
+            .repaired
+            .push("Recreated the text index (meta.json was missing)".to_string());
+        return Ok(true);
+    }
+    let damaged = match Index::open_in_dir(index_path)
+        .map_err(YgrepError::from)
+        .and_then(|index| damaged_segments(&index))
+    {
+        Ok(damaged) => damaged,
+        Err(e) => {
+            remove_text_index(index_path)?;
+            report.repaired.push(format!(
+                "Recreated the text index (it couldn't be opened: {})",
+                e
+            ));
+            return Ok(true);
+        }
+    };
+    if damaged.is_empty() {
+        return Ok(false);
+    }
+
+    // Tantivy only reads the segments meta.json lists, so dropping them there is enough
+    let meta_path = index_path.join("meta.json");
+    let mut meta: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&meta_path)?)
+            .map_err(|e| YgrepError::Config(format!("Failed to read meta.json: {}", e)))?;
+    if let Some(segments) = meta.get_mut("segments").and_then(|s| s.as_array_mut()) {
+        segments.retain(|segment| {
+            let id = segment
+                .get("segment_id")
+                .and_then(|id| id.as_str())
+                .unwrap_or_default()
+                .replace('-', "");
+            !damaged.contains(&id)
+        });
+    }
+    let json = serde_json::to_string_pretty(&meta)
+        .map_err(|e| YgrepError::Config(format!("Failed to write meta.json: {}", e)))?;
+    let temp = index_path.join("meta.json.tmp");
+    std::fs::write(&temp, json)?;
+    std::fs::rename(temp, &meta_path)?;
+
+    for entry in std::fs::read_dir(index_path)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(stem) = name.to_str().and_then(|name| name.split('.').next()) else {
+            continue;
+        };
+        if damaged.contains(stem) {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+    report.repaired.push(format!(
+        "Dropped {} damaged text index segment{}",
+        damaged.len(),
+        if damaged.len() == 1 { "" } else { "s" }
+    ));
+    Ok(true)
+}
+
+/// Paths of the files in the text index, by doc_id (chunks left out)
+pub(crate) fn indexed_documents(index: &Index) -> Result<HashMap<String, String>> {
+    let fields = SchemaFields::new(&index.schema());
+    let text = |doc: &TantivyDocument, field| match doc.get_first(field) {
+        Some(tantivy::schema::OwnedValue::Str(s)) => Some(s.clone()),
+        _ => None,
+    };
+
+    let reader = index.reader()?;
+    let mut documents = HashMap::new();
+    for segment in reader.searcher().segment_readers() {
+        let store = segment.get_store_reader(1)?;
+        for doc in store.iter::<TantivyDocument>(segment.alive_bitset()) {
+            let doc = doc?;
+            if text(&doc, fields.chunk_id).is_some_and(|chunk| !chunk.is_empty()) {
+                continue;
+            }
+            if let (Some(doc_id), Some(path)) = (text(&doc, fields.doc_id), text(&doc, fields.path))
+            {
+                documents.insert(doc_id, path);
+            }
+        }
+    }
+    Ok(documents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_tantivy_file() {
+        assert!(is_tantivy_file("meta.json"));
+        assert!(is_tantivy_file(".managed.json"));
+        assert!(is_tantivy_file("c433b1d101704f97b0e691f60a2dcba6.store"));
+        assert!(is_tantivy_file("c433b1d101704f97b0e691f60a2dcba6.12.del"));
+        assert!(!is_tantivy_file("workspace.json"));
+        assert!(!is_tantivy_file("history.json"));
+        assert!(!is_tantivy_file("vectors"));
+    }
+}
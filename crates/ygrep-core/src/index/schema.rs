@@ -3,6 +3,9 @@ use tantivy::schema::{
 };
 use tantivy::tokenizer::{LowerCaser, RemoveLongFilter, TextAnalyzer, TokenizerManager};
 
+use super::normalize::{is_cjk, is_token_mark, normalize};
+use super::stemming::{prose_analyzer, PROSE_TOKENIZER};
+
 /// Name of our custom code tokenizer
 pub const CODE_TOKENIZER: &str = "code";
 
@@ -16,6 +19,11 @@ pub fn register_tokenizers(tokenizer_manager: &TokenizerManager) {
         .build();
 
     tokenizer_manager.register(CODE_TOKENIZER, code_tokenizer);
+    // English until `Stemming::register` sets the configured language
+    tokenizer_manager.register(
+        PROSE_TOKENIZER,
+        prose_analyzer(tantivy::tokenizer::Language::English),
+    );
 }
 
 /// Custom tokenizer for code that preserves $, @, #, etc.
@@ -30,6 +38,7 @@ impl tantivy::tokenizer::Tokenizer for CodeTokenizer {
             text,
             chars: text.char_indices().peekable(),
             token: tantivy::tokenizer::Token::default(),
+            pending_pieces: Vec::new(),
         }
     }
 }
@@ -38,13 +47,68 @@ struct CodeTokenStream<'a> {
     text: &'a str,
     chars: std::iter::Peekable<std::str::CharIndices<'a>>,
     token: tantivy::tokenizer::Token,
+    /// Byte ranges still to emit from a word mixing CJK and other text, each at its own
+    /// position (reversed)
+    pending_pieces: Vec<(usize, usize)>,
+}
+
+impl CodeTokenStream<'_> {
+    /// Make `text[start..end]` the current token
+    fn emit(&mut self, start: usize, end: usize) {
+        self.token.offset_from = start;
+        self.token.offset_to = end;
+        // Index the normalized, diacritic-free form so every spelling is a candidate;
+        // the literal post-filter decides how strictly to compare
+        self.token
+            .text
+            .push_str(&normalize(&self.text[start..end], true));
+    }
+}
+
+/// Pieces of the word `text[start..end]`: its non-CJK stretches whole, and every pair of
+/// adjacent characters in its CJK runs (a lone CJK character on its own)
+fn cjk_pieces(text: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut pieces = Vec::new();
+    let mut chars = text[start..end].char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        let from = start + offset;
+        if !is_cjk(c) {
+            let mut to = from + c.len_utf8();
+            while let Some(&(offset, c)) = chars.peek() {
+                if is_cjk(c) {
+                    break;
+                }
+                to = start + offset + c.len_utf8();
+                chars.next();
+            }
+            pieces.push((from, to));
+            continue;
+        }
+        match chars.peek() {
+            Some(&(offset, next)) if is_cjk(next) => {
+                pieces.push((from, start + offset + next.len_utf8()))
+            }
+            // The end of a run was covered by the last pair, unless the run is one character
+            _ if pieces
+                .last()
+                .is_some_and(|&(_, to)| to == from + c.len_utf8()) => {}
+            _ => pieces.push((from, from + c.len_utf8())),
+        }
+    }
+    pieces
 }
 
 impl<'a> tantivy::tokenizer::TokenStream for CodeTokenStream<'a> {
     fn advance(&mut self) -> bool {
         self.token.text.clear();
+
         self.token.position = self.token.position.wrapping_add(1);
 
+        if let Some((start, end)) = self.pending_pieces.pop() {
+            self.emit(start, end);
+            return true;
+        }
+
         // Skip whitespace
         while let Some(&(_, c)) = self.chars.peek() {
             if !c.is_whitespace() {
@@ -58,10 +122,17 @@ impl<'a> tantivy::tokenizer::TokenStream for CodeTokenStream<'a> {
             None => return false,
         };
 
-        // Collect token: alphanumeric + code chars ($, @, #, _, -)
+        // Collect token: alphanumeric + code chars ($, @, #, _, -) + combining marks
         let mut end = start;
         while let Some(&(pos, c)) = self.chars.peek() {
-            if c.is_alphanumeric() || c == '_' || c == '$' || c == '@' || c == '#' || c == '-' {
+            if c.is_alphanumeric()
+                || c == '_'
+                || c == '$'
+                || c == '@'
+                || c == '#'
+                || c == '-'
+                || (pos > start && is_token_mark(c))
+            {
                 end = pos + c.len_utf8();
                 self.chars.next();
             } else if c.is_whitespace() {
@@ -79,9 +150,17 @@ impl<'a> tantivy::tokenizer::TokenStream for CodeTokenStream<'a> {
         }
 
         if end > start {
-            self.token.offset_from = start;
-            self.token.offset_to = end;
-            self.token.text.push_str(&self.text[start..end]);
+            // CJK text has no spaces between words: its runs become overlapping character
+            // pairs, so any word of two or more characters is found as a phrase of pairs
+            if self.text[start..end].chars().any(is_cjk) {
+                let mut pieces = cjk_pieces(self.text, start, end);
+                pieces.reverse();
+                self.pending_pieces = pieces;
+                let (start, end) = self.pending_pieces.pop().unwrap_or((start, end));
+                self.emit(start, end);
+            } else {
+                self.emit(start, end);
+            }
             true
         } else {
             false
@@ -103,13 +182,36 @@ pub mod fields {
     pub const PATH: &str = "path";
     pub const WORKSPACE: &str = "workspace";
     pub const CONTENT: &str = "content";
+    /// Stemmed content of prose files (`indexer.stemming`), indexed only
+    pub const PROSE: &str = "prose";
     pub const MTIME: &str = "mtime";
     pub const SIZE: &str = "size";
     pub const EXTENSION: &str = "extension";
+    pub const KIND: &str = "kind";
+    /// Encoding the file was read in (`utf-8`, `utf-16le`, `windows-1252`, ...)
+    pub const ENCODING: &str = "encoding";
+    /// Whether only the start of the file is indexed (`indexer.oversized_head_kb`)
+    pub const TRUNCATED: &str = "truncated";
     pub const LINE_START: &str = "line_start";
     pub const LINE_END: &str = "line_end";
     pub const CHUNK_ID: &str = "chunk_id";
     pub const PARENT_DOC: &str = "parent_doc";
+    pub const UNIT: &str = "unit";
+    /// How many other files import the file (`indexer.reference_counts`)
+    pub const REFERENCES: &str = "references";
+    /// Name of the git submodule holding the file (`""` outside any)
+    pub const SUBMODULE: &str = "submodule";
+    /// Byte ranges of the comments and strings in the content (`regions::Regions`),
+    /// stored only; missing for languages the lexer doesn't know
+    pub const REGIONS: &str = "regions";
+}
+
+/// Values of the `unit` field: how much of a file a document covers
+pub mod units {
+    pub const FILE: &str = "file";
+    pub const CHUNK: &str = "chunk";
+    /// A single line (`indexer.line_index`)
+    pub const LINE: &str = "line";
 }
 
 /// Build the Tantivy schema for document indexing
@@ -135,9 +237,25 @@ pub fn build_document_schema() -> Schema {
     schema_builder.add_u64_field(fields::MTIME, FAST | STORED);
     schema_builder.add_u64_field(fields::SIZE, FAST | STORED);
     schema_builder.add_text_field(fields::EXTENSION, STRING | STORED);
+    // source/test/config/docs/generated (fast, so ranking can weight by kind)
+    schema_builder.add_text_field(fields::KIND, STRING | STORED | FAST);
+    schema_builder.add_text_field(fields::ENCODING, STRING | STORED);
+    schema_builder.add_bool_field(fields::TRUNCATED, STORED);
+    // Files importing this one (fast, so ranking can favor widely used modules)
+    schema_builder.add_u64_field(fields::REFERENCES, FAST | STORED);
+    schema_builder.add_text_field(fields::SUBMODULE, STRING | STORED);
+    schema_builder.add_text_field(fields::REGIONS, STORED);
 
     // Content for full-text search
     schema_builder.add_text_field(fields::CONTENT, text_options);
+    schema_builder.add_text_field(
+        fields::PROSE,
+        TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(PROSE_TOKENIZER)
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        ),
+    );
 
     // Line range for the document/chunk
     schema_builder.add_u64_field(fields::LINE_START, FAST | STORED);
@@ -146,6 +264,7 @@ pub fn build_document_schema() -> Schema {
     // Chunk-specific fields
     schema_builder.add_text_field(fields::CHUNK_ID, STRING | STORED);
     schema_builder.add_text_field(fields::PARENT_DOC, STRING | STORED);
+    schema_builder.add_text_field(fields::UNIT, STRING | STORED);
 
     schema_builder.build()
 }
@@ -157,13 +276,21 @@ pub struct SchemaFields {
     pub path: tantivy::schema::Field,
     pub workspace: tantivy::schema::Field,
     pub content: tantivy::schema::Field,
+    pub prose: tantivy::schema::Field,
     pub mtime: tantivy::schema::Field,
     pub size: tantivy::schema::Field,
     pub extension: tantivy::schema::Field,
+    pub kind: tantivy::schema::Field,
+    pub encoding: tantivy::schema::Field,
+    pub truncated: tantivy::schema::Field,
+    pub references: tantivy::schema::Field,
+    pub submodule: tantivy::schema::Field,
+    pub regions: tantivy::schema::Field,
     pub line_start: tantivy::schema::Field,
     pub line_end: tantivy::schema::Field,
     pub chunk_id: tantivy::schema::Field,
     pub parent_doc: tantivy::schema::Field,
+    pub unit: tantivy::schema::Field,
 }
 
 impl SchemaFields {
@@ -173,13 +300,21 @@ impl SchemaFields {
             path: schema.get_field(fields::PATH).unwrap(),
             workspace: schema.get_field(fields::WORKSPACE).unwrap(),
             content: schema.get_field(fields::CONTENT).unwrap(),
+            prose: schema.get_field(fields::PROSE).unwrap(),
             mtime: schema.get_field(fields::MTIME).unwrap(),
             size: schema.get_field(fields::SIZE).unwrap(),
             extension: schema.get_field(fields::EXTENSION).unwrap(),
+            kind: schema.get_field(fields::KIND).unwrap(),
+            encoding: schema.get_field(fields::ENCODING).unwrap(),
+            truncated: schema.get_field(fields::TRUNCATED).unwrap(),
+            references: schema.get_field(fields::REFERENCES).unwrap(),
+            submodule: schema.get_field(fields::SUBMODULE).unwrap(),
+            regions: schema.get_field(fields::REGIONS).unwrap(),
             line_start: schema.get_field(fields::LINE_START).unwrap(),
             line_end: schema.get_field(fields::LINE_END).unwrap(),
             chunk_id: schema.get_field(fields::CHUNK_ID).unwrap(),
             parent_doc: schema.get_field(fields::PARENT_DOC).unwrap(),
+            unit: schema.get_field(fields::UNIT).unwrap(),
         }
     }
 }
@@ -202,4 +337,38 @@ mod tests {
         let _ = fields.doc_id;
         let _ = fields.content;
     }
+
+    #[test]
+    fn test_cjk_bigrams() {
+        use tantivy::tokenizer::TokenStream;
+
+        let manager = TokenizerManager::default();
+        register_tokenizers(&manager);
+        let mut tokenizer = manager.get(CODE_TOKENIZER).unwrap();
+        let mut tokens = |text: &str| {
+            let mut stream = tokenizer.token_stream(text);
+            let mut tokens = Vec::new();
+            while stream.advance() {
+                let token = stream.token();
+                tokens.push((token.text.clone(), token.position));
+            }
+            tokens
+        };
+
+        let pairs = tokens("连接数据库 db");
+        let expected = [
+            ("连接", 0),
+            ("接数", 1),
+            ("数据", 2),
+            ("据库", 3),
+            ("db", 4),
+        ];
+        assert_eq!(pairs, expected.map(|(t, p)| (t.to_string(), p)).to_vec());
+        // Mixed words keep their other text whole; lone characters stay single
+        let mixed: Vec<String> = tokens("getUser用户 库")
+            .into_iter()
+            .map(|(t, _)| t)
+            .collect();
+        assert_eq!(mixed, ["getuser", "用户", "库"]);
+    }
 }
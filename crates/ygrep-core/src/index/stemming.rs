@@ -0,0 +1,173 @@
+//! Stemming for prose files (`indexer.stemming`): their text is also indexed in a stemmed
+//! field, so `searching` finds `search` in documentation while code keeps exact tokens
+
+use tantivy::tokenizer::{
+    Language, LowerCaser, RemoveLongFilter, SimpleTokenizer, Stemmer, TextAnalyzer, TokenStream,
+    TokenizerManager,
+};
+
+use crate::config::IndexerConfig;
+use crate::error::{Result, YgrepError};
+
+/// Name of the stemming tokenizer of the prose field
+pub const PROSE_TOKENIZER: &str = "prose";
+
+/// Stemmer languages by `indexer.stem_language` name
+const LANGUAGES: [(&str, Language); 18] = [
+    ("arabic", Language::Arabic),
+    ("danish", Language::Danish),
+    ("dutch", Language::Dutch),
+    ("english", Language::English),
+    ("finnish", Language::Finnish),
+    ("french", Language::French),
+    ("german", Language::German),
+    ("greek", Language::Greek),
+    ("hungarian", Language::Hungarian),
+    ("italian", Language::Italian),
+    ("norwegian", Language::Norwegian),
+    ("portuguese", Language::Portuguese),
+    ("romanian", Language::Romanian),
+    ("russian", Language::Russian),
+    ("spanish", Language::Spanish),
+    ("swedish", Language::Swedish),
+    ("tamil", Language::Tamil),
+    ("turkish", Language::Turkish),
+];
+
+/// Which files are stemmed, and how
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stemming {
+    /// `indexer.stem_language` name
+    pub language: String,
+    /// Extensions of the stemmed files (lowercase, without the dot)
+    pub extensions: Vec<String>,
+    stemmer: Language,
+}
+
+impl Stemming {
+    /// Stemming as configured, or None when `indexer.stemming` is off
+    pub fn from_config(config: &IndexerConfig) -> Result<Option<Self>> {
+        if !config.stemming {
+            return Ok(None);
+        }
+        let language = config.stem_language.to_lowercase();
+        let stemmer = LANGUAGES
+            .iter()
+            .find(|(name, _)| *name == language)
+            .map(|(_, stemmer)| *stemmer)
+            .ok_or_else(|| {
+                YgrepError::Config(format!(
+                    "Unknown stem_language '{}' (expected one of: {})",
+                    config.stem_language,
+                    LANGUAGES.map(|(name, _)| name).join(", ")
+                ))
+            })?;
+        Ok(Some(Self {
+            language,
+            extensions: config
+                .stem_extensions
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .collect(),
+            stemmer,
+        }))
+    }
+
+    /// Whether files with this extension are stemmed
+    pub fn applies_to(&self, extension: &str) -> bool {
+        self.extensions
+            .iter()
+            .any(|ext| ext.eq_ignore_ascii_case(extension))
+    }
+
+    /// Use this language for the prose field of `manager`'s index
+    pub fn register(&self, manager: &TokenizerManager) {
+        manager.register(PROSE_TOKENIZER, prose_analyzer(self.stemmer));
+    }
+
+    /// Stemmed words of `text`, in order
+    pub fn stems(&self, text: &str) -> Vec<String> {
+        self.tokens(text)
+            .into_iter()
+            .map(|(stem, _)| stem)
+            .collect()
+    }
+
+    /// Byte offset in `content` of the first run of words stemming to `stems`
+    pub fn find(&self, content: &str, stems: &[String]) -> Option<usize> {
+        if stems.is_empty() {
+            return None;
+        }
+        let tokens = self.tokens(content);
+        tokens
+            .windows(stems.len())
+            .find(|window| window.iter().map(|(stem, _)| stem).eq(stems.iter()))
+            .map(|window| window[0].1)
+    }
+
+    fn tokens(&self, text: &str) -> Vec<(String, usize)> {
+        let mut analyzer = prose_analyzer(self.stemmer);
+        let mut stream = analyzer.token_stream(text);
+        let mut tokens = Vec::new();
+        while stream.advance() {
+            let token = stream.token();
+            tokens.push((token.text.clone(), token.offset_from));
+        }
+        tokens
+    }
+}
+
+/// Words split on punctuation, lowercased and stemmed
+pub(crate) fn prose_analyzer(language: Language) -> TextAnalyzer {
+    TextAnalyzer::builder(SimpleTokenizer::default())
+        .filter(LowerCaser)
+        .filter(RemoveLongFilter::limit(100))
+        .filter(Stemmer::new(language))
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn english() -> Stemming {
+        let config = IndexerConfig {
+            stemming: true,
+            ..Default::default()
+        };
+        Stemming::from_config(&config).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_from_config() {
+        assert!(Stemming::from_config(&IndexerConfig::default())
+            .unwrap()
+            .is_none());
+
+        let stemming = english();
+        assert!(stemming.applies_to("md"));
+        assert!(stemming.applies_to("MD"));
+        assert!(!stemming.applies_to("rs"));
+
+        let config = IndexerConfig {
+            stemming: true,
+            stem_language: "klingon".to_string(),
+            ..Default::default()
+        };
+        assert!(Stemming::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_find() {
+        let stemming = english();
+        let content = "# Guide\n\nSearching indexes quickly.";
+        let stems = stemming.stems("search index");
+        assert_eq!(stems, vec!["search", "index"]);
+        assert_eq!(stemming.find(content, &stems), content.find("Searching"));
+        assert_eq!(
+            stemming.find(content, &stemming.stems("index search")),
+            None
+        );
+        assert_eq!(stemming.find(content, &[]), None);
+    }
+}
@@ -0,0 +1,261 @@
+//! Syntax-aware chunking (the `treesitter` feature): source files are split along their
+//! definitions (functions, methods, classes, impl blocks) instead of fixed line windows,
+//! so a chunk hit is a whole function and each chunk's embedding describes one symbol.
+//!
+//! A definition keeps the comments, doc comments and attributes right above it. Classes
+//! and impl blocks longer than a chunk are split into their members, other definitions
+//! that long into line windows, and so are long stretches of code between definitions.
+
+use std::ops::Range;
+use tree_sitter::{Language, Node, Parser};
+
+use crate::fs::language_for_extension;
+
+/// The grammar for files with `extension`, if there is one
+fn grammar(extension: &str) -> Option<Language> {
+    if extension.eq_ignore_ascii_case("tsx") {
+        return Some(tree_sitter_typescript::LANGUAGE_TSX.into());
+    }
+    let language = match language_for_extension(extension)? {
+        "rust" => tree_sitter_rust::LANGUAGE,
+        "python" => tree_sitter_python::LANGUAGE,
+        "javascript" => tree_sitter_javascript::LANGUAGE,
+        "typescript" => tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+        "go" => tree_sitter_go::LANGUAGE,
+        _ => return None,
+    };
+    Some(language.into())
+}
+
+/// Whether files with `extension` are chunked along their definitions
+pub fn supports(extension: &str) -> bool {
+    grammar(extension).is_some()
+}
+
+/// Line ranges (0-based, end exclusive) of the chunks of a source file: its definitions,
+/// with pieces of at most `max_lines` lines for those longer than that. `None` for
+/// languages without a grammar and files without definitions.
+pub fn symbol_ranges(
+    extension: &str,
+    content: &str,
+    max_lines: usize,
+) -> Option<Vec<Range<usize>>> {
+    let mut parser = Parser::new();
+    parser.set_language(&grammar(extension)?).ok()?;
+    let tree = parser.parse(content, None)?;
+    let line_count = content.lines().count();
+
+    let mut ranges = Vec::new();
+    collect(tree.root_node(), max_lines.max(1), &mut ranges);
+    if ranges.is_empty() {
+        return None;
+    }
+
+    // Long stretches between definitions (top-level code, tables) get windows of their own
+    let mut chunks = Vec::new();
+    let mut covered = 0;
+    for range in ranges {
+        let range = range.start.min(line_count)..range.end.min(line_count);
+        if range.start < covered || range.is_empty() {
+            continue;
+        }
+        add_gap(covered..range.start, max_lines, &mut chunks);
+        covered = range.end;
+        chunks.push(range);
+    }
+    add_gap(covered..line_count, max_lines, &mut chunks);
+    Some(chunks)
+}
+
+/// Add windows over `gap` when it is longer than a chunk
+fn add_gap(gap: Range<usize>, max_lines: usize, chunks: &mut Vec<Range<usize>>) {
+    if gap.len() > max_lines {
+        chunks.extend(windows(gap, max_lines));
+    }
+}
+
+/// Add the chunks of the definitions among `node`'s children
+fn collect(node: Node, max_lines: usize, ranges: &mut Vec<Range<usize>>) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        // `export function ...`, `export default class ...`
+        if child.kind() == "export_statement" {
+            if let Some(declaration) = child.child_by_field_name("declaration") {
+                if is_definition(declaration) {
+                    add_definition(child, declaration, max_lines, ranges);
+                }
+            }
+            continue;
+        }
+        if is_definition(child) {
+            add_definition(child, child, max_lines, ranges);
+        }
+    }
+}
+
+/// Add `outer` (the definition with any wrapper such as `export`), split into the members
+/// of `definition` or into windows when it is longer than a chunk
+fn add_definition(outer: Node, definition: Node, max_lines: usize, ranges: &mut Vec<Range<usize>>) {
+    let lines = leading_comments_start(outer)..end_line(outer);
+    if lines.len() <= max_lines {
+        ranges.push(lines);
+        return;
+    }
+    // Python's decorated definitions hold the class or function
+    let definition = definition
+        .child_by_field_name("definition")
+        .unwrap_or(definition);
+    let members = match definition.child_by_field_name("body") {
+        Some(body) if is_container(definition) => {
+            let mut members = Vec::new();
+            collect(body, max_lines, &mut members);
+            members
+        }
+        _ => Vec::new(),
+    };
+    if members.is_empty() {
+        ranges.extend(windows(lines, max_lines));
+    } else {
+        ranges.extend(members);
+    }
+}
+
+/// Definitions that make a chunk of their own
+fn is_definition(node: Node) -> bool {
+    match node.kind() {
+        // Rust
+        "function_item" | "impl_item" | "trait_item" | "struct_item" | "enum_item"
+        | "union_item" | "mod_item" | "macro_definition" => true,
+        // Python
+        "function_definition" | "class_definition" | "decorated_definition" => true,
+        // JavaScript and TypeScript
+        "function_declaration"
+        | "generator_function_declaration"
+        | "class_declaration"
+        | "abstract_class_declaration"
+        | "method_definition"
+        | "interface_declaration"
+        | "enum_declaration"
+        | "internal_module" => true,
+        // `const handler = () => {...}`
+        "lexical_declaration" | "variable_declaration" => {
+            let mut cursor = node.walk();
+            let defines_function = node.named_children(&mut cursor).any(|declarator| {
+                declarator
+                    .child_by_field_name("value")
+                    .is_some_and(|value| {
+                        matches!(
+                            value.kind(),
+                            "arrow_function" | "function_expression" | "function" | "class"
+                        )
+                    })
+            });
+            defines_function
+        }
+        // Go (`function_declaration` as in JavaScript)
+        "method_declaration" | "type_declaration" => true,
+        _ => false,
+    }
+}
+
+/// Definitions whose members are chunked when the whole is too long
+fn is_container(node: Node) -> bool {
+    matches!(
+        node.kind(),
+        "impl_item"
+            | "trait_item"
+            | "mod_item"
+            | "class_definition"
+            | "class_declaration"
+            | "abstract_class_declaration"
+            | "internal_module"
+    )
+}
+
+/// First line of `node` including the comments and attributes right above it
+fn leading_comments_start(node: Node) -> usize {
+    let mut start = node.start_position().row;
+    let mut previous = node.prev_named_sibling();
+    while let Some(sibling) = previous {
+        let attached = sibling.kind().contains("comment") || sibling.kind() == "attribute_item";
+        if !attached || end_line(sibling) < start {
+            break;
+        }
+        start = sibling.start_position().row;
+        previous = sibling.prev_named_sibling();
+    }
+    start
+}
+
+/// Line after the last line of `node`
+fn end_line(node: Node) -> usize {
+    let end = node.end_position();
+    if end.column == 0 && end.row > node.start_position().row {
+        end.row
+    } else {
+        end.row + 1
+    }
+}
+
+/// `lines` in consecutive windows of at most `size` lines
+fn windows(lines: Range<usize>, size: usize) -> impl Iterator<Item = Range<usize>> {
+    let end = lines.end;
+    lines
+        .step_by(size)
+        .map(move |start| start..(start + size).min(end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_ranges() {
+        let rust = "use std::fmt;\n\
+                    \n\
+                    /// Adds\n\
+                    #[inline]\n\
+                    fn add(a: u32, b: u32) -> u32 {\n\
+                    \x20   a + b\n\
+                    }\n\
+                    \n\
+                    struct Point {\n\
+                    \x20   x: i32,\n\
+                    }\n";
+        assert_eq!(symbol_ranges("rs", rust, 50), Some(vec![2..7, 8..11]));
+
+        // An impl block too long for one chunk is split into its methods
+        let long_impl = "impl Point {\n\
+                         \x20   fn x(&self) -> i32 {\n\
+                         \x20       self.x\n\
+                         \x20   }\n\
+                         \x20   fn y(&self) -> i32 {\n\
+                         \x20       self.y\n\
+                         \x20   }\n\
+                         }\n";
+        assert_eq!(symbol_ranges("rs", long_impl, 4), Some(vec![1..4, 4..7]));
+
+        let python = "import os\n\
+                      \n\
+                      @cache\n\
+                      def load(path):\n\
+                      \x20   return open(path).read()\n\
+                      \n\
+                      class Store:\n\
+                      \x20   pass\n";
+        assert_eq!(symbol_ranges("py", python, 50), Some(vec![2..5, 6..8]));
+
+        let typescript = "export const handler = async (event) => {\n\
+                          \x20 return event;\n\
+                          };\n\
+                          \n\
+                          export interface Options {\n\
+                          \x20 retries: number;\n\
+                          }\n";
+        assert_eq!(symbol_ranges("ts", typescript, 50), Some(vec![0..3, 4..7]));
+
+        assert_eq!(symbol_ranges("go", "package main\n", 50), None);
+        assert_eq!(symbol_ranges("md", "# Title\n", 50), None);
+        assert!(supports("tsx") && !supports("txt"));
+    }
+}
@@ -0,0 +1,84 @@
+//! Background priority (`indexer.background_priority`, `--throttle`): indexing runs at a
+//! lower CPU and IO priority, pauses after each file and embeds with fewer threads, so
+//! watch mode and background indexing don't make the machine feel sluggish
+
+use std::time::Duration;
+
+/// Longest pause after a file or embedding batch
+const MAX_PAUSE: Duration = Duration::from_millis(100);
+
+/// Nice value of throttled indexing threads (19 is the lowest priority)
+#[cfg(unix)]
+const NICE: libc::c_int = 10;
+
+/// Threads for tokenizing embedding batches while throttled
+pub const EMBEDDING_THREADS: usize = 2;
+
+/// Embedding batch size while throttled (shorter bursts of CPU use)
+pub const EMBEDDING_BATCH_SIZE: usize = 16;
+
+/// How long to pause after work that took `worked`: as long again, so throttled
+/// indexing keeps a core busy about half of the time
+pub fn pause_for(worked: Duration) -> Duration {
+    worked.min(MAX_PAUSE)
+}
+
+pub fn pause_after(worked: Duration) {
+    std::thread::sleep(pause_for(worked));
+}
+
+/// Lower the CPU and IO priority of the calling thread (and of threads it starts later,
+/// such as the embedding model's) and limit the embedding thread pool
+pub fn enter_background_mode() {
+    #[cfg(unix)]
+    {
+        // SAFETY: plain syscall on the calling thread; failure only leaves the priority
+        let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, NICE) };
+        if result != 0 {
+            tracing::debug!(
+                "Failed to lower CPU priority: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+        const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+        const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+        // SAFETY: as above; the idle class only gets disk time no one else wants
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                0,
+                IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            )
+        };
+        if result != 0 {
+            tracing::debug!(
+                "Failed to lower IO priority: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
+    // Read when the embedding thread pool starts, i.e. on the first embedding
+    if std::env::var_os("RAYON_NUM_THREADS").is_none() {
+        std::env::set_var("RAYON_NUM_THREADS", EMBEDDING_THREADS.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_for() {
+        let ms = Duration::from_millis;
+        assert_eq!(pause_for(ms(3)), ms(3));
+        assert_eq!(pause_for(Duration::from_secs(5)), MAX_PAUSE);
+        assert_eq!(pause_for(Duration::ZERO), Duration::ZERO);
+    }
+}
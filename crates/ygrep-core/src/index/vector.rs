@@ -1,23 +1,68 @@
 //! HNSW vector index for semantic search
+//!
+//! Large indexes can be split into shards (`indexer.vector_shards`): each document (with
+//! its chunks) goes to one shard by a hash of its ID, every shard keeps its own HNSW
+//! graph in `shard-NNN/`, and searches run over all shards in parallel and merge the
+//! results. Shards are loaded from disk on first use and only re-saved when they change.
+//!
+//! Vectors are L2-normalized when inserted, so the default distance
+//! (`indexer.vector_distance = "dot"`) is a plain dot product; the distance an index was
+//! built with is saved with it, and indexes from before default to cosine.
+//!
+//! With `indexer.mmap_vectors`, a shard's vectors are memory-mapped from its HNSW data
+//! file (the graph refers to them by offset) instead of being read into memory, so large
+//! indexes open quickly and processes searching the same index share the pages. Saving
+//! never rewrites a data file another process may have mapped: each save writes a new
+//! generation of the dump, and the doc_id index names the current one.
 
-use hnsw_rs::hnswio::HnswIo;
+use hnsw_rs::hnswio::{HnswIo, ReloadOptions};
 use hnsw_rs::prelude::*;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use xxhash_rust::xxh3::xxh3_64;
 
+use crate::config::VectorDistance;
 use crate::error::{Result, YgrepError};
 
-/// HNSW dump file basename
+/// HNSW dump file basename (of the first generation; later ones are `hnsw-<n>`)
 const HNSW_BASENAME: &str = "hnsw";
 
+/// Extensions of the HNSW dump files
+const HNSW_EXTENSIONS: [&str; 2] = [".hnsw.graph", ".hnsw.data"];
+
+/// Compact doc_id index of a shard
+const DOC_IDS_FILE: &str = "doc_ids.json";
+
+/// Legacy vector dump
+const LEGACY_FILE: &str = "vectors.json";
+
+/// Shard layout of a sharded index (a single shard uses the unsharded layout)
+const MANIFEST_FILE: &str = "shards.json";
+
+/// Low bits of a vector ID that hold its ID within the shard
+const SHARD_ID_BITS: u32 = 48;
+
 /// Compact doc_id index (fast to load)
 #[derive(Debug, Serialize, Deserialize)]
 struct DocIdIndex {
     dimension: usize,
+    #[serde(default = "saved_before_distance")]
+    distance: VectorDistance,
+    /// Generation of the HNSW dump (0 for indexes saved before there were several)
+    #[serde(default)]
+    generation: u64,
     doc_ids: Vec<String>,
 }
 
+/// Distance of indexes saved before it was recorded (whose vectors aren't normalized)
+fn saved_before_distance() -> VectorDistance {
+    VectorDistance::Cosine
+}
+
 /// Stored vector with its document ID (legacy format)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct StoredVector {
@@ -32,71 +77,280 @@ struct VectorData {
     vectors: Vec<StoredVector>,
 }
 
+/// Dimension and vector count per shard, so shards can be loaded lazily
+#[derive(Debug, Serialize, Deserialize)]
+struct ShardManifest {
+    dimension: usize,
+    #[serde(default = "saved_before_distance")]
+    distance: VectorDistance,
+    shards: Vec<usize>,
+}
+
+/// What `VectorIndex::salvage` did to a saved index
+#[derive(Debug, Default)]
+pub struct VectorSalvage {
+    /// What was repaired, for the report
+    pub repaired: Vec<String>,
+    /// Vectors dropped because their document is no longer indexed
+    pub orphans: usize,
+    /// Indexed documents whose vectors were lost with a damaged shard (to embed again)
+    pub lost: Vec<String>,
+}
+
 /// HNSW vector index for storing and searching embeddings
 pub struct VectorIndex {
     path: PathBuf,
-    hnsw: RwLock<Hnsw<'static, f32, DistCosine>>,
     dimension: usize,
+    /// Distance of the current shards (changed by `reset`)
+    distance: RwLock<VectorDistance>,
+    shards: RwLock<Vec<Shard>>,
+}
+
+/// One HNSW graph and the documents in it
+struct Shard {
+    dir: PathBuf,
+    /// Graph and doc_ids, `None` until first used
+    data: RwLock<Option<ShardData>>,
+    /// Number of vectors (known without loading the shard)
+    len: AtomicUsize,
+    /// Changed since the last save
+    dirty: AtomicBool,
+    /// Memory-map the saved vectors when loading
+    mmap: bool,
+}
+
+struct ShardData {
+    /// Declared ahead of `_reloader` so it drops first: a loaded graph borrows from it
+    hnsw: Graph,
+    /// Reloader of the dump the graph was loaded from, `None` for a graph built in memory
+    _reloader: Option<Reloader>,
     /// Document IDs (index matches HNSW point ID)
-    doc_ids: RwLock<Vec<String>>,
+    doc_ids: Vec<String>,
+    /// Generation of the dump the data was loaded from or last saved to
+    generation: AtomicU64,
 }
 
-impl VectorIndex {
-    /// Create a new vector index
-    pub fn new(path: PathBuf, dimension: usize) -> Result<Self> {
-        std::fs::create_dir_all(&path)?;
+/// Cosine distance of L2-normalized vectors, `1 - a·b`
+/// (anndists' `DistDot` asserts on the rounding error of nearly identical vectors)
+#[derive(Debug, Default, Clone, Copy)]
+struct DistNormalized;
+
+impl Distance<f32> for DistNormalized {
+    fn eval(&self, va: &[f32], vb: &[f32]) -> f32 {
+        let dot: f32 = va.iter().zip(vb).map(|(a, b)| a * b).sum();
+        (1.0 - dot).max(0.0)
+    }
+}
 
+/// An HNSW graph with the distance its index was built with
+enum Graph {
+    Dot(Hnsw<'static, f32, DistNormalized>),
+    Cosine(Hnsw<'static, f32, DistCosine>),
+}
+
+/// An `HnswIo` a loaded graph borrows from (vectors included, when memory-mapped).
+/// Owned by the `ShardData` holding the graph and freed with it, so reloading a shard
+/// (as the daemon does after every reindex) releases the previous dump and mapping.
+struct Reloader(NonNull<HnswIo>);
+
+// SAFETY: the reloader is only used through the pointer while loading the graph;
+// afterwards the graph reads its data through its own borrows and the box is only freed
+unsafe impl Send for Reloader {}
+unsafe impl Sync for Reloader {}
+
+impl Drop for Reloader {
+    fn drop(&mut self) {
+        // SAFETY: boxed by `Graph::load` and freed only here, after the graph borrowing
+        // from it (`ShardData` drops `hnsw` before `_reloader`)
+        drop(unsafe { Box::from_raw(self.0.as_ptr()) });
+    }
+}
+
+/// `$body` with `$hnsw` bound to the graph, whatever its distance
+macro_rules! with_hnsw {
+    ($graph:expr, $hnsw:ident => $body:expr) => {
+        match $graph {
+            Graph::Dot($hnsw) => $body,
+            Graph::Cosine($hnsw) => $body,
+        }
+    };
+}
+
+impl Graph {
+    fn new(distance: VectorDistance, capacity: usize) -> Self {
         // HNSW parameters:
         // - max_nb_connection (M): 16 is a good default
         // - max_elements: Initial capacity, will grow
         // - max_layer: log2(max_elements) is optimal
         // - ef_construction: Higher = better quality, slower build
-        let hnsw = Hnsw::new(
-            16,     // max_nb_connection (M)
-            10_000, // initial capacity
-            16,     // max_layer
-            200,    // ef_construction
-            DistCosine {},
-        );
+        let capacity = capacity.max(10_000);
+        match distance {
+            VectorDistance::Dot => Graph::Dot(Hnsw::new(16, capacity, 16, 200, DistNormalized)),
+            VectorDistance::Cosine => Graph::Cosine(Hnsw::new(16, capacity, 16, 200, DistCosine)),
+        }
+    }
 
-        Ok(Self {
-            path,
-            hnsw: RwLock::new(hnsw),
-            dimension,
-            doc_ids: RwLock::new(Vec::new()),
-        })
+    /// Load generation `generation` of the HNSW dump in `dir` (memory-mapping its
+    /// vectors with `mmap`), with the reloader it borrows from. The graph must be
+    /// dropped before the reloader.
+    fn load(
+        dir: &Path,
+        generation: u64,
+        distance: VectorDistance,
+        mmap: bool,
+    ) -> Result<(Self, Reloader)> {
+        let reloader = Reloader(NonNull::from(Box::leak(Box::new(HnswIo::new(
+            dir,
+            &hnsw_basename(generation),
+        )))));
+        // SAFETY: the box stays allocated until `reloader` drops, which callers only
+        // let happen after the graph; nothing else touches it meanwhile
+        let io = unsafe { &mut *reloader.0.as_ptr() };
+        if mmap {
+            io.set_options(ReloadOptions::default().set_mmap(true));
+        }
+        let graph = match distance {
+            VectorDistance::Dot => io.load_hnsw::<f32, DistNormalized>().map(Graph::Dot),
+            VectorDistance::Cosine => io.load_hnsw::<f32, DistCosine>().map(Graph::Cosine),
+        };
+        let graph =
+            graph.map_err(|e| YgrepError::Config(format!("Failed to load HNSW index: {}", e)))?;
+        Ok((graph, reloader))
     }
 
-    /// Load an existing vector index
-    pub fn load(path: PathBuf) -> Result<Self> {
+    fn distance(&self) -> VectorDistance {
+        match self {
+            Graph::Dot(_) => VectorDistance::Dot,
+            Graph::Cosine(_) => VectorDistance::Cosine,
+        }
+    }
+
+    fn insert(&self, vector: &[f32], id: usize) {
+        with_hnsw!(self, hnsw => hnsw.insert((&vector.to_vec(), id)))
+    }
+
+    /// Stored vectors with their point IDs
+    fn points(&self) -> Vec<(usize, Vec<f32>)> {
+        with_hnsw!(self, hnsw => hnsw
+            .get_point_indexation()
+            .into_iter()
+            .map(|point| (point.get_origin_id(), point.get_v().to_vec()))
+            .collect())
+    }
+}
+
+/// `vector` scaled to unit length (zero vectors are left as they are)
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter().map(|v| v / norm).collect()
+    } else {
+        vector.to_vec()
+    }
+}
+
+/// Basename of a generation of the HNSW dump
+fn hnsw_basename(generation: u64) -> String {
+    match generation {
+        0 => HNSW_BASENAME.to_string(),
+        n => format!("{}-{}", HNSW_BASENAME, n),
+    }
+}
+
+/// Directory of a shard: the index directory itself when there is only one
+fn shard_dir(path: &Path, shard: usize, count: usize) -> PathBuf {
+    if count == 1 {
+        path.to_path_buf()
+    } else {
+        path.join(format!("shard-{:03}", shard))
+    }
+}
+
+/// Document a doc_id belongs to (chunks are `<doc_id>:<n>`)
+fn parent_of(doc_id: &str) -> &str {
+    doc_id.split(':').next().unwrap_or(doc_id)
+}
+
+/// Shard for a document ID; chunks stay with their file
+fn shard_of(doc_id: &str, count: usize) -> usize {
+    if count == 1 {
+        return 0;
+    }
+    (xxh3_64(parent_of(doc_id).as_bytes()) % count as u64) as usize
+}
+
+/// The number saved under `key` in a (possibly truncated) JSON object
+fn salvage_number<T: std::str::FromStr>(content: &str, key: &str) -> Option<T> {
+    let key = format!("\"{}\":", key);
+    let i = content.find(&key)?;
+    let digits: String = content[i + key.len()..]
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
+}
+
+/// Dimension and doc_ids up to where a truncated doc_id index was cut off
+fn salvage_doc_ids(content: &str) -> Option<(Option<usize>, Vec<String>)> {
+    let dimension = salvage_number(content, "dimension");
+
+    let start = content.find("\"doc_ids\":")? + "\"doc_ids\":".len();
+    let mut rest = content[start..].trim_start().strip_prefix('[')?;
+    let mut doc_ids = Vec::new();
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        let mut values = serde_json::Deserializer::from_str(rest).into_iter::<String>();
+        match values.next() {
+            Some(Ok(doc_id)) => {
+                doc_ids.push(doc_id);
+                rest = &rest[values.byte_offset()..];
+            }
+            // The end of the list, or the entry that was cut off
+            _ => break,
+        }
+    }
+    Some((dimension, doc_ids))
+}
+
+impl ShardData {
+    fn empty(distance: VectorDistance) -> Self {
+        Self {
+            hnsw: Graph::new(distance, 0),
+            _reloader: None,
+            doc_ids: Vec::new(),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Load a shard directory (memory-mapping its vectors with `mmap`), returning its
+    /// data and embedding dimension
+    fn load(dir: &Path, mmap: bool) -> Result<(Self, usize)> {
         // Try fast path: load from doc_ids.json + HNSW dump
-        let doc_ids_path = path.join("doc_ids.json");
-        let hnsw_graph = path.join(format!("{}.hnsw.graph", HNSW_BASENAME));
+        let doc_ids_path = dir.join(DOC_IDS_FILE);
 
-        if doc_ids_path.exists() && hnsw_graph.exists() {
+        if doc_ids_path.exists() && has_hnsw_dump(dir) {
             // Fast path: load compact doc_id index + HNSW dump
             let doc_index: DocIdIndex =
                 serde_json::from_reader(std::fs::File::open(&doc_ids_path)?).map_err(|e| {
                     YgrepError::Config(format!("Failed to load doc_id index: {}", e))
                 })?;
 
-            let reloader = Box::leak(Box::new(HnswIo::new(&path, HNSW_BASENAME)));
-            let hnsw = reloader
-                .load_hnsw::<f32, DistCosine>()
-                .map_err(|e| YgrepError::Config(format!("Failed to load HNSW index: {}", e)))?;
-
-            return Ok(Self {
-                path,
-                hnsw: RwLock::new(hnsw),
-                dimension: doc_index.dimension,
-                doc_ids: RwLock::new(doc_index.doc_ids),
-            });
+            let (hnsw, reloader) =
+                Graph::load(dir, doc_index.generation, doc_index.distance, mmap)?;
+            let data = Self {
+                hnsw,
+                _reloader: Some(reloader),
+                doc_ids: doc_index.doc_ids,
+                generation: AtomicU64::new(doc_index.generation),
+            };
+            return Ok((data, doc_index.dimension));
         }
 
         // Fallback: load from legacy vectors.json
-        let data_path = path.join("vectors.json");
+        let data_path = dir.join(LEGACY_FILE);
         if !data_path.exists() {
-            return Err(YgrepError::WorkspaceNotIndexed(path.clone()));
+            return Err(YgrepError::WorkspaceNotIndexed(dir.to_path_buf()));
         }
 
         // Load legacy vector data (slow but backwards compatible)
@@ -107,26 +361,633 @@ impl VectorIndex {
         let doc_ids: Vec<String> = data.vectors.iter().map(|sv| sv.doc_id.clone()).collect();
 
         // Rebuild HNSW from vectors
-        let hnsw = Hnsw::new(16, data.vectors.len().max(10_000), 16, 200, DistCosine {});
+        let hnsw = Graph::new(VectorDistance::Cosine, data.vectors.len());
         for (id, sv) in data.vectors.iter().enumerate() {
-            hnsw.insert((&sv.vector, id));
+            hnsw.insert(&sv.vector, id);
+        }
+
+        let shard = Self {
+            hnsw,
+            _reloader: None,
+            doc_ids,
+            generation: AtomicU64::new(0),
+        };
+        Ok((shard, data.dimension))
+    }
+}
+
+impl ShardData {
+    /// A copy with only the vectors whose doc_id `keep` accepts, plus the number of
+    /// vectors without a doc_id and the number rejected (both dropped)
+    fn retain(&self, keep: impl Fn(&str) -> bool) -> (Self, usize, usize) {
+        let mut points = self.hnsw.points();
+        points.sort_by_key(|(id, _)| *id);
+
+        // Same generation, so saving the copy writes the next one
+        let mut kept = Self {
+            hnsw: Graph::new(self.hnsw.distance(), points.len()),
+            _reloader: None,
+            doc_ids: Vec::new(),
+            generation: AtomicU64::new(self.generation.load(Ordering::Relaxed)),
+        };
+        let (mut unmapped, mut rejected) = (0, 0);
+        for (id, vector) in &points {
+            match self.doc_ids.get(*id) {
+                None => unmapped += 1,
+                Some(doc_id) if !keep(doc_id) => rejected += 1,
+                Some(doc_id) => {
+                    kept.hnsw.insert(vector, kept.doc_ids.len());
+                    kept.doc_ids.push(doc_id.clone());
+                }
+            }
+        }
+        (kept, unmapped, rejected)
+    }
+
+    /// The vectors whose doc_id `wanted` accepts, with their doc_ids
+    fn vectors(&self, wanted: impl Fn(&str) -> bool) -> Vec<(String, Vec<f32>)> {
+        self.hnsw
+            .points()
+            .into_iter()
+            .filter_map(|(id, vector)| {
+                let doc_id = self.doc_ids.get(id)?;
+                wanted(doc_id).then(|| (doc_id.clone(), vector))
+            })
+            .collect()
+    }
+
+    /// Vectors in the HNSW graph
+    fn point_count(&self) -> usize {
+        with_hnsw!(&self.hnsw, hnsw => hnsw.get_nb_point())
+    }
+
+    /// Length of the stored vectors (`None` without any)
+    fn vector_dimension(&self) -> Option<usize> {
+        with_hnsw!(&self.hnsw, hnsw => hnsw
+            .get_point_indexation()
+            .into_iter()
+            .next()
+            .map(|point| point.get_v().len()))
+    }
+
+    /// Load a shard for repair: like `load`, but recovering what it can of a truncated
+    /// doc_id index. Returns the data, its dimension if known and whether doc_ids were
+    /// recovered, or `None` for a shard without files.
+    fn salvage(dir: &Path) -> Result<Option<(Self, Option<usize>, bool)>> {
+        let doc_ids_path = dir.join(DOC_IDS_FILE);
+        if !doc_ids_path.exists() && !dir.join(LEGACY_FILE).exists() {
+            return Ok(None);
+        }
+        let error = match Self::load(dir, false) {
+            Ok((data, dimension)) => return Ok(Some((data, Some(dimension), false))),
+            Err(e) => e,
+        };
+
+        // Only the doc_id index can be recovered; anything else loses the shard
+        let content = std::fs::read_to_string(&doc_ids_path).map_err(|_| error)?;
+        if serde_json::from_str::<DocIdIndex>(&content).is_ok() {
+            return Err(YgrepError::Config("HNSW graph is unreadable".to_string()));
+        }
+        let (dimension, doc_ids) = salvage_doc_ids(&content)
+            .ok_or_else(|| YgrepError::Config("doc_id index is unreadable".to_string()))?;
+        // Saved ahead of the doc_ids, so they survive the cut
+        let distance = if content.contains("\"distance\":\"dot\"") {
+            VectorDistance::Dot
+        } else {
+            VectorDistance::Cosine
+        };
+        let generation = salvage_number(&content, "generation").unwrap_or(0);
+        let (hnsw, reloader) = Graph::load(dir, generation, distance, false)?;
+        let data = Self {
+            hnsw,
+            _reloader: Some(reloader),
+            doc_ids,
+            generation: AtomicU64::new(generation),
+        };
+        Ok(Some((data, dimension, true)))
+    }
+}
+
+impl Shard {
+    /// An empty shard (marked changed, so saving replaces whatever is on disk)
+    fn empty(dir: PathBuf, distance: VectorDistance) -> Self {
+        Self {
+            dir,
+            data: RwLock::new(Some(ShardData::empty(distance))),
+            len: AtomicUsize::new(0),
+            dirty: AtomicBool::new(true),
+            mmap: false,
+        }
+    }
+
+    /// A saved shard, loaded on first use
+    fn saved(dir: PathBuf, len: usize, distance: VectorDistance, mmap: bool) -> Self {
+        Self {
+            dir,
+            data: RwLock::new((len == 0).then(|| ShardData::empty(distance))),
+            len: AtomicUsize::new(len),
+            dirty: AtomicBool::new(false),
+            mmap,
+        }
+    }
+
+    fn loaded(dir: PathBuf, data: ShardData) -> Self {
+        Self {
+            dir,
+            len: AtomicUsize::new(data.doc_ids.len()),
+            data: RwLock::new(Some(data)),
+            dirty: AtomicBool::new(false),
+            mmap: false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    fn ensure_loaded(&self) -> Result<()> {
+        if self.data.read().is_some() {
+            return Ok(());
+        }
+        let mut data = self.data.write();
+        if data.is_none() {
+            *data = Some(ShardData::load(&self.dir, self.mmap)?.0);
+        }
+        Ok(())
+    }
+
+    fn insert(&self, doc_id: &str, embedding: &[f32]) -> Result<usize> {
+        self.ensure_loaded()?;
+        let mut guard = self.data.write();
+        let data = guard.as_mut().expect("shard is loaded");
+
+        // Store the doc_id, then insert into HNSW
+        let id = data.doc_ids.len();
+        data.doc_ids.push(doc_id.to_string());
+        data.hnsw.insert(embedding, id);
+
+        self.len.store(data.doc_ids.len(), Ordering::Relaxed);
+        self.dirty.store(true, Ordering::Relaxed);
+        Ok(id)
+    }
+
+    /// Nearest neighbours as (ID within the shard, distance, doc_id), skipping doc_ids
+    /// `keep` rejects. The graph search widens until `k` pass or the shard is exhausted.
+    fn search(
+        &self,
+        query: &[f32],
+        k: usize,
+        keep: &(dyn Fn(&str) -> bool + Sync),
+    ) -> Result<Vec<(usize, f32, String)>> {
+        if self.len() == 0 || k == 0 {
+            return Ok(vec![]);
+        }
+        self.ensure_loaded()?;
+        let guard = self.data.read();
+        let data = guard.as_ref().expect("shard is loaded");
+
+        let mut fetch = k.min(data.doc_ids.len());
+        loop {
+            // ef_search should be >= k, higher = better recall
+            let ef_search = fetch.max(30);
+            let neighbors = with_hnsw!(&data.hnsw, hnsw => hnsw.search(query, fetch, ef_search));
+            let found = neighbors.len();
+
+            let kept: Vec<(usize, f32, String)> = neighbors
+                .into_iter()
+                .filter_map(|n| {
+                    data.doc_ids
+                        .get(n.d_id)
+                        .filter(|doc_id| keep(doc_id))
+                        .map(|doc_id| (n.d_id, n.distance, doc_id.clone()))
+                })
+                .take(k)
+                .collect();
+            if kept.len() >= k || found < fetch || fetch >= data.doc_ids.len() {
+                return Ok(kept);
+            }
+            fetch = (fetch * 4).min(data.doc_ids.len());
+        }
+    }
+
+    /// Write the shard if it changed; empty shards have their files removed instead
+    fn save(&self, dimension: usize) -> Result<()> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let guard = self.data.read();
+        let Some(data) = guard.as_ref() else {
+            return Ok(());
+        };
+
+        if data.doc_ids.is_empty() {
+            for file in shard_files(&self.dir) {
+                std::fs::remove_file(file)?;
+            }
+            return Ok(());
+        }
+        std::fs::create_dir_all(&self.dir)?;
+
+        // Save HNSW graph for fast loading, as a new generation: the previous data file
+        // may be mapped, here or by another process
+        let generation = data.generation.load(Ordering::Relaxed) + 1;
+        let basename = hnsw_basename(generation);
+        with_hnsw!(&data.hnsw, hnsw => hnsw.file_dump(&self.dir, &basename))
+            .map_err(|e| YgrepError::Config(format!("Failed to save HNSW index: {}", e)))?;
+
+        // Save compact doc_id index (fast to load), switching to the new generation
+        let doc_index = DocIdIndex {
+            dimension,
+            distance: data.hnsw.distance(),
+            generation,
+            doc_ids: data.doc_ids.clone(),
+        };
+        let tmp = self.dir.join(format!("{}.tmp", DOC_IDS_FILE));
+        serde_json::to_writer(std::fs::File::create(&tmp)?, &doc_index)
+            .map_err(|e| YgrepError::Config(format!("Failed to save doc_id index: {}", e)))?;
+        std::fs::rename(&tmp, self.dir.join(DOC_IDS_FILE))?;
+        data.generation.store(generation, Ordering::Relaxed);
+
+        // Mappings outlive their file on Unix; elsewhere, files still mapped are removed
+        // by a later save
+        for file in shard_files(&self.dir) {
+            let name = file
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("");
+            if is_hnsw_file(name) && !name.starts_with(&format!("{}.", basename)) {
+                let _ = std::fs::remove_file(file);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Number of shards of a sharded layout, from its `shard-NNN` directories
+fn shard_dirs_in(path: &Path) -> usize {
+    std::fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix("shard-"))
+                .and_then(|n| n.parse::<usize>().ok())
+        })
+        .map(|shard| shard + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Whether `name` is a file of an HNSW dump (of any generation)
+fn is_hnsw_file(name: &str) -> bool {
+    name.starts_with(HNSW_BASENAME)
+        && HNSW_EXTENSIONS
+            .iter()
+            .any(|extension| name.ends_with(extension))
+}
+
+/// Whether `dir` has an HNSW graph (of any generation)
+fn has_hnsw_dump(dir: &Path) -> bool {
+    shard_files(dir).iter().any(|file| {
+        file.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.ends_with(HNSW_EXTENSIONS[0]))
+    })
+}
+
+/// Files making up a saved shard in `dir`
+fn shard_files(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry.file_name().to_str().is_some_and(|name| {
+                name == DOC_IDS_FILE || name == LEGACY_FILE || is_hnsw_file(name)
+            })
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+impl VectorIndex {
+    /// Create a new (unsharded) vector index with the default distance
+    pub fn new(path: PathBuf, dimension: usize) -> Result<Self> {
+        Self::with_shards(path, dimension, 1, VectorDistance::default())
+    }
+
+    /// Create a new vector index split into `shards` shards
+    pub fn with_shards(
+        path: PathBuf,
+        dimension: usize,
+        shards: usize,
+        distance: VectorDistance,
+    ) -> Result<Self> {
+        std::fs::create_dir_all(&path)?;
+        let index = Self {
+            path,
+            dimension,
+            distance: RwLock::new(distance),
+            shards: RwLock::new(Vec::new()),
+        };
+        index.reset(shards, distance);
+        Ok(index)
+    }
+
+    /// Load an existing vector index (shards are read from disk when first used)
+    pub fn load(path: PathBuf) -> Result<Self> {
+        Self::load_with_mmap(path, false)
+    }
+
+    /// Load an existing vector index, memory-mapping the saved vectors with `mmap`
+    /// rather than reading them into memory
+    pub fn load_with_mmap(path: PathBuf, mmap: bool) -> Result<Self> {
+        let manifest_path = path.join(MANIFEST_FILE);
+        if manifest_path.exists() {
+            let manifest: ShardManifest =
+                serde_json::from_reader(std::fs::File::open(&manifest_path)?).map_err(|e| {
+                    YgrepError::Config(format!("Failed to load vector shard manifest: {}", e))
+                })?;
+            let count = manifest.shards.len().max(1);
+            let shards = (0..count)
+                .map(|i| {
+                    let len = manifest.shards.get(i).copied().unwrap_or(0);
+                    Shard::saved(shard_dir(&path, i, count), len, manifest.distance, mmap)
+                })
+                .collect();
+            return Ok(Self {
+                path,
+                dimension: manifest.dimension,
+                distance: RwLock::new(manifest.distance),
+                shards: RwLock::new(shards),
+            });
         }
 
+        // Unsharded index
+        let (data, dimension) = ShardData::load(&path, mmap)?;
+        let distance = data.hnsw.distance();
+        let shard = Shard {
+            mmap,
+            ..Shard::loaded(path.clone(), data)
+        };
         Ok(Self {
+            distance: RwLock::new(distance),
+            shards: RwLock::new(vec![shard]),
             path,
-            hnsw: RwLock::new(hnsw),
-            dimension: data.dimension,
-            doc_ids: RwLock::new(doc_ids),
+            dimension,
         })
     }
 
+    /// Problems with the saved shards: files that can't be read, or vector counts that
+    /// disagree between the manifest, the doc_id index and the HNSW graph
+    pub fn verify(&self) -> Vec<String> {
+        let shards = self.shards.read();
+        let mut problems = Vec::new();
+        for (i, shard) in shards.iter().enumerate() {
+            if shard.len() == 0 {
+                continue;
+            }
+            let label = if shards.len() == 1 {
+                "Vectors".to_string()
+            } else {
+                format!("Shard {}", i)
+            };
+
+            // Read from disk again, whatever is loaded in memory
+            let (data, dimension) = match ShardData::load(&shard.dir, false) {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    problems.push(format!("{}: cannot load: {}", label, e));
+                    continue;
+                }
+            };
+            if dimension != self.dimension {
+                problems.push(format!(
+                    "{}: dimension {} (index has {})",
+                    label, dimension, self.dimension
+                ));
+            }
+            if data.doc_ids.len() != shard.len() {
+                problems.push(format!(
+                    "{}: {} doc_ids, but {} vectors recorded",
+                    label,
+                    data.doc_ids.len(),
+                    shard.len()
+                ));
+            }
+            let points = data.point_count();
+            if points != data.doc_ids.len() {
+                problems.push(format!(
+                    "{}: {} vectors in the HNSW graph for {} doc_ids",
+                    label,
+                    points,
+                    data.doc_ids.len()
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Repair a saved index in place: recover doc_ids from a truncated doc_id index, drop
+    /// vectors without a doc_id or whose document isn't among `documents` (the parent
+    /// doc_ids in the text index), and rewrite the shards that changed. Unreadable shards
+    /// are removed, and their documents reported as lost.
+    pub fn salvage(path: &Path, documents: &HashSet<String>) -> Result<VectorSalvage> {
+        let mut salvage = VectorSalvage::default();
+
+        let manifest_path = path.join(MANIFEST_FILE);
+        let manifest: Option<ShardManifest> = if manifest_path.exists() {
+            let manifest = std::fs::read_to_string(&manifest_path)
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok());
+            if manifest.is_none() {
+                salvage
+                    .repaired
+                    .push("Rebuilt the unreadable vector shard manifest".to_string());
+            }
+            manifest
+        } else {
+            None
+        };
+        let count = match &manifest {
+            Some(manifest) => manifest.shards.len().max(1),
+            None => shard_dirs_in(path).max(1),
+        };
+        let mut dimension = manifest.as_ref().map(|m| m.dimension);
+        let mut distance = manifest
+            .as_ref()
+            .map(|m| m.distance)
+            .unwrap_or_else(saved_before_distance);
+
+        let mut lens = Vec::with_capacity(count);
+        let mut damaged = vec![false; count];
+        // Documents with at least one vector left
+        let mut embedded: HashSet<String> = HashSet::new();
+        for (shard, damaged) in damaged.iter_mut().enumerate() {
+            let dir = shard_dir(path, shard, count);
+            let label = if count == 1 {
+                "Vectors".to_string()
+            } else {
+                format!("Vector shard {}", shard)
+            };
+
+            let (data, shard_dimension, recovered) = match ShardData::salvage(&dir) {
+                Ok(Some(loaded)) => loaded,
+                Ok(None) => {
+                    let expected = manifest
+                        .as_ref()
+                        .and_then(|m| m.shards.get(shard).copied())
+                        .unwrap_or(0);
+                    *damaged = expected > 0;
+                    lens.push(0);
+                    continue;
+                }
+                Err(e) => {
+                    for file in shard_files(&dir) {
+                        std::fs::remove_file(file)?;
+                    }
+                    salvage
+                        .repaired
+                        .push(format!("{}: removed, it was unreadable ({})", label, e));
+                    *damaged = true;
+                    lens.push(0);
+                    continue;
+                }
+            };
+
+            // A recovered doc_id index may have lost its dimension; the vectors know it
+            let shard_dimension = shard_dimension
+                .or(dimension)
+                .or_else(|| data.vector_dimension())
+                .unwrap_or(0);
+            distance = data.hnsw.distance();
+            let (kept, unmapped, orphans) =
+                data.retain(|doc_id| documents.contains(parent_of(doc_id)));
+            embedded.extend(kept.doc_ids.iter().map(|id| parent_of(id).to_string()));
+            salvage.orphans += orphans;
+
+            if recovered {
+                salvage.repaired.push(format!(
+                    "{}: recovered {} doc_ids from a truncated doc_id index",
+                    label,
+                    data.doc_ids.len()
+                ));
+            }
+            if unmapped > 0 {
+                salvage.repaired.push(format!(
+                    "{}: dropped {} vectors without a doc_id",
+                    label, unmapped
+                ));
+            }
+            *damaged = recovered || unmapped > 0 || data.point_count() != data.doc_ids.len();
+
+            lens.push(kept.doc_ids.len());
+            if kept.doc_ids.len() != data.doc_ids.len() || *damaged {
+                let shard = Shard::loaded(dir, kept);
+                shard.dirty.store(true, Ordering::Relaxed);
+                shard.save(shard_dimension)?;
+            }
+        }
+        if salvage.orphans > 0 {
+            salvage.repaired.push(format!(
+                "Dropped {} vectors of documents that are no longer indexed",
+                salvage.orphans
+            ));
+        }
+
+        if count > 1 {
+            let manifest = ShardManifest {
+                dimension: dimension.unwrap_or(0),
+                distance,
+                shards: lens,
+            };
+            serde_json::to_writer(std::fs::File::create(&manifest_path)?, &manifest).map_err(
+                |e| YgrepError::Config(format!("Failed to save vector shard manifest: {}", e)),
+            )?;
+        }
+
+        salvage.lost = documents
+            .iter()
+            .filter(|doc_id| damaged[shard_of(doc_id, count)] && !embedded.contains(*doc_id))
+            .cloned()
+            .collect();
+        salvage.lost.sort();
+        Ok(salvage)
+    }
+
+    /// Drop the vectors whose doc_id `keep` rejects (e.g. of documents no longer in the
+    /// text index) and those without a doc_id; returns how many were dropped. The shards
+    /// that changed are written by the next `save`.
+    pub fn retain(&self, keep: impl Fn(&str) -> bool) -> Result<usize> {
+        let shards = self.shards.read();
+        let mut dropped = 0;
+        for shard in shards.iter() {
+            if shard.len() == 0 {
+                continue;
+            }
+            shard.ensure_loaded()?;
+            let mut guard = shard.data.write();
+            let data = guard.as_mut().expect("shard is loaded");
+            let (kept, unmapped, rejected) = data.retain(&keep);
+            if unmapped + rejected == 0 {
+                continue;
+            }
+            dropped += unmapped + rejected;
+            *data = kept;
+            shard.len.store(data.doc_ids.len(), Ordering::Relaxed);
+            shard.dirty.store(true, Ordering::Relaxed);
+        }
+        Ok(dropped)
+    }
+
+    /// The vectors whose doc_id `wanted` accepts (e.g. those of the documents an index
+    /// delta carries), with their doc_ids
+    pub fn vectors(&self, wanted: impl Fn(&str) -> bool) -> Result<Vec<(String, Vec<f32>)>> {
+        let shards = self.shards.read();
+        let mut vectors = Vec::new();
+        for shard in shards.iter() {
+            if shard.len() == 0 {
+                continue;
+            }
+            shard.ensure_loaded()?;
+            let guard = shard.data.read();
+            let data = guard.as_ref().expect("shard is loaded");
+            vectors.extend(data.vectors(&wanted));
+        }
+        Ok(vectors)
+    }
+
+    /// Number of vectors whose doc_id `wanted` accepts
+    pub fn count(&self, wanted: impl Fn(&str) -> bool) -> Result<usize> {
+        let shards = self.shards.read();
+        let mut count = 0;
+        for shard in shards.iter() {
+            if shard.len() == 0 {
+                continue;
+            }
+            shard.ensure_loaded()?;
+            let guard = shard.data.read();
+            let data = guard.as_ref().expect("shard is loaded");
+            count += data
+                .doc_ids
+                .iter()
+                .filter(|doc_id| wanted(doc_id.as_str()))
+                .count();
+        }
+        Ok(count)
+    }
+
     /// Check if a vector index exists at the path
     pub fn exists(path: &Path) -> bool {
-        // Check for new format (doc_ids.json + HNSW dump) or legacy format (vectors.json)
-        let new_format = path.join("doc_ids.json").exists()
-            && path.join(format!("{}.hnsw.graph", HNSW_BASENAME)).exists();
-        let legacy_format = path.join("vectors.json").exists();
-        new_format || legacy_format
+        // Check for a sharded index, the new format (doc_ids.json + HNSW dump) or the
+        // legacy format (vectors.json)
+        let sharded = path.join(MANIFEST_FILE).exists();
+        let new_format = path.join(DOC_IDS_FILE).exists() && has_hnsw_dump(path);
+        let legacy_format = path.join(LEGACY_FILE).exists();
+        sharded || new_format || legacy_format
     }
 
     /// Insert an embedding and return its ID
@@ -139,23 +1000,28 @@ impl VectorIndex {
             )));
         }
 
-        let mut doc_ids = self.doc_ids.write();
-        let id = doc_ids.len();
-
-        // Store the doc_id
-        doc_ids.push(doc_id.to_string());
-
-        // Insert into HNSW
-        let hnsw = self.hnsw.write();
-        hnsw.insert((&embedding.to_vec(), id));
-
-        Ok(id as u64)
+        let shards = self.shards.read();
+        let shard = shard_of(doc_id, shards.len());
+        let id = shards[shard].insert(doc_id, &normalized(embedding))?;
+        Ok(((shard as u64) << SHARD_ID_BITS) | id as u64)
     }
 
     /// Search for similar vectors
     ///
     /// Returns (vector_id, distance, doc_id) tuples, sorted by distance (ascending)
     pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<(u64, f32, String)>> {
+        self.search_filtered(query, k, |_| true)
+    }
+
+    /// Search for similar vectors among the doc_ids `keep` accepts (e.g. those of the
+    /// files passing `-e`/`-p`), over-fetching from the graph so rejected neighbours
+    /// don't reduce the result below `k` while enough matching vectors exist
+    pub fn search_filtered(
+        &self,
+        query: &[f32],
+        k: usize,
+        keep: impl Fn(&str) -> bool + Sync,
+    ) -> Result<Vec<(u64, f32, String)>> {
         if query.len() != self.dimension {
             return Err(YgrepError::Config(format!(
                 "Query dimension mismatch: expected {}, got {}",
@@ -164,50 +1030,95 @@ impl VectorIndex {
             )));
         }
 
-        let hnsw = self.hnsw.read();
-        let doc_ids = self.doc_ids.read();
+        let query = normalized(query);
+        let query = query.as_slice();
+        let shards = self.shards.read();
+        let keep = &keep as &(dyn Fn(&str) -> bool + Sync);
+        let per_shard: Vec<Result<Vec<(usize, f32, String)>>> = if shards.len() == 1 {
+            vec![shards[0].search(query, k, keep)]
+        } else {
+            // Each shard returns its own top k; the best k overall are among them
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = shards
+                    .iter()
+                    .map(|shard| scope.spawn(move || shard.search(query, k, keep)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle.join().unwrap_or_else(|_| {
+                            Err(YgrepError::Config("Vector shard search panicked".into()))
+                        })
+                    })
+                    .collect()
+            })
+        };
 
-        if doc_ids.is_empty() {
-            return Ok(vec![]);
+        let mut results = Vec::new();
+        for (shard, neighbors) in per_shard.into_iter().enumerate() {
+            results.extend(neighbors?.into_iter().map(|(id, distance, doc_id)| {
+                (
+                    ((shard as u64) << SHARD_ID_BITS) | id as u64,
+                    distance,
+                    doc_id,
+                )
+            }));
         }
-
-        // ef_search should be >= k, higher = better recall
-        let ef_search = k.max(30);
-        let neighbors = hnsw.search(query, k, ef_search);
-
-        Ok(neighbors
-            .into_iter()
-            .filter_map(|n| {
-                doc_ids
-                    .get(n.d_id)
-                    .map(|doc_id| (n.d_id as u64, n.distance, doc_id.clone()))
-            })
-            .collect())
+        results.sort_by(|a, b| a.1.total_cmp(&b.1));
+        results.truncate(k);
+        Ok(results)
     }
 
-    /// Save the index to disk
+    /// Save the index to disk (only the shards that changed)
     pub fn save(&self) -> Result<()> {
-        // Save compact doc_id index (fast to load)
-        let doc_ids_path = self.path.join("doc_ids.json");
-        let doc_ids = self.doc_ids.read();
-        let doc_index = DocIdIndex {
-            dimension: self.dimension,
-            doc_ids: doc_ids.clone(),
-        };
-        serde_json::to_writer(std::fs::File::create(&doc_ids_path)?, &doc_index)
-            .map_err(|e| YgrepError::Config(format!("Failed to save doc_id index: {}", e)))?;
+        let shards = self.shards.read();
+        for shard in shards.iter() {
+            shard.save(self.dimension)?;
+        }
 
-        // Save HNSW graph for fast loading
-        let hnsw = self.hnsw.read();
-        hnsw.file_dump(&self.path, HNSW_BASENAME)
-            .map_err(|e| YgrepError::Config(format!("Failed to save HNSW index: {}", e)))?;
+        let manifest_path = self.path.join(MANIFEST_FILE);
+        if shards.len() > 1 {
+            let manifest = ShardManifest {
+                dimension: self.dimension,
+                distance: *self.distance.read(),
+                shards: shards.iter().map(Shard::len).collect(),
+            };
+            serde_json::to_writer(std::fs::File::create(&manifest_path)?, &manifest).map_err(
+                |e| YgrepError::Config(format!("Failed to save vector shard manifest: {}", e)),
+            )?;
+        } else if manifest_path.exists() {
+            std::fs::remove_file(&manifest_path)?;
+        }
+        self.remove_stale_shards(shards.len())
+    }
 
+    /// Remove files of a previous layout with a different number of shards
+    fn remove_stale_shards(&self, count: usize) -> Result<()> {
+        for entry in std::fs::read_dir(&self.path)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(shard) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("shard-"))
+                .and_then(|n| n.parse::<usize>().ok())
+            else {
+                continue;
+            };
+            if count == 1 || shard >= count {
+                std::fs::remove_dir_all(entry.path())?;
+            }
+        }
+        if count > 1 {
+            for file in shard_files(&self.path) {
+                std::fs::remove_file(file)?;
+            }
+        }
         Ok(())
     }
 
     /// Get the number of vectors in the index
     pub fn len(&self) -> usize {
-        self.doc_ids.read().len()
+        self.shards.read().iter().map(Shard::len).sum()
     }
 
     /// Check if the index is empty
@@ -220,11 +1131,29 @@ impl VectorIndex {
         self.dimension
     }
 
+    /// Number of shards
+    pub fn shard_count(&self) -> usize {
+        self.shards.read().len()
+    }
+
+    /// Distance the index ranks vectors by
+    pub fn distance(&self) -> VectorDistance {
+        *self.distance.read()
+    }
+
     /// Clear the index
     pub fn clear(&self) {
-        let mut hnsw = self.hnsw.write();
-        *hnsw = Hnsw::new(16, 10_000, 16, 200, DistCosine {});
-        self.doc_ids.write().clear();
+        self.reset(self.shard_count(), self.distance());
+    }
+
+    /// Clear the index, split it into `shards` shards and compare vectors by `distance`
+    /// from now on
+    pub fn reset(&self, shards: usize, distance: VectorDistance) {
+        let count = shards.max(1);
+        *self.distance.write() = distance;
+        *self.shards.write() = (0..count)
+            .map(|i| Shard::empty(shard_dir(&self.path, i, count), distance))
+            .collect();
     }
 }
 
@@ -233,6 +1162,63 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    #[test]
+    fn test_salvage_doc_ids() {
+        let (dimension, doc_ids) =
+            salvage_doc_ids(r#"{"dimension":4,"doc_ids":["a", "b:1","c"#).unwrap();
+        assert_eq!(dimension, Some(4));
+        assert_eq!(doc_ids, vec!["a", "b:1"]);
+        assert_eq!(salvage_doc_ids(r#"{"dimension":4,"doc"#), None);
+    }
+
+    #[test]
+    fn test_vector_index_retain() -> Result<()> {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_path_buf();
+        let index = VectorIndex::with_shards(path.clone(), 4, 2, VectorDistance::Dot)?;
+        index.insert("doc1", &[1.0, 0.0, 0.0, 0.0])?;
+        index.insert("doc1:1", &[0.9, 0.1, 0.0, 0.0])?;
+        index.insert("doc2", &[0.0, 1.0, 0.0, 0.0])?;
+        index.save()?;
+
+        let index = VectorIndex::load(path.clone())?;
+        assert_eq!(index.retain(|doc_id| doc_id != "doc1:1")?, 1);
+        assert_eq!(index.retain(|_| true)?, 0);
+        index.save()?;
+
+        let index = VectorIndex::load(path)?;
+        assert_eq!(index.len(), 2);
+        let results = index.search(&[0.9, 0.1, 0.0, 0.0], 3)?;
+        assert!(results.iter().all(|(_, _, doc_id)| doc_id != "doc1:1"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_vector_index_salvage() -> Result<()> {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_path_buf();
+        let index = VectorIndex::new(path.clone(), 4)?;
+        index.insert("doc1", &[1.0, 0.0, 0.0, 0.0])?;
+        index.insert("doc2", &[0.0, 1.0, 0.0, 0.0])?;
+        index.insert("doc3", &[0.0, 0.0, 1.0, 0.0])?;
+        index.save()?;
+
+        // doc2 is no longer indexed, and the doc_id index was cut off after doc2
+        let content = std::fs::read_to_string(path.join(DOC_IDS_FILE))?;
+        let cut = content.find("\"doc3").unwrap();
+        std::fs::write(path.join(DOC_IDS_FILE), &content[..cut])?;
+        let documents: HashSet<String> = ["doc1", "doc3"].map(String::from).into();
+
+        let salvage = VectorIndex::salvage(&path, &documents)?;
+        assert_eq!(salvage.orphans, 1);
+        assert_eq!(salvage.lost, vec!["doc3"]);
+
+        let index = VectorIndex::load(path)?;
+        assert_eq!(index.len(), 1);
+        assert!(index.verify().is_empty());
+        Ok(())
+    }
+
     #[test]
     fn test_vector_index_basic() -> Result<()> {
         let temp_dir = tempdir().unwrap();
@@ -278,6 +1264,7 @@ mod tests {
             let index = VectorIndex::load(path)?;
             assert_eq!(index.len(), 2);
             assert_eq!(index.dimension(), 4);
+            assert!(index.verify().is_empty());
 
             // Search should work
             let results = index.search(&[1.0, 0.0, 0.0, 0.0], 1)?;
@@ -287,4 +1274,128 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_vector_index_mmap() -> Result<()> {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_path_buf();
+        let index = VectorIndex::new(path.clone(), 4)?;
+        index.insert("doc1", &[1.0, 0.0, 0.0, 0.0])?;
+        index.insert("doc2", &[0.0, 1.0, 0.0, 0.0])?;
+        index.save()?;
+
+        let index = VectorIndex::load_with_mmap(path.clone(), true)?;
+        assert_eq!(index.search(&[0.0, 1.0, 0.0, 0.0], 1)?[0].2, "doc2");
+        // Saved next to the mapped dump, which is then removed
+        index.insert("doc3", &[0.0, 0.0, 1.0, 0.0])?;
+        index.save()?;
+        let mut files: Vec<String> = shard_files(&path)
+            .iter()
+            .filter_map(|file| Some(file.file_name()?.to_str()?.to_string()))
+            .collect();
+        files.sort();
+        #[cfg(unix)]
+        assert_eq!(
+            files,
+            vec!["doc_ids.json", "hnsw-2.hnsw.data", "hnsw-2.hnsw.graph"]
+        );
+        // The mapped vectors still search after their file is gone
+        assert_eq!(index.search(&[1.0, 0.0, 0.0, 0.0], 1)?[0].2, "doc1");
+
+        let index = VectorIndex::load_with_mmap(path, true)?;
+        assert_eq!(index.len(), 3);
+        assert!(index.verify().is_empty());
+        assert_eq!(index.search(&[0.0, 0.0, 1.0, 0.0], 1)?[0].2, "doc3");
+        Ok(())
+    }
+
+    #[test]
+    fn test_vector_distances() -> Result<()> {
+        for distance in [VectorDistance::Dot, VectorDistance::Cosine] {
+            let temp_dir = tempdir().unwrap();
+            let path = temp_dir.path().to_path_buf();
+            let index = VectorIndex::with_shards(path.clone(), 4, 1, distance)?;
+            index.insert("doc1", &[3.0, 0.0, 0.0, 0.0])?;
+            index.insert("doc2", &[0.0, 0.5, 0.0, 0.0])?;
+            index.insert("doc3", &[0.9, 0.1, 0.0, 0.0])?;
+            index.save()?;
+
+            let index = VectorIndex::load(path.clone())?;
+            assert_eq!(index.distance(), distance);
+            // Stored normalized: the query's length doesn't matter either
+            let results = index.search(&[0.1, 0.0, 0.0, 0.0], 3)?;
+            let doc_ids: Vec<_> = results.iter().map(|(_, _, id)| id.as_str()).collect();
+            assert_eq!(doc_ids, vec!["doc1", "doc3", "doc2"]);
+            assert!(results[0].1.abs() < 1e-5);
+            assert!((results[2].1 - 1.0).abs() < 1e-5);
+        }
+
+        // Indexes saved before the distance was recorded are cosine
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_path_buf();
+        let index = VectorIndex::with_shards(path.clone(), 4, 1, VectorDistance::Cosine)?;
+        index.insert("doc1", &[1.0, 0.0, 0.0, 0.0])?;
+        index.save()?;
+        let content = std::fs::read_to_string(path.join(DOC_IDS_FILE))?;
+        std::fs::write(
+            path.join(DOC_IDS_FILE),
+            content.replace("\"distance\":\"cosine\",", ""),
+        )?;
+        assert_eq!(VectorIndex::load(path)?.distance(), VectorDistance::Cosine);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sharded_vector_index() -> Result<()> {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().to_path_buf();
+        let vector = |i: usize| {
+            let angle = i as f32 * 0.1;
+            [angle.cos(), angle.sin(), 0.0, 0.0]
+        };
+
+        {
+            let index = VectorIndex::with_shards(path.clone(), 4, 4, VectorDistance::Dot)?;
+            for i in 0..40 {
+                index.insert(&format!("{:016x}", i), &vector(i))?;
+                index.insert(&format!("{:016x}:0", i), &vector(i))?;
+            }
+            assert_eq!(index.len(), 80);
+            index.save()?;
+        }
+        assert!(path.join(MANIFEST_FILE).exists());
+        assert!(!path.join(DOC_IDS_FILE).exists());
+
+        let index = VectorIndex::load(path.clone())?;
+        assert_eq!(index.shard_count(), 4);
+        assert_eq!(index.len(), 80);
+
+        // Results from all shards, merged by distance
+        let results = index.search(&vector(7), 4)?;
+        assert_eq!(results.len(), 4);
+        assert!(results.windows(2).all(|w| w[0].1 <= w[1].1));
+        let doc_ids: Vec<_> = results.iter().map(|(_, _, id)| id.as_str()).collect();
+        assert!(doc_ids.contains(&format!("{:016x}", 7).as_str()));
+        assert!(doc_ids.contains(&format!("{:016x}:0", 7).as_str()));
+
+        // Filtered: the nearest chunks are skipped, and enough files are still found
+        let results = index.search_filtered(&vector(7), 4, |doc_id| !doc_id.contains(':'))?;
+        assert_eq!(results.len(), 4);
+        assert!(results.iter().all(|(_, _, id)| !id.contains(':')));
+        assert_eq!(results[0].2, format!("{:016x}", 7));
+        // Fewer matches than k: all of them, however far
+        let results = index.search_filtered(&vector(7), 4, |doc_id| doc_id.ends_with('1'))?;
+        assert_eq!(results.len(), 3);
+        assert!(index.search_filtered(&vector(7), 4, |_| false)?.is_empty());
+
+        // Back to a single shard: the shard directories go away
+        index.reset(1, VectorDistance::Dot);
+        index.insert("doc1", &[1.0, 0.0, 0.0, 0.0])?;
+        index.save()?;
+        assert!(!path.join(MANIFEST_FILE).exists());
+        assert!(!path.join("shard-000").exists());
+        assert_eq!(VectorIndex::load(path)?.len(), 1);
+
+        Ok(())
+    }
 }
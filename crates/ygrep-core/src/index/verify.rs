@@ -0,0 +1,285 @@
+//! Index integrity checks (`ygrep indexes verify`): workspace metadata, Tantivy segments
+//! and the vector index, each reported with the specific problems found
+
+use std::path::Path;
+use tantivy::schema::TantivyDocument;
+use tantivy::Index;
+
+/// Result of one check
+#[derive(Debug, Clone)]
+pub struct VerifyCheck {
+    pub name: &'static str,
+    /// What was checked (e.g. document counts), or why the check was skipped
+    pub summary: Option<String>,
+    pub problems: Vec<String>,
+}
+
+impl VerifyCheck {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            summary: None,
+            problems: Vec::new(),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Results of all checks on one index directory
+#[derive(Debug, Clone)]
+pub struct VerifyReport {
+    pub checks: Vec<VerifyCheck>,
+}
+
+impl VerifyReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(VerifyCheck::passed)
+    }
+
+    pub fn problem_count(&self) -> usize {
+        self.checks.iter().map(|check| check.problems.len()).sum()
+    }
+}
+
+/// Check an index directory without modifying it
+pub fn verify_index(index_path: &Path) -> VerifyReport {
+    let (metadata_check, semantic) = verify_metadata(index_path);
+    let checks = vec![
+        metadata_check,
+        verify_text_index(index_path),
+        verify_vector_index(index_path, semantic),
+    ];
+    VerifyReport { checks }
+}
+
+/// workspace.json exists, parses and describes an existing workspace; also returns the
+/// stored semantic flag
+fn verify_metadata(index_path: &Path) -> (VerifyCheck, bool) {
+    let mut check = VerifyCheck::new("workspace.json");
+    let content = match std::fs::read_to_string(index_path.join("workspace.json")) {
+        Ok(content) => content,
+        Err(e) => {
+            check
+                .problems
+                .push(format!("Cannot read workspace.json: {}", e));
+            return (check, false);
+        }
+    };
+    let metadata: serde_json::Value = match serde_json::from_str(&content) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            check.problems.push(format!("Invalid JSON: {}", e));
+            return (check, false);
+        }
+    };
+
+    match metadata.get("workspace").and_then(|w| w.as_str()) {
+        Some(workspace) if Path::new(workspace).is_dir() => {
+            check.summary = Some(workspace.to_string());
+        }
+        Some(workspace) => check.problems.push(format!(
+            "Workspace {} no longer exists (`ygrep indexes clean` removes its index)",
+            workspace
+        )),
+        None => check
+            .problems
+            .push("Missing \"workspace\" path".to_string()),
+    }
+    match metadata.get("indexed_at").and_then(|t| t.as_str()) {
+        Some(indexed_at) => {
+            if chrono::DateTime::parse_from_rfc3339(indexed_at).is_err() {
+                check
+                    .problems
+                    .push(format!("Invalid \"indexed_at\" timestamp: {}", indexed_at));
+            }
+        }
+        None => check.problems.push("Missing \"indexed_at\"".to_string()),
+    }
+    if metadata
+        .get("files_indexed")
+        .and_then(|n| n.as_u64())
+        .is_none()
+    {
+        check
+            .problems
+            .push("Missing or invalid \"files_indexed\"".to_string());
+    }
+    let semantic = match metadata.get("semantic") {
+        None => false,
+        Some(value) => value.as_bool().unwrap_or_else(|| {
+            check
+                .problems
+                .push(format!("Invalid \"semantic\" flag: {}", value));
+            false
+        }),
+    };
+    (check, semantic)
+}
+
+/// The Tantivy index opens, has the current schema, its files match their checksums
+/// and every stored document in every segment can be read
+fn verify_text_index(index_path: &Path) -> VerifyCheck {
+    let mut check = VerifyCheck::new("text index");
+    let index = match Index::open_in_dir(index_path) {
+        Ok(index) => index,
+        Err(e) => {
+            check.problems.push(format!("Cannot open index: {}", e));
+            return check;
+        }
+    };
+    super::register_tokenizers(index.tokenizers());
+
+    let existing = index.schema();
+    let schema = super::build_document_schema();
+    let missing: Vec<&str> = schema
+        .fields()
+        .map(|(_, entry)| entry.name())
+        .filter(|name| existing.get_field(name).is_err())
+        .collect();
+    if !missing.is_empty() {
+        check.problems.push(format!(
+            "Outdated schema, missing fields: {} (run `ygrep index --rebuild`)",
+            missing.join(", ")
+        ));
+    }
+
+    match index.validate_checksum() {
+        Ok(corrupted) => {
+            let mut corrupted: Vec<_> = corrupted.into_iter().collect();
+            corrupted.sort();
+            for file in corrupted {
+                check
+                    .problems
+                    .push(format!("Checksum mismatch in {}", file.display()));
+            }
+        }
+        Err(e) => check
+            .problems
+            .push(format!("Cannot validate checksums: {}", e)),
+    }
+
+    let reader = match index.reader() {
+        Ok(reader) => reader,
+        Err(e) => {
+            check.problems.push(format!("Cannot open reader: {}", e));
+            return check;
+        }
+    };
+    let searcher = reader.searcher();
+    let mut documents = 0u64;
+    for segment in searcher.segment_readers() {
+        let id = segment.segment_id().short_uuid_string();
+        let store = match segment.get_store_reader(1) {
+            Ok(store) => store,
+            Err(e) => {
+                check
+                    .problems
+                    .push(format!("Segment {}: cannot open document store: {}", id, e));
+                continue;
+            }
+        };
+        let mut unreadable = 0;
+        for doc in store.iter::<TantivyDocument>(segment.alive_bitset()) {
+            match doc {
+                Ok(_) => documents += 1,
+                Err(_) => unreadable += 1,
+            }
+        }
+        if unreadable > 0 {
+            check.problems.push(format!(
+                "Segment {}: {} unreadable documents",
+                id, unreadable
+            ));
+        }
+    }
+    check.summary = Some(format!(
+        "{} documents in {} segments",
+        documents,
+        searcher.segment_readers().len()
+    ));
+    check
+}
+
+/// Shards load, their vector and doc_id counts agree, and the dimension matches the
+/// embedding model
+#[cfg(feature = "embeddings")]
+fn verify_vector_index(index_path: &Path, semantic: bool) -> VerifyCheck {
+    use super::VectorIndex;
+
+    let mut check = VerifyCheck::new("vector index");
+    let path = index_path.join("vectors");
+    if !VectorIndex::exists(&path) {
+        if semantic {
+            check
+                .problems
+                .push("Semantic index, but no vectors are saved".to_string());
+        } else {
+            check.summary = Some("none (text-only index)".to_string());
+        }
+        return check;
+    }
+
+    let vector_index = match VectorIndex::load(path) {
+        Ok(vector_index) => vector_index,
+        Err(e) => {
+            check.problems.push(format!("Cannot load: {}", e));
+            return check;
+        }
+    };
+    if vector_index.dimension() != crate::EMBEDDING_DIM {
+        check.problems.push(format!(
+            "Dimension {} doesn't match the embedding model ({})",
+            vector_index.dimension(),
+            crate::EMBEDDING_DIM
+        ));
+    }
+    check.problems.extend(vector_index.verify());
+    check.summary = Some(format!(
+        "{} vectors in {} shards, dimension {}",
+        vector_index.len(),
+        vector_index.shard_count(),
+        vector_index.dimension()
+    ));
+    check
+}
+
+#[cfg(not(feature = "embeddings"))]
+fn verify_vector_index(_index_path: &Path, _semantic: bool) -> VerifyCheck {
+    let mut check = VerifyCheck::new("vector index");
+    check.summary = Some("skipped (built without semantic search)".to_string());
+    check
+}
+
+#[cfg(all(test, feature = "index-write"))]
+mod tests {
+    use super::*;
+    use crate::{Config, Workspace};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_verify_index() -> crate::Result<()> {
+        let temp = tempdir()?;
+        let root = temp.path().join("workspace");
+        std::fs::create_dir_all(&root)?;
+        std::fs::write(root.join("main.rs"), "fn main() {}")?;
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp.path().join("data");
+        let workspace = Workspace::create_with_config(&root, config)?;
+        workspace.index_all()?;
+
+        let report = verify_index(workspace.index_path());
+        assert!(report.passed(), "{:?}", report);
+
+        // Damaged metadata is reported, with the other checks still passing
+        std::fs::write(workspace.index_path().join("workspace.json"), "{")?;
+        let report = verify_index(workspace.index_path());
+        assert!(!report.passed());
+        assert_eq!(report.problem_count(), 1);
+        assert!(!report.checks[0].passed());
+        Ok(())
+    }
+}
@@ -1,16 +1,49 @@
 use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::ops::Range;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tantivy::{Index, IndexWriter, TantivyDocument, Term};
 use xxhash_rust::xxh3::xxh3_64;
 
-use super::schema::SchemaFields;
+use super::incremental::modified_secs;
+use super::memory;
+use super::notebook::Notebook;
+use super::regions::{line_spans, Regions};
+use super::schema::{units, SchemaFields};
+use super::secrets::{SecretHandling, SecretScanner};
+use super::stemming::Stemming;
 #[cfg(feature = "embeddings")]
 use super::VectorIndex;
 use crate::config::IndexerConfig;
 #[cfg(feature = "embeddings")]
 use crate::embeddings::{EmbeddingCache, EmbeddingModel};
 use crate::error::{Result, YgrepError};
+use crate::fs::{
+    detect_language, extension_for_language, find_submodules, read_head, read_text, relative_path,
+    submodule_of, FileKind, Submodule, TextEncoding,
+};
+
+/// What a file's documents (the file, its chunks and lines) have in common
+struct FileFields<'a> {
+    /// Relative to the workspace root
+    path: &'a str,
+    extension: &'a str,
+    kind: FileKind,
+    /// Only the start of the file is indexed (`indexer.oversized_head_kb`)
+    truncated: bool,
+    /// Files importing it
+    references: u64,
+    /// Name of the submodule it is in (`""` outside any)
+    submodule: &'a str,
+    /// Its comments and strings, unless the lexer doesn't know its language
+    regions: Option<&'a Regions>,
+    /// Byte ranges of its lines, to cut the regions of chunks and lines from
+    line_spans: &'a [Range<usize>],
+    /// Line ranges of a notebook's cells, chunked one cell at a time
+    cells: Option<&'a [Range<usize>]>,
+}
 
 /// Handles indexing of files and content
 pub struct Indexer {
@@ -19,6 +52,16 @@ pub struct Indexer {
     writer: Arc<RwLock<IndexWriter>>,
     fields: SchemaFields,
     workspace_root: String,
+    /// Credential scanner, unless `secrets` is off
+    secrets: Option<SecretScanner>,
+    /// Prose files to stem, with `stemming` on
+    stemming: Option<Stemming>,
+    /// Files read in another encoding than UTF-8
+    transcoded: AtomicUsize,
+    /// Files importing each file, by path (`indexer.reference_counts`)
+    references: HashMap<String, u64>,
+    /// The workspace's git submodules, whose files are tagged with their name
+    submodules: Vec<Submodule>,
     /// Optional vector index for semantic search
     #[cfg(feature = "embeddings")]
     vector_index: Option<Arc<VectorIndex>>,
@@ -33,11 +76,21 @@ pub struct Indexer {
 impl Indexer {
     /// Create a new indexer for a workspace (text search only)
     pub fn new(config: IndexerConfig, index: Index, workspace_root: &Path) -> Result<Self> {
-        let writer = index.writer(50_000_000)?; // 50MB heap
+        // (the prose tokenizer must be set before the writer picks up the tokenizers)
+        let stemming = Stemming::from_config(&config)?;
+        if let Some(ref stemming) = stemming {
+            stemming.register(index.tokenizers());
+        }
+        let writer = index.writer(memory::writer_heap(&config))?;
         let schema = index.schema();
         let fields = SchemaFields::new(&schema);
 
         Ok(Self {
+            secrets: (config.secrets != SecretHandling::Off).then(SecretScanner::new),
+            stemming,
+            transcoded: AtomicUsize::new(0),
+            references: HashMap::new(),
+            submodules: find_submodules(workspace_root),
             config,
             index,
             writer: Arc::new(RwLock::new(writer)),
@@ -62,11 +115,21 @@ impl Indexer {
         embedding_model: Arc<EmbeddingModel>,
         embedding_cache: Arc<EmbeddingCache>,
     ) -> Result<Self> {
-        let writer = index.writer(50_000_000)?; // 50MB heap
+        // (the prose tokenizer must be set before the writer picks up the tokenizers)
+        let stemming = Stemming::from_config(&config)?;
+        if let Some(ref stemming) = stemming {
+            stemming.register(index.tokenizers());
+        }
+        let writer = index.writer(memory::writer_heap(&config))?;
         let schema = index.schema();
         let fields = SchemaFields::new(&schema);
 
         Ok(Self {
+            secrets: (config.secrets != SecretHandling::Off).then(SecretScanner::new),
+            stemming,
+            transcoded: AtomicUsize::new(0),
+            references: HashMap::new(),
+            submodules: find_submodules(workspace_root),
             config,
             index,
             writer: Arc::new(RwLock::new(writer)),
@@ -78,62 +141,162 @@ impl Indexer {
         })
     }
 
+    /// Store how many files import each file (by path) with its documents, from
+    /// `references::ReferenceScanner::count`
+    pub fn with_references(mut self, references: HashMap<String, u64>) -> Self {
+        self.references = references;
+        self
+    }
+
+    /// Number of files indexed so far that weren't UTF-8 and were transcoded
+    pub fn transcoded(&self) -> usize {
+        self.transcoded.load(Ordering::Relaxed)
+    }
+
+    /// Read a file the way it is indexed, transcoding UTF-16 and Latin-1 files to UTF-8;
+    /// files over `max_file_size` are skipped, or only their start is read (`truncated`)
+    fn read(&self, path: &Path, size: u64) -> Result<(String, TextEncoding, bool)> {
+        let truncated = size > self.config.max_file_size;
+        let (content, encoding) = match self.config.oversized_head_kb {
+            _ if !truncated => read_text(path)?,
+            Some(head_kb) => read_head(path, head_kb * 1024)?,
+            None => {
+                return Err(YgrepError::FileTooLarge {
+                    path: path.to_path_buf(),
+                    size,
+                    max: self.config.max_file_size,
+                })
+            }
+        };
+        Ok((content, encoding, truncated))
+    }
+
+    /// The doc_id a file would be indexed under (the hash of its content), without
+    /// indexing it
+    pub fn doc_id_of(&self, path: &Path) -> Result<String> {
+        let (content, _, _) = self.read(path, std::fs::metadata(path)?.len())?;
+        Ok(format!("{:016x}", xxh3_64(content.as_bytes())))
+    }
+
     /// Index a single file
     pub fn index_file(&self, path: &Path) -> Result<String> {
-        // Read file content
-        let content = std::fs::read_to_string(path)?;
         let metadata = std::fs::metadata(path)?;
-
-        // Check file size
         let size = metadata.len();
-        if size > self.config.max_file_size {
-            return Err(YgrepError::FileTooLarge {
-                path: path.to_path_buf(),
-                size,
-                max: self.config.max_file_size,
-            });
+        let (content, encoding, truncated) = self.read(path, size)?;
+        if encoding.is_transcoded() {
+            tracing::debug!("Transcoded {} from {}", path.display(), encoding);
         }
+        let mtime = modified_secs(&metadata);
+        self.index_text(path, content, encoding, size, mtime, truncated)
+    }
 
+    /// Index `content` as the file at `path` in place of what is on disk (the unsaved
+    /// changes of an editor buffer); the file doesn't need to exist
+    pub fn index_content(&self, path: &Path, content: &str) -> Result<String> {
+        let size = content.len() as u64;
+        let truncated = size > self.config.max_file_size;
+        let content = match self.config.oversized_head_kb {
+            _ if !truncated => content,
+            Some(head_kb) => &content[..content.floor_char_boundary((head_kb * 1024) as usize)],
+            None => {
+                return Err(YgrepError::FileTooLarge {
+                    path: path.to_path_buf(),
+                    size,
+                    max: self.config.max_file_size,
+                })
+            }
+        };
+        // (newer than the file on disk, so the next index reads the file again)
+        let mtime = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let encoding = TextEncoding::Utf8;
+        self.index_text(path, content.to_string(), encoding, size, mtime, truncated)
+    }
+
+    /// Index the text of the file at `path`, read in `encoding`
+    fn index_text(
+        &self,
+        path: &Path,
+        content: String,
+        encoding: TextEncoding,
+        size: u64,
+        mtime: u64,
+        truncated: bool,
+    ) -> Result<String> {
         // Generate content hash for deduplication and doc_id
         let content_hash = xxh3_64(content.as_bytes());
         let doc_id = format!("{:016x}", content_hash);
 
         // Get relative path
-        let rel_path = path
-            .strip_prefix(&self.workspace_root)
-            .unwrap_or(path)
-            .to_string_lossy()
-            .to_string();
-
-        // Get file extension
-        let extension = path
-            .extension()
-            .map(|e| e.to_string_lossy().to_string())
-            .unwrap_or_default();
-
-        // Get modification time
-        let mtime = metadata
-            .modified()
-            .ok()
-            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-            .map(|d| d.as_secs())
-            .unwrap_or(0);
+        let rel_path = relative_path(path, Path::new(&self.workspace_root));
+
+        // (extensionless files get their language's, so `ext:`/`lang:` filters find them)
+        let extension = file_extension(path, &content);
+
+        // Notebooks are indexed as the text of their cells
+        let (content, cells) = match Notebook::extract(&extension, &content) {
+            Some(notebook) => (notebook.text, Some(notebook.cells)),
+            None => (content, None),
+        };
+
+        // Keep credentials out of the stored content (and so out of snippets)
+        let content = match self.secrets.as_ref().map(|s| s.redact(&content)) {
+            Some(Some(_)) if self.config.secrets == SecretHandling::Skip => {
+                self.delete_by_path(&rel_path)?;
+                return Err(YgrepError::FileExcluded {
+                    path: path.to_path_buf(),
+                    reason: "contains secrets",
+                });
+            }
+            Some(Some(redacted)) => redacted,
+            _ => content,
+        };
+
+        let kind = FileKind::classify(Path::new(&rel_path), &content);
+        if kind == FileKind::Generated && self.config.skip_generated {
+            // Drop any version indexed before it became (or was seen as) generated
+            self.delete_by_path(&rel_path)?;
+            return Err(YgrepError::FileExcluded {
+                path: path.to_path_buf(),
+                reason: "generated",
+            });
+        }
 
         let line_count = content.lines().count() as u64;
+        let regions = Regions::lex(&extension, &content);
+        let spans = match regions {
+            Some(_) => line_spans(&content),
+            None => Vec::new(),
+        };
+        let file = FileFields {
+            path: &rel_path,
+            extension: &extension,
+            kind,
+            truncated,
+            references: self.references.get(&rel_path).copied().unwrap_or(0),
+            submodule: submodule_of(&self.submodules, &rel_path)
+                .map_or("", |submodule| submodule.name.as_str()),
+            regions: regions.as_ref(),
+            line_spans: &spans,
+            cells: cells.as_deref(),
+        };
 
         // Build the document
         let mut doc = TantivyDocument::new();
         doc.add_text(self.fields.doc_id, &doc_id);
-        doc.add_text(self.fields.path, &rel_path);
         doc.add_text(self.fields.workspace, &self.workspace_root);
-        doc.add_text(self.fields.content, &content);
+        self.add_content(&mut doc, &extension, &content);
         doc.add_u64(self.fields.mtime, mtime);
         doc.add_u64(self.fields.size, size);
-        doc.add_text(self.fields.extension, &extension);
+        self.add_file_fields(&mut doc, &file);
+        self.add_regions(&mut doc, &file, None);
+        doc.add_text(self.fields.encoding, encoding.as_str());
         doc.add_u64(self.fields.line_start, 1);
         doc.add_u64(self.fields.line_end, line_count);
         doc.add_text(self.fields.chunk_id, ""); // Not a chunk
         doc.add_text(self.fields.parent_doc, ""); // Not a chunk
+        doc.add_text(self.fields.unit, units::FILE);
 
         // Delete any existing document with same path
         self.delete_by_path(&rel_path)?;
@@ -141,12 +304,15 @@ impl Indexer {
         // Add the document
         let mut writer = self.writer.write();
         writer.add_document(doc)?;
+        if encoding.is_transcoded() {
+            self.transcoded.fetch_add(1, Ordering::Relaxed);
+        }
 
         // Also create chunks for the file
-        #[cfg(feature = "embeddings")]
-        let chunk_ids = self.index_chunks(&content, &doc_id, &rel_path, &mut writer)?;
-        #[cfg(not(feature = "embeddings"))]
-        let _ = self.index_chunks(&content, &doc_id, &rel_path, &mut writer)?;
+        let (chunks, by_symbol) = self.index_chunks(&content, &doc_id, &file, &mut writer)?;
+        if self.config.line_index {
+            self.index_lines(&content, &doc_id, &file, &mut writer)?;
+        }
 
         // Release the writer lock before embedding generation
         drop(writer);
@@ -158,76 +324,196 @@ impl Indexer {
             &self.embedding_model,
             &self.embedding_cache,
         ) {
-            // Embed the full document
-            let embedding = cache.get_or_insert(&content, || {
-                model.embed(&content).unwrap_or_else(|_| vec![0.0; 384])
-            });
-            vector_index.insert(&doc_id, &embedding)?;
+            // Embed the full document, unless its definitions are embedded instead
+            if !by_symbol {
+                let embedding = cache.get_or_insert(&content, || {
+                    model.embed(&content).unwrap_or_else(|_| vec![0.0; 384])
+                });
+                vector_index.insert(&doc_id, &embedding)?;
+            }
 
             // Embed chunks
-            for (chunk_id, chunk_content) in chunk_ids {
-                let chunk_embedding = cache.get_or_insert(&chunk_content, || {
+            for (chunk_id, chunk_content) in &chunks {
+                let chunk_embedding = cache.get_or_insert(chunk_content, || {
                     model
-                        .embed(&chunk_content)
+                        .embed(chunk_content)
                         .unwrap_or_else(|_| vec![0.0; 384])
                 });
-                vector_index.insert(&chunk_id, &chunk_embedding)?;
+                vector_index.insert(chunk_id, &chunk_embedding)?;
             }
         }
 
+        #[cfg(not(feature = "embeddings"))]
+        let _ = (chunks, by_symbol);
         Ok(doc_id)
     }
 
+    /// The (chunk_id, content) of a file's chunks when they follow its definitions
+    /// (`syntax_chunks`) or a notebook's cells, as `index_file` indexes them; none for
+    /// files chunked by line windows. Such files are embedded a chunk at a time rather
+    /// than whole.
+    pub fn symbol_chunks(&self, path: &Path, doc_id: &str, content: &str) -> Vec<(String, String)> {
+        let extension = file_extension(path, content);
+        let notebook = Notebook::extract(&extension, content);
+        let content = notebook.as_ref().map_or(content, |notebook| &notebook.text);
+        let lines: Vec<&str> = content.lines().collect();
+        let ranges = match &notebook {
+            Some(notebook) => Some(notebook.cells.clone()),
+            None => self.symbol_ranges(&extension, content, lines.len()),
+        };
+        ranges
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .map(|(n, range)| (format!("{}:{}", doc_id, n), lines[range].join("\n")))
+            .collect()
+    }
+
+    /// Add a document's content, stemmed as well for prose files
+    fn add_content(&self, doc: &mut TantivyDocument, extension: &str, content: &str) {
+        doc.add_text(self.fields.content, content);
+        if self
+            .stemming
+            .as_ref()
+            .is_some_and(|stemming| stemming.applies_to(extension))
+        {
+            doc.add_text(self.fields.prose, content);
+        }
+    }
+
+    /// Add the values every document of a file carries
+    fn add_file_fields(&self, doc: &mut TantivyDocument, file: &FileFields) {
+        doc.add_text(self.fields.path, file.path);
+        doc.add_text(self.fields.extension, file.extension);
+        doc.add_text(self.fields.kind, file.kind.as_str());
+        doc.add_bool(self.fields.truncated, file.truncated);
+        doc.add_u64(self.fields.references, file.references);
+        doc.add_text(self.fields.submodule, file.submodule);
+    }
+
+    /// Store the comments and strings of a document: the file, or its `lines`
+    fn add_regions(
+        &self,
+        doc: &mut TantivyDocument,
+        file: &FileFields,
+        lines: Option<Range<usize>>,
+    ) {
+        let Some(regions) = file.regions else {
+            return;
+        };
+        let encoded = match lines {
+            Some(lines) => regions.for_lines(&file.line_spans[lines]).encode(),
+            None => regions.encode(),
+        };
+        doc.add_text(self.fields.regions, encoded);
+    }
+
     /// Index chunks of a file for more granular search
-    /// Returns a list of (chunk_id, chunk_content) tuples for embedding generation
+    /// Returns a list of (chunk_id, chunk_content) tuples for embedding generation, and
+    /// whether the chunks follow the file's definitions (or a notebook's cells) rather
+    /// than line windows
     fn index_chunks(
         &self,
         content: &str,
         parent_doc_id: &str,
-        path: &str,
+        file: &FileFields,
         writer: &mut IndexWriter,
-    ) -> Result<Vec<(String, String)>> {
+    ) -> Result<(Vec<(String, String)>, bool)> {
         let lines: Vec<&str> = content.lines().collect();
-        let chunk_size = self.config.chunk_size;
-        let overlap = self.config.chunk_overlap;
-
-        if lines.len() <= chunk_size {
-            // File is small enough, no need for chunks
-            return Ok(vec![]);
-        }
-
         let mut chunks = Vec::new();
-        let mut start = 0;
-        let mut chunk_num = 0;
-
-        while start < lines.len() {
-            let end = (start + chunk_size).min(lines.len());
+        let symbols = match file.cells {
+            Some(cells) => Some(cells.to_vec()),
+            None => self.symbol_ranges(file.extension, content, lines.len()),
+        };
+        let (ranges, by_symbol) = match symbols {
+            Some(ranges) => (ranges, true),
+            None => (
+                chunk_ranges(
+                    lines.len(),
+                    self.config.chunk_size,
+                    self.config.chunk_overlap,
+                ),
+                false,
+            ),
+        };
+        for (chunk_num, std::ops::Range { start, end }) in ranges.into_iter().enumerate() {
             let chunk_content = lines[start..end].join("\n");
             let chunk_id = format!("{}:{}", parent_doc_id, chunk_num);
 
             let mut doc = TantivyDocument::new();
             doc.add_text(self.fields.doc_id, &chunk_id);
-            doc.add_text(self.fields.path, path);
             doc.add_text(self.fields.workspace, &self.workspace_root);
-            doc.add_text(self.fields.content, &chunk_content);
+            self.add_content(&mut doc, file.extension, &chunk_content);
             doc.add_u64(self.fields.mtime, 0);
             doc.add_u64(self.fields.size, chunk_content.len() as u64);
-            doc.add_text(self.fields.extension, "");
+            // (so extension filters match chunks without looking up their file)
+            self.add_file_fields(&mut doc, file);
+            self.add_regions(&mut doc, file, Some(start..end));
             doc.add_u64(self.fields.line_start, (start + 1) as u64);
             doc.add_u64(self.fields.line_end, end as u64);
             doc.add_text(self.fields.chunk_id, &chunk_id);
             doc.add_text(self.fields.parent_doc, parent_doc_id);
+            doc.add_text(self.fields.unit, units::CHUNK);
 
             writer.add_document(doc)?;
 
             // Store chunk info for embedding
             chunks.push((chunk_id, chunk_content));
+        }
+
+        Ok((chunks, by_symbol))
+    }
 
-            chunk_num += 1;
-            start += chunk_size - overlap;
+    /// Chunks along the definitions of a source file longer than a chunk
+    /// (`syntax_chunks`), as in `syntax::symbol_ranges`
+    #[cfg(feature = "treesitter")]
+    fn symbol_ranges(
+        &self,
+        extension: &str,
+        content: &str,
+        line_count: usize,
+    ) -> Option<Vec<std::ops::Range<usize>>> {
+        if !self.config.syntax_chunks || line_count <= self.config.chunk_size {
+            return None;
         }
+        super::syntax::symbol_ranges(extension, content, self.config.chunk_size)
+    }
 
-        Ok(chunks)
+    #[cfg(not(feature = "treesitter"))]
+    fn symbol_ranges(&self, _: &str, _: &str, _: usize) -> Option<Vec<std::ops::Range<usize>>> {
+        None
+    }
+
+    /// Index every line as its own document (line index mode). Lines aren't embedded;
+    /// they only serve grep-style searches.
+    fn index_lines(
+        &self,
+        content: &str,
+        parent_doc_id: &str,
+        file: &FileFields,
+        writer: &mut IndexWriter,
+    ) -> Result<()> {
+        for (i, line) in content.lines().enumerate() {
+            let line_number = (i + 1) as u64;
+            let line_id = format!("{}:L{}", parent_doc_id, line_number);
+
+            let mut doc = TantivyDocument::new();
+            doc.add_text(self.fields.doc_id, &line_id);
+            doc.add_text(self.fields.workspace, &self.workspace_root);
+            self.add_content(&mut doc, file.extension, line);
+            doc.add_u64(self.fields.mtime, 0);
+            doc.add_u64(self.fields.size, line.len() as u64);
+            self.add_file_fields(&mut doc, file);
+            self.add_regions(&mut doc, file, Some(i..i + 1));
+            doc.add_u64(self.fields.line_start, line_number);
+            doc.add_u64(self.fields.line_end, line_number);
+            doc.add_text(self.fields.chunk_id, &line_id);
+            doc.add_text(self.fields.parent_doc, parent_doc_id);
+            doc.add_text(self.fields.unit, units::LINE);
+
+            writer.add_document(doc)?;
+        }
+        Ok(())
     }
 
     /// Delete a document by path
@@ -266,6 +552,40 @@ impl Indexer {
     }
 }
 
+/// A file's extension; extensionless files (`bin/deploy`, `Makefile`) get the usual
+/// extension of the language detected from their shebang or name
+fn file_extension(path: &Path, content: &str) -> String {
+    match path.extension() {
+        Some(ext) => ext.to_string_lossy().to_string(),
+        None => detect_language(path, content)
+            .and_then(extension_for_language)
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// Line ranges (0-based, end exclusive) of the chunks of a file of `line_count` lines:
+/// windows of `size` lines, each starting `overlap` lines before the previous one ended,
+/// the last ending at the last line. Files of up to `size` lines aren't chunked.
+fn chunk_ranges(line_count: usize, size: usize, overlap: usize) -> Vec<std::ops::Range<usize>> {
+    let size = size.max(1);
+    if line_count <= size {
+        return Vec::new();
+    }
+    // (an overlap of the whole window would never move on)
+    let step = size - overlap.min(size - 1);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = (start + size).min(line_count);
+        ranges.push(start..end);
+        if end == line_count {
+            return ranges;
+        }
+        start += step;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -300,4 +620,59 @@ mod tests {
         assert!(!doc_id.is_empty());
         Ok(())
     }
+
+    #[test]
+    fn test_chunk_ranges() {
+        assert!(chunk_ranges(50, 50, 10).is_empty());
+        assert_eq!(chunk_ranges(90, 50, 10), vec![0..50, 40..90]);
+        assert_eq!(chunk_ranges(100, 50, 10), vec![0..50, 40..90, 80..100]);
+        assert_eq!(chunk_ranges(5, 2, 0), vec![0..2, 2..4, 4..5]);
+        // An overlap as large as the window still moves on a line at a time
+        assert_eq!(chunk_ranges(4, 3, 3), vec![0..3, 1..4]);
+        assert_eq!(chunk_ranges(2, 0, 0), vec![0..1, 1..2]);
+    }
+
+    #[test]
+    fn test_index_transcoded_file() -> Result<()> {
+        use tantivy::schema::Value;
+
+        let temp_dir = tempdir().unwrap();
+        let index_path = temp_dir.path().join("index");
+        std::fs::create_dir_all(&index_path).unwrap();
+
+        // UTF-16LE with a byte order mark, as Windows tools write it
+        let test_file = temp_dir.path().join("legacy.cs");
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(
+            "// café\nclass Legacy {}"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        );
+        std::fs::write(&test_file, bytes).unwrap();
+
+        let index = Index::create_in_dir(&index_path, build_document_schema())?;
+        crate::index::register_tokenizers(index.tokenizers());
+        let fields = SchemaFields::new(&index.schema());
+        let indexer = Indexer::new(IndexerConfig::default(), index.clone(), temp_dir.path())?;
+        indexer.index_file(&test_file)?;
+        indexer.commit()?;
+        assert_eq!(indexer.transcoded(), 1);
+
+        let searcher = index.reader()?.searcher();
+        let term = Term::from_field_text(fields.path, "legacy.cs");
+        let query = tantivy::query::TermQuery::new(term, tantivy::schema::IndexRecordOption::Basic);
+        let (_, address) = searcher.search(&query, &tantivy::collector::TopDocs::with_limit(1))?[0];
+        let doc: TantivyDocument = searcher.doc(address)?;
+        let text = |field| {
+            doc.get_first(field)
+                .and_then(|v| v.as_str())
+                .map(String::from)
+        };
+        assert_eq!(
+            text(fields.content).as_deref(),
+            Some("// café\nclass Legacy {}")
+        );
+        assert_eq!(text(fields.encoding).as_deref(), Some("utf-16le"));
+        Ok(())
+    }
 }
@@ -0,0 +1,156 @@
+//! Where ygrep keeps its files: indexes, snapshots and the workspace registry are durable
+//! and live in the data directory (`indexer.data_dir`, e.g. `~/.local/share/ygrep`), while
+//! what can be downloaded or built again (embedding models, `ygrep model bench` scratch
+//! indexes) lives in the cache directory (`indexer.cache_dir`, e.g. `~/.cache/ygrep`),
+//! which backup tools and cleaners may skip or empty.
+//!
+//! Older versions kept everything in the data directory and downloaded models into
+//! `.fastembed_cache` in the working directory; `migrate` moves them.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::config::IndexerConfig;
+use crate::error::Result;
+
+/// Marks a data directory as migrated to the current layout
+const LAYOUT_FILE: &str = "layout.json";
+
+/// Layout version written to `LAYOUT_FILE`
+const LAYOUT_VERSION: u32 = 2;
+
+/// Where older versions downloaded embedding models to, relative to the working directory
+pub const LEGACY_MODEL_CACHE: &str = ".fastembed_cache";
+
+/// Downloaded embedding models
+pub fn models_dir(config: &IndexerConfig) -> PathBuf {
+    config.cache_dir.join("models")
+}
+
+/// Scratch indexes of `ygrep model bench`
+pub fn bench_dir(config: &IndexerConfig) -> PathBuf {
+    config.cache_dir.join("bench")
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LayoutMarker {
+    version: u32,
+}
+
+/// What a migration moved
+#[derive(Debug, Default)]
+pub struct Migration {
+    /// (from, to) for each directory moved
+    pub moved: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Whether the data directory still needs `migrate` (it exists and has no layout marker)
+pub fn needs_migration(config: &IndexerConfig) -> bool {
+    if !config.data_dir.is_dir() {
+        return false;
+    }
+    let marker = std::fs::read_to_string(config.data_dir.join(LAYOUT_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str::<LayoutMarker>(&s).ok());
+    marker.is_none_or(|marker| marker.version < LAYOUT_VERSION)
+}
+
+/// Migrate once: run `migrate` if the data directory hasn't been yet
+pub fn migrate_once(config: &IndexerConfig) -> Result<Migration> {
+    if !needs_migration(config) {
+        return Ok(Migration::default());
+    }
+    migrate(config, &[])
+}
+
+/// Move caches out of the data directory into the cache directory, along with the model
+/// caches in `model_caches` (e.g. a workspace's `.fastembed_cache`), then mark the data
+/// directory as migrated. Files already in the cache directory are kept.
+pub fn migrate(config: &IndexerConfig, model_caches: &[PathBuf]) -> Result<Migration> {
+    let mut migration = Migration::default();
+    let mut moves = vec![(config.data_dir.join("bench"), bench_dir(config))];
+    moves.extend(
+        model_caches
+            .iter()
+            .map(|cache| (cache.clone(), models_dir(config))),
+    );
+    for (from, to) in moves {
+        if from.is_dir() && from != to {
+            move_dir(&from, &to)?;
+            tracing::info!("Moved {} to {}", from.display(), to.display());
+            migration.moved.push((from, to));
+        }
+    }
+
+    if config.data_dir.is_dir() {
+        let marker = LayoutMarker {
+            version: LAYOUT_VERSION,
+        };
+        std::fs::write(
+            config.data_dir.join(LAYOUT_FILE),
+            serde_json::to_string(&marker).unwrap_or_default(),
+        )?;
+    }
+    Ok(migration)
+}
+
+/// Move a directory's contents into `to`, merging with what is there; copies when a
+/// rename isn't possible (the cache and data directories on different filesystems)
+fn move_dir(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            move_dir(&entry.path(), &target)?;
+        } else if !target.exists() && std::fs::rename(entry.path(), &target).is_err() {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    std::fs::remove_dir_all(from)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_migrate() -> Result<()> {
+        let temp = tempdir()?;
+        let config = IndexerConfig {
+            data_dir: temp.path().join("data"),
+            cache_dir: temp.path().join("cache"),
+            ..Default::default()
+        };
+        let index = config.data_dir.join("indexes/0123456789abcdef");
+        std::fs::create_dir_all(&index)?;
+        std::fs::create_dir_all(config.data_dir.join("bench/bge-small-en-v1.5"))?;
+        let legacy = temp.path().join("workspace").join(LEGACY_MODEL_CACHE);
+        std::fs::create_dir_all(legacy.join("models--Xenova--all-MiniLM-L6-v2"))?;
+        std::fs::write(
+            legacy.join("models--Xenova--all-MiniLM-L6-v2/model.onnx"),
+            "onnx",
+        )?;
+
+        assert!(needs_migration(&config));
+        let migration = migrate(&config, std::slice::from_ref(&legacy))?;
+        assert_eq!(migration.moved.len(), 2);
+        assert!(index.is_dir());
+        assert!(!config.data_dir.join("bench").exists());
+        assert!(bench_dir(&config).join("bge-small-en-v1.5").is_dir());
+        assert!(!legacy.exists());
+        assert_eq!(
+            std::fs::read_to_string(
+                models_dir(&config).join("models--Xenova--all-MiniLM-L6-v2/model.onnx")
+            )?,
+            "onnx"
+        );
+
+        // Only once
+        assert!(!needs_migration(&config));
+        assert!(migrate_once(&config)?.moved.is_empty());
+        Ok(())
+    }
+}
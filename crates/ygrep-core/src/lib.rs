@@ -12,27 +12,36 @@ pub mod config;
 pub mod embeddings;
 pub mod error;
 pub mod fs;
+pub mod hooks;
 pub mod index;
+pub mod layout;
+pub mod registry;
 pub mod search;
+pub mod storage;
+#[cfg(feature = "watch")]
 pub mod watcher;
 
 pub use config::Config;
 pub use error::{Result, YgrepError};
-pub use watcher::{FileWatcher, WatchEvent};
+#[cfg(feature = "watch")]
+pub use watcher::{EmbeddingQueue, FileWatcher, WatchEvent};
 
 use std::path::Path;
 use tantivy::Index;
 
 #[cfg(feature = "embeddings")]
-use embeddings::{EmbeddingCache, EmbeddingModel};
+use embeddings::{EmbeddingCache, EmbeddingModel, ModelType};
 #[cfg(feature = "embeddings")]
 use index::VectorIndex;
 #[cfg(feature = "embeddings")]
 use std::sync::Arc;
 
-/// Embedding dimension for all-MiniLM-L6-v2
-#[cfg(feature = "embeddings")]
-const EMBEDDING_DIM: usize = 384;
+/// Recent commits counted when ranking files for the index size budget
+#[cfg(all(feature = "embeddings", feature = "index-write"))]
+const CHURN_COMMITS: usize = 1000;
+
+/// Commits of a workspace's history an index from before it moved may have been built at
+const MOVED_INDEX_COMMITS: usize = 1000;
 
 /// High-level workspace for indexing and searching
 pub struct Workspace {
@@ -53,6 +62,12 @@ pub struct Workspace {
     /// Embedding cache
     #[cfg(feature = "embeddings")]
     embedding_cache: Arc<EmbeddingCache>,
+    /// Opened with `open_read_only`: nothing is written to the index directory
+    read_only: bool,
+    /// Callbacks and commands run on workspace events
+    hooks: hooks::Hooks,
+    /// Opened with `scan`: the files read into an in-memory index
+    scan: Option<ScanStats>,
 }
 
 impl Workspace {
@@ -68,29 +83,158 @@ impl Workspace {
     }
 
     /// Create or open a workspace for indexing
+    #[cfg(feature = "index-write")]
     pub fn create(root: &Path) -> Result<Self> {
         let config = Config::load();
         Self::open_internal(root, config, true)
     }
 
     /// Create or open a workspace with custom config for indexing
+    #[cfg(feature = "index-write")]
     pub fn create_with_config(root: &Path, config: Config) -> Result<Self> {
         Self::open_internal(root, config, true)
     }
 
-    /// Open or create a workspace with custom config
-    /// If create is false, returns an error if the index doesn't exist
-    fn open_internal(root: &Path, config: Config, create: bool) -> Result<Self> {
+    /// Create or open an index in `index_path` instead of the data directory, e.g. a CI
+    /// artifact (`ygrep index --output`). Documents store paths relative to the root, so
+    /// the directory can be moved and then searched with `open_read_only`.
+    #[cfg(feature = "index-write")]
+    pub fn create_in(root: &Path, index_path: &Path, config: Config) -> Result<Self> {
+        let root = std::fs::canonicalize(root)?;
+        Self::open_in(root, index_path.to_path_buf(), config, true)
+    }
+
+    /// Open an index directory built with `create_in` for searching only, wherever it
+    /// was built; hits resolve against `root`, the checkout being searched. Saved result
+    /// sets and feedback aren't written to it.
+    pub fn open_read_only(index_path: &Path, root: &Path, config: Config) -> Result<Self> {
+        if !index_path.join("workspace.json").exists() {
+            return Err(YgrepError::WorkspaceNotIndexed(index_path.to_path_buf()));
+        }
         let root = std::fs::canonicalize(root)?;
+        let mut workspace = Self::open_in(root, index_path.to_path_buf(), config, false)?;
+        workspace.read_only = true;
+        Ok(workspace)
+    }
 
-        // Calculate index directory path based on workspace path hash
-        let workspace_hash = hash_path(&root);
-        let index_path = config
+    /// Directory holding a workspace's index (whether or not it exists yet)
+    pub fn index_dir(root: &Path, config: &Config) -> Result<std::path::PathBuf> {
+        // Based on the workspace path hash
+        let root = std::fs::canonicalize(root)?;
+        Ok(config
             .indexer
             .data_dir
             .join("indexes")
-            .join(&workspace_hash);
+            .join(hash_path(&root)))
+    }
+
+    /// Directory holding the snapshots of a workspace's index (`storage::snapshot`)
+    pub fn snapshots_dir(root: &Path, config: &Config) -> Result<std::path::PathBuf> {
+        let root = std::fs::canonicalize(root)?;
+        Ok(config
+            .indexer
+            .data_dir
+            .join("snapshots")
+            .join(hash_path(&root)))
+    }
+
+    /// The index this workspace had before it was moved or renamed, when it has none: an
+    /// index whose workspace directory no longer exists and that was built at a commit in
+    /// this workspace's history (outside git, of a directory with the same name). Indexes
+    /// of a directory with the same name win, then the most recently updated.
+    pub fn find_moved_index(root: &Path, config: &Config) -> Result<Option<MovedIndex>> {
+        let root = std::fs::canonicalize(root)?;
+        let own = Self::index_dir(&root, config)?;
+        if own.join("workspace.json").exists() {
+            return Ok(None);
+        }
+        let Ok(entries) = std::fs::read_dir(config.indexer.data_dir.join("indexes")) else {
+            return Ok(None);
+        };
+        let commits = fs::recent_commits(&root, MOVED_INDEX_COMMITS);
+
+        let mut found: Option<((bool, String), MovedIndex)> = None;
+        for entry in entries.flatten() {
+            let index_path = entry.path();
+            let Some(metadata) = std::fs::read_to_string(index_path.join("workspace.json"))
+                .ok()
+                .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+            else {
+                continue;
+            };
+            let Some(old_root) = metadata["workspace"].as_str().map(std::path::PathBuf::from)
+            else {
+                continue;
+            };
+            if old_root.exists() || index_path == own {
+                continue;
+            }
+            let same_name = old_root.file_name() == root.file_name();
+            let matches = match metadata["fingerprint"]["head"].as_str() {
+                Some(head) => commits.iter().any(|commit| commit == head),
+                None => commits.is_empty() && same_name,
+            };
+            if !matches {
+                continue;
+            }
+            let indexed_at = metadata["indexed_at"].as_str().unwrap_or_default();
+            let moved = MovedIndex {
+                index_path,
+                old_root,
+                indexed_at: chrono::DateTime::parse_from_rfc3339(indexed_at)
+                    .ok()
+                    .map(|t| t.with_timezone(&chrono::Utc)),
+            };
+            let rank = (same_name, indexed_at.to_string());
+            if found.as_ref().is_none_or(|(best, _)| rank > *best) {
+                found = Some((rank, moved));
+            }
+        }
+        Ok(found.map(|(_, moved)| moved))
+    }
+
+    /// Point the metadata of an index synced from storage at this checkout of the
+    /// workspace instead of the one it was built in
+    fn relocate_metadata(index_path: &Path, root: &Path) -> Result<()> {
+        let metadata_path = index_path.join("workspace.json");
+        let mut metadata: serde_json::Value = std::fs::read_to_string(&metadata_path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_else(|| serde_json::json!({}));
+        metadata["workspace"] = root.to_string_lossy().into_owned().into();
+        let json = serde_json::to_string_pretty(&metadata)
+            .map_err(|e| YgrepError::Config(format!("Failed to save workspace metadata: {}", e)))?;
+        std::fs::write(metadata_path, json)?;
+        Ok(())
+    }
+
+    /// Open or create a workspace with custom config
+    /// If create is false, returns an error if the index doesn't exist
+    fn open_internal(root: &Path, config: Config, create: bool) -> Result<Self> {
+        let root = std::fs::canonicalize(root)?;
+        let index_path = Self::index_dir(&root, &config)?;
+
+        // A published index is synced before searching; indexing here makes it a local one
+        if create {
+            storage::detach(&index_path)?;
+        } else if let Some(url) = &config.indexer.remote_index {
+            let max_age = std::time::Duration::from_secs(config.indexer.remote_refresh_secs);
+            if let Some(stats) = storage::sync_if_stale(url, &index_path, max_age)? {
+                tracing::debug!("Synced {} files from {}", stats.copied, url);
+                Self::relocate_metadata(&index_path, &root)?;
+            }
+        }
+
+        Self::open_in(root, index_path, config, create)
+    }
 
+    /// Open or create the index in `index_path` for the (canonical) workspace `root`
+    fn open_in(
+        root: std::path::PathBuf,
+        index_path: std::path::PathBuf,
+        config: Config,
+        create: bool,
+    ) -> Result<Self> {
         // Check if workspace has been properly indexed (workspace.json is written after indexing)
         let workspace_indexed = index_path.join("workspace.json").exists();
         // Check if Tantivy files exist (meta.json is created by Tantivy)
@@ -98,16 +242,22 @@ impl Workspace {
 
         // If not creating and workspace not indexed, return error
         if !create && !workspace_indexed {
-            return Err(YgrepError::Config(format!(
-                "Workspace not indexed: {}",
-                root.display()
-            )));
+            return Err(YgrepError::WorkspaceNotIndexed(root));
         }
 
         // Open or create Tantivy index
         let schema = index::build_document_schema();
         let index = if tantivy_exists {
-            Index::open_in_dir(&index_path)?
+            let index = Index::open_in_dir(&index_path)?;
+            // Indexes from before a schema change lack some fields
+            let existing = index.schema();
+            if schema
+                .fields()
+                .any(|(_, entry)| existing.get_field(entry.name()).is_err())
+            {
+                return Err(YgrepError::IndexOutdated(root));
+            }
+            index
         } else {
             // Create directory only when explicitly creating the index
             std::fs::create_dir_all(&index_path)?;
@@ -117,27 +267,93 @@ impl Workspace {
         // Register our custom code tokenizer
         index::register_tokenizers(index.tokenizers());
 
+        Self::with_index(root, index_path, config, index)
+    }
+
+    /// Search a workspace that has no index by reading its files (`search.fallback_scan`):
+    /// up to `search.scan_max_files` files are indexed in memory, so searches match them
+    /// the way they match an index on disk. Nothing is written to the data directory.
+    #[cfg(feature = "index-write")]
+    pub fn scan(root: &Path, config: Config) -> Result<Self> {
+        let root = std::fs::canonicalize(root)?;
+        let index = Index::create_in_ram(index::build_document_schema());
+        index::register_tokenizers(index.tokenizers());
+
+        let indexer = index::Indexer::new(config.indexer.clone(), index.clone(), &root)?;
+        let mut walker = fs::FileWalker::new(root.clone(), config.indexer.clone())?;
+        let max_files = config.search.scan_max_files;
+        let mut stats = ScanStats::default();
+        for entry in walker.walk() {
+            if stats.files >= max_files {
+                stats.limited = true;
+                break;
+            }
+            match indexer.index_file(&entry.path) {
+                Ok(_) => stats.files += 1,
+                Err(e) => tracing::debug!("Not scanned: {}: {}", entry.path.display(), e),
+            }
+        }
+        indexer.commit()?;
+
+        // (the semantic index stays empty; only its directory is created, in the temp dir)
+        let index_path = std::env::temp_dir().join("ygrep-scan");
+        let mut workspace = Self::with_index(root, index_path, config, index)?;
+        workspace.read_only = true;
+        workspace.scan = Some(stats);
+        Ok(workspace)
+    }
+
+    /// A workspace searching `index`
+    fn with_index(
+        root: std::path::PathBuf,
+        index_path: std::path::PathBuf,
+        config: Config,
+        index: Index,
+    ) -> Result<Self> {
         #[cfg(feature = "embeddings")]
         let (vector_index, embedding_model, embedding_cache) = {
+            // An existing semantic index keeps the model its vectors were built with
+            let stored_model = std::fs::read_to_string(index_path.join("workspace.json"))
+                .ok()
+                .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+                .and_then(|metadata| Some(metadata.get("embedding_model")?.as_str()?.to_string()));
+            let model_type: ModelType = stored_model
+                .as_deref()
+                .unwrap_or(&config.indexer.embedding_model)
+                .parse()?;
+
             // Create vector index path
             let vector_path = index_path.join("vectors");
 
             // Load or create vector index
             let vector_index = if VectorIndex::exists(&vector_path) {
-                Arc::new(VectorIndex::load(vector_path)?)
+                Arc::new(VectorIndex::load_with_mmap(
+                    vector_path,
+                    config.indexer.mmap_vectors,
+                )?)
             } else {
-                Arc::new(VectorIndex::new(vector_path, EMBEDDING_DIM)?)
+                Arc::new(VectorIndex::with_shards(
+                    vector_path,
+                    model_type.dimension(),
+                    config.indexer.vector_shards,
+                    config.indexer.vector_distance,
+                )?)
             };
 
             // Create embedding model (lazy-loaded on first use)
-            let embedding_model = Arc::new(EmbeddingModel::default()); // Uses all-MiniLM-L6-v2
+            let embedding_model = Arc::new(
+                EmbeddingModel::new(model_type).with_cache_dir(layout::models_dir(&config.indexer)),
+            );
 
-            // Create embedding cache (100MB cache, 384 dimensions)
-            let embedding_cache = Arc::new(EmbeddingCache::new(100, EMBEDDING_DIM));
+            let embedding_cache = Arc::new(EmbeddingCache::new(
+                config.indexer.embedding_cache_mb,
+                model_type.dimension(),
+            ));
 
             (vector_index, embedding_model, embedding_cache)
         };
 
+        let hooks = hooks::Hooks::new(config.hooks.clone());
         Ok(Self {
             root,
             config,
@@ -149,112 +365,325 @@ impl Workspace {
             embedding_model,
             #[cfg(feature = "embeddings")]
             embedding_cache,
+            read_only: false,
+            hooks,
+            scan: None,
         })
     }
 
     /// Index all files in the workspace (text-only by default, fast)
+    #[cfg(feature = "index-write")]
     pub fn index_all(&self) -> Result<IndexStats> {
         self.index_all_with_options(false)
     }
 
     /// Index all files with options
-    #[allow(unused_variables)]
+    #[cfg(feature = "index-write")]
     pub fn index_all_with_options(&self, with_embeddings: bool) -> Result<IndexStats> {
-        // Clear vector index for fresh re-index
+        self.index_all_with_progress(with_embeddings, &index::ProgressBarOutput::default())
+    }
+
+    /// Index all files, reporting progress to `progress` instead of the stderr progress bar
+    #[cfg(feature = "index-write")]
+    #[allow(unused_variables)]
+    pub fn index_all_with_progress(
+        &self,
+        with_embeddings: bool,
+        progress: &dyn index::IndexProgress,
+    ) -> Result<IndexStats> {
+        use index::checkpoint::{IndexCheckpoint, CHECKPOINT_INTERVAL};
+        use index::{IndexPhase, ProgressEvent};
+
+        // An interrupted run left a checkpoint: keep what it committed and embedded
+        let mut checkpoint = IndexCheckpoint::load(&self.index_path).unwrap_or_default();
+        if !with_embeddings {
+            checkpoint.embedded.clear();
+        }
+        if !checkpoint.is_empty() {
+            progress.report(ProgressEvent::Resume {
+                indexed: checkpoint.committed.len(),
+                embedded: checkpoint.embedded.len(),
+            });
+        }
+        let run_started = std::time::Instant::now();
+        let mut last_checkpoint = run_started;
+
+        // Files that didn't change since the last index are kept rather than read again,
+        // along with their embeddings when that index was semantic too
+        let mut previous = self.previous_files()?;
+        let reuse_vectors =
+            with_embeddings && previous.is_some() && self.stored_semantic_flag() == Some(true);
+        // (a changed shard count or distance takes a fresh vector index)
         #[cfg(feature = "embeddings")]
-        self.vector_index.clear();
+        let reuse_vectors = reuse_vectors
+            && self.vector_index.shard_count() == self.config.indexer.vector_shards.max(1)
+            && self.vector_index.distance() == self.config.indexer.vector_distance;
 
-        // Phase 1: Index all files with BM25 (fast)
-        let indexer =
-            index::Indexer::new(self.config.indexer.clone(), self.index.clone(), &self.root)?;
+        // Clear vector index for fresh re-index (picking up the configured shard count and
+        // distance), unless resuming with the embeddings saved so far
+        #[cfg(feature = "embeddings")]
+        if checkpoint.embedded.is_empty() && !reuse_vectors {
+            self.vector_index.reset(
+                self.config.indexer.vector_shards,
+                self.config.indexer.vector_distance,
+            );
+        }
 
+        // Uses the configured hidden-file setting, which is then stored for later updates
         let mut walker = fs::FileWalker::new(self.root.clone(), self.config.indexer.clone())?;
 
         let mut indexed = 0;
+        let mut unchanged = 0;
         let mut skipped = 0;
         let mut errors = 0;
+        let memory = index::MemoryBudget::from_config(&self.config.indexer);
+        let throttled = self.config.indexer.background_priority;
+        if throttled {
+            index::throttle::enter_background_mode();
+        }
 
         // Collect content for batch embedding
         #[cfg(feature = "embeddings")]
-        let mut embedding_batch: Vec<(String, String)> = Vec::new(); // (doc_id, content)
-                                                                     // Larger batch size = more efficient SIMD/vectorization in ONNX Runtime
+        let mut embedding_batch: Vec<(String, String, String)> = Vec::new(); // (doc_id, path, content)
+                                                                             // Larger batch size = more efficient SIMD/vectorization in ONNX Runtime
         #[cfg(feature = "embeddings")]
         const BATCH_SIZE: usize = 64;
+        // Very short content (< 50 chars) and very long content (> 50KB) don't embed well
+        // or are too slow
+        #[cfg(feature = "embeddings")]
+        const EMBED_MIN_LEN: usize = 50;
+        #[cfg(feature = "embeddings")]
+        const EMBED_MAX_LEN: usize = 50_000;
+        // Truncate to ~4KB for embedding - sufficient context for code, faster tokenization
+        #[cfg(feature = "embeddings")]
+        const EMBED_TRUNCATE: usize = 4096;
+
+        let entries: Vec<fs::WalkEntry> = walker.walk().collect();
+        let total = entries.len() as u64;
+
+        // How many files import each one, for the ranking prior; kept for later updates
+        // (and from the last full index when indexing incrementally)
+        let references = if previous.is_some() {
+            index::references::load(&self.index_path)
+        } else if self.config.indexer.reference_counts {
+            let paths: Vec<String> = entries
+                .iter()
+                .filter(|entry| entry.path.starts_with(&self.root))
+                .map(|entry| fs::relative_path(&entry.path, &self.root))
+                .collect();
+            let counts = index::references::ReferenceScanner::new().count(&self.root, &paths);
+            index::references::save(&self.index_path, Some(&counts))?;
+            counts
+        } else {
+            index::references::save(&self.index_path, None)?;
+            Default::default()
+        };
 
-        for entry in walker.walk() {
-            match indexer.index_file(&entry.path) {
+        // Phase 1: Index all files with BM25 (fast)
+        let indexer =
+            index::Indexer::new(self.config.indexer.clone(), self.index.clone(), &self.root)?
+                .with_references(references);
+        progress.report(ProgressEvent::Phase {
+            phase: IndexPhase::Text,
+            total,
+        });
+
+        // Paths still in the workspace, to drop the documents of every other one afterwards
+        let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+        for entry in entries {
+            let relative = fs::relative_path(&entry.path, &self.root);
+            let metadata = std::fs::metadata(&entry.path).ok();
+            let committed = metadata
+                .as_ref()
+                .and_then(|m| checkpoint.committed_doc_id(&relative, m))
+                .map(str::to_string);
+
+            // A file of the last index is kept if it looks the same, or reads the same
+            let indexed_before = previous.as_mut().and_then(|files| files.take(&relative));
+            let was_indexed = indexed_before.is_some();
+            let kept = indexed_before.filter(|file| {
+                let looks_unchanged = match (&previous, &metadata) {
+                    (Some(files), Some(metadata)) => files.looks_unchanged(file, metadata),
+                    _ => false,
+                };
+                looks_unchanged
+                    || indexer
+                        .doc_id_of(&entry.path)
+                        .is_ok_and(|doc_id| doc_id == file.doc_id)
+            });
+            let is_kept = committed.is_none() && kept.is_some();
+
+            let started = std::time::Instant::now();
+            let result = match (committed, kept) {
+                (Some(doc_id), _) => Ok(doc_id),
+                (None, Some(file)) => {
+                    unchanged += 1;
+                    Ok(file.doc_id)
+                }
+                (None, None) => indexer.index_file(&entry.path).inspect(|doc_id| {
+                    if let Some(metadata) = &metadata {
+                        checkpoint.record_committed(&relative, doc_id, metadata);
+                    }
+                }),
+            };
+            if throttled {
+                index::throttle::pause_after(started.elapsed());
+            }
+            progress.report(ProgressEvent::Progress {
+                phase: IndexPhase::Text,
+                done: (indexed + skipped + errors + 1) as u64,
+                total,
+                path: Some(relative.clone()),
+            });
+            match result {
                 Ok(doc_id) => {
                     indexed += 1;
-                    if indexed % 500 == 0 {
-                        eprint!("\r  Indexed {} files...          ", indexed);
-                    }
+                    seen.insert(relative.clone());
 
-                    // Collect for embedding if enabled
+                    // Collect for embedding if enabled (kept files keep their embeddings)
                     #[cfg(feature = "embeddings")]
-                    if with_embeddings {
-                        if let Ok(content) = std::fs::read_to_string(&entry.path) {
-                            embedding_batch.push((doc_id, content));
+                    if with_embeddings && !(reuse_vectors && is_kept) {
+                        let content = self.indexed_text(&entry.path).ok();
+                        // Files chunked along their definitions are embedded a definition
+                        // at a time (`syntax_chunks`)
+                        let symbols = content.as_deref().map_or_else(Vec::new, |content| {
+                            indexer.symbol_chunks(&entry.path, &doc_id, content)
+                        });
+                        if !symbols.is_empty() {
+                            embedding_batch.extend(
+                                symbols
+                                    .into_iter()
+                                    .map(|(chunk_id, chunk)| (chunk_id, relative.clone(), chunk)),
+                            );
+                        } else {
+                            // Under a memory cap only the part that gets embedded is kept
+                            let content = match content {
+                                Some(content)
+                                    if memory.is_some() && content.len() > EMBED_MAX_LEN =>
+                                {
+                                    None
+                                }
+                                Some(mut content) if memory.is_some() => {
+                                    content.truncate(content.floor_char_boundary(EMBED_TRUNCATE));
+                                    Some(content)
+                                }
+                                content => content,
+                            };
+                            if let Some(content) = content {
+                                embedding_batch.push((doc_id, relative, content));
+                            }
                         }
                     }
                     #[cfg(not(feature = "embeddings"))]
-                    let _ = doc_id;
+                    let _ = (doc_id, is_kept);
                 }
-                Err(YgrepError::FileTooLarge { .. }) => {
+                Err(YgrepError::FileTooLarge { .. } | YgrepError::FileExcluded { .. }) => {
+                    // (the version indexed before it grew too large is dropped)
+                    if was_indexed {
+                        indexer.delete_by_path(&relative)?;
+                    }
                     skipped += 1;
                 }
                 Err(e) => {
+                    // (the file is still there, so whatever was indexed of it stays)
                     tracing::debug!("Error indexing {}: {}", entry.path.display(), e);
+                    seen.insert(relative);
                     errors += 1;
                 }
             }
+
+            // Near the memory cap, flush the documents buffered in the writer early
+            let seen = indexed + skipped + errors;
+            let flush = seen % 100 == 0 && memory.is_some_and(|m| m.is_exceeded());
+            if flush || last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+                indexer.commit()?;
+                checkpoint.save(&self.index_path)?;
+                last_checkpoint = std::time::Instant::now();
+            }
         }
 
-        eprintln!("\r  Indexed {} files.              ", indexed);
+        // Files indexed before that are gone (deleted, or now ignored or skipped), whether
+        // by the last index or by any before it
         indexer.commit()?;
+        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
+        let removed: Vec<String> = searcher
+            .indexed_paths()?
+            .into_iter()
+            .filter(|path| !seen.contains(path))
+            .collect();
+        for path in &removed {
+            indexer.delete_by_path(path)?;
+        }
+
+        progress.report(ProgressEvent::PhaseDone {
+            phase: IndexPhase::Text,
+            done: indexed as u64,
+        });
+        indexer.commit()?;
+        checkpoint.save(&self.index_path)?;
 
         // Track embedded count
+        #[allow(unused_mut)]
         let mut total_embedded = 0usize;
+        #[allow(unused_mut)]
+        let mut budget_excluded: Vec<String> = Vec::new();
 
         // Phase 2: Generate embeddings in batches (if enabled)
         #[cfg(feature = "embeddings")]
         if with_embeddings && !embedding_batch.is_empty() {
-            // Filter out very short content (< 50 chars) and very long content (> 50KB)
-            // These don't embed well or are too slow
-            let filtered_batch: Vec<_> = embedding_batch
+            let mut filtered_batch: Vec<_> = embedding_batch
                 .into_iter()
-                .filter(|(_, content)| {
-                    let len = content.len();
-                    len >= 50 && len <= 50_000
-                })
+                .filter(|(_, _, content)| (EMBED_MIN_LEN..=EMBED_MAX_LEN).contains(&content.len()))
                 .collect();
 
+            // Leave the least valuable files out if the embeddings would exceed the budget
+            if let Some(max_size) = self.config.indexer.max_index_size {
+                // (reported in the stats)
+                budget_excluded = self.apply_index_budget(&mut filtered_batch, max_size)?;
+            }
+
+            // Embeddings saved before an interruption are kept
+            let candidates = filtered_batch.len();
+            filtered_batch.retain(|(doc_id, _, _)| !checkpoint.embedded.contains(doc_id));
+            total_embedded = candidates - filtered_batch.len();
+
             if filtered_batch.is_empty() {
-                eprintln!("No documents suitable for semantic indexing.");
+                if total_embedded == 0 {
+                    eprintln!("No documents suitable for semantic indexing.");
+                }
             } else {
-                use indicatif::{ProgressBar, ProgressStyle};
-
                 let total_docs = filtered_batch.len() as u64;
-                eprintln!("Building semantic index for {} documents...", total_docs);
 
-                // Pre-load the semantic model before starting progress bar
+                // Pre-load the semantic model before reporting progress
                 self.embedding_model.preload()?;
 
-                let pb = ProgressBar::new(total_docs);
-                pb.set_style(
-                    ProgressStyle::default_bar()
-                        .template("  [{bar:40.cyan/blue}] {pos}/{len} ({percent}%)")
-                        .unwrap()
-                        .progress_chars("━╸─"),
-                );
-                pb.enable_steady_tick(std::time::Duration::from_millis(100));
-
-                for chunk in filtered_batch.chunks(BATCH_SIZE) {
-                    // Truncate to ~4KB for embedding - sufficient context for code, faster tokenization
+                progress.report(ProgressEvent::Phase {
+                    phase: IndexPhase::Embed,
+                    total: total_docs,
+                });
+                let mut processed = 0u64;
+
+                // Batches are smaller when throttled and shrink while memory is near the cap
+                let max_batch_size = if throttled {
+                    index::throttle::EMBEDDING_BATCH_SIZE
+                } else {
+                    BATCH_SIZE
+                };
+                let mut batch_size = max_batch_size;
+                let mut start = 0;
+                while start < filtered_batch.len() {
+                    let end = (start + batch_size).min(filtered_batch.len());
+                    let chunk = &filtered_batch[start..end];
+                    start = end;
+                    if let Some(memory) = memory {
+                        batch_size = memory.adjust_batch_size(batch_size, max_batch_size);
+                    }
+                    let started = std::time::Instant::now();
+
                     // Use floor_char_boundary to avoid slicing in the middle of multi-byte UTF-8 characters
-                    const EMBED_TRUNCATE: usize = 4096;
                     let texts: Vec<&str> = chunk
                         .iter()
-                        .map(|(_, content)| {
+                        .map(|(_, _, content)| {
                             if content.len() > EMBED_TRUNCATE {
                                 let boundary = content.floor_char_boundary(EMBED_TRUNCATE);
                                 &content[..boundary]
@@ -266,7 +695,7 @@ impl Workspace {
 
                     match self.embedding_model.embed_batch(&texts) {
                         Ok(embeddings) => {
-                            for ((doc_id, _), embedding) in chunk.iter().zip(embeddings) {
+                            for ((doc_id, _, _), embedding) in chunk.iter().zip(embeddings) {
                                 if let Err(e) = self.vector_index.insert(doc_id, &embedding) {
                                     tracing::debug!(
                                         "Failed to insert embedding for {}: {}",
@@ -276,17 +705,36 @@ impl Workspace {
                                 }
                             }
                             total_embedded += chunk.len();
-                            pb.set_position(total_embedded as u64);
+
+                            checkpoint
+                                .embedded
+                                .extend(chunk.iter().map(|(doc_id, _, _)| doc_id.clone()));
+                            if last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+                                self.vector_index.save()?;
+                                checkpoint.save(&self.index_path)?;
+                                last_checkpoint = std::time::Instant::now();
+                            }
                         }
                         Err(e) => {
                             tracing::warn!("Batch embedding failed: {}", e);
-                            pb.inc(chunk.len() as u64);
                         }
                     }
+                    processed += chunk.len() as u64;
+                    progress.report(ProgressEvent::Progress {
+                        phase: IndexPhase::Embed,
+                        done: processed,
+                        total: total_docs,
+                        path: None,
+                    });
+                    if throttled {
+                        index::throttle::pause_after(started.elapsed());
+                    }
                 }
 
-                pb.finish_and_clear();
-                eprintln!("  Indexed {} documents.", total_embedded);
+                progress.report(ProgressEvent::PhaseDone {
+                    phase: IndexPhase::Embed,
+                    done: total_embedded as u64,
+                });
                 self.vector_index.save()?;
             }
         }
@@ -296,14 +744,32 @@ impl Workspace {
             eprintln!("Warning: Semantic search feature not available in this build.");
         }
 
+        // Embeddings of changed and removed files
+        if reuse_vectors || !removed.is_empty() {
+            self.remove_orphan_vectors()?;
+        }
+
         let stats = walker.stats();
 
+        #[cfg(feature = "embeddings")]
+        let embedding_model = with_embeddings.then(|| self.embedding_model.id());
+        #[cfg(not(feature = "embeddings"))]
+        let embedding_model: Option<&str> = None;
+
         // Save workspace metadata for index management
+        // (the fingerprint lets `refresh` find files changed since this run)
         let metadata = serde_json::json!({
             "workspace": self.root.to_string_lossy(),
             "indexed_at": chrono::Utc::now().to_rfc3339(),
             "files_indexed": indexed,
             "semantic": with_embeddings,
+            "embedding_model": embedding_model,
+            "include_hidden": self.config.indexer.include_hidden,
+            "respect_gitignore": self.config.indexer.respect_gitignore,
+            "line_index": self.config.indexer.line_index,
+            "stemming": self.stemming_metadata()?,
+            "fingerprint": fs::WorkspaceFingerprint::compute(&self.root),
+            "budget_excluded": budget_excluded,
         });
         let metadata_path = self.index_path.join("workspace.json");
         if let Err(e) = std::fs::write(
@@ -312,131 +778,1271 @@ impl Workspace {
         ) {
             tracing::warn!("Failed to save workspace metadata: {}", e);
         }
+        IndexCheckpoint::remove(&self.index_path)?;
+
+        let entry = index::HistoryEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            files: indexed,
+            embedded: total_embedded,
+            size: index::history::index_size(&self.index_path),
+            duration_ms: run_started.elapsed().as_millis() as u64,
+            semantic: with_embeddings,
+        };
+        if let Err(e) = index::history::record(&self.index_path, entry) {
+            tracing::warn!("Failed to save index history: {}", e);
+        }
 
+        progress.report(ProgressEvent::Done {
+            indexed,
+            embedded: total_embedded,
+            skipped,
+            errors,
+        });
+        self.hooks.fire(
+            &self.root,
+            hooks::WorkspaceEvent::IndexComplete {
+                indexed,
+                embedded: total_embedded,
+                skipped,
+                errors,
+                duration_ms: run_started.elapsed().as_millis() as u64,
+            },
+        );
         Ok(IndexStats {
             indexed,
+            unchanged,
+            removed: removed.len(),
             embedded: total_embedded,
             skipped,
             errors,
             unique_paths: stats.visited_paths,
+            budget_excluded,
+            transcoded: indexer.transcoded(),
         })
     }
 
-    /// Search the workspace
-    pub fn search(&self, query: &str, limit: Option<usize>) -> Result<search::SearchResult> {
-        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
-        searcher.search(query, limit)
+    /// The files of the last full index, to index incrementally against
+    /// (`indexer.incremental`); `None` without one, or when it was built with other
+    /// line index or stemming settings
+    #[cfg(feature = "index-write")]
+    fn previous_files(&self) -> Result<Option<index::IndexedFiles>> {
+        if !self.config.indexer.incremental {
+            return Ok(None);
+        }
+        let Some(metadata) = self.read_metadata() else {
+            return Ok(None);
+        };
+        let line_index = metadata.get("line_index").and_then(|v| v.as_bool());
+        let stemming = metadata.get("stemming").unwrap_or(&serde_json::Value::Null);
+        if line_index != Some(self.config.indexer.line_index)
+            || *stemming != self.stemming_metadata()?
+        {
+            return Ok(None);
+        }
+        let Some(indexed_at) = metadata
+            .get("indexed_at")
+            .and_then(|v| v.as_str())
+            .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+        else {
+            return Ok(None);
+        };
+        let files = index::IndexedFiles::load(&self.index, indexed_at.timestamp().max(0) as u64)?;
+        Ok(Some(files))
     }
 
-    /// Search with filters
-    pub fn search_filtered(
-        &self,
-        query: &str,
-        limit: Option<usize>,
-        extensions: Option<Vec<String>>,
-        paths: Option<Vec<String>>,
-        use_regex: bool,
-    ) -> Result<search::SearchResult> {
-        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
-        let filters = search::SearchFilters { extensions, paths };
-        searcher.search_filtered(query, limit, filters, use_regex)
+    /// The `stemming` setting as stored in workspace.json
+    #[cfg(feature = "index-write")]
+    fn stemming_metadata(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::json!(index::Stemming::from_config(
+            &self.config.indexer
+        )?
+        .map(|stemming| {
+            serde_json::json!({
+                "language": stemming.language,
+                "extensions": stemming.extensions,
+            })
+        })))
     }
 
-    /// Hybrid search combining BM25 and vector search
-    #[cfg(feature = "embeddings")]
-    pub fn search_hybrid(&self, query: &str, limit: Option<usize>) -> Result<search::SearchResult> {
-        let searcher = search::HybridSearcher::new(
-            self.config.search.clone(),
-            self.index.clone(),
-            self.vector_index.clone(),
-            self.embedding_model.clone(),
-            self.embedding_cache.clone(),
-        );
-        searcher.search(query, limit)
-    }
+    /// Repair a damaged index in place (`ygrep indexes repair`): drop unreadable text
+    /// segments, recover truncated vector doc_id indexes, drop vectors of documents that
+    /// are no longer indexed, fill in missing metadata, and index the files whose
+    /// documents or vectors were lost again
+    #[cfg(feature = "index-write")]
+    pub fn repair(root: &Path, mut config: Config) -> Result<index::RepairReport> {
+        use index::repair::{indexed_documents, salvage_text_index};
 
-    /// Check if semantic search is available (vector index has data)
-    #[cfg(feature = "embeddings")]
-    pub fn has_semantic_index(&self) -> bool {
-        !self.vector_index.is_empty()
-    }
+        let root = std::fs::canonicalize(root)?;
+        let index_path = Self::index_dir(&root, &config)?;
+        if !index_path.is_dir() {
+            return Err(YgrepError::WorkspaceNotIndexed(root));
+        }
+        let metadata: Option<serde_json::Value> =
+            std::fs::read_to_string(index_path.join("workspace.json"))
+                .ok()
+                .and_then(|s| serde_json::from_str(&s).ok());
+        // Files are indexed again with the settings of the last full index
+        if let Some(hidden) = metadata
+            .as_ref()
+            .and_then(|m| m.get("include_hidden"))
+            .and_then(|h| h.as_bool())
+        {
+            config.indexer.include_hidden = hidden;
+        }
+        if let Some(respect_gitignore) = metadata
+            .as_ref()
+            .and_then(|m| m.get("respect_gitignore"))
+            .and_then(|r| r.as_bool())
+        {
+            config.indexer.respect_gitignore = respect_gitignore;
+        }
+        if let Some(line_index) = metadata
+            .as_ref()
+            .and_then(|m| m.get("line_index"))
+            .and_then(|l| l.as_bool())
+        {
+            config.indexer.line_index = line_index;
+        }
+        if let Some(ref metadata) = metadata {
+            apply_stored_stemming(&mut config.indexer, metadata);
+        }
+        let mut report = index::RepairReport::default();
 
-    /// Check if semantic search is available (always false without embeddings feature)
-    #[cfg(not(feature = "embeddings"))]
-    pub fn has_semantic_index(&self) -> bool {
-        false
-    }
+        // Text first: files of dropped segments are indexed again right away, so the
+        // vectors of unchanged files (same content hash) still belong to a document
+        let text_lost = salvage_text_index(&index_path, &mut report)?;
+        let text_index = if index_path.join("meta.json").exists() {
+            Index::open_in_dir(&index_path)?
+        } else {
+            Index::create_in_dir(&index_path, index::build_document_schema())?
+        };
+        index::register_tokenizers(text_index.tokenizers());
+        if text_lost {
+            report.reindexed += Self::index_missing_files(&root, &config, &text_index)?;
+        }
+        let documents = indexed_documents(&text_index)?;
 
-    /// Get the workspace root
-    pub fn root(&self) -> &Path {
-        &self.root
-    }
+        // Vectors next, before the workspace loads them
+        #[cfg(feature = "embeddings")]
+        let lost: Vec<String> = {
+            let vector_path = index_path.join("vectors");
+            if vector_path.is_dir() {
+                let doc_ids = documents.keys().cloned().collect();
+                let salvage = VectorIndex::salvage(&vector_path, &doc_ids)?;
+                report.repaired.extend(salvage.repaired);
+                salvage
+                    .lost
+                    .iter()
+                    .filter_map(|doc_id| documents.get(doc_id).cloned())
+                    .collect()
+            } else {
+                Vec::new()
+            }
+        };
 
-    /// Get the index path
-    pub fn index_path(&self) -> &Path {
-        &self.index_path
-    }
+        let workspace = Self::open_internal(&root, config, true)?;
 
-    /// Check if the workspace has been indexed
-    /// (workspace.json is only created after actual indexing, not just opening)
-    pub fn is_indexed(&self) -> bool {
-        self.index_path.join("workspace.json").exists()
+        let missing: Vec<&str> = ["workspace", "indexed_at", "files_indexed", "semantic"]
+            .into_iter()
+            .filter(|key| metadata.as_ref().and_then(|m| m.get(*key)).is_none())
+            .collect();
+        if !missing.is_empty() {
+            #[cfg(feature = "embeddings")]
+            let semantic = !workspace.vector_index.is_empty();
+            #[cfg(not(feature = "embeddings"))]
+            let semantic = false;
+            workspace.update_metadata(|metadata| {
+                let values: [(&str, serde_json::Value); 4] = [
+                    ("workspace", workspace.root.to_string_lossy().into()),
+                    ("indexed_at", chrono::Utc::now().to_rfc3339().into()),
+                    ("files_indexed", documents.len().into()),
+                    ("semantic", semantic.into()),
+                ];
+                for (key, value) in values {
+                    if missing.contains(&key) {
+                        metadata[key] = value;
+                    }
+                }
+            })?;
+            report.repaired.push(format!(
+                "Filled in missing workspace.json fields: {}",
+                missing.join(", ")
+            ));
+        }
+
+        #[cfg(feature = "embeddings")]
+        if !lost.is_empty() && workspace.stored_semantic_flag() == Some(true) {
+            report.reindexed += workspace.reindex_paths(&lost)?;
+            workspace.vector_index.save()?;
+        }
+
+        Ok(report)
     }
 
-    /// Index or re-index a single file (for incremental updates)
-    /// Note: path can be under workspace root OR under a symlink target
-    pub fn index_file(&self, path: &Path) -> Result<()> {
-        // Create indexer and index the file
-        let indexer =
-            index::Indexer::new(self.config.indexer.clone(), self.index.clone(), &self.root)?;
+    /// Index the files missing from a text index (text only; used by `repair`)
+    #[cfg(feature = "index-write")]
+    fn index_missing_files(root: &Path, config: &Config, index: &Index) -> Result<usize> {
+        let indexed: std::collections::HashSet<String> = index::repair::indexed_documents(index)?
+            .into_values()
+            .collect();
+        let indexer = index::Indexer::new(config.indexer.clone(), index.clone(), root)?;
+        let mut walker = fs::FileWalker::new(root.to_path_buf(), config.indexer.clone())?;
 
-        match indexer.index_file(path) {
-            Ok(_doc_id) => {
-                indexer.commit()?;
-                tracing::debug!("Indexed: {}", path.display());
-                Ok(())
+        let mut count = 0;
+        for entry in walker.walk() {
+            let relative = fs::relative_path(&entry.path, root);
+            if indexed.contains(&relative) {
+                continue;
             }
-            Err(YgrepError::FileTooLarge { .. }) => {
-                tracing::debug!("Skipped (too large): {}", path.display());
-                Ok(())
+            match indexer.index_file(&entry.path) {
+                Ok(_) => count += 1,
+                Err(YgrepError::FileTooLarge { .. } | YgrepError::FileExcluded { .. }) => {}
+                Err(e) => tracing::debug!("Error indexing {}: {}", entry.path.display(), e),
             }
-            Err(e) => Err(e),
         }
+        indexer.commit()?;
+        Ok(count)
     }
 
-    /// Delete a file from the index (for incremental updates)
-    pub fn delete_file(&self, path: &Path) -> Result<()> {
-        use tantivy::Term;
-
-        // Get the relative path as doc_id
-        let relative_path = path
-            .strip_prefix(&self.root)
-            .unwrap_or(path)
-            .to_string_lossy();
+    /// Drop files from an embedding batch (doc_id, path, content) until their embeddings
+    /// fit within `max_size` bytes next to the text index and the vectors kept from the
+    /// last index; returns the dropped paths
+    #[cfg(all(feature = "embeddings", feature = "index-write"))]
+    fn apply_index_budget(
+        &self,
+        batch: &mut Vec<(String, String, String)>,
+        max_size: u64,
+    ) -> Result<Vec<String>> {
+        use fs::looks_generated;
+        use index::budget::{bytes_per_embedding, excluded_by_budget, EmbeddingCandidate};
+        use std::collections::HashSet;
+
+        // Everything but the new embeddings counts against the budget first: the text
+        // index and the vectors of unchanged files (those of the batch, saved before an
+        // interruption, are counted with it)
+        let dimension = self.embedding_model.dimension();
+        let batch_ids: HashSet<&str> = batch.iter().map(|(doc_id, _, _)| doc_id.as_str()).collect();
+        let kept = self
+            .vector_index
+            .count(|doc_id| !batch_ids.contains(doc_id))? as u64;
+        let used = self.text_index_size() + kept * bytes_per_embedding(dimension);
+
+        let churn = fs::file_churn(&self.root, CHURN_COMMITS).unwrap_or_default();
+        let candidates: Vec<EmbeddingCandidate> = batch
+            .iter()
+            .map(|(_, path, content)| EmbeddingCandidate {
+                path: path.clone(),
+                size: content.len() as u64,
+                generated: looks_generated(Path::new(path), content),
+                churn: churn.get(path).copied().unwrap_or(0),
+            })
+            .collect();
+
+        let excluded = excluded_by_budget(&candidates, max_size.saturating_sub(used), dimension);
+        let dropped: Vec<String> = excluded.iter().map(|&i| batch[i].1.clone()).collect();
+        let excluded: HashSet<usize> = excluded.into_iter().collect();
+        let mut position = 0;
+        batch.retain(|_| {
+            position += 1;
+            !excluded.contains(&(position - 1))
+        });
+        Ok(dropped)
+    }
 
-        let schema = self.index.schema();
-        let doc_id_field = schema
-            .get_field("doc_id")
-            .map_err(|_| YgrepError::Config("doc_id field not found in schema".to_string()))?;
+    /// Bytes on disk of the index without its vector store
+    #[cfg(all(feature = "embeddings", feature = "index-write"))]
+    fn text_index_size(&self) -> u64 {
+        walkdir::WalkDir::new(&self.index_path)
+            .into_iter()
+            .filter_entry(|e| e.file_name() != "vectors")
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter_map(|e| e.metadata().ok())
+            .map(|m| m.len())
+            .sum()
+    }
 
-        let term = Term::from_field_text(doc_id_field, &relative_path);
+    /// Re-index files changed since the last index or refresh, if it was longer ago than
+    /// `max_age`. Changes are found by comparing the git fingerprint recorded in
+    /// workspace.json; workspaces outside git are left alone.
+    ///
+    /// Returns the number of files updated or removed, or `None` if no check was needed.
+    #[cfg(feature = "index-write")]
+    pub fn refresh_if_stale(&self, max_age: std::time::Duration) -> Result<Option<usize>> {
+        // Read-only and synced indexes are updated where they were built
+        if self.read_only || storage::is_synced(&self.index_path) {
+            return Ok(None);
+        }
+        if let Some(freshness) = self.freshness() {
+            if std::time::Duration::from_secs(freshness.age_secs) < max_age {
+                return Ok(None);
+            }
+        }
+        let metadata = self.read_metadata().unwrap_or_default();
 
-        let mut writer = self.index.writer::<tantivy::TantivyDocument>(50_000_000)?;
-        writer.delete_term(term);
-        writer.commit()?;
+        let Some(current) = fs::WorkspaceFingerprint::compute(&self.root) else {
+            return Ok(None);
+        };
+        let previous: Option<fs::WorkspaceFingerprint> = metadata
+            .get("fingerprint")
+            .and_then(|v| serde_json::from_value(v.clone()).ok());
+
+        let updated = match previous.and_then(|p| current.changed_since(&self.root, &p)) {
+            Some(paths) => self.reindex_paths(&paths)?,
+            // Nothing to compare against (or history was rewritten): start over
+            None => {
+                let semantic = self.stored_semantic_flag().unwrap_or(false);
+                return Ok(Some(self.index_all_with_options(semantic)?.indexed));
+            }
+        };
 
-        tracing::debug!("Deleted from index: {}", path.display());
-        Ok(())
+        self.update_metadata(|metadata| {
+            metadata["refreshed_at"] = chrono::Utc::now().to_rfc3339().into();
+            metadata["fingerprint"] = serde_json::to_value(&current).unwrap_or_default();
+        })?;
+        Ok(Some(updated))
     }
 
-    /// Create a file watcher for this workspace
-    pub fn create_watcher(&self) -> Result<FileWatcher> {
-        FileWatcher::new(self.root.clone(), self.config.indexer.clone())
+    /// Re-index (or drop) files given relative to the workspace root, in one commit
+    #[cfg(feature = "index-write")]
+    fn reindex_paths(&self, paths: &[String]) -> Result<usize> {
+        if paths.is_empty() {
+            return Ok(0);
+        }
+
+        #[cfg(feature = "embeddings")]
+        let indexer = if self.stored_semantic_flag().unwrap_or(false) {
+            index::Indexer::with_semantic(
+                self.update_config(),
+                self.index.clone(),
+                &self.root,
+                self.vector_index.clone(),
+                self.embedding_model.clone(),
+                self.embedding_cache.clone(),
+            )?
+        } else {
+            index::Indexer::new(self.update_config(), self.index.clone(), &self.root)?
+        };
+        #[cfg(not(feature = "embeddings"))]
+        let indexer = index::Indexer::new(self.update_config(), self.index.clone(), &self.root)?;
+        // Counts from the last full index
+        let indexer = indexer.with_references(index::references::load(&self.index_path));
+        let walker = self.file_walker()?;
+
+        let mut updated = 0;
+        for relative in paths {
+            let path = self.root.join(relative);
+            // Drop the old version; deleted or newly ignored files stay dropped
+            indexer.delete_by_path(relative)?;
+            if !path.is_file() || !walker.accepts(&path) {
+                updated += 1;
+                continue;
+            }
+            match indexer.index_file(&path) {
+                Ok(_) => updated += 1,
+                Err(YgrepError::FileTooLarge { .. } | YgrepError::FileExcluded { .. }) => {}
+                Err(e) => tracing::debug!("Error indexing {}: {}", path.display(), e),
+            }
+        }
+        indexer.commit()?;
+        Ok(updated)
     }
 
-    /// Get the indexer config
-    pub fn indexer_config(&self) -> &config::IndexerConfig {
-        &self.config.indexer
+    /// Bring the index in line with changed ignore files (`.gitignore`, `.ygrepignore`):
+    /// documents of files that are now ignored are dropped, and files that `previous` (the
+    /// rules before the change, with `FileWalker::load_ignore_files` called before it)
+    /// ignored but the current rules accept are indexed
+    #[cfg(feature = "index-write")]
+    pub fn reconcile_ignored(&self, previous: &fs::FileWalker) -> Result<IgnoreChanges> {
+        let mut walker = self.file_walker()?;
+        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
+        let indexed: std::collections::HashSet<String> =
+            searcher.indexed_paths()?.into_iter().collect();
+
+        let ignored: Vec<String> = indexed
+            .iter()
+            .filter(|relative| !walker.accepts(&self.root.join(relative)))
+            .cloned()
+            .collect();
+        let root = self.root.clone();
+        let included: Vec<String> = walker
+            .walk()
+            .filter(|entry| !previous.accepts(&entry.path))
+            .filter(|entry| entry.path.starts_with(&root))
+            .map(|entry| fs::relative_path(&entry.path, &root))
+            .filter(|relative| !indexed.contains(relative))
+            .collect();
+
+        let mut paths = ignored.clone();
+        paths.extend(included.iter().cloned());
+        self.reindex_paths(&paths)?;
+        Ok(IgnoreChanges {
+            removed: ignored,
+            added: included,
+        })
+    }
+
+    /// When the index was last updated (full index or refresh), from workspace.json
+    pub fn freshness(&self) -> Option<search::IndexFreshness> {
+        let metadata = self.read_metadata()?;
+        let indexed_at = ["refreshed_at", "indexed_at"]
+            .iter()
+            .filter_map(|key| metadata.get(*key).and_then(|v| v.as_str()))
+            .filter_map(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+            .max()?;
+        let age = chrono::Utc::now().signed_duration_since(indexed_at);
+        let commit = metadata
+            .get("fingerprint")
+            .and_then(|f| f.get("head"))
+            .and_then(|head| head.as_str())
+            .map(String::from);
+
+        Some(search::IndexFreshness {
+            indexed_at: indexed_at.to_rfc3339(),
+            age_secs: age.num_seconds().max(0) as u64,
+            commit,
+        })
+    }
+
+    /// Add what consumers need besides the hits: the index age, the workspace root and
+    /// absolute paths
+    fn with_workspace_info(&self, mut result: search::SearchResult) -> search::SearchResult {
+        result.index = self.freshness();
+        result.workspace = Some(self.root.to_string_lossy().into_owned());
+        for hit in &mut result.hits {
+            hit.abs_path = Some(self.root.join(&hit.path).to_string_lossy().into_owned());
+        }
+        result
+    }
+
+    /// Complete a search's result (chunk context, workspace info) and call the search
+    /// hooks
+    fn finish_search(
+        &self,
+        query: &str,
+        result: search::SearchResult,
+    ) -> Result<search::SearchResult> {
+        let result = self.with_expanded_hits(self.with_chunk_context(result)?)?;
+        let mut result = self.with_workspace_info(result);
+        if let Some(scan) = self.scan.as_ref().filter(|scan| scan.limited) {
+            result.incomplete.get_or_insert_with(|| {
+                format!(
+                    "Scanned only the first {} files (search.scan_max_files); matches may be missing",
+                    scan.files
+                )
+            });
+        }
+        self.hooks.fire(
+            &self.root,
+            hooks::WorkspaceEvent::Search {
+                query: query.to_string(),
+                hits: result.hits.len(),
+                query_time_ms: result.query_time_ms,
+            },
+        );
+        Ok(result)
+    }
+
+    /// Call `callback` after each full index
+    pub fn on_index_complete(
+        &self,
+        callback: impl Fn(&hooks::WorkspaceEvent) + Send + Sync + 'static,
+    ) {
+        self.hooks
+            .register(hooks::HookKind::IndexComplete, callback);
+    }
+
+    /// Call `callback` after each file indexed again or removed from the index
+    pub fn on_file_indexed(
+        &self,
+        callback: impl Fn(&hooks::WorkspaceEvent) + Send + Sync + 'static,
+    ) {
+        self.hooks.register(hooks::HookKind::FileIndexed, callback);
+    }
+
+    /// Call `callback` after each search
+    pub fn on_search(&self, callback: impl Fn(&hooks::WorkspaceEvent) + Send + Sync + 'static) {
+        self.hooks.register(hooks::HookKind::Search, callback);
+    }
+
+    /// Tell chunk hits' place in their file (`search.chunk_context`)
+    fn with_chunk_context(&self, mut result: search::SearchResult) -> Result<search::SearchResult> {
+        if self.config.search.chunk_context {
+            let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
+            searcher.add_chunk_context(&mut result.hits)?;
+        }
+        Ok(result)
+    }
+
+    /// Replace hits' snippets with their lines and `search.expand_lines` more on each side
+    fn with_expanded_hits(&self, mut result: search::SearchResult) -> Result<search::SearchResult> {
+        let lines = self.config.search.expand_lines;
+        if lines > 0 {
+            let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
+            for hit in &mut result.hits {
+                if let Some(expanded) = searcher.expand_hit(&hit.expand_id(), lines, lines)? {
+                    hit.snippet = expanded.content;
+                    hit.line_start = expanded.line_start;
+                    hit.line_end = expanded.line_end;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Suggest corrected queries when a (non-regex) search found nothing
+    fn with_suggestions(
+        &self,
+        query: &str,
+        mut result: search::SearchResult,
+    ) -> Result<search::SearchResult> {
+        if result.hits.is_empty() {
+            let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
+            result.suggestions = searcher.suggest(query)?;
+        }
+        Ok(result)
+    }
+
+    /// Contents of workspace.json
+    fn read_metadata(&self) -> Option<serde_json::Value> {
+        std::fs::read_to_string(self.index_path.join("workspace.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+    }
+
+    /// Modify workspace.json in place
+    #[cfg(feature = "index-write")]
+    fn update_metadata(&self, update: impl FnOnce(&mut serde_json::Value)) -> Result<()> {
+        let mut metadata = self
+            .read_metadata()
+            .unwrap_or_else(|| serde_json::json!({}));
+        update(&mut metadata);
+        let json = serde_json::to_string_pretty(&metadata)
+            .map_err(|e| YgrepError::Config(format!("Failed to save workspace metadata: {}", e)))?;
+        std::fs::write(self.index_path.join("workspace.json"), json)?;
+        Ok(())
+    }
+
+    /// Search the workspace
+    pub fn search(&self, query: &str, limit: Option<usize>) -> Result<search::SearchResult> {
+        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone())
+            .with_stemming(self.stemming()?);
+        let result = self.with_suggestions(query, searcher.search(query, limit)?)?;
+        self.finish_search(query, result)
+    }
+
+    /// Search with filters
+    pub fn search_filtered(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+        extensions: Option<Vec<String>>,
+        paths: Option<Vec<String>>,
+        use_regex: bool,
+    ) -> Result<search::SearchResult> {
+        let filters = search::SearchFilters {
+            extensions,
+            paths,
+            ..Default::default()
+        };
+        self.search_with_filters(query, limit, filters, use_regex)
+    }
+
+    /// Search with a full set of filters
+    pub fn search_with_filters(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+        filters: search::SearchFilters,
+        use_regex: bool,
+    ) -> Result<search::SearchResult> {
+        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone())
+            .with_stemming(self.stemming()?);
+        let mut result = searcher.search_filtered(query, limit, filters, use_regex)?;
+        if !use_regex {
+            result = self.with_suggestions(query, result)?;
+        }
+        self.finish_search(query, result)
+    }
+
+    /// How a search would interpret and run a query, without running it (`hybrid` plans
+    /// hybrid search instead of a literal or regex search)
+    pub fn explain_query(
+        &self,
+        query: &str,
+        filters: search::SearchFilters,
+        use_regex: bool,
+        hybrid: bool,
+    ) -> Result<search::QueryPlan> {
+        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone())
+            .with_stemming(self.stemming()?);
+        let mut plan = searcher.plan(query, filters, use_regex, hybrid)?;
+        if hybrid && !self.load_feedback().is_empty() {
+            plan.boosts.push("relevance feedback".to_string());
+        }
+        Ok(plan)
+    }
+
+    /// Drop hits (and matched paths) that fail `filters`, typing files by what was
+    /// indexed for them (detected extension, file kind)
+    pub fn retain_matching(
+        &self,
+        result: &mut search::SearchResult,
+        filters: &search::SearchFilters,
+    ) -> Result<()> {
+        let fields = index::SchemaFields::new(&self.index.schema());
+        let searcher = self.index.reader()?.searcher();
+        let matches = |path: &str| filters.matches_indexed(path, &searcher, &fields);
+        result.hits.retain(|hit| matches(&hit.path));
+        result.matched_paths.retain(|path| matches(path));
+        Ok(())
+    }
+
+    /// File walker with this workspace's indexing rules (`accepts` tells whether a
+    /// changed file belongs in the index)
+    #[cfg(feature = "index-write")]
+    pub fn file_walker(&self) -> Result<fs::FileWalker> {
+        fs::FileWalker::new(self.root.clone(), self.update_config())
+    }
+
+    /// Indexer config for incremental updates: hidden files, ignore files and line
+    /// documents are handled the way the last full index handled them
+    fn update_config(&self) -> config::IndexerConfig {
+        let mut config = self.config.indexer.clone();
+        if let Some(include_hidden) = self.stored_hidden_flag() {
+            config.include_hidden = include_hidden;
+        }
+        if let Some(respect_gitignore) = self
+            .read_metadata()
+            .and_then(|metadata| metadata.get("respect_gitignore")?.as_bool())
+        {
+            config.respect_gitignore = respect_gitignore;
+        }
+        if let Some(line_index) = self
+            .read_metadata()
+            .and_then(|metadata| metadata.get("line_index")?.as_bool())
+        {
+            config.line_index = line_index;
+        }
+        if let Some(metadata) = self.read_metadata() {
+            apply_stored_stemming(&mut config, &metadata);
+        }
+        config
+    }
+
+    /// The text indexed for a file: only its start when it is over `max_file_size`
+    #[cfg(all(feature = "embeddings", feature = "index-write"))]
+    fn indexed_text(&self, path: &Path) -> Result<String> {
+        let oversized = std::fs::metadata(path)?.len() > self.config.indexer.max_file_size;
+        let (text, _) = match self.config.indexer.oversized_head_kb {
+            Some(head_kb) if oversized => fs::read_head(path, head_kb * 1024)?,
+            _ => fs::read_text(path)?,
+        };
+        Ok(text)
+    }
+
+    /// How prose files were stemmed by the last full index
+    fn stemming(&self) -> Result<Option<index::Stemming>> {
+        index::Stemming::from_config(&self.update_config())
+    }
+
+    /// Record whether a hit was relevant for a query; hybrid search uses the accumulated
+    /// votes as a small prior for that file. `doc_id` is a hit's `doc_id` (a path relative
+    /// to the workspace also works). Returns the file the feedback applies to.
+    pub fn record_feedback(&self, query: &str, doc_id: &str, relevant: bool) -> Result<String> {
+        let path = self.indexed_path_for(doc_id)?.ok_or_else(|| {
+            YgrepError::Search(format!("No indexed document or file '{}'", doc_id))
+        })?;
+        self.check_writable()?;
+        let mut feedback = search::FeedbackStore::load(&self.index_path)?;
+        feedback.record(query, &path, relevant);
+        feedback.save(&self.index_path)?;
+        Ok(path)
+    }
+
+    /// Recorded feedback (empty if none, or if it can't be read)
+    fn load_feedback(&self) -> search::FeedbackStore {
+        search::FeedbackStore::load(&self.index_path).unwrap_or_else(|e| {
+            tracing::debug!("Ignoring relevance feedback: {}", e);
+            search::FeedbackStore::default()
+        })
+    }
+
+    /// Path of the document with this doc_id, or the path itself if it is indexed
+    fn indexed_path_for(&self, doc_id: &str) -> Result<Option<String>> {
+        use tantivy::collector::TopDocs;
+        use tantivy::query::TermQuery;
+        use tantivy::schema::IndexRecordOption;
+
+        let fields = index::SchemaFields::new(&self.index.schema());
+        let searcher = self.index.reader()?.searcher();
+        for field in [fields.doc_id, fields.path] {
+            let term = tantivy::Term::from_field_text(field, doc_id);
+            let query = TermQuery::new(term, IndexRecordOption::Basic);
+            if let Some((_, address)) = searcher.search(&query, &TopDocs::with_limit(1))?.first() {
+                let doc: tantivy::TantivyDocument = searcher.doc(*address)?;
+                let path = doc
+                    .get_first(fields.path)
+                    .and_then(|v| tantivy::schema::Value::as_str(&v))
+                    .map(str::to_string);
+                return Ok(path);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runtime statistics: embedding cache hits, misses and evictions since the
+    /// workspace was opened
+    pub fn stats(&self) -> search::WorkspaceStats {
+        search::WorkspaceStats {
+            #[cfg(feature = "embeddings")]
+            embedding_cache: Some(self.embedding_cache.stats()),
+            #[cfg(not(feature = "embeddings"))]
+            embedding_cache: None,
+        }
+    }
+
+    /// Find indexed files by fuzzy or glob pattern
+    pub fn find_files(&self, pattern: &str, limit: usize) -> Result<search::FileMatches> {
+        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
+        search::FileMatches::find(&searcher.indexed_paths()?, pattern, limit)
+    }
+
+    /// Where a symbol is defined and used, ranked by file importance (`ygrep refs`)
+    pub fn find_references(&self, symbol: &str, limit: usize) -> Result<search::SymbolRefs> {
+        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
+        searcher.references(symbol, limit)
+    }
+
+    /// The TODO/FIXME markers of the workspace (`markers`, or `search.todo_markers` if
+    /// empty), grouped by directory or by author with at most `limit` items per group
+    /// (`ygrep todos`)
+    pub fn find_todos(
+        &self,
+        markers: &[String],
+        grouping: search::TodoGrouping,
+        limit: usize,
+    ) -> Result<search::TodoReport> {
+        let start = std::time::Instant::now();
+        let markers = if markers.is_empty() {
+            &self.config.search.todo_markers
+        } else {
+            markers
+        };
+        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
+        let mut items = searcher.todos(markers)?;
+        if grouping == search::TodoGrouping::Author {
+            // Each file is blamed once, as it is on disk
+            let mut authors: std::collections::HashMap<String, Option<Vec<String>>> =
+                Default::default();
+            for item in &mut items {
+                let lines = authors
+                    .entry(item.path.clone())
+                    .or_insert_with(|| fs::line_authors(&self.root, &item.path));
+                item.author = lines
+                    .as_ref()
+                    .and_then(|lines| lines.get(item.line as usize - 1))
+                    .cloned();
+            }
+        }
+        Ok(search::TodoReport::group(items, grouping, limit, start))
+    }
+
+    /// A file as it is indexed, by path or doc_id (a chunk's giving its whole file)
+    /// (`ygrep get`)
+    pub fn get_file(&self, id: &str) -> Result<search::IndexedFile> {
+        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
+        searcher
+            .indexed_file(id)?
+            .ok_or_else(|| YgrepError::Search(format!("No indexed document or file '{}'", id)))
+    }
+
+    /// A hit with `lines_before` and `lines_after` more lines of its file, read from the
+    /// index rather than the file: `doc_id` is a hit's, with `:L<start>-<end>` for the lines
+    /// of its snippet (`SearchHit::expand_id`) rather than of its whole document
+    pub fn expand_hit(
+        &self,
+        doc_id: &str,
+        lines_before: usize,
+        lines_after: usize,
+    ) -> Result<search::ExpandedHit> {
+        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
+        searcher
+            .expand_hit(doc_id, lines_before, lines_after)?
+            .ok_or_else(|| YgrepError::Search(format!("No indexed document '{}'", doc_id)))
+    }
+
+    /// Save the paths of a search result under a name (for refining later searches)
+    pub fn save_result_set(
+        &self,
+        name: &str,
+        query: &str,
+        result: &search::SearchResult,
+    ) -> Result<()> {
+        self.check_writable()?;
+        search::SavedResultSet::from_result(query, result).save(&self.index_path, name)
+    }
+
+    /// Fail for a workspace opened with `open_read_only`
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(YgrepError::Config(format!(
+                "The index at {} is read-only",
+                self.index_path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Load the paths of a previously saved search result
+    pub fn load_result_set(&self, name: &str) -> Result<search::SavedResultSet> {
+        search::SavedResultSet::load(&self.index_path, name)
+    }
+
+    /// Hybrid search combining BM25 and vector search, with the filters applied to both
+    #[cfg(feature = "embeddings")]
+    pub fn search_hybrid(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+        filters: search::SearchFilters,
+    ) -> Result<search::SearchResult> {
+        let searcher = search::HybridSearcher::new(
+            self.config.search.clone(),
+            self.index.clone(),
+            self.vector_index.clone(),
+            self.embedding_model.clone(),
+            self.embedding_cache.clone(),
+        )
+        .with_feedback(self.load_feedback())
+        .with_stemming(self.stemming()?);
+        let result = self.with_suggestions(query, searcher.search(query, limit, filters)?)?;
+        self.finish_search(query, result)
+    }
+
+    /// Hybrid search with a regex: regex matches fused with the vector search for the
+    /// words of the pattern (`match_type` tells which found each hit)
+    #[cfg(feature = "embeddings")]
+    pub fn search_hybrid_regex(
+        &self,
+        pattern: &str,
+        limit: Option<usize>,
+        filters: search::SearchFilters,
+    ) -> Result<search::SearchResult> {
+        let searcher = search::HybridSearcher::new(
+            self.config.search.clone(),
+            self.index.clone(),
+            self.vector_index.clone(),
+            self.embedding_model.clone(),
+            self.embedding_cache.clone(),
+        )
+        .with_feedback(self.load_feedback())
+        .with_stemming(self.stemming()?);
+        let mut result = searcher.search_regex(pattern, limit, filters)?;
+        // Only a pattern of plain words can be corrected word by word
+        if regex::escape(pattern) == pattern {
+            result = self.with_suggestions(pattern, result)?;
+        }
+        self.finish_search(pattern, result)
+    }
+
+    /// Check if semantic search is available (vector index has data)
+    #[cfg(feature = "embeddings")]
+    pub fn has_semantic_index(&self) -> bool {
+        !self.vector_index.is_empty()
+    }
+
+    /// Check if semantic search is available (always false without embeddings feature)
+    #[cfg(not(feature = "embeddings"))]
+    pub fn has_semantic_index(&self) -> bool {
+        false
+    }
+
+    /// Drop vectors whose document is no longer in the text index (deleted or changed
+    /// files, failed commits); returns how many were dropped
+    #[cfg(all(feature = "embeddings", feature = "index-write"))]
+    pub fn remove_orphan_vectors(&self) -> Result<usize> {
+        if self.vector_index.is_empty() {
+            return Ok(0);
+        }
+        let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
+        let doc_ids = searcher.indexed_doc_ids()?;
+        let dropped = self
+            .vector_index
+            .retain(|doc_id| doc_ids.contains(doc_id))?;
+        if dropped > 0 {
+            self.vector_index.save()?;
+        }
+        Ok(dropped)
+    }
+
+    /// Drop orphan vectors (there are none without the embeddings feature)
+    #[cfg(all(not(feature = "embeddings"), feature = "index-write"))]
+    pub fn remove_orphan_vectors(&self) -> Result<usize> {
+        Ok(0)
+    }
+
+    /// Merge the text index into a single segment (purging deleted documents), drop orphan
+    /// vectors and remove files no longer used
+    #[cfg(feature = "index-write")]
+    pub fn optimize(&self) -> Result<OptimizeStats> {
+        let segments = self.index.searchable_segment_ids()?;
+        let mut writer = self
+            .index
+            .writer::<tantivy::TantivyDocument>(index::memory::writer_heap(&self.config.indexer))?;
+        if segments.len() > 1 {
+            writer.merge(&segments).wait()?;
+        }
+        writer.garbage_collect_files().wait()?;
+        writer.wait_merging_threads()?;
+
+        Ok(OptimizeStats {
+            segments_before: segments.len(),
+            segments_after: self.index.searchable_segment_ids()?.len(),
+            orphan_vectors: self.remove_orphan_vectors()?,
+        })
+    }
+
+    /// Get the workspace root
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get the index path
+    pub fn index_path(&self) -> &Path {
+        &self.index_path
+    }
+
+    /// Publish the index to storage (see `storage::open_storage`) for read-only consumers
+    /// with `indexer.remote_index` set
+    pub fn publish(&self, url: &str) -> Result<storage::TransferStats> {
+        let storage = storage::open_storage(url)?;
+        storage::publish(&self.index_path, storage.as_ref())
+    }
+
+    /// Copy the index as a snapshot, to search later with `--at` (default name: when the
+    /// index was last updated)
+    pub fn take_snapshot(&self, name: Option<&str>) -> Result<storage::Snapshot> {
+        let dir = Self::snapshots_dir(&self.root, &self.config)?;
+        storage::snapshot::take(&self.index_path, &dir, name)
+    }
+
+    /// Upload the index as a bundle for its repository and the commit it was built at,
+    /// for teammates to fetch with `pull_bundle`. `repo` overrides the repository key
+    /// derived from the `origin` remote.
+    pub fn push_bundle(&self, url: &str, repo: Option<&str>) -> Result<storage::BundleInfo> {
+        let repo = Self::bundle_repository(&self.root, repo)?;
+        let commit = self
+            .read_metadata()
+            .and_then(|metadata| {
+                Some(
+                    metadata
+                        .get("fingerprint")?
+                        .get("head")?
+                        .as_str()?
+                        .to_string(),
+                )
+            })
+            .ok_or_else(|| {
+                YgrepError::Storage(
+                    "The index wasn't built in a git checkout with commits".to_string(),
+                )
+            })?;
+
+        let name = storage::bundle::bundle_name(&commit);
+        let bundle = storage::bundle::TempFile::new(&name);
+        storage::bundle::export_bundle(&self.index_path, &bundle.0)?;
+        let storage = storage::open_storage(&storage::bundle::repository_url(url, &repo))?;
+        storage.upload(&bundle.0, &name)?;
+        Ok(storage::BundleInfo {
+            size: std::fs::metadata(&bundle.0)?.len(),
+            repo,
+            commit,
+        })
+    }
+
+    /// Replace the workspace's index with the bundle pushed for HEAD, or for the newest of
+    /// the commits before it that has one, then index the files changed since
+    #[cfg(feature = "index-write")]
+    pub fn pull_bundle(
+        root: &Path,
+        config: Config,
+        url: &str,
+        repo: Option<&str>,
+    ) -> Result<(Self, storage::PulledBundle)> {
+        use storage::bundle::{bundle_name, BUNDLE_SEARCH_DEPTH};
+
+        let root = std::fs::canonicalize(root)?;
+        let repo = Self::bundle_repository(&root, repo)?;
+        let storage = storage::open_storage(&storage::bundle::repository_url(url, &repo))?;
+        let published: std::collections::HashSet<String> =
+            storage.list()?.into_iter().map(|file| file.path).collect();
+        let commits = fs::recent_commits(&root, BUNDLE_SEARCH_DEPTH + 1);
+        let Some((behind, commit)) = commits
+            .iter()
+            .enumerate()
+            .find(|(_, commit)| published.contains(&bundle_name(commit)))
+        else {
+            return Err(YgrepError::Storage(format!(
+                "No bundle of {} at HEAD or the {} commits before it in {}",
+                repo, BUNDLE_SEARCH_DEPTH, url
+            )));
+        };
+
+        let bundle = storage::bundle::TempFile::new(&bundle_name(commit));
+        storage.download(&bundle_name(commit), &bundle.0)?;
+        let index_path = Self::index_dir(&root, &config)?;
+        storage::bundle::import_bundle(&bundle.0, &index_path)?;
+        Self::relocate_metadata(&index_path, &root)?;
+
+        let workspace = Self::open_internal(&root, config, true)?;
+        let refreshed = workspace
+            .refresh_if_stale(std::time::Duration::ZERO)?
+            .unwrap_or(0);
+        let pulled = storage::PulledBundle {
+            bundle: storage::BundleInfo {
+                repo,
+                commit: commit.clone(),
+                size: std::fs::metadata(&bundle.0)?.len(),
+            },
+            behind,
+            refreshed,
+        };
+        Ok((workspace, pulled))
+    }
+
+    /// Take over the index found by `find_moved_index` instead of indexing from scratch:
+    /// move it (and its snapshots) to where this workspace's index is looked up, then
+    /// index the files changed since. Returns the workspace and the files re-indexed.
+    #[cfg(feature = "index-write")]
+    pub fn relink(root: &Path, moved: &MovedIndex, config: Config) -> Result<(Self, usize)> {
+        let root = std::fs::canonicalize(root)?;
+        let index_path = Self::index_dir(&root, &config)?;
+        if index_path.join("workspace.json").exists() {
+            return Err(YgrepError::Config(format!(
+                "{} already has an index",
+                root.display()
+            )));
+        }
+        if index_path.exists() {
+            std::fs::remove_dir_all(&index_path)?;
+        }
+        std::fs::rename(&moved.index_path, &index_path)?;
+
+        if let Some(hash) = moved.index_path.file_name() {
+            let snapshots = config.indexer.data_dir.join("snapshots");
+            let (old, new) = (snapshots.join(hash), Self::snapshots_dir(&root, &config)?);
+            if old.is_dir() && !new.exists() {
+                std::fs::rename(old, new)?;
+            }
+        }
+        Self::relocate_metadata(&index_path, &root)?;
+
+        let workspace = Self::open_internal(&root, config, true)?;
+        let refreshed = workspace
+            .refresh_if_stale(std::time::Duration::ZERO)?
+            .unwrap_or(0);
+        Ok((workspace, refreshed))
+    }
+
+    /// Bring the index up to date with a delta (see `storage::IndexDelta`) made from a
+    /// snapshot of it; nothing happens when the delta is already applied
+    #[cfg(feature = "index-write")]
+    pub fn apply_delta(&self, delta: &storage::IndexDelta) -> Result<storage::DeltaStats> {
+        self.check_writable()?;
+        let digest = storage::delta::index_digest(&self.index)?;
+        if digest == delta.target {
+            return Ok(storage::DeltaStats::default());
+        }
+        if digest != delta.base {
+            return Err(YgrepError::Storage(format!(
+                "The delta was made from a different index (built at {})",
+                delta.base_commit.as_deref().unwrap_or("an unknown commit")
+            )));
+        }
+        #[cfg(not(feature = "embeddings"))]
+        if !delta.vectors().is_empty() {
+            return Err(YgrepError::Storage(
+                "The delta has embeddings; applying it needs the embeddings feature".to_string(),
+            ));
+        }
+
+        let mut config = self.config.indexer.clone();
+        apply_stored_stemming(&mut config, &delta.metadata);
+        let stemming = index::Stemming::from_config(&config)?;
+        delta.apply_documents(
+            &self.index,
+            index::memory::writer_heap(&self.config.indexer),
+            stemming.as_ref(),
+        )?;
+
+        // Vectors of replaced documents are orphans now; those the delta carries replace
+        // any left for the same doc_id
+        #[cfg(feature = "embeddings")]
+        {
+            let searcher = search::Searcher::new(self.config.search.clone(), self.index.clone());
+            let doc_ids = searcher.indexed_doc_ids()?;
+            let replaced: std::collections::HashSet<&str> = delta
+                .vectors()
+                .iter()
+                .map(|(doc_id, _)| doc_id.as_str())
+                .collect();
+            self.vector_index
+                .retain(|doc_id| doc_ids.contains(doc_id) && !replaced.contains(doc_id))?;
+            for (doc_id, vector) in delta.vectors() {
+                self.vector_index.insert(doc_id, vector)?;
+            }
+            self.vector_index.save()?;
+        }
+
+        let root = self.root.to_string_lossy().into_owned();
+        self.update_metadata(|metadata| {
+            *metadata = delta.metadata.clone();
+            metadata["workspace"] = root.into();
+        })?;
+        Ok(delta.stats())
+    }
+
+    /// Key bundles are stored under: `repo`, else the one derived from the `origin` remote
+    fn bundle_repository(root: &Path, repo: Option<&str>) -> Result<String> {
+        match repo {
+            Some(repo) => Ok(repo.trim_matches('/').to_string()),
+            None => fs::repository_key(root).ok_or_else(|| {
+                YgrepError::Storage(format!(
+                    "{} has no `origin` remote to name its bundles by; pass a repository name",
+                    root.display()
+                ))
+            }),
+        }
+    }
+
+    /// Full index runs recorded in this workspace's index, oldest first
+    pub fn index_history(&self) -> Vec<index::HistoryEntry> {
+        index::history::load(&self.index_path)
+    }
+
+    /// Check if the workspace has been indexed
+    /// (workspace.json is only created after actual indexing, not just opening)
+    pub fn is_indexed(&self) -> bool {
+        self.index_path.join("workspace.json").exists()
+    }
+
+    /// Index or re-index a single file (for incremental updates)
+    /// Note: path can be under workspace root OR under a symlink target
+    #[cfg(feature = "index-write")]
+    pub fn index_file(&self, path: &Path) -> Result<()> {
+        self.index_file_with_options(path, false)
+    }
+
+    /// Delete a file from the index (for incremental updates)
+    #[cfg(feature = "index-write")]
+    pub fn delete_file(&self, path: &Path) -> Result<()> {
+        use tantivy::Term;
+
+        // Documents (the file's and its chunks and lines) carry its relative path
+        let relative_path = fs::relative_path(path, &self.root);
+
+        let schema = self.index.schema();
+        let path_field = schema
+            .get_field(index::schema::fields::PATH)
+            .map_err(|_| YgrepError::Config("path field not found in schema".to_string()))?;
+
+        let term = Term::from_field_text(path_field, &relative_path);
+
+        let mut writer = self
+            .index
+            .writer::<tantivy::TantivyDocument>(index::memory::writer_heap(&self.config.indexer))?;
+        writer.delete_term(term);
+        writer.commit()?;
+
+        tracing::debug!("Deleted from index: {}", path.display());
+        self.file_indexed(path, true);
+        Ok(())
+    }
+
+    /// Make the unsaved changes of an editor buffer searchable: index `content` as the file
+    /// at `path` (absolute, or relative to the workspace root) in place of its version on
+    /// disk, until the file is indexed from disk again (when the watcher sees it saved, by
+    /// `discard_content` or the next `ygrep index`). The file doesn't need to exist yet.
+    /// Returns the doc_id of the buffer's document.
+    #[cfg(feature = "index-write")]
+    pub fn index_content(&self, path: &Path, content: &str) -> Result<String> {
+        self.check_writable()?;
+        let path = self.buffer_path(path)?;
+        if !self.file_walker()?.accepts(&path) {
+            return Err(YgrepError::FileExcluded {
+                path,
+                reason: "not indexed in this workspace",
+            });
+        }
+        let indexer = index::Indexer::new(self.update_config(), self.index.clone(), &self.root)?
+            .with_references(index::references::load(&self.index_path));
+        let doc_id = indexer.index_content(&path, content)?;
+        indexer.commit()?;
+        tracing::debug!("Indexed unsaved content: {}", path.display());
+        Ok(doc_id)
+    }
+
+    /// Drop the content given to `index_content` for `path`: the file is indexed from disk
+    /// again, or dropped from the index if it doesn't exist
+    #[cfg(feature = "index-write")]
+    pub fn discard_content(&self, path: &Path) -> Result<()> {
+        self.check_writable()?;
+        let path = self.buffer_path(path)?;
+        if path.is_file() && self.file_walker()?.accepts(&path) {
+            return self.index_file(&path);
+        }
+        let indexer = index::Indexer::new(self.update_config(), self.index.clone(), &self.root)?;
+        indexer.delete_by_path(&fs::relative_path(&path, &self.root))?;
+        indexer.commit()
+    }
+
+    /// The absolute path of an editor buffer's file, which must be in the workspace
+    #[cfg(feature = "index-write")]
+    fn buffer_path(&self, path: &Path) -> Result<std::path::PathBuf> {
+        fs::within_root(path, &self.root).ok_or_else(|| YgrepError::FileExcluded {
+            path: path.to_path_buf(),
+            reason: "outside the workspace",
+        })
+    }
+
+    /// Create a file watcher for this workspace
+    #[cfg(feature = "watch")]
+    pub fn create_watcher(&self) -> Result<FileWatcher> {
+        FileWatcher::new(self.root.clone(), self.update_config())
+    }
+
+    /// What `scan` read, for a workspace searched without an index
+    pub fn scan_stats(&self) -> Option<&ScanStats> {
+        self.scan.as_ref()
+    }
+
+    /// Get the indexer config
+    pub fn indexer_config(&self) -> &config::IndexerConfig {
+        &self.config.indexer
+    }
+
+    /// Whether the last full index included hidden files (from workspace.json)
+    pub fn stored_hidden_flag(&self) -> Option<bool> {
+        self.read_metadata()?.get("include_hidden")?.as_bool()
+    }
+
+    /// Whether the semantic index was built with a different embedding model than
+    /// `indexer.embedding_model` asks for (its vectors can't be mixed with the new model's)
+    #[cfg(feature = "embeddings")]
+    pub fn embedding_model_changed(&self) -> bool {
+        self.read_metadata()
+            .and_then(|metadata| metadata.get("embedding_model")?.as_str().map(String::from))
+            .is_some_and(|stored| {
+                let configured = self.config.indexer.embedding_model.parse::<ModelType>();
+                configured.ok() != stored.parse::<ModelType>().ok()
+            })
+    }
+
+    /// Whether the embedding model changed (always false without embeddings feature)
+    #[cfg(not(feature = "embeddings"))]
+    pub fn embedding_model_changed(&self) -> bool {
+        false
     }
 
     /// Read the stored semantic flag from workspace.json metadata
@@ -449,150 +2055,1620 @@ impl Workspace {
                 .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
                 .and_then(|v| v.get("semantic").and_then(|s| s.as_bool()))
         } else {
-            None
+            None
+        }
+    }
+
+    /// Call the file hooks for a file indexed again or removed
+    #[cfg(feature = "index-write")]
+    fn file_indexed(&self, path: &Path, removed: bool) {
+        self.hooks.fire(
+            &self.root,
+            hooks::WorkspaceEvent::FileIndexed {
+                path: fs::relative_path(path, &self.root),
+                removed,
+            },
+        );
+    }
+
+    /// Index or re-index a single file with optional semantic indexing (for incremental updates)
+    #[cfg(feature = "index-write")]
+    pub fn index_file_with_options(&self, path: &Path, with_embeddings: bool) -> Result<()> {
+        let started = std::time::Instant::now();
+        let result = self.index_single_file(path, with_embeddings);
+        #[cfg(feature = "embeddings")]
+        if let Ok(documents) = &result {
+            for (doc_id, content) in documents {
+                embed_document(&self.vector_index, &self.embedding_model, doc_id, content);
+            }
+            // Save vector index after each file (incremental)
+            if !documents.is_empty() {
+                if let Err(e) = self.vector_index.save() {
+                    tracing::debug!("Failed to save vector index: {}", e);
+                }
+            }
+        }
+        if self.config.indexer.background_priority {
+            index::throttle::pause_after(started.elapsed());
+        }
+        result.map(|_| ())
+    }
+
+    /// Index a single file's text now and leave its embeddings to `queue`'s worker
+    /// (`ygrep watch` in semantic mode), so the text index doesn't wait for the model
+    #[cfg(feature = "watch")]
+    pub fn index_file_queued(&self, path: &Path, queue: &EmbeddingQueue) -> Result<()> {
+        let started = std::time::Instant::now();
+        let result = self.index_single_file(path, true);
+        if self.config.indexer.background_priority {
+            index::throttle::pause_after(started.elapsed());
+        }
+        queue.push(path, result?);
+        Ok(())
+    }
+
+    /// A queue embedding the documents of `index_file_queued` on a worker thread, with at
+    /// most `capacity` files waiting. Without the `embeddings` feature jobs are dropped.
+    #[cfg(feature = "watch")]
+    pub fn embedding_queue(&self, capacity: usize) -> EmbeddingQueue {
+        #[cfg(feature = "embeddings")]
+        {
+            let vector_index = Arc::clone(&self.vector_index);
+            let embedding_model = Arc::clone(&self.embedding_model);
+            EmbeddingQueue::new(capacity, move |path, documents| {
+                for (doc_id, content) in &documents {
+                    embed_document(&vector_index, &embedding_model, doc_id, content);
+                }
+                if let Err(e) = vector_index.save() {
+                    tracing::debug!("Failed to save vector index: {}", e);
+                }
+                tracing::debug!("Embedded: {}", path.display());
+            })
+        }
+        #[cfg(not(feature = "embeddings"))]
+        EmbeddingQueue::new(capacity, |_, _| {})
+    }
+
+    /// Index a single file's text, returning the documents to embed for it when
+    /// `with_embeddings` is set: the file, or its definitions (`syntax_chunks`)
+    #[cfg(feature = "index-write")]
+    fn index_single_file(
+        &self,
+        path: &Path,
+        with_embeddings: bool,
+    ) -> Result<Vec<(String, String)>> {
+        if self.config.indexer.background_priority {
+            index::throttle::enter_background_mode();
+        }
+
+        // Create indexer and index the file
+        let indexer = index::Indexer::new(self.update_config(), self.index.clone(), &self.root)?
+            .with_references(index::references::load(&self.index_path));
+
+        match indexer.index_file(path) {
+            Ok(doc_id) => {
+                indexer.commit()?;
+                tracing::debug!("Indexed: {}", path.display());
+                self.file_indexed(path, false);
+
+                if !with_embeddings || !cfg!(feature = "embeddings") {
+                    return Ok(Vec::new());
+                }
+                let Ok((content, _)) = fs::read_text(path) else {
+                    return Ok(Vec::new());
+                };
+                let symbols = indexer.symbol_chunks(path, &doc_id, &content);
+                if symbols.is_empty() {
+                    Ok(vec![(doc_id, content)])
+                } else {
+                    Ok(symbols)
+                }
+            }
+            Err(YgrepError::FileTooLarge { .. }) => {
+                tracing::debug!("Skipped (too large): {}", path.display());
+                Ok(Vec::new())
+            }
+            Err(YgrepError::FileExcluded { reason, .. }) => {
+                tracing::debug!("Skipped ({}): {}", reason, path.display());
+                Ok(Vec::new())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Embed one document (a file or one of its definitions) into `vector_index`
+#[cfg(feature = "embeddings")]
+fn embed_document(
+    vector_index: &VectorIndex,
+    embedding_model: &EmbeddingModel,
+    doc_id: &str,
+    content: &str,
+) {
+    // Only embed documents within size bounds
+    if !(50..=50_000).contains(&content.len()) {
+        return;
+    }
+    // Truncate for embedding
+    const EMBED_TRUNCATE: usize = 4096;
+    let text = &content[..content.floor_char_boundary(EMBED_TRUNCATE)];
+
+    match embedding_model.embed(text) {
+        Ok(embedding) => {
+            if let Err(e) = vector_index.insert(doc_id, &embedding) {
+                tracing::debug!("Failed to insert embedding for {}: {}", doc_id, e);
+            }
+        }
+        Err(e) => {
+            tracing::debug!("Failed to generate embedding for {}: {}", doc_id, e);
+        }
+    }
+}
+
+/// An index left behind by a workspace that was moved or renamed
+/// (`Workspace::find_moved_index`)
+#[derive(Debug, Clone)]
+pub struct MovedIndex {
+    pub index_path: std::path::PathBuf,
+    /// Where the workspace was when it was indexed
+    pub old_root: std::path::PathBuf,
+    pub indexed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Files `Workspace::scan` read
+#[derive(Debug, Clone, Default)]
+pub struct ScanStats {
+    /// Files read into the in-memory index
+    pub files: usize,
+    /// Files were left unread to stay within `search.scan_max_files`
+    pub limited: bool,
+}
+
+/// Statistics from an indexing operation
+#[derive(Debug, Clone, Default)]
+pub struct IndexStats {
+    pub indexed: usize,
+    /// Of the indexed files, those kept from the last index because they didn't change
+    pub unchanged: usize,
+    /// Files indexed before that were gone (or now skipped) and dropped
+    pub removed: usize,
+    pub embedded: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    pub unique_paths: usize,
+    /// Files left out of the semantic index to stay within `indexer.max_index_size`
+    pub budget_excluded: Vec<String>,
+    /// Files that weren't UTF-8 (UTF-16, Latin-1) and were transcoded
+    pub transcoded: usize,
+}
+
+/// Files `Workspace::reconcile_ignored` dropped from or added to the index, relative to
+/// the workspace root
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreChanges {
+    pub removed: Vec<String>,
+    pub added: Vec<String>,
+}
+
+/// What `Workspace::optimize` did
+#[derive(Debug, Clone, Default)]
+pub struct OptimizeStats {
+    pub segments_before: usize,
+    pub segments_after: usize,
+    /// Vectors dropped because their document is no longer indexed
+    pub orphan_vectors: usize,
+}
+
+/// Hash a path to create a unique identifier
+fn hash_path(path: &Path) -> String {
+    use xxhash_rust::xxh3::xxh3_64;
+    let hash = xxh3_64(fs::workspace_key(path).as_bytes());
+    format!("{:016x}", hash)
+}
+
+/// Stem prose files the way the last full index did (`stemming` in workspace.json)
+fn apply_stored_stemming(config: &mut config::IndexerConfig, metadata: &serde_json::Value) {
+    let Some(stored) = metadata.get("stemming") else {
+        return;
+    };
+    config.stemming = !stored.is_null();
+    if let Some(language) = stored.get("language").and_then(|l| l.as_str()) {
+        config.stem_language = language.to_string();
+    }
+    if let Some(extensions) = stored.get("extensions").and_then(|e| e.as_array()) {
+        config.stem_extensions = extensions
+            .iter()
+            .filter_map(|ext| Some(ext.as_str()?.to_string()))
+            .collect();
+    }
+}
+
+#[cfg(all(test, feature = "index-write"))]
+mod tests {
+    use super::*;
+    use fs::FileKind;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_workspace_open() -> Result<()> {
+        let temp_dir = tempdir().unwrap();
+
+        // Create a test file
+        std::fs::write(temp_dir.path().join("test.rs"), "fn main() {}").unwrap();
+
+        // Use create() to create a new workspace, then index it
+        let workspace = Workspace::create(temp_dir.path())?;
+        workspace.index_all()?;
+
+        // Now we can open the indexed workspace
+        let workspace = Workspace::open(temp_dir.path())?;
+        assert!(workspace.root().exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_workspace_index_and_search() -> Result<()> {
+        // Use a temp directory but create a subdirectory to avoid "tmp" filtering
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+
+        // Create test files
+        std::fs::write(
+            test_dir.join("hello.rs"),
+            "fn hello_world() { println!(\"Hello!\"); }",
+        )
+        .unwrap();
+        std::fs::write(
+            test_dir.join("goodbye.rs"),
+            "fn goodbye_world() { println!(\"Bye!\"); }",
+        )
+        .unwrap();
+
+        // Use create_with_config to create a new workspace
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+
+        // Index
+        let stats = workspace.index_all()?;
+        assert!(stats.indexed >= 2);
+
+        // Search (should work now since workspace is indexed)
+        let result = workspace.search("hello", None)?;
+        assert!(!result.is_empty());
+        assert!(result.hits.iter().any(|h| h.path.contains("hello")));
+
+        // Hits can be opened without knowing the workspace root
+        let root = workspace.root().to_string_lossy().into_owned();
+        assert_eq!(result.workspace.as_deref(), Some(root.as_str()));
+        let hit = &result.hits[0];
+        let abs_path = hit.abs_path.as_deref().unwrap();
+        assert_eq!(
+            std::path::Path::new(abs_path),
+            workspace.root().join(&hit.path)
+        );
+        assert!(std::path::Path::new(abs_path).is_file());
+        let json = result.format_json();
+        assert!(json.contains("\"abs_path\""));
+        assert!(json.contains("\"workspace\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_bundles() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let repo = temp_base.path().join("repo");
+        std::fs::create_dir_all(&repo).unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args([
+                    "-c",
+                    "user.name=ygrep",
+                    "-c",
+                    "user.email=ygrep@example.com",
+                ])
+                .args(args)
+                .output()
+                .is_ok_and(|output| output.status.success())
+        };
+        // Not without git
+        if !git(&["init", "-q"]) {
+            return Ok(());
+        }
+        git(&["remote", "add", "origin", "git@example.com:team/repo.git"]);
+        std::fs::write(repo.join("first.rs"), "fn first_bundle() {}").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "first"]);
+
+        let bucket = temp_base.path().join("bucket");
+        let bucket_url = bucket.to_string_lossy().into_owned();
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("pusher");
+        let workspace = Workspace::create_with_config(&repo, config)?;
+        workspace.index_all()?;
+        let pushed = workspace.push_bundle(&bucket_url, None)?;
+        assert_eq!(pushed.repo, "example.com/team/repo");
+        assert!(bucket
+            .join("example.com/team/repo")
+            .join(storage::bundle::bundle_name(&pushed.commit))
+            .is_file());
+
+        // A teammate one commit ahead gets the bundle plus the file changed since
+        std::fs::write(repo.join("second.rs"), "fn second_bundle() {}").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "second"]);
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("puller");
+        let (workspace, pulled) = Workspace::pull_bundle(&repo, config, &bucket_url, None)?;
+        assert_eq!(pulled.bundle.commit, pushed.commit);
+        assert_eq!((pulled.behind, pulled.refreshed), (1, 1));
+        assert!(!workspace.search("first_bundle", None)?.is_empty());
+        assert!(!workspace.search("second_bundle", None)?.is_empty());
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("other");
+        assert!(matches!(
+            Workspace::pull_bundle(&repo, config, &bucket_url, Some("other/repo")),
+            Err(YgrepError::Storage(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relocated_index() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(test_dir.join("artifact.rs"), "fn artifact_search() {}").unwrap();
+
+        let built = temp_base.path().join("built");
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        Workspace::create_in(&test_dir, &built, config.clone())?.index_all()?;
+        assert!(!config.indexer.data_dir.join("indexes").exists());
+
+        // Moved elsewhere, the index is still searchable against a checkout
+        let moved = temp_base.path().join("moved");
+        std::fs::rename(&built, &moved).unwrap();
+        let workspace = Workspace::open_read_only(&moved, &test_dir, config.clone())?;
+        let result = workspace.search("artifact_search", None)?;
+        assert_eq!(result.hits[0].path, "artifact.rs");
+        assert!(workspace.save_result_set("saved", "q", &result).is_err());
+        assert!(!moved.join("results").exists());
+
+        assert!(matches!(
+            Workspace::open_read_only(&built, &test_dir, config),
+            Err(YgrepError::WorkspaceNotIndexed(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_incremental_index() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(test_dir.join("kept.rs"), "fn kept_incremental() {}").unwrap();
+        std::fs::write(test_dir.join("touched.rs"), "fn touched_incremental() {}").unwrap();
+        std::fs::write(test_dir.join("changed.rs"), "fn before_incremental() {}").unwrap();
+        std::fs::write(test_dir.join("removed.rs"), "fn removed_incremental() {}").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config.clone())?;
+        let stats = workspace.index_all()?;
+        assert_eq!((stats.indexed, stats.unchanged), (4, 0));
+
+        // Rewritten with the same content: read again, but kept
+        std::fs::write(test_dir.join("touched.rs"), "fn touched_incremental() {}").unwrap();
+        std::fs::write(test_dir.join("changed.rs"), "fn after_incremental() {}").unwrap();
+        std::fs::remove_file(test_dir.join("removed.rs")).unwrap();
+        std::fs::write(test_dir.join("added.rs"), "fn added_incremental() {}").unwrap();
+        let stats = workspace.index_all()?;
+        assert_eq!((stats.indexed, stats.unchanged, stats.removed), (4, 2, 1));
+        for (query, found) in [
+            ("kept_incremental", true),
+            ("touched_incremental", true),
+            ("after_incremental", true),
+            ("added_incremental", true),
+            ("before_incremental", false),
+            ("removed_incremental", false),
+        ] {
+            assert_eq!(
+                !workspace.search(query, None)?.is_empty(),
+                found,
+                "{}",
+                query
+            );
+        }
+
+        // Without `incremental` every file is indexed again, and files that are gone
+        // are still dropped
+        config.indexer.incremental = false;
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        std::fs::remove_file(test_dir.join("added.rs")).unwrap();
+        let stats = workspace.index_all()?;
+        assert_eq!((stats.unchanged, stats.removed), (0, 1));
+        assert!(workspace.search("added_incremental", None)?.is_empty());
+
+        workspace.delete_file(&test_dir.join("kept.rs"))?;
+        assert!(workspace.search("kept_incremental", None)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_content() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(test_dir.join("buffer.rs"), "fn saved_version() {}").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        // The buffer replaces the file on disk in searches
+        workspace.index_content(Path::new("buffer.rs"), "fn unsaved_version() {}")?;
+        assert!(workspace.search("saved_version", None)?.is_empty());
+        assert!(!workspace.search("unsaved_version", None)?.is_empty());
+        // ...new files too, but not ones the workspace doesn't index
+        workspace.index_content(&test_dir.join("draft.rs"), "fn draft_buffer() {}")?;
+        assert!(!workspace.search("draft_buffer", None)?.is_empty());
+        assert!(matches!(
+            workspace.index_content(Path::new("target/out.rs"), "fn ignored() {}"),
+            Err(YgrepError::FileExcluded { .. })
+        ));
+        // ...nor ones outside of it
+        for outside in [Path::new("../outside.rs"), temp_base.path()] {
+            assert!(matches!(
+                workspace.index_content(outside, "fn outside() {}"),
+                Err(YgrepError::FileExcluded { .. })
+            ));
+        }
+
+        workspace.discard_content(Path::new("buffer.rs"))?;
+        workspace.discard_content(Path::new("draft.rs"))?;
+        assert!(!workspace.search("saved_version", None)?.is_empty());
+        assert!(workspace.search("unsaved_version", None)?.is_empty());
+        assert!(workspace.search("draft_buffer", None)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_delta() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(test_dir.join("kept.rs"), "fn kept_delta() {}").unwrap();
+        std::fs::write(test_dir.join("changed.rs"), "fn before_delta() {}").unwrap();
+        std::fs::write(test_dir.join("removed.rs"), "fn removed_delta() {}").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let build = |name: &str| -> Result<Workspace> {
+            let index_path = temp_base.path().join(name);
+            let workspace = Workspace::create_in(&test_dir, &index_path, config.clone())?;
+            workspace.index_all()?;
+            Ok(workspace)
+        };
+        let old = build("old")?;
+        let consumer = build("consumer")?;
+
+        std::fs::write(test_dir.join("changed.rs"), "fn after_delta() {}").unwrap();
+        std::fs::remove_file(test_dir.join("removed.rs")).unwrap();
+        std::fs::write(test_dir.join("added.rs"), "fn added_delta() {}").unwrap();
+        let new = build("new")?;
+        let delta = storage::IndexDelta::between(old.index_path(), new.index_path())?;
+        assert_eq!(delta.changed, vec!["added.rs", "changed.rs"]);
+        assert_eq!(delta.removed, vec!["removed.rs"]);
+
+        // Round-trips through a file and turns the old snapshot into the new one
+        let file = temp_base.path().join("delta.json.gz");
+        delta.write(&file)?;
+        let delta = storage::IndexDelta::read(&file)?;
+        assert_eq!(consumer.apply_delta(&delta)?.changed, 2);
+        for (query, found) in [
+            ("kept_delta", true),
+            ("after_delta", true),
+            ("added_delta", true),
+            ("before_delta", false),
+            ("removed_delta", false),
+        ] {
+            assert_eq!(
+                !consumer.search(query, None)?.is_empty(),
+                found,
+                "{}",
+                query
+            );
+        }
+        assert_eq!(
+            storage::delta::index_digest(&consumer.index)?,
+            storage::delta::index_digest(&new.index)?
+        );
+
+        // Applying it again does nothing; other indexes are refused
+        assert_eq!(consumer.apply_delta(&delta)?.changed, 0);
+        std::fs::write(test_dir.join("kept.rs"), "fn elsewhere() {}").unwrap();
+        let other = build("other")?;
+        assert!(matches!(
+            other.apply_delta(&delta),
+            Err(YgrepError::Storage(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_published_index() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let source = "fn hello_world() { println!(\"Hello!\"); }";
+        let ci_dir = temp_base.path().join("ci").join("repo");
+        let checkout = temp_base.path().join("checkout").join("repo");
+        for dir in [&ci_dir, &checkout] {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("hello.rs"), source).unwrap();
+        }
+        let bucket = temp_base.path().join("bucket");
+        let bucket_url = bucket.to_string_lossy().into_owned();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("ci-data");
+        let workspace = Workspace::create_with_config(&ci_dir, config)?;
+        workspace.index_all()?;
+        let stats = workspace.publish(&bucket_url)?;
+        assert!(stats.copied >= 3);
+
+        // A consumer searches the published index without indexing
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("consumer-data");
+        config.indexer.remote_index = Some(bucket_url);
+        let consumer = Workspace::open_with_config(&checkout, config)?;
+        assert!(storage::is_synced(consumer.index_path()));
+        let result = consumer.search("hello", None)?;
+        assert!(result.hits.iter().any(|h| h.path == "hello.rs"));
+        assert_eq!(
+            consumer.read_metadata().unwrap()["workspace"],
+            consumer.root().to_string_lossy().as_ref()
+        );
+        assert_eq!(consumer.refresh_if_stale(std::time::Duration::ZERO)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shebang_language_filter() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(test_dir.join("scripts")).unwrap();
+        std::fs::write(
+            test_dir.join("scripts/deploy"),
+            "#!/usr/bin/env python3\nrelease_train()\n",
+        )
+        .unwrap();
+        std::fs::write(test_dir.join("train.rs"), "fn release_train() {}").unwrap();
+        std::fs::write(test_dir.join("release.py"), "release_train()").unwrap();
+        std::fs::write(test_dir.join("Dockerfile"), "RUN release_train").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        let paths = |filters: search::SearchFilters| -> Result<Vec<String>> {
+            let result = workspace.search_with_filters("release_train", None, filters, false)?;
+            let mut paths: Vec<String> = result.hits.into_iter().map(|hit| hit.path).collect();
+            paths.sort();
+            Ok(paths)
+        };
+        let python = search::SearchFilters {
+            languages: Some(vec!["python".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(paths(python)?, vec!["release.py", "scripts/deploy"]);
+        let docker = search::SearchFilters {
+            languages: Some(vec!["dockerfile".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(paths(docker)?, vec!["Dockerfile"]);
+        let py_ext = search::SearchFilters {
+            extensions: Some(vec!["py".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(paths(py_ext)?, vec!["release.py", "scripts/deploy"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_kind_filters_and_weights() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(test_dir.join("src")).unwrap();
+        std::fs::create_dir_all(test_dir.join("tests")).unwrap();
+        std::fs::create_dir_all(test_dir.join("api")).unwrap();
+        let body = "fn session_token() { session_token_refresh() }";
+        std::fs::write(test_dir.join("src/session.rs"), body).unwrap();
+        std::fs::write(test_dir.join("tests/session.rs"), body).unwrap();
+        std::fs::write(
+            test_dir.join("api/session.rs"),
+            format!("// @generated by protoc\n{}", body),
+        )
+        .unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        let paths = |filters: search::SearchFilters| -> Result<Vec<String>> {
+            let result = workspace.search_with_filters("session_token", None, filters, false)?;
+            Ok(result.hits.into_iter().map(|hit| hit.path).collect())
+        };
+        // Tests and generated code rank below source by default
+        assert_eq!(
+            paths(search::SearchFilters::default())?,
+            vec!["src/session.rs", "tests/session.rs", "api/session.rs"]
+        );
+        let tests = search::SearchFilters {
+            kinds: Some(vec![FileKind::Test]),
+            ..Default::default()
+        };
+        assert_eq!(paths(tests)?, vec!["tests/session.rs"]);
+        let not_generated = search::SearchFilters {
+            exclude_kinds: Some(vec![FileKind::Generated]),
+            ..Default::default()
+        };
+        assert_eq!(
+            paths(not_generated.clone())?,
+            vec!["src/session.rs", "tests/session.rs"]
+        );
+
+        let mut result = workspace.search("session_token", None)?;
+        workspace.retain_matching(&mut result, &not_generated)?;
+        assert_eq!(result.hits.len(), 2);
+
+        // Or leave generated files out of the index altogether
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data-skip");
+        config.indexer.skip_generated = true;
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        let stats = workspace.index_all()?;
+        assert_eq!((stats.indexed, stats.skipped), (2, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_submodule_filter() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(test_dir.join("src")).unwrap();
+        std::fs::create_dir_all(test_dir.join("libs/proto/.git")).unwrap();
+        std::fs::write(
+            test_dir.join(".gitmodules"),
+            "[submodule \"proto\"]\n\tpath = libs/proto\n",
+        )
+        .unwrap();
+        std::fs::write(test_dir.join("src/codec.rs"), "fn decode_frame() {}").unwrap();
+        std::fs::write(test_dir.join("libs/proto/codec.rs"), "fn decode_frame() {}").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        let filters = search::SearchFilters {
+            submodules: Some(vec!["proto".to_string()]),
+            ..Default::default()
+        };
+        let result = workspace.search_with_filters("decode_frame", None, filters, false)?;
+        assert_eq!(result.hits.len(), 1);
+        assert_eq!(result.hits[0].path, "libs/proto/codec.rs");
+        assert_eq!(result.hits[0].submodule.as_deref(), Some("proto"));
+        let result = workspace.search("decode_frame", None)?;
+        assert_eq!(result.hits.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconcile_ignored() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(test_dir.join("src")).unwrap();
+        std::fs::create_dir_all(test_dir.join("generated")).unwrap();
+        std::fs::write(test_dir.join(".gitignore"), "generated/\n").unwrap();
+        std::fs::write(test_dir.join("src/main.rs"), "fn start_server() {}").unwrap();
+        std::fs::write(test_dir.join("src/fixtures.rs"), "fn load_fixtures() {}").unwrap();
+        std::fs::write(test_dir.join("generated/api.rs"), "fn api_client() {}").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        config.indexer.respect_gitignore = true;
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+        assert_eq!(workspace.search("api_client", None)?.hits.len(), 0);
+
+        let previous = workspace.file_walker()?;
+        previous.load_ignore_files();
+        std::fs::write(test_dir.join(".ygrepignore"), "src/fixtures.rs\n").unwrap();
+        std::fs::remove_file(test_dir.join(".gitignore")).unwrap();
+        let changes = workspace.reconcile_ignored(&previous)?;
+        assert_eq!(changes.removed, vec!["src/fixtures.rs"]);
+        assert_eq!(changes.added, vec!["generated/api.rs"]);
+
+        assert_eq!(workspace.search("api_client", None)?.hits.len(), 1);
+        assert_eq!(workspace.search("load_fixtures", None)?.hits.len(), 0);
+        assert_eq!(workspace.search("start_server", None)?.hits.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_notebook() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let notebook = serde_json::json!({
+            "cells": [
+                {"cell_type": "markdown", "metadata": {}, "source": ["# Quarterly revenue\n"]},
+                {
+                    "cell_type": "code",
+                    "execution_count": 1,
+                    "metadata": {},
+                    "outputs": [{"output_type": "stream", "name": "stdout", "text": ["iVBORw0KGgo_noise\n"]}],
+                    "source": ["import pandas as pd\n", "revenue = pd.read_csv('q3.csv')"]
+                }
+            ],
+            "metadata": {},
+            "nbformat": 4,
+            "nbformat_minor": 5
+        });
+        let json = serde_json::to_string_pretty(&notebook).unwrap();
+        std::fs::write(test_dir.join("analysis.ipynb"), &json).unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        // Hits point at the line of the file the cell's source line is on
+        let hit = workspace.search("read_csv", None)?.hits.remove(0);
+        let line = json.lines().position(|l| l.contains("read_csv")).unwrap() as u64 + 1;
+        assert_eq!(hit.match_line, Some(line));
+        assert!(hit.snippet.contains("revenue = pd.read_csv"));
+        assert!(!hit.snippet.contains("\"source\""));
+        // Outputs aren't indexed
+        assert!(workspace.search("iVBORw0KGgo_noise", None)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_todos() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let repo = temp_base.path().join("repo");
+        std::fs::create_dir_all(repo.join("src")).unwrap();
+        std::fs::write(
+            repo.join("src/pool.rs"),
+            "// TODO: cap connections\nfn open() {}\nlet s = \"FIXME\"; // FIXME: timeout\n",
+        )
+        .unwrap();
+        std::fs::write(repo.join("build.sh"), "# HACK until CI has rustup\n").unwrap();
+        std::fs::write(repo.join("notes.md"), "Nothing to do here\n").unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(["-c", "user.name=Ana", "-c", "user.email=ana@example.com"])
+                .args(args)
+                .output()
+                .is_ok_and(|output| output.status.success())
+        };
+        let blamed =
+            git(&["init", "-q"]) && git(&["add", "."]) && git(&["commit", "-q", "-m", "x"]);
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&repo, config)?;
+        workspace.index_all()?;
+
+        let report = workspace.find_todos(&[], search::TodoGrouping::Directory(1), 10)?;
+        assert_eq!((report.total, report.total_files), (3, 2));
+        let groups: Vec<(&str, usize)> = report
+            .groups
+            .iter()
+            .map(|group| (group.name.as_str(), group.total))
+            .collect();
+        assert_eq!(groups, vec![("src", 2), (".", 1)]);
+        // The marker in a string literal doesn't count, the comment after it does
+        assert_eq!(report.groups[0].items[1].line, 3);
+
+        let report = workspace.find_todos(&["HACK".into()], search::TodoGrouping::Author, 10)?;
+        assert_eq!(report.total, 1);
+        let author = if blamed {
+            "Ana"
+        } else {
+            search::UNKNOWN_AUTHOR
+        };
+        assert_eq!(report.groups[0].name, author);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scoped_search() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(
+            test_dir.join("jobs.rs"),
+            "fn retry() {}\n/* TODO: retry\n   with backoff */\nlog(\"retry failed\");\n",
+        )
+        .unwrap();
+        std::fs::write(test_dir.join("notes.md"), "retry later\n").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        let matches = |query: &str, regex: bool| -> Result<Vec<(String, Option<u64>)>> {
+            let result = workspace.search_with_filters(query, None, Default::default(), regex)?;
+            let mut matches: Vec<_> = result
+                .hits
+                .into_iter()
+                .map(|hit| (hit.path, hit.match_line))
+                .collect();
+            matches.sort();
+            matches.dedup();
+            Ok(matches)
+        };
+        let jobs = |line: u64| vec![("jobs.rs".to_string(), Some(line))];
+        assert_eq!(matches("retry in:comments", false)?, jobs(2));
+        assert_eq!(matches("in:strings retry", false)?, jobs(4));
+        // Markdown isn't lexed, so it has no code to match
+        assert_eq!(matches("in:code retry", false)?, jobs(1));
+        assert_eq!(matches(r"in:comment backoff\s+\*/", true)?, jobs(3));
+        assert!(matches("in:strings TODO", false)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_line_index() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let files = [
+            (
+                "a.rs",
+                "fn run() {\n    retry(3);\n    log(\"no Retry\");\n}\n",
+            ),
+            (
+                "b.rs",
+                "// retry policy\nconst MAX: u32 = 3;\nfn retry(all: bool) {}\n",
+            ),
+        ];
+        for (path, content) in files {
+            std::fs::write(test_dir.join(path), content).unwrap();
+        }
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        config.indexer.line_index = true;
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        // Every matching line with its column, as `grep -in --column` would list them
+        let positions = |result: search::SearchResult| {
+            let mut positions: Vec<_> = result
+                .hits
+                .iter()
+                .map(|hit| (hit.path.clone(), hit.match_line, hit.match_column))
+                .collect();
+            positions.sort();
+            positions
+        };
+        let mut expected = Vec::new();
+        for (path, content) in files {
+            for (i, line) in content.lines().enumerate() {
+                if let Some(column) = line.to_lowercase().find("retry") {
+                    let (line, column) = (i as u64 + 1, column as u64 + 1);
+                    expected.push((path.to_string(), Some(line), Some(column)));
+                }
+            }
+        }
+        assert_eq!(expected.len(), 4);
+        assert_eq!(positions(workspace.search("retry", None)?), expected);
+
+        // Regexes match line by line, so `^` anchors at every line
+        let result = workspace.search_with_filters(r"^\s+retry", None, Default::default(), true)?;
+        assert_eq!(
+            positions(result),
+            vec![("a.rs".to_string(), Some(2), Some(1))]
+        );
+
+        // Updates keep indexing lines the way the full index did
+        std::fs::write(test_dir.join("b.rs"), "fn none() {}\n\nfn retry() {}\n").unwrap();
+        workspace.index_file(&test_dir.join("b.rs"))?;
+        let result = workspace.search("retry", Some(10))?;
+        assert!(result
+            .hits
+            .iter()
+            .any(|hit| hit.path == "b.rs" && hit.match_line == Some(3)));
+        Ok(())
+    }
+
+    /// Progress events of an index run, in order
+    #[derive(Default)]
+    struct Events(std::cell::RefCell<Vec<index::ProgressEvent>>);
+
+    impl index::IndexProgress for Events {
+        fn report(&self, event: index::ProgressEvent) {
+            self.0.borrow_mut().push(event);
         }
     }
 
-    /// Index or re-index a single file with optional semantic indexing (for incremental updates)
-    #[allow(unused_variables)]
-    pub fn index_file_with_options(&self, path: &Path, with_embeddings: bool) -> Result<()> {
-        // Create indexer and index the file
-        let indexer =
-            index::Indexer::new(self.config.indexer.clone(), self.index.clone(), &self.root)?;
+    #[test]
+    fn test_index_progress() -> Result<()> {
+        use index::{IndexPhase, ProgressEvent};
+
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(test_dir.join("a.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(test_dir.join("b.rs"), "fn b() {}\n").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        let events = Events(Default::default());
+        workspace.index_all_with_progress(false, &events)?;
+
+        let events = events.0.into_inner();
+        assert_eq!(
+            events[0],
+            ProgressEvent::Phase {
+                phase: IndexPhase::Text,
+                total: 2
+            }
+        );
+        let paths: Vec<(u64, &str)> = events
+            .iter()
+            .filter_map(|event| match event {
+                ProgressEvent::Progress {
+                    done,
+                    total: 2,
+                    path: Some(path),
+                    ..
+                } => Some((*done, path.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[1].0, 2);
+        assert_eq!(
+            events[3..],
+            [
+                ProgressEvent::PhaseDone {
+                    phase: IndexPhase::Text,
+                    done: 2
+                },
+                ProgressEvent::Done {
+                    indexed: 2,
+                    embedded: 0,
+                    skipped: 0,
+                    errors: 0
+                }
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunk_context() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        // 120 lines: chunks of 50 lines starting every 40 (1-50, 41-90, 81-120)
+        let mut content: String = (1..100).map(|i| format!("let v{} = {};\n", i, i)).collect();
+        content.push_str("fn deep_marker() {}\n");
+        content.push_str(
+            &(101..=120)
+                .map(|i| format!("let v{} = {};\n", i, i))
+                .collect::<String>(),
+        );
+        std::fs::write(test_dir.join("long.rs"), &content).unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        config.search.chunk_context = true;
+        // (line windows, not the definitions of the `treesitter` feature)
+        config.indexer.syntax_chunks = false;
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        let result = workspace.search("deep_marker", None)?;
+        let chunk_hit = result.hits.iter().find(|hit| hit.is_chunk).unwrap();
+        let chunk = chunk_hit.chunk.as_ref().unwrap();
+        assert_eq!((chunk.number, chunk.count), (3, 3));
+        assert_eq!((chunk.line_start, chunk.line_end), (81, 120));
+        assert!(result.format_ai().contains("[chunk 3 of 3, lines 81-120]"));
+        // Whole-file hits have no chunk context
+        assert!(result
+            .hits
+            .iter()
+            .all(|hit| hit.is_chunk == hit.chunk.is_some()));
+
+        // The whole file, by path or by one of its chunks
+        let file = workspace.get_file("long.rs")?;
+        assert_eq!((file.content.as_str(), file.chunks), (content.as_str(), 3));
+        assert_eq!(workspace.get_file(&chunk_hit.doc_id)?, file);
+        assert_eq!(workspace.get_file(&chunk.parent_doc)?, file);
+        assert!(workspace.get_file("missing.rs").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_hit() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let content: String = (1..=40)
+            .map(|i| match i {
+                20 => "fn expand_marker() {}\n".to_string(),
+                i => format!("let v{} = {};\n", i, i),
+            })
+            .collect();
+        std::fs::write(test_dir.join("main.rs"), &content).unwrap();
 
-        match indexer.index_file(path) {
-            Ok(doc_id) => {
-                indexer.commit()?;
-                tracing::debug!("Indexed: {}", path.display());
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        config.search.snippet_lines = 3;
+        config.search.context_before = 1;
+        let workspace = Workspace::create_with_config(&test_dir, config.clone())?;
+        workspace.index_all()?;
 
-                // Generate embedding if semantic indexing is enabled
-                #[cfg(feature = "embeddings")]
-                if with_embeddings {
-                    if let Ok(content) = std::fs::read_to_string(path) {
-                        // Only embed files within size bounds
-                        let len = content.len();
-                        if len >= 50 && len <= 50_000 {
-                            // Truncate for embedding
-                            const EMBED_TRUNCATE: usize = 4096;
-                            let text = if content.len() > EMBED_TRUNCATE {
-                                let boundary = content.floor_char_boundary(EMBED_TRUNCATE);
-                                &content[..boundary]
-                            } else {
-                                content.as_str()
-                            };
+        let hit = &workspace.search("expand_marker", None)?.hits[0];
+        assert_eq!((hit.line_start, hit.line_end), (19, 21));
+        let expanded = workspace.expand_hit(&hit.expand_id(), 2, 4)?;
+        assert_eq!((expanded.line_start, expanded.line_end), (17, 25));
+        assert_eq!((expanded.hit_line_start, expanded.hit_line_end), (19, 21));
+        assert_eq!(expanded.path, "main.rs");
+        assert!(expanded.content.starts_with("let v17 = 17;\nlet v18"));
+        assert!(expanded.content.ends_with("let v25 = 25;"));
+        // The whole document without a line range, clamped to the file
+        let whole = workspace.expand_hit(&hit.doc_id, 5, 5)?;
+        assert_eq!((whole.line_start, whole.line_end), (1, 40));
+        assert!(workspace.expand_hit("missing", 1, 1).is_err());
+
+        // `search.expand_lines` expands every hit's snippet
+        config.search.expand_lines = 3;
+        let workspace = Workspace::open_with_config(&test_dir, config)?;
+        let hit = &workspace.search("expand_marker", None)?.hits[0];
+        assert_eq!((hit.line_start, hit.line_end), (16, 24));
+        assert_eq!(hit.snippet.lines().count(), 9);
+        Ok(())
+    }
 
-                            match self.embedding_model.embed(text) {
-                                Ok(embedding) => {
-                                    if let Err(e) = self.vector_index.insert(&doc_id, &embedding) {
-                                        tracing::debug!(
-                                            "Failed to insert embedding for {}: {}",
-                                            doc_id,
-                                            e
-                                        );
-                                    } else {
-                                        // Save vector index after each file (incremental)
-                                        if let Err(e) = self.vector_index.save() {
-                                            tracing::debug!("Failed to save vector index: {}", e);
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    tracing::debug!(
-                                        "Failed to generate embedding for {}: {}",
-                                        doc_id,
-                                        e
-                                    );
-                                }
-                            }
-                        }
-                    }
-                }
+    #[test]
+    fn test_hooks() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(test_dir.join("a.rs"), "fn retry() {}\n").unwrap();
 
-                Ok(())
-            }
-            Err(YgrepError::FileTooLarge { .. }) => {
-                tracing::debug!("Skipped (too large): {}", path.display());
-                Ok(())
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        let events = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+        for kind in [
+            hooks::HookKind::IndexComplete,
+            hooks::HookKind::FileIndexed,
+            hooks::HookKind::Search,
+        ] {
+            let events = std::sync::Arc::clone(&events);
+            let record = move |event: &hooks::WorkspaceEvent| events.lock().push(event.clone());
+            match kind {
+                hooks::HookKind::IndexComplete => workspace.on_index_complete(record),
+                hooks::HookKind::FileIndexed => workspace.on_file_indexed(record),
+                hooks::HookKind::Search => workspace.on_search(record),
             }
-            Err(e) => Err(e),
         }
-    }
-}
 
-/// Statistics from an indexing operation
-#[derive(Debug, Clone, Default)]
-pub struct IndexStats {
-    pub indexed: usize,
-    pub embedded: usize,
-    pub skipped: usize,
-    pub errors: usize,
-    pub unique_paths: usize,
-}
+        workspace.index_all()?;
+        std::fs::write(test_dir.join("b.rs"), "fn retry_all() {}\n").unwrap();
+        workspace.index_file(&test_dir.join("b.rs"))?;
+        workspace.delete_file(&test_dir.join("a.rs"))?;
+        workspace.search("retry", None)?;
+
+        let events = events.lock();
+        assert!(matches!(
+            events[0],
+            hooks::WorkspaceEvent::IndexComplete { indexed: 1, .. }
+        ));
+        assert_eq!(
+            events[1..3],
+            [
+                hooks::WorkspaceEvent::FileIndexed {
+                    path: "b.rs".to_string(),
+                    removed: false,
+                },
+                hooks::WorkspaceEvent::FileIndexed {
+                    path: "a.rs".to_string(),
+                    removed: true,
+                },
+            ]
+        );
+        assert!(matches!(
+            &events[3],
+            hooks::WorkspaceEvent::Search { query, .. } if query == "retry"
+        ));
+        assert_eq!(events.len(), 4);
+        Ok(())
+    }
 
-/// Hash a path to create a unique identifier
-fn hash_path(path: &Path) -> String {
-    use xxhash_rust::xxh3::xxh3_64;
-    let hash = xxh3_64(path.to_string_lossy().as_bytes());
-    format!("{:016x}", hash)
-}
+    #[test]
+    fn test_scan() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(test_dir.join("a.rs"), "fn retry() {}\n").unwrap();
+        std::fs::write(test_dir.join("b.py"), "def retry_all():\n    retry()\n").unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::tempdir;
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::scan(&test_dir, config.clone())?;
+        assert_eq!(workspace.scan_stats().map(|scan| scan.files), Some(2));
+        let result = workspace.search("retry", None)?;
+        assert_eq!(result.hits.len(), 2);
+        assert_eq!(result.incomplete, None);
+        let filters = search::SearchFilters {
+            extensions: Some(vec!["py".to_string()]),
+            ..Default::default()
+        };
+        let result = workspace.search_with_filters(r"retry_\w+", None, filters, true)?;
+        assert_eq!(result.hits.len(), 1);
+        assert_eq!(result.hits[0].match_line, Some(1));
+        // Nothing is written to the data directory
+        assert!(!config.indexer.data_dir.exists());
+
+        config.search.scan_max_files = 1;
+        let workspace = Workspace::scan(&test_dir, config)?;
+        assert_eq!(workspace.search("retry", None)?.hits.len(), 1);
+        assert!(workspace.search("retry", None)?.incomplete.is_some());
+        Ok(())
+    }
 
     #[test]
-    fn test_workspace_open() -> Result<()> {
-        let temp_dir = tempdir().unwrap();
+    fn test_relink_moved_workspace() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let old_root = temp_base.path().join("old").join("project");
+        std::fs::create_dir_all(&old_root).unwrap();
+        std::fs::write(old_root.join("a.rs"), "fn retry() {}\n").unwrap();
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        Workspace::create_with_config(&old_root, config.clone())?.index_all()?;
 
-        // Create a test file
-        std::fs::write(temp_dir.path().join("test.rs"), "fn main() {}").unwrap();
+        let new_root = temp_base.path().join("new").join("project");
+        std::fs::create_dir_all(new_root.parent().unwrap()).unwrap();
+        std::fs::rename(&old_root, &new_root).unwrap();
+        std::fs::write(new_root.join("b.rs"), "fn retry_all() {}\n").unwrap();
 
-        // Use create() to create a new workspace, then index it
-        let workspace = Workspace::create(temp_dir.path())?;
-        workspace.index_all()?;
+        let moved = Workspace::find_moved_index(&new_root, &config)?.expect("moved index");
+        assert_eq!(
+            moved.old_root,
+            std::fs::canonicalize(temp_base.path())?.join("old/project")
+        );
+        // Another directory doesn't take it
+        let other = temp_base.path().join("other");
+        std::fs::create_dir_all(&other).unwrap();
+        assert!(Workspace::find_moved_index(&other, &config)?.is_none());
+
+        let (workspace, _) = Workspace::relink(&new_root, &moved, config.clone())?;
+        assert!(!moved.index_path.exists());
+        assert_eq!(workspace.search("retry", None)?.hits.len(), 1);
+        assert!(Workspace::find_moved_index(&new_root, &config)?.is_none());
+        Ok(())
+    }
 
-        // Now we can open the indexed workspace
-        let workspace = Workspace::open(temp_dir.path())?;
-        assert!(workspace.root().exists());
+    #[test]
+    fn test_oversized_head() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let mut content = "fn head_marker() {}\n".to_string();
+        content.push_str(&"let filler = 0;\n".repeat(200));
+        content.push_str("fn tail_marker() {}\n");
+        std::fs::write(test_dir.join("generated.rs"), &content).unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        config.indexer.max_file_size = 1024;
+        let workspace = Workspace::create_with_config(&test_dir, config.clone())?;
+        assert_eq!(workspace.index_all()?.indexed, 0);
 
+        config.indexer.oversized_head_kb = Some(1);
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        assert_eq!(workspace.index_all()?.indexed, 1);
+        let result = workspace.search("head_marker", None)?;
+        // The file's chunks are marked too
+        assert!(!result.hits.is_empty() && result.hits.iter().all(|hit| hit.truncated));
+        assert!(result
+            .format_ai()
+            .contains("generated.rs:1 (100%) [truncated]\n"));
+        assert!(workspace.search("tail_marker", None)?.hits.is_empty());
         Ok(())
     }
 
     #[test]
-    fn test_workspace_index_and_search() -> Result<()> {
-        // Use a temp directory but create a subdirectory to avoid "tmp" filtering
+    fn test_stemming() -> Result<()> {
         let temp_base = tempdir().unwrap();
         let test_dir = temp_base.path().join("test_workspace");
         std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(
+            test_dir.join("guide.md"),
+            "# Guide\n\nHow indexing works.\n",
+        )
+        .unwrap();
+        std::fs::write(test_dir.join("index.rs"), "fn indexing() {}\n").unwrap();
 
-        // Create test files
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config.clone())?;
+        workspace.index_all()?;
+        assert!(workspace.search("indexes", None)?.hits.is_empty());
+
+        config.indexer.stemming = true;
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+        // Prose matches by stem, at the stemmed word; code keeps exact tokens
+        let hits = workspace.search("indexes", None)?.hits;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "guide.md");
+        assert_eq!(
+            (hits[0].match_line, hits[0].match_column),
+            (Some(3), Some(5))
+        );
+        // Smart case asks for the exact text
+        let mut smart_case = workspace.config.clone();
+        smart_case.search.smart_case = true;
+        let exact = Workspace::open_with_config(&test_dir, smart_case)?;
+        assert!(exact.search("Indexes", None)?.hits.is_empty());
+
+        let plan = workspace.explain_query("indexes", Default::default(), false, false)?;
+        assert_eq!(plan.stemmed, vec!["index".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_did_you_mean() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
         std::fs::write(
-            test_dir.join("hello.rs"),
-            "fn hello_world() { println!(\"Hello!\"); }",
+            test_dir.join("embed.rs"),
+            "fn load_embeddings() {}\n// embeddings cache\n",
         )
         .unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        let result = workspace.search_with_filters(
+            "embedings cache",
+            None,
+            search::SearchFilters::default(),
+            false,
+        )?;
+        assert!(result.is_empty());
+        assert_eq!(result.suggestions, vec!["embeddings cache"]);
+        assert!(result
+            .format_ai()
+            .contains("# Did you mean \"embeddings cache\"?\n"));
+        assert!(result.format_json().contains("\"suggestions\""));
+
+        // Nothing to correct when every word is indexed, or for regex searches
+        assert!(workspace
+            .search("cache embeddings", None)?
+            .suggestions
+            .is_empty());
+        let regex = workspace.search_with_filters(
+            "embedings",
+            None,
+            search::SearchFilters::default(),
+            true,
+        )?;
+        assert!(regex.suggestions.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_feedback() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(test_dir.join("pool.rs"), "fn connection_pool() {}").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        let hit = workspace.search("connection_pool", None)?.hits.remove(0);
+        assert_eq!(
+            workspace.record_feedback("connection_pool", &hit.doc_id, true)?,
+            "pool.rs"
+        );
+        workspace.record_feedback("pool", "pool.rs", false)?;
+        assert!(workspace
+            .record_feedback("pool", "missing.rs", true)
+            .is_err());
+
+        let feedback = search::FeedbackStore::load(workspace.index_path())?;
+        let votes = feedback.get("pool.rs").unwrap();
+        assert_eq!((votes.relevant, votes.irrelevant), (1, 1));
+        Ok(())
+    }
+
+    #[cfg(feature = "embeddings")]
+    #[test]
+    fn test_hybrid_fetches_past_filtered_results() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        // Short (so best-ranked) files with the negated word fill the first fetch
+        for i in 0..150 {
+            std::fs::write(
+                test_dir.join(format!("legacy_{}.rs", i)),
+                "// widget legacy",
+            )
+            .unwrap();
+        }
+        for i in 0..12 {
+            std::fs::write(
+                test_dir.join(format!("current_{}.rs", i)),
+                "// widget rendered by the current layout engine with caching and retries",
+            )
+            .unwrap();
+        }
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        config.search.negation = true;
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        // (no vectors: the text side of the fusion finds them)
+        let result = workspace.search_hybrid(
+            "widget -legacy",
+            Some(10),
+            search::SearchFilters::default(),
+        )?;
+        assert_eq!(result.hits.len(), 10);
+        assert!(result
+            .hits
+            .iter()
+            .all(|hit| hit.path.starts_with("current_")));
+        Ok(())
+    }
+
+    #[cfg(feature = "embeddings")]
+    #[test]
+    fn test_index_budget_counts_kept_vectors() -> Result<()> {
+        let temp = tempdir().unwrap();
+        let test_dir = temp.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let mut config = Config::default();
+        config.indexer.data_dir = temp.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+
+        let dimension = workspace.embedding_model.dimension();
+        let budget = workspace.text_index_size() + index::budget::bytes_per_embedding(dimension);
+        let changed = || {
+            vec![(
+                "changed".to_string(),
+                "src/changed.rs".to_string(),
+                "fn changed() {}".to_string(),
+            )]
+        };
+        let mut batch = changed();
+        assert!(workspace.apply_index_budget(&mut batch, budget)?.is_empty());
+
+        // An incremental run keeps the vectors of unchanged files, which take the room
+        workspace
+            .vector_index
+            .insert("unchanged", &vec![0.5; dimension])?;
+        let mut batch = changed();
+        assert_eq!(
+            workspace.apply_index_budget(&mut batch, budget)?,
+            vec!["src/changed.rs"]
+        );
+        assert!(batch.is_empty());
+        Ok(())
+    }
+
+    #[cfg(feature = "embeddings")]
+    #[test]
+    fn test_hybrid_regex() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
         std::fs::write(
-            test_dir.join("goodbye.rs"),
-            "fn goodbye_world() { println!(\"Bye!\"); }",
+            test_dir.join("pool.rs"),
+            "fn connection_pool() {}\n// embeddings cache\n",
         )
         .unwrap();
+        std::fs::write(test_dir.join("notes.md"), "Pools are running out\n").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        config.indexer.stemming = true;
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        let result = workspace.search_hybrid_regex(
+            r"fn\s+connection_\w+",
+            None,
+            search::SearchFilters::default(),
+        )?;
+        assert_eq!(result.hits[0].path, "pool.rs");
+        assert_eq!(result.hits[0].match_line, Some(1));
+        assert!(workspace
+            .search_hybrid_regex("runn\\w+", None, search::SearchFilters::default())?
+            .hits
+            .iter()
+            .any(|hit| hit.path == "notes.md"));
+
+        // A pattern of plain words gets suggestions like a text search, others don't
+        let result =
+            workspace.search_hybrid_regex("embedings", None, search::SearchFilters::default())?;
+        assert!(result.is_empty());
+        assert_eq!(result.suggestions, vec!["embeddings"]);
+        let result = workspace.search_hybrid_regex(
+            r"embedings\s",
+            None,
+            search::SearchFilters::default(),
+        )?;
+        assert!(result.suggestions.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_refresh_if_stale() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let git_init = std::process::Command::new("git")
+            .arg("init")
+            .arg("-q")
+            .arg(&test_dir)
+            .status();
+        if !git_init.is_ok_and(|status| status.success()) {
+            return Ok(());
+        }
+        std::fs::write(test_dir.join("pool.rs"), "fn old_pool() {}").unwrap();
 
-        // Use create_with_config to create a new workspace
         let mut config = Config::default();
         config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+
+        // Recently indexed: no check
+        let hour = std::time::Duration::from_secs(3600);
+        assert_eq!(workspace.refresh_if_stale(hour)?, None);
 
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(test_dir.join("pool.rs"), "fn new_pool() {}").unwrap();
+        let zero = std::time::Duration::ZERO;
+        assert_eq!(workspace.refresh_if_stale(zero)?, Some(1));
+        assert!(!workspace.search("new_pool", None)?.is_empty());
+        assert!(workspace.search("old_pool", None)?.is_empty());
+        assert_eq!(workspace.refresh_if_stale(zero)?, Some(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(test_dir.join("done.rs"), "fn already_indexed() {}").unwrap();
+        std::fs::write(test_dir.join("todo.rs"), "fn not_yet_indexed() {}").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
         let workspace = Workspace::create_with_config(&test_dir, config)?;
 
-        // Index
-        let stats = workspace.index_all()?;
-        assert!(stats.indexed >= 2);
+        // An interrupted run that had committed done.rs (left out here, so a resumed run
+        // that re-indexed it would be noticed)
+        let mut checkpoint = index::IndexCheckpoint::default();
+        let metadata = std::fs::metadata(test_dir.join("done.rs"))?;
+        checkpoint.record_committed("done.rs", "0000000000000001", &metadata);
+        checkpoint.save(workspace.index_path())?;
+
+        let events = Events::default();
+        let stats = workspace.index_all_with_progress(false, &events)?;
+        assert_eq!(stats.indexed, 2);
+        assert_eq!(
+            events.0.borrow()[0],
+            index::ProgressEvent::Resume {
+                indexed: 1,
+                embedded: 0
+            }
+        );
+        assert!(!workspace.search("not_yet_indexed", None)?.is_empty());
+        assert!(workspace.search("already_indexed", None)?.is_empty());
+        assert!(index::IndexCheckpoint::load(workspace.index_path()).is_none());
 
-        // Search (should work now since workspace is indexed)
-        let result = workspace.search("hello", None)?;
-        assert!(!result.is_empty());
-        assert!(result.hits.iter().any(|h| h.path.contains("hello")));
+        // The next full run starts over
+        workspace.index_all()?;
+        assert!(!workspace.search("already_indexed", None)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_optimize() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(test_dir.join("a.rs"), "fn optimized_alpha() {}").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let workspace = Workspace::create_with_config(&test_dir, config)?;
+        workspace.index_all()?;
+        // Each incremental update commits a segment of its own
+        std::fs::write(test_dir.join("b.rs"), "fn optimized_beta() {}").unwrap();
+        workspace.index_file(&test_dir.join("b.rs"))?;
+
+        let stats = workspace.optimize()?;
+        assert_eq!(stats.segments_before, 2);
+        assert_eq!(stats.segments_after, 1);
+        assert_eq!(stats.orphan_vectors, 0);
+        assert!(!workspace.search("optimized_alpha", None)?.is_empty());
+        assert!(!workspace.search("optimized_beta", None)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair() -> Result<()> {
+        let temp_base = tempdir().unwrap();
+        let test_dir = temp_base.path().join("test_workspace");
+        std::fs::create_dir_all(&test_dir).unwrap();
+        std::fs::write(test_dir.join("a.rs"), "fn repaired_alpha() {}").unwrap();
+        std::fs::write(test_dir.join("b.rs"), "fn repaired_beta() {}").unwrap();
+
+        let mut config = Config::default();
+        config.indexer.data_dir = temp_base.path().join("data");
+        let index_path = {
+            let workspace = Workspace::create_with_config(&test_dir, config.clone())?;
+            workspace.index_all()?;
+            workspace.index_path().to_path_buf()
+        };
+
+        // A healthy index is left alone
+        assert!(Workspace::repair(&test_dir, config.clone())?.is_clean());
+
+        // Without meta.json (or workspace.json) the index can't be opened
+        std::fs::remove_file(index_path.join("meta.json"))?;
+        std::fs::remove_file(index_path.join("workspace.json"))?;
+        assert!(Workspace::open_with_config(&test_dir, config.clone()).is_err());
+
+        let report = Workspace::repair(&test_dir, config.clone())?;
+        assert_eq!(report.reindexed, 2);
+        assert_eq!(report.repaired.len(), 2);
 
+        let workspace = Workspace::open_with_config(&test_dir, config.clone())?;
+        assert!(!workspace.search("repaired_alpha", None)?.is_empty());
+        assert!(!workspace.search("repaired_beta", None)?.is_empty());
+        assert!(index::verify_index(&index_path).passed());
         Ok(())
     }
 }
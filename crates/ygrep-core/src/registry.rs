@@ -0,0 +1,165 @@
+//! Named workspaces (`ygrep -C @backend`), stored in `<data_dir>/workspaces.json`
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, YgrepError};
+
+/// Registry file inside the data directory
+const REGISTRY_FILE: &str = "workspaces.json";
+
+/// Prefix that marks a workspace argument as a registered name
+pub const NAME_PREFIX: char = '@';
+
+/// Workspace names mapped to their root directories
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkspaceRegistry {
+    workspaces: BTreeMap<String, PathBuf>,
+}
+
+impl WorkspaceRegistry {
+    /// Load the registry from the data directory (empty if none was saved yet)
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(REGISTRY_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| YgrepError::Config(format!("Failed to load workspace registry: {}", e)))
+    }
+
+    /// Write the registry to the data directory
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(data_dir)?;
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| YgrepError::Config(format!("Failed to save workspace registry: {}", e)))?;
+        // Replace the file in one step, so an interrupted save leaves the old registry
+        let path = data_dir.join(REGISTRY_FILE);
+        let tmp = PathBuf::from(format!("{}.{}.tmp", path.display(), std::process::id()));
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Resolve a workspace argument with the registry in `data_dir`, which is only read
+    /// for `@name` arguments: plain paths work even when the registry can't be loaded
+    pub fn resolve_in(data_dir: &Path, workspace: &Path) -> Result<PathBuf> {
+        if registered_name(workspace).is_none() {
+            return Ok(workspace.to_path_buf());
+        }
+        Self::load(data_dir)?.resolve(workspace)
+    }
+
+    /// Register (or re-point) a name; returns the canonical workspace path
+    pub fn add(&mut self, name: &str, path: &Path) -> Result<PathBuf> {
+        let name = strip_prefix(name);
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(YgrepError::Config(format!(
+                "Invalid workspace name '{}' (use letters, digits, '-' or '_')",
+                name
+            )));
+        }
+        let root = std::fs::canonicalize(path)
+            .map_err(|_| YgrepError::WorkspaceNotFound(path.to_path_buf()))?;
+        self.workspaces.insert(name.to_string(), root.clone());
+        Ok(root)
+    }
+
+    /// Forget a name; returns the path it pointed to
+    pub fn remove(&mut self, name: &str) -> Option<PathBuf> {
+        self.workspaces.remove(strip_prefix(name))
+    }
+
+    /// Point the names registered for `from` at `to` (a workspace that was moved); false
+    /// when none were
+    pub fn relocate(&mut self, from: &Path, to: &Path) -> bool {
+        let mut relocated = false;
+        for path in self.workspaces.values_mut().filter(|path| *path == from) {
+            *path = to.to_path_buf();
+            relocated = true;
+        }
+        relocated
+    }
+
+    /// Path registered under a name (with or without the `@`)
+    pub fn get(&self, name: &str) -> Option<&Path> {
+        self.workspaces.get(strip_prefix(name)).map(|p| p.as_path())
+    }
+
+    /// Registered names and paths, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Path)> {
+        self.workspaces
+            .iter()
+            .map(|(name, path)| (name.as_str(), path.as_path()))
+    }
+
+    /// Resolve a workspace argument: `@name` is looked up, anything else is a path
+    pub fn resolve(&self, workspace: &Path) -> Result<PathBuf> {
+        match registered_name(workspace) {
+            Some(name) => self.get(name).map(Path::to_path_buf).ok_or_else(|| {
+                YgrepError::Config(format!(
+                    "Unknown workspace '@{}' (see `ygrep workspaces list`)",
+                    name
+                ))
+            }),
+            None => Ok(workspace.to_path_buf()),
+        }
+    }
+}
+
+/// The name in a `@name` workspace argument
+fn registered_name(workspace: &Path) -> Option<&str> {
+    workspace.to_str().and_then(|s| s.strip_prefix(NAME_PREFIX))
+}
+
+fn strip_prefix(name: &str) -> &str {
+    name.strip_prefix(NAME_PREFIX).unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_registry_roundtrip() -> Result<()> {
+        let data_dir = tempdir().unwrap();
+        let workspace = tempdir().unwrap();
+
+        let mut registry = WorkspaceRegistry::load(data_dir.path())?;
+        let root = registry.add("@backend", workspace.path())?;
+        assert!(registry.add("bad/name", workspace.path()).is_err());
+        registry.save(data_dir.path())?;
+
+        let mut registry = WorkspaceRegistry::load(data_dir.path())?;
+        assert_eq!(registry.resolve(Path::new("@backend"))?, root);
+        assert_eq!(registry.resolve(Path::new("src"))?, PathBuf::from("src"));
+        assert!(registry.resolve(Path::new("@frontend")).is_err());
+
+        assert_eq!(registry.remove("backend"), Some(root));
+        assert_eq!(registry.iter().count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_in_reads_registry_for_names_only() -> Result<()> {
+        let data_dir = tempdir().unwrap();
+        std::fs::write(data_dir.path().join(REGISTRY_FILE), "{ not json").unwrap();
+
+        let path = WorkspaceRegistry::resolve_in(data_dir.path(), Path::new("src"))?;
+        assert_eq!(path, PathBuf::from("src"));
+        assert!(WorkspaceRegistry::resolve_in(data_dir.path(), Path::new("@backend")).is_err());
+
+        // Saving replaces the damaged file whole, leaving no temporary file behind
+        WorkspaceRegistry::default().save(data_dir.path())?;
+        assert_eq!(WorkspaceRegistry::load(data_dir.path())?.iter().count(), 0);
+        assert_eq!(std::fs::read_dir(data_dir.path())?.count(), 1);
+        Ok(())
+    }
+}
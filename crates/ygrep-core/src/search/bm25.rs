@@ -0,0 +1,383 @@
+//! BM25 ranking with configurable `k1`/`b`, stopwords and file-kind weights
+//!
+//! Tantivy's own scorer hard-codes k1 = 1.2 and b = 0.75, so other values (or a stopword
+//! list) re-score the matching documents over the query's non-stopword terms. Kind
+//! weights (`search.kind_weights`) then scale each document's score by its file kind,
+//! and files other files import get a mild boost (`search.reference_weight`).
+
+use std::collections::HashSet;
+use tantivy::collector::{ScoreSegmentTweaker, ScoreTweaker, TopDocs};
+use tantivy::columnar::{Column, StrColumn};
+use tantivy::postings::{Postings, SegmentPostings};
+use tantivy::query::{Bm25StatisticsProvider, Query};
+use tantivy::schema::{Field, IndexRecordOption};
+use tantivy::{DocAddress, DocId, DocSet, Score, SegmentReader, Term};
+
+use crate::config::SearchConfig;
+use crate::error::Result;
+use crate::index::schema::fields;
+
+/// Tantivy's built-in BM25 parameters
+const DEFAULT_K1: f32 = 1.2;
+const DEFAULT_B: f32 = 0.75;
+
+/// Importers at which the reference boost reaches `search.reference_weight`
+const REFERENCE_SATURATION: f32 = 100.0;
+
+/// BM25 parameters, stopwords and kind weights from the search config
+#[derive(Debug, Clone)]
+pub(crate) struct Bm25Scoring {
+    k1: f32,
+    b: f32,
+    stopwords: HashSet<String>,
+    /// Multipliers for the values of the `kind` field (only those other than 1.0)
+    kind_weights: Vec<(String, f32)>,
+    reference_weight: f32,
+}
+
+impl Bm25Scoring {
+    pub fn from_config(config: &SearchConfig) -> Self {
+        Self {
+            k1: config.bm25_k1.max(0.0),
+            b: config.bm25_b.clamp(0.0, 1.0),
+            stopwords: config.stopwords.iter().map(|w| w.to_lowercase()).collect(),
+            kind_weights: config
+                .kind_weights
+                .iter()
+                .filter(|(_, weight)| **weight != 1.0)
+                .map(|(kind, weight)| (kind.as_str().to_string(), weight.max(0.0)))
+                .collect(),
+            reference_weight: config.reference_weight.max(0.0),
+        }
+    }
+
+    /// Whether Tantivy's BM25 scores can be used as they are
+    fn is_default(&self) -> bool {
+        self.k1 == DEFAULT_K1 && self.b == DEFAULT_B && self.stopwords.is_empty()
+    }
+
+    /// Ranking changes from the defaults, one line each (for query plans)
+    pub fn describe(&self) -> Vec<String> {
+        let mut described = Vec::new();
+        if self.k1 != DEFAULT_K1 || self.b != DEFAULT_B {
+            described.push(format!("BM25 k1 = {}, b = {}", self.k1, self.b));
+        }
+        for (kind, weight) in &self.kind_weights {
+            described.push(format!("{} files x{}", kind, weight));
+        }
+        if self.reference_weight > 0.0 {
+            described.push(format!(
+                "imported files up to x{}",
+                1.0 + self.reference_weight
+            ));
+        }
+        described
+    }
+
+    pub fn is_stopword(&self, word: &str) -> bool {
+        self.stopwords.contains(&word.to_lowercase())
+    }
+
+    /// Drop stopwords from query words, unless that would leave nothing to search for
+    pub fn without_stopwords<'a>(&self, words: Vec<&'a str>) -> Vec<&'a str> {
+        if words.iter().all(|w| self.is_stopword(w)) {
+            return words;
+        }
+        words.into_iter().filter(|w| !self.is_stopword(w)).collect()
+    }
+
+    /// Top documents for a query, scored with these parameters on `field`
+    pub fn top_docs(
+        &self,
+        searcher: &tantivy::Searcher,
+        query: &dyn Query,
+        field: Field,
+        limit: usize,
+    ) -> Result<Vec<(Score, DocAddress)>> {
+        let rescore = !self.is_default();
+        if !rescore && self.kind_weights.is_empty() && self.reference_weight == 0.0 {
+            return Ok(searcher.search(query, &TopDocs::with_limit(limit))?);
+        }
+
+        let mut terms: Vec<Term> = Vec::new();
+        query.query_terms(&mut |term, _| {
+            if term.field() == field && !terms.contains(term) {
+                terms.push(term.clone());
+            }
+        });
+        let scored: Vec<Term> = terms
+            .iter()
+            .filter(|term| !term.value().as_str().is_some_and(|w| self.is_stopword(w)))
+            .cloned()
+            .collect();
+        // Without rescoring only the kind weights and reference boost apply
+        let terms = match (rescore, scored.is_empty()) {
+            (false, _) => Vec::new(),
+            (true, true) => terms,
+            (true, false) => scored,
+        };
+
+        let num_docs = searcher.total_num_docs()?.max(1);
+        let average_length = searcher.total_num_tokens(field)? as f32 / num_docs as f32;
+        let weighted = terms
+            .into_iter()
+            .map(|term| {
+                let doc_freq = searcher.doc_freq(&term)?;
+                Ok((term, idf(doc_freq, num_docs)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let rescorer = Bm25Rescorer {
+            rescore,
+            k1: self.k1,
+            b: self.b,
+            field,
+            average_length: average_length.max(1.0),
+            terms: weighted,
+            kind_weights: self.kind_weights.clone(),
+            reference_weight: self.reference_weight,
+        };
+        Ok(searcher.search(query, &TopDocs::with_limit(limit).tweak_score(rescorer))?)
+    }
+}
+
+/// Inverse document frequency, as in Tantivy
+fn idf(doc_freq: u64, num_docs: u64) -> f32 {
+    let x = ((num_docs - doc_freq.min(num_docs)) as f32 + 0.5) / (doc_freq as f32 + 0.5);
+    (1.0 + x).ln()
+}
+
+/// Computes BM25 for each matching document from its term frequencies and length (when
+/// `rescore` is set, otherwise keeps Tantivy's score) and applies kind weights and the
+/// reference boost
+struct Bm25Rescorer {
+    rescore: bool,
+    k1: f32,
+    b: f32,
+    field: Field,
+    average_length: f32,
+    terms: Vec<(Term, f32)>,
+    kind_weights: Vec<(String, f32)>,
+    reference_weight: f32,
+}
+
+impl ScoreTweaker<Score> for Bm25Rescorer {
+    type Child = SegmentRescorer;
+
+    fn segment_tweaker(&self, segment_reader: &SegmentReader) -> tantivy::Result<SegmentRescorer> {
+        let inverted_index = segment_reader.inverted_index(self.field)?;
+        let mut postings = Vec::with_capacity(self.terms.len());
+        for (term, idf) in &self.terms {
+            if let Some(p) = inverted_index.read_postings(term, IndexRecordOption::WithFreqs)? {
+                postings.push((p, *idf));
+            }
+        }
+        // Resolve the kind weights to this segment's term ordinals
+        let kinds = segment_reader.fast_fields().str(fields::KIND)?;
+        let mut ord_weights = Vec::new();
+        if let Some(ref column) = kinds {
+            let mut kind = String::new();
+            for ord in 0..column.num_terms() as u64 {
+                kind.clear();
+                column.ord_to_str(ord, &mut kind)?;
+                let weight = self.kind_weights.iter().find(|(k, _)| *k == kind);
+                ord_weights.push(weight.map_or(1.0, |(_, w)| *w));
+            }
+        }
+
+        Ok(SegmentRescorer {
+            rescore: self.rescore,
+            k1: self.k1,
+            b: self.b,
+            average_length: self.average_length,
+            fieldnorms: segment_reader.get_fieldnorms_reader(self.field)?,
+            postings,
+            kinds,
+            ord_weights,
+            // Indexes without the field get no boost
+            references: (self.reference_weight > 0.0)
+                .then(|| segment_reader.fast_fields().u64(fields::REFERENCES).ok())
+                .flatten(),
+            reference_weight: self.reference_weight,
+        })
+    }
+}
+
+struct SegmentRescorer {
+    rescore: bool,
+    k1: f32,
+    b: f32,
+    average_length: f32,
+    fieldnorms: tantivy::fieldnorm::FieldNormReader,
+    postings: Vec<(SegmentPostings, f32)>,
+    kinds: Option<StrColumn>,
+    ord_weights: Vec<f32>,
+    references: Option<Column<u64>>,
+    reference_weight: f32,
+}
+
+impl SegmentRescorer {
+    /// Kind weight times reference boost
+    fn doc_weight(&self, doc: DocId) -> f32 {
+        let kind = self
+            .kinds
+            .as_ref()
+            .and_then(|column| column.term_ords(doc).next())
+            .and_then(|ord| self.ord_weights.get(ord as usize).copied())
+            .unwrap_or(1.0);
+        let references = self
+            .references
+            .as_ref()
+            .and_then(|column| column.first(doc))
+            .unwrap_or(0);
+        kind * reference_boost(references, self.reference_weight)
+    }
+}
+
+/// Score multiplier for a file `references` files import: grows with the log of the
+/// count up to `1 + weight` at `REFERENCE_SATURATION` importers
+fn reference_boost(references: u64, weight: f32) -> f32 {
+    if references == 0 || weight <= 0.0 {
+        return 1.0;
+    }
+    let share = (1.0 + references as f32).ln() / (1.0 + REFERENCE_SATURATION).ln();
+    1.0 + weight * share.min(1.0)
+}
+
+impl ScoreSegmentTweaker<Score> for SegmentRescorer {
+    // Documents arrive in increasing order, so each term's postings only move forward
+    fn score(&mut self, doc: DocId, score: Score) -> Score {
+        if !self.rescore {
+            return score * self.doc_weight(doc);
+        }
+        let length = self.fieldnorms.fieldnorm(doc) as f32;
+        let norm = self.k1 * (1.0 - self.b + self.b * length / self.average_length);
+        let mut score = 0.0;
+        for (postings, idf) in &mut self.postings {
+            if postings.doc() < doc {
+                postings.seek(doc);
+            }
+            if postings.doc() == doc {
+                let tf = postings.term_freq() as f32;
+                score += *idf * tf * (self.k1 + 1.0) / (tf + norm);
+            }
+        }
+        score * self.doc_weight(doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{build_document_schema, register_tokenizers, SchemaFields};
+    use tantivy::query::QueryParser;
+    use tantivy::{doc, Index};
+
+    fn scores(config: &SearchConfig, query: &str) -> Vec<(Score, String)> {
+        let index = Index::create_in_ram(build_document_schema());
+        register_tokenizers(index.tokenizers());
+        let fields = SchemaFields::new(&index.schema());
+        let mut writer = index.writer(15_000_000).unwrap();
+        for (path, content) in [
+            ("short.md", "the pool"),
+            (
+                "long.md",
+                "the pool the the the the the the the the the the",
+            ),
+        ] {
+            writer
+                .add_document(doc!(fields.path => path, fields.content => content))
+                .unwrap();
+        }
+        writer.commit().unwrap();
+
+        let searcher = index.reader().unwrap().searcher();
+        let parser = QueryParser::for_index(&index, vec![fields.content]);
+        let query = parser.parse_query(query).unwrap();
+        let scoring = Bm25Scoring::from_config(config);
+        let top = scoring
+            .top_docs(&searcher, &query, fields.content, 10)
+            .unwrap();
+        top.into_iter()
+            .map(|(score, address)| {
+                let doc: tantivy::TantivyDocument = searcher.doc(address).unwrap();
+                let path = doc.get_first(fields.path).unwrap();
+                (
+                    score,
+                    tantivy::schema::Value::as_str(&path).unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bm25_parameters() {
+        let config = SearchConfig::default();
+        assert_ne!(scores(&config, "the pool"), scores(&config, "pool"));
+
+        // Stopwords don't contribute to the score
+        let stopwords = SearchConfig {
+            stopwords: vec!["The".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(scores(&stopwords, "the pool"), scores(&stopwords, "pool"));
+
+        // Length normalization favours the short document; without it they tie
+        let default = scores(&config, "pool");
+        assert_eq!(default[0].1, "short.md");
+        assert!(default[0].0 > default[1].0);
+        let no_length = SearchConfig {
+            bm25_b: 0.0,
+            ..Default::default()
+        };
+        let flat = scores(&no_length, "pool");
+        assert!((flat[0].0 - flat[1].0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_reference_boost() {
+        assert_eq!(reference_boost(0, 0.1), 1.0);
+        assert_eq!(reference_boost(50, 0.0), 1.0);
+        assert!(reference_boost(1, 0.1) > 1.0);
+        assert!(reference_boost(10, 0.1) > reference_boost(1, 0.1));
+        assert!((reference_boost(100, 0.1) - 1.1).abs() < 1e-6);
+        assert!((reference_boost(5000, 0.1) - 1.1).abs() < 1e-6);
+
+        // The same content ranks higher in the file more files import
+        let index = Index::create_in_ram(build_document_schema());
+        register_tokenizers(index.tokenizers());
+        let fields = SchemaFields::new(&index.schema());
+        let mut writer = index.writer(15_000_000).unwrap();
+        for (path, references) in [("script.py", 0u64), ("core.py", 12)] {
+            writer
+                .add_document(doc!(
+                    fields.path => path,
+                    fields.content => "connect pool",
+                    fields.references => references,
+                ))
+                .unwrap();
+        }
+        writer.commit().unwrap();
+        let searcher = index.reader().unwrap().searcher();
+        let parser = QueryParser::for_index(&index, vec![fields.content]);
+        let query = parser.parse_query("pool").unwrap();
+        let paths = |config: &SearchConfig| -> Vec<String> {
+            Bm25Scoring::from_config(config)
+                .top_docs(&searcher, &query, fields.content, 10)
+                .unwrap()
+                .into_iter()
+                .map(|(_, address)| {
+                    let doc: tantivy::TantivyDocument = searcher.doc(address).unwrap();
+                    let path = doc.get_first(fields.path).unwrap();
+                    tantivy::schema::Value::as_str(&path).unwrap().to_string()
+                })
+                .collect()
+        };
+        assert_eq!(paths(&SearchConfig::default())[0], "core.py");
+        let off = SearchConfig {
+            reference_weight: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(paths(&off)[0], "script.py");
+    }
+}
@@ -0,0 +1,138 @@
+//! Chunks in the context of their file: which chunk of how many a hit is and the lines
+//! it covers (`search.chunk_context`), whole files read back from the index (`ygrep get`)
+//! and hits with the lines around them (`search.expand_lines`)
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Where a chunk hit sits in its file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ChunkContext {
+    /// Position of the chunk in its file (1-based)
+    pub number: usize,
+    /// Chunks the file is split into
+    pub count: usize,
+    /// Lines of the file the chunk covers
+    pub line_start: u64,
+    pub line_end: u64,
+    /// doc_id of the whole file
+    pub parent_doc: String,
+}
+
+impl ChunkContext {
+    /// `chunk 3 of 7, lines 120-180`
+    pub fn summary(&self) -> String {
+        format!(
+            "chunk {} of {}, lines {}-{}",
+            self.number, self.count, self.line_start, self.line_end
+        )
+    }
+}
+
+/// A file as it is indexed (`ygrep get`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexedFile {
+    /// File path relative to the workspace
+    pub path: String,
+    /// doc_id of the whole file
+    pub doc_id: String,
+    pub content: String,
+    /// Chunks the file is split into (0 for files indexed whole)
+    pub chunks: usize,
+    /// Put back together from its chunks, the file's own document being missing
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub from_chunks: bool,
+    /// Only the start of the file is indexed (`indexer.oversized_head_kb`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+impl IndexedFile {
+    pub fn format_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+/// A hit with more of its file around it, read back from the index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpandedHit {
+    /// File path relative to the workspace
+    pub path: String,
+    /// doc_id of the hit's document
+    pub doc_id: String,
+    /// Lines of the hit itself
+    pub hit_line_start: u64,
+    pub hit_line_end: u64,
+    /// Lines of `content`: the hit's with the lines added before and after it
+    pub line_start: u64,
+    pub line_end: u64,
+    pub content: String,
+}
+
+impl ExpandedHit {
+    pub fn format_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+/// Lines `start..=end` (1-based) of `content` with `before` and `after` more, clamped to
+/// the file: (first line, last line, text)
+pub(crate) fn expand_lines(
+    content: &str,
+    (start, end): (u64, u64),
+    before: usize,
+    after: usize,
+) -> (u64, u64, String) {
+    let lines: Vec<&str> = content.lines().collect();
+    let total = lines.len() as u64;
+    if total == 0 {
+        return (1, 1, String::new());
+    }
+    let first = start.saturating_sub(before as u64).clamp(1, total);
+    let last = end.saturating_add(after as u64).clamp(first, total);
+    let text = lines[first as usize - 1..last as usize].join("\n");
+    (first, last, text)
+}
+
+/// The text of a file's chunks, given as (first line, content) in any order, with the
+/// lines where consecutive chunks overlap taken once
+pub(crate) fn stitch(mut chunks: Vec<(u64, String)>) -> String {
+    chunks.sort_by_key(|(line_start, _)| *line_start);
+    let mut lines: Vec<&str> = Vec::new();
+    for (line_start, content) in &chunks {
+        let overlap = (lines.len() as u64 + 1).saturating_sub(*line_start) as usize;
+        lines.extend(content.lines().skip(overlap));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stitch() {
+        let lines: Vec<String> = (1..=10).map(|i| format!("line {}", i)).collect();
+        let chunk = |start: usize, end: usize| (start as u64, lines[start - 1..end].join("\n"));
+        // Chunks of 4 lines overlapping by 1, out of order
+        let stitched = stitch(vec![chunk(7, 10), chunk(1, 4), chunk(4, 7)]);
+        assert_eq!(stitched, lines.join("\n"));
+        assert_eq!(stitch(Vec::new()), "");
+    }
+
+    #[test]
+    fn test_expand_lines() {
+        let content = "a\nb\nc\nd\ne";
+        assert_eq!(
+            expand_lines(content, (3, 3), 1, 1),
+            (2, 4, "b\nc\nd".to_string())
+        );
+        // Clamped to the file
+        assert_eq!(
+            expand_lines(content, (2, 4), 5, 5),
+            (1, 5, content.to_string())
+        );
+        assert_eq!(expand_lines(content, (1, u64::MAX), 0, 0).0, 1);
+        assert_eq!(expand_lines("", (1, 1), 2, 2), (1, 1, String::new()));
+    }
+}
@@ -0,0 +1,131 @@
+//! Relevance feedback (`ygrep feedback`), applied as a small per-document prior in hybrid fusion
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::query::ParsedQuery;
+use crate::error::{Result, YgrepError};
+
+/// Feedback file inside the index directory
+const FEEDBACK_FILE: &str = "feedback.json";
+
+/// Largest boost (or penalty) feedback can apply to a fused score
+const MAX_PRIOR: f32 = 0.2;
+
+/// Net votes at which the prior stops growing
+const SATURATION: f32 = 5.0;
+
+/// Share of the prior that comes from votes for the same query (the rest is from all queries)
+const QUERY_SHARE: f32 = 2.0 / 3.0;
+
+/// Relevance votes per file, stored per workspace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FeedbackStore {
+    documents: BTreeMap<String, DocumentFeedback>,
+}
+
+/// Votes for one file (relative path)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocumentFeedback {
+    pub relevant: u32,
+    pub irrelevant: u32,
+    /// Net votes (relevant minus irrelevant) per normalized query
+    pub queries: BTreeMap<String, i32>,
+}
+
+impl FeedbackStore {
+    /// Load the feedback saved in an index directory (empty if there is none)
+    pub fn load(index_path: &Path) -> Result<Self> {
+        let path = index_path.join(FEEDBACK_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| YgrepError::Config(format!("Failed to load feedback: {}", e)))
+    }
+
+    /// Write the feedback to an index directory
+    pub fn save(&self, index_path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| YgrepError::Config(format!("Failed to save feedback: {}", e)))?;
+        std::fs::write(index_path.join(FEEDBACK_FILE), json)?;
+        Ok(())
+    }
+
+    /// Record whether a file was relevant for a query
+    pub fn record(&mut self, query: &str, path: &str, relevant: bool) {
+        let entry = self.documents.entry(path.to_string()).or_default();
+        if relevant {
+            entry.relevant += 1;
+        } else {
+            entry.irrelevant += 1;
+        }
+        *entry.queries.entry(query_key(query)).or_default() += if relevant { 1 } else { -1 };
+    }
+
+    /// Votes recorded for a file
+    pub fn get(&self, path: &str) -> Option<&DocumentFeedback> {
+        self.documents.get(path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Score multiplier for a file (within 1 ± `MAX_PRIOR`), or `None` without feedback.
+    ///
+    /// Votes for the same query count most; votes from other queries still nudge the
+    /// file as generally useful (or noisy).
+    pub fn prior(&self, query: &str, path: &str) -> Option<f32> {
+        let doc = self.documents.get(path)?;
+        let overall = doc.relevant as f32 - doc.irrelevant as f32;
+        let for_query = doc.queries.get(&query_key(query)).copied().unwrap_or(0) as f32;
+
+        let signal = |votes: f32| (votes / SATURATION).clamp(-1.0, 1.0);
+        let prior = QUERY_SHARE * signal(for_query) + (1.0 - QUERY_SHARE) * signal(overall);
+        (prior != 0.0).then_some(1.0 + MAX_PRIOR * prior)
+    }
+}
+
+/// Queries are matched on their search text, ignoring case, spacing and field qualifiers
+fn query_key(query: &str) -> String {
+    ParsedQuery::parse(query, false)
+        .search_text()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_feedback_prior() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let mut store = FeedbackStore::load(dir.path())?;
+        assert!(store.is_empty());
+
+        store.record("connection pool", "src/db/pool.rs", true);
+        store.record("Connection  Pool ext:rs", "src/db/pool.rs", true);
+        store.record("connection pool", "src/legacy/pool.rs", false);
+        store.save(dir.path())?;
+
+        let store = FeedbackStore::load(dir.path())?;
+        let boosted = store.prior("connection pool", "src/db/pool.rs").unwrap();
+        let other_query = store.prior("retry", "src/db/pool.rs").unwrap();
+        let penalized = store
+            .prior("connection pool", "src/legacy/pool.rs")
+            .unwrap();
+        assert!(boosted > other_query && other_query > 1.0);
+        assert!(penalized < 1.0);
+        assert!(boosted <= 1.0 + MAX_PRIOR);
+        assert_eq!(store.prior("connection pool", "src/main.rs"), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+//! File finder (`ygrep files <pattern>`): fuzzy or glob matching against indexed paths
+
+use globset::GlobBuilder;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+use crate::error::{Result, YgrepError};
+
+/// Characters after which a match counts as the start of a word
+const WORD_SEPARATORS: &[char] = &['/', '\\', '_', '-', '.', ' '];
+
+/// Maximum points a single pattern character can earn (see `fuzzy_score`)
+const MAX_CHAR_SCORE: f32 = 6.0;
+
+/// A path matching a file pattern
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileMatch {
+    /// File path relative to the workspace
+    pub path: String,
+    /// Match quality (0.0-1.0)
+    pub score: f32,
+}
+
+/// Ranked paths matching a file pattern
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileMatches {
+    pub files: Vec<FileMatch>,
+    /// Number of matching paths (may be more than `files` if limited)
+    pub total: usize,
+    /// Match time in milliseconds
+    pub query_time_ms: u64,
+}
+
+impl FileMatches {
+    /// Match a pattern against paths: a glob when it contains `*`, `?` or `[`, otherwise
+    /// an fzf-style fuzzy match. Matching is case-insensitive unless the pattern has
+    /// an uppercase letter.
+    pub fn find(paths: &[String], pattern: &str, limit: usize) -> Result<Self> {
+        let start = Instant::now();
+        let case_sensitive = pattern.chars().any(char::is_uppercase);
+
+        let mut files: Vec<FileMatch> = if pattern.contains(['*', '?', '[']) {
+            let glob = GlobBuilder::new(pattern)
+                .case_insensitive(!case_sensitive)
+                .build()
+                .map_err(|e| YgrepError::Search(format!("Invalid glob pattern: {}", e)))?
+                .compile_matcher();
+            // Every glob match is equally good; shorter paths rank first
+            paths
+                .iter()
+                .filter(|path| glob.is_match(path.as_str()))
+                .map(|path| FileMatch {
+                    path: path.clone(),
+                    score: 1.0,
+                })
+                .collect()
+        } else {
+            paths
+                .iter()
+                .filter_map(|path| {
+                    fuzzy_score(pattern, path, case_sensitive).map(|score| FileMatch {
+                        path: path.clone(),
+                        score,
+                    })
+                })
+                .collect()
+        };
+
+        files.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.path.len().cmp(&b.path.len()))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        let total = files.len();
+        files.truncate(limit);
+
+        Ok(Self {
+            files,
+            total,
+            query_time_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    /// One path per line with its score
+    pub fn format_ai(&self) -> String {
+        let mut output = format!("# {} files\n\n", self.total);
+        for file in &self.files {
+            output.push_str(&format!("{} ({:.0}%)\n", file.path, file.score * 100.0));
+        }
+        output
+    }
+
+    /// Format as JSON
+    pub fn format_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Plain path list (scores only with `show_scores`)
+    pub fn format_pretty(&self, show_scores: bool) -> String {
+        let mut output = format!("# {} files\n\n", self.total);
+        for file in &self.files {
+            if show_scores {
+                output.push_str(&format!("{:>3.0}%  {}\n", file.score * 100.0, file.path));
+            } else {
+                output.push_str(&format!("{}\n", file.path));
+            }
+        }
+        output
+    }
+}
+
+/// fzf-style score in 0.0-1.0, or `None` unless the pattern is a subsequence of the path.
+///
+/// Each matched character earns a point, plus bonuses for following the previous match,
+/// starting a word (after `/`, `_`, `-`, `.` or at a camelCase hump) and lying in the
+/// file name; gaps inside the matched span cost a little.
+fn fuzzy_score(pattern: &str, path: &str, case_sensitive: bool) -> Option<f32> {
+    let fold = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+    let pattern: Vec<char> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(fold)
+        .collect();
+    if pattern.is_empty() {
+        return None;
+    }
+    let original: Vec<char> = path.chars().collect();
+    let chars: Vec<char> = original.iter().map(|&c| fold(c)).collect();
+
+    let name_start = original
+        .iter()
+        .rposition(|c| *c == '/' || *c == '\\')
+        .map_or(0, |i| i + 1);
+
+    // Try each place the pattern could start and keep the best alignment
+    let best = (0..chars.len())
+        .filter(|&start| chars[start] == pattern[0])
+        .filter_map(|start| align(&pattern, &chars, start))
+        .map(|positions| score_positions(&positions, &original, name_start))
+        .fold(None, |best: Option<f32>, score| {
+            Some(best.map_or(score, |b| b.max(score)))
+        })?;
+
+    Some((best / (MAX_CHAR_SCORE * pattern.len() as f32)).clamp(0.0, 1.0))
+}
+
+/// Match the pattern greedily from `start`, then walk back from where it ended to
+/// find the tightest span; returns the matched positions
+fn align(pattern: &[char], chars: &[char], start: usize) -> Option<Vec<usize>> {
+    let mut p = 0;
+    let mut end = None;
+    for (i, &c) in chars.iter().enumerate().skip(start) {
+        if c == pattern[p] {
+            p += 1;
+            if p == pattern.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+
+    let mut positions = Vec::with_capacity(pattern.len());
+    let mut p = pattern.len();
+    for i in (start..=end?).rev() {
+        if chars[i] == pattern[p - 1] {
+            positions.push(i);
+            p -= 1;
+            if p == 0 {
+                break;
+            }
+        }
+    }
+    positions.reverse();
+    Some(positions)
+}
+
+fn score_positions(positions: &[usize], original: &[char], name_start: usize) -> f32 {
+    let mut score = 0.0;
+    for (n, &i) in positions.iter().enumerate() {
+        score += 1.0;
+        if n > 0 && positions[n - 1] + 1 == i {
+            score += 2.0;
+        }
+        let word_start = i == 0
+            || WORD_SEPARATORS.contains(&original[i - 1])
+            || (original[i - 1].is_lowercase() && original[i].is_uppercase());
+        if word_start {
+            score += 2.0;
+        }
+        if i >= name_start {
+            score += 1.0;
+        }
+    }
+    let gaps = positions[positions.len() - 1] - positions[0] + 1 - positions.len();
+    score - (gaps as f32 * 0.1).min(positions.len() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_fuzzy_find() -> Result<()> {
+        let indexed = paths(&[
+            "src/search/results.rs",
+            "src/search/searcher.rs",
+            "docs/research.md",
+            "src/auth.rs",
+        ]);
+
+        let found = FileMatches::find(&indexed, "searcher", 10)?;
+        assert_eq!(found.files[0].path, "src/search/searcher.rs");
+        assert!(found.files.iter().all(|f| f.path != "src/auth.rs"));
+
+        // Whitespace in the pattern is ignored
+        let found = FileMatches::find(&indexed, "srch rs", 10)?;
+        assert_eq!(found.total, 3);
+        assert!(found.files.iter().all(|f| f.path != "docs/research.md"));
+
+        assert_eq!(FileMatches::find(&indexed, "zzz", 10)?.total, 0);
+        assert_eq!(FileMatches::find(&indexed, "Auth", 10)?.total, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_glob_find() -> Result<()> {
+        let indexed = paths(&["src/auth.rs", "src/deep/db.rs", "README.md"]);
+
+        let found = FileMatches::find(&indexed, "*.rs", 1)?;
+        assert_eq!(found.total, 2);
+        assert_eq!(
+            found.files,
+            vec![FileMatch {
+                path: "src/auth.rs".to_string(),
+                score: 1.0
+            }]
+        );
+        assert_eq!(FileMatches::find(&indexed, "readme.*", 10)?.total, 1);
+        assert!(FileMatches::find(&indexed, "[", 10).is_err());
+        Ok(())
+    }
+}
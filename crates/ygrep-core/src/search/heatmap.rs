@@ -0,0 +1,308 @@
+//! Where hits concentrate (`--heatmap`): hit counts aggregated along a dimension (the
+//! directory tree, extension, file kind or git author), drawn as bars
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::results::SearchHit;
+use super::todos::UNKNOWN_AUTHOR;
+use crate::error::{Result, YgrepError};
+use crate::fs::{line_authors, FileKind};
+
+const BAR_WIDTH: usize = 20;
+
+/// What hits are aggregated by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapDimension {
+    /// The directory tree, cut off after N path segments (the whole path by default)
+    Directory(Option<usize>),
+    /// File extension (`(none)` without one)
+    Extension,
+    /// File kind (source, test, config, docs, generated), as far as the path and snippet
+    /// tell
+    Kind,
+    /// Who last changed the hit's first matching line (git blame)
+    Author,
+}
+
+impl HeatmapDimension {
+    /// Names accepted by `parse`
+    pub const NAMES: [&'static str; 4] = ["dir", "ext", "kind", "author"];
+
+    /// `dir` (cut off at `depth` segments), `ext`, `kind` or `author`
+    pub fn parse(name: &str, depth: Option<usize>) -> Result<Self> {
+        Ok(match name {
+            "dir" | "directory" => Self::Directory(depth),
+            "ext" | "extension" => Self::Extension,
+            "kind" => Self::Kind,
+            "author" => Self::Author,
+            _ => {
+                return Err(YgrepError::Search(format!(
+                    "Unknown heatmap dimension '{}' (expected {})",
+                    name,
+                    Self::NAMES.join(", ")
+                )))
+            }
+        })
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Directory(_) => "",
+            Self::Extension => " by extension",
+            Self::Kind => " by kind",
+            Self::Author => " by author",
+        }
+    }
+}
+
+/// A directory, extension, kind or author with the hits under it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HeatmapNode {
+    pub name: String,
+    pub count: usize,
+    /// Deeper directories were cut off
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Largest first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<HeatmapNode>,
+}
+
+impl HeatmapNode {
+    /// Count a hit under `keys`, the last of which was cut off from deeper ones with `cut`
+    fn add(&mut self, keys: &[String], cut: bool) {
+        self.count += 1;
+        let Some((key, rest)) = keys.split_first() else {
+            self.truncated |= cut;
+            return;
+        };
+        let child = match self.children.iter().position(|child| child.name == *key) {
+            Some(i) => &mut self.children[i],
+            None => {
+                self.children.push(HeatmapNode {
+                    name: key.clone(),
+                    ..Default::default()
+                });
+                self.children.last_mut().unwrap()
+            }
+        };
+        child.add(rest, cut);
+    }
+
+    fn sort(&mut self) {
+        self.children
+            .sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        self.children.iter_mut().for_each(Self::sort);
+    }
+
+    fn max_count(&self) -> usize {
+        self.children
+            .iter()
+            .map(Self::max_count)
+            .fold(self.count, usize::max)
+    }
+
+    fn label(&self) -> String {
+        match (self.children.is_empty(), self.truncated) {
+            (true, false) => self.name.clone(),
+            (true, true) => format!("{}/...", self.name),
+            (false, _) => format!("{}/", self.name),
+        }
+    }
+}
+
+/// Hit counts along one dimension
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Heatmap {
+    #[serde(skip)]
+    pub dimension: HeatmapDimension,
+    /// Hits aggregated
+    pub total: usize,
+    /// Largest first (a tree for `Directory`)
+    pub nodes: Vec<HeatmapNode>,
+}
+
+impl Heatmap {
+    /// Aggregate `hits` along `dimension`; authors are blamed from the hits' absolute paths
+    pub fn of_hits(hits: &[SearchHit], dimension: HeatmapDimension) -> Self {
+        let mut root = HeatmapNode::default();
+        let mut authors: HashMap<&str, Option<Vec<String>>> = HashMap::new();
+        for hit in hits {
+            let mut cut = false;
+            let keys = match dimension {
+                HeatmapDimension::Directory(depth) => {
+                    let segments: Vec<String> = hit
+                        .path
+                        .split('/')
+                        .filter(|segment| !segment.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                    if segments.is_empty() {
+                        continue;
+                    }
+                    let depth = depth.unwrap_or(segments.len()).max(1);
+                    cut = segments.len() > depth;
+                    segments.into_iter().take(depth).collect()
+                }
+                HeatmapDimension::Extension => vec![Path::new(&hit.path)
+                    .extension()
+                    .map(|e| e.to_string_lossy().to_lowercase())
+                    .unwrap_or_else(|| "(none)".to_string())],
+                HeatmapDimension::Kind => {
+                    vec![FileKind::classify(Path::new(&hit.path), &hit.snippet).to_string()]
+                }
+                HeatmapDimension::Author => {
+                    let lines = hit.abs_path.as_deref().and_then(|abs_path| {
+                        authors
+                            .entry(abs_path)
+                            .or_insert_with(|| blame(Path::new(abs_path)))
+                            .as_ref()
+                    });
+                    let line = hit.match_line.unwrap_or(hit.line_start).max(1) as usize;
+                    vec![lines
+                        .and_then(|lines| lines.get(line - 1))
+                        .cloned()
+                        .unwrap_or_else(|| UNKNOWN_AUTHOR.to_string())]
+                }
+            };
+            root.add(&keys, cut);
+        }
+        root.sort();
+        Self {
+            dimension,
+            total: root.count,
+            nodes: root.children,
+        }
+    }
+
+    /// Indented tree (flat for dimensions other than `Directory`) with a count and a bar
+    /// per node, scaled to the largest
+    pub fn format_tree(&self) -> String {
+        if self.total == 0 {
+            return "# 0 hits\n".to_string();
+        }
+        let max_count = self
+            .nodes
+            .iter()
+            .map(HeatmapNode::max_count)
+            .max()
+            .unwrap_or(0);
+        let count_width = max_count.max(1).to_string().len();
+        let mut lines = Vec::new();
+        tree_lines(&self.nodes, "", &mut lines);
+        let label_width = lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0);
+
+        let mut output = format!("# {} hits{}\n\n", self.total, self.dimension.describe());
+        for (line, count) in lines {
+            output.push_str(&format!(
+                "{:<label_width$}  {:>count_width$} {}",
+                line,
+                count,
+                bar(count, max_count)
+            ));
+            output.truncate(output.trim_end().len());
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Format as JSON
+    pub fn format_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+/// `(line, count)` per node, depth first, with `|-` and `` `- `` connectors
+fn tree_lines(nodes: &[HeatmapNode], prefix: &str, lines: &mut Vec<(String, usize)>) {
+    for (i, node) in nodes.iter().enumerate() {
+        let is_last = i + 1 == nodes.len();
+        let connector = if is_last { "`- " } else { "|- " };
+        lines.push((
+            format!("{}{}{}", prefix, connector, node.label()),
+            node.count,
+        ));
+        let branch = if is_last { "   " } else { "|  " };
+        tree_lines(&node.children, &format!("{}{}", prefix, branch), lines);
+    }
+}
+
+fn bar(count: usize, max_count: usize) -> String {
+    if max_count == 0 {
+        return String::new();
+    }
+    let len = (count as f64 / max_count as f64 * BAR_WIDTH as f64).round() as usize;
+    "#".repeat(if count > 0 { len.max(1) } else { len })
+}
+
+/// Authors of the lines of the file at `abs_path`
+fn blame(abs_path: &Path) -> Option<Vec<String>> {
+    let name = abs_path.file_name()?.to_str()?;
+    line_authors(abs_path.parent()?, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(path: &str) -> SearchHit {
+        SearchHit {
+            path: path.to_string(),
+            line_start: 1,
+            line_end: 1,
+            snippet: "example".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_heatmap_dimensions() {
+        let hits = vec![
+            hit("src/api/auth.rs"),
+            hit("src/api/users.rs"),
+            hit("src/main.rs"),
+            hit("tests/auth_test.py"),
+            hit("Makefile"),
+        ];
+
+        let heatmap = Heatmap::of_hits(&hits, HeatmapDimension::Directory(Some(1)));
+        let top: Vec<(&str, usize, bool)> = heatmap
+            .nodes
+            .iter()
+            .map(|node| (node.name.as_str(), node.count, node.truncated))
+            .collect();
+        assert_eq!(
+            top,
+            vec![("src", 3, true), ("Makefile", 1, false), ("tests", 1, true)]
+        );
+        let tree = heatmap.format_tree();
+        assert!(tree.starts_with("# 5 hits\n\n|- src/...    3 ####################\n"));
+        assert!(tree.ends_with("`- tests/...  1 #######\n"));
+
+        let heatmap = Heatmap::of_hits(&hits, HeatmapDimension::Extension);
+        let exts: Vec<(&str, usize)> = heatmap
+            .nodes
+            .iter()
+            .map(|node| (node.name.as_str(), node.count))
+            .collect();
+        assert_eq!(exts, vec![("rs", 3), ("(none)", 1), ("py", 1)]);
+        assert!(heatmap.format_tree().starts_with("# 5 hits by extension\n"));
+
+        let heatmap = Heatmap::of_hits(&hits, HeatmapDimension::Kind);
+        assert!(heatmap
+            .nodes
+            .iter()
+            .any(|node| node.name == "test" && node.count == 1));
+
+        // Hits without an absolute path can't be blamed
+        let heatmap = Heatmap::of_hits(&hits, HeatmapDimension::Author);
+        assert_eq!(heatmap.nodes[0].name, UNKNOWN_AUTHOR);
+
+        assert!(HeatmapDimension::parse("lang", None).is_err());
+        assert_eq!(
+            HeatmapDimension::parse("dir", Some(2)).unwrap(),
+            HeatmapDimension::Directory(Some(2))
+        );
+    }
+}
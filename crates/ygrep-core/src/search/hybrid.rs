@@ -1,17 +1,32 @@
 //! Hybrid search combining BM25 and vector search using Reciprocal Rank Fusion
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Instant;
 
-use tantivy::{collector::TopDocs, query::QueryParser, Index};
-
-use super::results::{MatchType, SearchHit, SearchResult};
+use tantivy::collector::{DocSetCollector, TopDocs};
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser};
+use tantivy::Index;
+
+use super::bm25::Bm25Scoring;
+use super::feedback::FeedbackStore;
+use super::query::ParsedQuery;
+use super::results::{
+    MatchType, ScoreBoost, ScoreExplanation, SearchHit, SearchResult, JSON_SCHEMA_VERSION,
+};
+use super::searcher::{
+    hybrid_bm25_query, line_docs_query, regex_semantic_text, PerFileLimit, SearchFilters, Searcher,
+};
+use super::snippet::SnippetWindow;
+use super::synonyms::Synonyms;
 use crate::config::SearchConfig;
 use crate::embeddings::{EmbeddingCache, EmbeddingModel};
 use crate::error::Result;
 use crate::index::schema::SchemaFields;
-use crate::index::VectorIndex;
+use crate::index::{Stemming, VectorIndex};
+
+/// Most candidates fetched from each method when filters drop fetched results
+const MAX_FETCH_LIMIT: usize = 10_000;
 
 /// Hybrid searcher combining BM25 text search and vector similarity search
 pub struct HybridSearcher {
@@ -21,6 +36,11 @@ pub struct HybridSearcher {
     vector_index: Arc<VectorIndex>,
     embedding_model: Arc<EmbeddingModel>,
     embedding_cache: Arc<EmbeddingCache>,
+    synonyms: Option<Synonyms>,
+    feedback: Option<FeedbackStore>,
+    bm25: Bm25Scoring,
+    /// Prose files indexed with stemmed words (`indexer.stemming`)
+    stemming: Option<Stemming>,
 }
 
 impl HybridSearcher {
@@ -34,6 +54,8 @@ impl HybridSearcher {
     ) -> Self {
         let schema = index.schema();
         let fields = SchemaFields::new(&schema);
+        let synonyms = config.expand_synonyms.then(|| Synonyms::load(&config));
+        let bm25 = Bm25Scoring::from_config(&config);
 
         Self {
             config,
@@ -42,37 +64,165 @@ impl HybridSearcher {
             vector_index,
             embedding_model,
             embedding_cache,
+            synonyms,
+            feedback: None,
+            bm25,
+            stemming: None,
         }
     }
 
+    /// Use recorded relevance feedback as a per-document prior during fusion
+    pub fn with_feedback(mut self, feedback: FeedbackStore) -> Self {
+        self.feedback = (!feedback.is_empty()).then_some(feedback);
+        self
+    }
+
+    /// Also match prose files by stemmed words, as the index was built with them
+    pub fn with_stemming(mut self, stemming: Option<Stemming>) -> Self {
+        if let Some(ref stemming) = stemming {
+            stemming.register(self.index.tokenizers());
+        }
+        self.stemming = stemming;
+        self
+    }
+
     /// Perform hybrid search combining BM25 and vector search
-    pub fn search(&self, query: &str, limit: Option<usize>) -> Result<SearchResult> {
+    ///
+    /// `filters` and field qualifiers (`path:`, `ext:`, `lang:`, `content:`) restrict both
+    /// the BM25 query and the vector candidates before fusion, so they don't starve the
+    /// limit; negated terms (`-bar`, `!bar`) exclude documents, `"a b"~N` requires
+    /// nearby terms and `in:comments` (`strings`, `code`) the query's words there.
+    pub fn search(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+        filters: SearchFilters,
+    ) -> Result<SearchResult> {
+        self.search_with(query, limit, filters, false)
+    }
+
+    /// Hybrid search with a regex: the regex search (as `Searcher::search_regex` runs it)
+    /// takes the place of BM25, and the words of the pattern are embedded for the vector
+    /// search. Each hit's `match_type` tells which of the two found it.
+    pub fn search_regex(
+        &self,
+        pattern: &str,
+        limit: Option<usize>,
+        filters: SearchFilters,
+    ) -> Result<SearchResult> {
+        self.search_with(pattern, limit, filters, true)
+    }
+
+    fn search_with(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+        filters: SearchFilters,
+        use_regex: bool,
+    ) -> Result<SearchResult> {
         let start = Instant::now();
+
+        let parsed = ParsedQuery::parse(query, self.config.negation);
+        // (the regex search merges the field qualifiers itself)
+        let text_filters = filters.clone();
+        let mut filters = filters;
+        parsed.merge_into(&mut filters);
+        let restriction = HybridRestriction {
+            filter: filters.index_query(&self.index, &self.fields)?,
+            line_docs: line_docs_query(&self.fields),
+        };
+        let search_text = if use_regex {
+            regex_semantic_text(&parsed.text)
+        } else {
+            parsed.search_text()
+        };
+        let pattern = query;
+        let query = search_text.as_str();
+
         let limit = limit
             .unwrap_or(self.config.default_limit)
             .min(self.config.max_limit);
 
-        // Fetch more results from each method for better fusion
-        let fetch_limit = limit * 3;
-
-        // Run BM25 search
-        let bm25_results = self.bm25_search(query, fetch_limit)?;
-
-        // Run vector search
-        let vector_results = self.vector_search(query, fetch_limit)?;
+        // (the regex search applies the scopes itself)
+        let words: Vec<String> = if use_regex {
+            Vec::new()
+        } else {
+            parsed
+                .text
+                .split_whitespace()
+                .map(|word| word.to_lowercase())
+                .collect()
+        };
+
+        // Fetch more results from each method for better fusion (and filtering)
+        let mut fetch_limit = if parsed.has_constraints() {
+            limit * 10
+        } else {
+            limit * 3
+        };
+
+        // Negated terms, proximity and `max_per_file` drop results after retrieval: fetch
+        // more until `limit` hits survive or neither method has more to give, but never
+        // more than the index holds (or `MAX_FETCH_LIMIT`)
+        let max_fetch = (self.index.reader()?.searcher().num_docs() as usize)
+            .min(MAX_FETCH_LIMIT)
+            .max(fetch_limit);
+        let (fused, incomplete) = loop {
+            // Run BM25 (or regex) search
+            let (mut bm25_results, incomplete) = if use_regex {
+                self.regex_search(pattern, fetch_limit, text_filters.clone())?
+            } else {
+                (self.bm25_search(query, fetch_limit, &restriction)?, None)
+            };
+
+            // Run vector search
+            let mut vector_results = self.vector_search(query, fetch_limit, &restriction)?;
+            // (a regex scan cut short by --timeout/--max-scanned isn't run again)
+            let exhausted = (bm25_results.len() < fetch_limit
+                && vector_results.len() < fetch_limit)
+                || incomplete.is_some();
+
+            // Drop documents with negated terms (`-bar`) or failing proximity (`"a b"~N`),
+            // and with `in:` scopes text results without the query's words in scope
+            bm25_results.retain(|result| passes(&parsed, result, &words));
+            vector_results.retain(|result| passes(&parsed, result, &[]));
+
+            // Fuse results using Reciprocal Rank Fusion
+            let fused = self.reciprocal_rank_fusion(
+                bm25_results,
+                vector_results,
+                self.config.bm25_weight,
+                self.config.vector_weight,
+                query,
+            );
+
+            let mut per_file = PerFileLimit::new(self.config.max_per_file);
+            let survivors = fused
+                .iter()
+                .filter(|hit| per_file.admit(&hit.path))
+                .take(limit)
+                .count();
+            if survivors >= limit || exhausted || fetch_limit >= max_fetch {
+                break (fused, incomplete);
+            }
+            fetch_limit = (fetch_limit * 4).min(max_fetch);
+        };
 
-        // Fuse results using Reciprocal Rank Fusion
-        let fused = self.reciprocal_rank_fusion(
-            bm25_results,
-            vector_results,
-            self.config.bm25_weight,
-            self.config.vector_weight,
-            query,
-        );
+        // Keep every fused path for facets before truncating
+        let matched_paths: Vec<String> = if self.config.facets {
+            fused.iter().map(|hit| hit.path.clone()).collect()
+        } else {
+            Vec::new()
+        };
 
-        // Take top results
+        // Take top results, at most `max_per_file` from any one file
         // Note: RRF scores are typically small (max ~0.016 with K=60), so we don't apply min_score filter
-        let hits: Vec<SearchHit> = fused.into_iter().take(limit).collect();
+        let mut per_file = PerFileLimit::new(self.config.max_per_file);
+        let hits: Vec<SearchHit> = fused
+            .into_iter()
+            .filter(|hit| per_file.admit(&hit.path))
+            .take(limit)
+            .collect();
 
         // Count text vs semantic hits
         let text_hits = hits
@@ -87,26 +237,47 @@ impl HybridSearcher {
         let query_time_ms = start.elapsed().as_millis() as u64;
 
         Ok(SearchResult {
-            total: hits.len(),
+            schema_version: JSON_SCHEMA_VERSION,
+            total: hits.len().max(matched_paths.len()),
             hits,
             query_time_ms,
             text_hits,
             semantic_hits,
+            matched_paths,
+            facets: None,
+            index: None,
+            incomplete,
+            suggestions: Vec::new(),
+            workspace: None,
+            stats: None,
         })
     }
 
     /// BM25 full-text search
-    fn bm25_search(&self, query: &str, limit: usize) -> Result<Vec<RankedResult>> {
+    fn bm25_search(
+        &self,
+        query: &str,
+        limit: usize,
+        restriction: &HybridRestriction,
+    ) -> Result<Vec<RankedResult>> {
         let reader = self.index.reader()?;
         let searcher = reader.searcher();
 
-        let query_parser = QueryParser::for_index(&self.index, vec![self.fields.content]);
+        // (prose files are also found by the stems of the words)
+        let mut query_fields = vec![self.fields.content];
+        if self.stemming.is_some() {
+            query_fields.push(self.fields.prose);
+        }
+        let query_parser = QueryParser::for_index(&self.index, query_fields);
 
-        // Wrap query in quotes for literal phrase matching (like grep)
-        let quoted_query = format!("\"{}\"", query.replace('"', "\\\""));
-        let (tantivy_query, _errors) = query_parser.parse_query_lenient(&quoted_query);
+        // The query as a phrase (like grep), optionally also with synonyms substituted
+        let query_str = hybrid_bm25_query(query, self.synonyms.as_ref());
+        let (tantivy_query, _errors) = query_parser.parse_query_lenient(&query_str);
+        let tantivy_query = restriction.apply(tantivy_query);
 
-        let top_docs = searcher.search(&tantivy_query, &TopDocs::with_limit(limit))?;
+        let top_docs = self
+            .bm25
+            .top_docs(&searcher, &tantivy_query, self.fields.content, limit)?;
 
         let mut results = Vec::with_capacity(top_docs.len());
 
@@ -123,18 +294,79 @@ impl HybridSearcher {
                 doc_id: doc_id.clone(),
                 path,
                 content,
+                regions: extract_text(&doc, self.fields.regions),
                 line_start,
                 is_chunk: !chunk_id.is_empty(),
+                truncated: extract_bool(&doc, self.fields.truncated).unwrap_or(false),
+                submodule: extract_text(&doc, self.fields.submodule).filter(|s| !s.is_empty()),
                 rank: rank + 1,
                 score: *score,
+                distance: None,
+                match_line: None,
+                match_column: None,
             });
         }
 
         Ok(results)
     }
 
-    /// Vector similarity search
-    fn vector_search(&self, query: &str, limit: usize) -> Result<Vec<RankedResult>> {
+    /// Regex search over the files passing the filters, ranked as `Searcher` ranks it;
+    /// hits keep the line and column of their first match. Also returns why the scan
+    /// stopped short, if it did.
+    fn regex_search(
+        &self,
+        pattern: &str,
+        limit: usize,
+        filters: SearchFilters,
+    ) -> Result<(Vec<RankedResult>, Option<String>)> {
+        // Fusion caps hits per file and collects facets, from the fused list
+        let config = SearchConfig {
+            max_limit: limit.max(self.config.max_limit),
+            max_per_file: None,
+            facets: false,
+            explain: false,
+            ..self.config.clone()
+        };
+        let text = Searcher::new(config, self.index.clone()).search_filtered(
+            pattern,
+            Some(limit),
+            filters,
+            true,
+        )?;
+
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+        let mut results = Vec::with_capacity(text.hits.len());
+        for (rank, hit) in text.hits.into_iter().enumerate() {
+            let Some(doc) = self.lookup_by_doc_id(&searcher, &hit.doc_id)? else {
+                continue;
+            };
+            results.push(RankedResult {
+                doc_id: hit.doc_id,
+                path: doc.path,
+                content: doc.content,
+                regions: doc.regions,
+                line_start: doc.line_start,
+                is_chunk: doc.is_chunk,
+                truncated: doc.truncated,
+                submodule: doc.submodule,
+                rank: rank + 1,
+                score: hit.score,
+                distance: None,
+                match_line: hit.match_line,
+                match_column: hit.match_column,
+            });
+        }
+        Ok((results, text.incomplete))
+    }
+
+    /// Vector similarity search, among the vectors of documents passing the filters
+    fn vector_search(
+        &self,
+        query: &str,
+        limit: usize,
+        restriction: &HybridRestriction,
+    ) -> Result<Vec<RankedResult>> {
         // Check if vector index has data
         if self.vector_index.is_empty() {
             return Ok(vec![]);
@@ -147,13 +379,19 @@ impl HybridSearcher {
                 .unwrap_or_else(|_| vec![0.0; 384])
         });
 
-        // Search vector index
-        let neighbors = self.vector_index.search(&query_embedding, limit)?;
-
         // Look up full document info from tantivy
         let reader = self.index.reader()?;
         let searcher = reader.searcher();
 
+        // Search vector index
+        let neighbors = match restriction.doc_ids(&searcher, &self.fields)? {
+            Some(allowed) => {
+                self.vector_index
+                    .search_filtered(&query_embedding, limit, |doc_id| allowed.contains(doc_id))?
+            }
+            None => self.vector_index.search(&query_embedding, limit)?,
+        };
+
         let mut results = Vec::with_capacity(neighbors.len());
 
         for (rank, (_, distance, doc_id)) in neighbors.iter().enumerate() {
@@ -163,10 +401,16 @@ impl HybridSearcher {
                     doc_id: doc_id.clone(),
                     path: hit.path,
                     content: hit.content,
+                    regions: hit.regions,
                     line_start: hit.line_start,
                     is_chunk: hit.is_chunk,
+                    truncated: hit.truncated,
+                    submodule: hit.submodule,
                     rank: rank + 1,
                     score: 1.0 / (1.0 + distance), // Convert distance to similarity
+                    distance: Some(*distance),
+                    match_line: None,
+                    match_column: None,
                 });
             }
         }
@@ -195,10 +439,13 @@ impl HybridSearcher {
             Ok(Some(DocInfo {
                 path: extract_text(&doc, self.fields.path).unwrap_or_default(),
                 content: extract_text(&doc, self.fields.content).unwrap_or_default(),
+                regions: extract_text(&doc, self.fields.regions),
                 line_start: extract_u64(&doc, self.fields.line_start).unwrap_or(1),
                 is_chunk: !extract_text(&doc, self.fields.chunk_id)
                     .unwrap_or_default()
                     .is_empty(),
+                truncated: extract_bool(&doc, self.fields.truncated).unwrap_or(false),
+                submodule: extract_text(&doc, self.fields.submodule).filter(|s| !s.is_empty()),
             }))
         } else {
             Ok(None)
@@ -223,12 +470,11 @@ impl HybridSearcher {
             let rrf_score = bm25_weight / (K + result.rank as f32);
             let entry = combined_scores
                 .entry(result.doc_id.clone())
-                .or_insert_with(|| FusedScore {
-                    result: result.clone(),
-                    bm25_rrf: 0.0,
-                    vector_rrf: 0.0,
-                });
+                .or_insert_with(|| FusedScore::new(result.clone()));
             entry.bm25_rrf = rrf_score;
+            entry.explanation.bm25_rank = Some(result.rank);
+            entry.explanation.bm25_score = Some(result.score);
+            entry.explanation.bm25_rrf = Some(rrf_score);
         }
 
         // Add vector results
@@ -236,21 +482,41 @@ impl HybridSearcher {
             let rrf_score = vector_weight / (K + result.rank as f32);
             let entry = combined_scores
                 .entry(result.doc_id.clone())
-                .or_insert_with(|| FusedScore {
-                    result: result.clone(),
-                    bm25_rrf: 0.0,
-                    vector_rrf: 0.0,
-                });
+                .or_insert_with(|| FusedScore::new(result.clone()));
             entry.vector_rrf = rrf_score;
+            entry.explanation.vector_rank = Some(result.rank);
+            entry.explanation.vector_distance = result.distance;
+            entry.explanation.vector_rrf = Some(rrf_score);
         }
 
         // Calculate final scores and convert to SearchHit
+        let window = SnippetWindow::from_config(&self.config);
         let mut hits: Vec<SearchHit> = combined_scores
             .into_values()
-            .map(|fused| {
-                let total_score = fused.bm25_rrf + fused.vector_rrf;
-                let (snippet, match_offset, line_count) =
-                    create_relevant_snippet(&fused.result.content, query, 10);
+            .map(|mut fused| {
+                let mut total_score = fused.bm25_rrf + fused.vector_rrf;
+
+                // Nudge files that were marked relevant (or not) for similar searches
+                let prior = self
+                    .feedback
+                    .as_ref()
+                    .and_then(|feedback| feedback.prior(query, &fused.result.path));
+                if let Some(factor) = prior {
+                    total_score *= factor;
+                    fused.explanation.boosts.push(ScoreBoost {
+                        reason: "feedback".to_string(),
+                        factor,
+                    });
+                }
+
+                // Regex hits are cut around their match, the rest around the query words
+                let (snippet, match_offset, line_count) = match fused.result.match_line {
+                    Some(line) => window.extract(
+                        &fused.result.content,
+                        Some(line.saturating_sub(fused.result.line_start) as usize),
+                    ),
+                    None => create_relevant_snippet(&fused.result.content, query, &window),
+                };
 
                 // Adjust line numbers to reflect the snippet position
                 let actual_line_start = fused.result.line_start + match_offset as u64;
@@ -266,13 +532,20 @@ impl HybridSearcher {
 
                 SearchHit {
                     path: fused.result.path,
+                    abs_path: None,
                     line_start: actual_line_start,
                     line_end: actual_line_end,
+                    match_line: fused.result.match_line,
+                    match_column: fused.result.match_column,
                     snippet,
                     score: total_score,
                     is_chunk: fused.result.is_chunk,
                     doc_id: fused.result.doc_id,
                     match_type,
+                    explanation: self.config.explain.then_some(fused.explanation),
+                    truncated: fused.result.truncated,
+                    submodule: fused.result.submodule,
+                    chunk: None,
                 }
             })
             .collect();
@@ -288,25 +561,96 @@ impl HybridSearcher {
     }
 }
 
+/// Whether a result passes the negated terms and proximity clauses of `parsed` in the part
+/// of its content the `in:` scopes cover; with scopes that part must also hold each of
+/// `words`, and documents the lexer doesn't know are dropped
+fn passes(parsed: &ParsedQuery, result: &RankedResult, words: &[String]) -> bool {
+    let Some(scoped) = parsed.scoped(&result.content, result.regions.as_deref()) else {
+        return false;
+    };
+    if !parsed.allows(&scoped) {
+        return false;
+    }
+    if parsed.scopes.is_empty() {
+        return true;
+    }
+    let lower = scoped.to_lowercase();
+    words.iter().all(|word| lower.contains(word.as_str()))
+}
+
 /// Intermediate result with ranking info
 #[derive(Debug, Clone)]
 struct RankedResult {
     doc_id: String,
     path: String,
     content: String,
+    /// Comments and strings of the content (`index::regions`), if lexed
+    regions: Option<String>,
     line_start: u64,
     is_chunk: bool,
+    truncated: bool,
+    submodule: Option<String>,
     rank: usize,
-    #[allow(dead_code)]
     score: f32,
+    /// Embedding distance (vector results only)
+    distance: Option<f32>,
+    /// Line and column of the first match (regex results only)
+    match_line: Option<u64>,
+    match_column: Option<u64>,
 }
 
 /// Document info from lookup
 struct DocInfo {
     path: String,
     content: String,
+    regions: Option<String>,
     line_start: u64,
     is_chunk: bool,
+    truncated: bool,
+    submodule: Option<String>,
+}
+
+/// Search filters, resolved once per search for both retrieval methods
+struct HybridRestriction {
+    /// Index-side query for the filters (`None` without any)
+    filter: Option<Box<dyn Query>>,
+    /// Single-line documents (`indexer.line_index`), which only grep-style searches use
+    line_docs: Box<dyn Query>,
+}
+
+impl HybridRestriction {
+    /// Restrict a BM25 query to the files and chunks passing the filters
+    fn apply(&self, query: Box<dyn Query>) -> Box<dyn Query> {
+        let mut clauses = vec![
+            (Occur::Must, query),
+            (Occur::MustNot, self.line_docs.box_clone()),
+        ];
+        if let Some(ref filter) = self.filter {
+            clauses.push((Occur::Must, filter.box_clone()));
+        }
+        Box::new(BooleanQuery::new(clauses))
+    }
+
+    /// doc_ids of the files and chunks passing the filters, which vector neighbors must
+    /// be among (`None` without any filters)
+    fn doc_ids(
+        &self,
+        searcher: &tantivy::Searcher,
+        fields: &SchemaFields,
+    ) -> Result<Option<HashSet<String>>> {
+        let Some(ref filter) = self.filter else {
+            return Ok(None);
+        };
+        let filter = self.apply(filter.box_clone());
+        let mut doc_ids = HashSet::new();
+        for address in searcher.search(filter.as_ref(), &DocSetCollector)? {
+            let doc = searcher.doc(address)?;
+            if let Some(doc_id) = extract_text(&doc, fields.doc_id) {
+                doc_ids.insert(doc_id);
+            }
+        }
+        Ok(Some(doc_ids))
+    }
 }
 
 /// Fused score from multiple retrieval methods
@@ -314,6 +658,18 @@ struct FusedScore {
     result: RankedResult,
     bm25_rrf: f32,
     vector_rrf: f32,
+    explanation: ScoreExplanation,
+}
+
+impl FusedScore {
+    fn new(result: RankedResult) -> Self {
+        Self {
+            result,
+            bm25_rrf: 0.0,
+            vector_rrf: 0.0,
+            explanation: ScoreExplanation::default(),
+        }
+    }
 }
 
 /// Extract text value from a document
@@ -338,43 +694,31 @@ fn extract_u64(doc: &tantivy::TantivyDocument, field: tantivy::schema::Field) ->
     })
 }
 
+/// Extract bool value from a document
+fn extract_bool(doc: &tantivy::TantivyDocument, field: tantivy::schema::Field) -> Option<bool> {
+    doc.get_first(field).and_then(|v| {
+        if let tantivy::schema::OwnedValue::Bool(b) = v {
+            Some(*b)
+        } else {
+            None
+        }
+    })
+}
+
 /// Create a snippet showing lines relevant to the query
 /// Returns (snippet, line_offset_from_start, line_count)
-fn create_relevant_snippet(content: &str, query: &str, max_lines: usize) -> (String, usize, usize) {
-    let lines: Vec<&str> = content.lines().collect();
+fn create_relevant_snippet(
+    content: &str,
+    query: &str,
+    window: &SnippetWindow,
+) -> (String, usize, usize) {
     let query_lower = query.to_lowercase();
     let query_terms: Vec<&str> = query_lower.split_whitespace().collect();
 
-    // Find lines that contain any query term
-    let mut matching_indices: Vec<usize> = Vec::new();
-    for (i, line) in lines.iter().enumerate() {
+    // Find the first line that contains any query term
+    let first_match = content.lines().position(|line| {
         let line_lower = line.to_lowercase();
-        if query_terms.iter().any(|term| line_lower.contains(term)) {
-            matching_indices.push(i);
-        }
-    }
-
-    if matching_indices.is_empty() {
-        // No direct matches, return first lines
-        let snippet = lines
-            .iter()
-            .take(max_lines)
-            .copied()
-            .collect::<Vec<_>>()
-            .join("\n");
-        let line_count = snippet.lines().count();
-        return (snippet, 0, line_count);
-    }
-
-    // Get context around the first match
-    let first_match = matching_indices[0];
-    let context_before = 2;
-    let context_after = max_lines.saturating_sub(context_before + 1);
-
-    let start = first_match.saturating_sub(context_before);
-    let end = (first_match + context_after + 1).min(lines.len());
-
-    let snippet = lines[start..end].join("\n");
-    let line_count = end - start;
-    (snippet, start, line_count)
+        query_terms.iter().any(|term| line_lower.contains(term))
+    });
+    window.extract(content, first_match)
 }
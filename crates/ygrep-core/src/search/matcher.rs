@@ -0,0 +1,196 @@
+//! Regex engines for `--regex` searches
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, YgrepError};
+
+/// Regex engine used for regex searches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegexEngine {
+    /// The `regex` crate: linear time, no lookarounds or backreferences
+    #[default]
+    Default,
+    /// The `fancy-regex` crate: backtracking, with lookarounds and backreferences in
+    /// Perl/PCRE syntax, but not PCRE2 itself (requires the `fancy` feature)
+    Fancy,
+}
+
+impl RegexEngine {
+    /// Whether this build can use the engine
+    pub fn is_available(self) -> bool {
+        match self {
+            RegexEngine::Default => true,
+            RegexEngine::Fancy => cfg!(feature = "fancy"),
+        }
+    }
+}
+
+impl std::str::FromStr for RegexEngine {
+    type Err = YgrepError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "default" | "rust" => Ok(RegexEngine::Default),
+            "fancy" | "fancy-regex" => Ok(RegexEngine::Fancy),
+            other => Err(YgrepError::Config(format!(
+                "Unknown regex engine '{}' (expected 'default' or 'fancy')",
+                other
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for RegexEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegexEngine::Default => write!(f, "default"),
+            RegexEngine::Fancy => write!(f, "fancy"),
+        }
+    }
+}
+
+/// A compiled pattern for one of the engines
+pub(crate) struct RegexMatcher {
+    pattern: Pattern,
+    /// Texts the pattern couldn't be run on
+    failures: AtomicUsize,
+    /// Why it couldn't, the first time
+    first_failure: OnceLock<String>,
+}
+
+enum Pattern {
+    Default(regex::Regex),
+    #[cfg(feature = "fancy")]
+    Fancy(fancy_regex::Regex),
+}
+
+impl RegexMatcher {
+    /// Compile a pattern, case-insensitive unless `case_sensitive` is set
+    pub fn new(pattern: &str, engine: RegexEngine, case_sensitive: bool) -> Result<Self> {
+        let invalid =
+            |e: &dyn std::fmt::Display| YgrepError::Search(format!("Invalid regex pattern: {}", e));
+
+        let pattern = match engine {
+            RegexEngine::Default => regex::RegexBuilder::new(pattern)
+                .case_insensitive(!case_sensitive)
+                .build()
+                .map(Pattern::Default)
+                .map_err(|e| invalid(&e))?,
+            #[cfg(feature = "fancy")]
+            // The builder's case_insensitive flag doesn't reach backtracking parts of
+            // the pattern, so use an inline flag instead
+            RegexEngine::Fancy => {
+                let flags = if case_sensitive { "" } else { "(?i)" };
+                fancy_regex::Regex::new(&format!("{}{}", flags, pattern))
+                    .map(Pattern::Fancy)
+                    .map_err(|e| invalid(&e))?
+            }
+            #[cfg(not(feature = "fancy"))]
+            RegexEngine::Fancy => {
+                return Err(YgrepError::Search(
+                    "The fancy regex engine is not available in this build (rebuild with the `fancy` feature)"
+                        .to_string(),
+                ))
+            }
+        };
+        Ok(Self {
+            pattern,
+            failures: AtomicUsize::new(0),
+            first_failure: OnceLock::new(),
+        })
+    }
+
+    /// Whether the pattern matches anywhere in the text
+    pub fn is_match(&self, text: &str) -> bool {
+        match &self.pattern {
+            Pattern::Default(regex) => regex.is_match(text),
+            // Texts the pattern fails on (past the backtracking limit) don't match, and
+            // are reported by `failures`
+            #[cfg(feature = "fancy")]
+            Pattern::Fancy(regex) => regex.is_match(text).unwrap_or_else(|e| {
+                self.fail(&e);
+                false
+            }),
+        }
+    }
+
+    /// Byte offset where the first match in the text starts
+    pub fn find(&self, text: &str) -> Option<usize> {
+        match &self.pattern {
+            Pattern::Default(regex) => regex.find(text).map(|m| m.start()),
+            #[cfg(feature = "fancy")]
+            Pattern::Fancy(regex) => match regex.find(text) {
+                Ok(found) => found.map(|m| m.start()),
+                Err(e) => {
+                    self.fail(&e);
+                    None
+                }
+            },
+        }
+    }
+
+    /// Why some texts couldn't be checked, if any were: the fancy engine gives up on
+    /// patterns that backtrack too much
+    pub fn failures(&self) -> Option<String> {
+        let count = self.failures.load(Ordering::Relaxed);
+        let reason = self.first_failure.get()?;
+        Some(format!(
+            "the regex could not be run on {} document{}: {}",
+            count,
+            if count == 1 { "" } else { "s" },
+            reason
+        ))
+    }
+
+    #[cfg(feature = "fancy")]
+    fn fail(&self, error: &fancy_regex::Error) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        self.first_failure.get_or_init(|| error.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engines() {
+        assert_eq!("Fancy".parse::<RegexEngine>().unwrap(), RegexEngine::Fancy);
+        assert!("pcre2".parse::<RegexEngine>().is_err());
+        assert!("perl".parse::<RegexEngine>().is_err());
+
+        let default = RegexMatcher::new(r"ba+r", RegexEngine::Default, false).unwrap();
+        assert_eq!(default.find("foo\nBAAR"), Some(4));
+        assert_eq!(default.find("foo"), None);
+
+        // Lookbehind isn't supported by the default engine
+        assert!(RegexMatcher::new(r"(?<!foo)bar", RegexEngine::Default, false).is_err());
+
+        let fancy = RegexMatcher::new(r"(?<!foo)bar", RegexEngine::Fancy, false);
+        if RegexEngine::Fancy.is_available() {
+            let fancy = fancy.unwrap();
+            assert!(fancy.is_match("let BAR = 1"));
+            assert!(!fancy.is_match("foobar"));
+            assert_eq!(fancy.find("foobar bar"), Some(7));
+            assert_eq!(fancy.failures(), None);
+        } else {
+            assert!(fancy.is_err());
+        }
+    }
+
+    #[cfg(feature = "fancy")]
+    #[test]
+    fn test_fancy_failures() {
+        // Exponential backtracking runs into the engine's limit
+        let fancy = RegexMatcher::new(r"(?:a(?=a)|a)*b", RegexEngine::Fancy, true).unwrap();
+        let text = "a".repeat(40);
+        assert!(!fancy.is_match(&text));
+        assert_eq!(fancy.find(&text), None);
+        let failures = fancy.failures().unwrap();
+        assert!(failures.starts_with("the regex could not be run on 2 documents: "));
+    }
+}
@@ -1,9 +1,38 @@
+mod bm25;
+mod chunks;
+mod feedback;
+mod files;
+mod heatmap;
 #[cfg(feature = "embeddings")]
 mod hybrid;
+mod matcher;
+mod plan;
+mod query;
+mod refs;
 mod results;
+mod saved;
 mod searcher;
+mod snippet;
+mod suggest;
+mod synonyms;
+mod todos;
 
+pub use chunks::{ChunkContext, ExpandedHit, IndexedFile};
+pub use feedback::{DocumentFeedback, FeedbackStore};
+pub use files::{FileMatch, FileMatches};
+pub use heatmap::{Heatmap, HeatmapDimension, HeatmapNode};
 #[cfg(feature = "embeddings")]
 pub use hybrid::HybridSearcher;
-pub use results::{MatchType, SearchHit, SearchResult};
+pub use matcher::RegexEngine;
+pub use plan::{PlanMode, QueryPlan};
+pub use query::{ParsedQuery, Scope};
+pub use refs::{FileRefs, RefKind, RefSite, SymbolRefs};
+pub use results::{
+    DisplayOptions, EmbeddingCacheStats, FacetCount, Facets, IndexFreshness, MatchType, ScoreBoost,
+    ScoreExplanation, SearchHit, SearchResult, WorkspaceStats, JSON_SCHEMA_VERSION,
+};
+pub use saved::{SavedResultSet, LAST_RESULT_SET};
 pub use searcher::{SearchFilters, Searcher};
+pub use snippet::SnippetWindow;
+pub use synonyms::Synonyms;
+pub use todos::{TodoGroup, TodoGrouping, TodoItem, TodoReport, UNKNOWN_AUTHOR};
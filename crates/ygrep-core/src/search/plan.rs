@@ -0,0 +1,118 @@
+//! Query plans (`ygrep explain-query`): how a query is interpreted and run, without
+//! running it
+
+use serde::Serialize;
+
+/// Which search a query runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanMode {
+    /// Index terms narrow the candidates, which must contain the exact text
+    Literal,
+    /// Index terms rank the candidates, then every document is checked against the regex
+    Regex,
+    /// BM25 phrase search and vector search, fused by reciprocal rank
+    Hybrid,
+}
+
+impl std::fmt::Display for PlanMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlanMode::Literal => write!(f, "literal"),
+            PlanMode::Regex => write!(f, "regex"),
+            PlanMode::Hybrid => write!(f, "hybrid"),
+        }
+    }
+}
+
+/// How a query is interpreted: what is looked up in the index, how candidates are
+/// checked, and which filters and boosts apply
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryPlan {
+    /// The query as given
+    pub query: String,
+    pub mode: PlanMode,
+    /// Text searched for once field qualifiers, negations and proximity clauses are
+    /// taken out
+    pub text: String,
+    /// Terms looked up in the index to find (or rank) candidates
+    pub index_terms: Vec<String>,
+    /// Stemmed terms looked up in prose files (`indexer.stemming`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stemmed: Vec<String>,
+    /// Query words left out of the index lookup and ranking as stopwords
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stopwords: Vec<String>,
+    /// Text embedded for the vector search when it isn't the query text (the words of
+    /// the pattern in a hybrid regex search)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub semantic: Option<String>,
+    /// Synonym clauses OR-ed into the BM25 query (`--synonyms`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub synonyms: Option<String>,
+    /// The exact check candidates must pass (none in hybrid mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_filter: Option<String>,
+    /// When documents beyond the index candidates are scanned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scan: Option<String>,
+    /// Filters from flags and field qualifiers, applied inside the index
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<String>,
+    /// Negated terms; documents containing them are dropped
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub excluded: Vec<String>,
+    /// Proximity clauses (`"a b"~N`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub proximity: Vec<String>,
+    /// Parts of source files matches must be in (`in:comments`, `in:strings`, `in:code`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+    /// Ranking adjustments (BM25 parameters, kind weights, fusion weights, feedback)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub boosts: Vec<String>,
+    /// Whether hybrid search runs
+    pub hybrid: bool,
+    /// Why hybrid search does or doesn't run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hybrid_reason: Option<String>,
+}
+
+impl QueryPlan {
+    /// One `label: value` line per part of the plan, leaving out empty parts
+    pub fn format_text(&self) -> String {
+        let mut lines: Vec<(&str, String)> = vec![
+            ("query", self.query.clone()),
+            ("mode", self.mode.to_string()),
+            ("text", self.text.clone()),
+            ("terms", self.index_terms.join(" ")),
+            ("stemmed", self.stemmed.join(" ")),
+            ("stopwords", self.stopwords.join(" ")),
+            ("semantic", self.semantic.clone().unwrap_or_default()),
+            ("synonyms", self.synonyms.clone().unwrap_or_default()),
+            ("check", self.post_filter.clone().unwrap_or_default()),
+            ("scan", self.scan.clone().unwrap_or_default()),
+            ("filters", self.filters.join("; ")),
+            ("exclude", self.excluded.join(", ")),
+            ("near", self.proximity.join(", ")),
+            ("in", self.scopes.join(", ")),
+            ("boosts", self.boosts.join("; ")),
+        ];
+        let hybrid = if self.hybrid { "yes" } else { "no" };
+        lines.push((
+            "hybrid",
+            match self.hybrid_reason {
+                Some(ref reason) => format!("{} ({})", hybrid, reason),
+                None => hybrid.to_string(),
+            },
+        ));
+
+        let mut output = String::new();
+        for (label, value) in lines {
+            if !value.is_empty() {
+                output.push_str(&format!("{:<10} {}\n", format!("{}:", label), value));
+            }
+        }
+        output
+    }
+}
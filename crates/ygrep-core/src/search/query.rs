@@ -0,0 +1,442 @@
+//! Query string syntax: field qualifiers (`path:auth ext:rs lang:python content:"connection pool"`),
+//! scopes (`in:comments`), negated terms (`foo -bar`, `foo !bar`, with `search.negation`) and
+//! proximity (`"open file"~5`)
+
+use std::borrow::Cow;
+
+use super::searcher::SearchFilters;
+use super::todos::{first_word, is_word_char};
+use crate::index::regions::{RegionKind, Regions};
+
+/// Prefixes recognised at the start of a whitespace-separated token
+const FIELD_PREFIXES: &[&str] = &["path:", "ext:", "lang:", "in:", "content:"];
+
+/// A query string split into its search text and field filters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedQuery {
+    /// Text to search for, with field qualifiers removed
+    pub text: String,
+    /// Values from `ext:` (comma-separated, leading dot optional)
+    pub extensions: Vec<String>,
+    /// Values from `path:`
+    pub paths: Vec<String>,
+    /// Values from `lang:` (comma-separated)
+    pub languages: Vec<String>,
+    /// Terms from `-term` / `!term` (lowercased); documents containing them as whole words
+    /// are excluded
+    pub excluded: Vec<String>,
+    /// Clauses from `"a b"~N`
+    pub proximity: Vec<Proximity>,
+    /// Values from `in:` (comma-separated): where in source files the query must match
+    pub scopes: Vec<Scope>,
+}
+
+/// Part of a source file a search can be limited to (`in:comments`, `in:strings`,
+/// `in:code`), as the lexer tagged it at index time (`index::regions`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Comments,
+    Strings,
+    /// Everything but comments and strings
+    Code,
+}
+
+impl Scope {
+    /// Parse an `in:` value (`comments`, `strings`, `code`; singular forms too)
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "comments" | "comment" => Some(Self::Comments),
+            "strings" | "string" => Some(Self::Strings),
+            "code" => Some(Self::Code),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Comments => "comments",
+            Self::Strings => "strings",
+            Self::Code => "code",
+        }
+    }
+
+    /// Whether text of `kind` (`None`: code) is in the scope
+    fn includes(self, kind: Option<RegionKind>) -> bool {
+        matches!(
+            (self, kind),
+            (Self::Comments, Some(RegionKind::Comment))
+                | (Self::Strings, Some(RegionKind::String))
+                | (Self::Code, None)
+        )
+    }
+}
+
+/// Terms that must occur near each other (`"open file"~5`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proximity {
+    /// Lowercased terms, in query order
+    pub terms: Vec<String>,
+    /// Maximum distance, in tokens for the index query and in lines for the post-check
+    pub distance: u32,
+}
+
+impl ParsedQuery {
+    /// Split field qualifiers out of a query.
+    ///
+    /// Qualifiers must start a token; values run to the next whitespace or, when
+    /// quoted, to the closing quote. Everything else is kept verbatim so literal and
+    /// regex searches still see the exact text, and `"a b"~N` requires the terms within N
+    /// tokens and lines of each other. With `negation`, a token that is `-` or `!` followed
+    /// by a whole word or a quoted phrase excludes it (`-bar`, `!bar`, `-"two words"`);
+    /// without it they are searched for like any other text (`rm -rf`, `if !ready`).
+    /// Prefix a qualifier or negation with `\` to search for it literally (`\path:foo`,
+    /// `\-bar`).
+    pub fn parse(query: &str, negation: bool) -> Self {
+        let mut parsed = Self::default();
+        let mut rest = query;
+
+        while !rest.is_empty() {
+            // Copy whitespace between tokens
+            let ws_len = rest.len() - rest.trim_start().len();
+            parsed.text.push_str(&rest[..ws_len]);
+            rest = &rest[ws_len..];
+            if rest.is_empty() {
+                break;
+            }
+
+            if let Some(escaped) = rest.strip_prefix('\\') {
+                if FIELD_PREFIXES.iter().any(|p| escaped.starts_with(p))
+                    || (negation && is_negation(escaped))
+                {
+                    let end = token_end(escaped);
+                    parsed.text.push_str(&escaped[..end]);
+                    rest = &escaped[end..];
+                    continue;
+                }
+            }
+
+            if let Some((proximity, consumed)) = read_proximity(rest) {
+                parsed.proximity.push(proximity);
+                rest = rest[consumed..].trim_start();
+                continue;
+            }
+
+            if negation && is_negation(rest) {
+                let (value, consumed) = read_value(&rest[1..]);
+                if !value.is_empty() {
+                    parsed.excluded.push(value.to_lowercase());
+                }
+                rest = rest[1 + consumed..].trim_start();
+                continue;
+            }
+
+            let field = FIELD_PREFIXES
+                .iter()
+                .find(|p| rest.starts_with(*p))
+                .map(|p| &p[..p.len() - 1]);
+            let Some(field) = field else {
+                let end = token_end(rest);
+                parsed.text.push_str(&rest[..end]);
+                rest = &rest[end..];
+                continue;
+            };
+
+            let after_prefix = &rest[field.len() + 1..];
+            let (value, consumed) = read_value(after_prefix);
+            if value.is_empty() {
+                // A bare `path:` is just text
+                let end = token_end(rest);
+                parsed.text.push_str(&rest[..end]);
+                rest = &rest[end..];
+                continue;
+            }
+
+            match field {
+                "path" => parsed.paths.push(value.to_string()),
+                "ext" => parsed
+                    .extensions
+                    .extend(split_list(value).map(|ext| ext.trim_start_matches('.').to_string())),
+                "lang" => parsed
+                    .languages
+                    .extend(split_list(value).map(|lang| lang.to_lowercase())),
+                "in" => match split_list(value)
+                    .map(Scope::parse)
+                    .collect::<Option<Vec<_>>>()
+                {
+                    Some(scopes) => parsed.scopes.extend(scopes),
+                    // `in:` something else is just text
+                    None => parsed.text.push_str(&rest[..field.len() + 1 + consumed]),
+                },
+                _ => parsed.text.push_str(value),
+            }
+
+            rest = &after_prefix[consumed..];
+            if field != "content" {
+                // Drop the separator so removed qualifiers don't leave double spaces
+                rest = rest.trim_start();
+            }
+        }
+
+        parsed.text = parsed.text.trim().to_string();
+        parsed
+    }
+
+    /// Whether any field qualifiers were present
+    pub fn has_filters(&self) -> bool {
+        !self.extensions.is_empty() || !self.paths.is_empty() || !self.languages.is_empty()
+    }
+
+    /// Whether the query has constraints beyond its text (filters, scopes, negations,
+    /// proximity)
+    pub fn has_constraints(&self) -> bool {
+        self.has_filters()
+            || !self.scopes.is_empty()
+            || !self.excluded.is_empty()
+            || !self.proximity.is_empty()
+    }
+
+    /// The part of a document's content the query searches with its `in:` scopes: all of
+    /// it without scopes, otherwise the content with what is out of scope blanked out
+    /// (offsets and lines kept). `None` for documents without stored regions, whose
+    /// language the lexer doesn't know.
+    pub fn scoped<'a>(&self, content: &'a str, regions: Option<&str>) -> Option<Cow<'a, str>> {
+        if self.scopes.is_empty() {
+            return Some(Cow::Borrowed(content));
+        }
+        let regions = Regions::decode(regions?);
+        let masked = regions.mask(content, |kind| {
+            self.scopes.iter().any(|scope| scope.includes(kind))
+        });
+        Some(Cow::Owned(masked))
+    }
+
+    /// Text plus the proximity terms, for retrieval methods that can't apply the clauses
+    pub fn search_text(&self) -> String {
+        let mut words = vec![self.text.clone()];
+        for proximity in &self.proximity {
+            words.extend(proximity.terms.iter().cloned());
+        }
+        words.retain(|w| !w.is_empty());
+        words.join(" ")
+    }
+
+    /// Whether content passes the negated terms (matched as whole words, like the index's
+    /// MustNot clauses) and proximity clauses
+    pub fn allows(&self, content: &str) -> bool {
+        if self.excluded.is_empty() && self.proximity.is_empty() {
+            return true;
+        }
+        let lower = content.to_lowercase();
+        !self
+            .excluded
+            .iter()
+            .any(|term| first_word(&lower, term).is_some())
+            && self.proximity.iter().all(|p| p.find_line(&lower).is_some())
+    }
+
+    /// Line (0-based) where the first proximity clause matches
+    pub fn proximity_line(&self, content: &str) -> Option<usize> {
+        self.proximity
+            .first()
+            .and_then(|p| p.find_line(&content.to_lowercase()))
+    }
+
+    /// Add the parsed qualifiers to a set of filters
+    pub fn merge_into(&self, filters: &mut SearchFilters) {
+        extend_filter(&mut filters.extensions, &self.extensions);
+        extend_filter(&mut filters.paths, &self.paths);
+        extend_filter(&mut filters.languages, &self.languages);
+    }
+}
+
+impl Proximity {
+    /// First line (0-based) of a region where every term occurs within `distance`
+    /// lines of an occurrence of the first term. Expects lowercased content.
+    pub fn find_line(&self, content: &str) -> Option<usize> {
+        let lines: Vec<&str> = content.lines().collect();
+        let occurrences: Vec<Vec<usize>> = self
+            .terms
+            .iter()
+            .map(|term| {
+                lines
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, line)| line.contains(term.as_str()))
+                    .map(|(i, _)| i)
+                    .collect()
+            })
+            .collect();
+
+        let (first, others) = occurrences.split_first()?;
+        let distance = self.distance as usize;
+        first.iter().find_map(|&anchor| {
+            let mut start = anchor;
+            for lines in others {
+                let near = lines.iter().find(|&&l| l.abs_diff(anchor) <= distance)?;
+                start = start.min(*near);
+            }
+            Some(start)
+        })
+    }
+}
+
+/// Parse `"a b"~N` at the start of `s`; returns the clause and bytes consumed
+fn read_proximity(s: &str) -> Option<(Proximity, usize)> {
+    let quoted = s.strip_prefix('"')?;
+    let close = quoted.find('"')?;
+    let after = quoted[close + 1..].strip_prefix('~')?;
+    let digits = token_end(after);
+    let distance = after[..digits].parse().ok()?;
+    let terms: Vec<String> = quoted[..close]
+        .split_whitespace()
+        .map(|t| t.to_lowercase())
+        .collect();
+    if terms.is_empty() {
+        return None;
+    }
+    Some((Proximity { terms, distance }, close + 3 + digits))
+}
+
+/// Whether a token is `-term`, `!term` or `-"some phrase"`, where `term` is a whole word
+/// (`-1`, `->get(` and `!is_empty()` are not)
+fn is_negation(token: &str) -> bool {
+    let Some(rest) = token.strip_prefix(['-', '!']) else {
+        return false;
+    };
+    if rest.starts_with('"') {
+        return true;
+    }
+    let word = &rest[..token_end(rest)];
+    word.starts_with(|c: char| c.is_alphabetic() || c == '_') && word.chars().all(is_word_char)
+}
+
+/// Byte length of the token at the start of `s`
+fn token_end(s: &str) -> usize {
+    s.find(char::is_whitespace).unwrap_or(s.len())
+}
+
+/// Read a qualifier value; returns the value and the number of bytes consumed
+fn read_value(s: &str) -> (&str, usize) {
+    if let Some(quoted) = s.strip_prefix('"') {
+        match quoted.find('"') {
+            Some(end) => (&quoted[..end], end + 2),
+            None => (quoted, s.len()),
+        }
+    } else {
+        let end = token_end(s);
+        (&s[..end], end)
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').filter(|v| !v.is_empty())
+}
+
+fn extend_filter(filter: &mut Option<Vec<String>>, values: &[String]) {
+    if !values.is_empty() {
+        filter
+            .get_or_insert_with(Vec::new)
+            .extend_from_slice(values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_field_qualifiers() {
+        let parsed = ParsedQuery::parse(
+            r#"path:auth ext:rs,.py lang:Python content:"connection pool""#,
+            false,
+        );
+        assert_eq!(parsed.text, "connection pool");
+        assert_eq!(parsed.paths, vec!["auth"]);
+        assert_eq!(parsed.extensions, vec!["rs", "py"]);
+        assert_eq!(parsed.languages, vec!["python"]);
+
+        let parsed = ParsedQuery::parse("fn  main ext:rs -> x", false);
+        assert_eq!(parsed.text, "fn  main -> x");
+        assert_eq!(parsed.extensions, vec!["rs"]);
+
+        let parsed = ParsedQuery::parse(r"http://host \path:x path:", false);
+        assert_eq!(parsed.text, "http://host path:x path:");
+        assert!(!parsed.has_filters());
+    }
+
+    #[test]
+    fn test_scopes() {
+        let parsed = ParsedQuery::parse("retry in:comments,strings in:nowhere", false);
+        assert_eq!(parsed.text, "retry in:nowhere");
+        assert_eq!(parsed.scopes, vec![Scope::Comments, Scope::Strings]);
+        assert!(parsed.has_constraints());
+
+        let content = "retry(); // retry later";
+        let regions = Regions::lex("rs", content).map(|regions| regions.encode());
+        let scoped = parsed.scoped(content, regions.as_deref()).unwrap();
+        assert_eq!(scoped, "         // retry later");
+        let code = ParsedQuery::parse("in:code retry", false);
+        assert_eq!(
+            code.scoped(content, regions.as_deref()).unwrap(),
+            format!("retry();{}", " ".repeat(15))
+        );
+        // Files the lexer doesn't know have nothing in scope
+        assert_eq!(code.scoped("retry", None), None);
+        assert_eq!(
+            ParsedQuery::parse("retry", false)
+                .scoped("retry", None)
+                .as_deref(),
+            Some("retry")
+        );
+    }
+
+    #[test]
+    fn test_parse_negated_terms() {
+        let parsed = ParsedQuery::parse(r#"foo -Bar !baz -"two words" ->get( -1 \-keep"#, true);
+        assert_eq!(parsed.text, "foo ->get( -1 -keep");
+        assert_eq!(parsed.excluded, vec!["bar", "baz", "two words"]);
+
+        // Only whole words are negated
+        let parsed = ParsedQuery::parse("assert !is_empty() -x.y", true);
+        assert_eq!(parsed.text, "assert !is_empty() -x.y");
+        assert!(parsed.excluded.is_empty());
+
+        // Without `negation` the query is literal
+        for query in ["if !ready", "rm -rf", "!is_empty()", r"\-keep"] {
+            let parsed = ParsedQuery::parse(query, false);
+            assert_eq!(parsed.text, query);
+            assert!(!parsed.has_constraints());
+        }
+    }
+
+    #[test]
+    fn test_allows_whole_words() {
+        let parsed = ParsedQuery::parse(r#"auth -log -"two words""#, true);
+        assert!(parsed.allows(
+            "fn login() {}
+blog catalog"
+        ));
+        assert!(parsed.allows("two wordsmiths"));
+        assert!(!parsed.allows("log::info!(auth)"));
+        assert!(!parsed.allows("some_call(Log)"));
+        assert!(!parsed.allows("// two words"));
+    }
+
+    #[test]
+    fn test_proximity() {
+        let parsed = ParsedQuery::parse(r#"fs "open file"~2 "keep me""#, false);
+        assert_eq!(parsed.text, r#"fs "keep me""#);
+        assert_eq!(
+            parsed.proximity,
+            vec![Proximity {
+                terms: vec!["open".to_string(), "file".to_string()],
+                distance: 2,
+            }]
+        );
+
+        let content = "fn read() {\n    let f = File::open(path);\n}\n\n\n\nopen\n";
+        assert!(parsed.allows(content));
+        assert_eq!(parsed.proximity_line(content), Some(1));
+        assert!(!parsed.allows("open\n\n\n\nfile\n"));
+    }
+}
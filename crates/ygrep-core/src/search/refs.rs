@@ -0,0 +1,291 @@
+//! Find references (`ygrep refs <symbol>`): the lines defining and using a symbol, with
+//! files ranked by importance (how many files import them, see `indexer.reference_counts`)
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+use crate::error::{Result, YgrepError};
+
+/// Whether a line defines the symbol or uses it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RefKind {
+    Definition,
+    Usage,
+}
+
+/// A line mentioning the symbol
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefSite {
+    /// Line number (1-based)
+    pub line: u64,
+    pub kind: RefKind,
+    /// The line, trimmed
+    pub text: String,
+}
+
+/// The lines of one file mentioning the symbol
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileRefs {
+    /// File path relative to the workspace
+    pub path: String,
+    /// Files importing this one (0 unless indexed with `indexer.reference_counts`)
+    pub importers: u64,
+    pub definitions: usize,
+    pub usages: usize,
+    pub sites: Vec<RefSite>,
+}
+
+/// Where a symbol is defined and used across the workspace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SymbolRefs {
+    pub symbol: String,
+    /// Defining lines in all matching files
+    pub definitions: usize,
+    /// Other lines mentioning the symbol in all matching files
+    pub usages: usize,
+    /// Files defining the symbol first, then by importers and usages (may be fewer than
+    /// `total_files` if limited)
+    pub files: Vec<FileRefs>,
+    pub total_files: usize,
+    /// Search time in milliseconds
+    pub query_time_ms: u64,
+}
+
+impl SymbolRefs {
+    /// Check that `symbol` is an identifier that can be looked up
+    pub fn validate(symbol: &str) -> Result<()> {
+        if symbol.is_empty() || !symbol.chars().all(is_identifier_char) {
+            return Err(YgrepError::Search(format!(
+                "'{}' is not a symbol (letters, digits, `_` and `$` only)",
+                symbol
+            )));
+        }
+        Ok(())
+    }
+
+    /// Classify the lines mentioning `symbol` as a whole word (case-sensitive) in each
+    /// `(path, importers, content)` file
+    pub fn collect(
+        symbol: &str,
+        files: impl IntoIterator<Item = (String, u64, String)>,
+        limit: usize,
+        start: Instant,
+    ) -> Result<Self> {
+        Self::validate(symbol)?;
+        let definition = definition_regex(symbol);
+
+        let mut matched: Vec<FileRefs> = files
+            .into_iter()
+            .filter_map(|(path, importers, content)| {
+                let sites: Vec<RefSite> = content
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| mentions(line, symbol))
+                    .map(|(i, line)| RefSite {
+                        line: i as u64 + 1,
+                        kind: if definition.is_match(line) {
+                            RefKind::Definition
+                        } else {
+                            RefKind::Usage
+                        },
+                        text: line.trim().to_string(),
+                    })
+                    .collect();
+                if sites.is_empty() {
+                    return None;
+                }
+                let definitions = sites
+                    .iter()
+                    .filter(|site| site.kind == RefKind::Definition)
+                    .count();
+                Some(FileRefs {
+                    path,
+                    importers,
+                    definitions,
+                    usages: sites.len() - definitions,
+                    sites,
+                })
+            })
+            .collect();
+
+        matched.sort_by(|a, b| {
+            (b.definitions > 0)
+                .cmp(&(a.definitions > 0))
+                .then_with(|| b.importers.cmp(&a.importers))
+                .then_with(|| b.usages.cmp(&a.usages))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        let total_files = matched.len();
+        let definitions = matched.iter().map(|file| file.definitions).sum();
+        let usages = matched.iter().map(|file| file.usages).sum();
+        matched.truncate(limit);
+
+        Ok(Self {
+            symbol: symbol.to_string(),
+            definitions,
+            usages,
+            files: matched,
+            total_files,
+            query_time_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    fn header(&self) -> String {
+        format!(
+            "# {}: {} definition{}, {} usage{} in {} file{}\n",
+            self.symbol,
+            self.definitions,
+            plural(self.definitions),
+            self.usages,
+            plural(self.usages),
+            self.total_files,
+            plural(self.total_files)
+        )
+    }
+
+    /// One `path:line` per site, definitions marked
+    pub fn format_ai(&self) -> String {
+        let mut output = self.header();
+        output.push('\n');
+        for file in &self.files {
+            for site in &file.sites {
+                let marker = match site.kind {
+                    RefKind::Definition => " [def]",
+                    RefKind::Usage => "",
+                };
+                output.push_str(&format!(
+                    "{}:{}{}  {}\n",
+                    file.path, site.line, marker, site.text
+                ));
+            }
+        }
+        output
+    }
+
+    /// Format as JSON
+    pub fn format_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Sites grouped under each file, with its importers
+    pub fn format_pretty(&self) -> String {
+        let mut output = self.header();
+        for file in &self.files {
+            output.push('\n');
+            if file.importers > 0 {
+                output.push_str(&format!("{} (imported by {})\n", file.path, file.importers));
+            } else {
+                output.push_str(&format!("{}\n", file.path));
+            }
+            for site in &file.sites {
+                let marker = match site.kind {
+                    RefKind::Definition => "def",
+                    RefKind::Usage => "   ",
+                };
+                output.push_str(&format!("  {:>5} {}  {}\n", site.line, marker, site.text));
+            }
+        }
+        output
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Whether `line` contains `symbol` as a whole identifier
+fn mentions(line: &str, symbol: &str) -> bool {
+    line.match_indices(symbol).any(|(start, _)| {
+        let before = line[..start].chars().next_back();
+        let after = line[start + symbol.len()..].chars().next();
+        !before.is_some_and(is_identifier_char) && !after.is_some_and(is_identifier_char)
+    })
+}
+
+/// Lines declaring `symbol`: after a definition keyword (`fn`, `def`, `class`, `struct`,
+/// `function`, `const`, ...), as a Go method or a C macro
+fn definition_regex(symbol: &str) -> Regex {
+    let symbol = regex::escape(symbol);
+    Regex::new(&format!(
+        r"(?x)
+        (?:^|[^\w$])
+        (?:
+            (?:fn|def|class|struct|enum|union|trait|type|interface|mod|module|object
+              |const|static|let|var|val|function|func|macro_rules!|typedef\s+\w+)
+            \s+(?:\*\s*)?{symbol}
+          | func\s*\([^)]*\)\s*{symbol}
+          | \#\s*define\s+{symbol}
+        )
+        (?:[^\w$]|$)"
+    ))
+    .expect("escaped symbol makes a valid regex")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_refs() {
+        let files = vec![
+            (
+                "src/store.rs".to_string(),
+                0,
+                "pub struct UserStore {}\nimpl UserStore {\n    fn new() {}\n}\n".to_string(),
+            ),
+            (
+                "src/lib.rs".to_string(),
+                4,
+                "use store::UserStore;\nlet s = UserStore::new();\n// UserStores\n".to_string(),
+            ),
+            (
+                "scripts/seed.rs".to_string(),
+                0,
+                "let a = UserStore::new();\nlet b = UserStore::new();\nlet c = UserStore::new();\n"
+                    .to_string(),
+            ),
+            ("README.md".to_string(), 0, "MyUserStore".to_string()),
+        ];
+        let refs = SymbolRefs::collect("UserStore", files.clone(), 10, Instant::now()).unwrap();
+        assert_eq!((refs.definitions, refs.usages, refs.total_files), (1, 6, 3));
+        let order: Vec<&str> = refs.files.iter().map(|f| f.path.as_str()).collect();
+        // The defining file, then by importers before usages
+        assert_eq!(order, vec!["src/store.rs", "src/lib.rs", "scripts/seed.rs"]);
+        assert_eq!(refs.files[0].sites[0].kind, RefKind::Definition);
+        assert_eq!(refs.files[0].sites[1].kind, RefKind::Usage);
+        assert_eq!(refs.files[1].sites.len(), 2);
+
+        let top = SymbolRefs::collect("UserStore", files, 1, Instant::now()).unwrap();
+        assert_eq!((top.files.len(), top.total_files, top.usages), (1, 3, 6));
+        assert!(SymbolRefs::validate("user store").is_err());
+    }
+
+    #[test]
+    fn test_definition_regex() {
+        let definition = definition_regex("connect");
+        for line in [
+            "pub async fn connect(url: &str) {",
+            "def connect(self):",
+            "export function connect() {",
+            "func (c *Client) connect() error {",
+            "func connect() error {",
+            "const connect = () => {}",
+            "#define connect(x) open(x)",
+        ] {
+            assert!(definition.is_match(line), "{}", line);
+        }
+        for line in ["client.connect();", "connect(url)", "fn connector() {"] {
+            assert!(!definition.is_match(line), "{}", line);
+        }
+    }
+}
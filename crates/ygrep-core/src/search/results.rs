@@ -1,9 +1,31 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::chunks::ChunkContext;
+use crate::config::SearchConfig;
+use crate::error::{Result, YgrepError};
+use crate::fs::language_for_path;
+
+/// Maximum values shown per facet in text output
+const FACET_DISPLAY_LIMIT: usize = 10;
+
+/// Default line truncation for the AI format
+const AI_LINE_LENGTH: usize = 100;
+
+/// Default line truncation for the pretty format
+const PRETTY_LINE_LENGTH: usize = 80;
+
+/// Version of the JSON output (`schema_version`). Fields may be added within a version;
+/// it is bumped when fields are renamed, removed or change meaning.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
 
 /// Type of match for a search hit
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum MatchType {
     /// Matched via BM25 text search
+    #[default]
     Text,
     /// Matched via semantic vector search
     Semantic,
@@ -22,8 +44,11 @@ impl std::fmt::Display for MatchType {
 }
 
 /// Result of a search operation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SearchResult {
+    /// Version of this JSON shape (`JSON_SCHEMA_VERSION`)
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
     /// Search hits
     pub hits: Vec<SearchHit>,
     /// Total number of results (may be more than hits if limited)
@@ -36,16 +61,174 @@ pub struct SearchResult {
     /// Number of hits from semantic search
     #[serde(default)]
     pub semantic_hits: usize,
+    /// Paths of every matching document, including those past the limit
+    /// (only collected when `search.facets` is enabled)
+    #[serde(skip)]
+    pub matched_paths: Vec<String>,
+    /// Distribution of matches (set when facets are requested)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub facets: Option<Facets>,
+    /// Age of the searched index (set by `Workspace` searches)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<IndexFreshness>,
+    /// Why a regex scan stopped before covering every document (`--timeout`/`--max-scanned`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incomplete: Option<String>,
+    /// Corrected queries when nothing matched ("did you mean ...")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+    /// Root of the searched workspace (set by `Workspace` searches)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+    /// Workspace statistics after the search (`--stats`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<WorkspaceStats>,
+}
+
+impl Default for SearchResult {
+    fn default() -> Self {
+        Self {
+            schema_version: JSON_SCHEMA_VERSION,
+            hits: Vec::new(),
+            total: 0,
+            query_time_ms: 0,
+            text_hits: 0,
+            semantic_hits: 0,
+            matched_paths: Vec::new(),
+            facets: None,
+            index: None,
+            incomplete: None,
+            suggestions: Vec::new(),
+            workspace: None,
+            stats: None,
+        }
+    }
+}
+
+/// When the searched index was last updated, so consumers can judge how far to trust
+/// line numbers
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct IndexFreshness {
+    /// Last full index or refresh (RFC 3339)
+    pub indexed_at: String,
+    /// Seconds between `indexed_at` and the search
+    pub age_secs: u64,
+    /// Commit checked out at that time (git workspaces)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+}
+
+impl IndexFreshness {
+    /// Short form for text headers: `indexed 2h ago @ 1a2b3c4`
+    pub fn summary(&self) -> String {
+        let age = match self.age_secs {
+            s if s < 60 => format!("{}s", s),
+            s if s < 3600 => format!("{}m", s / 60),
+            s if s < 86400 => format!("{}h", s / 3600),
+            s => format!("{}d", s / 86400),
+        };
+        match &self.commit {
+            Some(commit) => format!("indexed {} ago @ {}", age, &commit[..commit.len().min(7)]),
+            None => format!("indexed {} ago", age),
+        }
+    }
+}
+
+/// Runtime statistics of an open workspace (`Workspace::stats`, `--stats`)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct WorkspaceStats {
+    /// `None` in builds without semantic search
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_cache: Option<EmbeddingCacheStats>,
+}
+
+impl WorkspaceStats {
+    /// `# embedding cache: ...` line for the text formats
+    pub fn format_text(&self) -> String {
+        match &self.embedding_cache {
+            Some(cache) => format!("# embedding cache: {}\n", cache.summary()),
+            None => "# embedding cache: not available in this build\n".to_string(),
+        }
+    }
+}
+
+/// Counters of the embedding cache since the workspace was opened
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct EmbeddingCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to make room (least recently used first)
+    pub evictions: u64,
+    /// hits / (hits + misses), 0 before the first lookup
+    pub hit_rate: f64,
+    /// Embeddings held
+    pub entries: usize,
+    /// Embeddings it can hold (0 when disabled)
+    pub capacity: usize,
+    /// `indexer.embedding_cache_mb`
+    pub capacity_mb: usize,
+}
+
+impl EmbeddingCacheStats {
+    /// `4 hits, 1 miss (80%), 0 evictions, 5/27306 entries (100 MB)`
+    pub fn summary(&self) -> String {
+        let plural = |n: u64, word: &str, words: &str| {
+            format!("{} {}", n, if n == 1 { word } else { words })
+        };
+        if self.capacity == 0 {
+            return format!(
+                "disabled ({} computed)",
+                plural(self.misses, "embedding", "embeddings")
+            );
+        }
+        format!(
+            "{}, {} ({:.0}%), {}, {}/{} entries ({} MB)",
+            plural(self.hits, "hit", "hits"),
+            plural(self.misses, "miss", "misses"),
+            self.hit_rate * 100.0,
+            plural(self.evictions, "eviction", "evictions"),
+            self.entries,
+            self.capacity,
+            self.capacity_mb
+        )
+    }
+}
+
+/// Match counts grouped by extension, top-level directory and language
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Facets {
+    /// Number of distinct matching files
+    pub files: usize,
+    pub extensions: Vec<FacetCount>,
+    pub directories: Vec<FacetCount>,
+    pub languages: Vec<FacetCount>,
+}
+
+/// A facet value and the number of matching files with it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: usize,
 }
 
 /// A single search hit
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct SearchHit {
     /// File path (relative to workspace)
     pub path: String,
+    /// Absolute file path, so the file can be opened without knowing the workspace root
+    /// (set by `Workspace` searches)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abs_path: Option<String>,
     /// Line range (start-end)
     pub line_start: u64,
     pub line_end: u64,
+    /// Line of the first match, when known exactly (literal and regex matches)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_line: Option<u64>,
+    /// Byte column (1-based) where that match starts on its line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_column: Option<u64>,
     /// Content snippet
     pub snippet: String,
     /// Relevance score (0.0-1.0)
@@ -57,12 +240,90 @@ pub struct SearchHit {
     /// Type of match (text, semantic, or hybrid)
     #[serde(default = "default_match_type")]
     pub match_type: MatchType,
+    /// How the score was computed (set when `search.explain` is enabled)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<ScoreExplanation>,
+    /// Only the start of the file is indexed: it is over `indexer.max_file_size`
+    /// (`indexer.oversized_head_kb`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Git submodule the file belongs to (`.gitmodules` name)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submodule: Option<String>,
+    /// Which chunk of its file a chunk hit is and the lines it covers (set when
+    /// `search.chunk_context` is enabled)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk: Option<ChunkContext>,
+}
+
+/// Breakdown of a hit's score across the retrieval methods
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ScoreExplanation {
+    /// Rank in the BM25 results (1-based)
+    pub bm25_rank: Option<usize>,
+    /// Raw BM25 score
+    pub bm25_score: Option<f32>,
+    /// Rank in the vector results (1-based)
+    pub vector_rank: Option<usize>,
+    /// Distance between the query and document embeddings
+    pub vector_distance: Option<f32>,
+    /// Reciprocal Rank Fusion contribution from BM25
+    pub bm25_rrf: Option<f32>,
+    /// Reciprocal Rank Fusion contribution from vector search
+    pub vector_rrf: Option<f32>,
+    /// Multipliers applied after retrieval
+    pub boosts: Vec<ScoreBoost>,
+}
+
+/// A score multiplier and why it was applied
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ScoreBoost {
+    pub reason: String,
+    pub factor: f32,
+}
+
+impl ScoreExplanation {
+    /// One-line summary used by the AI and pretty formats
+    pub fn format_text(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(rank) = self.bm25_rank {
+            parts.push(format!(
+                "bm25 #{} ({:.2})",
+                rank,
+                self.bm25_score.unwrap_or(0.0)
+            ));
+        }
+        if let Some(rank) = self.vector_rank {
+            parts.push(format!(
+                "vector #{} (distance {:.3})",
+                rank,
+                self.vector_distance.unwrap_or(0.0)
+            ));
+        }
+        if self.bm25_rrf.is_some() || self.vector_rrf.is_some() {
+            parts.push(format!(
+                "rrf {:.4} + {:.4}",
+                self.bm25_rrf.unwrap_or(0.0),
+                self.vector_rrf.unwrap_or(0.0)
+            ));
+        }
+        for boost in &self.boosts {
+            parts.push(format!("{} x{:.2}", boost.reason, boost.factor));
+        }
+        format!("  = {}\n", parts.join(", "))
+    }
 }
 
 fn default_match_type() -> MatchType {
     MatchType::Text
 }
 
+/// Output written before `schema_version` existed has the first version's shape
+fn default_schema_version() -> u32 {
+    1
+}
+
 impl SearchHit {
     /// Format line range as string (e.g., "10-25")
     pub fn lines_str(&self) -> String {
@@ -72,6 +333,27 @@ impl SearchHit {
             format!("{}-{}", self.line_start, self.line_end)
         }
     }
+
+    /// The id `Workspace::expand_hit` takes to expand this hit's lines: its doc_id with
+    /// its line range, `<doc_id>:L<start>-<end>`
+    pub fn expand_id(&self) -> String {
+        format!("{}:L{}-{}", self.doc_id, self.line_start, self.line_end)
+    }
+
+    /// Index of the matching line within the snippet (its first line if unknown)
+    fn match_offset(&self) -> usize {
+        self.match_line
+            .map_or(0, |line| line.saturating_sub(self.line_start) as usize)
+    }
+
+    /// Names of the fields a hit can have in JSON output (as `--fields` takes them)
+    pub fn field_names() -> Vec<String> {
+        let schema = serde_json::to_value(schemars::schema_for!(SearchHit)).unwrap_or_default();
+        schema["properties"]
+            .as_object()
+            .map(|properties| properties.keys().cloned().collect())
+            .unwrap_or_default()
+    }
 }
 
 impl SearchResult {
@@ -83,14 +365,71 @@ impl SearchResult {
         }
     }
 
+    /// ` [truncated]` for hits in files indexed only in part
+    fn truncated_marker(hit: &SearchHit) -> &'static str {
+        if hit.truncated {
+            " [truncated]"
+        } else {
+            ""
+        }
+    }
+
+    /// ` [chunk 3 of 7, lines 120-180]` for chunk hits with their context
+    fn chunk_marker(hit: &SearchHit) -> String {
+        hit.chunk
+            .as_ref()
+            .map(|chunk| format!(" [{}]", chunk.summary()))
+            .unwrap_or_default()
+    }
+
     /// Create an empty result
     pub fn empty() -> Self {
-        Self {
-            hits: vec![],
-            total: 0,
-            query_time_ms: 0,
-            text_hits: 0,
-            semantic_hits: 0,
+        Self::default()
+    }
+
+    /// Compute facet counts over the full match set (falls back to the returned hits
+    /// when the full set wasn't collected). Each file is counted once.
+    pub fn facets(&self) -> Facets {
+        let source: Vec<&String> = if self.matched_paths.is_empty() {
+            self.hits.iter().map(|h| &h.path).collect()
+        } else {
+            self.matched_paths.iter().collect()
+        };
+
+        let mut seen = std::collections::HashSet::new();
+        let mut extensions: HashMap<String, usize> = HashMap::new();
+        let mut directories: HashMap<String, usize> = HashMap::new();
+        let mut languages: HashMap<String, usize> = HashMap::new();
+
+        for path in source {
+            if !seen.insert(path.as_str()) {
+                continue;
+            }
+            let p = Path::new(path);
+
+            let ext = p
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| "(none)".to_string());
+            *extensions.entry(ext).or_default() += 1;
+
+            let mut components = p.components();
+            let first = components.next();
+            let dir = match (first, components.next()) {
+                (Some(dir), Some(_)) => dir.as_os_str().to_string_lossy().to_string(),
+                _ => ".".to_string(),
+            };
+            *directories.entry(dir).or_default() += 1;
+
+            let lang = language_for_path(p).unwrap_or("other");
+            *languages.entry(lang.to_string()).or_default() += 1;
+        }
+
+        Facets {
+            files: seen.len(),
+            extensions: sorted_counts(extensions),
+            directories: sorted_counts(directories),
+            languages: sorted_counts(languages),
         }
     }
 
@@ -110,6 +449,27 @@ impl SearchResult {
         }
     }
 
+    /// `, indexed 2h ago @ 1a2b3c4` when the index age is known
+    fn freshness_suffix(&self) -> String {
+        self.index
+            .as_ref()
+            .map(|index| format!(", {}", index.summary()))
+            .unwrap_or_default()
+    }
+
+    /// `# Did you mean "embeddings"?` when there are suggestions
+    fn suggestions_line(&self) -> String {
+        if self.suggestions.is_empty() {
+            return String::new();
+        }
+        let quoted: Vec<String> = self
+            .suggestions
+            .iter()
+            .map(|s| format!("\"{}\"", s))
+            .collect();
+        format!("# Did you mean {}?\n", quoted.join(" or "))
+    }
+
     /// Normalize score for display (RRF scores are tiny ~0.01, we want 0-100 range)
     fn display_score(score: f32) -> f32 {
         // RRF scores max out around 0.016 for K=60, scale to 0-100
@@ -119,57 +479,179 @@ impl SearchResult {
 
     /// Format results for AI-optimized output (minimal tokens, maximum density)
     pub fn format_ai(&self) -> String {
+        self.format_ai_with(&DisplayOptions::default())
+    }
+
+    /// AI-optimized output with explicit preview options
+    pub fn format_ai_with(&self, options: &DisplayOptions) -> String {
+        let max_len = options.max_line_length.unwrap_or(AI_LINE_LENGTH);
         let mut output = String::new();
 
-        // Header with count and search type breakdown
+        // Header with count, search type breakdown and index age
         output.push_str(&format!(
-            "# {} results ({})\n\n",
+            "# {} results ({}{})\n\n",
             self.hits.len(),
-            self.search_type_summary()
+            self.search_type_summary(),
+            self.freshness_suffix()
         ));
 
-        for hit in &self.hits {
-            // Single line format: path:line (score%) [match_type]
-            let score_pct = Self::display_score(hit.score);
-            let match_indicator = Self::match_indicator(hit.match_type);
-            output.push_str(&format!(
-                "{}:{} ({:.0}%){}\n",
-                hit.path, hit.line_start, score_pct, match_indicator
-            ));
+        output.push_str(&self.suggestions_line());
 
-            // Show only the first matching line, trimmed
-            if let Some(first_line) = hit.snippet.lines().next() {
-                let trimmed = first_line.trim();
-                let preview = if trimmed.len() > 100 {
-                    let boundary = trimmed.floor_char_boundary(100);
-                    format!("{}...", &trimmed[..boundary])
-                } else {
-                    trimmed.to_string()
-                };
-                output.push_str(&format!("  {}\n", preview));
+        if let Some(budget) = options.max_tokens {
+            self.push_ai_hits_within(&mut output, budget, max_len);
+        } else {
+            for hit in &self.hits {
+                output.push_str(&Self::ai_hit_header(hit));
+
+                if options.whole_snippets {
+                    for line in hit.snippet.lines() {
+                        output.push_str(&format!("  {}\n", truncate_line(line, max_len)));
+                    }
+                } else if let Some(first_line) = hit.snippet.lines().nth(hit.match_offset()) {
+                    // Show only the first matching line, trimmed
+                    output.push_str(&format!("  {}\n", truncate_line(first_line, max_len)));
+                }
+                output.push('\n');
             }
-            output.push('\n');
+        }
+
+        if let Some(ref incomplete) = self.incomplete {
+            output.push_str(&format!("# {}\n", incomplete));
+        }
+        if let Some(ref facets) = self.facets {
+            output.push_str(&facets.format_text());
+        }
+        if let Some(ref stats) = self.stats {
+            output.push_str(&stats.format_text());
         }
 
         output
     }
 
+    /// `path:line (score%) [match_type]`, plus the score breakdown with `--explain`
+    fn ai_hit_header(hit: &SearchHit) -> String {
+        let score_pct = Self::display_score(hit.score);
+        let match_indicator = Self::match_indicator(hit.match_type);
+        let mut header = format!(
+            "{}:{} ({:.0}%){}{}{}\n",
+            hit.path,
+            hit.match_line.unwrap_or(hit.line_start),
+            score_pct,
+            match_indicator,
+            Self::truncated_marker(hit),
+            Self::chunk_marker(hit)
+        );
+        if let Some(ref explanation) = hit.explanation {
+            header.push_str(&explanation.format_text());
+        }
+        header
+    }
+
+    /// Hits in rank order with their whole snippet while they fit in the token budget,
+    /// then path-only lines; whatever doesn't fit at all is counted at the end
+    fn push_ai_hits_within(&self, output: &mut String, budget: usize, max_len: usize) {
+        let mut used = estimate_tokens(output);
+        let mut paths_only = false;
+        let mut omitted = 0;
+
+        for hit in &self.hits {
+            let header = Self::ai_hit_header(hit);
+            if !paths_only {
+                let mut entry = header.clone();
+                for line in hit.snippet.lines() {
+                    entry.push_str(&format!("  {}\n", truncate_line(line, max_len)));
+                }
+                entry.push('\n');
+
+                let cost = estimate_tokens(&entry);
+                if used + cost <= budget {
+                    output.push_str(&entry);
+                    used += cost;
+                    continue;
+                }
+                paths_only = true;
+            }
+
+            let cost = estimate_tokens(&header);
+            if used + cost <= budget {
+                output.push_str(&header);
+                used += cost;
+            } else {
+                omitted += 1;
+            }
+        }
+
+        if omitted > 0 {
+            if !output.ends_with("\n\n") {
+                output.push('\n');
+            }
+            output.push_str(&format!(
+                "# {} more results omitted (token budget)\n",
+                omitted
+            ));
+        }
+    }
+
     /// Format results as JSON (includes all metadata)
     pub fn format_json(&self) -> String {
         serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
     }
 
+    /// `format_json` with each hit reduced to `fields` (`--fields path,line_start,score`),
+    /// e.g. to leave out snippets; all fields when empty
+    pub fn format_json_fields(&self, fields: &[String]) -> Result<String> {
+        if fields.is_empty() {
+            return Ok(self.format_json());
+        }
+        let known = SearchHit::field_names();
+        if let Some(unknown) = fields.iter().find(|field| !known.contains(field)) {
+            return Err(YgrepError::Config(format!(
+                "Unknown field '{}' (fields: {})",
+                unknown,
+                known.join(", ")
+            )));
+        }
+
+        let mut json = serde_json::to_value(self)
+            .map_err(|e| YgrepError::Config(format!("Failed to serialize results: {}", e)))?;
+        for hit in json["hits"].as_array_mut().into_iter().flatten() {
+            if let Some(hit) = hit.as_object_mut() {
+                hit.retain(|key, _| fields.contains(key));
+            }
+        }
+        Ok(serde_json::to_string_pretty(&json).unwrap_or_else(|_| "{}".to_string()))
+    }
+
+    /// JSON Schema of the `format_json` output
+    pub fn json_schema() -> String {
+        let schema = schemars::schema_for!(SearchResult);
+        serde_json::to_string_pretty(&schema).unwrap_or_else(|_| "{}".to_string())
+    }
+
     /// Format results for human-readable output (more context, line numbers)
     pub fn format_pretty(&self, show_scores: bool) -> String {
+        self.format_pretty_with(show_scores, &DisplayOptions::default())
+    }
+
+    /// Human-readable output with explicit preview options
+    pub fn format_pretty_with(&self, show_scores: bool, options: &DisplayOptions) -> String {
+        let max_len = options.max_line_length.unwrap_or(PRETTY_LINE_LENGTH);
         let mut output = String::new();
 
-        // Header with breakdown
+        // Header with breakdown and index age
         let type_info = if self.text_hits > 0 || self.semantic_hits > 0 {
-            format!(" ({})", self.search_type_summary())
+            format!(
+                " ({}{})",
+                self.search_type_summary(),
+                self.freshness_suffix()
+            )
+        } else if let Some(ref index) = self.index {
+            format!(" ({})", index.summary())
         } else {
             String::new()
         };
         output.push_str(&format!("# {} results{}\n\n", self.hits.len(), type_info));
+        output.push_str(&self.suggestions_line());
 
         for hit in &self.hits {
             // Header: path:line_range (+ optional score)
@@ -177,35 +659,140 @@ impl SearchResult {
                 let score_pct = Self::display_score(hit.score);
                 let match_indicator = Self::match_indicator(hit.match_type);
                 output.push_str(&format!(
-                    "{}:{} ({:.0}%){}\n",
+                    "{}:{} ({:.0}%){}{}{}\n",
                     hit.path,
                     hit.lines_str(),
                     score_pct,
-                    match_indicator
+                    match_indicator,
+                    Self::truncated_marker(hit),
+                    Self::chunk_marker(hit)
                 ));
             } else {
-                output.push_str(&format!("{}:{}\n", hit.path, hit.lines_str()));
+                output.push_str(&format!(
+                    "{}:{}{}{}\n",
+                    hit.path,
+                    hit.lines_str(),
+                    Self::truncated_marker(hit),
+                    Self::chunk_marker(hit)
+                ));
+            }
+            if let Some(ref explanation) = hit.explanation {
+                output.push_str(&explanation.format_text());
             }
 
             // Show first few lines of snippet with line numbers
-            for (i, line) in hit.snippet.lines().take(3).enumerate() {
+            let preview_lines = if options.whole_snippets {
+                usize::MAX
+            } else {
+                options.preview_lines
+            };
+            for (i, line) in hit.snippet.lines().take(preview_lines).enumerate() {
                 let line_num = hit.line_start + i as u64;
-                let trimmed = line.trim();
-                let preview = if trimmed.len() > 80 {
-                    let boundary = trimmed.floor_char_boundary(80);
-                    format!("{}...", &trimmed[..boundary])
-                } else {
-                    trimmed.to_string()
-                };
-                output.push_str(&format!("  {}: {}\n", line_num, preview));
+                output.push_str(&format!(
+                    "  {}: {}\n",
+                    line_num,
+                    truncate_line(line, max_len)
+                ));
             }
             output.push('\n');
         }
 
+        if let Some(ref incomplete) = self.incomplete {
+            output.push_str(&format!("# {}\n", incomplete));
+        }
+        if let Some(ref facets) = self.facets {
+            output.push_str(&facets.format_text());
+        }
+        if let Some(ref stats) = self.stats {
+            output.push_str(&stats.format_text());
+        }
+
+        output
+    }
+}
+
+/// How much of each snippet the text formats show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayOptions {
+    /// Snippet lines shown per hit in the pretty format
+    pub preview_lines: usize,
+    /// Truncate preview lines to this many bytes (`None` = per-format default, 0 = never)
+    pub max_line_length: Option<usize>,
+    /// Approximate token budget for the AI format (`None` = unlimited)
+    pub max_tokens: Option<usize>,
+    /// Show whole snippets, as hits expanded with `search.expand_lines` have, rather than
+    /// the matching line (AI format) or `preview_lines` (pretty format)
+    pub whole_snippets: bool,
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self::from_config(&SearchConfig::default())
+    }
+}
+
+impl DisplayOptions {
+    pub fn from_config(config: &SearchConfig) -> Self {
+        Self {
+            preview_lines: config.preview_lines,
+            max_line_length: config.max_line_length,
+            max_tokens: config.max_tokens,
+            whole_snippets: config.expand_lines > 0,
+        }
+    }
+}
+
+/// Rough token count for budgeting output (about four bytes per token for code)
+fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// Trim a preview line and cut it at `max_len` bytes (0 = no limit)
+fn truncate_line(line: &str, max_len: usize) -> String {
+    let trimmed = line.trim();
+    if max_len > 0 && trimmed.len() > max_len {
+        let boundary = trimmed.floor_char_boundary(max_len);
+        format!("{}...", &trimmed[..boundary])
+    } else {
+        trimmed.to_string()
+    }
+}
+
+impl Facets {
+    /// Compact text rendering used by the AI and pretty formats
+    pub fn format_text(&self) -> String {
+        let line = |label: &str, counts: &[FacetCount]| {
+            let values: Vec<String> = counts
+                .iter()
+                .take(FACET_DISPLAY_LIMIT)
+                .map(|c| format!("{} {}", c.value, c.count))
+                .collect();
+            let more = if counts.len() > FACET_DISPLAY_LIMIT {
+                format!(", ... ({} more)", counts.len() - FACET_DISPLAY_LIMIT)
+            } else {
+                String::new()
+            };
+            format!("{}: {}{}\n", label, values.join(", "), more)
+        };
+
+        let mut output = format!("## Facets ({} files)\n", self.files);
+        output.push_str(&line("ext", &self.extensions));
+        output.push_str(&line("dir", &self.directories));
+        output.push_str(&line("lang", &self.languages));
         output
     }
 }
 
+/// Sort facet counts by count (descending), then value
+fn sorted_counts(counts: HashMap<String, usize>) -> Vec<FacetCount> {
+    let mut counts: Vec<FacetCount> = counts
+        .into_iter()
+        .map(|(value, count)| FacetCount { value, count })
+        .collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+    counts
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -221,6 +808,7 @@ mod tests {
             is_chunk: false,
             doc_id: "abc123".to_string(),
             match_type: MatchType::Text,
+            ..Default::default()
         };
         assert_eq!(hit.lines_str(), "10-25");
 
@@ -244,11 +832,13 @@ mod tests {
                 is_chunk: false,
                 doc_id: "abc".to_string(),
                 match_type: MatchType::Text,
+                ..Default::default()
             }],
             total: 1,
             query_time_ms: 15,
             text_hits: 1,
             semantic_hits: 0,
+            ..Default::default()
         };
 
         let output = result.format_ai();
@@ -256,4 +846,361 @@ mod tests {
         assert!(output.contains("src/main.rs:1"));
         assert!(output.contains("(90%)"));
     }
+
+    #[test]
+    fn test_facets_use_full_match_set() {
+        let hit = SearchHit {
+            path: "src/main.rs".to_string(),
+            line_start: 1,
+            line_end: 1,
+            snippet: String::new(),
+            score: 1.0,
+            is_chunk: false,
+            doc_id: "a".to_string(),
+            match_type: MatchType::Text,
+            ..Default::default()
+        };
+        let result = SearchResult {
+            hits: vec![hit],
+            total: 4,
+            matched_paths: vec![
+                "src/main.rs".to_string(),
+                "src/main.rs".to_string(),
+                "src/lib.rs".to_string(),
+                "docs/guide.md".to_string(),
+                "README.md".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let facets = result.facets();
+        assert_eq!(facets.files, 4);
+        assert_eq!(
+            facets.extensions[0],
+            FacetCount {
+                value: "md".to_string(),
+                count: 2
+            }
+        );
+        assert_eq!(facets.directories[0].value, "src");
+        assert_eq!(facets.directories[0].count, 2);
+        assert!(facets.directories.iter().any(|d| d.value == "."));
+        assert!(facets
+            .languages
+            .iter()
+            .any(|l| l.value == "rust" && l.count == 2));
+    }
+
+    #[test]
+    fn test_explanation_in_ai_format() {
+        let result = SearchResult {
+            hits: vec![SearchHit {
+                path: "src/auth.rs".to_string(),
+                snippet: "fn login() {}".to_string(),
+                match_type: MatchType::Hybrid,
+                explanation: Some(ScoreExplanation {
+                    bm25_rank: Some(2),
+                    bm25_score: Some(7.5),
+                    vector_rank: Some(1),
+                    vector_distance: Some(0.25),
+                    bm25_rrf: Some(0.0161),
+                    vector_rrf: Some(0.0164),
+                    boosts: vec![],
+                }),
+                ..Default::default()
+            }],
+            total: 1,
+            ..Default::default()
+        };
+
+        let output = result.format_ai();
+        assert!(output
+            .contains("  = bm25 #2 (7.50), vector #1 (distance 0.250), rrf 0.0161 + 0.0164\n"));
+    }
+    #[test]
+    fn test_index_freshness_in_header() {
+        let mut result = SearchResult {
+            text_hits: 1,
+            index: Some(IndexFreshness {
+                indexed_at: "2025-01-01T00:00:00+00:00".to_string(),
+                age_secs: 7300,
+                commit: Some("1a2b3c4d5e6f".to_string()),
+            }),
+            ..Default::default()
+        };
+        assert!(result
+            .format_ai()
+            .starts_with("# 0 results (text, indexed 2h ago @ 1a2b3c4)\n"));
+
+        result.index.as_mut().unwrap().commit = None;
+        result.text_hits = 0;
+        assert!(result
+            .format_pretty(false)
+            .starts_with("# 0 results (indexed 2h ago)\n"));
+        assert!(result.format_json().contains("\"age_secs\": 7300"));
+    }
+
+    #[test]
+    fn test_workspace_stats_text() {
+        let mut cache = EmbeddingCacheStats {
+            hits: 4,
+            misses: 1,
+            hit_rate: 0.8,
+            entries: 5,
+            capacity: 27306,
+            capacity_mb: 100,
+            ..Default::default()
+        };
+        assert_eq!(
+            cache.summary(),
+            "4 hits, 1 miss (80%), 0 evictions, 5/27306 entries (100 MB)"
+        );
+        cache.capacity = 0;
+        assert_eq!(cache.summary(), "disabled (1 embedding computed)");
+
+        let result = SearchResult {
+            stats: Some(WorkspaceStats::default()),
+            ..Default::default()
+        };
+        assert!(result
+            .format_ai()
+            .ends_with("# embedding cache: not available in this build\n"));
+    }
+
+    #[test]
+    fn test_display_options() {
+        let long = "x".repeat(120);
+        let result = SearchResult {
+            hits: vec![SearchHit {
+                path: "src/lib.rs".to_string(),
+                line_start: 1,
+                snippet: format!("{}\nb\nc\nd", long),
+                ..Default::default()
+            }],
+            total: 1,
+            ..Default::default()
+        };
+
+        assert!(result
+            .format_ai()
+            .contains(&format!("  {}...\n", &long[..100])));
+        assert!(result.format_pretty(false).contains("  3: c\n"));
+        assert!(!result.format_pretty(false).contains("  4: d\n"));
+
+        let options = DisplayOptions {
+            preview_lines: 4,
+            max_line_length: Some(0),
+            max_tokens: None,
+            whole_snippets: false,
+        };
+        assert!(result
+            .format_ai_with(&options)
+            .contains(&format!("  {}\n", long)));
+        assert!(result
+            .format_pretty_with(false, &options)
+            .contains("  4: d\n"));
+
+        // Expanded hits show their whole snippet
+        let options = DisplayOptions {
+            whole_snippets: true,
+            ..Default::default()
+        };
+        assert!(result.format_ai_with(&options).contains("  b\n  c\n  d\n"));
+        assert!(result
+            .format_pretty_with(false, &options)
+            .contains("  4: d\n"));
+    }
+
+    #[test]
+    fn test_ai_token_budget() {
+        let hit = |path: &str, score: f32| SearchHit {
+            path: path.to_string(),
+            line_start: 10,
+            snippet: "fn connect() {\n    pool.get()\n}".to_string(),
+            score,
+            ..Default::default()
+        };
+        let result = SearchResult {
+            hits: (0..20)
+                .map(|i| hit(&format!("src/db/pool_{:02}.rs", i), 1.0 - i as f32 / 100.0))
+                .collect(),
+            total: 20,
+            ..Default::default()
+        };
+
+        let options = DisplayOptions {
+            max_tokens: Some(100),
+            ..Default::default()
+        };
+        let output = result.format_ai_with(&options);
+        // Best hits keep their whole snippet, later ones are paths only
+        assert!(
+            output.contains("src/db/pool_00.rs:10 (100%)\n  fn connect() {\n  pool.get()\n  }\n")
+        );
+        assert!(output.contains("  }\n\nsrc/db/pool_05.rs:10 (100%)\nsrc/db/pool_06.rs:10"));
+        assert!(output.ends_with("results omitted (token budget)\n"));
+        assert!(estimate_tokens(&output) <= 100 + 10);
+
+        let unlimited = DisplayOptions {
+            max_tokens: Some(10_000),
+            ..Default::default()
+        };
+        assert!(!result.format_ai_with(&unlimited).contains("omitted"));
+    }
+
+    #[test]
+    fn test_format_json_fields() {
+        let result = SearchResult {
+            hits: vec![SearchHit {
+                path: "src/lib.rs".to_string(),
+                line_start: 10,
+                line_end: 12,
+                snippet: "fn connect() {}".to_string(),
+                score: 0.5,
+                ..Default::default()
+            }],
+            total: 1,
+            ..Default::default()
+        };
+        let fields = ["path".to_string(), "line_start".to_string()];
+        let json: serde_json::Value =
+            serde_json::from_str(&result.format_json_fields(&fields).unwrap()).unwrap();
+        assert_eq!(
+            json["hits"][0],
+            serde_json::json!({"path": "src/lib.rs", "line_start": 10})
+        );
+        assert_eq!(json["total"], 1);
+
+        assert!(result.format_json_fields(&["lines".to_string()]).is_err());
+        assert!(SearchHit::field_names().contains(&"snippet".to_string()));
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let hit = SearchHit {
+            path: "src/lib.rs".to_string(),
+            abs_path: Some("/repo/src/lib.rs".to_string()),
+            line_start: 1,
+            line_end: 3,
+            match_line: Some(2),
+            match_column: Some(5),
+            snippet: "fn main() {}".to_string(),
+            score: 0.5,
+            doc_id: "abc".to_string(),
+            explanation: Some(ScoreExplanation {
+                bm25_rank: Some(1),
+                boosts: vec![ScoreBoost {
+                    reason: "feedback".to_string(),
+                    factor: 1.5,
+                }],
+                ..Default::default()
+            }),
+            truncated: true,
+            submodule: Some("proto".to_string()),
+            chunk: Some(ChunkContext {
+                number: 3,
+                count: 7,
+                line_start: 120,
+                line_end: 180,
+                parent_doc: "abc".to_string(),
+            }),
+            ..Default::default()
+        };
+        let mut result = SearchResult {
+            hits: vec![hit],
+            total: 1,
+            index: Some(IndexFreshness {
+                indexed_at: "2024-01-01T00:00:00+00:00".to_string(),
+                age_secs: 60,
+                commit: Some("1a2b3c4".to_string()),
+            }),
+            incomplete: Some("timeout".to_string()),
+            suggestions: vec!["main".to_string()],
+            workspace: Some("/repo".to_string()),
+            matched_paths: vec!["src/lib.rs".to_string()],
+            stats: Some(WorkspaceStats {
+                embedding_cache: Some(EmbeddingCacheStats::default()),
+            }),
+            ..Default::default()
+        };
+        result.facets = Some(result.facets());
+
+        let json: serde_json::Value = serde_json::from_str(&result.format_json()).unwrap();
+        assert_eq!(json["schema_version"], JSON_SCHEMA_VERSION);
+        let keys = |value: &serde_json::Value| -> Vec<String> {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        // The shape consumers rely on: changing it needs a new schema version
+        assert_eq!(
+            keys(&json),
+            [
+                "facets",
+                "hits",
+                "incomplete",
+                "index",
+                "query_time_ms",
+                "schema_version",
+                "semantic_hits",
+                "stats",
+                "suggestions",
+                "text_hits",
+                "total",
+                "workspace",
+            ]
+        );
+        assert_eq!(
+            keys(&json["hits"][0]),
+            [
+                "abs_path",
+                "chunk",
+                "doc_id",
+                "explanation",
+                "is_chunk",
+                "line_end",
+                "line_start",
+                "match_column",
+                "match_line",
+                "match_type",
+                "path",
+                "score",
+                "snippet",
+                "submodule",
+                "truncated",
+            ]
+        );
+
+        // Every field is described by the schema, and every required one is present
+        let schema: serde_json::Value = serde_json::from_str(&SearchResult::json_schema()).unwrap();
+        let definitions = &schema["definitions"];
+        for (value, schema) in [
+            (&json, &schema),
+            (&json["hits"][0], &definitions["SearchHit"]),
+            (
+                &json["hits"][0]["explanation"],
+                &definitions["ScoreExplanation"],
+            ),
+            (&json["hits"][0]["chunk"], &definitions["ChunkContext"]),
+            (&json["index"], &definitions["IndexFreshness"]),
+            (&json["facets"], &definitions["Facets"]),
+        ] {
+            let properties = schema["properties"].as_object().unwrap();
+            for key in keys(value) {
+                assert!(
+                    properties.contains_key(&key),
+                    "{} is not in the schema",
+                    key
+                );
+            }
+            for required in schema["required"].as_array().into_iter().flatten() {
+                assert!(value.get(required.as_str().unwrap()).is_some());
+            }
+        }
+
+        // Output without a version predates it
+        let old: SearchResult =
+            serde_json::from_str(r#"{"hits": [], "total": 0, "query_time_ms": 1}"#).unwrap();
+        assert_eq!(old.schema_version, 1);
+    }
 }
@@ -0,0 +1,135 @@
+//! Saved result sets for refining searches (`--within-last`, `--within <name>`)
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::results::SearchResult;
+use crate::error::{Result, YgrepError};
+
+/// Name of the result set that is overwritten after every search
+pub const LAST_RESULT_SET: &str = "last";
+
+/// Directory (inside the index directory) holding saved result sets
+const RESULTS_DIR: &str = "results";
+
+/// Paths returned by a previous search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedResultSet {
+    /// Query that produced the results
+    pub query: String,
+    /// When the results were saved (RFC 3339)
+    pub saved_at: String,
+    /// Unique file paths (relative to workspace), in rank order
+    pub paths: Vec<String>,
+}
+
+impl SavedResultSet {
+    /// Build a result set from search hits (deduplicated by path)
+    pub fn from_result(query: &str, result: &SearchResult) -> Self {
+        let mut paths: Vec<String> = Vec::with_capacity(result.hits.len());
+        for hit in &result.hits {
+            if !paths.contains(&hit.path) {
+                paths.push(hit.path.clone());
+            }
+        }
+
+        Self {
+            query: query.to_string(),
+            saved_at: chrono::Utc::now().to_rfc3339(),
+            paths,
+        }
+    }
+
+    /// Save under `name` in the given index directory
+    pub fn save(&self, index_path: &Path, name: &str) -> Result<()> {
+        let path = result_set_path(index_path, name)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| YgrepError::Config(format!("Failed to save result set: {}", e)))?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Load the result set saved under `name` in the given index directory
+    pub fn load(index_path: &Path, name: &str) -> Result<Self> {
+        let path = result_set_path(index_path, name)?;
+        if !path.exists() {
+            return Err(YgrepError::Search(format!(
+                "No saved results named '{}'",
+                name
+            )));
+        }
+        let content = std::fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| YgrepError::Config(format!("Failed to load result set: {}", e)))
+    }
+}
+
+/// Resolve the file for a named result set, rejecting names that could escape the directory
+fn result_set_path(index_path: &Path, name: &str) -> Result<PathBuf> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(YgrepError::Config(format!(
+            "Invalid result set name '{}' (use letters, digits, '-' or '_')",
+            name
+        )));
+    }
+    Ok(index_path.join(RESULTS_DIR).join(format!("{}.json", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{MatchType, SearchHit};
+    use tempfile::tempdir;
+
+    fn make_hit(path: &str) -> SearchHit {
+        SearchHit {
+            path: path.to_string(),
+            line_start: 1,
+            line_end: 1,
+            snippet: String::new(),
+            score: 0.5,
+            is_chunk: false,
+            doc_id: path.to_string(),
+            match_type: MatchType::Text,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_dedups_paths() -> Result<()> {
+        let temp_dir = tempdir().unwrap();
+        let result = SearchResult {
+            hits: vec![
+                make_hit("src/a.rs"),
+                make_hit("src/b.rs"),
+                make_hit("src/a.rs"),
+            ],
+            total: 3,
+            query_time_ms: 0,
+            text_hits: 3,
+            semantic_hits: 0,
+            ..Default::default()
+        };
+
+        SavedResultSet::from_result("auth", &result).save(temp_dir.path(), LAST_RESULT_SET)?;
+        let loaded = SavedResultSet::load(temp_dir.path(), LAST_RESULT_SET)?;
+
+        assert_eq!(loaded.query, "auth");
+        assert_eq!(loaded.paths, vec!["src/a.rs", "src/b.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_path_like_names() {
+        let temp_dir = tempdir().unwrap();
+        assert!(SavedResultSet::load(temp_dir.path(), "../escape").is_err());
+        assert!(SavedResultSet::load(temp_dir.path(), "missing").is_err());
+    }
+}
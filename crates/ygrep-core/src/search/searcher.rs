@@ -1,17 +1,46 @@
-use regex::RegexBuilder;
-use std::time::Instant;
-use tantivy::{collector::TopDocs, query::QueryParser, Index};
-
-use super::results::{MatchType, SearchHit, SearchResult};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tantivy::collector::{Count, DocSetCollector};
+use tantivy::query::{
+    BooleanQuery, Occur, PhraseQuery, Query, RegexQuery, TermQuery, TermSetQuery,
+};
+use tantivy::schema::{Field, IndexRecordOption, TantivyDocument};
+use tantivy::{query::QueryParser, DocAddress, DocId, Index, Term};
+use tantivy::{DocSet, TERMINATED};
+
+use super::bm25::Bm25Scoring;
+use super::chunks::{expand_lines, stitch, ChunkContext, ExpandedHit, IndexedFile};
+use super::matcher::RegexMatcher;
+use super::plan::{PlanMode, QueryPlan};
+use super::query::ParsedQuery;
+use super::refs::SymbolRefs;
+use super::results::{MatchType, ScoreExplanation, SearchHit, SearchResult, JSON_SCHEMA_VERSION};
+use super::snippet::SnippetWindow;
+use super::suggest;
+use super::synonyms::Synonyms;
+use super::todos::{self, TodoItem};
 use crate::config::SearchConfig;
-use crate::error::Result;
-use crate::index::schema::SchemaFields;
+use crate::error::{Result, YgrepError};
+use crate::fs::{
+    extension_for_language, language_for_extension, language_for_path, to_slash, FileKind,
+};
+use crate::index::normalize::{identifier_parts, is_cjk, MatchNormalizer};
+use crate::index::schema::{units, SchemaFields};
+use crate::index::Stemming;
+
+/// Ranked candidates checked in parallel at a time; the limit is checked between batches
+const CANDIDATE_BATCH: usize = 256;
 
 /// Search engine for querying the index
 pub struct Searcher {
     config: SearchConfig,
     index: Index,
     fields: SchemaFields,
+    bm25: Bm25Scoring,
+    /// Prose files indexed with stemmed words (`indexer.stemming`)
+    stemming: Option<Stemming>,
 }
 
 impl Searcher {
@@ -19,16 +48,45 @@ impl Searcher {
     pub fn new(config: SearchConfig, index: Index) -> Self {
         let schema = index.schema();
         let fields = SchemaFields::new(&schema);
+        let bm25 = Bm25Scoring::from_config(&config);
 
         Self {
             config,
             index,
             fields,
+            bm25,
+            stemming: None,
+        }
+    }
+
+    /// Also match prose files by stemmed words, as the index was built with them
+    pub fn with_stemming(mut self, stemming: Option<Stemming>) -> Self {
+        if let Some(ref stemming) = stemming {
+            stemming.register(self.index.tokenizers());
         }
+        self.stemming = stemming;
+        self
     }
 
     /// Search the index with a query string (literal text matching like grep)
+    ///
+    /// Field qualifiers (`path:`, `ext:`, `lang:`, `content:`) are applied as filters,
+    /// negated terms (`-bar`, `!bar`, with `negation`) exclude documents, `"a b"~N` requires nearby terms
+    /// and `in:comments` (`strings`, `code`) only matches there.
     pub fn search(&self, query: &str, limit: Option<usize>) -> Result<SearchResult> {
+        if ParsedQuery::parse(query, self.config.negation).has_constraints() {
+            return self.search_filtered(query, limit, SearchFilters::default(), false);
+        }
+        self.search_literal(query, limit, Restriction::default())
+    }
+
+    /// Literal search, optionally restricted
+    fn search_literal(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+        mut restriction: Restriction,
+    ) -> Result<SearchResult> {
         let start = Instant::now();
         let limit = limit
             .unwrap_or(self.config.default_limit)
@@ -37,62 +95,110 @@ impl Searcher {
         // Get a reader
         let reader = self.index.reader()?;
         let searcher = reader.searcher();
+        restriction.filters.extend(self.line_docs(&searcher)?);
 
         // Build query parser for content field
         let query_parser = QueryParser::for_index(&self.index, vec![self.fields.content]);
 
         // Extract alphanumeric words for Tantivy query (it can't search special chars)
         // Then we'll post-filter for exact literal match
-        let search_terms: Vec<&str> = query
-            .split(|c: char| !c.is_alphanumeric() && c != '_')
-            .filter(|s| !s.is_empty())
-            .collect();
+        // (stopwords still have to match literally but don't narrow or rank the candidates)
+        let search_terms = self.bm25.without_stopwords(self.literal_words(query));
 
-        // If no searchable terms, return empty
-        if search_terms.is_empty() {
-            return Ok(SearchResult {
-                total: 0,
-                hits: vec![],
-                query_time_ms: start.elapsed().as_millis() as u64,
-                text_hits: 0,
-                semantic_hits: 0,
-            });
-        }
+        // Without searchable terms (and proximity clauses to search for) the files are
+        // scanned for the literal string instead
+        let proximity_only = search_terms.is_empty() && !restriction.query.proximity.is_empty();
 
         // Search for the extracted terms
-        let tantivy_query_str = search_terms.join(" ");
-        let (tantivy_query, _errors) = query_parser.parse_query_lenient(&tantivy_query_str);
+        let top_docs = if search_terms.is_empty() && !proximity_only {
+            Vec::new()
+        } else {
+            let tantivy_query: Box<dyn Query> = if proximity_only {
+                Box::new(tantivy::query::AllQuery)
+            } else {
+                let tantivy_query_str = self.literal_index_query(&search_terms);
+                let content_query = query_parser.parse_query_lenient(&tantivy_query_str).0;
+                match self.stemming {
+                    // Prose files are also found by the stems of the words
+                    Some(_) if !self.is_case_sensitive(query, false) => {
+                        let prose_parser =
+                            QueryParser::for_index(&self.index, vec![self.fields.prose]);
+                        let prose_query = prose_parser.parse_query_lenient(&tantivy_query_str).0;
+                        Box::new(BooleanQuery::new(vec![
+                            (Occur::Should, content_query),
+                            (Occur::Should, prose_query),
+                        ]))
+                    }
+                    _ => content_query,
+                }
+            };
+            let tantivy_query = restriction.apply(tantivy_query, &self.index, self.fields.content);
 
-        // Fetch more results since we'll filter them down
-        let fetch_limit = limit * 10;
-        let top_docs = searcher.search(&tantivy_query, &TopDocs::with_limit(fetch_limit))?;
+            // Fetch more results since we'll filter them down
+            // (facets need every candidate so they cover the full match set)
+            let fetch_limit = if self.config.facets {
+                (searcher.num_docs() as usize).max(1)
+            } else {
+                limit * 10
+            };
+            self.bm25
+                .top_docs(&searcher, &tantivy_query, self.fields.content, fetch_limit)?
+        };
 
         // Build results
         let mut hits = Vec::with_capacity(top_docs.len());
         let max_score = top_docs.first().map(|(score, _)| *score).unwrap_or(1.0);
 
-        // Case-insensitive literal matching (like grep -i)
-        let query_lower = query.to_lowercase();
-
-        for (score, doc_address) in top_docs {
-            // Stop if we have enough results
-            if hits.len() >= limit {
-                break;
+        let mut matched_paths = Vec::new();
+
+        // Case-insensitive literal matching (like grep -i), unless smart case applies
+        // Compare NFKC-normalized text so `café` matches regardless of encoding form
+        // (and, in identifier mode, `getUserId` matches `get_user_id`)
+        let case_sensitive = self.is_case_sensitive(query, false);
+        let normalizer = self.normalizer();
+        let mut per_file = PerFileLimit::new(self.config.max_per_file);
+        let query_norm = normalizer.normalize(query).into_owned();
+        let query_lower = query_norm.to_lowercase();
+        let literal_match = |content: &str| {
+            let content_norm = normalizer.normalize(content);
+            let found = if case_sensitive {
+                content_norm.contains(&query_norm)
+            } else {
+                content_norm.to_lowercase().contains(&query_lower)
+            };
+            found && restriction.query.allows(content)
+        };
+        // Prose files also match when their words stem like the query's (case-insensitive
+        // queries only; smart case asks for the exact text)
+        let query_stems = match self.stemming {
+            Some(ref stemming) if !case_sensitive => stemming.stems(query),
+            _ => Vec::new(),
+        };
+        let stemmed_match = |doc: &TantivyDocument, content: &str| {
+            let stemming = self.stemming.as_ref()?;
+            let extension = extract_text(doc, self.fields.extension).unwrap_or_default();
+            if !stemming.applies_to(&extension) || !restriction.query.allows(content) {
+                return None;
             }
+            stemming
+                .find(content, &query_stems)
+                .map(|offset| MatchPosition::at(content, offset))
+        };
+        let mut checked = HashSet::new();
 
-            let doc = searcher.doc(doc_address)?;
-
-            // Extract fields
-            let path = extract_text(&doc, self.fields.path).unwrap_or_default();
-            let doc_id = extract_text(&doc, self.fields.doc_id).unwrap_or_default();
-            let content = extract_text(&doc, self.fields.content).unwrap_or_default();
-            let line_start = extract_u64(&doc, self.fields.line_start).unwrap_or(1);
-            let chunk_id = extract_text(&doc, self.fields.chunk_id).unwrap_or_default();
+        let check = |rank: usize, score: f32, doc: &TantivyDocument| {
+            let content = extract_text(doc, self.fields.content).unwrap_or_default();
+            // (only the comments, strings or code of it with `in:` scopes)
+            let scoped = restriction
+                .query
+                .scoped(&content, extract_text(doc, self.fields.regions).as_deref())?;
 
             // LITERAL GREP-LIKE FILTER: Only include if content contains exact query string
-            if !content.to_lowercase().contains(&query_lower) {
-                continue;
-            }
+            let stemmed = if literal_match(&scoped) {
+                None
+            } else {
+                Some(stemmed_match(doc, &scoped)?)
+            };
 
             // Normalize score to 0-1 range
             let normalized_score = if max_score > 0.0 {
@@ -101,35 +207,99 @@ impl Searcher {
                 0.0
             };
 
-            // Create snippet showing lines that match the query
-            let (snippet, match_line_offset, snippet_line_count) =
-                create_relevant_snippet(&content, query, 10);
-
-            // Adjust line numbers to reflect where the match actually is
-            let actual_line_start = line_start + match_line_offset as u64;
-            let actual_line_end = actual_line_start + snippet_line_count.saturating_sub(1) as u64;
+            // Cut the snippet around the match (or the proximity clause)
+            let position = if proximity_only {
+                restriction
+                    .query
+                    .proximity_line(&scoped)
+                    .map(|line| MatchPosition { line, column: None })
+            } else {
+                stemmed.or_else(|| {
+                    literal_match_position(&scoped, &query_norm, case_sensitive, &normalizer)
+                })
+            };
+            let explanation = self.explain_bm25(rank, score);
+            Some(self.text_hit(doc, &content, position, normalized_score, explanation))
+        };
+        let candidates = ranked(top_docs);
+        'batches: for batch in candidates.chunks(CANDIDATE_BATCH) {
+            let batch_hits = check_candidates(&searcher, batch, &check)?;
+            for (&(_, _, doc_address), hit) in batch.iter().zip(batch_hits) {
+                // Stop if we have enough results
+                if hits.len() >= limit && !self.config.facets {
+                    break 'batches;
+                }
+                checked.insert(doc_address);
+                let Some(hit) = hit else {
+                    continue;
+                };
+
+                if self.config.facets {
+                    matched_paths.push(hit.path.clone());
+                    if hits.len() >= limit {
+                        continue;
+                    }
+                }
+                if per_file.admit(&hit.path) {
+                    hits.push(hit);
+                }
+            }
+        }
 
-            hits.push(SearchHit {
-                path,
-                line_start: actual_line_start,
-                line_end: actual_line_end,
-                snippet,
-                score: normalized_score,
-                is_chunk: !chunk_id.is_empty(),
-                doc_id,
-                match_type: MatchType::Text,
-            });
+        // Punctuation (`?:`, `|>`, `foo.bar`) can tokenize differently in the query and
+        // the files, so when the candidates held no literal match the rest are scanned
+        if hits.is_empty() && !proximity_only && (search_terms.is_empty() || is_punctuated(query)) {
+            let wanted =
+                (!self.config.facets && self.config.max_per_file.is_none()).then_some(limit);
+            let all_query = restriction.apply(
+                Box::new(tantivy::query::AllQuery),
+                &self.index,
+                self.fields.content,
+            );
+            let scan = self.scan_documents(
+                &searcher,
+                all_query.as_ref(),
+                &checked,
+                wanted,
+                false,
+                &|doc| self.scoped_match(doc, &restriction.query, &literal_match),
+            )?;
+            for doc in scan.docs {
+                let path = extract_text(&doc, self.fields.path).unwrap_or_default();
+                if self.config.facets {
+                    matched_paths.push(path.clone());
+                }
+                if hits.len() >= limit || !per_file.admit(&path) {
+                    continue;
+                }
+                let content = extract_text(&doc, self.fields.content).unwrap_or_default();
+                let position = restriction
+                    .query
+                    .scoped(&content, extract_text(&doc, self.fields.regions).as_deref())
+                    .and_then(|scoped| {
+                        literal_match_position(&scoped, &query_norm, case_sensitive, &normalizer)
+                    });
+                hits.push(self.text_hit(&doc, &content, position, 1.0, None));
+            }
         }
 
         let query_time_ms = start.elapsed().as_millis() as u64;
         let text_hits = hits.len();
 
         Ok(SearchResult {
-            total: hits.len(),
+            schema_version: JSON_SCHEMA_VERSION,
+            total: hits.len().max(matched_paths.len()),
             hits,
             query_time_ms,
             text_hits,
             semantic_hits: 0,
+            matched_paths,
+            facets: None,
+            index: None,
+            incomplete: None,
+            suggestions: Vec::new(),
+            workspace: None,
+            stats: None,
         })
     }
 
@@ -141,114 +311,101 @@ impl Searcher {
         filters: SearchFilters,
         use_regex: bool,
     ) -> Result<SearchResult> {
-        // Use regex search if requested
-        let mut result = if use_regex {
-            self.search_regex(query, Some(limit.unwrap_or(self.config.max_limit) * 2))?
-        } else {
-            self.search(query, Some(limit.unwrap_or(self.config.max_limit) * 2))?
+        // Pull field qualifiers out of the query string
+        let parsed = ParsedQuery::parse(query, self.config.negation);
+        let mut filters = filters;
+        parsed.merge_into(&mut filters);
+        let query = parsed.text.as_str();
+
+        // Filter inside Tantivy, so the limit and `total` count matching documents only
+        let restriction = Restriction {
+            filters: filters
+                .index_query(&self.index, &self.fields)?
+                .into_iter()
+                .collect(),
+            query: parsed.clone(),
         };
 
-        // Apply filters
-        if let Some(ref extensions) = filters.extensions {
-            result.hits.retain(|hit| {
-                if let Some(ext) = std::path::Path::new(&hit.path).extension() {
-                    extensions
-                        .iter()
-                        .any(|e| e.eq_ignore_ascii_case(&ext.to_string_lossy()))
-                } else {
-                    false
-                }
-            });
-        }
-
-        if let Some(ref paths) = filters.paths {
-            result.hits.retain(|hit| {
-                paths
-                    .iter()
-                    .any(|p| hit.path.starts_with(p) || hit.path.contains(p))
-            });
+        // Use regex search if requested
+        if use_regex {
+            self.search_regex_restricted(query, limit, restriction)
+        } else {
+            self.search_literal(query, limit, restriction)
         }
-
-        // Re-limit
-        let limit = limit
-            .unwrap_or(self.config.default_limit)
-            .min(self.config.max_limit);
-        result.hits.truncate(limit);
-        result.total = result.hits.len();
-
-        Ok(result)
     }
 
     /// Search the index with a regex pattern
     pub fn search_regex(&self, pattern: &str, limit: Option<usize>) -> Result<SearchResult> {
+        self.search_regex_restricted(pattern, limit, Restriction::default())
+    }
+
+    /// Regex search, optionally restricted
+    fn search_regex_restricted(
+        &self,
+        pattern: &str,
+        limit: Option<usize>,
+        mut restriction: Restriction,
+    ) -> Result<SearchResult> {
         let start = Instant::now();
         let limit = limit
             .unwrap_or(self.config.default_limit)
             .min(self.config.max_limit);
 
         // Compile regex (case-insensitive by default, like grep -i)
-        let regex = match RegexBuilder::new(pattern).case_insensitive(true).build() {
-            Ok(r) => r,
-            Err(e) => {
-                return Err(crate::error::YgrepError::Search(format!(
-                    "Invalid regex pattern: {}",
-                    e
-                )));
-            }
-        };
+        let regex = RegexMatcher::new(
+            pattern,
+            self.config.regex_engine,
+            self.is_case_sensitive(pattern, true),
+        )?;
 
         // Get a reader
         let reader = self.index.reader()?;
         let searcher = reader.searcher();
+        restriction.filters.extend(self.line_docs(&searcher)?);
 
         // Build query parser for content field
         let query_parser = QueryParser::for_index(&self.index, vec![self.fields.content]);
 
         // Extract alphanumeric words from the regex pattern for Tantivy pre-filter
-        // This is a rough heuristic - we extract literal parts from the regex
-        let search_terms: Vec<&str> = pattern
-            .split(|c: char| !c.is_alphanumeric() && c != '_')
-            .filter(|s| !s.is_empty() && s.len() > 1) // Skip single chars (likely regex syntax)
-            .collect();
+        let search_terms = regex_words(pattern);
 
-        // If we have searchable terms, use Tantivy to narrow down candidates
+        // Documents containing words of the pattern are checked first, best ranked first,
+        // so the likeliest matches fill the limit early; this is only an optimization
         let candidates: Vec<_> = if !search_terms.is_empty() {
             let tantivy_query_str = search_terms.join(" ");
             let (tantivy_query, _errors) = query_parser.parse_query_lenient(&tantivy_query_str);
+            let tantivy_query = restriction.apply(tantivy_query, &self.index, self.fields.content);
 
             // Fetch many candidates since regex might be selective
-            let fetch_limit = limit * 20;
-            searcher.search(&tantivy_query, &TopDocs::with_limit(fetch_limit))?
+            let fetch_limit = if self.config.facets {
+                (searcher.num_docs() as usize).max(1)
+            } else {
+                limit * 20
+            };
+            self.bm25
+                .top_docs(&searcher, &tantivy_query, self.fields.content, fetch_limit)?
         } else {
-            // No good search terms - scan all documents
-            // This is slow but necessary for patterns like "^#" or ".*"
-            let all_query = tantivy::query::AllQuery;
-            let fetch_limit = limit * 50;
-            searcher.search(&all_query, &TopDocs::with_limit(fetch_limit))?
+            Vec::new()
         };
 
         // Build results by applying regex filter
-        let mut hits = Vec::with_capacity(candidates.len());
+        let mut per_file = PerFileLimit::new(self.config.max_per_file);
+        let mut hits = Vec::with_capacity(limit);
         let max_score = candidates.first().map(|(score, _)| *score).unwrap_or(1.0);
+        let matches = |content: &str| regex.is_match(content) && restriction.query.allows(content);
 
-        for (score, doc_address) in candidates {
-            // Stop if we have enough results
-            if hits.len() >= limit {
-                break;
-            }
-
-            let doc = searcher.doc(doc_address)?;
+        let mut matched_paths = Vec::new();
+        let mut checked = HashSet::new();
 
-            // Extract fields
-            let path = extract_text(&doc, self.fields.path).unwrap_or_default();
-            let doc_id = extract_text(&doc, self.fields.doc_id).unwrap_or_default();
-            let content = extract_text(&doc, self.fields.content).unwrap_or_default();
-            let line_start = extract_u64(&doc, self.fields.line_start).unwrap_or(1);
-            let chunk_id = extract_text(&doc, self.fields.chunk_id).unwrap_or_default();
+        let check = |rank: usize, score: f32, doc: &TantivyDocument| {
+            let content = extract_text(doc, self.fields.content).unwrap_or_default();
+            let scoped = restriction
+                .query
+                .scoped(&content, extract_text(doc, self.fields.regions).as_deref())?;
 
             // REGEX FILTER: Only include if content matches the regex
-            if !regex.is_match(&content) {
-                continue;
+            if !matches(&scoped) {
+                return None;
             }
 
             // Normalize score to 0-1 range
@@ -257,24 +414,82 @@ impl Searcher {
             } else {
                 0.0
             };
+            let position = regex_match_position(&scoped, &regex);
+            let explanation = self.explain_bm25(rank, score);
+            Some(self.text_hit(doc, &content, position, normalized_score, explanation))
+        };
+        let candidates = ranked(candidates);
+        'batches: for batch in candidates.chunks(CANDIDATE_BATCH) {
+            let batch_hits = check_candidates(&searcher, batch, &check)?;
+            for (&(_, _, doc_address), hit) in batch.iter().zip(batch_hits) {
+                // Stop if we have enough results
+                if hits.len() >= limit && !self.config.facets {
+                    break 'batches;
+                }
+                checked.insert(doc_address);
+                let Some(hit) = hit else {
+                    continue;
+                };
+
+                if self.config.facets {
+                    matched_paths.push(hit.path.clone());
+                    if hits.len() >= limit {
+                        continue;
+                    }
+                }
+                if per_file.admit(&hit.path) {
+                    hits.push(hit);
+                }
+            }
+        }
 
-            // Create snippet showing lines that match the regex
-            let (snippet, match_line_offset, snippet_line_count) =
-                create_regex_snippet(&content, &regex, 10);
-
-            // Adjust line numbers to reflect where the match actually is
-            let actual_line_start = line_start + match_line_offset as u64;
-            let actual_line_end = actual_line_start + snippet_line_count.saturating_sub(1) as u64;
+        // Then scan every other document, so matches without those words (or past the
+        // candidates) aren't missed
+        let mut incomplete = None;
+        if hits.len() < limit || self.config.facets {
+            // Unless hits per file are capped, the first `wanted` matches of each segment
+            // are enough
+            let wanted = (!self.config.facets && self.config.max_per_file.is_none())
+                .then(|| limit - hits.len());
+            let all_query = restriction.apply(
+                Box::new(tantivy::query::AllQuery),
+                &self.index,
+                self.fields.content,
+            );
+            let scan = self.scan_documents(
+                &searcher,
+                all_query.as_ref(),
+                &checked,
+                wanted,
+                true,
+                &|doc| self.scoped_match(doc, &restriction.query, &matches),
+            )?;
+            // Scanned matches have no words of the pattern to rank them by
+            let score = if search_terms.is_empty() { 1.0 } else { 0.0 };
+
+            for doc in scan.docs {
+                let path = extract_text(&doc, self.fields.path).unwrap_or_default();
+                if self.config.facets {
+                    matched_paths.push(path.clone());
+                }
+                if hits.len() >= limit || !per_file.admit(&path) {
+                    continue;
+                }
+                let content = extract_text(&doc, self.fields.content).unwrap_or_default();
+                let position = restriction
+                    .query
+                    .scoped(&content, extract_text(&doc, self.fields.regions).as_deref())
+                    .and_then(|scoped| regex_match_position(&scoped, &regex));
+                hits.push(self.text_hit(&doc, &content, position, score, None));
+            }
+            incomplete = scan.incomplete;
+        }
 
-            hits.push(SearchHit {
-                path,
-                line_start: actual_line_start,
-                line_end: actual_line_end,
-                snippet,
-                score: normalized_score,
-                is_chunk: !chunk_id.is_empty(),
-                doc_id,
-                match_type: MatchType::Text,
+        // Documents the pattern couldn't be run on went unchecked too
+        if let Some(failures) = regex.failures() {
+            incomplete = Some(match incomplete {
+                Some(reason) => format!("{}; {}", reason, failures),
+                None => failures,
             });
         }
 
@@ -282,15 +497,738 @@ impl Searcher {
         let text_hits = hits.len();
 
         Ok(SearchResult {
-            total: hits.len(),
+            schema_version: JSON_SCHEMA_VERSION,
+            total: hits.len().max(matched_paths.len()),
             hits,
             query_time_ms,
             text_hits,
             semantic_hits: 0,
+            matched_paths,
+            facets: None,
+            index: None,
+            incomplete,
+            suggestions: Vec::new(),
+            workspace: None,
+            stats: None,
+        })
+    }
+
+    /// Whether the part of a document's content `query` searches (with its `in:` scopes)
+    /// passes `matches`
+    fn scoped_match(
+        &self,
+        doc: &TantivyDocument,
+        query: &ParsedQuery,
+        matches: &dyn Fn(&str) -> bool,
+    ) -> bool {
+        let content = extract_text(doc, self.fields.content).unwrap_or_default();
+        query
+            .scoped(&content, extract_text(doc, self.fields.regions).as_deref())
+            .is_some_and(|scoped| matches(&scoped))
+    }
+
+    /// A text hit for a matching document, with the snippet cut around the match
+    fn text_hit(
+        &self,
+        doc: &TantivyDocument,
+        content: &str,
+        position: Option<MatchPosition>,
+        score: f32,
+        explanation: Option<ScoreExplanation>,
+    ) -> SearchHit {
+        // Chunks start further into their file
+        let line_start = extract_u64(doc, self.fields.line_start).unwrap_or(1);
+        let chunk_id = extract_text(doc, self.fields.chunk_id).unwrap_or_default();
+        let (snippet, snippet_offset, snippet_line_count) =
+            SnippetWindow::from_config(&self.config).extract(content, position.map(|p| p.line));
+
+        // Adjust line numbers to reflect where the snippet actually is
+        let actual_line_start = line_start + snippet_offset as u64;
+        let actual_line_end = actual_line_start + snippet_line_count.saturating_sub(1) as u64;
+
+        SearchHit {
+            path: extract_text(doc, self.fields.path).unwrap_or_default(),
+            abs_path: None,
+            line_start: actual_line_start,
+            line_end: actual_line_end,
+            match_line: position.map(|p| line_start + p.line as u64),
+            match_column: position.and_then(|p| p.column).map(|column| column as u64),
+            snippet,
+            score,
+            is_chunk: !chunk_id.is_empty(),
+            doc_id: extract_text(doc, self.fields.doc_id).unwrap_or_default(),
+            match_type: MatchType::Text,
+            explanation,
+            truncated: extract_bool(doc, self.fields.truncated).unwrap_or(false),
+            submodule: extract_text(doc, self.fields.submodule).filter(|name| !name.is_empty()),
+            chunk: None,
+        }
+    }
+
+    /// Documents matching `query` (except those already checked) that pass `matches`, in
+    /// index order. Segments are scanned in parallel, each stopping after
+    /// `wanted` matches (`None` scans everything); `budgeted` scans stop altogether once
+    /// `search.regex_timeout_ms` or `search.regex_max_scanned` runs out.
+    fn scan_documents(
+        &self,
+        searcher: &tantivy::Searcher,
+        query: &dyn Query,
+        checked: &HashSet<DocAddress>,
+        wanted: Option<usize>,
+        budgeted: bool,
+        matches: &(dyn Fn(&TantivyDocument) -> bool + Sync),
+    ) -> Result<DocumentScan> {
+        let mut segments: BTreeMap<u32, Vec<DocId>> = BTreeMap::new();
+        for address in searcher.search(query, &DocSetCollector)? {
+            if !checked.contains(&address) {
+                segments
+                    .entry(address.segment_ord)
+                    .or_default()
+                    .push(address.doc_id);
+            }
+        }
+        let total: usize = segments.values().map(Vec::len).sum();
+
+        let timeout_ms = self.config.regex_timeout_ms.filter(|_| budgeted);
+        let deadline = timeout_ms.map(|ms| Instant::now() + Duration::from_millis(ms));
+        let max_scanned = self.config.regex_max_scanned.filter(|_| budgeted);
+        let scanned = AtomicUsize::new(0);
+        let (over_count, timed_out) = (AtomicBool::new(false), AtomicBool::new(false));
+
+        let scan_segment = |segment_ord: u32, mut docs: Vec<DocId>| -> Result<_> {
+            docs.sort_unstable();
+            let mut found = Vec::new();
+            for doc_id in docs {
+                if wanted.is_some_and(|wanted| found.len() >= wanted)
+                    || over_count.load(Ordering::Relaxed)
+                    || timed_out.load(Ordering::Relaxed)
+                {
+                    break;
+                }
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    timed_out.store(true, Ordering::Relaxed);
+                    break;
+                }
+                if max_scanned.is_some_and(|max| scanned.fetch_add(1, Ordering::Relaxed) >= max) {
+                    scanned.fetch_sub(1, Ordering::Relaxed);
+                    over_count.store(true, Ordering::Relaxed);
+                    break;
+                }
+                let doc: TantivyDocument = searcher.doc(DocAddress::new(segment_ord, doc_id))?;
+                if max_scanned.is_none() {
+                    scanned.fetch_add(1, Ordering::Relaxed);
+                }
+                if matches(&doc) {
+                    found.push(doc);
+                }
+            }
+            Ok(found)
+        };
+
+        let per_segment: Vec<Result<Vec<TantivyDocument>>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = segments
+                .into_iter()
+                .map(|(segment_ord, docs)| scope.spawn(move || scan_segment(segment_ord, docs)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle.join().unwrap_or_else(|_| {
+                        Err(YgrepError::Config("Regex scan thread panicked".into()))
+                    })
+                })
+                .collect()
+        });
+        let mut docs = Vec::new();
+        for found in per_segment {
+            docs.extend(found?);
+        }
+
+        let escape_valve = if over_count.into_inner() {
+            max_scanned.map(|max| format!("--max-scanned {}", max))
+        } else if timed_out.into_inner() {
+            timeout_ms.map(|ms| format!("--timeout {}", ms))
+        } else {
+            None
+        };
+        let incomplete = escape_valve.map(|valve| {
+            format!(
+                "Regex scan stopped by {} after {} of {} documents; matches may be missing",
+                valve,
+                scanned.into_inner(),
+                total
+            )
+        });
+        Ok(DocumentScan { docs, incomplete })
+    }
+
+    /// Lines defining and using `symbol` in the indexed files, ranked by file importance
+    /// (up to `limit` files)
+    pub fn references(&self, symbol: &str, limit: usize) -> Result<SymbolRefs> {
+        let start = Instant::now();
+        SymbolRefs::validate(symbol)?;
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+
+        // Files containing every token of the symbol; lines are matched exactly afterwards
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![(
+            Occur::Must,
+            Box::new(TermQuery::new(
+                Term::from_field_text(self.fields.unit, units::FILE),
+                IndexRecordOption::Basic,
+            )),
+        )];
+        for token in tokenize(&self.index, self.fields.content, symbol) {
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(self.fields.content, &token),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+        let query = BooleanQuery::new(clauses);
+
+        let mut files = Vec::new();
+        for address in searcher.search(&query, &DocSetCollector)? {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let (Some(path), Some(content)) = (
+                extract_text(&doc, self.fields.path),
+                extract_text(&doc, self.fields.content),
+            ) else {
+                continue;
+            };
+            let importers = extract_u64(&doc, self.fields.references).unwrap_or(0);
+            files.push((path, importers, content));
+        }
+        SymbolRefs::collect(symbol, files, limit, start)
+    }
+
+    /// The lines of indexed files carrying one of `markers` (`ygrep todos`)
+    pub fn todos(&self, markers: &[String]) -> Result<Vec<TodoItem>> {
+        todos::validate_markers(markers)?;
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+
+        // Files containing any marker's tokens; lines are matched exactly afterwards
+        let mut any: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for marker in markers {
+            for token in tokenize(&self.index, self.fields.content, marker) {
+                any.push((
+                    Occur::Should,
+                    Box::new(TermQuery::new(
+                        Term::from_field_text(self.fields.content, &token),
+                        IndexRecordOption::Basic,
+                    )),
+                ));
+            }
+        }
+        let query = BooleanQuery::new(vec![
+            (
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(self.fields.unit, units::FILE),
+                    IndexRecordOption::Basic,
+                )) as Box<dyn Query>,
+            ),
+            (Occur::Must, Box::new(BooleanQuery::new(any))),
+        ]);
+
+        let mut items = Vec::new();
+        for address in searcher.search(&query, &DocSetCollector)? {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let (Some(path), Some(content)) = (
+                extract_text(&doc, self.fields.path),
+                extract_text(&doc, self.fields.content),
+            ) else {
+                continue;
+            };
+            let regions = extract_text(&doc, self.fields.regions);
+            items.extend(todos::find_markers(
+                markers,
+                &path,
+                &content,
+                regions.as_deref(),
+            ));
+        }
+        Ok(items)
+    }
+
+    /// Every indexed file path (unique, sorted), read from the path term dictionary
+    pub fn indexed_paths(&self) -> Result<Vec<String>> {
+        Ok(live_terms(&self.index, self.fields.path)?
+            .into_iter()
+            .collect())
+    }
+
+    /// Every doc_id in the index (files and their chunks), from the doc_id term dictionary
+    pub fn indexed_doc_ids(&self) -> Result<BTreeSet<String>> {
+        live_terms(&self.index, self.fields.doc_id)
+    }
+
+    /// Fill in which chunk of its file each chunk hit is and the lines it covers
+    /// (`search.chunk_context`)
+    pub fn add_chunk_context(&self, hits: &mut [SearchHit]) -> Result<()> {
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for hit in hits.iter_mut().filter(|hit| hit.is_chunk) {
+            let Some(doc) = self.doc_by_id(&searcher, &hit.doc_id)? else {
+                continue;
+            };
+            if extract_text(&doc, self.fields.unit).as_deref() != Some(units::CHUNK) {
+                continue;
+            }
+            let parent_doc = extract_text(&doc, self.fields.parent_doc).unwrap_or_default();
+            // Chunk doc_ids are `<parent doc_id>:<n>`, counting from 0
+            let Some(number) = hit
+                .doc_id
+                .strip_prefix(parent_doc.as_str())
+                .and_then(|rest| rest.strip_prefix(':'))
+                .and_then(|n| n.parse::<usize>().ok())
+            else {
+                continue;
+            };
+            let count = match counts.get(&parent_doc) {
+                Some(count) => *count,
+                None => {
+                    let count = searcher.search(&self.chunks_of(&parent_doc), &Count)?;
+                    counts.insert(parent_doc.clone(), count);
+                    count
+                }
+            };
+            hit.chunk = Some(ChunkContext {
+                number: number + 1,
+                count,
+                line_start: extract_u64(&doc, self.fields.line_start).unwrap_or(1),
+                line_end: extract_u64(&doc, self.fields.line_end).unwrap_or(1),
+                parent_doc,
+            });
+        }
+        Ok(())
+    }
+
+    /// A file as it is indexed, by doc_id (a chunk's giving its whole file) or path: its
+    /// own document, or its chunks put back together when that is missing
+    pub fn indexed_file(&self, id: &str) -> Result<Option<IndexedFile>> {
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+        let path = match self.doc_by_id(&searcher, id)? {
+            Some(doc) => extract_text(&doc, self.fields.path).unwrap_or_default(),
+            None => id.to_string(),
+        };
+        let query = TermQuery::new(
+            Term::from_field_text(self.fields.path, &path),
+            IndexRecordOption::Basic,
+        );
+
+        let mut file = None;
+        let mut chunks = Vec::new();
+        for address in searcher.search(&query, &DocSetCollector)? {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            match extract_text(&doc, self.fields.unit).as_deref() {
+                Some(units::CHUNK) => chunks.push(doc),
+                Some(units::LINE) => {}
+                // (indexes from before `unit` existed hold files and chunks alike)
+                _ if extract_text(&doc, self.fields.chunk_id).is_some_and(|id| !id.is_empty()) => {
+                    chunks.push(doc)
+                }
+                _ => file = Some(doc),
+            }
+        }
+
+        let count = chunks.len();
+        let truncated =
+            |doc: &TantivyDocument| extract_bool(doc, self.fields.truncated).unwrap_or(false);
+        if let Some(doc) = file {
+            return Ok(Some(IndexedFile {
+                path,
+                doc_id: extract_text(&doc, self.fields.doc_id).unwrap_or_default(),
+                content: extract_text(&doc, self.fields.content).unwrap_or_default(),
+                chunks: count,
+                from_chunks: false,
+                truncated: truncated(&doc),
+            }));
+        }
+        let Some(first) = chunks.first() else {
+            return Ok(None);
+        };
+        Ok(Some(IndexedFile {
+            doc_id: extract_text(first, self.fields.parent_doc).unwrap_or_default(),
+            truncated: truncated(first),
+            content: stitch(
+                chunks
+                    .iter()
+                    .map(|doc| {
+                        (
+                            extract_u64(doc, self.fields.line_start).unwrap_or(1),
+                            extract_text(doc, self.fields.content).unwrap_or_default(),
+                        )
+                    })
+                    .collect(),
+            ),
+            path,
+            chunks: count,
+            from_chunks: true,
+        }))
+    }
+
+    /// A hit with `lines_before` and `lines_after` more lines of its file, by doc_id: the
+    /// lines of its document (a chunk's, a line's, or a whole file), or those given after
+    /// it as `<doc_id>:L<start>-<end>` (`SearchHit::expand_id`)
+    pub fn expand_hit(
+        &self,
+        id: &str,
+        lines_before: usize,
+        lines_after: usize,
+    ) -> Result<Option<ExpandedHit>> {
+        let reader = self.index.reader()?;
+        let searcher = reader.searcher();
+        let (doc_id, lines) = match id.rsplit_once(":L").and_then(|(doc_id, lines)| {
+            let (start, end) = lines.split_once('-').unwrap_or((lines, lines));
+            Some((
+                doc_id,
+                (start.parse::<u64>().ok()?, end.parse::<u64>().ok()?),
+            ))
+        }) {
+            // (a line document's own id ends in `:L<line>` too)
+            Some((doc_id, lines)) if self.doc_by_id(&searcher, doc_id)?.is_some() => {
+                (doc_id, Some(lines))
+            }
+            _ => (id, None),
+        };
+        let Some(doc) = self.doc_by_id(&searcher, doc_id)? else {
+            return Ok(None);
+        };
+        let lines = lines.unwrap_or_else(|| {
+            (
+                extract_u64(&doc, self.fields.line_start).unwrap_or(1),
+                extract_u64(&doc, self.fields.line_end).unwrap_or(u64::MAX),
+            )
+        });
+        let path = extract_text(&doc, self.fields.path).unwrap_or_default();
+        let Some(file) = self.indexed_file(&path)? else {
+            return Ok(None);
+        };
+        let (line_start, line_end, content) =
+            expand_lines(&file.content, lines, lines_before, lines_after);
+        Ok(Some(ExpandedHit {
+            path,
+            doc_id: doc_id.to_string(),
+            hit_line_start: lines.0,
+            hit_line_end: lines.1.min(line_end),
+            line_start,
+            line_end,
+            content,
+        }))
+    }
+
+    /// The live document with this doc_id
+    fn doc_by_id(
+        &self,
+        searcher: &tantivy::Searcher,
+        doc_id: &str,
+    ) -> Result<Option<TantivyDocument>> {
+        let query = TermQuery::new(
+            Term::from_field_text(self.fields.doc_id, doc_id),
+            IndexRecordOption::Basic,
+        );
+        match searcher
+            .search(&query, &DocSetCollector)?
+            .into_iter()
+            .next()
+        {
+            Some(address) => Ok(Some(searcher.doc(address)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The chunk documents of the file with doc_id `parent_doc`
+    fn chunks_of(&self, parent_doc: &str) -> BooleanQuery {
+        let term = |field, value: &str| -> Box<dyn Query> {
+            Box::new(TermQuery::new(
+                Term::from_field_text(field, value),
+                IndexRecordOption::Basic,
+            ))
+        };
+        BooleanQuery::new(vec![
+            (Occur::Must, term(self.fields.parent_doc, parent_doc)),
+            (Occur::Must, term(self.fields.unit, units::CHUNK)),
+        ])
+    }
+
+    /// Line documents, when the index has them (`indexer.line_index`): grep-style
+    /// searches then match line by line, returning every matching line
+    fn line_docs(&self, searcher: &tantivy::Searcher) -> Result<Option<Box<dyn Query>>> {
+        let term = Term::from_field_text(self.fields.unit, units::LINE);
+        Ok((searcher.doc_freq(&term)? > 0).then(|| line_docs_query(&self.fields)))
+    }
+
+    /// Corrected queries for a search without hits, replacing words that aren't in the
+    /// index with the closest indexed terms ("did you mean ...")
+    pub fn suggest(&self, query: &str) -> Result<Vec<String>> {
+        let reader = self.index.reader()?;
+        suggest::suggest(&reader.searcher(), self.fields.content, query)
+    }
+
+    /// Words of a literal query that can be looked up in the index. Words with a lone CJK
+    /// character are left out: CJK runs are indexed as character pairs, which a single
+    /// character can't be looked up in.
+    fn literal_words<'a>(&self, query: &'a str) -> Vec<&'a str> {
+        query
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|s| !s.is_empty() && !has_lone_cjk(s))
+            .collect()
+    }
+
+    /// Index query for the words of a literal query. In identifier mode a compound
+    /// identifier also looks up how its other case conventions are indexed (`getUserId`:
+    /// `getuserid`, `get_user_id`, `get-user-id`), so the index needs nothing extra.
+    fn literal_index_query(&self, words: &[&str]) -> String {
+        if !self.config.identifier_matching {
+            return words.join(" ");
+        }
+        let mut terms = Vec::new();
+        for word in words {
+            let parts: Vec<String> = identifier_parts(word)
+                .iter()
+                .map(|part| part.to_lowercase())
+                .collect();
+            if parts.len() < 2 {
+                terms.push(word.to_string());
+                continue;
+            }
+            for separator in ["", "_", "-"] {
+                terms.push(format!("\"{}\"", parts.join(separator)));
+            }
+        }
+        terms.join(" ")
+    }
+
+    /// How `search_filtered` would run a query, or with `hybrid` the BM25 side of hybrid
+    /// search (the regex search, with `use_regex`): the index terms, the exact check,
+    /// scans, filters and boosts
+    pub fn plan(
+        &self,
+        query: &str,
+        filters: SearchFilters,
+        use_regex: bool,
+        hybrid: bool,
+    ) -> Result<QueryPlan> {
+        let parsed = ParsedQuery::parse(query, self.config.negation);
+        let mut filters = filters;
+        parsed.merge_into(&mut filters);
+
+        let reader = self.index.reader()?;
+        let line_index = self.line_docs(&reader.searcher())?.is_some() && (!hybrid || use_regex);
+        let mut query_parser = QueryParser::for_index(&self.index, vec![self.fields.content]);
+        let case = |case_sensitive: bool| {
+            if case_sensitive {
+                "case-sensitive"
+            } else {
+                "case-insensitive"
+            }
+        };
+
+        let mut plan = QueryPlan {
+            query: query.to_string(),
+            mode: PlanMode::Literal,
+            text: parsed.text.clone(),
+            index_terms: Vec::new(),
+            stemmed: Vec::new(),
+            stopwords: Vec::new(),
+            semantic: None,
+            synonyms: None,
+            post_filter: None,
+            scan: None,
+            filters: filters.describe(),
+            excluded: parsed.excluded.clone(),
+            proximity: parsed
+                .proximity
+                .iter()
+                .map(|p| format!("\"{}\"~{}", p.terms.join(" "), p.distance))
+                .collect(),
+            scopes: parsed
+                .scopes
+                .iter()
+                .map(|scope| scope.as_str().to_string())
+                .collect(),
+            boosts: self.bm25.describe(),
+            hybrid,
+            hybrid_reason: None,
+        };
+
+        // Prose files are also looked up by stems in hybrid and case-insensitive literal
+        // searches
+        let mut stemmed = false;
+        let index_query = if hybrid && !use_regex {
+            stemmed = self.stemming.is_some();
+            plan.mode = PlanMode::Hybrid;
+            plan.text = parsed.search_text();
+            let synonyms = self
+                .config
+                .expand_synonyms
+                .then(|| Synonyms::load(&self.config));
+            plan.synonyms = synonyms
+                .as_ref()
+                .and_then(|synonyms| synonyms.expand_query(&plan.text));
+            plan.boosts.push(format!(
+                "reciprocal rank fusion: BM25 weight {}, vector weight {}",
+                self.config.bm25_weight, self.config.vector_weight
+            ));
+            Some(hybrid_bm25_query(&plan.text, synonyms.as_ref()))
+        } else if use_regex {
+            plan.mode = PlanMode::Regex;
+            let case_sensitive = self.is_case_sensitive(&parsed.text, true);
+            // Fails like the search would on an invalid pattern
+            RegexMatcher::new(&parsed.text, self.config.regex_engine, case_sensitive)?;
+            plan.post_filter = Some(format!(
+                "matches /{}/ ({} engine, {}{})",
+                parsed.text,
+                self.config.regex_engine,
+                case(case_sensitive),
+                if line_index { ", line by line" } else { "" }
+            ));
+            let mut scan = "every other document, until the limit is reached".to_string();
+            if let Some(ms) = self.config.regex_timeout_ms {
+                scan.push_str(&format!(" or {}ms have passed", ms));
+            }
+            if let Some(max) = self.config.regex_max_scanned {
+                scan.push_str(&format!(" or {} documents were read", max));
+            }
+            plan.scan = Some(scan);
+            if hybrid {
+                plan.semantic = Some(regex_semantic_text(&parsed.text));
+                plan.boosts.push(format!(
+                    "reciprocal rank fusion: regex weight {}, vector weight {}",
+                    self.config.bm25_weight, self.config.vector_weight
+                ));
+            }
+            let words = regex_words(&parsed.text);
+            (!words.is_empty()).then(|| words.join(" "))
+        } else {
+            let case_sensitive = self.is_case_sensitive(&parsed.text, false);
+            let words = self.literal_words(&parsed.text);
+            let search_terms = self.bm25.without_stopwords(words.clone());
+            plan.stopwords = words
+                .iter()
+                .filter(|word| !search_terms.contains(word))
+                .map(|word| word.to_string())
+                .collect();
+
+            let mut normalized = vec![case(case_sensitive)];
+            if self.config.identifier_matching {
+                normalized.push("any identifier case");
+            }
+            if self.config.fold_diacritics {
+                normalized.push("ignoring diacritics");
+            }
+            if line_index {
+                normalized.push("line by line");
+            }
+            let proximity_only = search_terms.is_empty() && !parsed.proximity.is_empty();
+            if !proximity_only && !parsed.text.is_empty() {
+                let mut post_filter = format!(
+                    "contains \"{}\" ({})",
+                    self.normalizer().normalize(&parsed.text),
+                    normalized.join(", ")
+                );
+                if let Some(ref stemming) = self.stemming {
+                    stemmed = !case_sensitive && !search_terms.is_empty();
+                    if stemmed {
+                        post_filter.push_str(&format!(
+                            ", or its stems in .{} files",
+                            stemming.extensions.join("/.")
+                        ));
+                    }
+                }
+                plan.post_filter = Some(post_filter);
+            }
+            if !proximity_only && (search_terms.is_empty() || is_punctuated(&parsed.text)) {
+                plan.scan =
+                    Some("every other document, if no candidate contains the text".to_string());
+            }
+            (!search_terms.is_empty()).then(|| self.literal_index_query(&search_terms))
+        };
+
+        if stemmed {
+            query_parser =
+                QueryParser::for_index(&self.index, vec![self.fields.content, self.fields.prose]);
+        }
+        if let Some(index_query) = index_query {
+            let (index_query, _errors) = query_parser.parse_query_lenient(&index_query);
+            index_query.query_terms(&mut |term, _| {
+                let text = term.value().as_str().unwrap_or_default().to_string();
+                let terms = if term.field() == self.fields.content {
+                    &mut plan.index_terms
+                } else if term.field() == self.fields.prose {
+                    &mut plan.stemmed
+                } else {
+                    return;
+                };
+                if !terms.contains(&text) {
+                    terms.push(text);
+                }
+            });
+        }
+        Ok(plan)
+    }
+
+    /// Normalization applied to both sides of the literal comparison
+    fn normalizer(&self) -> MatchNormalizer {
+        MatchNormalizer {
+            fold_diacritics: self.config.fold_diacritics,
+            identifiers: self.config.identifier_matching,
+        }
+    }
+
+    /// Smart case: queries with an uppercase letter match case-sensitively
+    fn is_case_sensitive(&self, query: &str, is_regex: bool) -> bool {
+        self.config.smart_case && has_uppercase(query, is_regex)
+    }
+
+    /// Score breakdown for a BM25-only hit (rank is 0-based)
+    fn explain_bm25(&self, rank: usize, score: f32) -> Option<ScoreExplanation> {
+        self.config.explain.then(|| ScoreExplanation {
+            bm25_rank: Some(rank + 1),
+            bm25_score: Some(score),
+            ..Default::default()
         })
     }
 }
 
+/// Matching documents found by a full scan
+struct DocumentScan {
+    docs: Vec<TantivyDocument>,
+    /// Why the scan stopped before covering every document
+    incomplete: Option<String>,
+}
+
+/// Caps how many hits one file contributes (`search.max_per_file`)
+pub(crate) struct PerFileLimit {
+    max: Option<usize>,
+    counts: HashMap<String, usize>,
+}
+
+impl PerFileLimit {
+    pub fn new(max: Option<usize>) -> Self {
+        Self {
+            max,
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Count a hit for `path`; false once the file has reached its cap
+    pub fn admit(&mut self, path: &str) -> bool {
+        let Some(max) = self.max else {
+            return true;
+        };
+        let count = self.counts.entry(path.to_string()).or_default();
+        *count += 1;
+        *count <= max
+    }
+}
+
 /// Filters for search
 #[derive(Debug, Clone, Default)]
 pub struct SearchFilters {
@@ -298,6 +1236,532 @@ pub struct SearchFilters {
     pub extensions: Option<Vec<String>>,
     /// Filter by path patterns
     pub paths: Option<Vec<String>>,
+    /// Only files under these directories (relative to the workspace root, `""` for all),
+    /// e.g. those of the packages named with `--package`
+    pub dirs: Option<Vec<String>>,
+    /// Only files in these git submodules (by name, as in `.gitmodules`)
+    pub submodules: Option<Vec<String>>,
+    /// Restrict to these exact paths (e.g., from a saved result set)
+    pub within: Option<Vec<String>>,
+    /// Filter by language (e.g., ["rust", "python"])
+    pub languages: Option<Vec<String>>,
+    /// Only files of these kinds (e.g., tests)
+    pub kinds: Option<Vec<FileKind>>,
+    /// Skip files of these kinds (e.g., generated)
+    pub exclude_kinds: Option<Vec<FileKind>>,
+}
+
+impl SearchFilters {
+    /// Check whether a path passes the extension, path and language filters
+    pub fn matches_path(&self, path: &str) -> bool {
+        self.matches(path, None)
+    }
+
+    /// Like `matches_path`, but a path without an extension is typed by `indexed_extension`
+    /// (what the indexer detected from its shebang or file name, e.g. `py` for `bin/deploy`)
+    pub fn matches(&self, path: &str, indexed_extension: Option<&str>) -> bool {
+        let path_extension = std::path::Path::new(path)
+            .extension()
+            .map(|ext| ext.to_string_lossy());
+        let extension = path_extension.as_deref().or(indexed_extension);
+
+        if let Some(ref extensions) = self.extensions {
+            let matches_ext =
+                extension.is_some_and(|ext| extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)));
+            if !matches_ext {
+                return false;
+            }
+        }
+
+        if let Some(ref paths) = self.paths {
+            if !paths
+                .iter()
+                .map(|p| to_slash(p))
+                .any(|p| path.starts_with(p.as_ref()) || path.contains(p.as_ref()))
+            {
+                return false;
+            }
+        }
+
+        if let Some(ref dirs) = self.dirs {
+            if !dirs.iter().any(|dir| is_in_dir(path, dir)) {
+                return false;
+            }
+        }
+
+        if let Some(ref languages) = self.languages {
+            let language = language_for_path(std::path::Path::new(path))
+                .or_else(|| indexed_extension.and_then(language_for_extension));
+            if !language.is_some_and(|lang| languages.iter().any(|l| l.eq_ignore_ascii_case(lang)))
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Whether a file kind passes the kind filters
+    pub fn matches_kind(&self, kind: FileKind) -> bool {
+        self.kinds
+            .as_ref()
+            .is_none_or(|kinds| kinds.contains(&kind))
+            && !self
+                .exclude_kinds
+                .as_ref()
+                .is_some_and(|kinds| kinds.contains(&kind))
+    }
+
+    /// `matches`, looking up the indexed extension of extensionless paths when an
+    /// extension or language filter is set, and the indexed kind and submodule when a kind
+    /// or submodule filter is
+    pub(crate) fn matches_indexed(
+        &self,
+        path: &str,
+        searcher: &tantivy::Searcher,
+        fields: &SchemaFields,
+    ) -> bool {
+        if let Some(ref submodules) = self.submodules {
+            let submodule = indexed_text(searcher, fields, path, fields.submodule);
+            if !submodule.is_some_and(|submodule| submodules.contains(&submodule)) {
+                return false;
+            }
+        }
+        if self.kinds.is_some() || self.exclude_kinds.is_some() {
+            let kind = indexed_text(searcher, fields, path, fields.kind)
+                .and_then(|kind| kind.parse().ok())
+                .unwrap_or_default();
+            if !self.matches_kind(kind) {
+                return false;
+            }
+        }
+
+        let typed = self.extensions.is_some() || self.languages.is_some();
+        if !typed || std::path::Path::new(path).extension().is_some() {
+            return self.matches_path(path);
+        }
+        self.matches(
+            path,
+            indexed_text(searcher, fields, path, fields.extension).as_deref(),
+        )
+    }
+
+    /// One line per filter that is set, e.g. `ext: rs, py`
+    pub fn describe(&self) -> Vec<String> {
+        let list = |values: &[String]| values.join(", ");
+        let kinds = |kinds: &[FileKind]| {
+            kinds
+                .iter()
+                .map(|kind| kind.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut described = Vec::new();
+        if let Some(ref extensions) = self.extensions {
+            described.push(format!("ext: {}", list(extensions)));
+        }
+        if let Some(ref paths) = self.paths {
+            described.push(format!("path: {}", list(paths)));
+        }
+        if let Some(ref dirs) = self.dirs {
+            let dirs: Vec<String> = dirs
+                .iter()
+                .map(|dir| if dir.is_empty() { "." } else { dir }.to_string())
+                .collect();
+            described.push(format!("dir: {}", list(&dirs)));
+        }
+        if let Some(ref languages) = self.languages {
+            described.push(format!("lang: {}", list(languages)));
+        }
+        if let Some(ref submodules) = self.submodules {
+            described.push(format!("submodule: {}", list(submodules)));
+        }
+        if let Some(ref within) = self.within {
+            described.push(format!("within: {} files", within.len()));
+        }
+        if let Some(ref included) = self.kinds {
+            described.push(format!("kind: {}", kinds(included)));
+        }
+        if let Some(ref excluded) = self.exclude_kinds {
+            described.push(format!("not kind: {}", kinds(excluded)));
+        }
+        described
+    }
+
+    /// Index-side restriction for all the filters, so they're applied inside Tantivy (to
+    /// files and their chunks alike) rather than to the results; `None` without any
+    pub(crate) fn index_query(
+        &self,
+        index: &Index,
+        fields: &SchemaFields,
+    ) -> Result<Option<Box<dyn Query>>> {
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        if let Some(ref within) = self.within {
+            clauses.push((Occur::Must, path_set_query(fields, within)));
+        }
+        if let Some(ref extensions) = self.extensions {
+            clauses.push((Occur::Must, extension_query(fields, extensions)?));
+        }
+        if let Some(ref paths) = self.paths {
+            clauses.push((Occur::Must, path_pattern_query(fields, paths)?));
+        }
+        if let Some(ref dirs) = self.dirs {
+            // The workspace root holds every file
+            if !dirs.iter().any(|dir| dir.is_empty()) {
+                clauses.push((Occur::Must, dir_query(fields, dirs)?));
+            }
+        }
+        if let Some(ref languages) = self.languages {
+            clauses.push((Occur::Must, language_query(index, fields, languages)?));
+        }
+        if let Some(ref submodules) = self.submodules {
+            let terms = submodules
+                .iter()
+                .map(|name| Term::from_field_text(fields.submodule, name));
+            clauses.push((Occur::Must, Box::new(TermSetQuery::new(terms))));
+        }
+        if let Some(kinds) = self.kind_query(fields) {
+            clauses.push((Occur::Must, kinds));
+        }
+
+        Ok(match clauses.len() {
+            0 => None,
+            1 => Some(clauses.remove(0).1),
+            _ => Some(Box::new(BooleanQuery::new(clauses))),
+        })
+    }
+
+    /// Index-side restriction for the kind filters (`None` without any)
+    fn kind_query(&self, fields: &SchemaFields) -> Option<Box<dyn Query>> {
+        let terms = |kinds: &Vec<FileKind>| {
+            let terms = kinds
+                .iter()
+                .map(|kind| Term::from_field_text(fields.kind, kind.as_str()));
+            Box::new(TermSetQuery::new(terms)) as Box<dyn Query>
+        };
+        if self.kinds.is_none() && self.exclude_kinds.is_none() {
+            return None;
+        }
+        // (a query of only exclusions would match nothing)
+        let included = match self.kinds {
+            Some(ref kinds) => terms(kinds),
+            None => Box::new(tantivy::query::AllQuery),
+        };
+        let mut clauses = vec![(Occur::Must, included)];
+        if let Some(ref excluded) = self.exclude_kinds {
+            clauses.push((Occur::MustNot, terms(excluded)));
+        }
+        Some(Box::new(BooleanQuery::new(clauses)))
+    }
+}
+
+/// Tantivy query string for the BM25 side of hybrid search: the query as a phrase
+/// (like grep), OR-ed with its synonym expansion
+pub(crate) fn hybrid_bm25_query(query: &str, synonyms: Option<&Synonyms>) -> String {
+    let quoted_query = format!("\"{}\"", query.replace('"', "\\\""));
+    match synonyms.and_then(|synonyms| synonyms.expand_query(query)) {
+        Some(expanded) => format!("{} OR ({})", quoted_query, expanded),
+        None => quoted_query,
+    }
+}
+
+/// BM25 candidates (score, address) numbered by rank
+fn ranked(top_docs: Vec<(f32, DocAddress)>) -> Vec<(usize, f32, DocAddress)> {
+    top_docs
+        .into_iter()
+        .enumerate()
+        .map(|(rank, (score, address))| (rank, score, address))
+        .collect()
+}
+
+/// Load and check a batch of ranked candidates in parallel, keeping their order: the hit
+/// `check` makes of each (`None` for those that don't match)
+fn check_candidates(
+    searcher: &tantivy::Searcher,
+    batch: &[(usize, f32, DocAddress)],
+    check: &(dyn Fn(usize, f32, &TantivyDocument) -> Option<SearchHit> + Sync),
+) -> Result<Vec<Option<SearchHit>>> {
+    batch
+        .par_iter()
+        .map(|&(rank, score, address)| {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            Ok(check(rank, score, &doc))
+        })
+        .collect()
+}
+
+/// Words of a regex pattern used to rank candidates: a rough heuristic for its literal
+/// parts, skipping single characters (likely regex syntax)
+fn regex_words(pattern: &str) -> Vec<&str> {
+    pattern
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|s| !s.is_empty() && s.len() > 1)
+        .collect()
+}
+
+/// Text embedded for the semantic side of a hybrid regex search: the words of the
+/// pattern, or the pattern itself when it has none
+pub(crate) fn regex_semantic_text(pattern: &str) -> String {
+    let words: Vec<&str> = regex_words(pattern)
+        .into_iter()
+        .map(|word| word.trim_matches('_'))
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.is_empty() {
+        pattern.to_string()
+    } else {
+        words.join(" ")
+    }
+}
+
+/// Whether a word has a CJK character without a CJK neighbour
+fn has_lone_cjk(word: &str) -> bool {
+    let chars: Vec<char> = word.chars().collect();
+    (0..chars.len()).any(|i| {
+        is_cjk(chars[i])
+            && !(i > 0 && is_cjk(chars[i - 1]))
+            && !chars.get(i + 1).is_some_and(|&c| is_cjk(c))
+    })
+}
+
+/// Whether a query has punctuation, which may tokenize differently in the files
+fn is_punctuated(query: &str) -> bool {
+    query
+        .chars()
+        .any(|c| !c.is_alphanumeric() && c != '_' && !c.is_whitespace())
+}
+
+/// The documents of single lines (`indexer.line_index`)
+pub(crate) fn line_docs_query(fields: &SchemaFields) -> Box<dyn Query> {
+    let term = Term::from_field_text(fields.unit, units::LINE);
+    Box::new(TermQuery::new(term, IndexRecordOption::Basic))
+}
+
+/// Documents with one of these exact paths
+fn path_set_query(fields: &SchemaFields, paths: &[String]) -> Box<dyn Query> {
+    let terms = paths.iter().map(|p| Term::from_field_text(fields.path, p));
+    Box::new(TermSetQuery::new(terms))
+}
+
+/// Documents with one of these extensions (case-insensitively): by the path, or by the
+/// extension indexed for extensionless files typed by their shebang or name
+fn extension_query(fields: &SchemaFields, extensions: &[String]) -> Result<Box<dyn Query>> {
+    let escaped: Vec<String> = extensions.iter().map(|e| regex::escape(e)).collect();
+    let by_path =
+        RegexQuery::from_pattern(&format!(r"(?i).*\.(?:{})", escaped.join("|")), fields.path)?;
+    let by_type = TermSetQuery::new(
+        extensions
+            .iter()
+            .map(|e| Term::from_field_text(fields.extension, &e.to_lowercase())),
+    );
+    Ok(Box::new(BooleanQuery::new(vec![
+        (Occur::Should, Box::new(by_path) as Box<dyn Query>),
+        (Occur::Should, Box::new(by_type)),
+    ])))
+}
+
+/// Documents of files in one of these languages. A language comes from the file name
+/// (`Makefile`) or extension, so those paths are picked from the path term dictionary, or
+/// from the shebang of an extensionless file, which was indexed with the language's
+/// extension (`py` for `bin/deploy`).
+fn language_query(
+    index: &Index,
+    fields: &SchemaFields,
+    languages: &[String],
+) -> Result<Box<dyn Query>> {
+    let wanted = |language: &str| languages.iter().any(|l| l.eq_ignore_ascii_case(language));
+    let by_path: Vec<String> = live_terms(index, fields.path)?
+        .into_iter()
+        .filter(|path| language_for_path(std::path::Path::new(path)).is_some_and(wanted))
+        .collect();
+    let by_type = TermSetQuery::new(
+        languages
+            .iter()
+            .filter_map(|language| extension_for_language(&language.to_lowercase()))
+            .map(|ext| Term::from_field_text(fields.extension, ext)),
+    );
+    Ok(Box::new(BooleanQuery::new(vec![
+        (Occur::Should, path_set_query(fields, &by_path)),
+        (Occur::Should, Box::new(by_type)),
+    ])))
+}
+
+/// Documents whose path contains one of these patterns
+fn path_pattern_query(fields: &SchemaFields, patterns: &[String]) -> Result<Box<dyn Query>> {
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+    for pattern in patterns {
+        let contains = format!(".*{}.*", regex::escape(&to_slash(pattern)));
+        clauses.push((
+            Occur::Should,
+            Box::new(RegexQuery::from_pattern(&contains, fields.path)?),
+        ));
+    }
+    Ok(Box::new(BooleanQuery::new(clauses)))
+}
+
+/// Files under any of the directories
+fn dir_query(fields: &SchemaFields, dirs: &[String]) -> Result<Box<dyn Query>> {
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+    for dir in dirs {
+        let under = format!("{}/.*", regex::escape(&to_slash(dir)));
+        clauses.push((
+            Occur::Should,
+            Box::new(RegexQuery::from_pattern(&under, fields.path)?),
+        ));
+    }
+    Ok(Box::new(BooleanQuery::new(clauses)))
+}
+
+/// Whether `path` lies under the directory `dir` (both relative to the workspace root)
+fn is_in_dir(path: &str, dir: &str) -> bool {
+    dir.is_empty()
+        || path
+            .strip_prefix(to_slash(dir).as_ref())
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Terms of a string field that belong to at least one live document
+fn live_terms(index: &Index, field: Field) -> Result<BTreeSet<String>> {
+    let reader = index.reader()?;
+    let searcher = reader.searcher();
+    let mut found = BTreeSet::new();
+
+    for segment in searcher.segment_readers() {
+        let inverted = segment.inverted_index(field)?;
+        let alive = segment.alive_bitset();
+        let mut terms = inverted.terms().stream()?;
+        while terms.advance() {
+            // Terms of deleted documents linger until segments merge
+            let mut postings =
+                inverted.read_postings_from_terminfo(terms.value(), IndexRecordOption::Basic)?;
+            let mut live = false;
+            while postings.doc() != TERMINATED {
+                if alive.is_none_or(|alive| alive.is_alive(postings.doc())) {
+                    live = true;
+                    break;
+                }
+                postings.advance();
+            }
+            if live {
+                found.insert(String::from_utf8_lossy(terms.key()).into_owned());
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/// A text field stored for a file at index time (skipping empty values, as in chunks)
+fn indexed_text(
+    searcher: &tantivy::Searcher,
+    fields: &SchemaFields,
+    path: &str,
+    field: Field,
+) -> Option<String> {
+    let query = TermQuery::new(
+        Term::from_field_text(fields.path, path),
+        IndexRecordOption::Basic,
+    );
+    let docs = searcher.search(&query, &DocSetCollector).ok()?;
+    docs.into_iter().find_map(|address| {
+        let doc = searcher.doc(address).ok()?;
+        extract_text(&doc, field).filter(|value| !value.is_empty())
+    })
+}
+
+/// Constraints on a search beyond the query text
+#[derive(Default)]
+struct Restriction {
+    /// Documents must also match these queries (e.g. a saved result set)
+    filters: Vec<Box<dyn Query>>,
+    /// Negated terms and proximity clauses from the query string
+    query: ParsedQuery,
+}
+
+impl Restriction {
+    /// Combine with the main query. Whole-word exclusions become MustNot clauses and
+    /// proximity clauses become sloppy phrase queries; `ParsedQuery::allows` does the
+    /// exact checks afterwards since the index can't match substrings or lines.
+    fn apply(&self, query: Box<dyn Query>, index: &Index, content_field: Field) -> Box<dyn Query> {
+        let mut clauses = vec![(Occur::Must, query)];
+        for filter in &self.filters {
+            clauses.push((Occur::Must, filter.box_clone()));
+        }
+
+        for term in &self.query.excluded {
+            if term.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                let term = Term::from_field_text(content_field, term);
+                clauses.push((
+                    Occur::MustNot,
+                    Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+                ));
+            }
+        }
+
+        for proximity in &self.query.proximity {
+            let mut terms: Vec<Term> = tokenize(index, content_field, &proximity.terms.join(" "))
+                .iter()
+                .map(|token| Term::from_field_text(content_field, token))
+                .collect();
+            let clause: Box<dyn Query> = match terms.len() {
+                0 => continue,
+                1 => Box::new(TermQuery::new(terms.remove(0), IndexRecordOption::Basic)),
+                _ => {
+                    let mut phrase = PhraseQuery::new(terms);
+                    phrase.set_slop(proximity.distance);
+                    Box::new(phrase)
+                }
+            };
+            clauses.push((Occur::Must, clause));
+        }
+
+        if clauses.len() == 1 {
+            clauses.remove(0).1
+        } else {
+            Box::new(BooleanQuery::new(clauses))
+        }
+    }
+}
+
+/// Split text into index tokens using the content field's tokenizer
+fn tokenize(index: &Index, field: Field, text: &str) -> Vec<String> {
+    let Ok(mut analyzer) = index.tokenizer_for_field(field) else {
+        return Vec::new();
+    };
+    let mut stream = analyzer.token_stream(text);
+    let mut tokens = Vec::new();
+    while stream.advance() {
+        tokens.push(stream.token().text.clone());
+    }
+    tokens
+}
+
+/// Whether the query contains an uppercase letter, ignoring regex escapes like `\S` or `\p{Lu}`
+fn has_uppercase(query: &str, is_regex: bool) -> bool {
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        if is_regex && c == '\\' {
+            if let Some('p' | 'P') = chars.next() {
+                // Skip the class name: `\pL` or `\p{Lu}`
+                let mut rest = chars.clone();
+                if rest.next() == Some('{') {
+                    for c in rest.by_ref() {
+                        if c == '}' {
+                            break;
+                        }
+                    }
+                    chars = rest;
+                } else {
+                    chars.next();
+                }
+            }
+            continue;
+        }
+        if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
 }
 
 /// Extract text value from a document
@@ -322,87 +1786,67 @@ fn extract_u64(doc: &tantivy::TantivyDocument, field: tantivy::schema::Field) ->
     })
 }
 
-/// Create a snippet showing lines relevant to the query
-/// Returns (snippet, line_offset_from_start, line_count)
-fn create_relevant_snippet(content: &str, query: &str, max_lines: usize) -> (String, usize, usize) {
-    let lines: Vec<&str> = content.lines().collect();
-    let query_lower = query.to_lowercase();
-    let query_terms: Vec<&str> = query_lower.split_whitespace().collect();
-
-    // Find lines that contain any query term
-    let mut matching_indices: Vec<usize> = Vec::new();
-    for (i, line) in lines.iter().enumerate() {
-        let line_lower = line.to_lowercase();
-        if query_terms.iter().any(|term| line_lower.contains(term)) {
-            matching_indices.push(i);
+/// Extract bool value from a document
+fn extract_bool(doc: &tantivy::TantivyDocument, field: tantivy::schema::Field) -> Option<bool> {
+    doc.get_first(field).and_then(|v| {
+        if let tantivy::schema::OwnedValue::Bool(b) = v {
+            Some(*b)
+        } else {
+            None
         }
-    }
-
-    if matching_indices.is_empty() {
-        // No direct matches, return first lines
-        let snippet = lines
-            .iter()
-            .take(max_lines)
-            .copied()
-            .collect::<Vec<_>>()
-            .join("\n");
-        let line_count = snippet.lines().count();
-        return (snippet, 0, line_count);
-    }
-
-    // Get context around the first match
-    let first_match = matching_indices[0];
-    let context_before = 2;
-    let context_after = max_lines.saturating_sub(context_before + 1);
-
-    let start = first_match.saturating_sub(context_before);
-    let end = (first_match + context_after + 1).min(lines.len());
-
-    let snippet = lines[start..end].join("\n");
-    let line_count = end - start;
-    (snippet, start, line_count)
+    })
 }
 
-/// Create a snippet showing lines relevant to a regex match
-/// Returns (snippet, line_offset_from_start, line_count)
-fn create_regex_snippet(
-    content: &str,
-    regex: &regex::Regex,
-    max_lines: usize,
-) -> (String, usize, usize) {
-    let lines: Vec<&str> = content.lines().collect();
+/// Where the first match in a document's content starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MatchPosition {
+    /// 0-based line within the content
+    line: usize,
+    /// 1-based byte column, when the match itself (not just its line) is known
+    column: Option<usize>,
+}
 
-    // Find lines that match the regex
-    let mut matching_indices: Vec<usize> = Vec::new();
-    for (i, line) in lines.iter().enumerate() {
-        if regex.is_match(line) {
-            matching_indices.push(i);
+impl MatchPosition {
+    /// Position of a byte offset in `text`
+    fn at(text: &str, offset: usize) -> Self {
+        let before = &text.as_bytes()[..offset];
+        let line_start = before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        Self {
+            line: before.iter().filter(|&&b| b == b'\n').count(),
+            column: Some(offset - line_start + 1),
         }
     }
+}
 
-    if matching_indices.is_empty() {
-        // No direct line matches, but document matched - return first lines
-        let snippet = lines
-            .iter()
-            .take(max_lines)
-            .copied()
-            .collect::<Vec<_>>()
-            .join("\n");
-        let line_count = snippet.lines().count();
-        return (snippet, 0, line_count);
+/// Where the literal query (already normalized) first occurs, like grep reports it.
+/// Normalization keeps line breaks, so offsets in the normalized content land on the
+/// same line.
+fn literal_match_position(
+    content: &str,
+    query_norm: &str,
+    case_sensitive: bool,
+    normalizer: &MatchNormalizer,
+) -> Option<MatchPosition> {
+    let content_norm = normalizer.normalize(content);
+    if case_sensitive {
+        let offset = content_norm.find(query_norm)?;
+        Some(MatchPosition::at(&content_norm, offset))
+    } else {
+        let content_lower = content_norm.to_lowercase();
+        let offset = content_lower.find(&query_norm.to_lowercase())?;
+        Some(MatchPosition::at(&content_lower, offset))
     }
+}
 
-    // Get context around the first match
-    let first_match = matching_indices[0];
-    let context_before = 2;
-    let context_after = max_lines.saturating_sub(context_before + 1);
-
-    let start = first_match.saturating_sub(context_before);
-    let end = (first_match + context_after + 1).min(lines.len());
-
-    let snippet = lines[start..end].join("\n");
-    let line_count = end - start;
-    (snippet, start, line_count)
+/// Where the first regex match starts; a match spanning several lines is reported at
+/// its first line
+fn regex_match_position(content: &str, regex: &RegexMatcher) -> Option<MatchPosition> {
+    regex
+        .find(content)
+        .map(|offset| MatchPosition::at(content, offset))
 }
 
 #[cfg(test)]
@@ -454,4 +1898,412 @@ mod tests {
 
         Ok(())
     }
+
+    /// Build an index holding one whole-file document per (path, content) pair
+    fn index_with(docs: &[(&str, &str)]) -> Result<(tempfile::TempDir, Index)> {
+        let temp_dir = tempdir().unwrap();
+        let schema = build_document_schema();
+        let index = Index::create_in_dir(temp_dir.path(), schema.clone())?;
+        crate::index::register_tokenizers(index.tokenizers());
+
+        let fields = SchemaFields::new(&schema);
+        let mut writer = index.writer(50_000_000)?;
+        for (path, content) in docs {
+            writer.add_document(doc!(
+                fields.doc_id => *path,
+                fields.path => *path,
+                fields.workspace => "/test",
+                fields.content => *content,
+                fields.mtime => 0u64,
+                fields.size => content.len() as u64,
+                fields.extension => std::path::Path::new(path)
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                fields.line_start => 1u64,
+                fields.line_end => content.lines().count() as u64,
+                fields.chunk_id => "",
+                fields.parent_doc => ""
+            ))?;
+        }
+        writer.commit()?;
+        Ok((temp_dir, index))
+    }
+
+    #[test]
+    fn test_smart_case() -> Result<()> {
+        let (_dir, index) = index_with(&[
+            ("upper.rs", "struct Config;"),
+            ("lower.rs", "let config = load();"),
+        ])?;
+        let config = SearchConfig {
+            smart_case: true,
+            ..Default::default()
+        };
+        let searcher = Searcher::new(config, index);
+
+        assert_eq!(searcher.search("config", None)?.hits.len(), 2);
+        let result = searcher.search("Config", None)?;
+        assert_eq!(result.hits.len(), 1);
+        assert_eq!(result.hits[0].path, "upper.rs");
+
+        // Escapes like \S don't count as uppercase
+        assert_eq!(searcher.search_regex(r"config\S*", None)?.hits.len(), 2);
+        assert_eq!(searcher.search_regex(r"C\w+g", None)?.hits.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unicode_normalization() -> Result<()> {
+        let (_dir, index) = index_with(&[
+            ("composed.rs", "// caf\u{e9} au lait"),
+            ("plain.rs", "// cafe au lait"),
+        ])?;
+
+        // Decomposed query matches the composed form only
+        let searcher = Searcher::new(SearchConfig::default(), index.clone());
+        let result = searcher.search("cafe\u{301} au", None)?;
+        assert_eq!(result.hits.len(), 1);
+        assert_eq!(result.hits[0].path, "composed.rs");
+
+        let config = SearchConfig {
+            fold_diacritics: true,
+            ..Default::default()
+        };
+        let searcher = Searcher::new(config, index);
+        assert_eq!(searcher.search("cafe au", None)?.hits.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_identifier_matching() -> Result<()> {
+        let (_dir, index) = index_with(&[
+            ("camel.ts", "const id = getUserId(req);"),
+            ("snake.py", "user = get_user_id(request)"),
+            ("screaming.rs", "const GET_USER_ID: &str = \"x\";"),
+            ("other.rs", "fn get_user() {}"),
+        ])?;
+        let config = SearchConfig {
+            identifier_matching: true,
+            ..Default::default()
+        };
+        let searcher = Searcher::new(config, index);
+
+        for query in ["getUserId", "get_user_id", "GET_USER_ID"] {
+            let mut paths: Vec<String> = searcher
+                .search(query, None)?
+                .hits
+                .into_iter()
+                .map(|h| h.path)
+                .collect();
+            paths.sort();
+            assert_eq!(
+                paths,
+                vec!["camel.ts", "screaming.rs", "snake.py"],
+                "{}",
+                query
+            );
+        }
+
+        // The index only has whole identifiers: without identifier mode each is exact
+        let exact = Searcher::new(SearchConfig::default(), searcher.index.clone());
+        let hits = exact.search("getUserId", None)?.hits;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "camel.ts");
+        Ok(())
+    }
+
+    #[test]
+    fn test_candidates_checked_in_rank_order() -> Result<()> {
+        // More candidates than fit in a batch, half of them matching
+        let docs: Vec<(String, String)> = (0..700)
+            .map(|i| {
+                let call = if i % 2 == 0 { "refresh()" } else { "revoke()" };
+                (
+                    format!("src/f{}.rs", i),
+                    format!("{}{}", "token ".repeat(1 + i % 7), call),
+                )
+            })
+            .collect();
+        let docs: Vec<(&str, &str)> = docs.iter().map(|(p, c)| (p.as_str(), c.as_str())).collect();
+        let (_dir, index) = index_with(&docs)?;
+        let config = SearchConfig {
+            max_limit: 1000,
+            ..Default::default()
+        };
+        let searcher = Searcher::new(config, index);
+
+        for result in [
+            searcher.search("refresh()", Some(300))?,
+            searcher.search_regex(r"token \w+\(\)", Some(300))?,
+        ] {
+            assert_eq!(result.hits.len(), 300);
+            assert!(result.hits.windows(2).all(|w| w[0].score >= w[1].score));
+        }
+        let result = searcher.search_regex(r"token refresh", Some(500))?;
+        assert_eq!(result.hits.len(), 350);
+        assert!(result
+            .hits
+            .iter()
+            .all(|hit| hit.snippet.contains("refresh")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_per_file() -> Result<()> {
+        // Chunks of one large file share its path
+        let (_dir, index) = index_with(&[
+            ("big.rs", "let pool = 1;"),
+            ("big.rs", "pool.get();"),
+            ("big.rs", "drop(pool);"),
+            ("small.rs", "fn pool() {}"),
+        ])?;
+        let config = SearchConfig {
+            max_per_file: Some(1),
+            ..Default::default()
+        };
+        let searcher = Searcher::new(config, index);
+
+        let mut paths: Vec<String> = searcher
+            .search("pool", None)?
+            .hits
+            .into_iter()
+            .map(|h| h.path)
+            .collect();
+        paths.sort();
+        assert_eq!(paths, vec!["big.rs", "small.rs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_filters_applied_in_index() -> Result<()> {
+        // Better-ranked files outside the filters mustn't crowd out the ones inside
+        let mut docs: Vec<(String, &str)> = (0..30)
+            .map(|i| (format!("src/pool{}.rs", i), "pool pool pool"))
+            .collect();
+        docs.extend((0..3).map(|i| (format!("tests/pool{}.PY", i), "let pool = new_pool();")));
+        docs.push(("src/pool (copy).rs".to_string(), "pool"));
+        let docs: Vec<(&str, &str)> = docs.iter().map(|(p, c)| (p.as_str(), *c)).collect();
+        let (_dir, index) = index_with(&docs)?;
+        let searcher = Searcher::new(SearchConfig::default(), index);
+        let search = |filters: SearchFilters, limit: usize| {
+            searcher.search_filtered("pool", Some(limit), filters, false)
+        };
+
+        let tests = SearchFilters {
+            paths: Some(vec!["tests/".to_string()]),
+            ..Default::default()
+        };
+        let result = search(tests, 2)?;
+        assert_eq!(result.hits.len(), 2);
+        assert!(result.hits.iter().all(|hit| hit.path.starts_with("tests/")));
+
+        // Extensions match case-insensitively; `total` counts only matching files
+        let python = SearchFilters {
+            extensions: Some(vec!["py".to_string()]),
+            ..Default::default()
+        };
+        let result = search(python, 10)?;
+        assert_eq!(result.total, 3);
+        assert!(result.hits.iter().all(|hit| hit.path.ends_with(".PY")));
+
+        // Regex metacharacters in path patterns are literal
+        let copy = SearchFilters {
+            paths: Some(vec!["(copy)".to_string()]),
+            within: Some(vec![
+                "src/pool (copy).rs".to_string(),
+                "src/pool1.rs".to_string(),
+            ]),
+            ..Default::default()
+        };
+        let result = search(copy, 10)?;
+        assert_eq!(result.hits.len(), 1);
+        assert_eq!(result.hits[0].path, "src/pool (copy).rs");
+
+        // Directories match whole path components
+        let dirs = |dirs: &[&str]| SearchFilters {
+            dirs: Some(dirs.iter().map(|dir| dir.to_string()).collect()),
+            ..Default::default()
+        };
+        assert_eq!(search(dirs(&["tests"]), 10)?.total, 3);
+        assert_eq!(search(dirs(&["test"]), 10)?.total, 0);
+        assert_eq!(search(dirs(&[""]), 100)?.total, 34);
+        Ok(())
+    }
+
+    #[test]
+    fn test_regex_scans_every_document() -> Result<()> {
+        let mut docs: Vec<(String, String)> = (0..120)
+            .map(|i| (format!("filler{:03}.rs", i), format!("let x = {};", i)))
+            .collect();
+        docs.push(("target.rs".to_string(), "zz marks the needle".to_string()));
+        let docs: Vec<(&str, &str)> = docs.iter().map(|(p, c)| (p.as_str(), c.as_str())).collect();
+        let (_dir, index) = index_with(&docs)?;
+        let searcher = Searcher::new(SearchConfig::default(), index.clone());
+
+        // No words to narrow by, with the match far past `limit * 50` documents
+        let result = searcher.search_regex("^z{2}", Some(1))?;
+        assert_eq!(result.hits.len(), 1);
+        assert_eq!(result.hits[0].path, "target.rs");
+        assert!(result.incomplete.is_none());
+
+        // Words that no indexed token contains (`\bneedle` yields `bneedle`)
+        let result = searcher.search_regex(r"\bneedle", None)?;
+        assert_eq!(result.hits.len(), 1);
+
+        // The escape valve stops the scan and says so
+        let config = SearchConfig {
+            regex_max_scanned: Some(10),
+            ..Default::default()
+        };
+        let searcher = Searcher::new(config, index);
+        let result = searcher.search_regex("^z{2}", None)?;
+        assert!(result.hits.is_empty());
+        assert!(result
+            .incomplete
+            .is_some_and(|reason| reason.contains("--max-scanned 10 after 10 of 121")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_literal_fallback_scan() -> Result<()> {
+        let (_dir, index) = index_with(&[
+            ("pipe.ex", "conn |> query() |> decode()"),
+            ("ternary.js", "const x = ok ? a : b;"),
+            ("regex.py", "re.compile(r'(?:abc)+')"),
+            ("dotted.rs", "let v = config.value;"),
+        ])?;
+        let searcher = Searcher::new(SearchConfig::default(), index);
+
+        // Nothing to search the index for: the files are scanned
+        let result = searcher.search("|>", None)?;
+        assert_eq!(result.hits.len(), 1);
+        assert_eq!(result.hits[0].path, "pipe.ex");
+        assert_eq!(searcher.search("(?:", None)?.hits[0].path, "regex.py");
+        assert!(searcher.search("=>", None)?.hits.is_empty());
+
+        // Words cut mid-token find no candidates, so the files are scanned too
+        assert_eq!(searcher.search("g.val", None)?.hits[0].path, "dotted.rs");
+        Ok(())
+    }
+
+    #[test]
+    fn test_cjk_search() -> Result<()> {
+        let (_dir, index) = index_with(&[
+            ("db.rs", "// 连接数据库失败时重试\nfn connect() {}"),
+            ("README.md", "数据同步"),
+        ])?;
+        let searcher = Searcher::new(SearchConfig::default(), index);
+        let paths = |query: &str| -> Result<Vec<String>> {
+            let mut paths: Vec<String> = searcher
+                .search(query, None)?
+                .hits
+                .into_iter()
+                .map(|hit| hit.path)
+                .collect();
+            paths.sort();
+            Ok(paths)
+        };
+
+        // Words inside unspaced CJK text are found through their character pairs
+        assert_eq!(paths("数据库")?, vec!["db.rs"]);
+        assert_eq!(paths("数据")?, vec!["README.md", "db.rs"]);
+        assert_eq!(paths("失败时")?, vec!["db.rs"]);
+        // A single character isn't indexed on its own: the files are scanned
+        assert_eq!(paths("库")?, vec!["db.rs"]);
+        assert!(paths("数据集")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_lines_like_grep() -> Result<()> {
+        let mut lines: Vec<String> = (1..=40).map(|i| format!("// filler {}", i)).collect();
+        lines[4] = "// refresh the cache first".to_string();
+        lines[11] = "let token = Token::new();".to_string();
+        lines[26] = "fn refresh_token(token: &Token) -> Token {".to_string();
+        let content = lines.join("\n");
+        let (_dir, index) = index_with(&[("auth.rs", content.as_str())])?;
+        let searcher = Searcher::new(SearchConfig::default(), index);
+
+        // 1-based line of the first line matching, as `grep -n -m1` reports it
+        let grep = |matches: &dyn Fn(&str) -> bool| {
+            content.lines().position(matches).map(|i| i as u64 + 1)
+        };
+
+        // Earlier lines holding only some of the words don't count
+        let hit = &searcher.search("REFRESH_token(token", None)?.hits[0];
+        let expected = grep(&|line| line.to_lowercase().contains("refresh_token(token"));
+        assert_eq!(hit.match_line, expected);
+        assert_eq!(hit.match_line, Some(27));
+        assert!((hit.line_start..=hit.line_end).contains(&27));
+
+        let hit = &searcher.search_regex(r"fn \w+_token\(", None)?.hits[0];
+        assert_eq!(
+            hit.match_line,
+            grep(&|line| line.starts_with("fn refresh_token("))
+        );
+
+        // The AI format reports the match line and shows it, not the context above
+        let output = searcher.search("token = Token", None)?.format_ai();
+        assert!(output.contains("auth.rs:12 ("));
+        assert!(output.contains("  let token = Token::new();\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan() -> Result<()> {
+        let (_dir, index) = index_with(&[("main.rs", "fn main() {}")])?;
+        let config = SearchConfig {
+            stopwords: vec!["the".to_string()],
+            expand_synonyms: true,
+            negation: true,
+            ..Default::default()
+        };
+        let searcher = Searcher::new(config, index);
+
+        let plan = searcher.plan(
+            r#"ext:rs the Config -legacy "open file"~3"#,
+            SearchFilters::default(),
+            false,
+            false,
+        )?;
+        assert_eq!(plan.mode, PlanMode::Literal);
+        assert_eq!(plan.text, "the Config");
+        assert_eq!(plan.index_terms, vec!["config"]);
+        assert_eq!(plan.stopwords, vec!["the"]);
+        assert_eq!(
+            plan.post_filter.as_deref(),
+            Some(r#"contains "the Config" (case-insensitive)"#)
+        );
+        assert!(plan.scan.is_none());
+        assert_eq!(plan.filters, vec!["ext: rs"]);
+        assert_eq!(plan.excluded, vec!["legacy"]);
+        assert_eq!(plan.proximity, vec![r#""open file"~3"#]);
+
+        // Punctuation may tokenize differently, so a miss falls back to a scan
+        let plan = searcher.plan("foo.bar(", SearchFilters::default(), false, false)?;
+        assert_eq!(plan.index_terms, vec!["foo", "bar"]);
+        assert!(plan.scan.is_some());
+
+        let plan = searcher.plan(r"fn\s+main", SearchFilters::default(), true, false)?;
+        assert_eq!(plan.mode, PlanMode::Regex);
+        assert_eq!(plan.index_terms, vec!["fn", "main"]);
+        assert!(searcher
+            .plan("(", SearchFilters::default(), true, false)
+            .is_err());
+        // Hybrid regex: the pattern's words are embedded
+        let plan = searcher.plan(r"fn\s+\w+_token\(", SearchFilters::default(), true, true)?;
+        assert_eq!(plan.mode, PlanMode::Regex);
+        assert_eq!(plan.semantic.as_deref(), Some("fn token"));
+        assert!(plan.format_text().contains("semantic:  fn token\n"));
+        assert_eq!(regex_semantic_text(r"\d+\.\d+"), r"\d+\.\d+");
+
+        let plan = searcher.plan("db config", SearchFilters::default(), false, true)?;
+        assert_eq!(plan.mode, PlanMode::Hybrid);
+        assert!(plan.synonyms.as_deref().unwrap().contains("database"));
+        assert!(plan.index_terms.contains(&"database".to_string()));
+        assert!(plan.post_filter.is_none());
+        assert!(plan.format_text().contains("mode:      hybrid\n"));
+        Ok(())
+    }
 }
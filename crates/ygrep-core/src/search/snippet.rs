@@ -0,0 +1,85 @@
+//! Snippet extraction around matching lines
+
+use crate::config::SearchConfig;
+
+/// How many lines a snippet shows around its first matching line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnippetWindow {
+    /// Maximum lines in a snippet
+    pub max_lines: usize,
+    /// Lines shown before the matching line
+    pub before: usize,
+    /// Lines shown after the matching line
+    pub after: usize,
+}
+
+impl Default for SnippetWindow {
+    fn default() -> Self {
+        Self::from_config(&SearchConfig::default())
+    }
+}
+
+impl SnippetWindow {
+    pub fn from_config(config: &SearchConfig) -> Self {
+        let max_lines = config.snippet_lines.max(1);
+        let before = config.context_before.min(max_lines - 1);
+        let after = config.context_after.unwrap_or(max_lines - before - 1);
+        Self {
+            max_lines,
+            before,
+            after,
+        }
+    }
+
+    /// Cut the snippet around the first matching line (0-based), or take the first
+    /// lines when nothing matched line by line.
+    /// Returns (snippet, line_offset_from_start, line_count)
+    pub fn extract(&self, content: &str, first_match: Option<usize>) -> (String, usize, usize) {
+        let lines: Vec<&str> = content.lines().collect();
+        let (start, end) = match first_match {
+            Some(line) => {
+                let start = line.saturating_sub(self.before).min(lines.len());
+                let end = (line + self.after + 1)
+                    .min(start + self.max_lines)
+                    .min(lines.len());
+                (start, end)
+            }
+            None => (0, self.max_lines.min(lines.len())),
+        };
+        (lines[start..end].join("\n"), start, end - start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window() {
+        let content = (1..=20)
+            .map(|i| format!("line {}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let default = SnippetWindow::default();
+        assert_eq!((default.before, default.after), (2, 7));
+        let (snippet, start, count) = default.extract(&content, Some(5));
+        assert_eq!((start, count), (3, 10));
+        assert!(snippet.starts_with("line 4\n"));
+
+        let config = SearchConfig {
+            snippet_lines: 3,
+            context_before: 0,
+            context_after: Some(5),
+            ..Default::default()
+        };
+        let (snippet, start, count) =
+            SnippetWindow::from_config(&config).extract(&content, Some(0));
+        assert_eq!(
+            (snippet.as_str(), start, count),
+            ("line 1\nline 2\nline 3", 0, 3)
+        );
+
+        assert_eq!(default.extract(&content, None).2, 10);
+    }
+}
@@ -0,0 +1,189 @@
+//! "Did you mean" suggestions for searches without hits: query words missing from the
+//! index are replaced by the closest indexed terms by edit distance
+
+use std::collections::HashMap;
+use tantivy::schema::Field;
+use tantivy::Term;
+
+use crate::error::Result;
+use crate::index::normalize::is_cjk;
+
+/// Alternative queries offered at most
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Words shorter than this aren't corrected (too many terms are one edit away)
+const MIN_WORD_LEN: usize = 3;
+
+/// Queries like `query` with its unknown words replaced by the closest indexed terms,
+/// best first; empty when every word is indexed or nothing is close
+pub(crate) fn suggest(
+    searcher: &tantivy::Searcher,
+    field: Field,
+    query: &str,
+) -> Result<Vec<String>> {
+    let mut unknown: Vec<String> = Vec::new();
+    for word in words(query) {
+        let word = word.to_lowercase();
+        if word.chars().count() < MIN_WORD_LEN
+            || word.chars().all(|c| c.is_ascii_digit())
+            // (CJK words are indexed as character pairs, not whole)
+            || word.chars().any(is_cjk)
+            || unknown.contains(&word)
+        {
+            continue;
+        }
+        if searcher.doc_freq(&Term::from_field_text(field, &word))? == 0 {
+            unknown.push(word);
+        }
+    }
+    if unknown.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Close terms per unknown word, with their distance and document frequency
+    let mut candidates: Vec<HashMap<String, (usize, u64)>> = vec![HashMap::new(); unknown.len()];
+    for segment in searcher.segment_readers() {
+        let inverted_index = segment.inverted_index(field)?;
+        let mut stream = inverted_index.terms().stream()?;
+        while stream.advance() {
+            let Ok(term) = std::str::from_utf8(stream.key()) else {
+                continue;
+            };
+            if !term.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                continue;
+            }
+            for (word, close) in unknown.iter().zip(candidates.iter_mut()) {
+                let max = max_distance(word);
+                if let Some(distance) = edit_distance(word, term, max) {
+                    let entry = close.entry(term.to_string()).or_insert((distance, 0));
+                    entry.1 += stream.value().doc_freq as u64;
+                }
+            }
+        }
+    }
+
+    let ranked: Vec<Vec<String>> = candidates
+        .into_iter()
+        .map(|close| {
+            let mut close: Vec<(String, (usize, u64))> = close.into_iter().collect();
+            close
+                .sort_by(|(a, (da, fa)), (b, (db, fb))| da.cmp(db).then(fb.cmp(fa)).then(a.cmp(b)));
+            close
+                .into_iter()
+                .take(MAX_SUGGESTIONS)
+                .map(|(term, _)| term)
+                .collect()
+        })
+        .collect();
+
+    let mut suggestions: Vec<String> = Vec::new();
+    for i in 0..MAX_SUGGESTIONS {
+        let replacements: HashMap<&str, &str> = unknown
+            .iter()
+            .zip(&ranked)
+            .filter_map(|(word, terms)| {
+                let term = terms.get(i).or(terms.last())?;
+                Some((word.as_str(), term.as_str()))
+            })
+            .collect();
+        if replacements.is_empty() {
+            break;
+        }
+        let suggestion = replace_words(query, &replacements);
+        if suggestion != query && !suggestions.contains(&suggestion) {
+            suggestions.push(suggestion);
+        }
+    }
+    Ok(suggestions)
+}
+
+/// Words of a query as the index splits them for lookup
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|s| !s.is_empty())
+}
+
+/// Edits allowed for a word: one for short words, two otherwise
+fn max_distance(word: &str) -> usize {
+    if word.chars().count() <= 4 {
+        1
+    } else {
+        2
+    }
+}
+
+/// Levenshtein distance between `a` and `b` if it is at most `max`
+fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max || a == b {
+        return None;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        let mut row_min = current[0];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            row_min = row_min.min(current[j + 1]);
+        }
+        // Every later row is at least this far apart
+        if row_min > max {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    let distance = previous[b.len()];
+    (distance <= max).then_some(distance)
+}
+
+/// Replace whole words (compared lowercased), keeping everything between them
+fn replace_words(text: &str, replacements: &HashMap<&str, &str>) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut word_start = None;
+    let flush =
+        |output: &mut String, word: &str| match replacements.get(word.to_lowercase().as_str()) {
+            Some(replacement) => output.push_str(replacement),
+            None => output.push_str(word),
+        };
+    for (i, c) in text.char_indices() {
+        if c.is_alphanumeric() || c == '_' {
+            word_start.get_or_insert(i);
+        } else {
+            if let Some(start) = word_start.take() {
+                flush(&mut output, &text[start..i]);
+            }
+            output.push(c);
+        }
+    }
+    if let Some(start) = word_start {
+        flush(&mut output, &text[start..]);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("embedings", "embeddings", 2), Some(1));
+        assert_eq!(edit_distance("conifg", "config", 2), Some(2));
+        assert_eq!(edit_distance("kitten", "sitting", 2), None);
+        assert_eq!(edit_distance("same", "same", 2), None);
+        assert_eq!(edit_distance("a", "abcd", 2), None);
+    }
+
+    #[test]
+    fn test_replace_words() {
+        let replacements = HashMap::from([("embedings", "embeddings")]);
+        assert_eq!(
+            replace_words("load(Embedings) path:src", &replacements),
+            "load(embeddings) path:src"
+        );
+    }
+}
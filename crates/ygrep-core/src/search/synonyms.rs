@@ -0,0 +1,149 @@
+//! Code synonyms and abbreviations for query expansion (BM25 side of hybrid search)
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::config::SearchConfig;
+
+/// Built-in synonym groups; every term in a group expands to the others
+const BUILTIN_GROUPS: &[&[&str]] = &[
+    &["cfg", "conf", "config", "configuration"],
+    &["auth", "authentication", "authenticate"],
+    &["authz", "authorization", "authorize"],
+    &["db", "database"],
+    &["repo", "repository"],
+    &["msg", "message"],
+    &["err", "error"],
+    &["req", "request"],
+    &["res", "resp", "response"],
+    &["ctx", "context"],
+    &["env", "environment"],
+    &["fn", "func", "function"],
+    &["init", "initialize", "initialise"],
+    &["impl", "implementation"],
+    &["param", "parameter"],
+    &["arg", "argument"],
+    &["attr", "attribute"],
+    &["str", "string"],
+    &["num", "number"],
+    &["idx", "index"],
+    &["len", "length"],
+    &["tmp", "temp", "temporary"],
+    &["dir", "directory"],
+    &["btn", "button"],
+    &["img", "image"],
+    &["util", "utils", "utility"],
+    &["async", "asynchronous"],
+    &["sync", "synchronize"],
+];
+
+/// Lookup table from a term to its synonyms
+#[derive(Debug, Clone, Default)]
+pub struct Synonyms {
+    map: HashMap<String, Vec<String>>,
+}
+
+impl Synonyms {
+    /// Only the built-in groups
+    pub fn builtin() -> Self {
+        let mut synonyms = Self::default();
+        for group in BUILTIN_GROUPS {
+            synonyms.add_group(group.iter().map(|t| t.to_string()).collect());
+        }
+        synonyms
+    }
+
+    /// Built-in groups plus the user's synonyms file (if present)
+    pub fn load(config: &SearchConfig) -> Self {
+        let mut synonyms = Self::builtin();
+        let path = config.synonyms_file.clone().or_else(default_synonyms_file);
+        if let Some(path) = path {
+            match std::fs::read_to_string(&path) {
+                Ok(content) => synonyms.parse_into(&content),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!("Failed to read synonyms file {}: {}", path.display(), e),
+            }
+        }
+        synonyms
+    }
+
+    /// Add groups from a synonyms file: one comma-separated group per line, `#` comments
+    pub fn parse_into(&mut self, content: &str) {
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let group: Vec<String> = line
+                .split(',')
+                .map(|t| t.trim().to_lowercase())
+                .filter(|t| !t.is_empty() && t.chars().all(|c| c.is_alphanumeric() || c == '_'))
+                .collect();
+            if group.len() > 1 {
+                self.add_group(group);
+            }
+        }
+    }
+
+    fn add_group(&mut self, group: Vec<String>) {
+        for term in &group {
+            let entry = self.map.entry(term.clone()).or_default();
+            for other in &group {
+                if other != term && !entry.contains(other) {
+                    entry.push(other.clone());
+                }
+            }
+        }
+    }
+
+    /// Synonyms of a term (case-insensitive)
+    pub fn get(&self, term: &str) -> Option<&[String]> {
+        self.map.get(&term.to_lowercase()).map(|v| v.as_slice())
+    }
+
+    /// Build a Tantivy query string that ORs each query term with its synonyms.
+    ///
+    /// Returns `None` when no term has synonyms, so callers can keep their original query.
+    pub fn expand_query(&self, query: &str) -> Option<String> {
+        let terms: Vec<&str> = query
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let mut expanded_any = false;
+        let clauses: Vec<String> = terms
+            .iter()
+            .map(|term| match self.get(term) {
+                Some(alternatives) => {
+                    expanded_any = true;
+                    let mut options = vec![term.to_lowercase()];
+                    options.extend(alternatives.iter().cloned());
+                    format!("({})", options.join(" OR "))
+                }
+                None => term.to_string(),
+            })
+            .collect();
+
+        expanded_any.then(|| clauses.join(" AND "))
+    }
+}
+
+/// `~/.config/ygrep/synonyms.txt`
+fn default_synonyms_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("ygrep").join("synonyms.txt"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_query() {
+        let mut synonyms = Synonyms::builtin();
+        synonyms.parse_into("# team jargon\nsvc, service\nbad-term, x\n");
+
+        assert_eq!(
+            synonyms.expand_query("db svc"),
+            Some("(db OR database) AND (svc OR service)".to_string())
+        );
+        assert_eq!(synonyms.expand_query("pool"), None);
+        assert!(synonyms.get("bad-term").is_none());
+    }
+}
@@ -0,0 +1,342 @@
+//! TODO/FIXME audit (`ygrep todos`): the lines of indexed files carrying a marker
+//! (`search.todo_markers`), grouped by directory or by who last changed them (git blame)
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use crate::error::{Result, YgrepError};
+use crate::index::regions::{RegionKind, Regions};
+
+/// Group name of items git blame knows nothing of (outside a git checkout, or files
+/// changed since they were indexed)
+pub const UNKNOWN_AUTHOR: &str = "(unknown)";
+
+/// How the items of a report are grouped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TodoGrouping {
+    /// By the first N directories of their path (files at the root under `.`)
+    Directory(usize),
+    /// By the author of the line, from git blame
+    Author,
+}
+
+/// A line carrying a marker
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TodoItem {
+    /// File path relative to the workspace
+    pub path: String,
+    /// Line number (1-based)
+    pub line: u64,
+    /// The first marker on the line
+    pub marker: String,
+    /// The line, trimmed
+    pub text: String,
+    /// Who last changed the line (only looked up when grouping by author)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+}
+
+/// The items of one directory or author
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TodoGroup {
+    pub name: String,
+    /// Items in the group (`items` may hold fewer if limited)
+    pub total: usize,
+    /// Items by marker
+    pub markers: BTreeMap<String, usize>,
+    pub items: Vec<TodoItem>,
+}
+
+/// The markers of a workspace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TodoReport {
+    /// Items by marker, over all groups
+    pub markers: BTreeMap<String, usize>,
+    pub total: usize,
+    /// Files with at least one item
+    pub total_files: usize,
+    /// Largest groups first
+    pub groups: Vec<TodoGroup>,
+    /// Search time in milliseconds
+    pub query_time_ms: u64,
+}
+
+/// Check that every marker is a word that can be looked up
+pub fn validate_markers(markers: &[String]) -> Result<()> {
+    if markers.is_empty() {
+        return Err(YgrepError::Search(
+            "No TODO markers to look for".to_string(),
+        ));
+    }
+    for marker in markers {
+        if marker.is_empty() || !marker.chars().all(is_word_char) {
+            return Err(YgrepError::Search(format!(
+                "'{}' is not a marker (letters, digits and `_` only)",
+                marker
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The lines of a file with one of `markers` as a whole word (case-sensitive). With the
+/// file's comment and string regions (as stored in the index), only markers in comments
+/// count, so `"TODO"` in a string literal isn't one.
+pub fn find_markers(
+    markers: &[String],
+    path: &str,
+    content: &str,
+    regions: Option<&str>,
+) -> Vec<TodoItem> {
+    let comments = regions.map(|regions| {
+        Regions::decode(regions).mask(content, |kind| kind == Some(RegionKind::Comment))
+    });
+    let scanned = comments.as_deref().unwrap_or(content);
+    scanned
+        .lines()
+        .zip(content.lines())
+        .enumerate()
+        .filter_map(|(i, (scanned, line))| {
+            let (_, marker) = markers
+                .iter()
+                .filter_map(|marker| Some((first_word(scanned, marker)?, marker)))
+                .min()?;
+            Some(TodoItem {
+                path: path.to_string(),
+                line: i as u64 + 1,
+                marker: marker.clone(),
+                text: line.trim().to_string(),
+                author: None,
+            })
+        })
+        .collect()
+}
+
+impl TodoReport {
+    /// Group `items`, keeping at most `limit` per group
+    pub fn group(
+        mut items: Vec<TodoItem>,
+        grouping: TodoGrouping,
+        limit: usize,
+        start: Instant,
+    ) -> Self {
+        items.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+        let mut markers = BTreeMap::new();
+        let mut files = 0;
+        let mut groups: BTreeMap<String, TodoGroup> = BTreeMap::new();
+        for (i, item) in items.iter().enumerate() {
+            if i == 0 || items[i - 1].path != item.path {
+                files += 1;
+            }
+            *markers.entry(item.marker.clone()).or_insert(0) += 1;
+            let name = match grouping {
+                TodoGrouping::Directory(depth) => directory_of(&item.path, depth),
+                TodoGrouping::Author => item
+                    .author
+                    .clone()
+                    .unwrap_or_else(|| UNKNOWN_AUTHOR.to_string()),
+            };
+            let group = groups.entry(name.clone()).or_insert_with(|| TodoGroup {
+                name,
+                total: 0,
+                markers: BTreeMap::new(),
+                items: Vec::new(),
+            });
+            group.total += 1;
+            *group.markers.entry(item.marker.clone()).or_insert(0) += 1;
+            if group.items.len() < limit {
+                group.items.push(item.clone());
+            }
+        }
+        let mut groups: Vec<TodoGroup> = groups.into_values().collect();
+        groups.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.name.cmp(&b.name)));
+
+        Self {
+            markers,
+            total: items.len(),
+            total_files: files,
+            groups,
+            query_time_ms: start.elapsed().as_millis() as u64,
+        }
+    }
+
+    fn header(&self) -> String {
+        let markers: Vec<String> = self
+            .markers
+            .iter()
+            .map(|(marker, count)| format!("{} {}", marker, count))
+            .collect();
+        let mut header = format!(
+            "# {} marker{} in {} file{}",
+            self.total,
+            plural(self.total),
+            self.total_files,
+            plural(self.total_files)
+        );
+        if !markers.is_empty() {
+            header.push_str(&format!(" ({})", markers.join(", ")));
+        }
+        header.push('\n');
+        header
+    }
+
+    /// One `path:line` per item under each group
+    pub fn format_ai(&self) -> String {
+        let mut output = self.header();
+        for group in &self.groups {
+            output.push_str(&format!("\n## {} ({})\n", group.name, group.total));
+            for item in &group.items {
+                output.push_str(&format!("{}:{}  {}\n", item.path, item.line, item.text));
+            }
+            if group.items.len() < group.total {
+                output.push_str(&format!("... {} more\n", group.total - group.items.len()));
+            }
+        }
+        output
+    }
+
+    /// Format as JSON
+    pub fn format_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Groups with their counts by marker, items indented under them
+    pub fn format_pretty(&self) -> String {
+        let mut output = self.header();
+        for group in &self.groups {
+            let markers: Vec<String> = group
+                .markers
+                .iter()
+                .map(|(marker, count)| format!("{} {}", marker, count))
+                .collect();
+            output.push_str(&format!(
+                "\n{}  {} ({})\n",
+                group.name,
+                group.total,
+                markers.join(", ")
+            ));
+            for item in &group.items {
+                let location = format!("{}:{}", item.path, item.line);
+                output.push_str(&format!("  {:<40} {}\n", location, item.text));
+            }
+            if group.items.len() < group.total {
+                output.push_str(&format!("  ... {} more\n", group.total - group.items.len()));
+            }
+        }
+        output
+    }
+}
+
+/// The first `depth` directories of `path`, `.` for a file at the root
+fn directory_of(path: &str, depth: usize) -> String {
+    let dirs: Vec<&str> = path.split('/').collect();
+    let dirs = &dirs[..dirs.len() - 1];
+    if dirs.is_empty() || depth == 0 {
+        return ".".to_string();
+    }
+    dirs[..depth.min(dirs.len())].join("/")
+}
+
+/// Offset of the first occurrence of `word` in `line` as a whole word
+pub(super) fn first_word(line: &str, word: &str) -> Option<usize> {
+    line.match_indices(word)
+        .map(|(start, _)| start)
+        .find(|&start| {
+            let before = line[..start].chars().next_back();
+            let after = line[start + word.len()..].chars().next();
+            !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+        })
+}
+
+pub(super) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markers() -> Vec<String> {
+        ["TODO", "FIXME", "HACK", "XXX"].map(String::from).to_vec()
+    }
+
+    #[test]
+    fn test_find_markers() {
+        let content =
+            "// FIXME: leaks, TODO: close\nlet s = \"TODO\";\nlet todo_list = 1; // TODOS\n# XXX\n";
+        let items = find_markers(&markers(), "src/a.rs", content, None);
+        let found: Vec<(u64, &str)> = items
+            .iter()
+            .map(|item| (item.line, item.marker.as_str()))
+            .collect();
+        // The first marker of a line, as a whole word
+        assert_eq!(found, vec![(1, "FIXME"), (2, "TODO"), (4, "XXX")]);
+        assert_eq!(items[0].text, "// FIXME: leaks, TODO: close");
+
+        // With the file's regions only comments count
+        let regions = Regions::lex("rs", content).unwrap().encode();
+        let items = find_markers(&markers(), "src/a.rs", content, Some(&regions));
+        let lines: Vec<u64> = items.iter().map(|item| item.line).collect();
+        assert_eq!(lines, vec![1]);
+
+        assert!(validate_markers(&["TODO".to_string()]).is_ok());
+        assert!(validate_markers(&["TO DO".to_string()]).is_err());
+        assert!(validate_markers(&[]).is_err());
+    }
+
+    #[test]
+    fn test_group_todos() {
+        let item = |path: &str, line, marker: &str, author: Option<&str>| TodoItem {
+            path: path.to_string(),
+            line,
+            marker: marker.to_string(),
+            text: format!("// {}", marker),
+            author: author.map(String::from),
+        };
+        let items = vec![
+            item("src/index/writer.rs", 3, "TODO", Some("ana")),
+            item("src/lib.rs", 9, "FIXME", Some("ana")),
+            item("src/lib.rs", 2, "TODO", None),
+            item("build.rs", 1, "HACK", Some("bo")),
+        ];
+
+        let report =
+            TodoReport::group(items.clone(), TodoGrouping::Directory(1), 1, Instant::now());
+        assert_eq!((report.total, report.total_files), (4, 3));
+        assert_eq!(report.markers["TODO"], 2);
+        let groups: Vec<(&str, usize)> = report
+            .groups
+            .iter()
+            .map(|group| (group.name.as_str(), group.total))
+            .collect();
+        assert_eq!(groups, vec![("src", 3), (".", 1)]);
+        // Limited items, in path and line order
+        assert_eq!(report.groups[0].items.len(), 1);
+        assert_eq!(report.groups[0].items[0].path, "src/index/writer.rs");
+        assert!(report
+            .format_ai()
+            .contains("## src (3)\nsrc/index/writer.rs:3  // TODO\n... 2 more\n"));
+
+        let report = TodoReport::group(
+            items.clone(),
+            TodoGrouping::Directory(2),
+            10,
+            Instant::now(),
+        );
+        assert_eq!(report.groups[0].name, "src");
+        assert!(report.groups.iter().any(|group| group.name == "src/index"));
+
+        let report = TodoReport::group(items, TodoGrouping::Author, 10, Instant::now());
+        let groups: Vec<&str> = report.groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(groups, vec!["ana", UNKNOWN_AUTHOR, "bo"]);
+    }
+}
@@ -0,0 +1,139 @@
+//! Index bundles (`ygrep indexes push`/`pull`): a whole index packed into one `.tar.gz`
+//! and stored by repository and commit, so teammates fetch an index someone else built
+//! instead of embedding the workspace themselves
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::path::{Path, PathBuf};
+
+use super::local_files;
+use crate::error::{Result, YgrepError};
+
+/// Commits before HEAD searched for a bundle when there is none for HEAD itself
+pub const BUNDLE_SEARCH_DEPTH: usize = 100;
+
+/// Name of the bundle of an index built at `commit`, within its repository's storage
+pub fn bundle_name(commit: &str) -> String {
+    format!("{}.tar.gz", commit)
+}
+
+/// Storage URL of a repository's bundles (`<url>/<repository key>`)
+pub fn repository_url(url: &str, repo: &str) -> String {
+    format!("{}/{}", url.trim_end_matches('/'), repo)
+}
+
+/// A bundle that was pushed or pulled
+#[derive(Debug, Clone)]
+pub struct BundleInfo {
+    /// Repository key (see `fs::repository_key`)
+    pub repo: String,
+    /// Commit the index was built at
+    pub commit: String,
+    /// Size of the bundle (bytes)
+    pub size: u64,
+}
+
+/// What `Workspace::pull_bundle` fetched
+#[derive(Debug, Clone)]
+pub struct PulledBundle {
+    pub bundle: BundleInfo,
+    /// Commits between the bundle's commit and HEAD (0 when it was built at HEAD)
+    pub behind: usize,
+    /// Files indexed again because they changed since the bundle's commit
+    pub refreshed: usize,
+}
+
+/// A temporary file next to the system's other temporary files, removed when dropped
+pub(crate) struct TempFile(pub PathBuf);
+
+impl TempFile {
+    pub fn new(name: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("ygrep-{}-{}", std::process::id(), name)))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Pack an index directory (without its locks, checkpoint, feedback and saved result
+/// sets) into a bundle at `dest`
+pub fn export_bundle(index_path: &Path, dest: &Path) -> Result<()> {
+    if !index_path.join("workspace.json").exists() {
+        return Err(YgrepError::WorkspaceNotIndexed(index_path.to_path_buf()));
+    }
+    let file = std::fs::File::create(dest)?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    for (path, _) in local_files(index_path)? {
+        archive.append_path_with_name(index_path.join(&path), &path)?;
+    }
+    archive.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Unpack a bundle into `index_path`, replacing the index there only once the bundle
+/// was unpacked completely
+pub fn import_bundle(bundle: &Path, index_path: &Path) -> Result<()> {
+    let name = index_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let unpacked = index_path.with_file_name(format!("{}.pull-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&unpacked);
+
+    let file = std::fs::File::open(bundle)?;
+    let result = tar::Archive::new(GzDecoder::new(file)).unpack(&unpacked);
+    if let Err(e) = result.map_err(YgrepError::from).and_then(|()| {
+        if unpacked.join("workspace.json").exists() {
+            Ok(())
+        } else {
+            Err(YgrepError::Storage("Bundle has no workspace.json".into()))
+        }
+    }) {
+        let _ = std::fs::remove_dir_all(&unpacked);
+        return Err(e);
+    }
+
+    if index_path.exists() {
+        std::fs::remove_dir_all(index_path)?;
+    }
+    std::fs::rename(&unpacked, index_path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let index = tempfile::tempdir().unwrap();
+        std::fs::write(index.path().join("workspace.json"), "{}").unwrap();
+        std::fs::write(index.path().join(".tantivy-writer.lock"), "").unwrap();
+        std::fs::create_dir(index.path().join("vectors")).unwrap();
+        std::fs::write(index.path().join("vectors/shards.json"), "[]").unwrap();
+
+        let temp = tempfile::tempdir().unwrap();
+        let bundle = temp.path().join(bundle_name("abc123"));
+        export_bundle(index.path(), &bundle).unwrap();
+
+        // An existing index is replaced
+        let target = temp.path().join("index");
+        std::fs::create_dir(&target).unwrap();
+        std::fs::write(target.join("stale.json"), "{}").unwrap();
+        import_bundle(&bundle, &target).unwrap();
+        assert!(target.join("workspace.json").exists());
+        assert!(target.join("vectors/shards.json").exists());
+        assert!(!target.join(".tantivy-writer.lock").exists());
+        assert!(!target.join("stale.json").exists());
+
+        // A broken bundle leaves it alone
+        std::fs::write(&bundle, "not a bundle").unwrap();
+        assert!(import_bundle(&bundle, &target).is_err());
+        assert!(target.join("workspace.json").exists());
+    }
+}
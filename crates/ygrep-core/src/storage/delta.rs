@@ -0,0 +1,292 @@
+//! Index deltas (`ygrep indexes diff`/`apply-delta`): the documents and vectors of the
+//! files that changed between two snapshots of an index, so a shared index is kept up
+//! to date by shipping what changed instead of the whole index again
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+use tantivy::schema::{Field, NamedFieldDocument, OwnedValue};
+use tantivy::{Document, Index, TantivyDocument};
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::error::{Result, YgrepError};
+use crate::index::SchemaFields;
+
+/// Format of delta files, bumped when it changes incompatibly
+const DELTA_VERSION: u32 = 1;
+
+/// The changes between two snapshots of an index
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexDelta {
+    version: u32,
+    /// Digest of the snapshot the delta applies to (see `index_digest`)
+    pub base: String,
+    /// Digest of the index once the delta is applied
+    pub target: String,
+    /// Commits the two snapshots were built at (`None` outside git)
+    pub base_commit: Option<String>,
+    pub target_commit: Option<String>,
+    /// Files no longer in the index
+    pub removed: Vec<String>,
+    /// Files added or changed
+    pub changed: Vec<String>,
+    /// Every document (file, chunks and lines) of the changed files; prose fields aren't
+    /// stored, so they are stemmed again when the delta is applied
+    documents: Vec<NamedFieldDocument>,
+    /// Embeddings of those documents by doc_id
+    vectors: Vec<(String, Vec<f32>)>,
+    /// workspace.json of the new snapshot
+    pub metadata: serde_json::Value,
+}
+
+/// Size of a delta
+#[derive(Debug, Clone, Default)]
+pub struct DeltaStats {
+    /// Files added or changed
+    pub changed: usize,
+    /// Files removed
+    pub removed: usize,
+    /// Documents (files, chunks and lines) of the changed files
+    pub documents: usize,
+    pub vectors: usize,
+}
+
+impl IndexDelta {
+    /// The changes from the index in directory `old` to the one in `new`
+    pub fn between(old: &Path, new: &Path) -> Result<Self> {
+        let (old_index, old_metadata) = open_snapshot(old)?;
+        let (new_index, metadata) = open_snapshot(new)?;
+        #[cfg(not(feature = "embeddings"))]
+        if metadata.get("semantic").and_then(|s| s.as_bool()) == Some(true) {
+            return Err(YgrepError::Storage(format!(
+                "{} has a semantic index; diffing it needs the embeddings feature",
+                new.display()
+            )));
+        }
+
+        let old_files = file_signatures(&old_index)?;
+        let new_files = file_signatures(&new_index)?;
+        let removed = old_files
+            .keys()
+            .filter(|path| !new_files.contains_key(*path))
+            .cloned()
+            .collect();
+        let changed: BTreeSet<String> = new_files
+            .iter()
+            .filter(|(path, signature)| old_files.get(*path) != Some(signature))
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let schema = new_index.schema();
+        let fields = SchemaFields::new(&schema);
+        let mut documents = Vec::new();
+        for_each_document(&new_index, |doc| {
+            if text(&doc, fields.path).is_some_and(|path| changed.contains(path)) {
+                documents.push(doc.to_named_doc(&schema));
+            }
+        })?;
+
+        #[cfg(feature = "embeddings")]
+        let vectors = {
+            let vector_path = new.join("vectors");
+            if crate::index::VectorIndex::exists(&vector_path) {
+                let doc_ids: std::collections::HashSet<&str> = documents
+                    .iter()
+                    .filter_map(
+                        |doc| match doc.0.get(crate::index::fields::DOC_ID)?.first()? {
+                            OwnedValue::Str(doc_id) => Some(doc_id.as_str()),
+                            _ => None,
+                        },
+                    )
+                    .collect();
+                crate::index::VectorIndex::load(vector_path)?
+                    .vectors(|doc_id| doc_ids.contains(doc_id))?
+            } else {
+                Vec::new()
+            }
+        };
+        #[cfg(not(feature = "embeddings"))]
+        let vectors = Vec::new();
+
+        Ok(Self {
+            version: DELTA_VERSION,
+            base: digest(&old_files),
+            target: digest(&new_files),
+            base_commit: commit(&old_metadata),
+            target_commit: commit(&metadata),
+            removed,
+            changed: changed.into_iter().collect(),
+            documents,
+            vectors,
+            metadata,
+        })
+    }
+
+    /// Read a delta file written by `write`
+    pub fn read(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        let delta: Self = serde_json::from_reader(std::io::BufReader::new(GzDecoder::new(file)))
+            .map_err(|e| {
+                YgrepError::Storage(format!("{} is not an index delta: {}", path.display(), e))
+            })?;
+        if delta.version != DELTA_VERSION {
+            return Err(YgrepError::Storage(format!(
+                "{} was made by a different version of ygrep",
+                path.display()
+            )));
+        }
+        Ok(delta)
+    }
+
+    /// Write the delta (gzipped JSON) to `dest`, returning its size
+    pub fn write(&self, dest: &Path) -> Result<u64> {
+        let file = std::io::BufWriter::new(std::fs::File::create(dest)?);
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        serde_json::to_writer(&mut encoder, self)
+            .map_err(|e| YgrepError::Storage(format!("Failed to write index delta: {}", e)))?;
+        encoder.finish()?.flush()?;
+        Ok(std::fs::metadata(dest)?.len())
+    }
+
+    pub fn stats(&self) -> DeltaStats {
+        DeltaStats {
+            changed: self.changed.len(),
+            removed: self.removed.len(),
+            documents: self.documents.len(),
+            vectors: self.vectors.len(),
+        }
+    }
+
+    /// Embeddings of the changed files' documents, by doc_id
+    pub fn vectors(&self) -> &[(String, Vec<f32>)] {
+        &self.vectors
+    }
+
+    /// Replace the documents of the removed and changed files in `index` with the
+    /// delta's, stemming the prose files `stemming` applies to
+    #[cfg(feature = "index-write")]
+    pub fn apply_documents(
+        &self,
+        index: &Index,
+        heap: usize,
+        stemming: Option<&crate::index::Stemming>,
+    ) -> Result<()> {
+        let schema = index.schema();
+        let fields = SchemaFields::new(&schema);
+        let mut writer = index.writer::<TantivyDocument>(heap)?;
+        for path in self.removed.iter().chain(&self.changed) {
+            writer.delete_term(tantivy::Term::from_field_text(fields.path, path));
+        }
+        for named in &self.documents {
+            let mut doc = TantivyDocument::new();
+            for (name, values) in &named.0 {
+                let field = schema.get_field(name)?;
+                for value in values {
+                    doc.add_field_value(field, value.clone());
+                }
+            }
+            let prose = stemming
+                .is_some_and(|stemming| {
+                    stemming.applies_to(text(&doc, fields.extension).unwrap_or_default())
+                })
+                .then(|| text(&doc, fields.content).map(str::to_string))
+                .flatten();
+            if let Some(content) = prose {
+                doc.add_text(fields.prose, content);
+            }
+            writer.add_document(doc)?;
+        }
+        writer.commit()?;
+        writer.wait_merging_threads()?;
+        Ok(())
+    }
+}
+
+/// Identifies the files in an index and how they were indexed: the same for two indexes
+/// of the same files, wherever they were built
+pub fn index_digest(index: &Index) -> Result<String> {
+    Ok(digest(&file_signatures(index)?))
+}
+
+fn digest(files: &BTreeMap<String, u64>) -> String {
+    let mut bytes = Vec::new();
+    for (path, signature) in files {
+        bytes.extend_from_slice(path.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(&signature.to_le_bytes());
+    }
+    format!("{:016x}", xxh3_64(&bytes))
+}
+
+/// A hash of each file's documents (content hashes and positions), by path
+fn file_signatures(index: &Index) -> Result<BTreeMap<String, u64>> {
+    let fields = SchemaFields::new(&index.schema());
+    let mut documents: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for_each_document(index, |doc| {
+        let Some(path) = text(&doc, fields.path) else {
+            return;
+        };
+        let line_start = match doc.get_first(fields.line_start) {
+            Some(OwnedValue::U64(line)) => *line,
+            _ => 0,
+        };
+        let key = format!(
+            "{}\0{}\0{}",
+            text(&doc, fields.doc_id).unwrap_or_default(),
+            text(&doc, fields.unit).unwrap_or_default(),
+            line_start
+        );
+        documents.entry(path.to_string()).or_default().push(key);
+    })?;
+
+    Ok(documents
+        .into_iter()
+        .map(|(path, mut keys)| {
+            keys.sort();
+            (path, xxh3_64(keys.join("\n").as_bytes()))
+        })
+        .collect())
+}
+
+/// Call `f` with every live document of the index
+fn for_each_document(index: &Index, mut f: impl FnMut(TantivyDocument)) -> Result<()> {
+    let reader = index.reader()?;
+    for segment in reader.searcher().segment_readers() {
+        let store = segment.get_store_reader(1)?;
+        for doc in store.iter::<TantivyDocument>(segment.alive_bitset()) {
+            f(doc?);
+        }
+    }
+    Ok(())
+}
+
+fn text(doc: &TantivyDocument, field: Field) -> Option<&str> {
+    match doc.get_first(field) {
+        Some(OwnedValue::Str(s)) => Some(s.as_str()),
+        _ => None,
+    }
+}
+
+/// The text index and workspace.json in an index directory
+fn open_snapshot(dir: &Path) -> Result<(Index, serde_json::Value)> {
+    let metadata = std::fs::read_to_string(dir.join("workspace.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .ok_or_else(|| YgrepError::WorkspaceNotIndexed(dir.to_path_buf()))?;
+    Ok((Index::open_in_dir(dir)?, metadata))
+}
+
+/// Commit an index was built at, from its workspace.json
+fn commit(metadata: &serde_json::Value) -> Option<String> {
+    Some(
+        metadata
+            .get("fingerprint")?
+            .get("head")?
+            .as_str()?
+            .to_string(),
+    )
+}
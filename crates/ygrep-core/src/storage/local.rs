@@ -0,0 +1,75 @@
+//! Indexes published to a directory (a network share, or a CI cache restored on disk)
+
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use super::{local_files, IndexStorage, StoredFile};
+use crate::error::{Result, YgrepError};
+
+/// A published index in a local directory
+pub struct LocalStorage {
+    root: PathBuf,
+    url: String,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        let url = root.to_string_lossy().into_owned();
+        Self { root, url }
+    }
+
+    fn file(&self, path: &str) -> PathBuf {
+        path.split('/')
+            .fold(self.root.clone(), |dir, part| dir.join(part))
+    }
+}
+
+impl IndexStorage for LocalStorage {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn list(&self) -> Result<Vec<StoredFile>> {
+        if !self.root.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut files = Vec::new();
+        for (path, size) in local_files(&self.root)? {
+            let modified = std::fs::metadata(self.file(&path))?
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| YgrepError::Storage(e.to_string()))?;
+            files.push(StoredFile {
+                version: format!("{}-{}", modified.as_nanos(), size),
+                path,
+                size,
+            });
+        }
+        Ok(files)
+    }
+
+    fn download(&self, path: &str, dest: &Path) -> Result<()> {
+        std::fs::copy(self.file(path), dest)?;
+        Ok(())
+    }
+
+    fn upload(&self, src: &Path, path: &str) -> Result<()> {
+        let dest = self.file(path);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Consumers syncing meanwhile never see a partly written file
+        let tmp = PathBuf::from(format!("{}.{}.tmp", dest.display(), std::process::id()));
+        std::fs::copy(src, &tmp)?;
+        std::fs::rename(&tmp, &dest)?;
+        Ok(())
+    }
+
+    fn delete(&self, path: &str) -> Result<()> {
+        match std::fs::remove_file(self.file(path)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,409 @@
+//! Indexes published to shared storage: CI builds an index once and publishes it
+//! (`ygrep indexes publish`), and read-only consumers sync it into their local index
+//! directory before searching instead of indexing themselves (`indexer.remote_index`)
+
+pub mod bundle;
+pub mod delta;
+mod local;
+#[cfg(feature = "object-storage")]
+mod object;
+pub mod snapshot;
+
+pub use bundle::{BundleInfo, PulledBundle};
+pub use delta::{DeltaStats, IndexDelta};
+pub use local::LocalStorage;
+#[cfg(feature = "object-storage")]
+pub use object::ObjectStorage;
+pub use snapshot::Snapshot;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::{Result, YgrepError};
+
+/// What was synced from storage, kept in the index directory; indexes without it were
+/// built locally
+const SYNC_FILE: &str = ".remote.json";
+
+/// Written last when publishing and syncing, so a half-copied index is never opened
+const COMMIT_FILES: [&str; 2] = ["meta.json", "workspace.json"];
+
+/// A file of a published index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredFile {
+    /// Path relative to the index directory, `/`-separated
+    pub path: String,
+    pub size: u64,
+    /// Changes whenever the file is written again (ETag or modification time)
+    pub version: String,
+}
+
+/// Where an index is published: an object store bucket or a shared directory
+pub trait IndexStorage {
+    /// Location shown to the user
+    fn url(&self) -> &str;
+
+    /// Every file of the published index
+    fn list(&self) -> Result<Vec<StoredFile>>;
+
+    /// Copy a published file to `dest`
+    fn download(&self, path: &str, dest: &Path) -> Result<()>;
+
+    /// Publish the file at `src` as `path`
+    fn upload(&self, src: &Path, path: &str) -> Result<()>;
+
+    fn delete(&self, path: &str) -> Result<()>;
+}
+
+/// Open the storage at `url`: `s3://bucket/prefix` or `gs://bucket/prefix` (with the
+/// `object-storage` feature), `file:///dir` or a directory path
+pub fn open_storage(url: &str) -> Result<Box<dyn IndexStorage>> {
+    if let Some(dir) = url.strip_prefix("file://") {
+        return Ok(Box::new(LocalStorage::new(dir)));
+    }
+    if !url.contains("://") {
+        return Ok(Box::new(LocalStorage::new(url)));
+    }
+    open_object_storage(url)
+}
+
+#[cfg(feature = "object-storage")]
+fn open_object_storage(url: &str) -> Result<Box<dyn IndexStorage>> {
+    Ok(Box::new(ObjectStorage::open(url)?))
+}
+
+#[cfg(not(feature = "object-storage"))]
+fn open_object_storage(url: &str) -> Result<Box<dyn IndexStorage>> {
+    Err(YgrepError::Storage(format!(
+        "{}: object storage needs ygrep built with the `object-storage` feature",
+        url
+    )))
+}
+
+/// Files copied by `publish` or `sync`
+#[derive(Debug, Clone, Default)]
+pub struct TransferStats {
+    /// Files uploaded or downloaded
+    pub copied: usize,
+    /// Bytes uploaded or downloaded
+    pub bytes: u64,
+    /// Files already up to date
+    pub unchanged: usize,
+    /// Files removed because the source no longer has them
+    pub removed: usize,
+}
+
+/// Files that only make sense next to the index that wrote them: locks, an unfinished
+/// index's checkpoint, search feedback and saved result sets
+fn is_local_only(path: &str) -> bool {
+    matches!(
+        path,
+        ".tantivy-meta.lock" | ".tantivy-writer.lock" | "checkpoint.json" | "feedback.json"
+    ) || path == SYNC_FILE
+        || path.starts_with("results/")
+        || path.ends_with(".tmp")
+}
+
+/// Tantivy segment files (`<segment uuid>.<component>`) are never written twice, so one
+/// of the same size is the same file
+fn is_immutable(path: &str) -> bool {
+    !path.contains('/')
+        && path
+            .split('.')
+            .next()
+            .is_some_and(|stem| stem.len() == 32 && stem.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// Commit files after the rest, in `COMMIT_FILES` order
+fn commit_order(path: &str) -> usize {
+    COMMIT_FILES
+        .iter()
+        .position(|file| *file == path)
+        .map_or(0, |i| i + 1)
+}
+
+/// Files of a local index directory, as `(relative path, size)`
+fn local_files(index_path: &Path) -> Result<Vec<(String, u64)>> {
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(index_path).min_depth(1) {
+        let entry = entry.map_err(|e| YgrepError::Storage(e.to_string()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(index_path) else {
+            continue;
+        };
+        let path = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if !is_local_only(&path) {
+            files.push((path, entry.metadata().map(|m| m.len()).unwrap_or(0)));
+        }
+    }
+    Ok(files)
+}
+
+/// Publish a local index: upload its files (segments already published are skipped,
+/// meta.json and workspace.json go last) and delete published files it no longer has
+pub fn publish(index_path: &Path, storage: &dyn IndexStorage) -> Result<TransferStats> {
+    if !index_path.join("workspace.json").exists() {
+        return Err(YgrepError::WorkspaceNotIndexed(index_path.to_path_buf()));
+    }
+    let published: BTreeMap<String, StoredFile> = storage
+        .list()?
+        .into_iter()
+        .map(|file| (file.path.clone(), file))
+        .collect();
+
+    let mut files = local_files(index_path)?;
+    files.sort_by_key(|(path, _)| commit_order(path));
+
+    let mut stats = TransferStats::default();
+    for (path, size) in &files {
+        let unchanged = is_immutable(path)
+            && published
+                .get(path)
+                .is_some_and(|stored| stored.size == *size);
+        if unchanged {
+            stats.unchanged += 1;
+            continue;
+        }
+        storage.upload(&index_path.join(path), path)?;
+        stats.copied += 1;
+        stats.bytes += size;
+    }
+
+    for path in published.keys() {
+        if !files.iter().any(|(local, _)| local == path) {
+            storage.delete(path)?;
+            stats.removed += 1;
+        }
+    }
+    Ok(stats)
+}
+
+/// Sync state of an index copied from storage
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncState {
+    url: String,
+    synced_at: String,
+    /// Version of each file when it was copied
+    files: BTreeMap<String, String>,
+}
+
+impl SyncState {
+    fn load(index_path: &Path) -> Option<Self> {
+        let json = std::fs::read_to_string(index_path.join(SYNC_FILE)).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    fn save(&self, index_path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| YgrepError::Storage(format!("Failed to save sync state: {}", e)))?;
+        std::fs::write(index_path.join(SYNC_FILE), json)?;
+        Ok(())
+    }
+
+    fn age(&self) -> Option<Duration> {
+        let synced_at = chrono::DateTime::parse_from_rfc3339(&self.synced_at).ok()?;
+        chrono::Utc::now()
+            .signed_duration_since(synced_at)
+            .to_std()
+            .ok()
+    }
+}
+
+/// Whether an index was synced from storage (and so is read-only here)
+pub fn is_synced(index_path: &Path) -> bool {
+    index_path.join(SYNC_FILE).exists()
+}
+
+/// Make a synced index a local one, e.g. before indexing into it
+pub fn detach(index_path: &Path) -> Result<()> {
+    match std::fs::remove_file(index_path.join(SYNC_FILE)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Copy a published index into `index_path`: files that changed since the last sync are
+/// downloaded (meta.json and workspace.json last), files no longer published are removed
+pub fn sync(storage: &dyn IndexStorage, index_path: &Path) -> Result<TransferStats> {
+    let mut published = storage.list()?;
+    if !published.iter().any(|file| file.path == "workspace.json") {
+        return Err(YgrepError::Storage(format!(
+            "No index published at {}",
+            storage.url()
+        )));
+    }
+    published.retain(|file| !is_local_only(&file.path));
+    published.sort_by_key(|file| commit_order(&file.path));
+
+    let previous = SyncState::load(index_path)
+        .filter(|state| state.url == storage.url())
+        .unwrap_or_default();
+    let mut state = SyncState {
+        url: storage.url().to_string(),
+        synced_at: chrono::Utc::now().to_rfc3339(),
+        files: BTreeMap::new(),
+    };
+    std::fs::create_dir_all(index_path)?;
+
+    let mut stats = TransferStats::default();
+    for file in &published {
+        let dest = index_path.join(&file.path);
+        if previous.files.get(&file.path) == Some(&file.version) && dest.exists() {
+            stats.unchanged += 1;
+        } else {
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            // Searches running meanwhile keep seeing the old file until the rename
+            let tmp = dest.with_file_name(format!(
+                "{}.{}.tmp",
+                dest.file_name().unwrap_or_default().to_string_lossy(),
+                std::process::id()
+            ));
+            storage.download(&file.path, &tmp)?;
+            std::fs::rename(&tmp, &dest)?;
+            stats.copied += 1;
+            stats.bytes += file.size;
+        }
+        state.files.insert(file.path.clone(), file.version.clone());
+    }
+
+    for path in previous.files.keys() {
+        if !state.files.contains_key(path) && std::fs::remove_file(index_path.join(path)).is_ok() {
+            stats.removed += 1;
+        }
+    }
+    state.save(index_path)?;
+    Ok(stats)
+}
+
+/// Sync the index published at `url` unless the last sync from there was less than
+/// `max_age` ago. An index built locally is left alone, and when storage can't be reached
+/// a synced copy is used as it is.
+///
+/// Returns what was copied, or `None` if no sync was needed.
+pub fn sync_if_stale(
+    url: &str,
+    index_path: &Path,
+    max_age: Duration,
+) -> Result<Option<TransferStats>> {
+    let indexed = index_path.join("workspace.json").exists();
+    let state = SyncState::load(index_path);
+    if state.is_none() && indexed {
+        return Ok(None);
+    }
+
+    let synced = open_storage(url).and_then(|storage| {
+        let recent = state.as_ref().is_some_and(|state| {
+            state.url == storage.url() && state.age().is_some_and(|age| age < max_age)
+        });
+        if recent {
+            return Ok(None);
+        }
+        sync(storage.as_ref(), index_path).map(Some)
+    });
+    match synced {
+        Err(e) if indexed => {
+            tracing::warn!("Using the cached index, failed to sync {}: {}", url, e);
+            Ok(None)
+        }
+        synced => synced,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, path: &str, content: &str) {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_publish_and_sync() {
+        let index = tempfile::tempdir().unwrap();
+        let bucket = tempfile::tempdir().unwrap();
+        let cache = tempfile::tempdir().unwrap();
+        let segment = "0123456789abcdef0123456789abcdef.store";
+        write(index.path(), "workspace.json", "{}");
+        write(index.path(), "meta.json", "{}");
+        write(index.path(), segment, "segment");
+        write(index.path(), "vectors/shards.json", "[]");
+        write(index.path(), ".tantivy-writer.lock", "");
+        write(index.path(), "results/last.json", "{}");
+
+        let storage = LocalStorage::new(bucket.path());
+        let stats = publish(index.path(), &storage).unwrap();
+        assert_eq!(stats.copied, 4);
+        let mut published: Vec<_> = storage
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        published.sort();
+        assert_eq!(
+            published,
+            [
+                segment,
+                "meta.json",
+                "vectors/shards.json",
+                "workspace.json"
+            ]
+        );
+
+        // Published segments aren't uploaded again; files gone locally are deleted
+        std::fs::remove_file(index.path().join("vectors/shards.json")).unwrap();
+        let stats = publish(index.path(), &storage).unwrap();
+        assert_eq!((stats.copied, stats.unchanged, stats.removed), (2, 1, 1));
+
+        let stats = sync(&storage, cache.path()).unwrap();
+        assert_eq!(stats.copied, 3);
+        assert!(is_synced(cache.path()));
+        assert_eq!(
+            std::fs::read_to_string(cache.path().join(segment)).unwrap(),
+            "segment"
+        );
+        assert!(!cache.path().join(".tantivy-writer.lock").exists());
+
+        // Only what changed is downloaded again
+        std::thread::sleep(Duration::from_millis(10));
+        write(index.path(), "meta.json", r#"{"segments":[]}"#);
+        std::fs::remove_file(index.path().join(segment)).unwrap();
+        publish(index.path(), &storage).unwrap();
+        let stats = sync(&storage, cache.path()).unwrap();
+        assert_eq!((stats.copied, stats.removed), (2, 1));
+        assert!(!cache.path().join(segment).exists());
+
+        // A recent sync isn't repeated, and an index built locally is left alone
+        let url = &format!("file://{}", bucket.path().display());
+        let hour = Duration::from_secs(3600);
+        assert!(sync_if_stale(url, cache.path(), hour).unwrap().is_none());
+        assert!(sync_if_stale(url, cache.path(), Duration::ZERO)
+            .unwrap()
+            .is_some());
+        detach(cache.path()).unwrap();
+        assert!(sync_if_stale(url, cache.path(), Duration::ZERO)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_sync_missing_index() {
+        let bucket = tempfile::tempdir().unwrap();
+        let cache = tempfile::tempdir().unwrap();
+        let url = bucket.path().to_str().unwrap();
+        let result = sync_if_stale(url, &cache.path().join("index"), Duration::ZERO);
+        assert!(matches!(result, Err(YgrepError::Storage(_))));
+    }
+}
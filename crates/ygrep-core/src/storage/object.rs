@@ -0,0 +1,116 @@
+//! Indexes published to S3 or GCS. Credentials and region come from the usual
+//! environment variables (`AWS_ACCESS_KEY_ID`, `AWS_REGION`, `GOOGLE_APPLICATION_CREDENTIALS`, ...)
+
+use futures::StreamExt;
+use object_store::buffered::BufWriter;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+
+use super::{IndexStorage, StoredFile};
+use crate::error::{Result, YgrepError};
+
+/// A published index under a prefix of an S3 or GCS bucket
+pub struct ObjectStorage {
+    store: Arc<dyn ObjectStore>,
+    prefix: ObjectPath,
+    url: String,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl ObjectStorage {
+    /// Open `s3://bucket/prefix` or `gs://bucket/prefix`
+    pub fn open(url: &str) -> Result<Self> {
+        let parsed = url::Url::parse(url)
+            .map_err(|e| YgrepError::Storage(format!("Invalid storage URL {}: {}", url, e)))?;
+        let options = std::env::vars().map(|(key, value)| (key.to_ascii_lowercase(), value));
+        let (store, prefix) = object_store::parse_url_opts(&parsed, options)
+            .map_err(|e| YgrepError::Storage(format!("{}: {}", url, e)))?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self {
+            store: Arc::from(store),
+            prefix,
+            url: url.to_string(),
+            runtime,
+        })
+    }
+
+    fn object(&self, path: &str) -> ObjectPath {
+        path.split('/')
+            .fold(self.prefix.clone(), |object, part| object.child(part))
+    }
+
+    fn error(&self, e: impl std::fmt::Display) -> YgrepError {
+        YgrepError::Storage(format!("{}: {}", self.url, e))
+    }
+}
+
+impl IndexStorage for ObjectStorage {
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn list(&self) -> Result<Vec<StoredFile>> {
+        self.runtime.block_on(async {
+            let mut listing = self.store.list(Some(&self.prefix));
+            let mut files = Vec::new();
+            while let Some(meta) = listing.next().await {
+                let meta = meta.map_err(|e| self.error(e))?;
+                let Some(parts) = meta.location.prefix_match(&self.prefix) else {
+                    continue;
+                };
+                let path = parts
+                    .map(|part| part.as_ref().to_string())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                files.push(StoredFile {
+                    path,
+                    size: meta.size,
+                    version: meta
+                        .e_tag
+                        .unwrap_or_else(|| meta.last_modified.to_rfc3339()),
+                });
+            }
+            Ok(files)
+        })
+    }
+
+    fn download(&self, path: &str, dest: &Path) -> Result<()> {
+        self.runtime.block_on(async {
+            let object = self
+                .store
+                .get(&self.object(path))
+                .await
+                .map_err(|e| self.error(e))?;
+            let mut file = tokio::fs::File::create(dest).await?;
+            let mut chunks = object.into_stream();
+            while let Some(chunk) = chunks.next().await {
+                file.write_all(&chunk.map_err(|e| self.error(e))?).await?;
+            }
+            file.flush().await?;
+            Ok(())
+        })
+    }
+
+    fn upload(&self, src: &Path, path: &str) -> Result<()> {
+        self.runtime.block_on(async {
+            // Large files (vectors, big segments) go up as multipart uploads
+            let mut writer = BufWriter::new(self.store.clone(), self.object(path));
+            let mut file = tokio::fs::File::open(src).await?;
+            tokio::io::copy(&mut file, &mut writer).await?;
+            writer.shutdown().await?;
+            Ok(())
+        })
+    }
+
+    fn delete(&self, path: &str) -> Result<()> {
+        match self.runtime.block_on(self.store.delete(&self.object(path))) {
+            Err(object_store::Error::NotFound { .. }) | Ok(()) => Ok(()),
+            Err(e) => Err(self.error(e)),
+        }
+    }
+}
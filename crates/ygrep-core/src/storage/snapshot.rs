@@ -0,0 +1,234 @@
+//! Index snapshots (`ygrep indexes snapshot`): named copies of a workspace's index,
+//! kept in `snapshots/<workspace hash>/<name>` in the data directory and searched
+//! read-only with `--at`, to compare against the code as it was indexed before
+
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, Utc};
+use std::path::{Path, PathBuf};
+
+use super::{publish, LocalStorage};
+use crate::error::{Result, YgrepError};
+
+/// A snapshot of an index
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub name: String,
+    /// The snapshot's index directory
+    pub path: PathBuf,
+    /// When the snapshotted index was last updated (from its workspace.json)
+    pub indexed_at: Option<DateTime<FixedOffset>>,
+    /// Commit the index was built at (`None` outside git)
+    pub commit: Option<String>,
+}
+
+impl Snapshot {
+    fn read(path: PathBuf) -> Option<Self> {
+        let metadata: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path.join("workspace.json")).ok()?)
+                .ok()?;
+        let indexed_at = ["refreshed_at", "indexed_at"]
+            .iter()
+            .filter_map(|key| metadata.get(*key).and_then(|v| v.as_str()))
+            .filter_map(|s| DateTime::parse_from_rfc3339(s).ok())
+            .max();
+        let commit = metadata
+            .get("fingerprint")
+            .and_then(|f| f.get("head"))
+            .and_then(|head| head.as_str())
+            .map(String::from);
+        Some(Self {
+            name: path.file_name()?.to_string_lossy().into_owned(),
+            path,
+            indexed_at,
+            commit,
+        })
+    }
+}
+
+/// Copy the index at `index_path` into `dir` as snapshot `name` (default: the time the
+/// index was last updated, e.g. `2026-10-09-143000`)
+pub fn take(index_path: &Path, dir: &Path, name: Option<&str>) -> Result<Snapshot> {
+    let current = Snapshot::read(index_path.to_path_buf())
+        .ok_or_else(|| YgrepError::WorkspaceNotIndexed(index_path.to_path_buf()))?;
+    let name = match name {
+        Some(name) => {
+            check_name(name)?;
+            name.to_string()
+        }
+        None => current
+            .indexed_at
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or_else(Utc::now)
+            .format("%Y-%m-%d-%H%M%S")
+            .to_string(),
+    };
+    let dest = dir.join(&name);
+    if dest.exists() {
+        return Err(YgrepError::Config(format!(
+            "A snapshot named '{}' already exists",
+            name
+        )));
+    }
+
+    // Copied aside first, so an interrupted copy is never listed
+    let tmp = dir.join(format!(".{}.tmp", name));
+    if tmp.exists() {
+        std::fs::remove_dir_all(&tmp)?;
+    }
+    publish(index_path, &LocalStorage::new(&tmp))?;
+    std::fs::rename(&tmp, &dest)?;
+    Snapshot::read(dest.clone()).ok_or(YgrepError::WorkspaceNotIndexed(dest))
+}
+
+/// Snapshots in `dir`, oldest first
+pub fn list(dir: &Path) -> Vec<Snapshot> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut snapshots: Vec<Snapshot> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|entry| Snapshot::read(entry.path()))
+        .collect();
+    snapshots.sort_by(|a, b| {
+        a.indexed_at
+            .cmp(&b.indexed_at)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    snapshots
+}
+
+/// The snapshot `at` names, or the newest one indexed at or before the time it gives:
+/// RFC 3339 (`2026-10-09T14:30:00Z`), a date (`2026-10-09`, up to the end of that day,
+/// local time) or an age (`30m`, `12h`, `7d`, `2w`)
+pub fn resolve(dir: &Path, at: &str) -> Result<Snapshot> {
+    let snapshots = list(dir);
+    if let Some(snapshot) = snapshots.iter().find(|snapshot| snapshot.name == at) {
+        return Ok(snapshot.clone());
+    }
+    let Some(time) = parse_time(at, Utc::now()) else {
+        return Err(YgrepError::Config(format!(
+            "No snapshot named '{}' (snapshots: {}), and it isn't a time like \
+             2026-10-09T14:30:00Z, 2026-10-09 or 7d",
+            at,
+            names(&snapshots)
+        )));
+    };
+    snapshots
+        .iter()
+        .rev()
+        .find(|snapshot| snapshot.indexed_at.is_some_and(|indexed| indexed <= time))
+        .cloned()
+        .ok_or_else(|| {
+            YgrepError::Config(format!(
+                "No snapshot indexed at or before {} (snapshots: {})",
+                time.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                names(&snapshots)
+            ))
+        })
+}
+
+fn names(snapshots: &[Snapshot]) -> String {
+    if snapshots.is_empty() {
+        return "none; take one with `ygrep indexes snapshot`".to_string();
+    }
+    snapshots
+        .iter()
+        .map(|snapshot| snapshot.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse_time(at: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(at) {
+        return Some(time.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(at, "%Y-%m-%d") {
+        let end = date.and_hms_opt(23, 59, 59)?.and_local_timezone(Local);
+        return end.latest().map(|time| time.with_timezone(&Utc));
+    }
+    let split = at.find(|c: char| !c.is_ascii_digit())?;
+    let (count, unit) = at.split_at(split);
+    let count: i64 = count.parse().ok()?;
+    let age = match unit {
+        "m" => Duration::try_minutes(count)?,
+        "h" => Duration::try_hours(count)?,
+        "d" => Duration::try_days(count)?,
+        "w" => Duration::try_weeks(count)?,
+        _ => return None,
+    };
+    now.checked_sub_signed(age)
+}
+
+/// Snapshot names become directory names
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(YgrepError::Config(format!(
+            "Invalid snapshot name '{}' (letters, digits, `-`, `_` and `.` only)",
+            name
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_index(dir: &Path, indexed_at: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(
+            dir.join("workspace.json"),
+            serde_json::json!({ "indexed_at": indexed_at }).to_string(),
+        )
+        .unwrap();
+        std::fs::write(dir.join("meta.json"), "{}").unwrap();
+        std::fs::write(dir.join(".tantivy-writer.lock"), "").unwrap();
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("index");
+        let snapshots = dir.path().join("snapshots");
+
+        fake_index(&index, "2026-10-01T12:00:00+00:00");
+        let first = take(&index, &snapshots, None).unwrap();
+        assert_eq!(first.name, "2026-10-01-120000");
+        assert!(first.path.join("meta.json").exists());
+        assert!(!first.path.join(".tantivy-writer.lock").exists());
+        assert!(take(&index, &snapshots, None).is_err());
+
+        fake_index(&index, "2026-10-08T12:00:00+00:00");
+        take(&index, &snapshots, Some("release")).unwrap();
+        assert!(take(&index, &snapshots, Some("../up")).is_err());
+
+        let names: Vec<String> = list(&snapshots).into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["2026-10-01-120000", "release"]);
+        assert_eq!(resolve(&snapshots, "release").unwrap().name, "release");
+        let at = |at: &str| resolve(&snapshots, at).map(|s| s.name);
+        assert_eq!(at("2026-10-05T00:00:00Z").unwrap(), "2026-10-01-120000");
+        assert_eq!(at("2026-10-09T00:00:00Z").unwrap(), "release");
+        assert!(at("2026-09-30T00:00:00Z").is_err());
+        assert!(at("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let now = DateTime::parse_from_rfc3339("2026-10-16T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let ago = |at: &str| parse_time(at, now).map(|time| (now - time).num_hours());
+        assert_eq!(ago("12h"), Some(12));
+        assert_eq!(ago("7d"), Some(168));
+        assert_eq!(ago("2w"), Some(336));
+        assert_eq!(ago("2026-10-16T10:00:00+00:00"), Some(2));
+        assert!(parse_time("2026-10-09", now).is_some());
+        assert_eq!(parse_time("7y", now), None);
+        assert_eq!(parse_time("d", now), None);
+    }
+}
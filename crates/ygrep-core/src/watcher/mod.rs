@@ -1,5 +1,9 @@
 //! File system watcher for incremental index updates
 
+mod queue;
+
+pub use queue::{EmbeddingJob, EmbeddingQueue};
+
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
@@ -10,6 +14,7 @@ use tokio::sync::mpsc;
 
 use crate::config::IndexerConfig;
 use crate::error::{Result, YgrepError};
+use crate::fs::is_ignore_file;
 
 /// Events emitted by the file watcher
 #[derive(Debug, Clone)]
@@ -22,6 +27,9 @@ pub enum WatchEvent {
     DirCreated(PathBuf),
     /// Directory was deleted
     DirDeleted(PathBuf),
+    /// An ignore file (`.gitignore`, `.ygrepignore`) was created, changed or deleted, so
+    /// files may have become ignored or no longer be
+    IgnoreChanged(PathBuf),
     /// Error occurred while watching
     Error(String),
 }
@@ -90,6 +98,7 @@ impl FileWatcher {
                         // Deduplicate events by path to avoid processing same file twice
                         let mut seen_changed: HashSet<PathBuf> = HashSet::new();
                         let mut seen_deleted: HashSet<PathBuf> = HashSet::new();
+                        let mut seen_ignore: HashSet<PathBuf> = HashSet::new();
 
                         for event in events {
                             let watch_events = process_notify_event(
@@ -109,6 +118,11 @@ impl FileWatcher {
                                             let _ = tx.send(e);
                                         }
                                     }
+                                    WatchEvent::IgnoreChanged(p) => {
+                                        if seen_ignore.insert(p.clone()) {
+                                            let _ = tx.send(e);
+                                        }
+                                    }
                                     _ => {
                                         let _ = tx.send(e);
                                     }
@@ -193,23 +207,37 @@ fn process_notify_event(
 
     for path in &event.paths {
         // Skip if path is not under any watched path
-        let is_under_watched = watched_paths.iter().any(|wp| path.starts_with(wp));
-        if !is_under_watched {
+        let Some(relative) = watched_paths
+            .iter()
+            .find_map(|wp| path.strip_prefix(wp).ok())
+        else {
             continue;
+        };
+
+        // Ignore files are hidden themselves, but decide what else is indexed
+        if is_ignore_file(path, config)
+            && !is_ignored_dir(relative)
+            && matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            )
+        {
+            events.push(WatchEvent::IgnoreChanged(path.clone()));
         }
 
-        // Skip hidden files/directories
-        if is_hidden(path) {
+        // Skip hidden files/directories (inside the workspace: it may itself live under
+        // a hidden or ignored directory such as /tmp)
+        if !config.include_hidden && is_hidden(relative) {
             continue;
         }
 
         // Skip ignored directories
-        if is_ignored_dir(path) {
+        if is_ignored_dir(relative) {
             continue;
         }
 
         // Skip files matching ignore patterns
-        if matches_ignore_pattern(path, config) {
+        if matches_ignore_pattern(relative, config) {
             continue;
         }
 
@@ -221,10 +249,8 @@ fn process_notify_event(
                     events.push(WatchEvent::Changed(path.clone()));
                 }
             }
-            EventKind::Modify(_) => {
-                if path.is_file() {
-                    events.push(WatchEvent::Changed(path.clone()));
-                }
+            EventKind::Modify(_) if path.is_file() => {
+                events.push(WatchEvent::Changed(path.clone()));
             }
             EventKind::Remove(_) => {
                 // Can't check if it was a file or dir since it's deleted
@@ -296,6 +322,8 @@ fn is_ignored_dir(path: &Path) -> bool {
         "build",
         "cache",
         ".git",
+        ".svn",
+        ".hg",
         "__pycache__",
         "logs",
         "tmp",
@@ -325,34 +353,32 @@ fn matches_ignore_pattern(path: &Path, config: &IndexerConfig) -> bool {
 /// Simple glob matching (copied from walker.rs for consistency)
 fn glob_match(pattern: &str, path: &str) -> bool {
     // Handle **/dir/** patterns (match dir anywhere in path)
-    if pattern.starts_with("**/") && pattern.ends_with("/**") {
-        let dir_name = &pattern[3..pattern.len() - 3];
+    if let Some(dir_name) = pattern
+        .strip_prefix("**/")
+        .and_then(|p| p.strip_suffix("/**"))
+    {
         return path.contains(&format!("/{}/", dir_name))
             || path.starts_with(&format!("{}/", dir_name))
             || path.ends_with(&format!("/{}", dir_name));
     }
 
     // Handle **/*.ext patterns (match extension anywhere)
-    if pattern.starts_with("**/*.") {
-        let ext = &pattern[5..];
+    if let Some(ext) = pattern.strip_prefix("**/*.") {
         return path.ends_with(&format!(".{}", ext));
     }
 
     // Handle **/something patterns (match at end)
-    if pattern.starts_with("**/") {
-        let suffix = &pattern[3..];
+    if let Some(suffix) = pattern.strip_prefix("**/") {
         return path.ends_with(suffix) || path.ends_with(&format!("/{}", suffix));
     }
 
     // Handle something/** patterns (match at start)
-    if pattern.ends_with("/**") {
-        let prefix = &pattern[..pattern.len() - 3];
+    if let Some(prefix) = pattern.strip_suffix("/**") {
         return path.starts_with(prefix) || path.contains(&format!("/{}", prefix));
     }
 
     // Handle simple * patterns (*.ext)
-    if pattern.starts_with("*.") {
-        let ext = &pattern[2..];
+    if let Some(ext) = pattern.strip_prefix("*.") {
         return path.ends_with(&format!(".{}", ext));
     }
 
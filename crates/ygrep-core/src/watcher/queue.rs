@@ -0,0 +1,142 @@
+//! Background embedding for `ygrep watch` in semantic mode: a file's text is indexed as
+//! soon as it changes, while the documents to embed for it wait in a bounded queue for a
+//! worker thread, so saves don't wait for the embedding model.
+//!
+//! Jobs are kept by path: a file saved again before its embeddings were computed only
+//! gets the documents of its latest version embedded.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use parking_lot::Mutex;
+
+/// Documents to embed: (doc_id, content)
+pub type EmbeddingJob = Vec<(String, String)>;
+
+/// A bounded queue of embedding jobs with the worker thread computing them
+pub struct EmbeddingQueue {
+    sender: Option<SyncSender<PathBuf>>,
+    /// Jobs not picked up by the worker yet, by path
+    pending: Arc<Mutex<HashMap<PathBuf, EmbeddingJob>>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl EmbeddingQueue {
+    /// Start a worker running `embed` on each job; at most `capacity` files wait at once
+    pub fn new<F>(capacity: usize, mut embed: F) -> Self
+    where
+        F: FnMut(&Path, EmbeddingJob) + Send + 'static,
+    {
+        let (sender, receiver) = sync_channel::<PathBuf>(capacity.max(1));
+        let pending: Arc<Mutex<HashMap<PathBuf, EmbeddingJob>>> = Arc::default();
+        let jobs = Arc::clone(&pending);
+        let worker = std::thread::spawn(move || {
+            for path in receiver {
+                // (gone when the file was deleted meanwhile)
+                let Some(job) = jobs.lock().remove(&path) else {
+                    continue;
+                };
+                embed(&path, job);
+            }
+        });
+        Self {
+            sender: Some(sender),
+            pending,
+            worker: Some(worker),
+        }
+    }
+
+    /// Queue the documents of `path`, replacing those still waiting for it. Blocks while
+    /// the queue is full.
+    pub fn push(&self, path: &Path, job: EmbeddingJob) {
+        if job.is_empty() {
+            return;
+        }
+        if self
+            .pending
+            .lock()
+            .insert(path.to_path_buf(), job)
+            .is_some()
+        {
+            return;
+        }
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(path.to_path_buf());
+        }
+    }
+
+    /// Drop the job waiting for `path` (the file was deleted)
+    pub fn forget(&self, path: &Path) {
+        self.pending.lock().remove(path);
+    }
+
+    /// Files waiting for their embeddings
+    pub fn pending(&self) -> usize {
+        self.pending.lock().len()
+    }
+
+    /// Wait for the worker to embed everything queued
+    pub fn finish(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.sender.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for EmbeddingQueue {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_embedding_queue() {
+        let (done_tx, done_rx) = channel();
+        let (gate_tx, gate_rx) = channel::<()>();
+        let embedded = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&embedded);
+        let queue = EmbeddingQueue::new(8, move |path, job| {
+            // Hold the worker on the first job so the others wait in the queue
+            if path == Path::new("first.rs") {
+                done_tx.send(()).unwrap();
+                gate_rx.recv().unwrap();
+            }
+            seen.lock().push((path.to_path_buf(), job));
+        });
+        let job = |doc_id: &str| vec![(doc_id.to_string(), "fn main() {}".to_string())];
+
+        queue.push(Path::new("first.rs"), job("a"));
+        done_rx.recv().unwrap();
+        queue.push(Path::new("lib.rs"), job("b"));
+        queue.push(Path::new("lib.rs"), job("c"));
+        queue.push(Path::new("gone.rs"), job("d"));
+        queue.push(Path::new("empty.rs"), Vec::new());
+        queue.forget(Path::new("gone.rs"));
+        assert_eq!(queue.pending(), 1);
+        gate_tx.send(()).unwrap();
+        queue.finish();
+
+        // Only the latest version of lib.rs, nothing for the deleted or empty files
+        let embedded = embedded.lock();
+        assert_eq!(
+            *embedded,
+            vec![
+                (PathBuf::from("first.rs"), job("a")),
+                (PathBuf::from("lib.rs"), job("c")),
+            ]
+        );
+    }
+}